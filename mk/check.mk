@@ -31,6 +31,10 @@ contenttest: $(S)src/test/harness/contenttest/contenttest.rs servo
 	@$(call E, compile: $@)
 	$(Q)$(RUSTC) $(RFLAGS_servo) -o $@ $< -L .
 
+layoutbench: $(S)src/test/harness/layoutbench/layoutbench.rs servo
+	@$(call E, compile: $@)
+	$(Q)$(RUSTC) -o $@ $<
+
 
 DEPS_CHECK_TESTABLE = $(filter-out $(NO_TESTS),$(DEPS_CHECK_ALL))
 DEPS_CHECK_TARGETS_ALL = $(addprefix check-,$(DEPS_CHECK_TESTABLE))
@@ -64,6 +68,11 @@ check-content: contenttest
 	@$(call E, check: contenttests)
 	$(Q)./contenttest --source-dir=$(S)src/test/html/content $(TESTNAME)
 
+.PHONY: bench-layout
+bench-layout: layoutbench
+	@$(call E, bench: layout)
+	$(Q)./layoutbench $(S)src/test/bench/*.list
+
 .PHONY: tidy
 tidy:
 	@$(call E, check: tidy)
@@ -15,11 +15,14 @@
 /// low-level drawing primitives.
 
 use color::Color;
+use effects::GroupEffects;
 use geometry::Au;
+use path::{FillRule, Subpath};
 use render_context::RenderContext;
 use text::SendableTextRun;
 
 use std::cast::transmute_region;
+use azure::AzFloat;
 use geom::{Point2D, Rect, Size2D, SideOffsets2D};
 use servo_net::image::base::Image;
 use servo_util::range::Range;
@@ -63,6 +66,9 @@ pub enum DisplayItem<E> {
     TextDisplayItemClass(~TextDisplayItem<E>),
     ImageDisplayItemClass(~ImageDisplayItem<E>),
     BorderDisplayItemClass(~BorderDisplayItem<E>),
+    WavyLineDisplayItemClass(~WavyLineDisplayItem<E>),
+    PathDisplayItemClass(~PathDisplayItem<E>),
+    GroupDisplayItemClass(~GroupDisplayItem<E>),
 }
 
 /// Information common to all display items.
@@ -88,6 +94,36 @@ pub struct TextDisplayItem<E> {
     text_run: ~SendableTextRun,
     range: Range,
     color: Color,
+    decorations: TextDecorations,
+    /// The blur radius of a text shadow, or zero for sharp text.
+    blur_radius: Au,
+}
+
+/// The lines drawn along a run of text by `text-decoration`. Each line is drawn in the color of
+/// the element that declared it, which may be an ancestor of the element the text belongs to.
+pub struct TextDecorations {
+    underline: Option<Color>,
+    overline: Option<Color>,
+    line_through: Option<Color>,
+}
+
+impl TextDecorations {
+    pub fn none() -> TextDecorations {
+        TextDecorations {
+            underline: None,
+            overline: None,
+            line_through: None,
+        }
+    }
+
+    /// Returns the same lines, all drawn in the given color, as in a text shadow.
+    pub fn with_color(&self, color: Color) -> TextDecorations {
+        TextDecorations {
+            underline: self.underline.map(|_| color),
+            overline: self.overline.map(|_| color),
+            line_through: self.line_through.map(|_| color),
+        }
+    }
 }
 
 /// Renders an image.
@@ -107,6 +143,50 @@ pub struct BorderDisplayItem<E> {
     color: SideOffsets2D<Color>,
 }
 
+/// Renders a wavy line that fills its bounds, such as the one marking a misspelled word.
+pub struct WavyLineDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+    color: Color,
+}
+
+/// Renders a vector path, such as the shape of an SVG `<path>`.
+pub struct PathDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+    /// The flattened path, in pixels from the origin of the bounds.
+    subpaths: ~[Subpath],
+    /// The color to fill the path with, and the `fill-rule` that decides what is inside it.
+    fill: Option<(Color, FillRule)>,
+    stroke: Option<PathStroke>,
+}
+
+/// The display list of an element with `position: fixed`, which the compositor keeps in a layer
+/// of its own above the page, so that the element stays where it is in the viewport while the
+/// page scrolls.
+pub struct FixedLayer<E> {
+    /// The rect of the layer in the page, which the items of its list are in the coordinates of.
+    bounds: Rect<Au>,
+    /// Where the top left of the layer is in the viewport.
+    viewport_position: Point2D<Au>,
+    list: DisplayList<E>,
+}
+
+/// Renders a list of display items as a group, which is composited into the page with effects
+/// that apply to it as a whole, such as a mask. Nothing in the group is drawn outside its bounds.
+pub struct GroupDisplayItem<E> {
+    base: BaseDisplayItem<E>,
+    list: DisplayList<E>,
+    effects: GroupEffects,
+}
+
+/// How the outline of a path is drawn.
+pub struct PathStroke {
+    color: Color,
+    width: AzFloat,
+    /// The lengths of the dashes and gaps of `stroke-dasharray`, or empty for a solid line.
+    dash_array: ~[AzFloat],
+    dash_offset: AzFloat,
+}
+
 impl<E> DisplayItem<E> {
     /// Renders this display item into the given render context.
     fn draw_into_context(&self, render_context: &RenderContext) {
@@ -125,20 +205,55 @@ impl<E> DisplayItem<E> {
                 let origin = text.base.bounds.origin;
                 let baseline_origin = Point2D(origin.x, origin.y + font.metrics.ascent);
 
-                font.draw_text_into_context(render_context,
-                                            new_run,
-                                            &text.range,
-                                            baseline_origin,
-                                            text.color);
-
-                if new_run.underline {
-                    // TODO(eatkinson): Use the font metrics to properly position the underline
-                    // bar.
-                    let width = text.base.bounds.size.width;
-                    let underline_size = font.metrics.underline_size;
-                    let underline_bounds = Rect(Point2D(baseline_origin.x, baseline_origin.y),
-                                                Size2D(width, underline_size));
-                    render_context.draw_solid_color(&underline_bounds, text.color);
+                // Underlines and overlines are drawn beneath the text, and line-throughs over
+                // it. The lines are as thick as the font's underline.
+                let thickness = font.metrics.underline_size;
+                let width = text.base.bounds.size.width;
+                let draw_line = |y: Au, color: Color| {
+                    let bounds = Rect(Point2D(origin.x, y), Size2D(width, thickness));
+                    render_context.draw_solid_color(&bounds, color)
+                };
+
+                // The underline offset is the distance from the baseline up to the top of the
+                // underline, so it is negative for underlines below the baseline.
+                for &color in text.decorations.underline.iter() {
+                    draw_line(baseline_origin.y - font.metrics.underline_offset, color)
+                }
+                for &color in text.decorations.overline.iter() {
+                    draw_line(origin.y, color)
+                }
+
+                if text.blur_radius == Au(0) {
+                    font.draw_text_into_context(render_context,
+                                                new_run,
+                                                &text.range,
+                                                baseline_origin,
+                                                text.color);
+                } else {
+                    // FIXME: Azure can't blur, so the blur is approximated by drawing the text
+                    // at a grid of points within the blur radius, each time with a fraction of
+                    // the opacity. Nine samples of alpha `b` drawn over each other come to
+                    // `1 - (1 - b)^9`, so `b` is picked for where they all overlap to come to the
+                    // alpha of the shadow.
+                    let step = text.blur_radius / Au(2);
+                    let sample_alpha = 1.0 - (1.0 - text.color.a).pow(&(1.0 / 9.0 as AzFloat));
+                    let color = Color { a: sample_alpha, ..text.color };
+                    for &dx in [-step, Au(0), step].iter() {
+                        for &dy in [-step, Au(0), step].iter() {
+                            let sample_origin = Point2D(baseline_origin.x + dx,
+                                                        baseline_origin.y + dy);
+                            font.draw_text_into_context(render_context,
+                                                        new_run,
+                                                        &text.range,
+                                                        sample_origin,
+                                                        color);
+                        }
+                    }
+                }
+
+                // Line-throughs are centered on the middle of lowercase letters.
+                for &color in text.decorations.line_through.iter() {
+                    draw_line(baseline_origin.y - (font.metrics.x_height + thickness) / Au(2), color)
                 }
             }
 
@@ -153,6 +268,27 @@ impl<E> DisplayItem<E> {
                                            border.border,
                                            border.color)
             }
+
+            WavyLineDisplayItemClass(ref line) => {
+                render_context.draw_wavy_line(&line.base.bounds, line.color)
+            }
+
+            PathDisplayItemClass(ref path) => {
+                let origin = Point2D(path.base.bounds.origin.x.to_nearest_px() as AzFloat,
+                                     path.base.bounds.origin.y.to_nearest_px() as AzFloat);
+                for &(color, rule) in path.fill.iter() {
+                    render_context.fill_path(origin, path.subpaths, rule, color)
+                }
+                for stroke in path.stroke.iter() {
+                    render_context.stroke_path(origin, path.subpaths, stroke)
+                }
+            }
+
+            GroupDisplayItemClass(ref group) => {
+                do render_context.draw_group(&group.base.bounds, &group.effects) |context| {
+                    group.list.draw_into_context(context)
+                }
+            }
         }
     }
 
@@ -163,7 +299,10 @@ impl<E> DisplayItem<E> {
                 SolidColorDisplayItemClass(ref solid_color) => transmute_region(&solid_color.base),
                 TextDisplayItemClass(ref text) => transmute_region(&text.base),
                 ImageDisplayItemClass(ref image_item) => transmute_region(&image_item.base),
-                BorderDisplayItemClass(ref border) => transmute_region(&border.base)
+                BorderDisplayItemClass(ref border) => transmute_region(&border.base),
+                WavyLineDisplayItemClass(ref line) => transmute_region(&line.base),
+                PathDisplayItemClass(ref path) => transmute_region(&path.base),
+                GroupDisplayItemClass(ref group) => transmute_region(&group.base),
             }
         }
     }
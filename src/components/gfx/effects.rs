@@ -0,0 +1,370 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Effects that apply to a group of display items as a whole. The group is rendered into a buffer
+//! of its own, the effects are applied to the pixels of the buffer, and the buffer is composited
+//! into the page. Filters of the backdrop of a group are applied to what has been drawn beneath
+//! it before the group is drawn.
+
+use geometry::Au;
+
+use geom::rect::Rect;
+use geom::size::Size2D;
+use servo_net::image::base::Image;
+use std::f32;
+use std::vec;
+use extra::arc::Arc;
+
+/// Which channel of a mask image sets the opacity of what it masks.
+#[deriving(Clone, Eq)]
+pub enum MaskMode {
+    AlphaMask,
+    /// The luminance of the image, multiplied by its alpha.
+    LuminanceMask,
+}
+
+/// An image that sets the opacity of the group it masks, as `mask-image` does.
+#[deriving(Clone)]
+pub struct Mask {
+    image: Arc<~Image>,
+    mode: MaskMode,
+    /// Where one copy of the image is drawn. The image is repeated to cover the whole group.
+    tile: Rect<Au>,
+}
+
+/// A filter function, as `backdrop-filter` takes. Amounts are fractions, where 1 is 100%, and
+/// angles are in degrees.
+#[deriving(Clone, Eq)]
+pub enum Filter {
+    /// A Gaussian blur with the given standard deviation.
+    Blur(Au),
+    Brightness(f32),
+    Contrast(f32),
+    Grayscale(f32),
+    HueRotate(f32),
+    Invert(f32),
+    Opacity(f32),
+    Saturate(f32),
+    Sepia(f32),
+}
+
+/// The effects applied to a group.
+#[deriving(Clone)]
+pub struct GroupEffects {
+    mask: Option<Mask>,
+    /// The filters applied to what is beneath the group, in order, before the group is drawn over
+    /// it.
+    backdrop_filters: ~[Filter],
+    /// Whether the group is drawn only to clip it to its bounds, as paint containment clips the
+    /// contents of a box. Every group is clipped to its bounds, since its buffer is their size.
+    clip: bool,
+}
+
+impl GroupEffects {
+    pub fn none() -> GroupEffects {
+        GroupEffects {
+            mask: None,
+            backdrop_filters: ~[],
+            clip: false,
+        }
+    }
+
+    /// Returns true if there are no effects, so that the group can be rendered without a buffer
+    /// of its own.
+    pub fn is_empty(&self) -> bool {
+        self.mask.is_none() && self.backdrop_filters.is_empty() && !self.clip
+    }
+}
+
+/// Returns the opacity that a pixel of a mask image gives what it masks. Images are premultiplied
+/// BGRA, so the luminance of the color is already multiplied by the alpha.
+fn mask_value(pixel: &[u8], mode: MaskMode) -> uint {
+    match mode {
+        AlphaMask => pixel[3] as uint,
+        // The weights of the luminance of sRGB, out of 256.
+        LuminanceMask => (54 * (pixel[2] as uint) + 183 * (pixel[1] as uint) +
+                          19 * (pixel[0] as uint)) >> 8,
+    }
+}
+
+/// Masks the premultiplied BGRA pixels of a group by an image repeated across it, with one copy
+/// of the image at `tile`, in pixels from the top left of the group. Each pixel of the group
+/// samples the pixel of the image nearest its center.
+pub fn apply_mask(pixels: &mut [u8],
+                  size: Size2D<uint>,
+                  image: &Image,
+                  tile: &Rect<f32>,
+                  mode: MaskMode) {
+    if image.width == 0 || image.height == 0 || tile.size.width <= 0.0 ||
+            tile.size.height <= 0.0 {
+        for value in pixels.mut_iter() {
+            *value = 0
+        }
+        return
+    }
+
+    // The image pixel each column and row of the group samples.
+    let sample = |position: uint, tile_origin: f32, tile_length: f32, image_length: uint| {
+        let offset = ((position as f32) + 0.5 - tile_origin) / tile_length;
+        let fraction = offset - offset.floor();
+        ((fraction * (image_length as f32)) as uint).min(&(image_length - 1))
+    };
+    let columns = do vec::from_fn(size.width) |x| {
+        sample(x, tile.origin.x, tile.size.width, image.width)
+    };
+
+    for y in range(0, size.height) {
+        let image_row = sample(y, tile.origin.y, tile.size.height, image.height);
+        for x in range(0, size.width) {
+            let image_index = (image_row * image.width + columns[x]) * 4;
+            let value = mask_value(image.data.slice(image_index, image_index + 4), mode);
+            let index = (y * size.width + x) * 4;
+            for channel in range(index, index + 4) {
+                pixels[channel] = (((pixels[channel] as uint) * value + 127) / 255) as u8
+            }
+        }
+    }
+}
+
+/// Applies filters, in order, to premultiplied BGRA pixels. Lengths are scaled by `scale` to
+/// device pixels.
+pub fn apply_filters(pixels: &mut [u8], size: Size2D<uint>, filters: &[Filter], scale: f32) {
+    for filter in filters.iter() {
+        match *filter {
+            Blur(deviation) => {
+                blur(pixels, size, *deviation.to_device_px(scale as float) as f32)
+            }
+            _ => apply_color_matrix(pixels, &color_matrix(filter)),
+        }
+    }
+}
+
+/// Returns the matrix that a filter other than a blur transforms colors by, as the Filter Effects
+/// specification defines it. Each row gives a channel of RGBA from the channels of the original
+/// color, followed by an offset.
+fn color_matrix(filter: &Filter) -> [f32, ..20] {
+    let clamp = |amount: f32| amount.max(&0.0).min(&1.0);
+    match *filter {
+        Brightness(amount) => [amount, 0.0, 0.0, 0.0, 0.0,
+                               0.0, amount, 0.0, 0.0, 0.0,
+                               0.0, 0.0, amount, 0.0, 0.0,
+                               0.0, 0.0, 0.0, 1.0, 0.0],
+        Contrast(amount) => {
+            let offset = 0.5 - 0.5 * amount;
+            [amount, 0.0, 0.0, 0.0, offset,
+             0.0, amount, 0.0, 0.0, offset,
+             0.0, 0.0, amount, 0.0, offset,
+             0.0, 0.0, 0.0, 1.0, 0.0]
+        }
+        Grayscale(amount) => {
+            let a = 1.0 - clamp(amount);
+            [0.2126 + 0.7874 * a, 0.7152 - 0.7152 * a, 0.0722 - 0.0722 * a, 0.0, 0.0,
+             0.2126 - 0.2126 * a, 0.7152 + 0.2848 * a, 0.0722 - 0.0722 * a, 0.0, 0.0,
+             0.2126 - 0.2126 * a, 0.7152 - 0.7152 * a, 0.0722 + 0.9278 * a, 0.0, 0.0,
+             0.0, 0.0, 0.0, 1.0, 0.0]
+        }
+        HueRotate(degrees) => {
+            let radians = degrees * f32::consts::pi / 180.0;
+            let (sin, cos) = (radians.sin(), radians.cos());
+            [0.213 + cos * 0.787 - sin * 0.213,
+             0.715 - cos * 0.715 - sin * 0.715,
+             0.072 - cos * 0.072 + sin * 0.928,
+             0.0, 0.0,
+             0.213 - cos * 0.213 + sin * 0.143,
+             0.715 + cos * 0.285 + sin * 0.140,
+             0.072 - cos * 0.072 - sin * 0.283,
+             0.0, 0.0,
+             0.213 - cos * 0.213 - sin * 0.787,
+             0.715 - cos * 0.715 + sin * 0.715,
+             0.072 + cos * 0.928 + sin * 0.072,
+             0.0, 0.0,
+             0.0, 0.0, 0.0, 1.0, 0.0]
+        }
+        Invert(amount) => {
+            let a = clamp(amount);
+            let scale = 1.0 - 2.0 * a;
+            [scale, 0.0, 0.0, 0.0, a,
+             0.0, scale, 0.0, 0.0, a,
+             0.0, 0.0, scale, 0.0, a,
+             0.0, 0.0, 0.0, 1.0, 0.0]
+        }
+        Opacity(amount) => [1.0, 0.0, 0.0, 0.0, 0.0,
+                            0.0, 1.0, 0.0, 0.0, 0.0,
+                            0.0, 0.0, 1.0, 0.0, 0.0,
+                            0.0, 0.0, 0.0, clamp(amount), 0.0],
+        Saturate(amount) => {
+            let s = amount.max(&0.0);
+            [0.213 + 0.787 * s, 0.715 - 0.715 * s, 0.072 - 0.072 * s, 0.0, 0.0,
+             0.213 - 0.213 * s, 0.715 + 0.285 * s, 0.072 - 0.072 * s, 0.0, 0.0,
+             0.213 - 0.213 * s, 0.715 - 0.715 * s, 0.072 + 0.928 * s, 0.0, 0.0,
+             0.0, 0.0, 0.0, 1.0, 0.0]
+        }
+        Sepia(amount) => {
+            let a = 1.0 - clamp(amount);
+            [0.393 + 0.607 * a, 0.769 - 0.769 * a, 0.189 - 0.189 * a, 0.0, 0.0,
+             0.349 - 0.349 * a, 0.686 + 0.314 * a, 0.168 - 0.168 * a, 0.0, 0.0,
+             0.272 - 0.272 * a, 0.534 - 0.534 * a, 0.131 + 0.869 * a, 0.0, 0.0,
+             0.0, 0.0, 0.0, 1.0, 0.0]
+        }
+        Blur(_) => fail!(~"blurs aren't color matrices"),
+    }
+}
+
+/// Transforms the colors of premultiplied BGRA pixels by a color matrix, which applies to colors
+/// that aren't premultiplied.
+fn apply_color_matrix(pixels: &mut [u8], matrix: &[f32, ..20]) {
+    for start in range(0, pixels.len() / 4) {
+        let pixel = pixels.mut_slice(start * 4, start * 4 + 4);
+        let alpha = (pixel[3] as f32) / 255.0;
+        if alpha == 0.0 && matrix[19] == 0.0 {
+            loop
+        }
+        let unpremultiply = |value: u8| if alpha == 0.0 { 0.0 } else {
+            (value as f32) / 255.0 / alpha
+        };
+        let color = [unpremultiply(pixel[2]), unpremultiply(pixel[1]), unpremultiply(pixel[0]),
+                     alpha];
+        let channel = |row: uint| {
+            let mut value = matrix[row * 5 + 4];
+            for column in range(0, 4) {
+                value += matrix[row * 5 + column] * color[column];
+            }
+            value.max(&0.0).min(&1.0)
+        };
+        let new_alpha = channel(3);
+        let premultiply = |value: f32| (value * new_alpha * 255.0 + 0.5) as u8;
+        let (red, green, blue) = (channel(0), channel(1), channel(2));
+        pixel[0] = premultiply(blue);
+        pixel[1] = premultiply(green);
+        pixel[2] = premultiply(red);
+        pixel[3] = (new_alpha * 255.0 + 0.5) as u8;
+    }
+}
+
+/// Blurs pixels with three box blurs in each direction, which come close to a Gaussian blur with
+/// the given standard deviation, in pixels. Pixels beyond the edges are the edge pixels repeated.
+fn blur(pixels: &mut [u8], size: Size2D<uint>, deviation: f32) {
+    // The width of the box blurs, as the Filter Effects specification gives it. It's rounded up
+    // to an odd number, so that the boxes are centered.
+    let width = (deviation * 3.0 * (2.0 * f32::consts::pi).sqrt() / 4.0 + 0.5).floor();
+    if width < 1.0 || size.width == 0 || size.height == 0 {
+        return
+    }
+    let radius = (width as uint) / 2;
+    for _ in range(0, 3) {
+        box_blur(pixels, size, radius, true);
+        box_blur(pixels, size, radius, false);
+    }
+}
+
+/// Replaces each pixel by the average of the pixels within `radius` of it, in rows or in columns.
+fn box_blur(pixels: &mut [u8], size: Size2D<uint>, radius: uint, horizontal: bool) {
+    let (length, lines) = if horizontal {
+        (size.width, size.height)
+    } else {
+        (size.height, size.width)
+    };
+    let index = |line: uint, position: uint| {
+        if horizontal {
+            (line * size.width + position) * 4
+        } else {
+            (position * size.width + line) * 4
+        }
+    };
+    let window = 2 * radius + 1;
+    let mut line_pixels = vec::from_elem(length * 4, 0u8);
+    for line in range(0, lines) {
+        for position in range(0, length) {
+            let start = index(line, position);
+            for channel in range(0, 4) {
+                line_pixels[position * 4 + channel] = pixels[start + channel]
+            }
+        }
+
+        // The window starts centered on the first pixel.
+        let mut sums = [0u, 0, 0, 0];
+        for offset in range(0, window) {
+            let position = ((offset as int) - (radius as int)).max(&0).min(&((length - 1) as int));
+            for channel in range(0, 4) {
+                sums[channel] += line_pixels[(position as uint) * 4 + channel] as uint
+            }
+        }
+        for position in range(0, length) {
+            let start = index(line, position);
+            let added = (position + radius + 1).min(&(length - 1));
+            let removed = ((position as int) - (radius as int)).max(&0) as uint;
+            for channel in range(0, 4) {
+                pixels[start + channel] = ((sums[channel] + window / 2) / window) as u8;
+                sums[channel] += line_pixels[added * 4 + channel] as uint;
+                sums[channel] -= line_pixels[removed * 4 + channel] as uint;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn image(pixels: &[[u8, ..4]]) -> Image {
+    let mut data = ~[];
+    for pixel in pixels.iter() {
+        data.push_all(*pixel)
+    }
+    Image(pixels.len(), 1, 4, data)
+}
+
+/// A tile one pixel high, at `x`.
+#[cfg(test)]
+fn row_tile(x: f32, width: f32) -> Rect<f32> {
+    Rect(geom::point::Point2D(x, 0.0), Size2D(width, 1.0))
+}
+
+#[test]
+fn test_alpha_mask() {
+    // Two pixels of opaque white, masked by an image whose left half is opaque.
+    let mut pixels = ~[255u8, 255, 255, 255, 255, 255, 255, 255];
+    let mask = image([[0, 0, 0, 255], [0, 0, 0, 0]]);
+    apply_mask(pixels, Size2D(2, 1), &mask, &row_tile(0.0, 2.0), AlphaMask);
+    assert!(pixels == ~[255, 255, 255, 255, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_luminance_mask() {
+    let mut pixels = ~[255u8, 255, 255, 255, 255, 255, 255, 255];
+    let mask = image([[255, 255, 255, 255], [0, 0, 0, 255]]);
+    apply_mask(pixels, Size2D(2, 1), &mask, &row_tile(0.0, 2.0), LuminanceMask);
+    assert!(pixels == ~[255, 255, 255, 255, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_mask_repeats() {
+    // A tile two pixels wide, starting a whole tile to the left of the group, repeats across it.
+    let mut pixels = vec::from_elem(16, 255u8);
+    let mask = image([[0, 0, 0, 255], [0, 0, 0, 0]]);
+    apply_mask(pixels, Size2D(4, 1), &mask, &row_tile(-2.0, 2.0), AlphaMask);
+    assert!(pixels == ~[255, 255, 255, 255, 0, 0, 0, 0, 255, 255, 255, 255, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_color_matrix_filters() {
+    // Opaque red, and half-transparent white.
+    let mut pixels = ~[0u8, 0, 255, 255, 128, 128, 128, 128];
+    apply_filters(pixels, Size2D(2, 1), [Grayscale(1.0)], 1.0);
+    assert!(pixels[0] == pixels[1] && pixels[1] == pixels[2] && pixels[2] == 54);
+    assert!(pixels.slice(4, 8).to_owned() == ~[128, 128, 128, 128]);
+
+    let mut pixels = ~[0u8, 0, 255, 255, 128, 128, 128, 128];
+    apply_filters(pixels, Size2D(2, 1), [Invert(1.0), Opacity(0.5)], 1.0);
+    assert!(pixels == ~[128, 128, 0, 128, 0, 0, 0, 64]);
+}
+
+#[test]
+fn test_blur_spreads_pixels() {
+    // A single opaque pixel in the middle of a transparent row spreads evenly to either side.
+    let mut pixels = vec::from_elem(9 * 4, 0u8);
+    for channel in range(16, 20) {
+        pixels[channel] = 255
+    }
+    apply_filters(pixels, Size2D(9, 1), [Blur(Au::from_px(1))], 1.0);
+    assert!(pixels[16 + 3] < 255 && pixels[12 + 3] > 0 && pixels[12 + 3] == pixels[20 + 3]);
+    assert!(pixels[3] == 0);
+}
@@ -4,25 +4,34 @@
 
 use color::Color;
 use font_context::FontContext;
-use geometry::Au;
+use glyph_atlas::{GlyphKey, RasterizedGlyph, SUBPIXEL_POSITIONS};
+use geometry::{Au, CSSPixel};
 use platform::font_context::FontContextHandle;
 use platform::font::{FontHandle, FontTable};
 use render_context::RenderContext;
 use servo_util::range::Range;
+use servo_util::shared_str::SharedStr;
+use std::ascii::StrAsciiExt;
 use std::cast;
 use std::ptr;
 use std::str;
 use std::vec;
 use servo_util::cache::{Cache, HashCache};
 use text::glyph::{GlyphStore, GlyphIndex};
-use text::shaping::ShaperMethods;
+use text::line_break::WordBreak;
+use text::text_run::TabSize;
+use text::script::TextDirection;
+use text::shaping::{ShaperMethods, ShapingOptions};
+use text::util::true_type_tag;
 use text::{Shaper, TextRun};
 use extra::arc::Arc;
 
 use azure::{AzFloat, AzScaledFontRef};
 use azure::scaled_font::ScaledFont;
-use azure::azure_hl::{BackendType, ColorPattern};
+use azure::azure_hl::{B8G8R8A8, BackendType, ColorPattern, DrawOptions, DrawSurfaceOptions};
+use azure::azure_hl::Linear;
 use geom::{Point2D, Rect, Size2D};
+use geom::matrix2d::Matrix2D;
 
 use servo_util::time;
 use servo_util::time::profile;
@@ -43,6 +52,7 @@ pub trait FontHandleMethods {
     fn face_name(&self) -> ~str;
     fn is_italic(&self) -> bool;
     fn boldness(&self) -> CSSFontWeight;
+    fn stretchiness(&self) -> CSSFontStretch;
 
     fn clone_with_style(&self, fctx: &FontContextHandle, style: &UsedFontStyle)
                      -> Result<FontHandle, ()>;
@@ -50,8 +60,19 @@ pub trait FontHandleMethods {
     fn glyph_h_advance(&self, GlyphIndex) -> Option<FractionalPixel>;
     fn get_metrics(&self) -> FontMetrics;
     fn get_table_for_tag(&self, FontTableTag) -> Option<FontTable>;
+
+    /// Creates the Azure font that glyphs of this font are drawn with at the given size.
+    fn create_scaled_font(&mut self, backend: BackendType, pt_size: float) -> ScaledFont;
+
+    /// Rasterizes a glyph at the size of this font multiplied by `scale`, shifted right by a
+    /// fraction of a pixel. Returns `None` if the glyph can't be rasterized apart from Azure.
+    fn rasterize_glyph(&self, glyph: GlyphIndex, scale: float, subpixel_offset: float)
+                       -> Option<RasterizedGlyph>;
 }
 
+/// How far synthetic oblique slants glyphs: the tangent of about 14 degrees.
+static SYNTHETIC_OBLIQUE_SKEW: AzFloat = 0.25;
+
 // Used to abstract over the shaper's choice of fixed int representation.
 pub type FractionalPixel = float;
 
@@ -109,6 +130,83 @@ impl CSSFontWeight {
             _ => false
         }
     }
+
+    /// Returns the numeric weight, from 100 to 900.
+    pub fn to_number(self) -> uint {
+        match self {
+            FontWeight100 => 100,
+            FontWeight200 => 200,
+            FontWeight300 => 300,
+            FontWeight400 => 400,
+            FontWeight500 => 500,
+            FontWeight600 => 600,
+            FontWeight700 => 700,
+            FontWeight800 => 800,
+            FontWeight900 => 900,
+        }
+    }
+}
+
+/// The values of `font-stretch`, from the narrowest to the widest.
+#[deriving(Clone, Eq)]
+pub enum CSSFontStretch {
+    FontStretchUltraCondensed,
+    FontStretchExtraCondensed,
+    FontStretchCondensed,
+    FontStretchSemiCondensed,
+    FontStretchNormal,
+    FontStretchSemiExpanded,
+    FontStretchExpanded,
+    FontStretchExtraExpanded,
+    FontStretchUltraExpanded,
+}
+
+impl CSSFontStretch {
+    /// Returns the width class of the stretch, from 1 for ultra-condensed to 9 for
+    /// ultra-expanded, as in the OpenType `OS/2` table.
+    pub fn to_number(self) -> uint {
+        self as uint + 1
+    }
+
+    /// Returns the stretch of the given width class, clamping classes out of range.
+    pub fn from_number(width_class: uint) -> CSSFontStretch {
+        match width_class {
+            0 | 1 => FontStretchUltraCondensed,
+            2 => FontStretchExtraCondensed,
+            3 => FontStretchCondensed,
+            4 => FontStretchSemiCondensed,
+            5 => FontStretchNormal,
+            6 => FontStretchSemiExpanded,
+            7 => FontStretchExpanded,
+            8 => FontStretchExtraExpanded,
+            _ => FontStretchUltraExpanded,
+        }
+    }
+}
+
+/// An OpenType feature turned on or off, or set to a value, for the text of a font style. This is
+/// how `font-feature-settings` and `font-kerning` reach the shaper.
+#[deriving(Clone, Eq)]
+pub struct FontFeature {
+    /// The tag of the feature, such as `kern` or `liga`, as a TrueType tag.
+    tag: u32,
+    /// 0 turns the feature off and 1 on. Features with alternates use larger values to pick one.
+    value: u32,
+}
+
+impl FontFeature {
+    /// Returns the feature named by the four-character `tag`, or `None` if the tag isn't made of
+    /// four printable ASCII characters, as OpenType requires.
+    pub fn new(tag: &str, value: u32) -> Option<FontFeature> {
+        if tag.len() != 4 || !tag.iter().all(|ch| ch >= ' ' && ch <= '~') {
+            return None
+        }
+        let tag: ~[char] = tag.iter().collect();
+        Some(FontFeature {
+            tag: true_type_tag(tag[0], tag[1], tag[2], tag[3]),
+            value: value,
+        })
+    }
 }
 
 // TODO(Issue #179): eventually this will be split into the specified
@@ -121,15 +219,112 @@ impl CSSFontWeight {
 pub struct FontStyle {
     pt_size: float,
     weight: CSSFontWeight,
+    stretch: CSSFontStretch,
     italic: bool,
     oblique: bool,
     families: ~str,
-    // TODO(Issue #198): font-stretch, text-decoration, font-variant, size-adjust
+    /// The language of the content, used to pick language-specific generic families.
+    lang: Option<~str>,
+    /// Whether lowercase letters are shown as small capitals, by `font-variant: small-caps`.
+    small_caps: bool,
+    /// Features to apply on top of the shaper's defaults, in order, so later settings of the same
+    /// feature win.
+    features: ~[FontFeature],
+    // TODO(Issue #198): text-decoration, size-adjust
 }
 
 pub type SpecifiedFontStyle = FontStyle;
 pub type UsedFontStyle = FontStyle;
 
+impl FontStyle {
+    /// Parses a value of the CSS `font` shorthand, as the `font` of canvas contexts takes it.
+    /// Relative sizes are relative to 10px, the initial size of canvas contexts, and line heights
+    /// are ignored. Returns `None` if the value is invalid.
+    pub fn from_css_font(value: &str) -> Option<FontStyle> {
+        let mut style = FontStyle {
+            pt_size: 10f,
+            weight: FontWeight400,
+            stretch: FontStretchNormal,
+            italic: false,
+            oblique: false,
+            families: ~"",
+            lang: None,
+            small_caps: false,
+            features: ~[],
+        };
+
+        // The style, variant, weight and stretch come in any order before the size.
+        let mut rest = value.trim_left();
+        loop {
+            let end = rest.find(|c: char| c.is_whitespace()).unwrap_or_default(rest.len());
+            let (word, remainder) = (rest.slice_to(end), rest.slice_from(end).trim_left());
+            rest = remainder;
+            match parse_font_size(word) {
+                Some(size) => {
+                    style.pt_size = size;
+                    break
+                }
+                None => {}
+            }
+            match word.to_ascii_lower().as_slice() {
+                "normal" => {}
+                "italic" => style.italic = true,
+                "oblique" => style.oblique = true,
+                "small-caps" => style.small_caps = true,
+                "bold" => style.weight = FontWeight700,
+                "100" => style.weight = FontWeight100,
+                "200" => style.weight = FontWeight200,
+                "300" => style.weight = FontWeight300,
+                "400" => style.weight = FontWeight400,
+                "500" => style.weight = FontWeight500,
+                "600" => style.weight = FontWeight600,
+                "700" => style.weight = FontWeight700,
+                "800" => style.weight = FontWeight800,
+                "900" => style.weight = FontWeight900,
+                "ultra-condensed" => style.stretch = FontStretchUltraCondensed,
+                "extra-condensed" => style.stretch = FontStretchExtraCondensed,
+                "condensed" => style.stretch = FontStretchCondensed,
+                "semi-condensed" => style.stretch = FontStretchSemiCondensed,
+                "semi-expanded" => style.stretch = FontStretchSemiExpanded,
+                "expanded" => style.stretch = FontStretchExpanded,
+                "extra-expanded" => style.stretch = FontStretchExtraExpanded,
+                "ultra-expanded" => style.stretch = FontStretchUltraExpanded,
+                _ => return None,
+            }
+        }
+
+        let families: ~[~str] = rest.split_iter(',').map(|family| {
+            family.trim().trim_chars(&'"').trim_chars(&'\'').to_owned()
+        }).collect();
+        if families.iter().any(|family| family.is_empty()) {
+            return None
+        }
+        style.families = families.connect(", ");
+        Some(style)
+    }
+}
+
+/// Parses the size of a `font` shorthand, which may have a line height after a slash, into
+/// pixels.
+fn parse_font_size(word: &str) -> Option<float> {
+    let size = match word.find('/') {
+        Some(slash) => word.slice_to(slash),
+        None => word,
+    };
+    let unit_start = size.find(|c: char| !(c.is_digit() || c == '.')).unwrap_or_default(size.len());
+    let number: float = match from_str(size.slice_to(unit_start)) {
+        Some(number) => number,
+        None => return None,
+    };
+    match size.slice_from(unit_start).to_ascii_lower().as_slice() {
+        "px" => Some(number),
+        "pt" => Some(number * 4.0 / 3.0),
+        "em" | "rem" => Some(number * 10.0),
+        "%" => Some(number * 0.1),
+        _ => None,
+    }
+}
+
 // FIXME: move me to layout
 struct ResolvedFont {
     group: @FontGroup,
@@ -146,13 +341,34 @@ struct ResolvedFont {
 pub struct FontDescriptor {
     style: UsedFontStyle,
     selector: FontSelector,
+    synthesis: FontSynthesis,
 }
 
 impl FontDescriptor {
-    pub fn new(style: UsedFontStyle, selector: FontSelector) -> FontDescriptor {
+    pub fn new(style: UsedFontStyle, selector: FontSelector, synthesis: FontSynthesis)
+               -> FontDescriptor {
         FontDescriptor {
             style: style,
             selector: selector,
+            synthesis: synthesis,
+        }
+    }
+}
+
+/// The parts of a font style that the face chosen for it lacks, and which are faked when the
+/// font is drawn: bold by drawing each glyph twice, slightly apart, and oblique by slanting the
+/// glyphs.
+#[deriving(Clone, Eq)]
+pub struct FontSynthesis {
+    bold: bool,
+    oblique: bool,
+}
+
+impl FontSynthesis {
+    pub fn none() -> FontSynthesis {
+        FontSynthesis {
+            bold: false,
+            oblique: false,
         }
     }
 }
@@ -191,11 +407,16 @@ impl FontGroup {
         self.fonts = ~[];
     }
 
-    pub fn create_textrun(&self, text: ~str, underline: bool) -> TextRun {
+    pub fn create_textrun(&self,
+                          text: ~str,
+                          direction: TextDirection,
+                          tab_size: TabSize,
+                          word_break: WordBreak)
+                          -> TextRun {
         assert!(self.fonts.len() > 0);
 
         // TODO(Issue #177): Actually fall back through the FontGroup when a font is unsuitable.
-        return TextRun::new(self.fonts[0], text, underline);
+        return TextRun::new(self.fonts[0], text, direction, tab_size, word_break);
     }
 }
 
@@ -227,6 +448,30 @@ impl RunMetrics {
     }
 }
 
+/// The metrics of a line of text, as the `measureText()` of canvas contexts reports them.
+#[deriving(Clone)]
+pub struct TextMetrics {
+    advance_width: Au,
+    /// The ascent and descent of the font, which canvas calls the font bounding box.
+    font_ascent: Au,
+    font_descent: Au,
+    /// The box around the glyphs, relative to the start of the baseline.
+    bounding_box: Rect<Au>,
+}
+
+impl TextMetrics {
+    pub fn from_run_metrics(metrics: &RunMetrics) -> TextMetrics {
+        // TODO(Issue #125): The bounding box of the run is as tall as the font and as wide as its
+        // advance, rather than the box that the glyphs ink.
+        TextMetrics {
+            advance_width: metrics.advance_width,
+            font_ascent: metrics.ascent,
+            font_descent: metrics.descent,
+            bounding_box: metrics.bounding_box,
+        }
+    }
+}
+
 /**
 A font instance. Layout can use this to calculate glyph metrics
 and the renderer can use it to render text.
@@ -236,10 +481,20 @@ pub struct Font {
     priv azure_font: Option<ScaledFont>,
     priv shaper: Option<@Shaper>,
     style: UsedFontStyle,
+    /// What is faked because the face doesn't match the style.
+    synthesis: FontSynthesis,
     metrics: FontMetrics,
     backend: BackendType,
     profiler_chan: ProfilerChan,
-    shape_cache: HashCache<~str, Arc<GlyphStore>>,
+    shape_cache: HashCache<ShapeCacheKey, Arc<GlyphStore>>,
+}
+
+/// The key under which a font caches shaped words. The same text can shape differently depending
+/// on the script and direction it is shaped with, so those are part of the key too.
+#[deriving(Clone, Eq, IterBytes)]
+struct ShapeCacheKey {
+    text: SharedStr,
+    options: ShapingOptions,
 }
 
 impl Font {
@@ -264,6 +519,7 @@ impl Font {
             azure_font: None,
             shaper: None,
             style: (*style).clone(),
+            synthesis: FontSynthesis::none(),
             metrics: metrics,
             backend: backend,
             profiler_chan: profiler_chan,
@@ -281,6 +537,7 @@ impl Font {
             azure_font: None,
             shaper: None,
             style: (*style).clone(),
+            synthesis: FontSynthesis::none(),
             metrics: metrics,
             backend: backend,
             profiler_chan: profiler_chan,
@@ -324,11 +581,6 @@ impl Font {
         return result;
     }
 
-    pub fn teardown(&mut self) {
-        self.shaper = None;
-        self.azure_font = None;
-    }
-
     // TODO: this should return a borrowed pointer, but I can't figure
     // out why borrowck doesn't like my implementation.
 
@@ -345,19 +597,8 @@ impl Font {
         return self.get_azure_font();
     }
 
-    #[cfg(target_os="macos")]
     fn create_azure_font(&mut self) -> ScaledFont {
-        let cg_font = self.handle.get_CGFont();
-        let size = self.style.pt_size as AzFloat;
-        ScaledFont::new(self.backend, &cg_font, size)
-    }
-
-    #[cfg(target_os="linux")]
-    #[cfg(target_os="android")]
-    fn create_azure_font(&self) -> ScaledFont {
-        let freetype_font = self.handle.face;
-        let size = self.style.pt_size as AzFloat;
-        ScaledFont::new(self.backend, freetype_font, size)
+        self.handle.create_scaled_font(self.backend, self.style.pt_size)
     }
 }
 
@@ -377,6 +618,27 @@ impl Font {
                     struct__AzPoint};
         use azure::azure::{AzDrawTargetFillGlyphs};
 
+        // Glyphs are drawn from the glyph atlas where they can be, and by Azure otherwise.
+        if !self.synthesis.oblique {
+            let mut glyphs = ~[];
+            let mut origin = baseline_origin;
+            for glyph in run.iter_glyphs_for_range(range) {
+                glyphs.push((glyph.index, Point2D(origin.x + glyph.offset.x,
+                                                  origin.y + glyph.offset.y)));
+                origin = Point2D(origin.x + glyph.advance, origin.y);
+            }
+            if self.draw_glyphs_from_atlas(rctx, glyphs, color) {
+                // Synthetic bold draws the glyphs again, a pixel to the right.
+                if self.synthesis.bold {
+                    let glyphs = do glyphs.map |&(index, position)| {
+                        (index, Point2D(position.x + Au::from_px(1), position.y))
+                    };
+                    self.draw_glyphs_from_atlas(rctx, glyphs, color);
+                }
+                return
+            }
+        }
+
         let target = rctx.get_draw_target();
         let azfontref = self.get_azure_font();
         let pattern = ColorPattern(color);
@@ -392,21 +654,16 @@ impl Font {
         let mut azglyphs = ~[];
         azglyphs.reserve(range.length());
 
-        for (glyphs, _offset, slice_range) in run.iter_slices_for_range(range) {
-            for (_i, glyph) in glyphs.iter_glyphs_for_char_range(&slice_range) {
-                let glyph_advance = glyph.advance();
-                let glyph_offset = glyph.offset().unwrap_or_default(Au::zero_point());
-
-                let azglyph = struct__AzGlyph {
-                    mIndex: glyph.index() as uint32_t,
-                    mPosition: struct__AzPoint {
-                        x: (origin.x + glyph_offset.x).to_nearest_px() as AzFloat,
-                        y: (origin.y + glyph_offset.y).to_nearest_px() as AzFloat
-                    }
-                };
-                origin = Point2D(origin.x + glyph_advance, origin.y);
-                azglyphs.push(azglyph)
+        for glyph in run.iter_glyphs_for_range(range) {
+            let azglyph = struct__AzGlyph {
+                mIndex: glyph.index as uint32_t,
+                mPosition: struct__AzPoint {
+                    x: (origin.x + glyph.offset.x).to_nearest_px() as AzFloat,
+                    y: (origin.y + glyph.offset.y).to_nearest_px() as AzFloat
+                }
             };
+            origin = Point2D(origin.x + glyph.advance, origin.y);
+            azglyphs.push(azglyph)
         }
 
         let azglyph_buf_len = azglyphs.len();
@@ -417,6 +674,17 @@ impl Font {
             mNumGlyphs: azglyph_buf_len as uint32_t            
         };
 
+        // Synthetic oblique slants the glyphs around the baseline, and then applies the current
+        // transform.
+        let old_transform = target.get_transform();
+        if self.synthesis.oblique {
+            let baseline = baseline_origin.y.to_nearest_px() as AzFloat;
+            let skew = Matrix2D::new(1.0, 0.0,
+                                     -SYNTHETIC_OBLIQUE_SKEW, 1.0,
+                                     SYNTHETIC_OBLIQUE_SKEW * baseline, 0.0);
+            target.set_transform(&skew.mul(&old_transform));
+        }
+
         unsafe {
             // TODO(Issue #64): this call needs to move into azure_hl.rs
             AzDrawTargetFillGlyphs(target.azure_draw_target,
@@ -426,16 +694,145 @@ impl Font {
                                    ptr::to_unsafe_ptr(&options),
                                    ptr::null());
         }
+
+        // Synthetic bold draws the glyphs again, a pixel to the right.
+        if self.synthesis.bold {
+            for azglyph in azglyphs.mut_iter() {
+                azglyph.mPosition.x += 1.0;
+            }
+            unsafe {
+                AzDrawTargetFillGlyphs(target.azure_draw_target,
+                                       azfontref,
+                                       ptr::to_unsafe_ptr(&glyphbuf),
+                                       azure_pattern,
+                                       ptr::to_unsafe_ptr(&options),
+                                       ptr::null());
+            }
+        }
+
+        if self.synthesis.oblique {
+            target.set_transform(&old_transform);
+        }
+    }
+
+    /// Draws glyphs at the given baseline positions, rasterized into the glyph atlas of the font
+    /// context of `rctx`. The glyphs are composited into a bitmap of their own, which is drawn at
+    /// once. Returns false, having drawn nothing, if the glyphs can't be drawn this way: if the
+    /// current transform does more than scale and translate, or if a glyph can't be rasterized.
+    fn draw_glyphs_from_atlas(&self,
+                              rctx: &RenderContext,
+                              glyphs: &[(GlyphIndex, Point2D<Au>)],
+                              color: Color)
+                              -> bool {
+        let target = rctx.get_draw_target();
+        let transform = target.get_transform();
+        if transform.m12 != 0.0 || transform.m21 != 0.0 || transform.m11 <= 0.0 ||
+                transform.m11 != transform.m22 {
+            return false
+        }
+        let scale = transform.m11;
+        let font = self.handle.face_identifier();
+        let size = (self.style.pt_size * (scale as float) * 64.0) as u32;
+        let atlas = &mut rctx.font_ctx.glyph_atlas;
+
+        // Place each glyph on the pixel grid of the device, and find the pixels they cover. The
+        // coverage of each glyph is copied out as it's found, as a later glyph of the run can
+        // fill the atlas and evict it.
+        let mut placed = ~[];
+        let (mut left, mut top, mut right, mut bottom) = (0, 0, 0, 0);
+        for &(index, position) in glyphs.iter() {
+            let x = (*position.x.to_device_px(scale as float) as AzFloat) + transform.m31;
+            let y = (*position.y.to_device_px(scale as float) as AzFloat) + transform.m32;
+            let pixel_x = x.floor();
+            let subpixel_offset = (((x - pixel_x) * (SUBPIXEL_POSITIONS as AzFloat)) as uint)
+                .min(&(SUBPIXEL_POSITIONS - 1));
+            let key = GlyphKey {
+                font: font.clone(),
+                size: size,
+                glyph: index,
+                subpixel_offset: subpixel_offset as u8,
+            };
+            let glyph = do atlas.find_or_rasterize(&key) {
+                let offset = (subpixel_offset as float) / (SUBPIXEL_POSITIONS as float);
+                self.handle.rasterize_glyph(index, scale as float, offset)
+            };
+            let glyph = match glyph {
+                Some(glyph) => glyph,
+                None => return false,
+            };
+            if glyph.width == 0 || glyph.height == 0 {
+                loop
+            }
+            let glyph_left = (pixel_x as int) + glyph.left;
+            let glyph_top = (y.round() as int) - glyph.top;
+            if placed.is_empty() {
+                left = glyph_left;
+                top = glyph_top;
+                right = glyph_left;
+                bottom = glyph_top;
+            }
+            left = left.min(&glyph_left);
+            top = top.min(&glyph_top);
+            right = right.max(&(glyph_left + (glyph.width as int)));
+            bottom = bottom.max(&(glyph_top + (glyph.height as int)));
+            let mut coverage = vec::with_capacity(glyph.width * glyph.height);
+            for row in range(0, glyph.height) {
+                coverage.push_all(atlas.row(&glyph, row));
+            }
+            placed.push((glyph, coverage, glyph_left, glyph_top));
+        }
+        if placed.is_empty() {
+            return true
+        }
+
+        // Composite the glyphs over each other, in premultiplied BGRA.
+        let (width, height) = ((right - left) as uint, (bottom - top) as uint);
+        let mut pixels = vec::from_elem(width * height * 4, 0u8);
+        let source = [color.b * color.a, color.g * color.a, color.r * color.a, color.a];
+        for &(ref glyph, ref coverage, glyph_left, glyph_top) in placed.iter() {
+            for row in range(0, glyph.height) {
+                let coverage = coverage.slice(row * glyph.width, (row + 1) * glyph.width);
+                let start = ((glyph_top - top) as uint + row) * width +
+                    (glyph_left - left) as uint;
+                for column in range(0, glyph.width) {
+                    let alpha = (coverage[column] as AzFloat) / 255.0;
+                    if alpha == 0.0 {
+                        loop
+                    }
+                    let index = (start + column) * 4;
+                    for channel in range(0, 4) {
+                        let destination = (pixels[index + channel] as AzFloat) / 255.0;
+                        let value = source[channel] * alpha + destination * (1.0 - color.a * alpha);
+                        pixels[index + channel] = (value * 255.0 + 0.5).min(&255.0) as u8;
+                    }
+                }
+            }
+        }
+
+        // The bitmap is already in device pixels, so it's drawn without the transform.
+        let size = Size2D(width as i32, height as i32);
+        let surface = target.create_source_surface_from_data(pixels, size, (width * 4) as i32,
+                                                             B8G8R8A8);
+        let source_rect = Rect(Point2D(0 as AzFloat, 0 as AzFloat),
+                               Size2D(width as AzFloat, height as AzFloat));
+        let dest_rect = Rect(Point2D(left as AzFloat, top as AzFloat),
+                             Size2D(width as AzFloat, height as AzFloat));
+        target.set_transform(&Matrix2D::identity());
+        target.draw_surface(surface,
+                            dest_rect,
+                            source_rect,
+                            DrawSurfaceOptions(Linear, true),
+                            DrawOptions(1.0 as AzFloat, 0));
+        target.set_transform(&transform);
+        true
     }
 
     pub fn measure_text(&self, run: &TextRun, range: &Range) -> RunMetrics {
         // TODO(Issue #199): alter advance direction for RTL
         // TODO(Issue #98): using inter-char and inter-word spacing settings  when measuring text
         let mut advance = Au(0);
-        for (glyphs, _offset, slice_range) in run.iter_slices_for_range(range) {
-            for (_i, glyph) in glyphs.iter_glyphs_for_char_range(&slice_range) {
-                advance = advance + glyph.advance();
-            }
+        for glyph in run.iter_glyphs_for_range(range) {
+            advance = advance + glyph.advance;
         }
         RunMetrics::new(advance, self.metrics.ascent, self.metrics.descent)
     }
@@ -451,19 +848,55 @@ impl Font {
         RunMetrics::new(advance, self.metrics.ascent, self.metrics.descent)
     }
 
-    pub fn shape_text(@mut self, text: ~str, is_whitespace: bool) -> Arc<GlyphStore> {
-        do profile(time::LayoutShapingCategory, self.profiler_chan.clone()) {
-            let shaper = self.get_shaper();
-            do self.shape_cache.find_or_create(&text) |txt| {
-                let mut glyphs = GlyphStore::new(text.char_len(), is_whitespace);
-                shaper.shape_text(*txt, &mut glyphs);
-                Arc::new(glyphs)
-            }
+    /// Shapes a single word (or run of whitespace), reusing the result of an earlier call with
+    /// the same text and options if there was one. Since fonts outlive reflows, this means
+    /// repeated words and repeated reflows of the same text are only shaped once.
+    ///
+    /// The word is usually a slice of a whole text run. Looking it up doesn't copy it; only words
+    /// that haven't been shaped before are copied into the cache.
+    pub fn shape_text(@mut self, text: SharedStr, options: &ShapingOptions, is_whitespace: bool)
+                      -> Arc<GlyphStore> {
+        let mut key = ShapeCacheKey {
+            text: text,
+            options: *options,
+        };
+        match self.shape_cache.find(&key) {
+            Some(glyphs) => return glyphs,
+            None => {}
         }
+
+        let glyphs = do profile(time::LayoutShapingCategory, self.profiler_chan.clone()) {
+            let shaper = self.get_shaper();
+            let mut glyphs = GlyphStore::new(key.text.as_slice().char_len(), is_whitespace);
+            shaper.shape_text(key.text.as_slice(), options, &mut glyphs);
+            Arc::new(glyphs)
+        };
+        // The cache outlives the run, so it shouldn't keep the rest of the run's text alive.
+        key.text = key.text.compact();
+        self.shape_cache.insert(key, glyphs.clone());
+        glyphs
     }
 
     pub fn get_descriptor(&self) -> FontDescriptor {
-        FontDescriptor::new(self.style.clone(), SelectorPlatformIdentifier(self.handle.face_identifier()))
+        FontDescriptor::new(self.style.clone(),
+                            SelectorPlatformIdentifier(self.handle.face_identifier()),
+                            self.synthesis)
+    }
+
+    /// Returns whether the font has the OpenType feature `tag`, such as `smcp`, in its `GSUB`
+    /// table. Fonts whose tables can't be read are taken not to have it.
+    pub fn has_feature(&self, tag: u32) -> bool {
+        let table = match self.get_table_for_tag(true_type_tag('G', 'S', 'U', 'B')) {
+            Some(table) => table,
+            None => return false,
+        };
+        let mut found = false;
+        do table.with_buffer |buffer, length| {
+            found = unsafe {
+                vec::raw::buf_as_slice(buffer, length, |bytes| gsub_has_feature(bytes, tag))
+            };
+        }
+        found
     }
 
     pub fn glyph_index(&self, codepoint: char) -> Option<GlyphIndex> {
@@ -476,6 +909,41 @@ impl Font {
           None => /* FIXME: Need fallback strategy */ 10f as FractionalPixel
         }
     }
+
+    /// Returns the advance of the glyph for "0", which the CSS `ch` unit is, if the font has one.
+    pub fn zero_advance(&self) -> Option<Au> {
+        do self.glyph_index('0').map_move |glyph| {
+            Au::from_css_px(CSSPixel(self.glyph_h_advance(glyph)))
+        }
+    }
+}
+
+/// Returns whether the feature list of the `GSUB` table `table` has a feature with the given tag.
+/// Truncated tables are read as far as they go.
+fn gsub_has_feature(table: &[u8], tag: u32) -> bool {
+    fn read_u16(bytes: &[u8], offset: uint) -> Option<uint> {
+        if offset + 2 > bytes.len() {
+            return None
+        }
+        Some((bytes[offset] as uint << 8) | bytes[offset + 1] as uint)
+    }
+
+    // The offset of the feature list follows the version and the offset of the script list.
+    let feature_list = match read_u16(table, 6) {
+        Some(offset) => offset,
+        None => return false,
+    };
+    let feature_count = read_u16(table, feature_list).unwrap_or_default(0);
+    for i in range(0, feature_count) {
+        // Each feature record is a tag and the offset of the feature.
+        let record = feature_list + 2 + i * 6;
+        match (read_u16(table, record), read_u16(table, record + 2)) {
+            (Some(high), Some(low)) if ((high << 16) | low) as u32 == tag => return true,
+            (Some(_), Some(_)) => {}
+            _ => return false,
+        }
+    }
+    false
 }
 
 /*fn should_destruct_on_fail_without_leaking() {
@@ -547,3 +1015,50 @@ fn should_be_able_to_create_instances_in_multiple_threads() {
 }
 
 */
+
+#[test]
+fn test_font_feature() {
+    let kern = FontFeature::new("kern", 0).unwrap();
+    assert!(kern.tag == true_type_tag('k', 'e', 'r', 'n'));
+    assert!(kern.value == 0);
+    assert!(FontFeature::new("ss01", 1).is_some());
+    assert!(FontFeature::new("liga ", 1).is_none());
+    assert!(FontFeature::new("lig", 1).is_none());
+    assert!(FontFeature::new("lig\n", 1).is_none());
+}
+
+#[test]
+fn test_gsub_has_feature() {
+    let gsub: &[u8] = &[
+        0, 1, 0, 0, 0, 10, 0, 10, 0, 0,
+        // The feature list: `liga` and `smcp`.
+        0, 2, 'l' as u8, 'i' as u8, 'g' as u8, 'a' as u8, 0, 14,
+        's' as u8, 'm' as u8, 'c' as u8, 'p' as u8, 0, 20,
+    ];
+    assert!(gsub_has_feature(gsub, true_type_tag('s', 'm', 'c', 'p')));
+    assert!(gsub_has_feature(gsub, true_type_tag('l', 'i', 'g', 'a')));
+    assert!(!gsub_has_feature(gsub, true_type_tag('c', '2', 's', 'c')));
+    assert!(!gsub_has_feature(gsub.slice_to(20), true_type_tag('s', 'm', 'c', 'p')));
+    assert!(!gsub_has_feature([], true_type_tag('s', 'm', 'c', 'p')));
+}
+
+#[test]
+fn test_font_style_from_css_font() {
+    let style = FontStyle::from_css_font("italic bold 12px/1.5 \"Helvetica Neue\", serif").unwrap();
+    assert!(style.italic);
+    assert!(style.weight == FontWeight700);
+    assert!(style.pt_size == 12f);
+    assert!(style.families == ~"Helvetica Neue, serif");
+
+    let style = FontStyle::from_css_font("2em sans-serif").unwrap();
+    assert!(style.pt_size == 20f);
+    assert!(style.weight == FontWeight400);
+}
+
+#[test]
+fn test_font_style_from_css_font_rejects_invalid_values() {
+    assert!(FontStyle::from_css_font("").is_none());
+    assert!(FontStyle::from_css_font("bold serif").is_none());
+    assert!(FontStyle::from_css_font("12px").is_none());
+    assert!(FontStyle::from_css_font("heavy 12px serif").is_none());
+}
@@ -4,27 +4,97 @@
 
 use font::{Font, FontDescriptor, FontGroup, FontHandleMethods, FontStyle,
            SelectorPlatformIdentifier};
-use font::{SpecifiedFontStyle, UsedFontStyle};
+use font::{SpecifiedFontStyle, TextMetrics, UsedFontStyle};
 use font_list::FontList;
+use text::line_break::WordBreakNormal;
+use text::text_run::TabSpaces;
+use text::script::LeftToRight;
+use opts::GenericFontPreference;
 use servo_util::cache::{Cache, LRUCache};
+use servo_util::range::Range;
 use servo_util::time::ProfilerChan;
+use glyph_atlas::GlyphAtlas;
 
 use platform::font::FontHandle;
 use platform::font_context::FontContextHandle;
 
 use azure::azure_hl::BackendType;
+use std::ascii::StrAsciiExt;
 use std::hashmap::HashMap;
 
 // TODO(Rust #3934): creating lots of new dummy styles is a workaround
 // for not being able to store symbolic enums in top-level constants.
 pub fn dummy_style() -> FontStyle {
-    use font::FontWeight300;
+    use font::{FontStretchNormal, FontWeight300};
     return FontStyle {
         pt_size: 20f,
         weight: FontWeight300,
+        stretch: FontStretchNormal,
         italic: false,
         oblique: false,
         families: ~"serif, sans-serif",
+        lang: None,
+        small_caps: false,
+        features: ~[],
+    }
+}
+
+/// Maps the CSS generic font families to concrete families. Mappings can be given for content in
+/// a particular language, which take precedence over the language-independent ones.
+pub struct GenericFontFamilies {
+    priv defaults: HashMap<~str, ~str>,
+    priv by_lang: HashMap<~str, HashMap<~str, ~str>>,
+}
+
+impl GenericFontFamilies {
+    pub fn new(prefs: &[GenericFontPreference]) -> GenericFontFamilies {
+        let mut defaults = HashMap::with_capacity(5);
+        defaults.insert(~"serif", ~"Times New Roman");
+        defaults.insert(~"sans-serif", ~"Arial");
+        defaults.insert(~"cursive", ~"Apple Chancery");
+        defaults.insert(~"fantasy", ~"Papyrus");
+        defaults.insert(~"monospace", ~"Menlo");
+
+        let mut families = GenericFontFamilies {
+            defaults: defaults,
+            by_lang: HashMap::new(),
+        };
+        for pref in prefs.iter() {
+            match pref.lang {
+                None => {
+                    families.defaults.insert(pref.generic.clone(), pref.family.clone());
+                }
+                Some(ref lang) => {
+                    let mapping = families.by_lang.find_or_insert_with(lang.clone(),
+                                                                       |_| HashMap::new());
+                    mapping.insert(pref.generic.clone(), pref.family.clone());
+                }
+            }
+        }
+        families
+    }
+
+    /// Returns the family that `generic` maps to for content in the given language. A mapping
+    /// for a language also applies to its more specific variants, so `zh` covers `zh-TW`.
+    pub fn find(&self, generic: &str, lang: Option<&str>) -> Option<~str> {
+        let generic = generic.to_ascii_lower();
+        for lang in lang.iter() {
+            let mut lang = lang.to_ascii_lower();
+            loop {
+                match self.by_lang.find(&lang).chain(|mapping| mapping.find(&generic)) {
+                    Some(family) => return Some(family.clone()),
+                    None => {}
+                }
+                match lang.rfind('-') {
+                    Some(i) => {
+                        let parent = lang.slice_to(i).to_owned();
+                        lang = parent;
+                    }
+                    None => break,
+                }
+            }
+        }
+        self.defaults.find(&generic).map(|family| (*family).clone())
     }
 }
 
@@ -39,13 +109,17 @@ pub struct FontContext {
     group_cache: LRUCache<SpecifiedFontStyle, @FontGroup>,
     handle: FontContextHandle,
     backend: BackendType,
-    generic_fonts: HashMap<~str,~str>,
+    generic_fonts: GenericFontFamilies,
+    /// The rasterized glyphs of every font, shared so that fonts of the same face at the same
+    /// size don't rasterize a glyph twice. Only rendering uses it.
+    glyph_atlas: GlyphAtlas,
     profiler_chan: ProfilerChan,
 }
 
 impl<'self> FontContext {
     pub fn new(backend: BackendType,
            needs_font_list: bool,
+           generic_font_prefs: &[GenericFontPreference],
            profiler_chan: ProfilerChan)
            -> FontContext {
         let handle = FontContextHandle::new();
@@ -53,13 +127,7 @@ impl<'self> FontContext {
                             Some(FontList::new(&handle, profiler_chan.clone())) }
                         else { None };
 
-        // TODO: Allow users to specify these.
-        let mut generic_fonts = HashMap::with_capacity(5);
-        generic_fonts.insert(~"serif", ~"Times New Roman");
-        generic_fonts.insert(~"sans-serif", ~"Arial");
-        generic_fonts.insert(~"cursive", ~"Apple Chancery");
-        generic_fonts.insert(~"fantasy", ~"Papyrus");
-        generic_fonts.insert(~"monospace", ~"Menlo");
+        let generic_fonts = GenericFontFamilies::new(generic_font_prefs);
 
         FontContext { 
             instance_cache: LRUCache::new(10),
@@ -68,10 +136,16 @@ impl<'self> FontContext {
             handle: handle,
             backend: backend,
             generic_fonts: generic_fonts,
+            glyph_atlas: GlyphAtlas::new(),
             profiler_chan: profiler_chan,
         }
     }
 
+    /// Returns how many fonts this context holds loaded.
+    pub fn font_count(&self) -> uint {
+        self.instance_cache.len()
+    }
+
     fn get_font_list(&'self self) -> &'self FontList {
         self.font_list.get_ref()
     }
@@ -91,6 +165,15 @@ impl<'self> FontContext {
         }
     }
 
+    /// Measures `text` as a single line in the given style, as the `measureText()` of canvas
+    /// contexts does.
+    pub fn measure_text(&mut self, style: &SpecifiedFontStyle, text: &str) -> TextMetrics {
+        let group = self.get_resolved_font_for_style(style);
+        // Tab stops are at the initial `tab-size`, 8 spaces apart.
+        let run = group.create_textrun(text.to_owned(), LeftToRight, TabSpaces(8), WordBreakNormal);
+        TextMetrics::from_run_metrics(&run.metrics_for_range(&Range::new(0, run.char_len())))
+    }
+
     pub fn get_font_by_descriptor(&mut self, desc: &FontDescriptor) -> Result<@mut Font, ()> {
         match self.instance_cache.find(desc) {
             Some(f) => {
@@ -110,13 +193,11 @@ impl<'self> FontContext {
         }
     }
 
-    fn transform_family(&self, family: &str) -> ~str {
-        // FIXME: Need a find_like() in HashMap.
-        let family = family.to_str();
-        debug!("(transform family) searching for `%s`", family);
-        match self.generic_fonts.find(&family) {
-            None => family,
-            Some(mapped_family) => (*mapped_family).clone()
+    fn transform_family(&self, family: &str, lang: Option<&str>) -> ~str {
+        debug!("(transform family) searching for `%s` (lang=%?)", family, lang);
+        match self.generic_fonts.find(family, lang) {
+            None => family.to_owned(),
+            Some(mapped_family) => mapped_family
         }
     }
 
@@ -128,7 +209,8 @@ impl<'self> FontContext {
         // TODO(Issue #193): make iteration over 'font-family' more robust.
         for family in style.families.split_iter(',') {
             let family_name = family.trim();
-            let transformed_family_name = self.transform_family(family_name);
+            let lang = style.lang.as_ref().map(|lang| lang.as_slice());
+            let transformed_family_name = self.transform_family(family_name, lang);
             debug!("(create font group) transformed family is `%s`", transformed_family_name);
 
             let result = do self.font_list.chain_ref |fl| {
@@ -141,7 +223,8 @@ impl<'self> FontContext {
 
                 let font_id =
                   SelectorPlatformIdentifier(font_entry.handle.face_identifier());
-                let font_desc = FontDescriptor::new((*style).clone(), font_id);
+                let font_desc = FontDescriptor::new((*style).clone(), font_id,
+                                                    font_entry.synthesis_for_style(style));
 
                 let instance = self.get_font_by_descriptor(&font_desc);
 
@@ -163,7 +246,8 @@ impl<'self> FontContext {
             for font_entry in result.iter() {
                 let font_id =
                   SelectorPlatformIdentifier(font_entry.handle.face_identifier());
-                let font_desc = FontDescriptor::new((*style).clone(), font_id);
+                let font_desc = FontDescriptor::new((*style).clone(), font_id,
+                                                    font_entry.synthesis_for_style(style));
 
                 let instance = self.get_font_by_descriptor(&font_desc);
 
@@ -189,13 +273,33 @@ impl<'self> FontContext {
                 let result_handle = self.handle.create_font_from_identifier((*identifier).clone(),
                                                                             desc.style.clone());
                 do result_handle.chain |handle| {
-                    Ok(Font::new_from_adopted_handle(self,
-                                                     handle,
-                                                     &desc.style,
-                                                     self.backend,
-                                                     self.profiler_chan.clone()))
+                    let font = Font::new_from_adopted_handle(self,
+                                                             handle,
+                                                             &desc.style,
+                                                             self.backend,
+                                                             self.profiler_chan.clone());
+                    font.synthesis = desc.synthesis;
+                    Ok(font)
                 }
             }
         };
     }
 }
+
+#[test]
+fn test_generic_font_families() {
+    let prefs = [
+        GenericFontPreference::from_str("serif=Georgia").unwrap(),
+        GenericFontPreference::from_str("serif:ja=IPAMincho").unwrap(),
+        GenericFontPreference::from_str("monospace:zh-TW=AR PL UMing TW").unwrap(),
+    ];
+    let families = GenericFontFamilies::new(prefs);
+
+    assert!(families.find("serif", None) == Some(~"Georgia"));
+    assert!(families.find("serif", Some("ja")) == Some(~"IPAMincho"));
+    assert!(families.find("serif", Some("ja-JP")) == Some(~"IPAMincho"));
+    assert!(families.find("serif", Some("fr")) == Some(~"Georgia"));
+    assert!(families.find("monospace", Some("zh-tw")) == Some(~"AR PL UMing TW"));
+    assert!(families.find("monospace", Some("zh")) == Some(~"Menlo"));
+    assert!(families.find("Helvetica", None) == None);
+}
@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use font::{CSSFontWeight, SpecifiedFontStyle};
+use font::{CSSFontStretch, CSSFontWeight, FontStretchNormal, FontSynthesis, SpecifiedFontStyle};
 use gfx_font::FontHandleMethods;
 use platform::font::FontHandle;
 use platform::font_context::FontContextHandle;
@@ -12,13 +12,14 @@ use servo_util::time::profile;
 use servo_util::time::ProfilerChan;
 
 use std::hashmap::HashMap;
+use std::uint;
 
 pub type FontFamilyMap = HashMap<~str, @mut FontFamily>;
 
-trait FontListHandleMethods {
-    fn get_available_families(&self, fctx: &FontContextHandle) -> FontFamilyMap;
+/// The platform's list of installed fonts, which FreeType with fontconfig or Core Text provides.
+pub trait FontListHandleMethods {
+    fn get_available_families(&self) -> FontFamilyMap;
     fn load_variations_for_family(&self, family: @mut FontFamily);
-    fn get_last_resort_font_families() -> ~[~str];
 }
 
 /// The platform-independent font list abstraction.
@@ -115,6 +116,8 @@ impl FontFamily {
         assert!(self.entries.len() > 0)
     }
 
+    /// Returns the face of the family that best matches `style`. Every family has one, though
+    /// it may lack the weight or slant asked for; see `FontEntry::synthesis_for_style`.
     pub fn find_font_for_style(@mut self, list: &FontListHandle, style: &SpecifiedFontStyle)
                             -> Option<@FontEntry> {
         self.load_family_variations(list);
@@ -122,18 +125,72 @@ impl FontFamily {
         // TODO(Issue #189): optimize lookup for
         // regular/bold/italic/bolditalic with fixed offsets and a
         // static decision table for fallback between these values.
+        let faces: ~[FaceProperties] = self.entries.iter().map(|entry| entry.properties).collect();
+        find_best_match(faces, style).map(|&i| self.entries[i])
+    }
+}
+
+/// The properties of a face that font matching looks at.
+#[deriving(Clone, Eq)]
+pub struct FaceProperties {
+    weight: CSSFontWeight,
+    stretch: CSSFontStretch,
+    /// Whether the face is italic or oblique.
+    italic: bool,
+}
+
+/// Returns the index of the face that best matches `style`, following the font matching algorithm
+/// of CSS Fonts Level 3: faces are narrowed down to the closest stretch, then to the right slant if
+/// there are faces with it, and the closest weight among those is picked. Ties go to the first
+/// face.
+pub fn find_best_match(faces: &[FaceProperties], style: &SpecifiedFontStyle) -> Option<uint> {
+    let desired_stretch = style.stretch.to_number();
+    let best_stretch = match faces.iter().map(|face| face.stretch.to_number()).min_by(|&width| {
+        preference(desired_stretch, width, desired_stretch <= FontStretchNormal.to_number())
+    }) {
+        Some(width) => width,
+        None => return None,
+    };
 
-        // TODO(Issue #190): if not in the fast path above, do
-        // expensive matching of weights, etc.
-        for entry in self.entries.iter() {
-            if (style.weight.is_bold() == entry.is_bold()) && 
-               (style.italic == entry.is_italic()) {
+    let slanted = style.italic || style.oblique;
+    let has_slant = faces.iter().any(|face| {
+        face.stretch.to_number() == best_stretch && face.italic == slanted
+    });
 
-                return Some(*entry);
-            }
+    let desired_weight = style.weight.to_number();
+    let mut best: Option<(uint, (uint, uint))> = None;
+    for (i, face) in faces.iter().enumerate() {
+        if face.stretch.to_number() != best_stretch || (has_slant && face.italic != slanted) {
+            loop
+        }
+        let rank = weight_preference(desired_weight, face.weight.to_number());
+        match best {
+            Some((_, best_rank)) if best_rank <= rank => {}
+            _ => best = Some((i, rank)),
         }
+    }
+    best.map(|&(i, _)| i)
+}
+
+/// Ranks `candidate` as a match for `desired`, lower being better: an exact match first, then
+/// values on the preferred side, nearest first, then values on the other side, nearest first.
+fn preference(desired: uint, candidate: uint, smaller_first: bool) -> (uint, uint) {
+    if candidate == desired {
+        (0, 0)
+    } else if (candidate < desired) == smaller_first {
+        (2, uint::max(desired, candidate) - uint::min(desired, candidate))
+    } else {
+        (3, uint::max(desired, candidate) - uint::min(desired, candidate))
+    }
+}
 
-        None
+/// Ranks the weight `candidate` as a match for `desired`. Lighter weights are preferred for 500
+/// and below, and heavier ones above it, except that 400 and 500 are each other's first choice.
+fn weight_preference(desired: uint, candidate: uint) -> (uint, uint) {
+    if (desired == 400 && candidate == 500) || (desired == 500 && candidate == 400) {
+        (1, 0)
+    } else {
+        preference(desired, candidate, desired <= 500)
     }
 }
 
@@ -144,8 +201,7 @@ impl FontFamily {
 /// standard four faces: Normal, Bold, Italic, BoldItalic.
 pub struct FontEntry {
     face_name: ~str,
-    priv weight: CSSFontWeight,
-    priv italic: bool,
+    priv properties: FaceProperties,
     handle: FontHandle,
     // TODO: array of OpenType features, etc.
 }
@@ -154,18 +210,73 @@ impl FontEntry {
     pub fn new(handle: FontHandle) -> FontEntry {
         FontEntry {
             face_name: handle.face_name(),
-            weight: handle.boldness(),
-            italic: handle.is_italic(),
+            properties: FaceProperties {
+                weight: handle.boldness(),
+                stretch: handle.stretchiness(),
+                italic: handle.is_italic(),
+            },
             handle: handle
         }
     }
 
     pub fn is_bold(&self) -> bool {
-        self.weight.is_bold()
+        self.properties.weight.is_bold()
     }
 
     pub fn is_italic(&self) -> bool {
-        self.italic
+        self.properties.italic
     }
+
+    /// Returns what has to be faked to draw text in `style` with this face: bold if a bold
+    /// weight is asked for and the face is lighter, and oblique if the face is upright.
+    pub fn synthesis_for_style(&self, style: &SpecifiedFontStyle) -> FontSynthesis {
+        FontSynthesis {
+            bold: style.weight.is_bold() && !self.is_bold(),
+            oblique: (style.italic || style.oblique) && !self.is_italic(),
+        }
+    }
+}
+
+#[test]
+fn test_find_best_match() {
+    use font::{FontWeight300, FontWeight400, FontWeight600, FontWeight700, FontWeight900};
+    use font::{FontStretchCondensed, FontStretchSemiCondensed, FontStretchSemiExpanded};
+    use font::FontStretchExpanded;
+    use font_context::dummy_style;
+
+    fn face(weight: CSSFontWeight, stretch: CSSFontStretch, italic: bool) -> FaceProperties {
+        FaceProperties { weight: weight, stretch: stretch, italic: italic }
+    }
+    let faces = [
+        face(FontWeight400, FontStretchNormal, false),
+        face(FontWeight700, FontStretchNormal, false),
+        face(FontWeight300, FontStretchNormal, true),
+        face(FontWeight400, FontStretchCondensed, false),
+        face(FontWeight900, FontStretchExpanded, false),
+    ];
+    let mut style = dummy_style();
+    style.weight = FontWeight400;
+    style.stretch = FontStretchNormal;
+    assert!(find_best_match(faces, &style) == Some(0));
+
+    // Heavier weights are preferred for bold, lighter ones for light.
+    style.weight = FontWeight600;
+    assert!(find_best_match(faces, &style) == Some(1));
+    style.weight = FontWeight300;
+    assert!(find_best_match(faces, &style) == Some(0));
+
+    // The slant is matched before the weight.
+    style.italic = true;
+    style.weight = FontWeight700;
+    assert!(find_best_match(faces, &style) == Some(2));
+
+    // The stretch is matched before either, preferring narrower faces for narrow stretches.
+    style.italic = false;
+    style.stretch = FontStretchSemiCondensed;
+    assert!(find_best_match(faces, &style) == Some(3));
+    style.stretch = FontStretchSemiExpanded;
+    assert!(find_best_match(faces, &style) == Some(4));
+
+    assert!(find_best_match([], &style) == None);
 }
 
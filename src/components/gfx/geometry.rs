@@ -80,22 +80,72 @@ pub fn box<T:Clone + Ord + Add<T,T> + Sub<T,T>>(x: T, y: T, w: T, h: T) -> Rect<
     Rect(Point2D(x, y), Size2D(w, h))
 }
 
+/// The number of app units in a CSS pixel.
+pub static AU_PER_PX: i32 = 60;
+
+/// A length in CSS pixels, of which there are 96 to the inch. Lengths in style sheets, and the
+/// coordinates that script and the compositor exchange, are in CSS pixels.
+#[deriving(Clone, Eq, Ord)]
+pub struct CSSPixel(float);
+
+/// A length in the pixels of the device that a page is rendered to. There are as many of them to
+/// a CSS pixel as the device pixel ratio, which zooming changes.
+#[deriving(Clone, Eq, Ord)]
+pub struct DevicePixel(float);
+
+impl CSSPixel {
+    pub fn to_device_px(self, device_pixel_ratio: float) -> DevicePixel {
+        DevicePixel(*self * device_pixel_ratio)
+    }
+}
+
+impl DevicePixel {
+    pub fn to_css_px(self, device_pixel_ratio: float) -> CSSPixel {
+        CSSPixel(*self / device_pixel_ratio)
+    }
+}
+
 impl Au {
     pub fn scale_by(self, factor: float) -> Au {
         Au(((*self as float) * factor).round() as i32)
     }
 
+    /// Converts a whole number of CSS pixels to app units.
     pub fn from_px(px: int) -> Au {
-        NumCast::from(px * 60)
+        Au(px as i32 * AU_PER_PX)
+    }
+
+    /// Converts a length in CSS pixels to app units, rounding it to the nearest app unit.
+    pub fn from_css_px(px: CSSPixel) -> Au {
+        Au((*px * (AU_PER_PX as float)).round() as i32)
+    }
+
+    pub fn to_css_px(self) -> CSSPixel {
+        CSSPixel((*self as float) / (AU_PER_PX as float))
+    }
+
+    /// Converts a length in device pixels to app units, at the given device pixel ratio.
+    pub fn from_device_px(px: DevicePixel, device_pixel_ratio: float) -> Au {
+        Au::from_css_px(px.to_css_px(device_pixel_ratio))
+    }
+
+    pub fn to_device_px(self, device_pixel_ratio: float) -> DevicePixel {
+        self.to_css_px().to_device_px(device_pixel_ratio)
     }
 
+    /// Converts a length in points, of which there are 72 to the inch, to app units.
+    pub fn from_pt(pt: float) -> Au {
+        Au::from_css_px(CSSPixel(pt_to_px(pt)))
+    }
+
+    /// Returns the number of whole CSS pixels nearest to this length.
     pub fn to_nearest_px(&self) -> int {
-        ((**self as float) / 60f).round() as int
+        ((**self as float) / (AU_PER_PX as float)).round() as int
     }
 
     pub fn to_snapped(&self) -> Au {
-        let res = **self % 60i32;
-        return if res >= 30i32 { return Au(**self - res + 60i32) }
+        let res = **self % AU_PER_PX;
+        return if res >= AU_PER_PX / 2 { return Au(**self - res + AU_PER_PX) }
                        else { return Au(**self - res) };
     }
 
@@ -108,14 +158,6 @@ impl Au {
         Rect(Point2D(z, z), Size2D(z, z))
     }
 
-    pub fn from_pt(pt: float) -> Au {
-        from_px(pt_to_px(pt) as int)
-    }
-
-    pub fn from_frac_px(px: float) -> Au {
-        Au((px * 60f) as i32)
-    }
-
     pub fn min(x: Au, y: Au) -> Au { if *x < *y { x } else { y } }
     pub fn max(x: Au, y: Au) -> Au { if *x > *y { x } else { y } }
 }
@@ -143,23 +185,23 @@ pub fn zero_size() -> Size2D<Au> {
     Size2D(Au(0), Au(0))
 }
 
-pub fn from_frac_px(px: float) -> Au {
-    Au((px * 60f) as i32)
-}
-
-pub fn from_px(px: int) -> Au {
-    NumCast::from(px * 60)
+#[test]
+fn test_css_px_round_trip() {
+    assert!(Au::from_css_px(CSSPixel(1.5)) == Au(90));
+    assert!(Au(90).to_css_px() == CSSPixel(1.5));
+    assert!(Au::from_px(2) == Au::from_css_px(CSSPixel(2.0)));
 }
 
-pub fn to_px(au: Au) -> int {
-    (*au / 60) as int
+#[test]
+fn test_device_px() {
+    assert!(Au::from_px(10).to_device_px(2.0) == DevicePixel(20.0));
+    assert!(Au::from_device_px(DevicePixel(20.0), 2.0) == Au::from_px(10));
+    assert!(DevicePixel(3.0).to_css_px(1.5) == CSSPixel(2.0));
 }
 
-pub fn to_frac_px(au: Au) -> float {
-    (*au as float) / 60f
-}
-
-// assumes 72 points per inch, and 96 px per inch
-pub fn from_pt(pt: float) -> Au {
-    from_px((pt / 72f * 96f) as int)
+#[test]
+fn test_from_pt_keeps_fractions() {
+    // 9pt is 12px exactly; 10pt is 13.33px, which whole pixels would truncate to 13.
+    assert!(Au::from_pt(9.0) == Au::from_px(12));
+    assert!(Au::from_pt(10.0) == Au(800));
 }
@@ -0,0 +1,290 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! An atlas of rasterized glyphs, shared by all the fonts of a font context, so that text that is
+//! painted again, or that repeats glyphs, doesn't rasterize them again.
+//!
+//! Glyphs are packed into rows of a single coverage bitmap. When it fills up, every glyph is
+//! evicted and packing starts again, which is cheap and keeps glyphs that are still in use from
+//! being rasterized more than once per fill.
+
+use text::glyph::GlyphIndex;
+
+use std::hashmap::HashMap;
+use std::vec;
+use servo_util::time::{MemoryReportMsg, ProfilerChan};
+
+/// The width and height of the atlas, in pixels.
+static ATLAS_SIZE: uint = 1024;
+
+/// How many positions a glyph is rasterized at within a pixel, horizontally, so that glyphs keep
+/// their fractional advances without being rasterized at every position.
+pub static SUBPIXEL_POSITIONS: uint = 4;
+
+/// A glyph rasterized at a size and a subpixel offset.
+pub struct RasterizedGlyph {
+    width: uint,
+    height: uint,
+    /// The distance from the pen position to the left edge of the bitmap, in pixels.
+    left: int,
+    /// The distance from the baseline up to the top edge of the bitmap, in pixels.
+    top: int,
+    /// The coverage of each pixel, in rows from the top.
+    coverage: ~[u8],
+}
+
+/// What a glyph is rasterized for.
+#[deriving(Clone, Eq, IterBytes)]
+pub struct GlyphKey {
+    /// The identifier of the face the glyph belongs to.
+    font: ~str,
+    /// The size of the font in device pixels, in 26.6 fixed point.
+    size: u32,
+    glyph: GlyphIndex,
+    /// The horizontal offset of the glyph within the pixel it's drawn at, in
+    /// `1 / SUBPIXEL_POSITIONS`ths of a pixel.
+    subpixel_offset: u8,
+}
+
+/// Where a glyph is in the atlas.
+#[deriving(Clone)]
+pub struct AtlasGlyph {
+    x: uint,
+    y: uint,
+    width: uint,
+    height: uint,
+    left: int,
+    top: int,
+}
+
+/// A row of glyphs in the atlas.
+struct Shelf {
+    y: uint,
+    height: uint,
+    /// Where the next glyph on the shelf goes.
+    x: uint,
+}
+
+/// How well the atlas is working, for the profiler.
+#[deriving(Clone, Eq)]
+pub struct GlyphAtlasStats {
+    hits: uint,
+    misses: uint,
+    /// How many glyphs have been evicted to make room for others.
+    evictions: uint,
+    /// How many glyphs are in the atlas now.
+    glyphs: uint,
+    /// The bytes of the atlas that glyphs are packed into so far.
+    used_bytes: uint,
+}
+
+pub struct GlyphAtlas {
+    size: uint,
+    coverage: ~[u8],
+    shelves: ~[Shelf],
+    glyphs: HashMap<GlyphKey, AtlasGlyph>,
+    stats: GlyphAtlasStats,
+}
+
+impl GlyphAtlas {
+    pub fn new() -> GlyphAtlas {
+        GlyphAtlas::with_size(ATLAS_SIZE)
+    }
+
+    fn with_size(size: uint) -> GlyphAtlas {
+        GlyphAtlas {
+            size: size,
+            coverage: vec::from_elem(size * size, 0u8),
+            shelves: ~[],
+            glyphs: HashMap::new(),
+            stats: GlyphAtlasStats {
+                hits: 0,
+                misses: 0,
+                evictions: 0,
+                glyphs: 0,
+                used_bytes: 0,
+            },
+        }
+    }
+
+    /// Returns where a glyph is in the atlas, rasterizing it with `rasterize` and adding it if it
+    /// isn't there yet. Returns `None` if the glyph can't be rasterized, or is too big for the
+    /// atlas.
+    pub fn find_or_rasterize(&mut self,
+                             key: &GlyphKey,
+                             rasterize: &fn() -> Option<RasterizedGlyph>)
+                             -> Option<AtlasGlyph> {
+        match self.glyphs.find(key) {
+            Some(glyph) => {
+                self.stats.hits += 1;
+                return Some(*glyph)
+            }
+            None => self.stats.misses += 1,
+        }
+
+        let rasterized = match rasterize() {
+            Some(rasterized) => rasterized,
+            None => return None,
+        };
+        if rasterized.width > self.size || rasterized.height > self.size {
+            return None
+        }
+        let (x, y) = match self.allocate(rasterized.width, rasterized.height) {
+            Some(position) => position,
+            None => {
+                self.evict_all();
+                self.allocate(rasterized.width, rasterized.height).unwrap()
+            }
+        };
+
+        for row in range(0, rasterized.height) {
+            let source = row * rasterized.width;
+            let destination = (y + row) * self.size + x;
+            for column in range(0, rasterized.width) {
+                self.coverage[destination + column] = rasterized.coverage[source + column]
+            }
+        }
+        let glyph = AtlasGlyph {
+            x: x,
+            y: y,
+            width: rasterized.width,
+            height: rasterized.height,
+            left: rasterized.left,
+            top: rasterized.top,
+        };
+        self.glyphs.insert(key.clone(), glyph);
+        self.stats.glyphs += 1;
+        Some(glyph)
+    }
+
+    /// Returns the coverage of a row of a glyph in the atlas.
+    pub fn row<'a>(&'a self, glyph: &AtlasGlyph, row: uint) -> &'a [u8] {
+        let start = (glyph.y + row) * self.size + glyph.x;
+        self.coverage.slice(start, start + glyph.width)
+    }
+
+    /// Finds room for a glyph on the shelf it wastes the least height on, or on a new shelf.
+    fn allocate(&mut self, width: uint, height: uint) -> Option<(uint, uint)> {
+        let mut best = None;
+        for (i, shelf) in self.shelves.iter().enumerate() {
+            if shelf.height >= height && self.size - shelf.x >= width {
+                match best {
+                    Some(best_index) if self.shelves[best_index].height <= shelf.height => {}
+                    _ => best = Some(i),
+                }
+            }
+        }
+
+        let index = match best {
+            Some(index) => index,
+            None => {
+                let y = match self.shelves.last_opt() {
+                    Some(shelf) => shelf.y + shelf.height,
+                    None => 0,
+                };
+                if self.size - y < height {
+                    return None
+                }
+                self.shelves.push(Shelf {
+                    y: y,
+                    height: height,
+                    x: 0,
+                });
+                self.stats.used_bytes = (y + height) * self.size;
+                self.shelves.len() - 1
+            }
+        };
+        let shelf = &mut self.shelves[index];
+        let position = (shelf.x, shelf.y);
+        shelf.x += width;
+        Some(position)
+    }
+
+    /// Removes every glyph from the atlas.
+    pub fn evict_all(&mut self) {
+        debug!("glyph atlas: evicting %u glyphs", self.glyphs.len());
+        self.stats.evictions += self.glyphs.len();
+        self.stats.glyphs = 0;
+        self.stats.used_bytes = 0;
+        self.glyphs.clear();
+        self.shelves = ~[];
+    }
+
+    pub fn stats(&self) -> GlyphAtlasStats {
+        self.stats
+    }
+
+    /// Sends the size of the atlas and how well it's working to the profiler.
+    pub fn report(&self, profiler_chan: &ProfilerChan) {
+        let report = |name: &str, value: uint| {
+            profiler_chan.send(MemoryReportMsg(fmt!("glyph-atlas/%s", name), value))
+        };
+        report("capacity-bytes", self.coverage.len());
+        report("used-bytes", self.stats.used_bytes);
+        report("glyphs", self.stats.glyphs);
+        report("hits", self.stats.hits);
+        report("misses", self.stats.misses);
+        report("evictions", self.stats.evictions);
+    }
+}
+
+#[cfg(test)]
+fn key(glyph: GlyphIndex) -> GlyphKey {
+    GlyphKey {
+        font: ~"Test",
+        size: 16 * 64,
+        glyph: glyph,
+        subpixel_offset: 0,
+    }
+}
+
+#[cfg(test)]
+fn square(size: uint, value: u8) -> Option<RasterizedGlyph> {
+    Some(RasterizedGlyph {
+        width: size,
+        height: size,
+        left: 0,
+        top: size as int,
+        coverage: vec::from_elem(size * size, value),
+    })
+}
+
+#[test]
+fn test_glyphs_are_rasterized_once() {
+    let mut atlas = GlyphAtlas::with_size(64);
+    let mut rasterized = 0;
+    for _ in range(0, 3) {
+        let glyph = atlas.find_or_rasterize(&key(1), || { rasterized += 1; square(8, 200) });
+        let glyph = glyph.unwrap();
+        assert!(atlas.row(&glyph, 7).to_owned() == vec::from_elem(8, 200u8));
+    }
+    assert!(rasterized == 1);
+    let stats = atlas.stats();
+    assert!(stats.hits == 2 && stats.misses == 1 && stats.glyphs == 1);
+}
+
+#[test]
+fn test_glyphs_share_shelves() {
+    let mut atlas = GlyphAtlas::with_size(64);
+    let a = atlas.find_or_rasterize(&key(1), || square(8, 1)).unwrap();
+    let b = atlas.find_or_rasterize(&key(2), || square(6, 2)).unwrap();
+    let c = atlas.find_or_rasterize(&key(3), || square(16, 3)).unwrap();
+    assert!((a.x, a.y) == (0, 0));
+    assert!((b.x, b.y) == (8, 0));
+    assert!((c.x, c.y) == (0, 8));
+    assert!(atlas.row(&b, 0).to_owned() == vec::from_elem(6, 2u8));
+}
+
+#[test]
+fn test_full_atlas_evicts_glyphs() {
+    let mut atlas = GlyphAtlas::with_size(32);
+    for glyph in range(0, 4u32) {
+        assert!(atlas.find_or_rasterize(&key(glyph), || square(16, 1)).is_some());
+    }
+    assert!(atlas.stats().evictions == 0);
+    assert!(atlas.find_or_rasterize(&key(4), || square(16, 1)).is_some());
+    let stats = atlas.stats();
+    assert!(stats.evictions == 4 && stats.glyphs == 1);
+    assert!(atlas.find_or_rasterize(&key(5), || square(33, 1)).is_none());
+}
@@ -8,8 +8,11 @@
 use azure::azure_hl::{BackendType, CairoBackend, CoreGraphicsBackend};
 use azure::azure_hl::{CoreGraphicsAcceleratedBackend, Direct2DBackend, SkiaBackend};
 
+use std::ascii::StrAsciiExt;
 use std::float;
+use std::from_str::FromStr;
 use std::result;
+use std::rt::io::net::ip::SocketAddr;
 use std::uint;
 
 #[deriving(Clone)]
@@ -19,8 +22,111 @@ pub struct Opts {
     n_render_threads: uint,
     tile_size: uint,
     profiler_period: Option<float>,
+    /// The file to write the profiler's measurements to, in machine-readable form, on exit.
+    profiler_report_file: Option<~str>,
+    /// The file to write the timelines of the most recent frames to on exit, as Chrome trace
+    /// events.
+    frame_trace_file: Option<~str>,
     exit_after_load: bool,
     output_file: Option<~str>,
+    generic_fonts: ~[GenericFontPreference],
+    hyphenation_patterns: ~[HyphenationPatternFile],
+    /// The word list to check the spelling of editable text against, instead of the system's.
+    spellcheck_dictionary: Option<~str>,
+    autoplay_policy: AutoplayPolicy,
+    /// The files of the style sheets to apply to every page at the user agent origin, after the
+    /// default style of the browser, such as to style widgets of the embedder, given on the
+    /// command line as `-g`.
+    user_agent_stylesheets: ~[~str],
+    /// The files of the style sheets to apply to every page at the user origin, after the
+    /// default style of the browser, so that they can override it and, with `!important`, the
+    /// style of the page.
+    user_stylesheets: ~[~str],
+    /// Whether viewport units are relative to the visual viewport, which pinch zoom shrinks, so
+    /// that they are resolved again as the user zooms, rather than to the layout viewport.
+    viewport_units_follow_zoom: bool,
+    /// How long a task of a script task may run, in seconds, before the user is asked whether to
+    /// stop its script, or 0 to let scripts run for as long as they like.
+    slow_script_timeout: uint,
+    /// The most memory that the decoded images of a document may take, in bytes, given on the
+    /// command line in megabytes as `-i`. The images that were used least recently are evicted to
+    /// keep within it.
+    max_document_image_bytes: Option<uint>,
+    /// The file to save the markup of the page to, as its scripts have left it once it has
+    /// loaded, given on the command line as `-e`.
+    save_page_file: Option<~str>,
+    /// The addresses of the origins that speak HTTP/2 in cleartext, to load their resources
+    /// over it, given on the command line as `-2 address:port`.
+    http2_addresses: ~[SocketAddr],
+}
+
+/// When media may start playing on its own, given on the command line as `-a allow`,
+/// `-a activation` or `-a block`.
+#[deriving(Clone, Eq)]
+pub enum AutoplayPolicy {
+    /// Media may always play.
+    AutoplayAllowed,
+    /// Muted media may always play, and other media once the user has interacted with the page.
+    AutoplayAfterActivation,
+    /// Media never starts on its own, and plays only once the user has interacted with the page.
+    AutoplayBlocked,
+}
+
+/// A user-specified mapping from a CSS generic font family (`serif`, `monospace`, ...) to a
+/// concrete family, optionally restricted to content in one language. Given on the command line as
+/// `-f generic=Family` or `-f generic:lang=Family`.
+#[deriving(Clone)]
+pub struct GenericFontPreference {
+    generic: ~str,
+    lang: Option<~str>,
+    family: ~str,
+}
+
+impl GenericFontPreference {
+    pub fn from_str(pref: &str) -> Option<GenericFontPreference> {
+        let (key, family) = match pref.find('=') {
+            Some(i) => (pref.slice_to(i).trim(), pref.slice_from(i + 1).trim()),
+            None => return None,
+        };
+        let (generic, lang) = match key.find(':') {
+            Some(i) => (key.slice_to(i).trim(), Some(key.slice_from(i + 1).trim().to_owned())),
+            None => (key, None),
+        };
+        if generic.is_empty() || family.is_empty() || lang == Some(~"") {
+            return None
+        }
+
+        Some(GenericFontPreference {
+            generic: generic.to_ascii_lower(),
+            lang: lang.map_move(|lang| lang.to_ascii_lower()),
+            family: family.to_owned(),
+        })
+    }
+}
+
+/// A file of TeX hyphenation patterns to use for content in a language, given on the command line
+/// as `-y lang=path`.
+#[deriving(Clone)]
+pub struct HyphenationPatternFile {
+    lang: ~str,
+    path: ~str,
+}
+
+impl HyphenationPatternFile {
+    pub fn from_str(pref: &str) -> Option<HyphenationPatternFile> {
+        let (lang, path) = match pref.find('=') {
+            Some(i) => (pref.slice_to(i).trim(), pref.slice_from(i + 1).trim()),
+            None => return None,
+        };
+        if lang.is_empty() || path.is_empty() {
+            return None
+        }
+
+        Some(HyphenationPatternFile {
+            lang: lang.to_ascii_lower(),
+            path: path.to_owned(),
+        })
+    }
 }
 
 pub fn from_cmdline_args(args: &[~str]) -> Opts {
@@ -34,7 +140,20 @@ pub fn from_cmdline_args(args: &[~str]) -> Opts {
         getopts::optopt("s"),  // size of tiles
         getopts::optopt("t"),  // threads to render with
         getopts::optflagopt("p"),  // profiler flag and output interval
+        getopts::optopt("m"),  // machine-readable profiler report file
+        getopts::optopt("j"),  // frame trace file
         getopts::optflag("x"), // exit after load flag
+        getopts::optmulti("f"), // generic font family mappings
+        getopts::optmulti("y"), // hyphenation pattern files
+        getopts::optopt("d"),  // spellcheck dictionary
+        getopts::optopt("a"),  // autoplay policy
+        getopts::optmulti("g"), // user agent style sheets
+        getopts::optmulti("u"), // user style sheets
+        getopts::optflag("z"), // viewport units follow pinch zoom
+        getopts::optopt("w"),  // slow script timeout
+        getopts::optopt("i"),  // image memory limit per document
+        getopts::optopt("e"),  // save page file
+        getopts::optmulti("2"), // origins that speak HTTP/2
     ];
 
     let opt_match = match getopts::getopts(args, opts) {
@@ -82,17 +201,91 @@ pub fn from_cmdline_args(args: &[~str]) -> Opts {
         float::from_str(*period).unwrap()
     };
 
+    let profiler_report_file = getopts::opt_maybe_str(&opt_match, "m");
+
+    let frame_trace_file = getopts::opt_maybe_str(&opt_match, "j");
+
     let exit_after_load = getopts::opt_present(&opt_match, "x");
 
     let output_file = getopts::opt_maybe_str(&opt_match, "o");
 
+    let generic_fonts = do getopts::opt_strs(&opt_match, "f").map |pref| {
+        match GenericFontPreference::from_str(*pref) {
+            Some(pref) => pref,
+            None => fail!(fmt!("invalid generic font family mapping `%s`, expected \
+                                `generic[:lang]=family`", *pref)),
+        }
+    };
+
+    let hyphenation_patterns = do getopts::opt_strs(&opt_match, "y").map |pref| {
+        match HyphenationPatternFile::from_str(*pref) {
+            Some(pref) => pref,
+            None => fail!(fmt!("invalid hyphenation pattern file `%s`, expected `lang=path`",
+                               *pref)),
+        }
+    };
+
+    let spellcheck_dictionary = getopts::opt_maybe_str(&opt_match, "d");
+
+    let autoplay_policy = match getopts::opt_maybe_str(&opt_match, "a") {
+        Some(policy_str) => {
+            if policy_str == ~"allow" {
+                AutoplayAllowed
+            } else if policy_str == ~"activation" {
+                AutoplayAfterActivation
+            } else if policy_str == ~"block" {
+                AutoplayBlocked
+            } else {
+                fail!(~"unknown autoplay policy, expected `allow`, `activation` or `block`")
+            }
+        }
+        None => AutoplayAfterActivation,
+    };
+
+    let user_agent_stylesheets = getopts::opt_strs(&opt_match, "g");
+
+    let user_stylesheets = getopts::opt_strs(&opt_match, "u");
+
+    let viewport_units_follow_zoom = getopts::opt_present(&opt_match, "z");
+
+    let slow_script_timeout: uint = match getopts::opt_maybe_str(&opt_match, "w") {
+        Some(timeout_str) => uint::from_str(timeout_str).unwrap(),
+        None => 10,
+    };
+
+    let max_document_image_bytes = do getopts::opt_maybe_str(&opt_match, "i").map |megabytes| {
+        uint::from_str(*megabytes).unwrap() * 1024 * 1024
+    };
+
+    let save_page_file = getopts::opt_maybe_str(&opt_match, "e");
+
+    let http2_addresses = do getopts::opt_strs(&opt_match, "2").map |address| {
+        match FromStr::from_str(*address) {
+            Some(address) => address,
+            None => fail!(fmt!("invalid HTTP/2 origin `%s`, expected `address:port`", *address)),
+        }
+    };
+
     Opts {
         urls: urls,
         render_backend: render_backend,
         n_render_threads: n_render_threads,
         tile_size: tile_size,
         profiler_period: profiler_period,
+        profiler_report_file: profiler_report_file,
+        frame_trace_file: frame_trace_file,
         exit_after_load: exit_after_load,
         output_file: output_file,
+        generic_fonts: generic_fonts,
+        hyphenation_patterns: hyphenation_patterns,
+        spellcheck_dictionary: spellcheck_dictionary,
+        autoplay_policy: autoplay_policy,
+        user_agent_stylesheets: user_agent_stylesheets,
+        user_stylesheets: user_stylesheets,
+        viewport_units_follow_zoom: viewport_units_follow_zoom,
+        slow_script_timeout: slow_script_timeout,
+        max_document_image_bytes: max_document_image_bytes,
+        save_page_file: save_page_file,
+        http2_addresses: http2_addresses,
     }
 }
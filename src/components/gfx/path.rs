@@ -0,0 +1,800 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Vector paths, as described by SVG path data (SVG 1.1 § 8.3), and their flattening into the
+//! polygons that path display items paint.
+//!
+//! Path data is normalized as it is parsed: relative coordinates are made absolute, the shorthand
+//! commands are expanded, and elliptical arcs are approximated with cubic Bézier curves, so that
+//! only moves, lines, curves and closes are left.
+//!
+//! FIXME: There are no SVG elements or SVG layout yet, so nothing parses the `d` attribute of a
+//! `<path>` into a path display item.
+
+use extra::sort;
+use geom::point::Point2D;
+use std::f32;
+use std::uint;
+use std::util;
+use std::vec;
+
+/// A normalized path data command. All points are absolute.
+#[deriving(Clone)]
+pub enum PathCommand {
+    MoveTo(Point2D<f32>),
+    LineTo(Point2D<f32>),
+    /// A quadratic Bézier curve with the given control point and end point.
+    QuadTo(Point2D<f32>, Point2D<f32>),
+    /// A cubic Bézier curve with the given control points and end point.
+    CubicTo(Point2D<f32>, Point2D<f32>, Point2D<f32>),
+    ClosePath,
+}
+
+/// The values of the `fill-rule` property, which decide which parts of a path that crosses
+/// itself are inside it.
+#[deriving(Clone, Eq)]
+pub enum FillRule {
+    /// A point is inside if the path winds around it at all.
+    NonZero,
+    /// A point is inside if a ray from it crosses the path an odd number of times.
+    EvenOdd,
+}
+
+/// A subpath flattened into a polyline.
+#[deriving(Clone)]
+pub struct Subpath {
+    points: ~[Point2D<f32>],
+    /// Whether the subpath ends with a close, which strokes a segment back to its start.
+    closed: bool,
+}
+
+/// The current state of a path as it is built from path data.
+struct PathBuilder {
+    commands: ~[PathCommand],
+    current: Point2D<f32>,
+    subpath_start: Point2D<f32>,
+    /// The second control point of the last segment, if it was a cubic curve, which the `S`
+    /// command reflects.
+    last_cubic_control: Option<Point2D<f32>>,
+    /// The control point of the last segment, if it was a quadratic curve, which the `T` command
+    /// reflects.
+    last_quad_control: Option<Point2D<f32>>,
+}
+
+impl PathBuilder {
+    fn new() -> PathBuilder {
+        PathBuilder {
+            commands: ~[],
+            current: Point2D(0f32, 0f32),
+            subpath_start: Point2D(0f32, 0f32),
+            last_cubic_control: None,
+            last_quad_control: None,
+        }
+    }
+
+    fn move_to(&mut self, point: Point2D<f32>) {
+        self.commands.push(MoveTo(point));
+        self.current = point;
+        self.subpath_start = point;
+        self.last_cubic_control = None;
+        self.last_quad_control = None;
+    }
+
+    fn line_to(&mut self, point: Point2D<f32>) {
+        self.commands.push(LineTo(point));
+        self.current = point;
+        self.last_cubic_control = None;
+        self.last_quad_control = None;
+    }
+
+    fn quad_to(&mut self, control: Point2D<f32>, point: Point2D<f32>) {
+        self.commands.push(QuadTo(control, point));
+        self.current = point;
+        self.last_cubic_control = None;
+        self.last_quad_control = Some(control);
+    }
+
+    fn cubic_to(&mut self, control1: Point2D<f32>, control2: Point2D<f32>, point: Point2D<f32>) {
+        self.commands.push(CubicTo(control1, control2, point));
+        self.current = point;
+        self.last_cubic_control = Some(control2);
+        self.last_quad_control = None;
+    }
+
+    fn close(&mut self) {
+        self.commands.push(ClosePath);
+        self.current = self.subpath_start;
+        self.last_cubic_control = None;
+        self.last_quad_control = None;
+    }
+
+    /// Returns the reflection of `control` about the current point, or the current point itself
+    /// if there is no control point to reflect.
+    fn reflect(&self, control: Option<Point2D<f32>>) -> Point2D<f32> {
+        match control {
+            Some(control) => Point2D(self.current.x * 2.0 - control.x,
+                                     self.current.y * 2.0 - control.y),
+            None => self.current,
+        }
+    }
+
+    /// Adds an elliptical arc to `point`, approximated by a cubic curve for every quarter turn
+    /// or part of one, following the conversion to center parameterization in SVG 1.1 § F.6.5.
+    fn arc_to(&mut self,
+              radii: Point2D<f32>,
+              x_axis_rotation: f32,
+              large_arc: bool,
+              sweep: bool,
+              point: Point2D<f32>) {
+        let start = self.current;
+        if start.x == point.x && start.y == point.y {
+            return
+        }
+        let (mut rx, mut ry) = (radii.x.abs(), radii.y.abs());
+        if rx == 0.0 || ry == 0.0 {
+            return self.line_to(point)
+        }
+
+        let phi = x_axis_rotation * f32::consts::pi / 180.0;
+        let (sin_phi, cos_phi) = (phi.sin(), phi.cos());
+        let half_dx = (start.x - point.x) / 2.0;
+        let half_dy = (start.y - point.y) / 2.0;
+        let x1 = cos_phi * half_dx + sin_phi * half_dy;
+        let y1 = -sin_phi * half_dx + cos_phi * half_dy;
+
+        // Radii too small to reach the end point are scaled up until they just do.
+        let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if lambda > 1.0 {
+            rx = rx * lambda.sqrt();
+            ry = ry * lambda.sqrt();
+        }
+
+        let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+        let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+        let mut coefficient = (numerator / denominator).max(&0.0).sqrt();
+        if large_arc == sweep {
+            coefficient = -coefficient
+        }
+        let center_x1 = coefficient * rx * y1 / ry;
+        let center_y1 = -coefficient * ry * x1 / rx;
+        let center = Point2D(cos_phi * center_x1 - sin_phi * center_y1 + (start.x + point.x) / 2.0,
+                             sin_phi * center_x1 + cos_phi * center_y1 + (start.y + point.y) / 2.0);
+
+        fn angle_between(ux: f32, uy: f32, vx: f32, vy: f32) -> f32 {
+            (ux * vy - uy * vx).atan2(&(ux * vx + uy * vy))
+        }
+        let start_angle = angle_between(1.0, 0.0, (x1 - center_x1) / rx, (y1 - center_y1) / ry);
+        let mut sweep_angle = angle_between((x1 - center_x1) / rx,
+                                            (y1 - center_y1) / ry,
+                                            (-x1 - center_x1) / rx,
+                                            (-y1 - center_y1) / ry);
+        if !sweep && sweep_angle > 0.0 {
+            sweep_angle = sweep_angle - 2.0 * f32::consts::pi
+        } else if sweep && sweep_angle < 0.0 {
+            sweep_angle = sweep_angle + 2.0 * f32::consts::pi
+        }
+
+        // Maps a point on the unit circle onto the ellipse.
+        let to_ellipse = |x: f32, y: f32| -> Point2D<f32> {
+            Point2D(center.x + rx * cos_phi * x - ry * sin_phi * y,
+                    center.y + rx * sin_phi * x + ry * cos_phi * y)
+        };
+
+        let segments = (sweep_angle.abs() / (f32::consts::pi / 2.0) - 0.001).ceil().max(&1.0);
+        let segment_angle = sweep_angle / segments;
+        let handle = 4.0 / 3.0 * (segment_angle / 4.0).tan();
+        for i in range(0, segments as uint) {
+            let angle1 = start_angle + segment_angle * (i as f32);
+            let angle2 = angle1 + segment_angle;
+            let (sin1, cos1) = (angle1.sin(), angle1.cos());
+            let (sin2, cos2) = (angle2.sin(), angle2.cos());
+            let control1 = to_ellipse(cos1 - handle * sin1, sin1 + handle * cos1);
+            let control2 = to_ellipse(cos2 + handle * sin2, sin2 - handle * cos2);
+            let end = if i + 1 == segments as uint { point } else { to_ellipse(cos2, sin2) };
+            self.cubic_to(control1, control2, end);
+        }
+    }
+}
+
+/// A reader of path data, which follows the grammar of SVG 1.1 § 8.3.9.
+struct PathDataParser<'self> {
+    data: &'self [u8],
+    position: uint,
+}
+
+impl<'self> PathDataParser<'self> {
+    fn at_end(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        if self.at_end() { None } else { Some(self.data[self.position]) }
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.peek() {
+                Some(b) if b == ' ' as u8 || b == '\t' as u8 || b == '\n' as u8 ||
+                           b == '\r' as u8 || b == 0x0C => self.position += 1,
+                _ => return,
+            }
+        }
+    }
+
+    /// Skips the whitespace, and at most one comma, that may separate two arguments.
+    fn skip_comma_whitespace(&mut self) {
+        self.skip_whitespace();
+        if self.peek() == Some(',' as u8) {
+            self.position += 1;
+            self.skip_whitespace();
+        }
+    }
+
+    fn skip_digits(&mut self) -> uint {
+        let start = self.position;
+        loop {
+            match self.peek() {
+                Some(b) if b >= '0' as u8 && b <= '9' as u8 => self.position += 1,
+                _ => return self.position - start,
+            }
+        }
+    }
+
+    /// Parses a number, and the separator after it. A number ends wherever the next character
+    /// can't continue it, so "1-2" and "0.5.5" are each two numbers.
+    fn parse_number(&mut self) -> Option<f32> {
+        let start = self.position;
+        match self.peek() {
+            Some(b) if b == '+' as u8 || b == '-' as u8 => self.position += 1,
+            _ => {}
+        }
+        let mut digits = self.skip_digits();
+        if self.peek() == Some('.' as u8) {
+            self.position += 1;
+            digits += self.skip_digits();
+        }
+        if digits == 0 {
+            self.position = start;
+            return None
+        }
+        match self.peek() {
+            Some(b) if b == 'e' as u8 || b == 'E' as u8 => {
+                let mantissa_end = self.position;
+                self.position += 1;
+                match self.peek() {
+                    Some(b) if b == '+' as u8 || b == '-' as u8 => self.position += 1,
+                    _ => {}
+                }
+                if self.skip_digits() == 0 {
+                    // Not an exponent after all.
+                    self.position = mantissa_end;
+                }
+            }
+            _ => {}
+        }
+
+        let value = number_value(self.data.slice(start, self.position));
+        self.skip_comma_whitespace();
+        Some(value)
+    }
+
+    /// Parses the large-arc or sweep flag of an arc, which is a single digit that needn't be
+    /// separated from what follows it.
+    fn parse_flag(&mut self) -> Option<bool> {
+        let flag = match self.peek() {
+            Some(b) if b == '0' as u8 => false,
+            Some(b) if b == '1' as u8 => true,
+            _ => return None,
+        };
+        self.position += 1;
+        self.skip_comma_whitespace();
+        Some(flag)
+    }
+
+    /// Parses the given number of points, all relative to `relative_to` if it is given.
+    fn parse_points(&mut self, count: uint, relative_to: Option<Point2D<f32>>)
+                    -> Option<~[Point2D<f32>]> {
+        let mut points = ~[];
+        for _ in range(0, count) {
+            match self.parse_point(relative_to) {
+                Some(point) => points.push(point),
+                None => return None,
+            }
+        }
+        Some(points)
+    }
+
+    fn parse_point(&mut self, relative_to: Option<Point2D<f32>>) -> Option<Point2D<f32>> {
+        let x = match self.parse_number() {
+            Some(x) => x,
+            None => return None,
+        };
+        let y = match self.parse_number() {
+            Some(y) => y,
+            None => return None,
+        };
+        match relative_to {
+            Some(origin) => Some(Point2D(origin.x + x, origin.y + y)),
+            None => Some(Point2D(x, y)),
+        }
+    }
+
+    /// Parses the arguments of one segment of the given command, and adds the segment to the
+    /// path. Returns false, adding nothing, if the arguments are invalid.
+    fn parse_segment(&mut self, command: u8, path: &mut PathBuilder) -> bool {
+        let relative = command >= 'a' as u8;
+        let origin = if relative { Some(path.current) } else { None };
+        let absolute_command = if relative { command - ('a' as u8 - 'A' as u8) } else { command };
+        match absolute_command as char {
+            'M' => match self.parse_point(origin) {
+                Some(point) => path.move_to(point),
+                None => return false,
+            },
+            'L' => match self.parse_point(origin) {
+                Some(point) => path.line_to(point),
+                None => return false,
+            },
+            'H' => match self.parse_number() {
+                Some(x) => {
+                    let x = if relative { path.current.x + x } else { x };
+                    path.line_to(Point2D(x, path.current.y))
+                }
+                None => return false,
+            },
+            'V' => match self.parse_number() {
+                Some(y) => {
+                    let y = if relative { path.current.y + y } else { y };
+                    path.line_to(Point2D(path.current.x, y))
+                }
+                None => return false,
+            },
+            'C' => match self.parse_points(3, origin) {
+                Some(points) => path.cubic_to(points[0], points[1], points[2]),
+                None => return false,
+            },
+            'S' => match self.parse_points(2, origin) {
+                Some(points) => {
+                    let control1 = path.reflect(path.last_cubic_control);
+                    path.cubic_to(control1, points[0], points[1])
+                }
+                None => return false,
+            },
+            'Q' => match self.parse_points(2, origin) {
+                Some(points) => path.quad_to(points[0], points[1]),
+                None => return false,
+            },
+            'T' => match self.parse_point(origin) {
+                Some(point) => {
+                    let control = path.reflect(path.last_quad_control);
+                    path.quad_to(control, point)
+                }
+                None => return false,
+            },
+            'A' => {
+                // The radii are never relative.
+                let radii = match self.parse_point(None) {
+                    Some(radii) => radii,
+                    None => return false,
+                };
+                let rotation = match self.parse_number() {
+                    Some(rotation) => rotation,
+                    None => return false,
+                };
+                let large_arc = match self.parse_flag() {
+                    Some(large_arc) => large_arc,
+                    None => return false,
+                };
+                let sweep = match self.parse_flag() {
+                    Some(sweep) => sweep,
+                    None => return false,
+                };
+                match self.parse_point(origin) {
+                    Some(point) => path.arc_to(radii, rotation, large_arc, sweep, point),
+                    None => return false,
+                }
+            }
+            'Z' => path.close(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Returns the value of a number that `PathDataParser::parse_number` has found.
+fn number_value(number: &[u8]) -> f32 {
+    let digit = |b: u8| (b - '0' as u8) as f64;
+    let mut i = 0;
+    let negative = number[0] == '-' as u8;
+    if number[0] == '-' as u8 || number[0] == '+' as u8 {
+        i += 1
+    }
+
+    let mut value = 0f64;
+    while i < number.len() && number[i] != '.' as u8 && number[i] != 'e' as u8 &&
+            number[i] != 'E' as u8 {
+        value = value * 10.0 + digit(number[i]);
+        i += 1;
+    }
+    if i < number.len() && number[i] == '.' as u8 {
+        i += 1;
+        let mut scale = 0.1f64;
+        while i < number.len() && number[i] != 'e' as u8 && number[i] != 'E' as u8 {
+            value = value + digit(number[i]) * scale;
+            scale = scale / 10.0;
+            i += 1;
+        }
+    }
+    if i < number.len() {
+        // The exponent.
+        i += 1;
+        let negative_exponent = number[i] == '-' as u8;
+        if number[i] == '-' as u8 || number[i] == '+' as u8 {
+            i += 1
+        }
+        let mut exponent = 0;
+        while i < number.len() {
+            exponent = uint::min(exponent * 10 + digit(number[i]) as uint, 400);
+            i += 1;
+        }
+        for _ in range(0, exponent) {
+            value = if negative_exponent { value / 10.0 } else { value * 10.0 }
+        }
+    }
+    (if negative { -value } else { value }) as f32
+}
+
+fn is_command(b: u8) -> bool {
+    match b as char {
+        'M' | 'm' | 'L' | 'l' | 'H' | 'h' | 'V' | 'v' | 'C' | 'c' | 'S' | 's' | 'Q' | 'q' |
+        'T' | 't' | 'A' | 'a' | 'Z' | 'z' => true,
+        _ => false,
+    }
+}
+
+/// Parses SVG path data into normalized commands. As SVG requires, a path with an error in it is
+/// rendered up to the last segment before the error.
+pub fn parse_path_data(data: &str) -> ~[PathCommand] {
+    let mut parser = PathDataParser {
+        data: data.as_bytes(),
+        position: 0,
+    };
+    let mut path = PathBuilder::new();
+    let mut last_command: Option<u8> = None;
+
+    parser.skip_whitespace();
+    while !parser.at_end() {
+        let next = parser.peek().unwrap();
+        let command = if is_command(next) {
+            parser.position += 1;
+            parser.skip_whitespace();
+            next
+        } else {
+            // Arguments without a command repeat the last command, except that coordinates
+            // after a move are lines.
+            match last_command {
+                Some(c) if c == 'M' as u8 => 'L' as u8,
+                Some(c) if c == 'm' as u8 => 'l' as u8,
+                Some(c) if c != 'Z' as u8 && c != 'z' as u8 => c,
+                _ => break,
+            }
+        };
+        // A path has to start with a move.
+        if last_command.is_none() && command != 'M' as u8 && command != 'm' as u8 {
+            break
+        }
+        if !parser.parse_segment(command, &mut path) {
+            break
+        }
+        last_command = Some(command);
+    }
+    path.commands
+}
+
+fn distance(a: Point2D<f32>, b: Point2D<f32>) -> f32 {
+    ((b.x - a.x) * (b.x - a.x) + (b.y - a.y) * (b.y - a.y)).sqrt()
+}
+
+fn lerp(a: Point2D<f32>, b: Point2D<f32>, t: f32) -> Point2D<f32> {
+    Point2D(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t)
+}
+
+/// Returns how many line segments a curve with the given second differences of its control
+/// points must be split into to stay within `tolerance` of the curve (Wang's formula).
+fn segment_count(second_difference: f32, scale: f32, tolerance: f32) -> uint {
+    let count = (scale * second_difference / tolerance).sqrt().ceil();
+    uint::max(1, uint::min(count as uint, 1000))
+}
+
+/// Flattens normalized path commands into polylines that are no further than `tolerance` from
+/// the curves they approximate.
+pub fn flatten(commands: &[PathCommand], tolerance: f32) -> ~[Subpath] {
+    let mut subpaths = ~[];
+    let mut current: ~[Point2D<f32>] = ~[];
+    let mut start = Point2D(0f32, 0f32);
+
+    for command in commands.iter() {
+        match *command {
+            MoveTo(point) => {
+                if current.len() > 1 {
+                    subpaths.push(Subpath {
+                        points: util::replace(&mut current, ~[]),
+                        closed: false,
+                    })
+                }
+                current = ~[point];
+                start = point;
+            }
+            LineTo(point) => {
+                if current.is_empty() {
+                    current.push(start)
+                }
+                current.push(point)
+            }
+            QuadTo(control, point) => {
+                if current.is_empty() {
+                    current.push(start)
+                }
+                let p0 = current[current.len() - 1];
+                let dd = distance(Point2D(p0.x - control.x, p0.y - control.y),
+                                  Point2D(control.x - point.x, control.y - point.y));
+                let count = segment_count(dd, 0.25, tolerance);
+                for i in range(1, count + 1) {
+                    let t = (i as f32) / (count as f32);
+                    current.push(lerp(lerp(p0, control, t), lerp(control, point, t), t))
+                }
+            }
+            CubicTo(control1, control2, point) => {
+                if current.is_empty() {
+                    current.push(start)
+                }
+                let p0 = current[current.len() - 1];
+                let dd1 = distance(Point2D(p0.x - control1.x, p0.y - control1.y),
+                                   Point2D(control1.x - control2.x, control1.y - control2.y));
+                let dd2 = distance(Point2D(control1.x - control2.x, control1.y - control2.y),
+                                   Point2D(control2.x - point.x, control2.y - point.y));
+                let count = segment_count(dd1.max(&dd2), 0.75, tolerance);
+                for i in range(1, count + 1) {
+                    let t = (i as f32) / (count as f32);
+                    let a = lerp(lerp(p0, control1, t), lerp(control1, control2, t), t);
+                    let b = lerp(lerp(control1, control2, t), lerp(control2, point, t), t);
+                    current.push(lerp(a, b, t))
+                }
+            }
+            ClosePath => {
+                if !current.is_empty() {
+                    subpaths.push(Subpath {
+                        points: util::replace(&mut current, ~[]),
+                        closed: true,
+                    })
+                }
+                // A segment after a close starts from where the closed subpath started.
+                current = ~[];
+            }
+        }
+    }
+    if current.len() > 1 {
+        subpaths.push(Subpath { points: current, closed: false })
+    }
+    subpaths
+}
+
+/// Splits a polyline into the dashes that a stroke with the given `stroke-dasharray` and
+/// `stroke-dashoffset` draws. An odd number of dash lengths is repeated to make an even number,
+/// and a dash array that is empty, has a negative length or adds up to zero draws a solid line.
+pub fn dash_polyline(points: &[Point2D<f32>], closed: bool, dash_array: &[f32], dash_offset: f32)
+                     -> ~[~[Point2D<f32>]] {
+    let mut points = points.to_owned();
+    if closed && !points.is_empty() {
+        points.push(points[0])
+    }
+    if points.len() < 2 || dash_array.iter().any(|&length| length < 0.0) {
+        return ~[points]
+    }
+    let dashes = if dash_array.len() % 2 == 1 {
+        vec::append(dash_array.to_owned(), dash_array)
+    } else {
+        dash_array.to_owned()
+    };
+    let total = dashes.iter().fold(0f32, |sum, &length| sum + length);
+    if total <= 0.0 {
+        return ~[points]
+    }
+
+    // Find where in the dash pattern the line starts.
+    let mut index = 0;
+    let mut remaining = dashes[0];
+    let mut offset = dash_offset % total;
+    if offset < 0.0 {
+        offset = offset + total
+    }
+    while offset > 0.0 {
+        if offset >= remaining {
+            offset = offset - remaining;
+            index = (index + 1) % dashes.len();
+            remaining = dashes[index];
+        } else {
+            remaining = remaining - offset;
+            offset = 0.0;
+        }
+    }
+
+    let mut pieces = ~[];
+    let mut current = if index % 2 == 0 { ~[points[0]] } else { ~[] };
+    for i in range(1, points.len()) {
+        let (a, b) = (points[i - 1], points[i]);
+        let length = distance(a, b);
+        let mut position = 0f32;
+        while length - position > remaining {
+            position = position + remaining;
+            let point = lerp(a, b, position / length);
+            if index % 2 == 0 {
+                current.push(point);
+                pieces.push(util::replace(&mut current, ~[]));
+            } else {
+                current = ~[point];
+            }
+            index = (index + 1) % dashes.len();
+            remaining = dashes[index];
+        }
+        remaining = remaining - (length - position);
+        if index % 2 == 0 {
+            current.push(b)
+        }
+    }
+    if current.len() > 1 {
+        pieces.push(current)
+    }
+    pieces
+}
+
+/// Returns the spans of the horizontal line at `y` that are inside the given subpaths, as pairs
+/// of left and right edges, from left to right. Every subpath is treated as closed, as it is
+/// when a path is filled.
+pub fn fill_spans(subpaths: &[Subpath], rule: FillRule, y: f32) -> ~[(f32, f32)] {
+    // The x coordinates at which the line crosses an edge, and which way the edge goes.
+    let mut crossings: ~[(f32, int)] = ~[];
+    for subpath in subpaths.iter() {
+        let points = &subpath.points;
+        for i in range(0, points.len()) {
+            let a = points[i];
+            let b = points[(i + 1) % points.len()];
+            if a.y == b.y {
+                loop
+            }
+            let (top, bottom) = if a.y < b.y { (a, b) } else { (b, a) };
+            if y < top.y || y >= bottom.y {
+                loop
+            }
+            let x = top.x + (bottom.x - top.x) * (y - top.y) / (bottom.y - top.y);
+            crossings.push((x, if a.y < b.y { 1 } else { -1 }));
+        }
+    }
+    sort::quick_sort(crossings, |&(a, _), &(b, _)| a <= b);
+
+    let mut spans = ~[];
+    let mut winding = 0;
+    let mut span_start = 0f32;
+    for &(x, direction) in crossings.iter() {
+        let was_inside = match rule {
+            NonZero => winding != 0,
+            EvenOdd => winding % 2 != 0,
+        };
+        winding += direction;
+        let is_inside = match rule {
+            NonZero => winding != 0,
+            EvenOdd => winding % 2 != 0,
+        };
+        if !was_inside && is_inside {
+            span_start = x
+        } else if was_inside && !is_inside && x > span_start {
+            spans.push((span_start, x))
+        }
+    }
+    spans
+}
+
+#[cfg(test)]
+fn approx_eq(a: Point2D<f32>, x: f32, y: f32) -> bool {
+    (a.x - x).abs() < 0.01 && (a.y - y).abs() < 0.01
+}
+
+#[test]
+fn test_parse_lines() {
+    let commands = parse_path_data("M 10,20 l 5 5 H 0 v-10 z");
+    assert!(commands.len() == 5);
+    match (commands[0], commands[1], commands[2], commands[3], commands[4]) {
+        (MoveTo(a), LineTo(b), LineTo(c), LineTo(d), ClosePath) => {
+            assert!(approx_eq(a, 10.0, 20.0));
+            assert!(approx_eq(b, 15.0, 25.0));
+            assert!(approx_eq(c, 0.0, 25.0));
+            assert!(approx_eq(d, 0.0, 15.0));
+        }
+        _ => fail!("unexpected commands"),
+    }
+}
+
+#[test]
+fn test_implicit_lines_and_compact_numbers() {
+    // Coordinates after a move are lines, and numbers needn't be separated by whitespace.
+    let commands = parse_path_data("m1-2.5.5.5 1e1,0");
+    assert!(commands.len() == 3);
+    match (commands[1], commands[2]) {
+        (LineTo(a), LineTo(b)) => {
+            assert!(approx_eq(a, 1.5, -2.0));
+            assert!(approx_eq(b, 11.5, -2.0));
+        }
+        _ => fail!("unexpected commands"),
+    }
+}
+
+#[test]
+fn test_smooth_curves_reflect_control_points() {
+    let commands = parse_path_data("M0 0 C 0 10 10 10 10 0 S 20 -10 20 0");
+    match commands[2] {
+        CubicTo(control1, _, point) => {
+            assert!(approx_eq(control1, 10.0, -10.0));
+            assert!(approx_eq(point, 20.0, 0.0));
+        }
+        _ => fail!("unexpected command"),
+    }
+}
+
+#[test]
+fn test_arcs() {
+    // A half circle is two quarter turns, and compact flags needn't be separated.
+    let commands = parse_path_data("M0 0 A5 5 0 0110 0");
+    assert!(commands.len() == 3);
+    match commands[2] {
+        CubicTo(_, _, point) => assert!(approx_eq(point, 10.0, 0.0)),
+        _ => fail!("unexpected command"),
+    }
+    let subpaths = flatten(commands, 0.1);
+    // The arc sweeps clockwise on screen, so it bulges upwards, to y = -5.
+    let top = subpaths[0].points.iter().fold(0f32, |top, p| top.min(&p.y));
+    assert!((top + 5.0).abs() < 0.1);
+}
+
+#[test]
+fn test_errors_render_up_to_the_error() {
+    assert!(parse_path_data("L 10 10").len() == 0);
+    assert!(parse_path_data("M 0 0 L 10 10 L 20").len() == 2);
+    assert!(parse_path_data("M 0 0 z 5 5").len() == 2);
+}
+
+#[test]
+fn test_dashes() {
+    let points = ~[Point2D(0f32, 0f32), Point2D(10f32, 0f32)];
+    let dashes = dash_polyline(points, false, [3.0, 2.0], 0.0);
+    assert!(dashes.len() == 2);
+    assert!(approx_eq(dashes[0][1], 3.0, 0.0));
+    assert!(approx_eq(dashes[1][0], 5.0, 0.0));
+    assert!(approx_eq(dashes[1][1], 8.0, 0.0));
+
+    // An offset moves the pattern back along the line.
+    let dashes = dash_polyline(points, false, [3.0, 2.0], 4.0);
+    assert!(approx_eq(dashes[0][0], 1.0, 0.0));
+
+    // An odd number of lengths is repeated, so dashes and gaps swap.
+    let dashes = dash_polyline(points, false, [2.0], 0.0);
+    assert!(dashes.len() == 3);
+}
+
+#[test]
+fn test_fill_rules() {
+    // A square inside another, both drawn in the same direction.
+    let outer = Subpath {
+        points: ~[Point2D(0f32, 0f32), Point2D(10f32, 0f32), Point2D(10f32, 10f32),
+                  Point2D(0f32, 10f32)],
+        closed: true,
+    };
+    let inner = Subpath {
+        points: ~[Point2D(3f32, 3f32), Point2D(7f32, 3f32), Point2D(7f32, 7f32),
+                  Point2D(3f32, 7f32)],
+        closed: true,
+    };
+    let subpaths = ~[outer, inner];
+    assert!(fill_spans(subpaths, NonZero, 5.0) == ~[(0.0, 10.0)]);
+    assert!(fill_spans(subpaths, EvenOdd, 5.0) == ~[(0.0, 3.0), (7.0, 10.0)]);
+    assert!(fill_spans(subpaths, EvenOdd, 1.0) == ~[(0.0, 10.0)]);
+}
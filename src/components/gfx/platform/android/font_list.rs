@@ -20,7 +20,7 @@ use fontconfig::fontconfig::{
 
 use font::{FontHandleMethods, UsedFontStyle};
 use font_context::FontContextHandleMethods;
-use font_list::{FontEntry, FontFamily, FontFamilyMap};
+use font_list::{FontEntry, FontFamily, FontFamilyMap, FontListHandleMethods};
 use platform::font::FontHandle;
 use platform::font_context::FontContextHandle;
 
@@ -39,7 +39,13 @@ impl FontListHandle {
         FontListHandle { fctx: fctx.clone() }
     }
 
-    pub fn get_available_families(&self) -> FontFamilyMap {
+    pub fn get_last_resort_font_families() -> ~[~str] {
+        ~[~"Roboto"]
+    }
+}
+
+impl FontListHandleMethods for FontListHandle {
+    fn get_available_families(&self) -> FontFamilyMap {
         let mut family_map : FontFamilyMap = HashMap::new();
         unsafe {
             let config = FcConfigGetCurrent();
@@ -62,7 +68,7 @@ impl FontListHandle {
         return family_map;
     }
 
-    pub fn load_variations_for_family(&self, family: @mut FontFamily) {
+    fn load_variations_for_family(&self, family: @mut FontFamily) {
         debug!("getting variations for %?", family);
         unsafe {
             let config = FcConfigGetCurrent();
@@ -127,10 +133,6 @@ impl FontListHandle {
             FcObjectSetDestroy(object_set);
         }
     }
-
-    pub fn get_last_resort_font_families() -> ~[~str] {
-        ~[~"Roboto"]
-    }
 }
 
 struct AutoPattern {
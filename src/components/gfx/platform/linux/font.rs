@@ -4,18 +4,24 @@
 
 extern mod freetype;
 
-use font::{CSSFontWeight, FontHandleMethods, FontMetrics, FontTableMethods};
+use font::{CSSFontStretch, CSSFontWeight, FontHandleMethods, FontMetrics, FontTableMethods};
+use font::FontStretchNormal;
 use font::{FontTableTag, FractionalPixel, SpecifiedFontStyle, UsedFontStyle, FontWeight100};
 use font::{FontWeight200, FontWeight300, FontWeight400, FontWeight500, FontWeight600};
 use font::{FontWeight700, FontWeight800, FontWeight900};
-use geometry::Au;
-use geometry;
+use geometry::{Au, CSSPixel};
 use platform::font_context::FontContextHandle;
+use glyph_atlas::RasterizedGlyph;
 use text::glyph::GlyphIndex;
 use text::util::{float_to_fixed, fixed_to_float};
 
+use azure::AzFloat;
+use azure::azure_hl::BackendType;
+use azure::scaled_font::ScaledFont;
 use freetype::freetype::{FT_Get_Char_Index, FT_Get_Postscript_Name};
 use freetype::freetype::{FT_Load_Glyph, FT_Set_Char_Size};
+use freetype::freetype::{FT_Fixed, FT_Matrix, FT_Pos, FT_RENDER_MODE_NORMAL, FT_Render_Glyph};
+use freetype::freetype::{FT_Set_Transform, FT_Vector};
 use freetype::freetype::{FT_New_Face, FT_Get_Sfnt_Table};
 use freetype::freetype::{FT_New_Memory_Face, FT_Done_Face};
 use freetype::freetype::{FTErrorMethods, FT_F26Dot6, FT_Face, FT_FaceRec};
@@ -28,6 +34,7 @@ use freetype::tt_os2::TT_OS2;
 use std::cast;
 use std::ptr;
 use std::str;
+use std::vec;
 
 fn float_to_fixed_ft(f: float) -> i32 {
     float_to_fixed(6, f)
@@ -37,6 +44,10 @@ fn fixed_to_float_ft(f: i32) -> float {
     fixed_to_float(6, f)
 }
 
+fn float_to_fixed_ft_16_16(f: float) -> FT_Fixed {
+    float_to_fixed(16, f) as FT_Fixed
+}
+
 pub struct FontTable {
     bogus: ()
 }
@@ -169,6 +180,18 @@ impl FontHandleMethods for FontHandle {
         }
     }
 
+    #[fixed_stack_segment]
+    fn stretchiness(&self) -> CSSFontStretch {
+        unsafe {
+            let os2 = FT_Get_Sfnt_Table(self.face, ft_sfnt_os2) as *TT_OS2;
+            if os2.is_not_null() && (*os2).version != 0xffff {
+                CSSFontStretch::from_number((*os2).usWidthClass as uint)
+            } else {
+                FontStretchNormal
+            }
+        }
+    }
+
     fn clone_with_style(&self,
                         fctx: &FontContextHandle,
                         style: &UsedFontStyle) -> Result<FontHandle, ()> {
@@ -233,8 +256,8 @@ impl FontHandleMethods for FontHandle {
         return FontMetrics {
             underline_size:   underline_size,
             underline_offset: underline_offset,
-            leading:          geometry::from_pt(0.0), //FIXME
-            x_height:         geometry::from_pt(0.0), //FIXME
+            leading:          Au(0), //FIXME
+            x_height:         Au(0), //FIXME
             em_size:          em_size,
             ascent:           ascent,
             descent:          -descent, // linux font's seem to use the opposite sign from mac
@@ -245,6 +268,66 @@ impl FontHandleMethods for FontHandle {
     fn get_table_for_tag(&self, _: FontTableTag) -> Option<FontTable> {
         None
     }
+
+    fn create_scaled_font(&mut self, backend: BackendType, pt_size: float) -> ScaledFont {
+        ScaledFont::new(backend, self.face, pt_size as AzFloat)
+    }
+
+    #[fixed_stack_segment]
+    fn rasterize_glyph(&self, glyph: GlyphIndex, scale: float, subpixel_offset: float)
+                       -> Option<RasterizedGlyph> {
+        assert!(self.face.is_not_null());
+        unsafe {
+            // The face stays at the size layout measures it at; the glyph is scaled as it's
+            // loaded instead.
+            let mut matrix = FT_Matrix {
+                xx: float_to_fixed_ft_16_16(scale),
+                xy: 0,
+                yx: 0,
+                yy: float_to_fixed_ft_16_16(scale),
+            };
+            let mut delta = FT_Vector {
+                x: float_to_fixed_ft(subpixel_offset) as FT_Pos,
+                y: 0,
+            };
+            FT_Set_Transform(self.face, &mut matrix, &mut delta);
+            let result = FT_Load_Glyph(self.face, glyph as FT_UInt, 0);
+            FT_Set_Transform(self.face, ptr::mut_null(), ptr::mut_null());
+            if !result.succeeded() {
+                debug!("Unable to load glyph %?. reason: %?", glyph, result);
+                return None
+            }
+
+            let slot: FT_GlyphSlot = cast::transmute((*self.face).glyph);
+            assert!(slot.is_not_null());
+            if !FT_Render_Glyph(slot, FT_RENDER_MODE_NORMAL).succeeded() {
+                debug!("Unable to render glyph %?", glyph);
+                return None
+            }
+
+            let bitmap = &(*slot).bitmap;
+            let (width, height) = (bitmap.width as uint, bitmap.rows as uint);
+            let pitch = bitmap.pitch as int;
+            let mut coverage = vec::with_capacity(width * height);
+            for row in range(0, height) {
+                // Bitmaps with a negative pitch are stored from the bottom row up.
+                let row_start = if pitch >= 0 {
+                    (row as int) * pitch
+                } else {
+                    ((row as int) - (height as int) + 1) * pitch
+                };
+                let row_pointer = ptr::offset(bitmap.buffer as *u8, row_start);
+                coverage.push_all(vec::raw::from_buf_raw(row_pointer, width));
+            }
+            Some(RasterizedGlyph {
+                width: width,
+                height: height,
+                left: (*slot).bitmap_left as int,
+                top: (*slot).bitmap_top as int,
+                coverage: coverage,
+            })
+        }
+    }
 }
 
 impl<'self> FontHandle {
@@ -334,7 +417,7 @@ impl<'self> FontHandle {
         // If this isn't true then we're scaling one of the axes wrong
         assert!(metrics.x_ppem == metrics.y_ppem);
 
-        return geometry::from_frac_px(value * x_scale);
+        return Au::from_css_px(CSSPixel(value * x_scale));
     }
 }
 
@@ -20,7 +20,7 @@ use fontconfig::fontconfig::{
 
 use font::{FontHandleMethods, UsedFontStyle};
 use font_context::FontContextHandleMethods;
-use font_list::{FontEntry, FontFamily, FontFamilyMap};
+use font_list::{FontEntry, FontFamily, FontFamilyMap, FontListHandleMethods};
 use platform::font::FontHandle;
 use platform::font_context::FontContextHandle;
 
@@ -39,8 +39,14 @@ impl FontListHandle {
         FontListHandle { fctx: fctx.clone() }
     }
 
+    pub fn get_last_resort_font_families() -> ~[~str] {
+        ~[~"Arial"]
+    }
+}
+
+impl FontListHandleMethods for FontListHandle {
     #[fixed_stack_segment]
-    pub fn get_available_families(&self) -> FontFamilyMap {
+    fn get_available_families(&self) -> FontFamilyMap {
         let mut family_map : FontFamilyMap = HashMap::new();
         unsafe {
             let config = FcConfigGetCurrent();
@@ -64,7 +70,7 @@ impl FontListHandle {
     }
 
     #[fixed_stack_segment]
-    pub fn load_variations_for_family(&self, family: @mut FontFamily) {
+    fn load_variations_for_family(&self, family: @mut FontFamily) {
         debug!("getting variations for %?", family);
         unsafe {
             let config = FcConfigGetCurrent();
@@ -129,10 +135,6 @@ impl FontListHandle {
             FcObjectSetDestroy(object_set);
         }
     }
-
-    pub fn get_last_resort_font_families() -> ~[~str] {
-        ~[~"Arial"]
-    }
 }
 
 struct AutoPattern {
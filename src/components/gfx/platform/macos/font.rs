@@ -8,14 +8,18 @@ extern mod core_foundation;
 extern mod core_graphics;
 extern mod core_text;
 
-use font::{CSSFontWeight, FontHandleMethods, FontMetrics, FontTableMethods};
+use font::{CSSFontStretch, CSSFontWeight, FontHandleMethods, FontMetrics, FontTableMethods};
 use font::{FontTableTag, FontWeight100, FontWeight200, FontWeight300, FontWeight400};
 use font::{FontWeight500, FontWeight600, FontWeight700, FontWeight800, FontWeight900};
 use font::{FractionalPixel, SpecifiedFontStyle};
-use geometry::{Au, px_to_pt};
+use geometry::{Au, CSSPixel, px_to_pt};
+use glyph_atlas::RasterizedGlyph;
 use platform::macos::font_context::FontContextHandle;
 use text::glyph::GlyphIndex;
 
+use azure::AzFloat;
+use azure::azure_hl::BackendType;
+use azure::scaled_font::ScaledFont;
 use core_foundation::base::CFIndex;
 use core_foundation::data::CFData;
 use core_foundation::string::UniChar;
@@ -122,6 +126,14 @@ impl FontHandleMethods for FontHandle {
         return FontWeight900;
     }
 
+    fn stretchiness(&self) -> CSSFontStretch {
+        // -1.0 to 1.0
+        let normalized = self.ctfont.all_traits().normalized_width();
+        // 1.0 to 9.0
+        let normalized = (normalized + 1.0) * 4.0 + 1.0;
+        CSSFontStretch::from_number(normalized.round() as uint)
+    }
+
     fn clone_with_style(&self, fctx: &FontContextHandle, style: &SpecifiedFontStyle)
                      -> Result<FontHandle,()> {
         let new_font = self.ctfont.clone_with_font_size(style.pt_size);
@@ -159,7 +171,7 @@ impl FontHandleMethods for FontHandle {
         let bounding_rect: CGRect = self.ctfont.bounding_box();
         let ascent = Au::from_pt(self.ctfont.ascent() as float);
         let descent = Au::from_pt(self.ctfont.descent() as float);
-        let em_size = Au::from_frac_px(self.ctfont.pt_size() as float);
+        let em_size = Au::from_css_px(CSSPixel(self.ctfont.pt_size() as float));
 
         let scale = px_to_pt(self.ctfont.pt_size() as float) / (self.ctfont.ascent() as float + self.ctfont.descent() as float);
 
@@ -193,5 +205,17 @@ impl FontHandleMethods for FontHandle {
     fn face_identifier(&self) -> ~str {
         self.ctfont.postscript_name()
     }
+
+    fn create_scaled_font(&mut self, backend: BackendType, pt_size: float) -> ScaledFont {
+        let cg_font = self.get_CGFont();
+        ScaledFont::new(backend, &cg_font, pt_size as AzFloat)
+    }
+
+    // FIXME: Glyphs should be rasterized with Core Graphics, into a bitmap context. Until then
+    // they're all drawn by Azure.
+    fn rasterize_glyph(&self, _glyph: GlyphIndex, _scale: float, _subpixel_offset: float)
+                       -> Option<RasterizedGlyph> {
+        None
+    }
 }
 
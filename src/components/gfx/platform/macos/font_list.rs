@@ -4,7 +4,7 @@
 
 use font::FontHandleMethods;
 use font_context::FontContextHandleMethods;
-use font_list::{FontEntry, FontFamily, FontFamilyMap};
+use font_list::{FontEntry, FontFamily, FontFamilyMap, FontListHandleMethods};
 use platform::macos::font::FontHandle;
 use platform::macos::font_context::FontContextHandle;
 
@@ -27,7 +27,13 @@ impl FontListHandle {
         }
     }
 
-    pub fn get_available_families(&self) -> FontFamilyMap {
+    pub fn get_last_resort_font_families() -> ~[~str] {
+        ~[~"Arial Unicode MS",~"Arial"]
+    }
+}
+
+impl FontListHandleMethods for FontListHandle {
+    fn get_available_families(&self) -> FontFamilyMap {
         let family_names: CFArray<CFStringRef> = core_text::font_collection::get_family_names();
         let mut family_map: FontFamilyMap = HashMap::new();
         for strref in family_names.iter() {
@@ -40,7 +46,7 @@ impl FontListHandle {
         family_map
     }
 
-    pub fn load_variations_for_family(&self, family: @mut FontFamily) {
+    fn load_variations_for_family(&self, family: @mut FontFamily) {
         debug!("Looking for faces of family: %s", family.family_name);
 
         let family_collection = core_text::font_collection::create_for_family(family.family_name);
@@ -55,8 +61,4 @@ impl FontListHandle {
             family.entries.push(entry)
         }
     }
-
-    pub fn get_last_resort_font_families() -> ~[~str] {
-        ~[~"Arial Unicode MS",~"Arial"]
-    }
 }
@@ -2,6 +2,18 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+//! The font backends: FreeType with fontconfig on Linux and Android, and Core Text on Mac OS.
+//! Each provides a `FontHandle`, `FontContextHandle`, and `FontListHandle` implementing the traits
+//! in `font`, `font_context`, and `font_list`, and nothing outside this module names the
+//! platform's font types.
+//!
+//! There is no backend for Windows. A DirectWrite one would implement the same three traits, but
+//! there are no DirectWrite bindings to build it on, and neither Azure nor the windowing code
+//! builds on Windows yet, so the font traits are only implemented for the platforms above. On
+//! other platforms, `font`, `font_context` and `font_list` are missing and `gfx` doesn't build.
+//!
+//! FIXME: Add a DirectWrite backend once Windows is a platform that the rest of the tree supports.
+
 #[cfg(target_os="linux")] pub use platform::linux::{font, font_context, font_list};
 #[cfg(target_os="macos")] pub use platform::macos::{font, font_context, font_list};
 #[cfg(target_os="android")] pub use platform::android::{font, font_context, font_list};
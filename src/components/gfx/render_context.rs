@@ -3,14 +3,23 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use servo_msg::compositor_msg::LayerBuffer;
+use display_list::PathStroke;
+use effects;
+use effects::{Filter, GroupEffects};
 use font_context::FontContext;
 use geometry::Au;
 use opts::Opts;
+use path;
+use path::{FillRule, Subpath};
 
 use azure::azure_hl::{B8G8R8A8, Color, ColorPattern, DrawOptions};
-use azure::azure_hl::{DrawSurfaceOptions, DrawTarget, Linear, StrokeOptions};
+use azure::azure_hl::{DrawSurfaceOptions, DrawTarget, Linear, SourceSurfaceMethods};
+use azure::azure_hl::StrokeOptions;
 use azure::AzFloat;
+use std::f32;
+use std::vec;
 use std::libc::types::common::c99::uint16_t;
+use geom::matrix2d::Matrix2D;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
@@ -18,6 +27,9 @@ use geom::side_offsets::SideOffsets2D;
 use servo_net::image::base::Image;
 use extra::arc::Arc;
 
+/// The Azure composition operator that replaces what is drawn over, `OP_SOURCE`.
+static SOURCE_OPERATOR: uint16_t = 5;
+
 pub struct RenderContext<'self> {
     canvas: &'self ~LayerBuffer,
     font_ctx: @mut FontContext,
@@ -76,6 +88,91 @@ impl<'self> RenderContext<'self>  {
         self.canvas.draw_target.stroke_line(start, end, &ColorPattern(color.left), &stroke_opts, &draw_opts);
     }
 
+    /// Draws a zigzag line across `bounds`, going up and down once for each time it moves
+    /// across by the height of the bounds.
+    pub fn draw_wavy_line(&self, bounds: &Rect<Au>, color: Color) {
+        let draw_opts = DrawOptions(1 as AzFloat, 0 as uint16_t);
+        let stroke_opts = StrokeOptions(1 as AzFloat, 10 as AzFloat, 0);
+        let pattern = ColorPattern(color);
+
+        let rect = bounds.to_azure_rect();
+        let top = rect.origin.y + 0.5;
+        let bottom = rect.origin.y + rect.size.height - 0.5;
+        let step = (rect.size.height - 1.0).max(&1.0);
+
+        self.canvas.draw_target.make_current();
+        let mut x = rect.origin.x;
+        let mut up = false;
+        while x < rect.origin.x + rect.size.width {
+            let next_x = (x + step).min(&(rect.origin.x + rect.size.width));
+            let (start_y, end_y) = if up { (bottom, top) } else { (top, bottom) };
+            self.canvas.draw_target.stroke_line(Point2D(x, start_y),
+                                                Point2D(next_x, end_y),
+                                                &pattern,
+                                                &stroke_opts,
+                                                &draw_opts);
+            x = next_x;
+            up = !up;
+        }
+    }
+
+    /// Fills the inside of a path whose points are relative to `origin`.
+    ///
+    /// FIXME: The path is filled a row of pixels at a time, so its edges aren't antialiased. It
+    /// should be filled by Azure once the Azure bindings can build paths.
+    pub fn fill_path(&self,
+                     origin: Point2D<AzFloat>,
+                     subpaths: &[Subpath],
+                     rule: FillRule,
+                     color: Color) {
+        let (mut top, mut bottom) = (f32::infinity, f32::neg_infinity);
+        for subpath in subpaths.iter() {
+            for point in subpath.points.iter() {
+                top = top.min(&point.y);
+                bottom = bottom.max(&point.y);
+            }
+        }
+
+        let pattern = ColorPattern(color);
+        self.canvas.draw_target.make_current();
+        // Each row is filled where its middle is inside the path.
+        let mut y = top.floor();
+        while y < bottom {
+            for &(left, right) in path::fill_spans(subpaths, rule, y + 0.5).iter() {
+                let rect = Rect(Point2D(origin.x + left, origin.y + y),
+                                Size2D(right - left, 1 as AzFloat));
+                self.canvas.draw_target.fill_rect(&rect, &pattern);
+            }
+            y = y + 1.0;
+        }
+    }
+
+    /// Strokes the outline of a path whose points are relative to `origin`.
+    ///
+    /// FIXME: Each segment is stroked as a line of its own, so the segments aren't joined, and
+    /// the ends of dashes are always butt caps.
+    pub fn stroke_path(&self, origin: Point2D<AzFloat>, subpaths: &[Subpath], stroke: &PathStroke) {
+        let draw_opts = DrawOptions(1 as AzFloat, 0 as uint16_t);
+        let stroke_opts = StrokeOptions(stroke.width, 10 as AzFloat, 0);
+        let pattern = ColorPattern(stroke.color);
+
+        self.canvas.draw_target.make_current();
+        for subpath in subpaths.iter() {
+            let pieces = path::dash_polyline(subpath.points,
+                                             subpath.closed,
+                                             stroke.dash_array,
+                                             stroke.dash_offset);
+            for piece in pieces.iter() {
+                for i in range(1, piece.len()) {
+                    let start = Point2D(origin.x + piece[i - 1].x, origin.y + piece[i - 1].y);
+                    let end = Point2D(origin.x + piece[i].x, origin.y + piece[i].y);
+                    self.canvas.draw_target.stroke_line(start, end, &pattern, &stroke_opts,
+                                                        &draw_opts);
+                }
+            }
+        }
+    }
+
     pub fn draw_image(&self, bounds: Rect<Au>, image: Arc<~Image>) {
         let image = image.get();
         let size = Size2D(image.width as i32, image.height as i32);
@@ -97,6 +194,152 @@ impl<'self> RenderContext<'self>  {
                                      draw_options);
     }
 
+    /// Draws a group of display items with `draw_contents` into a buffer of its own, covering
+    /// `bounds` at the resolution of this context, then applies the effects of the group to the
+    /// buffer and composites it into this context.
+    ///
+    /// The buffer of a group is the backdrop root of the items in it, as the Filter Effects
+    /// specification requires of elements with a `backdrop-filter`, a mask, or other effects: a
+    /// backdrop filter within the group filters only what the group has drawn so far.
+    pub fn draw_group(&self,
+                      bounds: &Rect<Au>,
+                      effects: &GroupEffects,
+                      draw_contents: &fn(&RenderContext)) {
+        if effects.is_empty() {
+            return draw_contents(self)
+        }
+
+        let scale = self.canvas.resolution as AzFloat;
+        let rect = bounds.to_azure_rect();
+        if !effects.backdrop_filters.is_empty() {
+            self.filter_backdrop(&rect, effects.backdrop_filters);
+        }
+        let size = Size2D((rect.size.width * scale).ceil() as i32,
+                          (rect.size.height * scale).ceil() as i32);
+        if size.width <= 0 || size.height <= 0 {
+            return
+        }
+
+        let group = ~LayerBuffer {
+            draw_target: DrawTarget::new(self.opts.render_backend, size, B8G8R8A8),
+            rect: rect,
+            screen_pos: Rect(Point2D(0, 0), Size2D(size.width as uint, size.height as uint)),
+            resolution: self.canvas.resolution,
+            stride: (size.width * 4) as uint,
+        };
+        {
+            let group_context = RenderContext {
+                canvas: &group,
+                font_ctx: self.font_ctx,
+                opts: self.opts,
+            };
+            let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
+            let matrix = matrix.scale(scale, scale);
+            let matrix = matrix.translate(-rect.origin.x, -rect.origin.y);
+            group.draw_target.set_transform(&matrix);
+            draw_contents(&group_context);
+            group.draw_target.flush();
+        }
+
+        // Read the pixels of the group back, without the padding at the ends of its rows.
+        let surface = group.draw_target.snapshot().get_data_surface();
+        let surface_stride = surface.stride() as uint;
+        let stride = group.stride;
+        let mut pixels = vec::with_capacity(stride * (size.height as uint));
+        do surface.with_data |data| {
+            for row in range(0, size.height as uint) {
+                let start = row * surface_stride;
+                pixels.push_all(data.slice(start, start + stride));
+            }
+        }
+
+        let pixel_size = Size2D(size.width as uint, size.height as uint);
+        for mask in effects.mask.iter() {
+            let tile = mask.tile.to_azure_rect();
+            let tile = Rect(Point2D((tile.origin.x - rect.origin.x) * scale,
+                                    (tile.origin.y - rect.origin.y) * scale),
+                            Size2D(tile.size.width * scale, tile.size.height * scale));
+            effects::apply_mask(pixels, pixel_size, &**mask.image.get(), &tile, mask.mode);
+        }
+
+        self.canvas.draw_target.make_current();
+        let draw_target_ref = &self.canvas.draw_target;
+        let azure_surface = draw_target_ref.create_source_surface_from_data(pixels, size,
+                                                                            stride as i32,
+                                                                            B8G8R8A8);
+        let source_rect = Rect(Point2D(0 as AzFloat, 0 as AzFloat),
+                               Size2D(size.width as AzFloat, size.height as AzFloat));
+        let dest_rect = Rect(rect.origin,
+                             Size2D(size.width as AzFloat / scale,
+                                    size.height as AzFloat / scale));
+        let draw_surface_options = DrawSurfaceOptions(Linear, true);
+        let draw_options = DrawOptions(1.0f as AzFloat, 0);
+        draw_target_ref.draw_surface(azure_surface,
+                                     dest_rect,
+                                     source_rect,
+                                     draw_surface_options,
+                                     draw_options);
+    }
+
+    /// Applies filters to what has been drawn into this context beneath `rect`, and replaces it
+    /// with the result, clipped to `rect`.
+    ///
+    /// FIXME: Tiles are filtered separately, so blurs don't reach across their edges. The
+    /// backdrop is left unfiltered where the transform does more than scale and translate.
+    fn filter_backdrop(&self, rect: &Rect<AzFloat>, filters: &[Filter]) {
+        let draw_target = &self.canvas.draw_target;
+        let transform = draw_target.get_transform();
+        if transform.m12 != 0.0 || transform.m21 != 0.0 || transform.m11 <= 0.0 ||
+                transform.m11 != transform.m22 {
+            return
+        }
+
+        // Find the pixels of the backdrop in the buffer.
+        let buffer_size = self.canvas.screen_pos.size;
+        let clamp = |value: AzFloat, limit: uint| value.max(&0.0).min(&(limit as AzFloat)) as uint;
+        let (x, y) = (rect.origin.x * transform.m11 + transform.m31,
+                      rect.origin.y * transform.m22 + transform.m32);
+        let (width, height) = (rect.size.width * transform.m11, rect.size.height * transform.m22);
+        let left = clamp(x.floor(), buffer_size.width);
+        let top = clamp(y.floor(), buffer_size.height);
+        let right = clamp((x + width).ceil(), buffer_size.width);
+        let bottom = clamp((y + height).ceil(), buffer_size.height);
+        if left >= right || top >= bottom {
+            return
+        }
+
+        // Read the backdrop back and filter it.
+        draw_target.flush();
+        let size = Size2D(right - left, bottom - top);
+        let stride = size.width * 4;
+        let mut pixels = vec::with_capacity(stride * size.height);
+        let surface = draw_target.snapshot().get_data_surface();
+        let surface_stride = surface.stride() as uint;
+        do surface.with_data |data| {
+            for row in range(top, bottom) {
+                let start = row * surface_stride + left * 4;
+                pixels.push_all(data.slice(start, start + stride));
+            }
+        }
+        effects::apply_filters(pixels, size, filters, transform.m11);
+
+        // Replace the backdrop with the filtered pixels, rather than drawing them over it.
+        let azure_size = Size2D(size.width as i32, size.height as i32);
+        let azure_surface = draw_target.create_source_surface_from_data(pixels, azure_size,
+                                                                        stride as i32, B8G8R8A8);
+        let source_rect = Rect(Point2D(0 as AzFloat, 0 as AzFloat),
+                               Size2D(size.width as AzFloat, size.height as AzFloat));
+        let dest_rect = Rect(Point2D(left as AzFloat, top as AzFloat),
+                             Size2D(size.width as AzFloat, size.height as AzFloat));
+        draw_target.set_transform(&Matrix2D::identity());
+        draw_target.draw_surface(azure_surface,
+                                 dest_rect,
+                                 source_rect,
+                                 DrawSurfaceOptions(Linear, true),
+                                 DrawOptions(1.0f as AzFloat, SOURCE_OPERATOR));
+        draw_target.set_transform(&transform);
+    }
+
     pub fn clear(&self) {
         let pattern = ColorPattern(Color(1.0, 1.0, 1.0, 1.0));
         let rect = Rect(Point2D(self.canvas.rect.origin.x as AzFloat,
@@ -6,12 +6,14 @@
 
 use azure::{AzFloat, AzGLContext};
 use azure::azure_hl::{B8G8R8A8, DrawTarget};
-use display_list::DisplayList;
+use display_list::{DisplayList, FixedLayer};
 use servo_msg::compositor_msg::{RenderListener, IdleRenderState, RenderingRenderState, LayerBuffer};
-use servo_msg::compositor_msg::{LayerBufferSet, Epoch};
+use servo_msg::compositor_msg::{LayerBufferSet, Epoch, OverscrollBehavior, ScrollSnapPositions};
+use servo_msg::compositor_msg::{HitTestData, ScrollbarStyle};
 use servo_msg::constellation_msg::PipelineId;
 use font_context::FontContext;
 use geom::matrix2d::Matrix2D;
+use geom::point::Point2D;
 use geom::size::Size2D;
 use geom::rect::Rect;
 use opts::Opts;
@@ -21,7 +23,7 @@ use std::cell::Cell;
 use std::comm::{Chan, Port, SharedChan};
 use extra::arc::Arc;
 
-use servo_util::time::{ProfilerChan, profile};
+use servo_util::time::{ProfilerChan, profile, profile_frame_stage};
 use servo_util::time;
 
 use buffer_map::BufferMap;
@@ -29,7 +31,21 @@ use buffer_map::BufferMap;
 
 pub struct RenderLayer<T> {
     display_list: Arc<DisplayList<T>>,
-    size: Size2D<uint>
+    /// The layers of the elements with `position: fixed`, which are drawn above the page and
+    /// stay put in the viewport as it scrolls.
+    fixed_layers: Arc<~[FixedLayer<T>]>,
+    size: Size2D<uint>,
+    /// Where scrolling the layer can come to rest.
+    scroll_snap: ScrollSnapPositions,
+    /// Which regions of the layer scroll gestures go to, such as the frames in it.
+    hit_test_data: HitTestData,
+    /// Whether the scrolls that script asks for are smooth.
+    smooth_scroll: bool,
+    /// What happens when scrolling the layer reaches the edge of the page, horizontally and
+    /// vertically.
+    overscroll_behavior: (OverscrollBehavior, OverscrollBehavior),
+    /// How the scrollbars of the layer look.
+    scrollbar_style: ScrollbarStyle,
 }
 
 pub enum Msg<T> {
@@ -92,6 +108,9 @@ struct RenderTask<C,T> {
     paint_permission: bool,
     /// Cached copy of last layers rendered
     last_paint_msg: Option<~LayerBufferSet>,
+    /// The scale that the fixed layers of the render layer were last rendered at, if they have
+    /// been since it came, or since the epoch changed.
+    fixed_layers_scale: Option<f32>,
     /// A counter for epoch messages
     epoch: Epoch,
     /// A data structure to store unused LayerBuffers
@@ -122,6 +141,7 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                 compositor: compositor,
                 font_ctx: @mut FontContext::new(opts.render_backend.clone(),
                                                 false,
+                                                opts.generic_fonts,
                                                 profiler_chan.clone()),
                 opts: opts,
                 profiler_chan: profiler_chan,
@@ -130,6 +150,7 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
 
                 paint_permission: false,
                 last_paint_msg: None,
+                fixed_layers_scale: None,
                 epoch: Epoch(0),
                 buffer_map: BufferMap::new(10000000),
             };
@@ -147,13 +168,32 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                     if self.paint_permission {
                         self.epoch.next();
                         self.compositor.set_layer_page_size(self.id, render_layer.size, self.epoch);
+                        self.compositor.set_layer_scroll_snap(self.id,
+                                                              render_layer.scroll_snap.clone());
+                        let hit_test_data = render_layer.hit_test_data.clone();
+                        self.compositor.set_layer_hit_test_data(self.id, hit_test_data);
+                        self.compositor.set_layer_smooth_scroll(self.id,
+                                                                render_layer.smooth_scroll);
+                        let (overscroll_x, overscroll_y) = render_layer.overscroll_behavior;
+                        self.compositor.set_layer_overscroll_behavior(self.id,
+                                                                      overscroll_x,
+                                                                      overscroll_y);
+                        self.compositor.set_layer_scrollbar_style(self.id,
+                                                                  render_layer.scrollbar_style);
                     }
                     self.render_layer = Some(render_layer);
                     self.last_paint_msg = None;
+                    self.fixed_layers_scale = None;
                 }
                 ReRenderMsg(tiles, scale, epoch) => {
                     if self.epoch == epoch {
-                        self.render(tiles, scale);
+                        let tiles = Cell::new(tiles);
+                        do profile_frame_stage(time::PaintStage, self.profiler_chan.clone()) {
+                            self.render(tiles.take(), scale);
+                            if self.paint_permission && self.fixed_layers_scale != Some(scale) {
+                                self.render_fixed_layers(scale);
+                            }
+                        }
                     } else {
                         debug!("renderer epoch mismatch: %? != %?", self.epoch, epoch);
                     }
@@ -166,10 +206,23 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                 }
                 PaintPermissionGranted => {
                     self.paint_permission = true;
+                    self.fixed_layers_scale = None;
                     match self.render_layer {
                         Some(ref render_layer) => {
                             self.epoch.next();
                             self.compositor.set_layer_page_size(self.id, render_layer.size, self.epoch);
+                            self.compositor.set_layer_scroll_snap(self.id,
+                                                                  render_layer.scroll_snap.clone());
+                            let hit_test_data = render_layer.hit_test_data.clone();
+                            self.compositor.set_layer_hit_test_data(self.id, hit_test_data);
+                            self.compositor.set_layer_smooth_scroll(self.id,
+                                                                    render_layer.smooth_scroll);
+                            let (overscroll_x, overscroll_y) = render_layer.overscroll_behavior;
+                            self.compositor.set_layer_overscroll_behavior(self.id,
+                                                                          overscroll_x,
+                                                                          overscroll_y);
+                            self.compositor.set_layer_scrollbar_style(self.id,
+                                                                      render_layer.scrollbar_style);
                         }
                         None => {}
                     }
@@ -268,6 +321,7 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
                 }
 
             }
+            self.font_ctx.glyph_atlas.report(&self.profiler_chan);
 
             let layer_buffer_set = ~LayerBufferSet {
                 buffers: new_buffers,
@@ -282,5 +336,82 @@ impl<C: RenderListener + Send,T:Send+Freeze> RenderTask<C,T> {
             self.compositor.set_render_state(IdleRenderState);
         }
     }
+
+    /// Renders each of the fixed layers of the render layer into a buffer of its own at the given
+    /// scale, and sends them to the compositor, which puts them where they are in the viewport.
+    fn render_fixed_layers(&mut self, scale: f32) {
+        let render_layer = match self.render_layer {
+            Some(ref render_layer) => render_layer,
+            None => return,
+        };
+
+        do time::profile(time::RenderingCategory, self.profiler_chan.clone()) {
+            let mut new_buffers = ~[];
+            for layer in render_layer.fixed_layers.get().iter() {
+                let page_rect = Rect(Point2D(layer.viewport_position.x.to_nearest_px() as f32,
+                                             layer.viewport_position.y.to_nearest_px() as f32),
+                                     Size2D(layer.bounds.size.width.to_nearest_px() as f32,
+                                            layer.bounds.size.height.to_nearest_px() as f32));
+                let width = (page_rect.size.width * scale).ceil() as uint;
+                let height = (page_rect.size.height * scale).ceil() as uint;
+                if width == 0 || height == 0 {
+                    loop
+                }
+                let screen_rect = Rect(Point2D((page_rect.origin.x * scale) as uint,
+                                               (page_rect.origin.y * scale) as uint),
+                                       Size2D(width, height));
+
+                let buffer = match self.buffer_map.find(screen_rect.size) {
+                    Some(buffer) => {
+                        let mut buffer = buffer;
+                        buffer.rect = page_rect;
+                        buffer.screen_pos = screen_rect;
+                        buffer.resolution = scale;
+                        buffer
+                    }
+                    None => ~LayerBuffer {
+                        draw_target: DrawTarget::new_with_fbo(self.opts.render_backend,
+                                                              self.share_gl_context,
+                                                              Size2D(width as i32, height as i32),
+                                                              B8G8R8A8),
+                        rect: page_rect,
+                        screen_pos: screen_rect,
+                        resolution: scale,
+                        stride: (width * 4) as uint
+                    }
+                };
+
+                {
+                    let ctx = RenderContext {
+                        canvas: &buffer,
+                        font_ctx: self.font_ctx,
+                        opts: &self.opts
+                    };
+
+                    // The items of the layer are where the element is in the page.
+                    let matrix: Matrix2D<AzFloat> = Matrix2D::identity();
+                    let matrix = matrix.scale(scale as AzFloat, scale as AzFloat);
+                    let matrix = matrix.translate(-(layer.bounds.origin.x.to_nearest_px()
+                                                    as AzFloat),
+                                                  -(layer.bounds.origin.y.to_nearest_px()
+                                                    as AzFloat));
+                    ctx.canvas.draw_target.set_transform(&matrix);
+                    ctx.clear();
+
+                    do profile(time::RenderingDrawingCategory, self.profiler_chan.clone()) {
+                        layer.list.draw_into_context(&ctx);
+                        ctx.canvas.draw_target.flush();
+                    }
+                }
+
+                new_buffers.push(buffer);
+            }
+
+            self.compositor.paint_fixed_layers(self.id,
+                                               ~LayerBufferSet { buffers: new_buffers },
+                                               self.epoch);
+        }
+        self.fixed_layers_scale = Some(scale);
+    }
 }
 
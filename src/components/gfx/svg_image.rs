@@ -0,0 +1,623 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Rasterization of SVG documents used as images, by `<img>` and `background-image`.
+//!
+//! SVG images are rasterized on the decoder tasks of the image cache, apart from any page. As
+//! SVG requires of images, they run no script and load no other resources, so all they can draw
+//! is the shapes in their own markup.
+//!
+//! FIXME: Only the basic shapes and paths are drawn, with the fill and stroke of their
+//! presentation attributes and those of the groups around them. Style sheets, the `style`
+//! attribute, `transform`, text, gradients and `<use>` are ignored.
+
+use color::{Color, rgb};
+use path;
+use path::{EvenOdd, FillRule, NonZero, Subpath};
+
+use geom::point::Point2D;
+use geom::size::Size2D;
+use servo_net::image::base::Image;
+use std::ascii::StrAsciiExt;
+use std::float;
+use std::uint;
+use std::vec;
+
+/// The size of an SVG image that doesn't say how big it is, which is the default size of
+/// replaced elements.
+static DEFAULT_SIZE: (uint, uint) = (300, 150);
+
+/// The most pixels an SVG image is rasterized into, so that no image can exhaust memory.
+static MAX_PIXELS: uint = 4096 * 4096;
+
+/// How many rows of samples are taken in each row of pixels, to antialias edges.
+static SAMPLES_PER_PIXEL: uint = 4;
+
+/// A tag of the markup of an SVG document.
+struct Tag {
+    name: ~str,
+    attributes: ~[(~str, ~str)],
+    is_end: bool,
+    is_self_closing: bool,
+}
+
+impl Tag {
+    fn attribute<'a>(&'a self, name: &str) -> Option<&'a str> {
+        self.attributes.iter().find(|&&(ref n, _)| n.as_slice() == name).map(|&(_, ref value)| {
+            value.as_slice()
+        })
+    }
+
+    fn length(&self, name: &str) -> Option<f32> {
+        self.attribute(name).chain(parse_length)
+    }
+}
+
+/// Splits markup into its tags, skipping text, comments, processing instructions and doctypes.
+fn parse_tags(markup: &str) -> ~[Tag] {
+    let bytes = markup.as_bytes();
+    let mut tags = ~[];
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != '<' as u8 {
+            i += 1;
+            loop
+        }
+        if markup.slice_from(i).starts_with("<!--") {
+            match markup.slice_from(i).find_str("-->") {
+                Some(end) => i += end + 3,
+                None => break,
+            }
+            loop
+        }
+
+        // Find the end of the tag, which may not be inside a quoted attribute value.
+        let mut end = i + 1;
+        let mut quote = None;
+        while end < bytes.len() {
+            match quote {
+                Some(q) if bytes[end] == q => quote = None,
+                Some(_) => {}
+                None if bytes[end] == '"' as u8 || bytes[end] == '\'' as u8 => {
+                    quote = Some(bytes[end])
+                }
+                None if bytes[end] == '>' as u8 => break,
+                None => {}
+            }
+            end += 1;
+        }
+        if end >= bytes.len() {
+            break
+        }
+        let contents = markup.slice(i + 1, end);
+        i = end + 1;
+        if !contents.starts_with("!") && !contents.starts_with("?") {
+            tags.push(parse_tag(contents))
+        }
+    }
+    tags
+}
+
+/// Parses the contents of a tag, between its angle brackets.
+fn parse_tag(contents: &str) -> Tag {
+    let is_end = contents.starts_with("/");
+    let is_self_closing = !is_end && contents.ends_with("/");
+    let contents = contents.slice(if is_end { 1 } else { 0 },
+                                  contents.len() - if is_self_closing { 1 } else { 0 });
+    let bytes = contents.as_bytes();
+    let is_space = |b: u8| b == ' ' as u8 || b == '\t' as u8 || b == '\n' as u8 ||
+                           b == '\r' as u8;
+
+    let mut i = 0;
+    while i < bytes.len() && !is_space(bytes[i]) {
+        i += 1
+    }
+    let name = contents.slice_to(i).to_owned();
+
+    let mut attributes = ~[];
+    loop {
+        while i < bytes.len() && is_space(bytes[i]) {
+            i += 1
+        }
+        if i >= bytes.len() {
+            break
+        }
+        let name_start = i;
+        while i < bytes.len() && !is_space(bytes[i]) && bytes[i] != '=' as u8 {
+            i += 1
+        }
+        let attribute_name = contents.slice(name_start, i).to_owned();
+        while i < bytes.len() && is_space(bytes[i]) {
+            i += 1
+        }
+        if i >= bytes.len() || bytes[i] != '=' as u8 {
+            attributes.push((attribute_name, ~""));
+            loop
+        }
+        i += 1;
+        while i < bytes.len() && is_space(bytes[i]) {
+            i += 1
+        }
+        if i >= bytes.len() {
+            attributes.push((attribute_name, ~""));
+            break
+        }
+        let value = if bytes[i] == '"' as u8 || bytes[i] == '\'' as u8 {
+            let quote = bytes[i];
+            let value_start = i + 1;
+            i = value_start;
+            while i < bytes.len() && bytes[i] != quote {
+                i += 1
+            }
+            let value = contents.slice(value_start, i);
+            i += 1;
+            value
+        } else {
+            let value_start = i;
+            while i < bytes.len() && !is_space(bytes[i]) {
+                i += 1
+            }
+            contents.slice(value_start, i)
+        };
+        attributes.push((attribute_name, unescape(value)));
+    }
+
+    Tag {
+        name: name,
+        attributes: attributes,
+        is_end: is_end,
+        is_self_closing: is_self_closing,
+    }
+}
+
+/// Replaces the predefined entities of XML in an attribute value.
+fn unescape(value: &str) -> ~str {
+    value.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"")
+         .replace("&apos;", "'").replace("&amp;", "&")
+}
+
+/// Parses a length in user units. Lengths in pixels are the same; other units aren't supported.
+fn parse_length(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let value = if value.ends_with("px") { value.slice_to(value.len() - 2) } else { value };
+    float::from_str(value).map_move(|length| length as f32)
+}
+
+/// Parses a list of numbers separated by commas or whitespace, as in `viewBox` and `points`.
+fn parse_number_list(value: &str) -> ~[f32] {
+    value.split_iter(|c: char| c == ',' || c.is_whitespace()).filter_map(|number| {
+        if number.is_empty() { None } else { parse_length(number) }
+    }).collect()
+}
+
+/// Parses a paint: `none`, a color in hex or `rgb()` notation, or one of the basic color keywords.
+/// Returns `Some(None)` for `none`, and `None` if the paint is invalid.
+fn parse_paint(value: &str) -> Option<Option<Color>> {
+    let value = value.trim().to_ascii_lower();
+    if value == ~"none" {
+        return Some(None)
+    }
+    if value.starts_with("#") {
+        let digits: ~[u8] = value.slice_from(1).iter().filter_map(|c| c.to_digit(16)).map(|d| {
+            d as u8
+        }).collect();
+        return match digits.len() {
+            _ if digits.len() != value.len() - 1 => None,
+            3 => Some(Some(rgb(digits[0] * 17, digits[1] * 17, digits[2] * 17))),
+            6 => Some(Some(rgb(digits[0] * 16 + digits[1],
+                               digits[2] * 16 + digits[3],
+                               digits[4] * 16 + digits[5]))),
+            _ => None,
+        }
+    }
+    if value.starts_with("rgb(") && value.ends_with(")") {
+        let components = parse_number_list(value.slice(4, value.len() - 1));
+        if components.len() != 3 {
+            return None
+        }
+        let component = |c: f32| c.max(&0.0).min(&255.0) as u8;
+        return Some(Some(rgb(component(components[0]),
+                             component(components[1]),
+                             component(components[2]))))
+    }
+    let color = match value.as_slice() {
+        "black" => rgb(0, 0, 0),
+        "silver" => rgb(192, 192, 192),
+        "gray" | "grey" => rgb(128, 128, 128),
+        "white" => rgb(255, 255, 255),
+        "maroon" => rgb(128, 0, 0),
+        "red" => rgb(255, 0, 0),
+        "purple" => rgb(128, 0, 128),
+        "fuchsia" => rgb(255, 0, 255),
+        "green" => rgb(0, 128, 0),
+        "lime" => rgb(0, 255, 0),
+        "olive" => rgb(128, 128, 0),
+        "yellow" => rgb(255, 255, 0),
+        "navy" => rgb(0, 0, 128),
+        "blue" => rgb(0, 0, 255),
+        "teal" => rgb(0, 128, 128),
+        "aqua" => rgb(0, 255, 255),
+        "orange" => rgb(255, 165, 0),
+        _ => return None,
+    };
+    Some(Some(color))
+}
+
+/// The presentation attributes that shapes inherit from the groups around them.
+#[deriving(Clone)]
+struct Paint {
+    fill: Option<Color>,
+    fill_rule: FillRule,
+    fill_opacity: f32,
+    stroke: Option<Color>,
+    stroke_width: f32,
+    stroke_opacity: f32,
+    dash_array: ~[f32],
+    dash_offset: f32,
+}
+
+impl Paint {
+    fn initial() -> Paint {
+        Paint {
+            fill: Some(rgb(0, 0, 0)),
+            fill_rule: NonZero,
+            fill_opacity: 1.0,
+            stroke: None,
+            stroke_width: 1.0,
+            stroke_opacity: 1.0,
+            dash_array: ~[],
+            dash_offset: 0.0,
+        }
+    }
+
+    /// Returns this paint with the presentation attributes of `tag` applied.
+    fn apply(&self, tag: &Tag) -> Paint {
+        let mut paint = self.clone();
+        for fill in tag.attribute("fill").chain(parse_paint).iter() {
+            paint.fill = *fill
+        }
+        match tag.attribute("fill-rule") {
+            Some("evenodd") => paint.fill_rule = EvenOdd,
+            Some("nonzero") => paint.fill_rule = NonZero,
+            _ => {}
+        }
+        for stroke in tag.attribute("stroke").chain(parse_paint).iter() {
+            paint.stroke = *stroke
+        }
+        for &width in tag.length("stroke-width").iter() {
+            if width >= 0.0 {
+                paint.stroke_width = width
+            }
+        }
+        match tag.attribute("stroke-dasharray") {
+            Some("none") => paint.dash_array = ~[],
+            Some(value) => paint.dash_array = parse_number_list(value),
+            None => {}
+        }
+        for &offset in tag.length("stroke-dashoffset").iter() {
+            paint.dash_offset = offset
+        }
+        let opacity = tag.length("opacity").unwrap_or_default(1.0);
+        for &fill_opacity in tag.length("fill-opacity").iter() {
+            paint.fill_opacity = fill_opacity
+        }
+        for &stroke_opacity in tag.length("stroke-opacity").iter() {
+            paint.stroke_opacity = stroke_opacity
+        }
+        // FIXME: Group opacity should composite the group as a whole, not each of its shapes.
+        paint.fill_opacity = paint.fill_opacity * opacity;
+        paint.stroke_opacity = paint.stroke_opacity * opacity;
+        paint
+    }
+}
+
+/// Returns the path data that draws a basic shape, or the `d` of a `<path>`.
+fn path_data_for_shape(tag: &Tag) -> Option<~str> {
+    let length = |name: &str| tag.length(name).unwrap_or_default(0.0);
+    match tag.name.as_slice() {
+        "path" => tag.attribute("d").map(|d| d.to_owned()),
+        "rect" => {
+            // FIXME: Rounded corners (`rx` and `ry`) are ignored.
+            let (x, y, width, height) = (length("x"), length("y"), length("width"),
+                                         length("height"));
+            if width <= 0.0 || height <= 0.0 {
+                return None
+            }
+            Some(fmt!("M%f %fh%fv%fh%fz", x as float, y as float, width as float,
+                      height as float, -width as float))
+        }
+        "circle" | "ellipse" => {
+            let (rx, ry) = if tag.name.as_slice() == "circle" {
+                (length("r"), length("r"))
+            } else {
+                (length("rx"), length("ry"))
+            };
+            if rx <= 0.0 || ry <= 0.0 {
+                return None
+            }
+            let (cx, cy) = (length("cx"), length("cy"));
+            Some(fmt!("M%f %fA%f %f 0 1 0 %f %fA%f %f 0 1 0 %f %fz",
+                      (cx - rx) as float, cy as float,
+                      rx as float, ry as float, (cx + rx) as float, cy as float,
+                      rx as float, ry as float, (cx - rx) as float, cy as float))
+        }
+        "line" => Some(fmt!("M%f %fL%f %f", length("x1") as float, length("y1") as float,
+                            length("x2") as float, length("y2") as float)),
+        "polyline" | "polygon" => {
+            let points = parse_number_list(tag.attribute("points").unwrap_or_default(""));
+            if points.len() < 4 {
+                return None
+            }
+            let mut data = ~"M";
+            for i in range(0, points.len() / 2) {
+                data.push_str(fmt!("%f %f ", points[i * 2] as float, points[i * 2 + 1] as float))
+            }
+            if tag.name.as_slice() == "polygon" {
+                data.push_char('z')
+            }
+            Some(data)
+        }
+        _ => None,
+    }
+}
+
+/// The mapping from the user units of the root of an SVG image to the pixels of the image, as
+/// its `viewBox` and the default `preserveAspectRatio` of `xMidYMid meet` describe it.
+struct Viewport {
+    scale: f32,
+    translation: Point2D<f32>,
+}
+
+impl Viewport {
+    fn to_pixels(&self, point: Point2D<f32>) -> Point2D<f32> {
+        Point2D(point.x * self.scale + self.translation.x,
+                point.y * self.scale + self.translation.y)
+    }
+}
+
+/// Returns the size of an SVG image in pixels, from the `width` and `height` of its root, or
+/// its `viewBox` if it has no size of its own.
+fn intrinsic_size(root: &Tag, view_box: Option<(f32, f32, f32, f32)>) -> Size2D<uint> {
+    let (default_width, default_height) = DEFAULT_SIZE;
+    let (width, height) = match (root.length("width"), root.length("height"), view_box) {
+        (Some(width), Some(height), _) => (width, height),
+        (Some(width), None, Some((_, _, vw, vh))) => (width, width * vh / vw),
+        (None, Some(height), Some((_, _, vw, vh))) => (height * vw / vh, height),
+        (None, None, Some((_, _, vw, vh))) => (vw, vh),
+        (Some(width), None, None) => (width, default_height as f32),
+        (None, Some(height), None) => (default_width as f32, height),
+        (None, None, None) => (default_width as f32, default_height as f32),
+    };
+    Size2D(width.ceil().max(&1.0) as uint, height.ceil().max(&1.0) as uint)
+}
+
+/// An image being rasterized, as premultiplied BGRA pixels, the layout of decoded images.
+struct Canvas {
+    width: uint,
+    height: uint,
+    pixels: ~[u8],
+}
+
+impl Canvas {
+    /// Paints the inside of the given subpaths, in pixels, with a color. Edges are antialiased by
+    /// sampling several rows in each row of pixels, and by the partial coverage of the pixels at
+    /// the ends of each span.
+    fn fill(&mut self, subpaths: &[Subpath], rule: FillRule, color: Color, opacity: f32) {
+        let mut top = self.height as f32;
+        let mut bottom = 0f32;
+        for subpath in subpaths.iter() {
+            for point in subpath.points.iter() {
+                top = top.min(&point.y);
+                bottom = bottom.max(&point.y);
+            }
+        }
+        let first_row = top.max(&0.0).floor() as uint;
+        let end_row = uint::min(bottom.max(&0.0).ceil() as uint, self.height);
+
+        let sample_weight = 1.0 / (SAMPLES_PER_PIXEL as f32);
+        let mut coverage = vec::from_elem(self.width, 0f32);
+        for row in range(first_row, end_row) {
+            for i in range(0, self.width) {
+                coverage[i] = 0.0
+            }
+            for sample in range(0, SAMPLES_PER_PIXEL) {
+                let y = (row as f32) + ((sample as f32) + 0.5) * sample_weight;
+                for &(left, right) in path::fill_spans(subpaths, rule, y).iter() {
+                    let left = left.max(&0.0);
+                    let right = right.min(&(self.width as f32));
+                    let mut x = left.floor();
+                    while x < right {
+                        let covered = right.min(&(x + 1.0)) - left.max(&x);
+                        coverage[x as uint] += covered * sample_weight;
+                        x = x + 1.0;
+                    }
+                }
+            }
+            for x in range(0, self.width) {
+                if coverage[x] > 0.0 {
+                    self.blend(x, row, color, coverage[x].min(&1.0) * opacity)
+                }
+            }
+        }
+    }
+
+    /// Composites a color with the given alpha over a pixel.
+    fn blend(&mut self, x: uint, y: uint, color: Color, alpha: f32) {
+        let alpha = alpha * color.a;
+        let i = (y * self.width + x) * 4;
+        let source = [color.b, color.g, color.r, 1.0];
+        for channel in range(0, 4) {
+            let destination = (self.pixels[i + channel] as f32) / 255.0;
+            let value = source[channel] * alpha + destination * (1.0 - alpha);
+            self.pixels[i + channel] = (value * 255.0 + 0.5).min(&255.0) as u8;
+        }
+    }
+}
+
+/// Returns the outline of a stroke along the given subpaths, as a quadrilateral for each
+/// segment of each dash. The quadrilaterals all wind the same way, so filling them with the
+/// nonzero rule paints their union.
+///
+/// FIXME: Segments aren't joined, and the ends of the stroke are always butt caps.
+fn stroke_outline(subpaths: &[Subpath], paint: &Paint, viewport: &Viewport) -> ~[Subpath] {
+    let half_width = paint.stroke_width * viewport.scale / 2.0;
+    let mut outline = ~[];
+    for subpath in subpaths.iter() {
+        let pieces = path::dash_polyline(subpath.points, subpath.closed, paint.dash_array,
+                                         paint.dash_offset);
+        for piece in pieces.iter() {
+            for i in range(1, piece.len()) {
+                let a = viewport.to_pixels(piece[i - 1]);
+                let b = viewport.to_pixels(piece[i]);
+                let length = ((b.x - a.x) * (b.x - a.x) + (b.y - a.y) * (b.y - a.y)).sqrt();
+                if length == 0.0 {
+                    loop
+                }
+                let normal = Point2D((a.y - b.y) / length * half_width,
+                                     (b.x - a.x) / length * half_width);
+                outline.push(Subpath {
+                    points: ~[Point2D(a.x + normal.x, a.y + normal.y),
+                              Point2D(b.x + normal.x, b.y + normal.y),
+                              Point2D(b.x - normal.x, b.y - normal.y),
+                              Point2D(a.x - normal.x, a.y - normal.y)],
+                    closed: true,
+                });
+            }
+        }
+    }
+    outline
+}
+
+/// Rasterizes an SVG image, at `size` if it is given, or else at the size the image says it is.
+/// Returns `None` if the markup has no `<svg>` root, or if the image would be empty or too big.
+pub fn rasterize(data: &[u8], size: Option<Size2D<uint>>) -> Option<Image> {
+    // Only the ASCII in the markup matters, so bytes are read as Latin-1, which never fails.
+    let markup: ~str = data.iter().map(|&byte| byte as char).collect();
+    let tags = parse_tags(markup);
+    let root = match tags.iter().position(|tag| !tag.is_end && tag.name.as_slice() == "svg") {
+        Some(root) => root,
+        None => return None,
+    };
+
+    let view_box = parse_number_list(tags[root].attribute("viewBox").unwrap_or_default(""));
+    let view_box = if view_box.len() == 4 && view_box[2] > 0.0 && view_box[3] > 0.0 {
+        Some((view_box[0], view_box[1], view_box[2], view_box[3]))
+    } else {
+        None
+    };
+    let size = match size {
+        Some(size) => size,
+        None => intrinsic_size(&tags[root], view_box),
+    };
+    if size.width == 0 || size.height == 0 || size.width * size.height > MAX_PIXELS {
+        return None
+    }
+    let viewport = match view_box {
+        Some((x, y, width, height)) => {
+            let scale = ((size.width as f32) / width).min(&((size.height as f32) / height));
+            Viewport {
+                scale: scale,
+                translation: Point2D(((size.width as f32) - width * scale) / 2.0 - x * scale,
+                                     ((size.height as f32) - height * scale) / 2.0 - y * scale),
+            }
+        }
+        None => {
+            // Without a view box, user units are pixels of the image at its own size.
+            let own_size = intrinsic_size(&tags[root], None);
+            let scale = ((size.width as f32) / (own_size.width as f32))
+                .min(&((size.height as f32) / (own_size.height as f32)));
+            Viewport {
+                scale: scale,
+                translation: Point2D(0f32, 0f32),
+            }
+        }
+    };
+
+    let mut canvas = Canvas {
+        width: size.width,
+        height: size.height,
+        pixels: vec::from_elem(size.width * size.height * 4, 0u8),
+    };
+
+    // The paint of each open group, innermost last.
+    let mut paints = ~[Paint::initial().apply(&tags[root])];
+    for tag in tags.slice_from(root + 1).iter() {
+        if tag.is_end {
+            match tag.name.as_slice() {
+                "svg" if paints.len() == 1 => break,
+                "g" | "svg" | "a" if paints.len() > 1 => { paints.pop(); }
+                _ => {}
+            }
+            loop
+        }
+        let paint = paints[paints.len() - 1].apply(tag);
+        match tag.name.as_slice() {
+            "g" | "svg" | "a" if !tag.is_self_closing => {
+                paints.push(paint);
+                loop
+            }
+            _ => {}
+        }
+
+        let data = match path_data_for_shape(tag) {
+            Some(data) => data,
+            None => loop,
+        };
+        let tolerance = 0.25 / viewport.scale;
+        let subpaths = path::flatten(path::parse_path_data(data), tolerance);
+        for &color in paint.fill.iter() {
+            let pixels = do subpaths.map |subpath| {
+                Subpath {
+                    points: subpath.points.map(|&point| viewport.to_pixels(point)),
+                    closed: subpath.closed,
+                }
+            };
+            canvas.fill(pixels, paint.fill_rule, color, paint.fill_opacity)
+        }
+        for &color in paint.stroke.iter() {
+            if paint.stroke_width > 0.0 {
+                let outline = stroke_outline(subpaths, &paint, &viewport);
+                canvas.fill(outline, NonZero, color, paint.stroke_opacity)
+            }
+        }
+    }
+
+    Some(Image(canvas.width, canvas.height, 4, canvas.pixels))
+}
+
+#[cfg(test)]
+fn pixel(image: &Image, x: uint, y: uint) -> ~[u8] {
+    let i = (y * image.width + x) * 4;
+    image.data.slice(i, i + 4).to_owned()
+}
+
+#[test]
+fn test_intrinsic_size() {
+    let image = rasterize(bytes!("<svg width='20' height='10'></svg>"), None).unwrap();
+    assert!(image.width == 20 && image.height == 10);
+    let image = rasterize(bytes!("<svg viewBox='0 0 40 20' width='10px'/>"), None).unwrap();
+    assert!(image.width == 10 && image.height == 5);
+    let image = rasterize(bytes!("<?xml version='1.0'?><svg></svg>"), None).unwrap();
+    assert!(image.width == 300 && image.height == 150);
+    assert!(rasterize(bytes!("<html></html>"), None).is_none());
+}
+
+#[test]
+fn test_fill() {
+    let svg = bytes!("<svg xmlns='http://www.w3.org/2000/svg' width='10' height='10'>",
+                     "<g fill='#f00'><rect x='0' y='0' width='5' height='10'/></g>",
+                     "<rect x='5' y='0' width='5' height='10' fill='none'/></svg>");
+    let image = rasterize(svg, None).unwrap();
+    // Red, in BGRA order, and transparent where nothing was drawn.
+    assert!(pixel(&image, 2, 5) == ~[0, 0, 255, 255]);
+    assert!(pixel(&image, 7, 5) == ~[0, 0, 0, 0]);
+}
+
+#[test]
+fn test_view_box_scales_to_size() {
+    let svg = bytes!("<svg viewBox='0 0 2 2'><rect width='1' height='1' fill='blue'/></svg>");
+    let image = rasterize(svg, Some(Size2D(20, 20))).unwrap();
+    assert!(pixel(&image, 5, 5) == ~[255, 0, 0, 255]);
+    assert!(pixel(&image, 15, 15) == ~[0, 0, 0, 0]);
+}
@@ -0,0 +1,271 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Extended grapheme clusters, following the Unicode Text Segmentation rules (UAX #29 § 3).
+//!
+//! A grapheme cluster is what a reader thinks of as one character: a base character with its
+//! combining marks, a Hangul syllable made of jamo, a flag made of two regional indicators, or an
+//! emoji sequence joined with zero width joiners. Lines must not be broken inside a cluster, and
+//! the caret moves over a whole cluster at a time.
+//!
+//! As with line breaking, properties are only assigned to the characters that matter most in
+//! practice; characters without a more specific property can't be combined with their
+//! neighbours.
+
+use std::uint;
+use std::vec;
+
+/// A grapheme cluster break property, as defined in UAX #29 § 3.1.
+#[deriving(Clone, Eq)]
+enum GraphemeClass {
+    CR,
+    LF,
+    Control,
+    /// Combining marks, variation selectors, and emoji modifiers.
+    Extend,
+    /// Zero width joiner.
+    ZWJ,
+    RegionalIndicator,
+    /// Characters that attach to the character after them.
+    Prepend,
+    /// Spacing combining marks.
+    SpacingMark,
+    /// Hangul leading consonant jamo.
+    L,
+    /// Hangul vowel jamo.
+    V,
+    /// Hangul trailing consonant jamo.
+    T,
+    /// Precomposed Hangul syllables without a trailing consonant.
+    LV,
+    /// Precomposed Hangul syllables with a trailing consonant.
+    LVT,
+    ExtendedPictographic,
+    Other,
+}
+
+fn grapheme_class(ch: char) -> GraphemeClass {
+    match ch as u32 {
+        0x000D => CR,
+        0x000A => LF,
+        0x0000..0x0009 | 0x000B | 0x000C | 0x000E..0x001F | 0x007F..0x009F | 0x00AD => Control,
+        0x061C | 0x180E | 0x200B | 0x200E | 0x200F | 0x2028..0x202E | 0x2060..0x206F => Control,
+        0xFEFF | 0xFFF0..0xFFFB => Control,
+        0x200D => ZWJ,
+        0x0300..0x036F | 0x0483..0x0489 | 0x0591..0x05BD | 0x05BF | 0x05C1 | 0x05C2 => Extend,
+        0x05C4 | 0x05C5 | 0x05C7 | 0x0610..0x061A | 0x064B..0x065F | 0x0670 => Extend,
+        0x06D6..0x06DC | 0x06DF..0x06E4 | 0x06E7 | 0x06E8 | 0x06EA..0x06ED => Extend,
+        0x0900..0x0902 | 0x093A | 0x093C | 0x0941..0x0948 | 0x094D | 0x0951..0x0957 => Extend,
+        0x0962 | 0x0963 | 0x0981 | 0x09BC | 0x09BE | 0x09C1..0x09C4 | 0x09CD | 0x09D7 => Extend,
+        0x09E2 | 0x09E3 | 0x0E31 | 0x0E34..0x0E3A | 0x0E47..0x0E4E => Extend,
+        0x1AB0..0x1AFF | 0x1DC0..0x1DFF | 0x200C | 0x20D0..0x20FF | 0x302A..0x302F => Extend,
+        0x3099 | 0x309A | 0xFE00..0xFE0F | 0xFE20..0xFE2F | 0x1F3FB..0x1F3FF => Extend,
+        0xE0020..0xE007F | 0xE0100..0xE01EF => Extend,
+        0x0903 | 0x093B | 0x093E..0x0940 | 0x0949..0x094C | 0x094E | 0x094F => SpacingMark,
+        0x0982 | 0x0983 | 0x09BF | 0x09C0 | 0x09C7 | 0x09C8 | 0x09CB | 0x09CC | 0x0E33 => {
+            SpacingMark
+        }
+        0x0600..0x0605 | 0x06DD | 0x070F | 0x08E2 | 0x110BD => Prepend,
+        0x1F1E6..0x1F1FF => RegionalIndicator,
+        0x1100..0x115F | 0xA960..0xA97C => L,
+        0x1160..0x11A7 | 0xD7B0..0xD7C6 => V,
+        0x11A8..0x11FF | 0xD7CB..0xD7FB => T,
+        // Syllables without a trailing consonant come first in each block of 28.
+        0xAC00..0xD7A3 if (ch as u32 - 0xAC00) % 28 == 0 => LV,
+        0xAC00..0xD7A3 => LVT,
+        0x00A9 | 0x00AE | 0x203C | 0x2049 | 0x2122 | 0x2139 | 0x2194..0x2199 => {
+            ExtendedPictographic
+        }
+        0x21A9 | 0x21AA | 0x231A | 0x231B | 0x2328 | 0x23CF | 0x23E9..0x23F3 => {
+            ExtendedPictographic
+        }
+        0x23F8..0x23FA | 0x24C2 | 0x25AA | 0x25AB | 0x25B6 | 0x25C0 | 0x25FB..0x25FE => {
+            ExtendedPictographic
+        }
+        0x2600..0x27BF | 0x2934 | 0x2935 | 0x2B05..0x2B07 | 0x2B1B | 0x2B1C | 0x2B50 => {
+            ExtendedPictographic
+        }
+        0x2B55 | 0x3030 | 0x303D | 0x3297 | 0x3299 | 0x1F000..0x1F0FF => ExtendedPictographic,
+        0x1F10D..0x1F10F | 0x1F12F | 0x1F16C..0x1F171 | 0x1F17E | 0x1F17F | 0x1F18E => {
+            ExtendedPictographic
+        }
+        0x1F191..0x1F19A | 0x1F201..0x1F20F | 0x1F21A | 0x1F22F | 0x1F232..0x1F23A => {
+            ExtendedPictographic
+        }
+        0x1F23C..0x1F23F | 0x1F249..0x1F3FA | 0x1F400..0x1F64F | 0x1F680..0x1F6FF => {
+            ExtendedPictographic
+        }
+        0x1F900..0x1FAFF | 0x1FC00..0x1FFFD => ExtendedPictographic,
+        _ => Other,
+    }
+}
+
+/// Returns, for each character of `text`, whether a grapheme cluster starts at it.
+pub fn cluster_starts(text: &str) -> ~[bool] {
+    let classes: ~[GraphemeClass] = text.iter().map(grapheme_class).collect();
+    let mut starts = vec::from_elem(classes.len(), true);
+
+    // Whether the characters before the current one are an emoji followed by any number of
+    // extending characters and a zero width joiner, for GB11.
+    let mut after_emoji = false;
+    // The number of regional indicators immediately before the current character, for GB12 and
+    // GB13.
+    let mut regional_indicators = 0u;
+
+    // GB1: a cluster starts at the start of text. GB2 (one ends at the end) is implicit.
+    for i in range(1, classes.len()) {
+        let (before, after) = (classes[i - 1], classes[i]);
+        after_emoji = match before {
+            ExtendedPictographic => true,
+            Extend => after_emoji,
+            ZWJ => after_emoji && (i < 2 || classes[i - 2] != ZWJ),
+            _ => false,
+        };
+        regional_indicators = match before {
+            RegionalIndicator => regional_indicators + 1,
+            _ => 0,
+        };
+        starts[i] = is_cluster_boundary(before, after, after_emoji, regional_indicators);
+    }
+    starts
+}
+
+/// Applies the rules GB3 to GB999 to decide whether there is a cluster boundary between a
+/// character of class `before` and one of class `after`.
+fn is_cluster_boundary(before: GraphemeClass, after: GraphemeClass, after_emoji: bool,
+                       regional_indicators: uint) -> bool {
+    match (before, after) {
+        // GB3: CR LF is a single cluster.
+        (CR, LF) => false,
+        // GB4, GB5: otherwise, break around controls and newlines.
+        (CR, _) | (LF, _) | (Control, _) | (_, CR) | (_, LF) | (_, Control) => true,
+        // GB6 to GB8: Hangul syllable sequences.
+        (L, L) | (L, V) | (L, LV) | (L, LVT) => false,
+        (LV, V) | (LV, T) | (V, V) | (V, T) => false,
+        (LVT, T) | (T, T) => false,
+        // GB9, GB9a, GB9b: extending characters and spacing marks attach to the character
+        // before them, and prepended characters to the character after them.
+        (_, Extend) | (_, ZWJ) | (_, SpacingMark) | (Prepend, _) => false,
+        // GB11: emoji joined with zero width joiners.
+        (ZWJ, ExtendedPictographic) => !after_emoji,
+        // GB12, GB13: regional indicators pair up into flags.
+        (RegionalIndicator, RegionalIndicator) => regional_indicators % 2 == 0,
+        // GB999
+        (_, _) => true,
+    }
+}
+
+/// Returns the byte offsets at which the grapheme clusters of `text` start, followed by the
+/// length of `text`.
+fn cluster_byte_offsets(text: &str) -> ~[uint] {
+    let starts = cluster_starts(text);
+    let mut offsets: ~[uint] = text.char_offset_iter().enumerate().filter_map(|(i, (byte, _))| {
+        if starts[i] { Some(byte) } else { None }
+    }).collect();
+    offsets.push(text.len());
+    offsets
+}
+
+/// An iterator over the grapheme clusters of a string.
+pub struct GraphemeIterator<'self> {
+    priv text: &'self str,
+    priv offsets: ~[uint],
+    priv index: uint,
+}
+
+impl<'self> Iterator<&'self str> for GraphemeIterator<'self> {
+    fn next(&mut self) -> Option<&'self str> {
+        if self.index + 1 >= self.offsets.len() {
+            return None
+        }
+        let cluster = self.text.slice(self.offsets[self.index], self.offsets[self.index + 1]);
+        self.index += 1;
+        Some(cluster)
+    }
+}
+
+/// Returns an iterator over the grapheme clusters of `text`.
+pub fn graphemes<'a>(text: &'a str) -> GraphemeIterator<'a> {
+    GraphemeIterator {
+        text: text,
+        offsets: cluster_byte_offsets(text),
+        index: 0,
+    }
+}
+
+/// Returns the slice of `text` made of the grapheme clusters from `begin` up to but not
+/// including `end`. Unlike slicing by characters, this never separates a character from the marks
+/// that combine with it.
+pub fn slice_graphemes<'a>(text: &'a str, begin: uint, end: uint) -> &'a str {
+    let offsets = cluster_byte_offsets(text);
+    assert!(begin <= end && end < offsets.len());
+    text.slice(offsets[begin], offsets[end])
+}
+
+/// Returns the character offset of the first cluster boundary after `offset`, which is where the
+/// caret goes when it moves forward from `offset`.
+pub fn next_cluster_boundary(text: &str, offset: uint) -> uint {
+    let starts = cluster_starts(text);
+    let mut next = offset + 1;
+    while next < starts.len() && !starts[next] {
+        next += 1;
+    }
+    uint::min(next, starts.len())
+}
+
+/// Returns the character offset of the last cluster boundary before `offset`, which is where the
+/// caret goes when it moves back from `offset`.
+pub fn previous_cluster_boundary(text: &str, offset: uint) -> uint {
+    let starts = cluster_starts(text);
+    let mut previous = uint::min(offset, starts.len());
+    while previous > 0 {
+        previous -= 1;
+        if starts[previous] {
+            break
+        }
+    }
+    previous
+}
+
+#[cfg(test)]
+fn cluster_positions(text: &str) -> ~[uint] {
+    cluster_starts(text).iter().enumerate().filter_map(|(i, &start)| {
+        if start { Some(i) } else { None }
+    }).collect()
+}
+
+#[test]
+fn test_combining_marks() {
+    // "e" followed by a combining acute accent.
+    assert!(cluster_positions("cafe\u0301s") == ~[0, 1, 2, 3, 5]);
+    assert!(cluster_positions("\u0301a") == ~[0, 1]);
+    assert!(cluster_positions("a\r\nb") == ~[0, 1, 3]);
+}
+
+#[test]
+fn test_hangul() {
+    // A syllable spelled with conjoining jamo, then a precomposed one.
+    assert!(cluster_positions("\u1100\u1161\u11a8\uac00") == ~[0, 3]);
+}
+
+#[test]
+fn test_emoji_sequences() {
+    // Man, zero width joiner, woman, zero width joiner, girl.
+    assert!(cluster_positions("\U0001f468\u200d\U0001f469\u200d\U0001f467") == ~[0]);
+    // A thumbs up with a skin tone modifier.
+    assert!(cluster_positions("\U0001f44d\U0001f3fd!") == ~[0, 2]);
+    // Two flags.
+    assert!(cluster_positions("\U0001f1eb\U0001f1f7\U0001f1e9\U0001f1ea") == ~[0, 2]);
+}
+
+#[test]
+fn test_slice_graphemes() {
+    let text = "ne\u0301e!";
+    assert!(graphemes(text).collect::<~[&str]>() == ~["n", "e\u0301", "e", "!"]);
+    assert!(slice_graphemes(text, 1, 3) == "e\u0301e");
+    assert!(next_cluster_boundary(text, 1) == 3);
+    assert!(previous_cluster_boundary(text, 3) == 1);
+    assert!(next_cluster_boundary(text, 4) == 5);
+    assert!(previous_cluster_boundary(text, 0) == 0);
+}
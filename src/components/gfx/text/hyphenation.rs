@@ -0,0 +1,243 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Automatic hyphenation, for `hyphens: auto`.
+//!
+//! Words are hyphenated with Liang's algorithm, using the pattern files TeX uses. The
+//! hyphenation pass does not break lines itself: it inserts soft hyphens (U+00AD) at the
+//! hyphenation points it finds, and line breaking then treats them like soft hyphens written by
+//! the author.
+
+use opts::HyphenationPatternFile;
+
+use std::ascii::StrAsciiExt;
+use std::hashmap::HashMap;
+use std::io;
+use std::uint;
+use std::vec;
+
+/// SOFT HYPHEN, which marks a place where a word may be broken with a hyphen.
+pub static SOFT_HYPHEN: char = '\u00ad';
+
+/// Finds the hyphenation points of words in one language.
+pub struct Hyphenator {
+    /// Maps the letters of each pattern to the priorities of the breaks before, between, and after
+    /// them. Odd priorities allow a break; even ones forbid it.
+    priv patterns: HashMap<~str, ~[u8]>,
+    /// Words whose hyphenation points are given explicitly, mapped to those points.
+    priv exceptions: HashMap<~str, ~[uint]>,
+    /// The length in characters of the longest pattern.
+    priv max_pattern_length: uint,
+    /// The minimum number of characters to leave before a hyphen.
+    left_min: uint,
+    /// The minimum number of characters to carry over after a hyphen.
+    right_min: uint,
+}
+
+impl Hyphenator {
+    /// Builds a hyphenator from the contents of a TeX-style pattern file. Patterns such as
+    /// `.ach4` or `1tion` are separated by whitespace, with digits giving the priority of a break
+    /// between the letters around them and `.` anchoring the pattern to the start or end of a
+    /// word. Entries that contain hyphens, such as `as-so-ciate`, are exceptions. Lines starting
+    /// with `%` are comments.
+    pub fn from_patterns(source: &str) -> Hyphenator {
+        let mut hyphenator = Hyphenator {
+            patterns: HashMap::new(),
+            exceptions: HashMap::new(),
+            max_pattern_length: 0,
+            left_min: 2,
+            right_min: 3,
+        };
+        for line in source.line_iter() {
+            if line.starts_with("%") {
+                loop
+            }
+            for entry in line.word_iter() {
+                if entry.contains_char('-') {
+                    hyphenator.add_exception(entry)
+                } else {
+                    hyphenator.add_pattern(entry)
+                }
+            }
+        }
+        hyphenator
+    }
+
+    fn add_pattern(&mut self, pattern: &str) {
+        let mut letters = ~"";
+        let mut priorities = ~[0u8];
+        for ch in pattern.iter() {
+            match ch.to_digit(10) {
+                Some(priority) => priorities[priorities.len() - 1] = priority as u8,
+                None => {
+                    letters.push_char(ch);
+                    priorities.push(0);
+                }
+            }
+        }
+        let length = letters.char_len();
+        self.max_pattern_length = uint::max(self.max_pattern_length, length);
+        self.patterns.insert(letters.to_ascii_lower(), priorities);
+    }
+
+    fn add_exception(&mut self, word: &str) {
+        let mut letters = ~"";
+        let mut points = ~[];
+        for ch in word.iter() {
+            if ch == '-' {
+                points.push(letters.char_len())
+            } else {
+                letters.push_char(ch)
+            }
+        }
+        self.exceptions.insert(letters.to_ascii_lower(), points);
+    }
+
+    /// Returns the character offsets in `word` before which a hyphen may be inserted.
+    pub fn hyphenation_points(&self, word: &str) -> ~[uint] {
+        let word = word.to_ascii_lower();
+        let length = word.char_len();
+        if length < self.left_min + self.right_min {
+            return ~[]
+        }
+        match self.exceptions.find(&word) {
+            Some(points) => return points.clone(),
+            None => {}
+        }
+
+        // Patterns are matched against the word with a `.` on either side, so the priority of
+        // the break before character `i` of the word ends up in `priorities[i + 1]`.
+        let chars: ~[char] = fmt!(".%s.", word).iter().collect();
+        let mut priorities = vec::from_elem(chars.len() + 1, 0u8);
+        for start in range(0, chars.len()) {
+            let max_end = uint::min(chars.len(), start + self.max_pattern_length);
+            let mut substring = ~"";
+            for end in range(start + 1, max_end + 1) {
+                substring.push_char(chars[end - 1]);
+                match self.patterns.find(&substring) {
+                    Some(pattern) => {
+                        for (i, &priority) in pattern.iter().enumerate() {
+                            if priority > priorities[start + i] {
+                                priorities[start + i] = priority
+                            }
+                        }
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        range(self.left_min, length - self.right_min + 1).filter(|&i| {
+            priorities[i + 1] % 2 == 1
+        }).collect()
+    }
+
+    /// Returns a copy of `text` with a soft hyphen at each hyphenation point of each word. Words
+    /// that already contain soft hyphens are left alone, since the author has hyphenated them.
+    pub fn insert_soft_hyphens(&self, text: &str) -> ~str {
+        let mut result = ~"";
+        let mut word = ~"";
+        for ch in text.iter() {
+            if ch.is_alphabetic() || ch == SOFT_HYPHEN {
+                word.push_char(ch);
+                loop
+            }
+            self.push_hyphenated_word(word, &mut result);
+            word = ~"";
+            result.push_char(ch);
+        }
+        self.push_hyphenated_word(word, &mut result);
+        result
+    }
+
+    fn push_hyphenated_word(&self, word: &str, result: &mut ~str) {
+        if word.contains_char(SOFT_HYPHEN) {
+            result.push_str(word);
+            return
+        }
+        let points = self.hyphenation_points(word);
+        let mut points_iter = points.iter().peekable();
+        for (i, ch) in word.iter().enumerate() {
+            if points_iter.peek() == Some(& &i) {
+                result.push_char(SOFT_HYPHEN);
+                points_iter.next();
+            }
+            result.push_char(ch);
+        }
+    }
+}
+
+/// The hyphenators for each language that has hyphenation patterns.
+pub struct Hyphenators {
+    priv by_lang: HashMap<~str, Hyphenator>,
+}
+
+impl Hyphenators {
+    /// Loads the given pattern files. Files that can't be read are skipped.
+    pub fn new(files: &[HyphenationPatternFile]) -> Hyphenators {
+        let mut hyphenators = Hyphenators {
+            by_lang: HashMap::new(),
+        };
+        for file in files.iter() {
+            match io::read_whole_file_str(&Path(file.path)) {
+                Ok(source) => hyphenators.add(file.lang, Hyphenator::from_patterns(source)),
+                Err(error) => {
+                    error!("failed to load hyphenation patterns for `%s` from %s: %s",
+                           file.lang, file.path, error)
+                }
+            }
+        }
+        hyphenators
+    }
+
+    pub fn add(&mut self, lang: &str, hyphenator: Hyphenator) {
+        self.by_lang.insert(lang.to_ascii_lower(), hyphenator);
+    }
+
+    /// Returns the hyphenator for content in the given language. Patterns for a language also
+    /// apply to its more specific variants, so `en` covers `en-GB`.
+    pub fn find<'a>(&'a self, lang: Option<&str>) -> Option<&'a Hyphenator> {
+        for lang in lang.iter() {
+            let mut lang = lang.to_ascii_lower();
+            loop {
+                match self.by_lang.find(&lang) {
+                    Some(hyphenator) => return Some(hyphenator),
+                    None => {}
+                }
+                match lang.rfind('-') {
+                    Some(i) => {
+                        let parent = lang.slice_to(i).to_owned();
+                        lang = parent;
+                    }
+                    None => break,
+                }
+            }
+        }
+        None
+    }
+}
+
+#[test]
+fn test_hyphenation_points() {
+    // The patterns that hyphenate "hyphenation" in the standard English patterns.
+    let hyphenator = Hyphenator::from_patterns("% test patterns\n\
+                                                hy3ph he2n hena4 hen5at 1na n2at 1tio 2io o2n\n\
+                                                ta-ble");
+    assert!(hyphenator.hyphenation_points("hyphenation") == ~[2, 6]);
+    assert!(hyphenator.hyphenation_points("Hyphenation") == ~[2, 6]);
+    assert!(hyphenator.hyphenation_points("table") == ~[2]);
+    assert!(hyphenator.hyphenation_points("hen") == ~[]);
+    assert!(hyphenator.insert_soft_hyphens("hyphenation!") == ~"hy\u00adphen\u00adation!");
+    assert!(hyphenator.insert_soft_hyphens("hy\u00adphenation") == ~"hy\u00adphenation");
+}
+
+#[test]
+fn test_hyphenators_find() {
+    let mut hyphenators = Hyphenators::new([]);
+    hyphenators.add("en", Hyphenator::from_patterns("1na"));
+    assert!(hyphenators.find(Some("en-GB")).is_some());
+    assert!(hyphenators.find(Some("EN")).is_some());
+    assert!(hyphenators.find(Some("fr")).is_none());
+    assert!(hyphenators.find(None).is_none());
+}
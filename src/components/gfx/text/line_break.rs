@@ -0,0 +1,337 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Line break opportunities, following the Unicode Line Breaking Algorithm (UAX #14).
+//!
+//! This is a subset of the full algorithm: character classes are only assigned for the
+//! characters that matter most in practice, and some of the rarer rules (regional indicators,
+//! Korean syllable blocks, Hebrew letters, and dictionary-based breaking of South East Asian
+//! scripts) are not implemented. Characters without a more specific class are treated as
+//! alphabetic.
+
+use std::vec;
+
+/// A line breaking class, as defined in UAX #14 § 5.1.
+#[deriving(Clone, Eq)]
+pub enum LineBreakClass {
+    /// Mandatory break.
+    BK,
+    /// Carriage return.
+    CR,
+    /// Line feed.
+    LF,
+    /// Next line.
+    NL,
+    /// Space.
+    SP,
+    /// Zero width space.
+    ZW,
+    /// Word joiner.
+    WJ,
+    /// Non-breaking ("glue").
+    GL,
+    /// Break after.
+    BA,
+    /// Break before.
+    BB,
+    /// Hyphen.
+    HY,
+    /// Close punctuation.
+    CL,
+    /// Close parenthesis.
+    CP,
+    /// Exclamation/interrogation.
+    EX,
+    /// Inseparable.
+    IN,
+    /// Nonstarter.
+    NS,
+    /// Open punctuation.
+    OP,
+    /// Quotation.
+    QU,
+    /// Infix numeric separator.
+    IS,
+    /// Numeric.
+    NU,
+    /// Postfix numeric.
+    PO,
+    /// Prefix numeric.
+    PR,
+    /// Symbols allowing break after.
+    SY,
+    /// Alphabetic.
+    AL,
+    /// Ideographic.
+    ID,
+    /// Combining mark.
+    CM,
+    /// Complex context (South East Asian).
+    SA,
+}
+
+/// The values of the `word-break` property, which control whether lines may be broken between
+/// the letters of words.
+#[deriving(Clone, Eq)]
+pub enum WordBreak {
+    /// Letters are only broken between where the rules of UAX #14 allow, which is between
+    /// ideographs but not inside alphabetic words.
+    WordBreakNormal,
+    /// Lines may be broken between any two letters, as they are between ideographs.
+    WordBreakBreakAll,
+    /// Lines may not be broken between letters, even between ideographs.
+    WordBreakKeepAll,
+}
+
+/// Returns the line breaking class of a character.
+pub fn line_break_class(ch: char) -> LineBreakClass {
+    match ch as u32 {
+        0x000A => LF,
+        0x000D => CR,
+        0x000B | 0x000C | 0x2028 | 0x2029 => BK,
+        0x0085 => NL,
+        0x0020 => SP,
+        0x0009 => BA,
+        0x200B => ZW,
+        0x2060 | 0xFEFF => WJ,
+        0x00A0 | 0x034F | 0x2007 | 0x2011 | 0x202F | 0x0F0C => GL,
+        0x002D => HY,
+        0x00AD | 0x058A | 0x2010 | 0x2012 | 0x2013 | 0x2014 | 0x1680 => BA,
+        0x2000..0x2006 | 0x2008..0x200A => BA,
+        0x00B4 | 0x02C8 | 0x02CC | 0x02DF | 0x0F01..0x0F04 => BB,
+        0x0028 | 0x005B | 0x007B | 0x00A1 | 0x00BF | 0x201A | 0x201E | 0x2045 => OP,
+        0x3008 | 0x300A | 0x300C | 0x300E | 0x3010 | 0x3014 | 0x3016 | 0x3018 | 0x301A => OP,
+        0xFF08 | 0xFF3B | 0xFF5B | 0xFF5F | 0xFF62 => OP,
+        0x0029 | 0x005D => CP,
+        0x007D | 0x2046 | 0x3001 | 0x3002 | 0xFE50 | 0xFE52 | 0xFF0C | 0xFF0E | 0xFF61 => CL,
+        0x3009 | 0x300B | 0x300D | 0x300F | 0x3011 | 0x3015 | 0x3017 | 0x3019 | 0x301B => CL,
+        0xFF09 | 0xFF3D | 0xFF5D | 0xFF60 | 0xFF63 | 0xFF64 => CL,
+        0x0021 | 0x003F | 0x05C6 | 0x061B | 0x061E | 0x061F | 0x06D4 | 0xFF01 | 0xFF1F => EX,
+        0x2024..0x2026 | 0x22EF | 0xFE19 => IN,
+        0x17D6 | 0x203C | 0x203D | 0x2047..0x2049 | 0x3005 | 0x301C | 0x303B | 0x303C => NS,
+        0x309B..0x309E | 0x30A0 | 0x30FB | 0x30FD | 0x30FE | 0xFF1A | 0xFF1B | 0xFF65 => NS,
+        0x0022 | 0x0027 | 0x00AB | 0x00BB | 0x2018 | 0x2019 | 0x201B..0x201D | 0x201F => QU,
+        0x2039 | 0x203A | 0x275B..0x275E => QU,
+        0x002C | 0x002E | 0x003A | 0x003B | 0x037E | 0x0589 | 0x060C | 0x060D | 0x2044 => IS,
+        0x002F => SY,
+        0x0030..0x0039 | 0x0660..0x0669 | 0x06F0..0x06F9 | 0x0966..0x096F => NU,
+        0x09E6..0x09EF | 0x0E50..0x0E59 | 0xFF10..0xFF19 => NU,
+        0x0025 | 0x00A2 | 0x00B0 | 0x2030..0x2037 | 0x2103 | 0x2109 | 0xFF05 | 0xFFE0 => PO,
+        0x0024 | 0x002B | 0x005C | 0x00A3..0x00A5 | 0x00B1 | 0x20A0..0x20CF => PR,
+        0x2116 | 0xFF04 | 0xFFE1 | 0xFFE5 | 0xFFE6 => PR,
+        0x0300..0x036F | 0x0483..0x0489 | 0x0591..0x05BD | 0x05BF | 0x05C1 | 0x05C2 => CM,
+        0x05C4 | 0x05C5 | 0x05C7 | 0x0610..0x061A | 0x064B..0x065F | 0x0670 => CM,
+        0x06D6..0x06DC | 0x06DF..0x06E4 | 0x06E7 | 0x06E8 | 0x06EA..0x06ED => CM,
+        0x0900..0x0903 | 0x093A..0x093C | 0x093E..0x094F | 0x0951..0x0957 | 0x0962 | 0x0963 => CM,
+        0x0981..0x0983 | 0x09BC | 0x09BE..0x09CD | 0x09D7 | 0x09E2 | 0x09E3 => CM,
+        0x1AB0..0x1AFF | 0x1DC0..0x1DFF | 0x200C..0x200F | 0x202A..0x202E | 0x2066..0x2069 => CM,
+        0x20D0..0x20FF | 0x302A..0x302F | 0x3099 | 0x309A | 0xFE00..0xFE0F | 0xFE20..0xFE2F => CM,
+        0x0E00..0x0E7F | 0x0E80..0x0EFF | 0x1000..0x109F | 0x1780..0x17FF => SA,
+        0x2E80..0x2FFF | 0x3003 | 0x3004 | 0x3006 | 0x3007 | 0x3012 | 0x3013 | 0x3020..0x3029 => ID,
+        0x3030..0x303A | 0x303D..0x303F | 0x3040..0x309F | 0x30A1..0x30FA | 0x30FC => ID,
+        0x30FF..0x31FF | 0x3200..0x4DBF | 0x4E00..0x9FFF | 0xA000..0xA4CF | 0xAC00..0xD7A3 => ID,
+        0xF900..0xFAFF | 0xFE30..0xFE4F | 0xFF02..0xFF03 | 0xFF06 | 0xFF07 | 0xFF0A | 0xFF0B => ID,
+        0xFF0D | 0xFF0F | 0xFF1C..0xFF1E | 0xFF20..0xFF3A | 0xFF3C | 0xFF3E..0xFF5A => ID,
+        0xFF5C | 0xFF5E | 0x1F000..0x1FAFF | 0x20000..0x3FFFD => ID,
+        _ => AL,
+    }
+}
+
+/// Returns, for each character of `text`, whether the line may be broken before it.
+pub fn break_opportunities(text: &str) -> ~[bool] {
+    break_opportunities_for_word_break(text, WordBreakNormal)
+}
+
+/// Returns, for each character of `text`, whether the line may be broken before it under the
+/// given `word-break`.
+pub fn break_opportunities_for_word_break(text: &str, word_break: WordBreak) -> ~[bool] {
+    // LB1: South East Asian scripts would need a dictionary to find word boundaries; until then,
+    // they break like alphabetic text. `word-break: break-all` treats letters and numbers as
+    // ideographs, which may be broken between, and `keep-all` treats ideographs as letters.
+    let classes: ~[LineBreakClass] = text.iter().map(|ch| {
+        match (line_break_class(ch), word_break) {
+            (AL, WordBreakBreakAll) | (NU, WordBreakBreakAll) | (SA, WordBreakBreakAll) => ID,
+            (SA, _) => AL,
+            (ID, WordBreakKeepAll) => AL,
+            (class, _) => class,
+        }
+    }).collect();
+
+    let mut breaks = vec::from_elem(classes.len(), false);
+    if classes.len() == 0 {
+        return breaks
+    }
+
+    // LB10: a combining mark with nothing to attach to is treated as alphabetic.
+    let mut prev = match classes[0] {
+        CM => AL,
+        class => class,
+    };
+    // The class of the last character that is not a space.
+    let mut before_spaces = prev;
+
+    // LB2: never break at the start of text. LB3 (always break at the end) is implicit.
+    for i in range(1, classes.len()) {
+        let mut class = classes[i];
+
+        // LB9: combining marks take on the class of the character they attach to.
+        if class == CM {
+            match prev {
+                BK | CR | LF | NL | SP | ZW => class = AL,
+                _ => { loop }
+            }
+        }
+
+        breaks[i] = can_break_between(prev, class, before_spaces);
+
+        prev = class;
+        if class != SP {
+            before_spaces = class;
+        }
+    }
+    breaks
+}
+
+/// Applies the pair rules LB4 to LB31 to decide whether a line may be broken between a character
+/// of class `before` and one of class `after`. `before_spaces` is the class of the last character
+/// before `after` that is not a space, for the rules that look through spaces.
+fn can_break_between(before: LineBreakClass, after: LineBreakClass, before_spaces: LineBreakClass)
+                     -> bool {
+    match (before, after) {
+        // LB4, LB5: break after hard line breaks, but not inside CR LF.
+        (BK, _) => return true,
+        (CR, LF) => return false,
+        (CR, _) | (LF, _) | (NL, _) => return true,
+        // LB6: don't break before hard line breaks.
+        (_, BK) | (_, CR) | (_, LF) | (_, NL) => return false,
+        // LB7: don't break before spaces or zero width space.
+        (_, SP) | (_, ZW) => return false,
+        _ => {}
+    }
+
+    // LB8: break after zero width space, even if spaces follow it.
+    if before_spaces == ZW {
+        return true
+    }
+
+    match (before, after) {
+        // LB11: don't break around word joiners.
+        (WJ, _) | (_, WJ) => return false,
+        // LB12: don't break after non-breaking characters.
+        (GL, _) => return false,
+        // LB12a: don't break before them either, except after spaces and hyphens.
+        (SP, GL) | (BA, GL) | (HY, GL) => {}
+        (_, GL) => return false,
+        _ => {}
+    }
+
+    // LB13: don't break before closing punctuation, even after spaces.
+    match after {
+        CL | CP | EX | IS | SY => return false,
+        _ => {}
+    }
+
+    // LB14 to LB16: rules that apply across spaces.
+    match (before_spaces, after) {
+        (OP, _) => return false,
+        (QU, OP) => return false,
+        (CL, NS) | (CP, NS) => return false,
+        _ => {}
+    }
+
+    // LB18: break after spaces.
+    if before == SP {
+        return true
+    }
+
+    match (before, after) {
+        // LB19: don't break around quotation marks.
+        (QU, _) | (_, QU) => false,
+        // LB21: don't break before hyphens and small kana, or after break-before characters.
+        (_, BA) | (_, HY) | (_, NS) | (BB, _) => false,
+        // LB22: don't break before ellipses.
+        (_, IN) => false,
+        // LB23 to LB25: keep numbers, and the letters and symbols around them, together.
+        (AL, NU) | (NU, AL) => false,
+        (PR, ID) | (ID, PO) => false,
+        (PR, AL) | (PO, AL) | (AL, PR) | (AL, PO) => false,
+        (CL, PO) | (CP, PO) | (CL, PR) | (CP, PR) | (NU, PO) | (NU, PR) => false,
+        (PO, OP) | (PO, NU) | (PR, OP) | (PR, NU) => false,
+        (HY, NU) | (IS, NU) | (NU, NU) | (SY, NU) => false,
+        // LB28: don't break between alphabetic characters.
+        (AL, AL) => false,
+        // LB29: don't break between numeric punctuation and letters.
+        (IS, AL) => false,
+        // LB30: don't break between letters or numbers and adjacent parentheses.
+        (AL, OP) | (NU, OP) | (CP, AL) | (CP, NU) => false,
+        // LB31: break everywhere else.
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+fn break_positions(text: &str) -> ~[uint] {
+    break_positions_for_word_break(text, WordBreakNormal)
+}
+
+#[cfg(test)]
+fn break_positions_for_word_break(text: &str, word_break: WordBreak) -> ~[uint] {
+    break_opportunities_for_word_break(text, word_break).iter().enumerate().filter_map(|(i, &b)| {
+        if b { Some(i) } else { None }
+    }).collect()
+}
+
+#[test]
+fn test_breaks_after_spaces() {
+    assert!(break_positions("foo bar  baz") == ~[4, 9]);
+    assert!(break_positions(" foo") == ~[1]);
+}
+
+#[test]
+fn test_no_breaks_around_glue() {
+    // No-break space, word joiner.
+    assert!(break_positions("10 km") == ~[]);
+    assert!(break_positions("a⁠b") == ~[]);
+}
+
+#[test]
+fn test_breaks_after_hyphens() {
+    assert!(break_positions("well-known") == ~[5]);
+    // But not inside numbers.
+    assert!(break_positions("-42") == ~[]);
+}
+
+#[test]
+fn test_breaks_between_ideographs() {
+    assert!(break_positions("日本語") == ~[1, 2]);
+    // Closing punctuation and small kana stay with the preceding character.
+    assert!(break_positions("日本。") == ~[1]);
+    assert!(break_positions("「日本」") == ~[2]);
+}
+
+#[test]
+fn test_punctuation_is_not_separated() {
+    assert!(break_positions("Hello, world!") == ~[7]);
+    assert!(break_positions("foo (bar)") == ~[4]);
+    assert!(break_positions("wait ! really") == ~[7]);
+}
+
+#[test]
+fn test_zero_width_space() {
+    assert!(break_positions("foo​bar") == ~[4]);
+}
+
+#[test]
+fn test_break_all() {
+    assert!(break_positions_for_word_break("abc", WordBreakBreakAll) == ~[1, 2]);
+    assert!(break_positions_for_word_break("ab cd", WordBreakBreakAll) == ~[1, 3, 4]);
+    // Punctuation still stays with the letter before it.
+    assert!(break_positions_for_word_break("ab, c", WordBreakBreakAll) == ~[1, 4]);
+}
+
+#[test]
+fn test_keep_all() {
+    assert!(break_positions_for_word_break("日本語", WordBreakKeepAll) == ~[]);
+    assert!(break_positions_for_word_break("日本 語", WordBreakKeepAll) == ~[3]);
+}
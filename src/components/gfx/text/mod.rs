@@ -13,7 +13,12 @@ pub use text::text_run::SendableTextRun;
 pub use text::text_run::TextRun;
 
 pub mod glyph;
+pub mod grapheme;
+pub mod hyphenation;
+pub mod line_break;
+pub mod script;
 #[path="shaping/mod.rs"] pub mod shaping;
+pub mod spellcheck;
 pub mod text_run;
 pub mod util;
 
@@ -0,0 +1,261 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Script and direction detection for text runs.
+//!
+//! The shaper needs to know which writing system a piece of text belongs to in order to pick
+//! the right shaping engine (Arabic joining, Indic reordering, and so on) and the right set of
+//! OpenType lookups from the font.
+
+use text::util::true_type_tag;
+
+/// The direction in which a run of text is laid out.
+#[deriving(Clone, Eq, IterBytes)]
+pub enum TextDirection {
+    LeftToRight,
+    RightToLeft,
+}
+
+/// A Unicode script, in the sense of UAX #24. Only the scripts that need special handling by
+/// the shaper, or that are common enough to be worth distinguishing, are listed here. Text in any
+/// other script is shaped as `UnknownScript`, which lets the shaper fall back to its default
+/// engine.
+#[deriving(Clone, Eq, IterBytes)]
+pub enum Script {
+    /// Characters shared between scripts, such as digits, punctuation, and spaces.
+    CommonScript,
+    /// Combining marks, which take on the script of the preceding base character.
+    InheritedScript,
+    UnknownScript,
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Syriac,
+    Thaana,
+    Devanagari,
+    Bengali,
+    Gurmukhi,
+    Gujarati,
+    Oriya,
+    Tamil,
+    Telugu,
+    Kannada,
+    Malayalam,
+    Sinhala,
+    Thai,
+    Lao,
+    Tibetan,
+    Myanmar,
+    Georgian,
+    Hangul,
+    Ethiopic,
+    Khmer,
+    Mongolian,
+    Hiragana,
+    Katakana,
+    Han,
+}
+
+impl Script {
+    /// Returns the ISO 15924 tag for this script, which is the representation HarfBuzz uses.
+    pub fn iso15924_tag(&self) -> u32 {
+        let (a, b, c, d) = match *self {
+            CommonScript    => ('Z', 'y', 'y', 'y'),
+            InheritedScript => ('Z', 'i', 'n', 'h'),
+            UnknownScript   => ('Z', 'z', 'z', 'z'),
+            Latin           => ('L', 'a', 't', 'n'),
+            Greek           => ('G', 'r', 'e', 'k'),
+            Cyrillic        => ('C', 'y', 'r', 'l'),
+            Armenian        => ('A', 'r', 'm', 'n'),
+            Hebrew          => ('H', 'e', 'b', 'r'),
+            Arabic          => ('A', 'r', 'a', 'b'),
+            Syriac          => ('S', 'y', 'r', 'c'),
+            Thaana          => ('T', 'h', 'a', 'a'),
+            Devanagari      => ('D', 'e', 'v', 'a'),
+            Bengali         => ('B', 'e', 'n', 'g'),
+            Gurmukhi        => ('G', 'u', 'r', 'u'),
+            Gujarati        => ('G', 'u', 'j', 'r'),
+            Oriya           => ('O', 'r', 'y', 'a'),
+            Tamil           => ('T', 'a', 'm', 'l'),
+            Telugu          => ('T', 'e', 'l', 'u'),
+            Kannada         => ('K', 'n', 'd', 'a'),
+            Malayalam       => ('M', 'l', 'y', 'm'),
+            Sinhala         => ('S', 'i', 'n', 'h'),
+            Thai            => ('T', 'h', 'a', 'i'),
+            Lao             => ('L', 'a', 'o', 'o'),
+            Tibetan         => ('T', 'i', 'b', 't'),
+            Myanmar         => ('M', 'y', 'm', 'r'),
+            Georgian        => ('G', 'e', 'o', 'r'),
+            Hangul          => ('H', 'a', 'n', 'g'),
+            Ethiopic        => ('E', 't', 'h', 'i'),
+            Khmer           => ('K', 'h', 'm', 'r'),
+            Mongolian       => ('M', 'o', 'n', 'g'),
+            Hiragana        => ('H', 'i', 'r', 'a'),
+            Katakana        => ('K', 'a', 'n', 'a'),
+            Han             => ('H', 'a', 'n', 'i'),
+        };
+        true_type_tag(a, b, c, d)
+    }
+
+    /// Returns true if this script does not identify a particular writing system on its own.
+    pub fn is_weak(&self) -> bool {
+        match *self {
+            CommonScript | InheritedScript | UnknownScript => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the direction text in this script is laid out in when no other information is
+    /// available.
+    pub fn direction(&self) -> TextDirection {
+        match *self {
+            Hebrew | Arabic | Syriac | Thaana => RightToLeft,
+            _ => LeftToRight,
+        }
+    }
+}
+
+/// Returns the script of a single character.
+pub fn script_for_char(ch: char) -> Script {
+    match ch as u32 {
+        0x0030..0x0039 => CommonScript,
+        0x0041..0x005A | 0x0061..0x007A => Latin,
+        0x0000..0x00BF => CommonScript,
+        0x00D7 | 0x00F7 => CommonScript,
+        0x00C0..0x024F => Latin,
+        0x0250..0x02AF => Latin,
+        0x02B0..0x02FF => CommonScript,
+        0x0300..0x036F => InheritedScript,
+        0x0370..0x03FF => Greek,
+        0x0400..0x052F => Cyrillic,
+        0x0530..0x058F => Armenian,
+        0x0591..0x05C7 => InheritedScript,
+        0x0590..0x05FF => Hebrew,
+        0x060C | 0x061B | 0x061F | 0x0640 => CommonScript,
+        0x064B..0x065F | 0x0670 => InheritedScript,
+        0x0600..0x06FF => Arabic,
+        0x0700..0x074F => Syriac,
+        0x0750..0x077F => Arabic,
+        0x0780..0x07BF => Thaana,
+        0x0964 | 0x0965 => CommonScript,
+        0x0900..0x097F => Devanagari,
+        0x0980..0x09FF => Bengali,
+        0x0A00..0x0A7F => Gurmukhi,
+        0x0A80..0x0AFF => Gujarati,
+        0x0B00..0x0B7F => Oriya,
+        0x0B80..0x0BFF => Tamil,
+        0x0C00..0x0C7F => Telugu,
+        0x0C80..0x0CFF => Kannada,
+        0x0D00..0x0D7F => Malayalam,
+        0x0D80..0x0DFF => Sinhala,
+        0x0E00..0x0E7F => Thai,
+        0x0E80..0x0EFF => Lao,
+        0x0F00..0x0FFF => Tibetan,
+        0x1000..0x109F => Myanmar,
+        0x10A0..0x10FF => Georgian,
+        0x1100..0x11FF => Hangul,
+        0x1200..0x139F => Ethiopic,
+        0x1780..0x17FF => Khmer,
+        0x1800..0x18AF => Mongolian,
+        0x1AB0..0x1AFF | 0x1DC0..0x1DFF | 0x200C | 0x200D | 0x20D0..0x20FF => InheritedScript,
+        0x1E00..0x1EFF => Latin,
+        0x1F00..0x1FFF => Greek,
+        0x2000..0x2BFF => CommonScript,
+        0x2E80..0x2FDF => Han,
+        0x3000..0x303F => CommonScript,
+        0x3099 | 0x309A => InheritedScript,
+        0x3040..0x309F => Hiragana,
+        0x30FC => CommonScript,
+        0x30A0..0x30FF | 0x31F0..0x31FF => Katakana,
+        0x3130..0x318F => Hangul,
+        0x3400..0x4DBF | 0x4E00..0x9FFF => Han,
+        0xAC00..0xD7AF => Hangul,
+        0xF900..0xFAFF => Han,
+        0xFB00..0xFB06 => Latin,
+        0xFB1D..0xFB4F => Hebrew,
+        0xFB50..0xFDFF => Arabic,
+        0xFE00..0xFE0F | 0xFE20..0xFE2F => InheritedScript,
+        0xFE70..0xFEFE => Arabic,
+        0xFF21..0xFF3A | 0xFF41..0xFF5A => Latin,
+        0xFF66..0xFF9D => Katakana,
+        0xFF00..0xFFEF => CommonScript,
+        0x20000..0x2FA1F => Han,
+        _ => UnknownScript,
+    }
+}
+
+/// Returns the script a run of text should be shaped with: the script of the first character
+/// that belongs to a particular writing system, or `CommonScript` if there is none.
+pub fn script_for_text(text: &str) -> Script {
+    for ch in text.iter() {
+        let script = script_for_char(ch);
+        if !script.is_weak() {
+            return script
+        }
+    }
+    CommonScript
+}
+
+/// Returns the direction of a character with a strong bidirectional type (L, R, or AL in UAX #9),
+/// or `None` if the character is neutral or weak. Digits, punctuation, and combining marks are
+/// all treated as weak.
+pub fn strong_direction_for_char(ch: char) -> Option<TextDirection> {
+    match ch {
+        // LEFT-TO-RIGHT MARK
+        '\u200e' => Some(LeftToRight),
+        // RIGHT-TO-LEFT MARK and ARABIC LETTER MARK
+        '\u200f' | '\u061c' => Some(RightToLeft),
+        // Arabic-Indic digits are weak (AN), even though their script is Arabic.
+        '\u0660'..'\u0669' | '\u06f0'..'\u06f9' => None,
+        _ => {
+            let script = script_for_char(ch);
+            if script.is_weak() { None } else { Some(script.direction()) }
+        }
+    }
+}
+
+/// Returns the direction of the first character in `text` with a strong bidirectional type, as
+/// used to find the paragraph direction (rules P2 and P3 of UAX #9) and to resolve `dir=auto`.
+pub fn first_strong_direction(text: &str) -> Option<TextDirection> {
+    for ch in text.iter() {
+        match strong_direction_for_char(ch) {
+            Some(direction) => return Some(direction),
+            None => {}
+        }
+    }
+    None
+}
+
+#[test]
+fn test_script_for_char() {
+    assert!(script_for_char('a') == Latin);
+    assert!(script_for_char('1') == CommonScript);
+    assert!(script_for_char(' ') == CommonScript);
+    assert!(script_for_char('ب') == Arabic);
+    assert!(script_for_char('א') == Hebrew);
+    assert!(script_for_char('क') == Devanagari);
+    assert!(script_for_char('́') == InheritedScript);
+    assert!(script_for_char('中') == Han);
+}
+
+#[test]
+fn test_script_for_text() {
+    assert!(script_for_text("hello") == Latin);
+    assert!(script_for_text("123 مرحبا") == Arabic);
+    assert!(script_for_text("(42)") == CommonScript);
+    assert!(script_for_text("مرحبا").direction() == RightToLeft);
+    assert!(script_for_text("hello").direction() == LeftToRight);
+}
+
+#[test]
+fn test_first_strong_direction() {
+    assert!(first_strong_direction("hello") == Some(LeftToRight));
+    assert!(first_strong_direction("123 שלום abc") == Some(RightToLeft));
+    assert!(first_strong_direction("١٢ (!)") == None);
+    assert!(first_strong_direction("\u200f1.") == Some(RightToLeft));
+    assert!(first_strong_direction("") == None);
+}
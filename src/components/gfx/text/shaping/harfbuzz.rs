@@ -4,13 +4,14 @@
 
 extern mod harfbuzz;
 
-use font::{Font, FontHandleMethods, FontTableMethods, FontTableTag};
-use geometry::Au;
+use font::{Font, FontFeature, FontHandleMethods, FontTableMethods, FontTableTag};
+use geometry::{Au, CSSPixel};
 use platform::font::FontTable;
 use text::glyph::{GlyphStore, GlyphIndex, GlyphData};
-use text::shaping::ShaperMethods;
+use text::script::{LeftToRight, RightToLeft};
+use text::shaping::{ShaperMethods, ShapingOptions};
 use servo_util::range::Range;
-use text::util::{float_to_fixed, fixed_to_float, fixed_to_rounded_int};
+use text::util::{float_to_fixed, fixed_to_float, fixed_to_rounded_int, true_type_tag};
 
 use std::cast::transmute;
 use std::char;
@@ -24,7 +25,8 @@ use geom::Point2D;
 use harfbuzz::{hb_blob_create, hb_face_create_for_tables};
 use harfbuzz::{hb_buffer_add_utf8};
 use harfbuzz::{hb_buffer_get_glyph_positions};
-use harfbuzz::{hb_buffer_set_direction};
+use harfbuzz::{hb_buffer_set_direction, hb_buffer_set_script};
+use harfbuzz::{hb_buffer_reverse};
 use harfbuzz::{hb_buffer_destroy};
 use harfbuzz::{hb_face_destroy};
 use harfbuzz::{hb_font_create};
@@ -37,10 +39,10 @@ use harfbuzz::{hb_font_set_funcs};
 use harfbuzz::{hb_font_set_ppem};
 use harfbuzz::{hb_font_set_scale};
 use harfbuzz::{hb_shape, hb_buffer_get_glyph_infos};
-use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR};
+use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR, HB_DIRECTION_RTL};
 use harfbuzz::{hb_blob_t};
 use harfbuzz::{hb_bool_t};
-use harfbuzz::{hb_face_t, hb_font_t};
+use harfbuzz::{hb_face_t, hb_font_t, hb_feature_t, hb_script_t};
 use harfbuzz::{hb_font_funcs_t, hb_buffer_t, hb_codepoint_t};
 use harfbuzz::{hb_glyph_info_t};
 use harfbuzz::{hb_glyph_position_t};
@@ -49,6 +51,16 @@ use harfbuzz::{hb_position_t, hb_tag_t};
 static NO_GLYPH: i32 = -1;
 static CONTINUATION_BYTE: i32 = -2;
 
+/// OpenType features that are explicitly turned on for every run. HarfBuzz enables the
+/// script-specific features (Arabic joining forms, Indic conjuncts, mark positioning, ...) by
+/// itself once it knows the script; these are the generic ones CSS expects by default. The features
+/// of the font's style are applied after these, so they can turn them off.
+static DEFAULT_FEATURES: &'static [(char, char, char, char)] = &[
+    ('k', 'e', 'r', 'n'),
+    ('l', 'i', 'g', 'a'),
+    ('c', 'l', 'i', 'g'),
+];
+
 pub struct ShapedGlyphData {
     count: uint,
     glyph_infos: *hb_glyph_info_t,
@@ -109,10 +121,10 @@ impl ShapedGlyphData {
             let x_advance = Shaper::fixed_to_float((*pos_info_i).x_advance);
             let y_advance = Shaper::fixed_to_float((*pos_info_i).y_advance);
 
-            let x_offset = Au::from_frac_px(x_offset);
-            let y_offset = Au::from_frac_px(y_offset);
-            let x_advance = Au::from_frac_px(x_advance);
-            let y_advance = Au::from_frac_px(y_advance);
+            let x_offset = Au::from_css_px(CSSPixel(x_offset));
+            let y_offset = Au::from_css_px(CSSPixel(y_offset));
+            let x_advance = Au::from_css_px(CSSPixel(x_advance));
+            let y_advance = Au::from_css_px(CSSPixel(y_advance));
 
             let offset = if x_offset == Au(0) && y_offset == Au(0) && y_advance == Au(0) {
                 None
@@ -135,11 +147,15 @@ impl ShapedGlyphData {
     }
 }
 
+/// A HarfBuzz shaper for a single font. The font owns its shaper, so the shaper refers to the font
+/// only through the raw pointer it hands to HarfBuzz's callbacks; holding a managed pointer would
+/// make a cycle that keeps both alive.
 pub struct Shaper {
-    font: @mut Font,
     priv hb_face: *hb_face_t,
     priv hb_font: *hb_font_t,
     priv hb_funcs: *hb_font_funcs_t,
+    /// The features to shape with, which depend only on the style of the font.
+    priv features: ~[hb_feature_t],
 }
 
 #[unsafe_destructor]
@@ -190,10 +206,10 @@ impl Shaper {
             hb_font_set_funcs(hb_font, hb_funcs, font_ptr as *c_void, None);
 
             Shaper {
-                font: font,
                 hb_face: hb_face,
                 hb_font: hb_font,
                 hb_funcs: hb_funcs,
+                features: Shaper::features(font.style.features),
             }
         }
     }
@@ -215,10 +231,14 @@ impl ShaperMethods for Shaper {
     /// Calculate the layout metrics associated with the given text when rendered in a specific
     /// font.
     #[fixed_stack_segment]
-    fn shape_text(&self, text: &str, glyphs: &mut GlyphStore) {
+    fn shape_text(&self, text: &str, options: &ShapingOptions, glyphs: &mut GlyphStore) {
         unsafe {
             let hb_buffer: *hb_buffer_t = hb_buffer_create();
-            hb_buffer_set_direction(hb_buffer, HB_DIRECTION_LTR);
+            hb_buffer_set_direction(hb_buffer, match options.direction {
+                LeftToRight => HB_DIRECTION_LTR,
+                RightToLeft => HB_DIRECTION_RTL,
+            });
+            hb_buffer_set_script(hb_buffer, options.script.iso15924_tag() as hb_script_t);
 
             // Using as_imm_buf because it never does a copy - we don't need the trailing null
             do text.as_imm_buf |ctext: *u8, _: uint| {
@@ -229,7 +249,18 @@ impl ShaperMethods for Shaper {
                                    text.len() as c_int);
             }
 
-            hb_shape(self.hb_font, hb_buffer, null(), 0);
+            hb_shape(self.hb_font,
+                     hb_buffer,
+                     vec::raw::to_ptr(self.features),
+                     self.features.len() as c_uint);
+
+            // HarfBuzz hands back right-to-left runs in visual order. Glyph stores are kept in
+            // logical order, so that they line up with the characters of the run.
+            // TODO(Issue #199): reorder glyphs when drawing and measuring RTL text.
+            if options.direction == RightToLeft {
+                hb_buffer_reverse(hb_buffer);
+            }
+
             self.save_glyph_results(text, glyphs, hb_buffer);
             hb_buffer_destroy(hb_buffer);
         }
@@ -237,6 +268,25 @@ impl ShaperMethods for Shaper {
 }
 
 impl Shaper {
+    /// Returns the default features followed by `style_features`. HarfBuzz applies the last
+    /// setting of a feature, so the style's settings override the defaults.
+    fn features(style_features: &[FontFeature]) -> ~[hb_feature_t] {
+        let defaults = DEFAULT_FEATURES.iter().map(|&(a, b, c, d)| {
+            FontFeature {
+                tag: true_type_tag(a, b, c, d),
+                value: 1,
+            }
+        });
+        defaults.chain(style_features.iter().map(|&feature| feature)).map(|feature| {
+            hb_feature_t {
+                tag: feature.tag as hb_tag_t,
+                value: feature.value,
+                start: 0,
+                end: uint::max_value as c_uint,
+            }
+        }).collect()
+    }
+
     fn save_glyph_results(&self, text: &str, glyphs: &mut GlyphStore, buffer: *hb_buffer_t) {
         let glyph_data = ShapedGlyphData::new(buffer);
         let glyph_count = glyph_data.len();
@@ -247,8 +297,8 @@ impl Shaper {
         // so, we must be careful to increment this when saving glyph entries.
         let mut char_idx = 0;
 
-        assert!(glyph_count <= char_max);
-
+        // Complex scripts can produce more glyphs than characters (e.g. split Indic vowel signs),
+        // or fewer (ligatures, Arabic lam-alef).
         debug!("Shaped text[char count=%u], got back %u glyph info records.",
                char_max,
                glyph_count);
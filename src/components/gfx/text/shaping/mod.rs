@@ -8,12 +8,39 @@
 /// Currently, only harfbuzz bindings are implemented.
 
 use text::glyph::GlyphStore;
+use text::script::{Script, TextDirection, script_for_text};
 
 pub use Shaper = text::shaping::harfbuzz::Shaper;
 
 pub mod harfbuzz;
 
+/// The per-run information the shaper needs, beyond the font, to select a shaping engine and the
+/// OpenType lookups to apply.
+#[deriving(Clone, Eq, IterBytes)]
+pub struct ShapingOptions {
+    script: Script,
+    direction: TextDirection,
+}
+
+impl ShapingOptions {
+    pub fn new(script: Script, direction: TextDirection) -> ShapingOptions {
+        ShapingOptions {
+            script: script,
+            direction: direction,
+        }
+    }
+
+    /// Guesses the shaping options for a run of text from the characters it contains. Text that
+    /// belongs to no particular script, such as punctuation, takes the direction of the paragraph
+    /// it is in.
+    pub fn for_text(text: &str, paragraph_direction: TextDirection) -> ShapingOptions {
+        let script = script_for_text(text);
+        let direction = if script.is_weak() { paragraph_direction } else { script.direction() };
+        ShapingOptions::new(script, direction)
+    }
+}
+
 pub trait ShaperMethods {
-    fn shape_text(&self, text: &str, glyphs: &mut GlyphStore);
+    fn shape_text(&self, text: &str, options: &ShapingOptions, glyphs: &mut GlyphStore);
 }
 
@@ -0,0 +1,204 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Spellchecking of editable text, against a word list such as the system dictionary.
+//!
+//! Layout asks the dictionary for the misspelled words of the text of editable elements and marks
+//! them with a wavy underline. Words are runs of letters, with apostrophes allowed inside them;
+//! words containing digits and single letters are never reported.
+
+use servo_util::range::Range;
+
+use std::ascii::StrAsciiExt;
+use std::hashmap::HashSet;
+use std::io;
+
+/// The word list that most Unix systems have.
+pub static SYSTEM_WORD_LIST: &'static str = "/usr/share/dict/words";
+
+/// The number of suggestions `suggestions` returns at most.
+static MAX_SUGGESTIONS: uint = 5;
+
+/// A set of correctly spelled words.
+pub struct Dictionary {
+    priv words: HashSet<~str>,
+}
+
+impl Dictionary {
+    /// Builds a dictionary from a word list with one word per line. Hunspell dictionaries also
+    /// work: their affix flags, after a `/`, are ignored, as is the word count on the first line.
+    pub fn from_word_list(source: &str) -> Dictionary {
+        let mut words = HashSet::new();
+        for line in source.line_iter() {
+            let word = match line.find('/') {
+                Some(i) => line.slice_to(i),
+                None => line,
+            }.trim();
+            if !word.is_empty() && !word.iter().all(|ch| ch.is_digit()) {
+                words.insert(word.to_owned());
+            }
+        }
+        Dictionary {
+            words: words,
+        }
+    }
+
+    /// Loads the word list at `path`, or the system word list if no path is given. Returns `None`
+    /// if the file can't be read.
+    pub fn load(path: Option<~str>) -> Option<Dictionary> {
+        let path = path.unwrap_or_default(SYSTEM_WORD_LIST.to_owned());
+        match io::read_whole_file_str(&Path(path)) {
+            Ok(source) => Some(Dictionary::from_word_list(source)),
+            Err(error) => {
+                debug!("no spellchecking: failed to load the word list %s: %s", path, error);
+                None
+            }
+        }
+    }
+
+    /// Returns whether `word` is spelled correctly. A word in the dictionary is also correct
+    /// capitalized or in capitals, but a capitalized entry, such as a name, must stay capitalized.
+    pub fn is_correct(&self, word: &str) -> bool {
+        if word.char_len() < 2 || word.iter().any(|ch| ch.is_digit()) {
+            return true
+        }
+        if self.words.contains(&word.to_owned()) {
+            return true
+        }
+        let lower = word.to_ascii_lower();
+        if self.words.contains(&lower) {
+            return true
+        }
+        // Capitalized forms of the lowercase entry are also correct in capitals.
+        let capitalized = capitalize(lower);
+        word == capitalized.to_ascii_upper() && self.words.contains(&capitalized)
+    }
+
+    /// Returns the character ranges of the misspelled words in `text`.
+    pub fn misspelled_words(&self, text: &str) -> ~[Range] {
+        let mut ranges = ~[];
+        let mut word = ~"";
+        let mut word_start = 0;
+        for (i, ch) in text.iter().enumerate() {
+            if ch.is_alphabetic() || ch.is_digit() || (ch == '\'' && !word.is_empty()) {
+                if word.is_empty() {
+                    word_start = i
+                }
+                word.push_char(ch);
+                loop
+            }
+            self.push_if_misspelled(word, word_start, &mut ranges);
+            word = ~"";
+        }
+        self.push_if_misspelled(word, word_start, &mut ranges);
+        ranges
+    }
+
+    fn push_if_misspelled(&self, word: &str, start: uint, ranges: &mut ~[Range]) {
+        // An apostrophe at the end of a word is a closing quote or a possessive.
+        let word = word.trim_right_chars(&'\'');
+        if !word.is_empty() && !self.is_correct(word) {
+            ranges.push(Range::new(start, word.char_len()))
+        }
+    }
+
+    /// Returns the correctly spelled words that are one edit away from `word`, for a user to
+    /// pick from: a letter deleted, inserted, replaced, or swapped with the next one. The
+    /// suggestions keep the capitalization of `word`.
+    ///
+    /// FIXME: Nothing offers these to the user yet, since the embedder has no context menu.
+    pub fn suggestions(&self, word: &str) -> ~[~str] {
+        let chars: ~[char] = word.to_ascii_lower().iter().collect();
+        let mut candidates = ~[];
+        for i in range(0, chars.len()) {
+            candidates.push(edited(chars, i, 1, []));
+            if i + 1 < chars.len() {
+                candidates.push(edited(chars, i, 2, [chars[i + 1], chars[i]]));
+            }
+        }
+        for i in range(0, chars.len() + 1) {
+            for letter in range('a' as u8, 'z' as u8 + 1) {
+                let letter = letter as char;
+                candidates.push(edited(chars, i, 0, [letter]));
+                if i < chars.len() && chars[i] != letter {
+                    candidates.push(edited(chars, i, 1, [letter]));
+                }
+            }
+        }
+
+        let capitalized = word.char_len() > 0 && word.char_at(0).is_uppercase();
+        let mut suggestions: ~[~str] = ~[];
+        for candidate in candidates.move_iter() {
+            let candidate = if capitalized { capitalize(candidate) } else { candidate };
+            if candidate.char_len() > 1 && candidate.as_slice() != word &&
+                    !suggestions.contains(&candidate) && self.is_correct(candidate) {
+                suggestions.push(candidate);
+                if suggestions.len() == MAX_SUGGESTIONS {
+                    break
+                }
+            }
+        }
+        suggestions
+    }
+}
+
+/// Returns `chars` with the `count` characters at `index` replaced by `replacement`.
+fn edited(chars: &[char], index: uint, count: uint, replacement: &[char]) -> ~str {
+    let mut result = ~"";
+    for &ch in chars.slice_to(index).iter() {
+        result.push_char(ch)
+    }
+    for &ch in replacement.iter() {
+        result.push_char(ch)
+    }
+    for &ch in chars.slice_from(index + count).iter() {
+        result.push_char(ch)
+    }
+    result
+}
+
+/// Returns `word` with its first letter in uppercase.
+fn capitalize(word: &str) -> ~str {
+    if word.is_empty() {
+        return ~""
+    }
+    let first = word.char_range_at(0);
+    let mut result = word.slice_to(first.next).to_ascii_upper();
+    result.push_str(word.slice_from(first.next));
+    result
+}
+
+#[test]
+fn test_is_correct() {
+    let dictionary = Dictionary::from_word_list("2\nteh/S\nthe\nParis\n");
+    assert!(dictionary.is_correct("the"));
+    assert!(dictionary.is_correct("The"));
+    assert!(dictionary.is_correct("THE"));
+    assert!(dictionary.is_correct("teh"));
+    assert!(dictionary.is_correct("Paris"));
+    assert!(dictionary.is_correct("PARIS"));
+    assert!(!dictionary.is_correct("paris"));
+    assert!(!dictionary.is_correct("thr"));
+    assert!(dictionary.is_correct("x"));
+    assert!(dictionary.is_correct("2nd"));
+}
+
+#[test]
+fn test_misspelled_words() {
+    let dictionary = Dictionary::from_word_list("the\ncat\ncat's\nsat\n");
+    let ranges = dictionary.misspelled_words("the cat's hat sat, 'cat' catt");
+    let ranges: ~[(uint, uint)] = ranges.iter().map(|r| (r.begin(), r.length())).collect();
+    assert!(ranges == ~[(10, 3), (25, 4)]);
+}
+
+#[test]
+fn test_suggestions() {
+    let dictionary = Dictionary::from_word_list("the\nten\ntea\n");
+    let suggestions = dictionary.suggestions("teh");
+    assert!(suggestions.contains(&~"the"));
+    assert!(suggestions.contains(&~"ten"));
+    assert!(suggestions.contains(&~"tea"));
+    assert!(!suggestions.contains(&~"teh"));
+    assert!(dictionary.suggestions("Teh").contains(&~"The"));
+}
@@ -5,26 +5,64 @@
 use std::vec::VecIterator;
 
 use font_context::FontContext;
-use geometry::Au;
-use text::glyph::GlyphStore;
+use geometry::{Au, CSSPixel};
+use text::glyph::{GlyphData, GlyphIndex, GlyphIterator, GlyphStore};
+use text::grapheme;
+use text::hyphenation::SOFT_HYPHEN;
+use text::line_break;
+use text::line_break::WordBreak;
+use text::script::TextDirection;
+use text::shaping::ShapingOptions;
 use font::{Font, FontDescriptor, RunMetrics};
 use servo_util::range::Range;
+use servo_util::shared_str::SharedStr;
 use extra::arc::Arc;
+use geom::point::Point2D;
+
+/// The distance between tab stops, as `tab-size` gives it.
+#[deriving(Clone, Eq)]
+pub enum TabSize {
+    /// A multiple of the advance of the space character.
+    TabSpaces(uint),
+    TabLength(Au),
+}
 
 /// A text run.
 pub struct TextRun {
-    text: ~str,
+    /// The text of the run, which the words it is shaped from share.
+    text: SharedStr,
     font: @mut Font,
-    underline: bool,
+    /// The direction of the paragraph this run belongs to.
+    direction: TextDirection,
+    /// The distance between tab stops.
+    tab_size: TabSize,
+    /// The `word-break` that the slices of the run were broken with.
+    word_break: WordBreak,
     glyphs: ~[Arc<GlyphStore>],
+    /// The character offset at which each slice starts, followed by the length of the run.
+    priv slice_offsets: ~[uint],
+    /// The total advance of all the slices before each entry of `slice_offsets`. Together, these
+    /// let the advance of any range of whole slices be found without measuring any glyphs.
+    priv advance_sums: ~[Au],
+    /// Whether each slice ends in a soft hyphen, which is only displayed if a line is broken there.
+    priv soft_hyphen_slices: ~[bool],
+    /// For each character, the offset of the character it was produced from by `text-transform`,
+    /// or empty if the transform kept every character at its own offset.
+    priv source_offsets: ~[uint],
 }
 
 /// This is a hack until TextRuns are normally sendable, or we instead use Arc<TextRun> everywhere.
 pub struct SendableTextRun {
-    text: ~str,
+    text: SharedStr,
     font: FontDescriptor,
-    underline: bool,
+    direction: TextDirection,
+    tab_size: TabSize,
+    word_break: WordBreak,
     priv glyphs: ~[Arc<GlyphStore>],
+    priv slice_offsets: ~[uint],
+    priv advance_sums: ~[Au],
+    priv soft_hyphen_slices: ~[bool],
+    priv source_offsets: ~[uint],
 }
 
 impl SendableTextRun {
@@ -37,8 +75,14 @@ impl SendableTextRun {
         TextRun {
             text: self.text.clone(),
             font: font,
-            underline: self.underline,
+            direction: self.direction,
+            tab_size: self.tab_size,
+            word_break: self.word_break,
             glyphs: self.glyphs.clone(),
+            slice_offsets: self.slice_offsets.clone(),
+            advance_sums: self.advance_sums.clone(),
+            soft_hyphen_slices: self.soft_hyphen_slices.clone(),
+            source_offsets: self.source_offsets.clone(),
         }
     }
 }
@@ -115,73 +159,221 @@ impl<'self> Iterator<Range> for LineIterator<'self> {
     }
 }
 
+/// A shaped glyph of a text run, with everything needed to paint it.
+pub struct RunGlyph {
+    index: GlyphIndex,
+    advance: Au,
+    /// The offset of the glyph from the pen position, which is zero unless the shaper moved it.
+    offset: Point2D<Au>,
+    /// The bytes of the text of the run that hold the character the glyph was shaped from.
+    byte_range: Range,
+}
+
+pub struct RunGlyphIterator<'self> {
+    priv text:        &'self str,
+    priv slices:      SliceIterator<'self>,
+    /// The glyphs of the current slice, and the character offset at which the slice starts.
+    priv glyphs:      Option<(uint, GlyphIterator<'self>)>,
+    /// A character offset into the text and the byte offset it starts at, which only move
+    /// forward, since glyphs are yielded in the order of their characters.
+    priv char_cursor: uint,
+    priv byte_cursor: uint,
+}
+
+impl<'self> RunGlyphIterator<'self> {
+    fn byte_range_for_char(&mut self, char_index: uint) -> Range {
+        while self.char_cursor < char_index {
+            self.byte_cursor = self.text.char_range_at(self.byte_cursor).next;
+            self.char_cursor += 1;
+        }
+        let end = self.text.char_range_at(self.byte_cursor).next;
+        Range::new(self.byte_cursor, end - self.byte_cursor)
+    }
+}
+
+impl<'self> Iterator<RunGlyph> for RunGlyphIterator<'self> {
+    fn next(&mut self) -> Option<RunGlyph> {
+        loop {
+            let next_glyph = match self.glyphs {
+                Some((offset, ref mut glyphs)) => {
+                    glyphs.next().map_move(|(char_i, glyph)| (offset + char_i, glyph))
+                }
+                None => None,
+            };
+            match next_glyph {
+                Some((char_index, glyph)) => {
+                    return Some(RunGlyph {
+                        index: glyph.index(),
+                        advance: glyph.advance(),
+                        offset: glyph.offset().unwrap_or_default(Au::zero_point()),
+                        byte_range: self.byte_range_for_char(char_index),
+                    })
+                }
+                None => {}
+            }
+
+            // The current slice has no glyphs left, so move on to the next one.
+            match self.slices.next() {
+                Some((glyphs, offset, slice_range)) => {
+                    self.glyphs = Some((offset, glyphs.iter_glyphs_for_char_range(&slice_range)))
+                }
+                None => return None,
+            }
+        }
+    }
+}
+
 impl<'self> TextRun {
-    pub fn new(font: @mut Font, text: ~str, underline: bool) -> TextRun {
-        let glyphs = TextRun::break_and_shape(font, text);
+    pub fn new(font: @mut Font,
+               text: ~str,
+               direction: TextDirection,
+               tab_size: TabSize,
+               word_break: WordBreak)
+               -> TextRun {
+        let text = SharedStr::from_owned(text);
+        let mut glyphs = TextRun::break_and_shape(font, &text, direction, word_break);
+
+        // Tabs aren't shaped as glyphs of their own, but advance to the next tab stop. Since each
+        // tab has a slice of its own, its slice can be replaced once the advance of the text
+        // before it is known.
+        //
+        // FIXME: Tab stops should be measured from the start of the line, not of the run.
+        let chars: ~[char] = text.as_slice().iter().collect();
+        let mut slice_offsets = ~[0];
+        let mut advance_sums = ~[Au(0)];
+        for i in range(0, glyphs.len()) {
+            let start = slice_offsets[i];
+            if chars[start] == '\t' {
+                glyphs[i] = TextRun::tab_glyphs(font, advance_sums[i], tab_size);
+            }
+            let slice_glyphs = glyphs[i].get();
+            let slice_range = Range::new(0, slice_glyphs.char_len());
+            let advance = font.measure_text_for_slice(slice_glyphs, &slice_range).advance_width;
+            slice_offsets.push(start + slice_glyphs.char_len());
+            advance_sums.push(advance_sums[i] + advance);
+        }
+
+        let mut soft_hyphen_slices = ~[];
+        for (char_i, ch) in text.as_slice().iter().enumerate() {
+            if char_i + 1 == slice_offsets[soft_hyphen_slices.len() + 1] {
+                soft_hyphen_slices.push(ch == SOFT_HYPHEN);
+            }
+        }
 
         let run = TextRun {
             text: text,
             font: font,
-            underline: underline,
+            direction: direction,
+            tab_size: tab_size,
+            word_break: word_break,
             glyphs: glyphs,
+            slice_offsets: slice_offsets,
+            advance_sums: advance_sums,
+            soft_hyphen_slices: soft_hyphen_slices,
+            source_offsets: ~[],
         };
         return run;
     }
 
-    pub fn teardown(&self) {
-        self.font.teardown();
+    /// Returns the glyph for a tab whose slice starts `position` from the start of the run.
+    /// Tab stops are `tab_size` apart, and a tab that would advance by less than half a space
+    /// advances to the following stop instead, as CSS Text Level 3 requires.
+    fn tab_glyphs(font: @mut Font, position: Au, tab_size: TabSize) -> Arc<GlyphStore> {
+        let space_glyph = font.glyph_index(' ').unwrap_or_default(0);
+        let space_advance = Au::from_css_px(CSSPixel(font.glyph_h_advance(space_glyph)));
+        let tab_interval = match tab_size {
+            TabSpaces(spaces) => space_advance * Au(spaces as i32),
+            TabLength(length) => length,
+        };
+
+        let mut advance = if tab_interval > Au(0) {
+            tab_interval - position % tab_interval
+        } else {
+            Au(0)
+        };
+        if advance * Au(2) < space_advance {
+            advance = advance + tab_interval
+        }
+
+        let mut glyphs = GlyphStore::new(1, true);
+        glyphs.add_glyph_for_char_index(0, &GlyphData::new(space_glyph, advance, None, false,
+                                                           true, true));
+        glyphs.set_char_is_tab(0);
+        glyphs.finalize_changes();
+        Arc::new(glyphs)
     }
 
-    pub fn break_and_shape(font: @mut Font, text: &str) -> ~[Arc<GlyphStore>] {
-        // TODO(Issue #230): do a better job. See Gecko's LineBreaker.
+    /// Shapes each slice of `text`. The slices share the text of the run, so no words are copied
+    /// unless the font hasn't shaped them before.
+    pub fn break_and_shape(font: @mut Font,
+                           shared_text: &SharedStr,
+                           direction: TextDirection,
+                           word_break: WordBreak)
+                           -> ~[Arc<GlyphStore>] {
+        fn is_whitespace(ch: char) -> bool {
+            match ch {
+                ' ' | '\t' | '\n' => true,
+                _ => false,
+            }
+        }
+
+        // Slices are the units that lines are broken between. They end at the line break
+        // opportunities found by the UAX #14 rules, as adjusted by `word-break`. Runs of
+        // whitespace are also given slices of their own so that they can be trimmed at the ends
+        // of lines, unless the line may not be broken after them (as in "foo !"). Slices never
+        // end inside a grapheme cluster, so a line is never broken between a character and the
+        // marks that combine with it.
+        let text = shared_text.as_slice();
+        let chars: ~[char] = text.iter().collect();
+        let breaks = line_break::break_opportunities_for_word_break(text, word_break);
+        let cluster_starts = grapheme::cluster_starts(text);
+        let whitespace_run_ends_in_break = |start: uint| -> bool {
+            let mut end = start;
+            while end < chars.len() && is_whitespace(chars[end]) {
+                end += 1;
+            }
+            end == chars.len() || breaks[end]
+        };
 
         let mut glyphs = ~[];
-        let mut byte_i = 0u;
-        let mut cur_slice_is_whitespace = false;
         let mut byte_last_boundary = 0;
-        while byte_i < text.len() {
-            let range = text.char_range_at(byte_i);
-            let ch = range.ch;
-            let next = range.next;
-
-            // Slices alternate between whitespace and non-whitespace,
-            // representing line break opportunities.
-            let can_break_before = if cur_slice_is_whitespace {
-                match ch {
-                    ' ' | '\t' | '\n' => false,
-                    _ => {
-                        cur_slice_is_whitespace = false;
-                        true
-                    }
-                }
-            } else {
-                match ch {
-                    ' ' | '\t' | '\n' => {
-                        cur_slice_is_whitespace = true;
-                        true
-                    },
-                    _ => false
-                }
-            };
+        let mut cur_slice_is_whitespace = false;
+        for (char_i, (byte_i, ch)) in text.char_offset_iter().enumerate() {
+            let ch_is_whitespace = is_whitespace(ch);
+            if byte_i == 0 {
+                cur_slice_is_whitespace = ch_is_whitespace;
+                loop
+            }
+
+            // Each tab is given a slice of its own so that it can be expanded to a tab stop.
+            let ends_slice = cluster_starts[char_i] &&
+                (breaks[char_i] ||
+                 (ch_is_whitespace && !cur_slice_is_whitespace &&
+                  whitespace_run_ends_in_break(char_i)) ||
+                 ch == '\t' || chars[char_i - 1] == '\t');
 
             // Create a glyph store for this slice if it's nonempty.
-            if can_break_before && byte_i > byte_last_boundary {
-                let slice = text.slice(byte_last_boundary, byte_i).to_owned();
+            if ends_slice {
+                let slice = shared_text.slice(byte_last_boundary, byte_i);
                 debug!("creating glyph store for slice %? (ws? %?), %? - %? in run %?",
-                        slice, !cur_slice_is_whitespace, byte_last_boundary, byte_i, text);
-                glyphs.push(font.shape_text(slice, !cur_slice_is_whitespace));
+                        slice.as_slice(), cur_slice_is_whitespace, byte_last_boundary, byte_i,
+                        text);
+                let options = ShapingOptions::for_text(slice.as_slice(), direction);
+                glyphs.push(font.shape_text(slice, &options, cur_slice_is_whitespace));
                 byte_last_boundary = byte_i;
+                cur_slice_is_whitespace = ch_is_whitespace;
+            } else if !ch_is_whitespace {
+                cur_slice_is_whitespace = false;
             }
-
-            byte_i = next;
         }
 
         // Create a glyph store for the final slice if it's nonempty.
-        if byte_i > byte_last_boundary {
-            let slice = text.slice(byte_last_boundary, text.len()).to_owned();
+        if text.len() > byte_last_boundary {
+            let slice = shared_text.slice(byte_last_boundary, text.len());
             debug!("creating glyph store for final slice %? (ws? %?), %? - %? in run %?",
-                slice, cur_slice_is_whitespace, byte_last_boundary, text.len(), text);
-            glyphs.push(font.shape_text(slice, cur_slice_is_whitespace));
+                slice.as_slice(), cur_slice_is_whitespace, byte_last_boundary, text.len(), text);
+            let options = ShapingOptions::for_text(slice.as_slice(), direction);
+            glyphs.push(font.shape_text(slice, &options, cur_slice_is_whitespace));
         }
 
         glyphs
@@ -191,8 +383,14 @@ impl<'self> TextRun {
         SendableTextRun {
             text: self.text.clone(),
             font: self.font.get_descriptor(),
-            underline: self.underline,
+            direction: self.direction,
+            tab_size: self.tab_size,
+            word_break: self.word_break,
             glyphs: self.glyphs.clone(),
+            slice_offsets: self.slice_offsets.clone(),
+            advance_sums: self.advance_sums.clone(),
+            soft_hyphen_slices: self.soft_hyphen_slices.clone(),
+            source_offsets: self.source_offsets.clone(),
         }
     }
 
@@ -212,7 +410,153 @@ impl<'self> TextRun {
     }
 
     pub fn metrics_for_range(&self, range: &Range) -> RunMetrics {
-        self.font.measure_text(self, range)
+        RunMetrics::new(self.advance_for_range(range),
+                        self.font.metrics.ascent,
+                        self.font.metrics.descent)
+    }
+
+    /// Returns the number of slices in this run.
+    pub fn slice_count(&self) -> uint {
+        self.glyphs.len()
+    }
+
+    /// Returns the character offset at which the given slice starts. `index` may also be the
+    /// number of slices, in which case the length of the run is returned.
+    pub fn slice_start(&self, index: uint) -> uint {
+        self.slice_offsets[index]
+    }
+
+    pub fn slice_is_whitespace(&self, index: uint) -> bool {
+        self.glyphs[index].get().is_whitespace()
+    }
+
+    pub fn slice_ends_in_soft_hyphen(&self, index: uint) -> bool {
+        self.soft_hyphen_slices[index]
+    }
+
+    /// Returns the advance of the hyphen shown when a line is broken at a soft hyphen.
+    pub fn hyphen_advance(&self) -> Au {
+        let options = ShapingOptions::for_text("-", self.direction);
+        let glyphs = self.font.shape_text(SharedStr::from_owned(~"-"), &options, false);
+        self.font.measure_text_for_slice(glyphs.get(), &Range::new(0, 1)).advance_width
+    }
+
+    /// Creates a run for the characters of `range`, which must end in a soft hyphen, with the soft
+    /// hyphen replaced by a visible hyphen. This is the text before a line broken at the soft
+    /// hyphen.
+    pub fn hyphenated_run_for_range(&self, range: &Range) -> TextRun {
+        let mut text = self.text.as_slice().slice_chars(range.begin(), range.end() - 1).to_owned();
+        text.push_char('-');
+        let mut run = TextRun::new(self.font, text, self.direction, self.tab_size,
+                                   self.word_break);
+        if !self.source_offsets.is_empty() {
+            let offsets = self.source_offsets.slice(range.begin(), range.end());
+            run.set_source_offsets(offsets.to_owned());
+        }
+        run
+    }
+
+    /// Records where each character of the run came from, as returned by
+    /// `util::apply_text_transform`.
+    pub fn set_source_offsets(&mut self, offsets: ~[uint]) {
+        assert!(offsets.is_empty() || offsets.len() == self.char_len());
+        self.source_offsets = offsets;
+    }
+
+    /// Returns the offset of the character that the character at `offset` was produced from
+    /// before `text-transform` was applied, so that offsets into the run can be mapped back to
+    /// offsets into the text of the DOM.
+    pub fn source_offset(&self, offset: uint) -> uint {
+        if self.source_offsets.is_empty() {
+            offset
+        } else if offset == self.source_offsets.len() {
+            // The end of the run.
+            self.source_offsets[offset - 1] + 1
+        } else {
+            self.source_offsets[offset]
+        }
+    }
+
+    /// Returns the offset of the first newline among the given range of characters, if there is
+    /// one. Newlines are only kept in the run when `white-space` preserves them.
+    pub fn first_newline_in_range(&self, range: &Range) -> Option<uint> {
+        let mut chars = self.text.as_slice().iter().enumerate().skip(range.begin()).take(range.length());
+        chars.find(|&(_, ch)| ch == '\n').map_move(|(offset, _)| offset)
+    }
+
+    /// Returns the index of the slice containing the character at `offset`.
+    pub fn slice_index_for_char(&self, offset: uint) -> uint {
+        assert!(offset < self.char_len());
+        let (mut lo, mut hi) = (0, self.glyphs.len() - 1);
+        while lo < hi {
+            let mid = (lo + hi + 1) / 2;
+            if self.slice_offsets[mid] <= offset {
+                lo = mid
+            } else {
+                hi = mid - 1
+            }
+        }
+        lo
+    }
+
+    /// Returns the advance of the given range of characters. Only the glyphs of the slices at the
+    /// ends of the range need to be measured; whole slices in between use the advances computed
+    /// when the run was created.
+    pub fn advance_for_range(&self, range: &Range) -> Au {
+        if range.is_empty() {
+            return Au(0)
+        }
+
+        let first = self.slice_index_for_char(range.begin());
+        let last = self.slice_index_for_char(range.end() - 1);
+        if first == last {
+            return self.advance_for_range_in_slice(first, range)
+        }
+
+        let first_range = Range::new(range.begin(), self.slice_offsets[first + 1] - range.begin());
+        let last_range = Range::new(self.slice_offsets[last], range.end() - self.slice_offsets[last]);
+        self.advance_for_range_in_slice(first, &first_range) +
+            (self.advance_sums[last] - self.advance_sums[first + 1]) +
+            self.advance_for_range_in_slice(last, &last_range)
+    }
+
+    /// Returns the advance of a range of characters that lies entirely within one slice.
+    fn advance_for_range_in_slice(&self, index: uint, range: &Range) -> Au {
+        let slice_begin = self.slice_offsets[index];
+        let slice_end = self.slice_offsets[index + 1];
+        assert!(range.begin() >= slice_begin && range.end() <= slice_end);
+
+        if range.begin() == slice_begin && range.end() == slice_end {
+            return self.advance_sums[index + 1] - self.advance_sums[index]
+        }
+
+        let mut slice_range = *range;
+        slice_range.shift_by(-(slice_begin.to_int()));
+        self.font.measure_text_for_slice(self.glyphs[index].get(), &slice_range).advance_width
+    }
+
+    /// Returns the furthest grapheme cluster boundary after `begin`, and no further than `end`,
+    /// at which the text from `begin` still fits in `max_width`. The first cluster is always
+    /// included even if it doesn't fit, so that a line broken there is never empty. This is
+    /// where `overflow-wrap: break-word` breaks a word too long for a line of its own.
+    pub fn cluster_boundary_for_width(&self, begin: uint, end: uint, max_width: Au) -> uint {
+        assert!(begin < end);
+        let cluster_starts = grapheme::cluster_starts(self.text.as_slice());
+        let boundaries: ~[uint] = range(begin + 1, end + 1).filter(|&i| {
+            i == end || cluster_starts[i]
+        }).collect();
+
+        // As in line breaking, binary search for the last boundary that fits.
+        let (mut lo, mut hi) = (0, boundaries.len() - 1);
+        while lo < hi {
+            let mid = (lo + hi + 1) / 2;
+            if self.advance_for_range(&Range::new(begin, boundaries[mid] - begin)) <= max_width {
+                lo = mid
+            } else {
+                hi = mid - 1
+            }
+        }
+        boundaries[lo]
     }
 
     pub fn metrics_for_slice(&self, glyphs: &GlyphStore, slice_range: &Range) -> RunMetrics {
@@ -238,6 +582,19 @@ impl<'self> TextRun {
         }
     }
 
+    /// Returns an iterator over the glyphs of a range of characters of the run, in the order they
+    /// are painted. Only the slices the range touches are visited, and nothing is reshaped, so
+    /// part of a run can be painted or measured as cheaply as all of it.
+    pub fn iter_glyphs_for_range(&'self self, range: &Range) -> RunGlyphIterator<'self> {
+        RunGlyphIterator {
+            text:        self.text.as_slice(),
+            slices:      self.iter_slices_for_range(range),
+            glyphs:      None,
+            char_cursor: 0,
+            byte_cursor: 0,
+        }
+    }
+
     pub fn iter_natural_lines_for_range(&'self self, range: &Range) -> LineIterator<'self> {
         LineIterator {
             range:  *range,
@@ -2,8 +2,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use servo_util::range::Range;
+
+use std::ascii::StrAsciiExt;
+
 #[deriving(Eq)]
-enum CompressionMode {
+pub enum CompressionMode {
     CompressNone,
     CompressWhitespace,
     CompressWhitespaceNewline,
@@ -95,6 +99,221 @@ pub fn transform_text(text: &str, mode: CompressionMode, incoming_whitespace: bo
     }
 }
 
+/// Collapses whitespace across the pieces of text of an inline formatting context, in order, as
+/// CSS does: a collapsible space that follows another one is removed even if it is in another
+/// piece, as when a sequence of spaces spans several text nodes or the edge of an inline element.
+/// A `None` piece stands for something that isn't text, such as an image, which ends a sequence of
+/// spaces. The pieces are taken to start a line, so their leading collapsible spaces are removed.
+///
+/// Segment breaks collapse into spaces with `CompressWhitespaceNewline`. With `CompressWhitespace`
+/// they are preserved, and the collapsible spaces on either side of them are removed, including
+/// those at the end of earlier pieces.
+pub fn collapse_whitespace(pieces: &[Option<(~str, CompressionMode)>]) -> ~[~str] {
+    let mut out: ~[~str] = ~[];
+    // Whether the last character kept was a collapsible space or a preserved segment break, or
+    // no character has been kept yet.
+    let mut after_space = true;
+    // The piece of the last character kept, if it is a collapsible space.
+    let mut last_collapsible_space = None;
+    for (i, piece) in pieces.iter().enumerate() {
+        let mut text = ~"";
+        match *piece {
+            None => {
+                after_space = false;
+                last_collapsible_space = None;
+            }
+            Some((ref piece_text, mode)) => {
+                for ch in piece_text.iter() {
+                    let collapsible = match mode {
+                        CompressWhitespace => ch == ' ' || ch == '\t',
+                        CompressWhitespaceNewline => ch == ' ' || ch == '\t' || ch == '\n',
+                        CompressNone | DiscardNewline => false,
+                    };
+                    if collapsible {
+                        if !after_space {
+                            text.push_char(' ');
+                            after_space = true;
+                            last_collapsible_space = Some(i);
+                        }
+                    } else if ch == '\n' && mode == CompressWhitespace {
+                        match last_collapsible_space {
+                            Some(j) if j == i => { text.pop_char(); }
+                            Some(j) => { out[j].pop_char(); }
+                            None => {}
+                        }
+                        text.push_char(ch);
+                        after_space = true;
+                        last_collapsible_space = None;
+                    } else if !(ch == '\n' && mode == DiscardNewline) {
+                        text.push_char(ch);
+                        after_space = false;
+                        last_collapsible_space = None;
+                    }
+                }
+            }
+        }
+        out.push(text);
+    }
+    out
+}
+
+/// The values of the `text-transform` property.
+#[deriving(Eq)]
+pub enum TextTransform {
+    TextTransformNone,
+    TextTransformUppercase,
+    TextTransformLowercase,
+    TextTransformCapitalize,
+}
+
+/// Applies `text-transform` to `text`, which has already had its whitespace processed.
+///
+/// Case mapping can change the number of characters, as when `ß` is uppercased to `SS`, so this
+/// also returns the offset in `text` of the character that each character of the result came
+/// from. The vector is empty if every character maps to the one at the same offset.
+///
+/// `lang` is the language of the text, which decides how some letters are mapped, such as the `i`
+/// of Turkish, whose uppercase is dotted. `in_word` tells whether `text` continues a word that has
+/// already had its first letter capitalized, and the returned flag tells the same for the text
+/// after it.
+pub fn apply_text_transform(text: &str, transform: TextTransform, lang: Option<&str>,
+                            in_word: bool)
+                            -> (~str, ~[uint], bool) {
+    if transform == TextTransformNone {
+        return (text.to_owned(), ~[], in_word)
+    }
+
+    let dotted_i = has_dotted_i(lang);
+    let mut out_str = ~"";
+    let mut offsets = ~[];
+    let mut in_word = in_word;
+    let mut lengths_differ = false;
+    for (i, ch) in text.iter().enumerate() {
+        let added_chars = match transform {
+            TextTransformNone => {
+                out_str.push_char(ch);
+                1
+            }
+            TextTransformUppercase => push_uppercase(ch, dotted_i, &mut out_str),
+            TextTransformLowercase => push_lowercase(ch, dotted_i, &mut out_str),
+            TextTransformCapitalize => {
+                // The first letter after whitespace starts a word, so "(hello" becomes "(Hello"
+                // but "don't" doesn't become "Don'T".
+                if ch.is_alphabetic() && !in_word {
+                    in_word = true;
+                    push_titlecase(ch, dotted_i, &mut out_str)
+                } else {
+                    out_str.push_char(ch);
+                    if ch.is_whitespace() {
+                        in_word = false;
+                    }
+                    1
+                }
+            }
+        };
+        lengths_differ = lengths_differ || added_chars != 1;
+        for _ in range(0, added_chars) {
+            offsets.push(i);
+        }
+    }
+    (out_str, if lengths_differ { offsets } else { ~[] }, in_word)
+}
+
+/// Splits `text` into the ranges of characters that synthesized small caps show as small capitals,
+/// which are the lowercase letters, and the ranges between them, which are shown as they are. Each
+/// range comes with whether it is made of lowercase letters.
+pub fn small_caps_ranges(text: &str) -> ~[(Range, bool)] {
+    fn is_lowercase(ch: char) -> bool {
+        let mut uppercase = ~"";
+        push_uppercase(ch, false, &mut uppercase) != 1 || uppercase.char_at(0) != ch
+    }
+
+    let mut ranges = ~[];
+    let mut start = 0;
+    let mut in_lowercase = false;
+    for (i, ch) in text.iter().enumerate() {
+        let lowercase = is_lowercase(ch);
+        if i > 0 && lowercase != in_lowercase {
+            ranges.push((Range::new(start, i - start), in_lowercase));
+            start = i;
+        }
+        in_lowercase = lowercase;
+    }
+    let length = text.char_len();
+    if length > start {
+        ranges.push((Range::new(start, length - start), in_lowercase));
+    }
+    ranges
+}
+
+/// Whether the case of `i` and `I` in a language is mapped to and from the dotted `İ` and the
+/// dotless `ı`, as it is in Turkish and Azerbaijani.
+fn has_dotted_i(lang: Option<&str>) -> bool {
+    match lang {
+        Some(lang) => {
+            let primary = lang.split_iter('-').next().unwrap().to_ascii_lower();
+            "tr" == primary || "az" == primary
+        }
+        None => false,
+    }
+}
+
+// FIXME: The standard library has no Unicode case mapping, so only Latin-1, Greek, and Cyrillic
+// letters, and the dotted and dotless i of Turkish, are mapped here.
+
+/// Pushes the uppercase of a character, and returns how many characters that took.
+fn push_uppercase(ch: char, dotted_i: bool, out_str: &mut ~str) -> uint {
+    match ch {
+        '\u00df' => {
+            out_str.push_str("SS");
+            return 2
+        }
+        'i' if dotted_i => out_str.push_char('\u0130'),
+        '\u00ff' => out_str.push_char('\u0178'),
+        '\u0131' => out_str.push_char('I'),
+        '\u03c2' => out_str.push_char('\u03a3'),
+        'a'..'z' | '\u00e0'..'\u00f6' | '\u00f8'..'\u00fe' | '\u03b1'..'\u03c1'
+        | '\u03c3'..'\u03c9' | '\u0430'..'\u044f' => {
+            out_str.push_char((ch as u32 - 0x20) as char)
+        }
+        '\u0450'..'\u045f' => out_str.push_char((ch as u32 - 0x50) as char),
+        _ => out_str.push_char(ch),
+    }
+    1
+}
+
+/// Pushes the lowercase of a character, and returns how many characters that took.
+fn push_lowercase(ch: char, dotted_i: bool, out_str: &mut ~str) -> uint {
+    match ch {
+        'I' if dotted_i => out_str.push_char('\u0131'),
+        '\u0130' if dotted_i => out_str.push_char('i'),
+        // Elsewhere, the dot is kept as a combining dot above.
+        '\u0130' => {
+            out_str.push_str("i\u0307");
+            return 2
+        }
+        '\u0178' => out_str.push_char('\u00ff'),
+        'A'..'Z' | '\u00c0'..'\u00d6' | '\u00d8'..'\u00de' | '\u0391'..'\u03a1'
+        | '\u03a3'..'\u03a9' | '\u0410'..'\u042f' => {
+            out_str.push_char((ch as u32 + 0x20) as char)
+        }
+        '\u0400'..'\u040f' => out_str.push_char((ch as u32 + 0x50) as char),
+        _ => out_str.push_char(ch),
+    }
+    1
+}
+
+/// Pushes the titlecase of a character, and returns how many characters that took.
+fn push_titlecase(ch: char, dotted_i: bool, out_str: &mut ~str) -> uint {
+    match ch {
+        '\u00df' => {
+            out_str.push_str("Ss");
+            2
+        }
+        _ => push_uppercase(ch, dotted_i, out_str),
+    }
+}
+
 pub fn float_to_fixed(before: int, f: float) -> i32 {
     (1i32 << before) * (f as i32)
 }
@@ -254,3 +473,92 @@ fn test_transform_compress_whitespace_newline() {
         assert!(trimmed_str == oracle_strs[i])
     }
 }
+
+#[test]
+fn test_collapse_whitespace_across_pieces() {
+    let normal = |text: &str| Some((text.to_owned(), CompressWhitespaceNewline));
+
+    let out = collapse_whitespace([normal("  foo "), normal(" bar"), normal("\n baz ")]);
+    assert!(out == ~[~"foo ", ~"bar", ~" baz "]);
+
+    // A sequence of spaces can span pieces that are only whitespace.
+    let out = collapse_whitespace([normal("foo "), normal(" \n "), normal(" bar")]);
+    assert!(out == ~[~"foo ", ~"", ~"bar"]);
+
+    // Something that isn't text ends a sequence of spaces.
+    let out = collapse_whitespace([normal("foo "), None, normal(" bar")]);
+    assert!(out == ~[~"foo ", ~"", ~" bar"]);
+
+    // Preserved spaces don't collapse with the collapsible ones after them.
+    let out = collapse_whitespace([Some((~"foo  ", CompressNone)), normal("  bar")]);
+    assert!(out == ~[~"foo  ", ~" bar"]);
+}
+
+#[test]
+fn test_collapse_whitespace_segment_breaks() {
+    let pre_line = |text: &str| Some((text.to_owned(), CompressWhitespace));
+
+    let out = collapse_whitespace([pre_line("foo  \n  bar")]);
+    assert!(out == ~[~"foo\nbar"]);
+
+    // The spaces before a preserved segment break are removed from earlier pieces too.
+    let out = collapse_whitespace([pre_line("foo "), pre_line("\n bar")]);
+    assert!(out == ~[~"foo", ~"\nbar"]);
+    let out = collapse_whitespace([pre_line("foo "), pre_line(" "), pre_line("\n\nbar")]);
+    assert!(out == ~[~"foo", ~"", ~"\n\nbar"]);
+
+    // Segment breaks are spaces where they collapse, and disappear where they are discarded.
+    let out = collapse_whitespace([Some((~"foo \n", CompressWhitespaceNewline)),
+                                   pre_line("\nbar")]);
+    assert!(out == ~[~"foo", ~"\nbar"]);
+    let out = collapse_whitespace([Some((~"foo\nbar", DiscardNewline))]);
+    assert!(out == ~[~"foobar"]);
+}
+
+#[test]
+fn test_apply_text_transform() {
+    let (text, offsets, in_word) = apply_text_transform("foo bar", TextTransformUppercase, None,
+                                                        false);
+    assert!(text == ~"FOO BAR" && offsets.is_empty() && !in_word);
+
+    let (text, offsets, _) = apply_text_transform("Straße", TextTransformUppercase, None, false);
+    assert!(text == ~"STRASSE");
+    assert!(offsets == ~[0, 1, 2, 3, 4, 4, 5]);
+
+    let (text, _, _) = apply_text_transform("ÀÉ Ωx", TextTransformLowercase, None, false);
+    assert!(text == ~"àé ωx");
+
+    let (text, _, in_word) = apply_text_transform("(hello) don't", TextTransformCapitalize, None,
+                                                  false);
+    assert!(text == ~"(Hello) Don't" && in_word);
+    let (text, _, _) = apply_text_transform("ward bound", TextTransformCapitalize, None, true);
+    assert!(text == ~"ward Bound");
+}
+
+#[test]
+fn test_apply_text_transform_dotted_i() {
+    let (text, _, _) = apply_text_transform("istanbul", TextTransformUppercase, Some("tr"), false);
+    assert!(text == ~"\u0130STANBUL");
+    let (text, _, _) = apply_text_transform("KIR", TextTransformLowercase, Some("az-Latn"),
+                                            false);
+    assert!(text == ~"k\u0131r");
+    let (text, _, _) = apply_text_transform("izmir", TextTransformCapitalize, Some("TR"), false);
+    assert!(text == ~"\u0130zmir");
+
+    let (text, _, _) = apply_text_transform("istanbul", TextTransformUppercase, Some("en"), false);
+    assert!(text == ~"ISTANBUL");
+    let (text, offsets, _) = apply_text_transform("\u0130a", TextTransformLowercase, None, false);
+    assert!(text == ~"i\u0307a" && offsets == ~[0, 0, 1]);
+}
+
+#[test]
+fn test_small_caps_ranges() {
+    let ranges = small_caps_ranges("Hello, wORld");
+    let ranges: ~[(uint, uint, bool)] = ranges.iter().map(|&(ref range, lowercase)| {
+        (range.begin(), range.length(), lowercase)
+    }).collect();
+    assert!(ranges == ~[(0, 1, false), (1, 4, true), (5, 2, false), (7, 1, true), (8, 2, false),
+                        (10, 2, true)]);
+    assert!(small_caps_ranges("").is_empty());
+    assert!(small_caps_ranges("Straße").len() == 2);
+}
@@ -3,20 +3,43 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::cell::Cell;
+use std::util::replace;
 use geom::point::Point2D;
 use geom::size::Size2D;
 use geom::rect::Rect;
 use geom::matrix::identity;
 use gfx::render_task::{ReRenderMsg, UnusedBufferMsg};
-use servo_msg::compositor_msg::{LayerBuffer, LayerBufferSet, Epoch};
+use servo_msg::compositor_msg::{LayerBuffer, LayerBufferSet, Epoch, ScrollSnapPositions};
+use servo_msg::compositor_msg::{AutoScroll, InstantScroll, SmoothScroll};
+use servo_msg::compositor_msg::{OverscrollAuto, OverscrollBehavior, ScrollbarStyle};
+use servo_msg::compositor_msg::{HitTestData, ScrollFrameRegion, TouchAction};
+use ScrollToBehavior = servo_msg::compositor_msg::ScrollBehavior;
 use servo_msg::constellation_msg::PipelineId;
-use script::dom::event::{ClickEvent, MouseDownEvent, MouseUpEvent};
+use script::dom::event::{ClickEvent, MouseDownEvent, MouseUpEvent, MouseMoveEvent};
+use script::dom::event::PickerResultEvent;
+use script::dom::event::{FocusNavigation, FocusNavigationEvent, AccessKeyEvent};
+use script::dom::event::{EditingCommand, EditingCommandEvent, ScrollEvent, ZoomEvent};
 use script::script_task::SendEventMsg;
 use windowing::{MouseWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent, MouseWindowMouseUpEvent};
+use windowing::MouseWindowMoveEvent;
 use compositing::quadtree::{Quadtree, Normal, Invalid, Hidden};
+use compositing::scroll_animation::{ScrollAnimation, pull_overscroll, snapped_position};
+use compositing::scroll_animation::take_back_overscroll;
 use layers::layers::{ContainerLayerKind, ContainerLayer, TextureLayerKind, TextureLayer, TextureManager};
 use pipeline::Pipeline;
 use constellation::{SendableChildFrameTree, SendableFrameTree};
+use extra::time::precise_time_s;
+
+/// How long after the user stops scrolling a layer it snaps, in seconds.
+static SNAP_DELAY: float = 0.1;
+
+/// How near a snap position scrolling has to stop to snap to it without mandatory snapping, as a
+/// fraction of the size of the window.
+static SNAP_PROXIMITY: f32 = 0.3;
+
+/// How far the root layer can be pulled past the edge of its page, as a fraction of the size of
+/// the window.
+static OVERSCROLL_LIMIT: f32 = 0.15;
 
 /// The CompositorLayer represents an element on a page that has a unique scroll
 /// or animation behavior. This can include absolute positioned elements, iframes, etc.
@@ -49,6 +72,33 @@ pub struct CompositorLayer {
     epoch: Epoch,
     /// The behavior of this layer when a scroll message is received. 
     scroll_behavior: ScrollBehavior,
+    /// Where scrolling this layer can come to rest.
+    scroll_snap: ScrollSnapPositions,
+    /// Which regions of the page of this layer scroll gestures go to, as layout last said, if it
+    /// has yet.
+    hit_test_data: Option<HitTestData>,
+    /// When the user last scrolled this layer, if it hasn't snapped since.
+    last_scroll_time: Option<float>,
+    /// The animated scroll that this layer is in the middle of, if any.
+    scroll_animation: Option<ScrollAnimation>,
+    /// Whether the scrolls that script asks for are animated, as the `scroll-behavior` of the
+    /// page says.
+    smooth_scroll: bool,
+    /// What happens when scrolling this layer reaches the edge of its page, horizontally and
+    /// vertically, as the `overscroll-behavior` of the page says.
+    overscroll_behavior: (OverscrollBehavior, OverscrollBehavior),
+    /// How far past the edge of its page the user has pulled this layer, if it's the root.
+    overscroll: Point2D<f32>,
+    /// The animation of this layer springing back from past the edge of its page, if any.
+    overscroll_animation: Option<ScrollAnimation>,
+    /// How the scrollbars of this layer look.
+    scrollbar_style: ScrollbarStyle,
+    /// The buffers of the elements of the page with `position: fixed`, which are positioned in
+    /// the window rather than the page.
+    fixed_buffers: ~[~LayerBuffer],
+    /// The layer above the page that the fixed buffers are drawn in, which cancels out the
+    /// scrolling of the page so that they stay put.
+    fixed_layer: @mut ContainerLayer,
 }
 
 /// Helper struct for keeping CompositorLayer children organized.
@@ -98,6 +148,17 @@ impl CompositorLayer {
             hidden: true,
             epoch: Epoch(0),
             scroll_behavior: Scroll,
+            scroll_snap: ScrollSnapPositions::none(),
+            hit_test_data: None,
+            last_scroll_time: None,
+            scroll_animation: None,
+            smooth_scroll: false,
+            overscroll_behavior: (OverscrollAuto, OverscrollAuto),
+            overscroll: Point2D(0f32, 0f32),
+            overscroll_animation: None,
+            scrollbar_style: ScrollbarStyle::default(),
+            fixed_buffers: ~[],
+            fixed_layer: @mut ContainerLayer(),
         }
     }
     
@@ -136,52 +197,396 @@ impl CompositorLayer {
     // Move the layer by as relative specified amount in page coordinates. Does not change
     // the position of the layer relative to its parent. This also takes in a cursor position
     // to see if the mouse is over child layers first. If a layer successfully scrolled, returns
-    // true; otherwise returns false, so a parent layer can scroll instead.
+    // true; otherwise returns false.
     pub fn scroll(&mut self, delta: Point2D<f32>, cursor: Point2D<f32>, window_size: Size2D<f32>) -> bool {
+        let (scrolled, _) = self.scroll_chained(delta, cursor, window_size);
+        scrolled
+    }
+
+    // Scrolls as the user asks, which is as `scroll` does, except that when nothing can scroll
+    // any further, this layer, the root, is pulled past the edge of its page, unless its
+    // `overscroll-behavior` stops it. It springs back once the user stops scrolling.
+    pub fn user_scroll(&mut self,
+                       delta: Point2D<f32>,
+                       cursor: Point2D<f32>,
+                       window_size: Size2D<f32>)
+                       -> bool {
+        // Scrolling back from past the edge of the page first undoes the overscroll.
+        let old_overscroll = self.overscroll;
+        let (overscroll_x, delta_x) = take_back_overscroll(self.overscroll.x, delta.x);
+        let (overscroll_y, delta_y) = take_back_overscroll(self.overscroll.y, delta.y);
+        self.overscroll = Point2D(overscroll_x, overscroll_y);
+
+        let (scrolled, remaining) = self.scroll_chained(Point2D(delta_x, delta_y),
+                                                        cursor,
+                                                        window_size);
+        self.overscroll = Point2D(pull_overscroll(self.overscroll.x,
+                                                  remaining.x,
+                                                  window_size.width * OVERSCROLL_LIMIT),
+                                  pull_overscroll(self.overscroll.y,
+                                                  remaining.y,
+                                                  window_size.height * OVERSCROLL_LIMIT));
+        if self.overscroll == old_overscroll {
+            return scrolled
+        }
+        self.overscroll_animation = None;
+        self.last_scroll_time = Some(precise_time_s());
+        self.update_transform();
+        true
+    }
+
+    // Scrolls the child layer under the cursor, or else this layer. What a layer that has reached
+    // the edge of its page can't scroll goes on to the layer beneath, unless its
+    // `overscroll-behavior` stops it along that axis. Returns whether a layer scrolled, and what
+    // of the delta this layer passes on.
+    fn scroll_chained(&mut self, delta: Point2D<f32>, cursor: Point2D<f32>,
+                      window_size: Size2D<f32>) -> (bool, Point2D<f32>) {
         let cursor = cursor - self.scroll_offset;
-        for child in self.children.mut_iter().filter(|x| !x.child.hidden) {
-            match child.container.scissor {
-                None => {
-                    error!("CompositorLayer: unable to perform cursor hit test for layer");
-                }
-                Some(rect) => {
-                    if cursor.x >= rect.origin.x && cursor.x < rect.origin.x + rect.size.width
-                        && cursor.y >= rect.origin.y && cursor.y < rect.origin.y + rect.size.height
-                        && child.child.scroll(delta, cursor - rect.origin, rect.size) {
-                        return true;
-                    }
+        let mut delta = delta;
+        match self.scroll_target(cursor) {
+            Some((index, rect)) => {
+                let (scrolled, remaining) = self.children[index].child.scroll_chained(
+                    delta,
+                    cursor - rect.origin,
+                    rect.size);
+                if scrolled {
+                    return (true, Point2D(0f32, 0f32))
                 }
+                delta = remaining;
             }
+            None => {}
         }
 
         // This scroll event is mine!
         match self.scroll_behavior {
             Scroll => {
-                // Scroll this layer!
-                let old_origin = self.scroll_offset;
-                self.scroll_offset = self.scroll_offset + delta;
-
-                // bounds checking
-                let page_size = match self.page_size {
-                    Some(size) => size,
-                    None => fail!("CompositorLayer: tried to scroll with no page size set"),
+                // Scroll this layer! Once the user stops scrolling it, it snaps.
+                if self.set_scroll_offset(self.scroll_offset + delta, window_size) {
+                    if delta != Point2D(0f32, 0f32) {
+                        self.scroll_animation = None;
+                        self.last_scroll_time = Some(precise_time_s());
+                    }
+                    return (true, Point2D(0f32, 0f32))
+                }
+                let (behavior_x, behavior_y) = self.overscroll_behavior;
+                (false, Point2D(if behavior_x == OverscrollAuto { delta.x } else { 0.0 },
+                                if behavior_y == OverscrollAuto { delta.y } else { 0.0 }))
+            }
+            FixedPosition => (false, delta), // Ignore this scroll event.
+        }
+    }
+
+    // Finds the child layer that a scroll at the given point of the page goes to, and where its
+    // frame is in the page. This is decided from the hit-test data that layout sent, without
+    // asking the page; until layout has sent any, the clipping rects of the children are used.
+    fn scroll_target(&self, point: Point2D<f32>) -> Option<(uint, Rect<f32>)> {
+        match self.hit_test_data {
+            Some(ref data) => {
+                let region = match data.region_at(point) {
+                    Some(region) => region,
+                    None => return None,
                 };
-                let min_x = (window_size.width - page_size.width).min(&0.0);
-                self.scroll_offset.x = self.scroll_offset.x.clamp(&min_x, &0.0);
-                let min_y = (window_size.height - page_size.height).min(&0.0);
-                self.scroll_offset.y = self.scroll_offset.y.clamp(&min_y, &0.0);
-
-                // check to see if we scrolled
-                if old_origin - self.scroll_offset == Point2D(0f32, 0f32) {
-                    return false;
+                match region.kind {
+                    ScrollFrameRegion(subpage_id) => {
+                        let index = do self.children.iter().position |x| {
+                            !x.child.hidden && x.child.pipeline.subpage_id == Some(subpage_id)
+                        };
+                        index.map_move(|index| (index, region.rect))
+                    }
                 }
+            }
+            None => {
+                for (index, child) in self.children.iter().enumerate() {
+                    if child.child.hidden {
+                        loop
+                    }
+                    match child.container.scissor {
+                        None => {
+                            error!("CompositorLayer: unable to perform cursor hit test for layer");
+                        }
+                        Some(rect) => {
+                            let hit = point.x >= rect.origin.x &&
+                                point.x < rect.origin.x + rect.size.width &&
+                                point.y >= rect.origin.y &&
+                                point.y < rect.origin.y + rect.size.height;
+                            if hit {
+                                return Some((index, rect))
+                            }
+                        }
+                    }
+                }
+                None
+            }
+        }
+    }
 
-                self.root_layer.common.set_transform(identity().translate(self.scroll_offset.x,
-                                                                          self.scroll_offset.y,
-                                                                          0.0));
-                true
+    // Returns the touch gestures that the compositor handles for a touch that starts at the given
+    // point of the window, as the `touch-action` of the elements there, and in the frames there,
+    // says.
+    pub fn touch_action_at(&self, point: Point2D<f32>) -> TouchAction {
+        let point = point - self.scroll_offset;
+        let action = match self.hit_test_data {
+            Some(ref data) => data.touch_action_at(point),
+            None => TouchAction::auto(),
+        };
+        match self.scroll_target(point) {
+            Some((index, rect)) => {
+                action.intersect(&self.children[index].child.touch_action_at(point - rect.origin))
+            }
+            None => action,
+        }
+    }
+
+    // Returns the rect of the window, in page coordinates, that shows the page of the layer of
+    // the given pipeline, given that the window sees the top left of the viewport of this layer
+    // at `origin` and sees no more of it than `clip`. Returns `None` if the layer isn't found or
+    // can't be seen.
+    pub fn visible_rect(&self, pipeline_id: PipelineId, origin: Point2D<f32>, clip: Rect<f32>)
+                        -> Option<Rect<f32>> {
+        if self.pipeline.id == pipeline_id {
+            return Some(clip)
+        }
+        let page_origin = origin + self.scroll_offset + self.overscroll;
+        for child in self.children.iter() {
+            if child.child.hidden {
+                loop
+            }
+            let scissor = match child.container.scissor {
+                Some(scissor) => scissor,
+                None => loop,
+            };
+            let child_origin = page_origin + scissor.origin;
+            let child_clip = match Rect(child_origin, scissor.size).intersection(&clip) {
+                Some(child_clip) => child_clip,
+                None => loop,
+            };
+            let rect = child.child.visible_rect(pipeline_id.clone(), child_origin, child_clip);
+            if rect.is_some() {
+                return rect
+            }
+        }
+        None
+    }
+
+    // Moves the page of this layer to the given offset, kept within the page, and tells the page
+    // where its viewport is now. Returns false if the layer didn't move.
+    fn set_scroll_offset(&mut self, offset: Point2D<f32>, window_size: Size2D<f32>) -> bool {
+        let old_origin = self.scroll_offset;
+        self.scroll_offset = self.clamp_scroll_offset(offset, window_size);
+
+        // check to see if we scrolled
+        if old_origin - self.scroll_offset == Point2D(0f32, 0f32) {
+            return false;
+        }
+
+        self.update_transform();
+
+        // Tell the page where its viewport is now.
+        let origin = Point2D(-self.scroll_offset.x, -self.scroll_offset.y);
+        self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(),
+                                                    ScrollEvent(origin)));
+        true
+    }
+
+    // Moves the page of this layer to where it is scrolled, and pulled past its edge. The fixed
+    // layers only move with the pull, as they stay where they are in the window when it scrolls.
+    fn update_transform(&mut self) {
+        let offset = self.scroll_offset + self.overscroll;
+        self.root_layer.common.set_transform(identity().translate(offset.x, offset.y, 0.0));
+        self.fixed_layer.common.set_transform(identity().translate(-self.scroll_offset.x,
+                                                                   -self.scroll_offset.y,
+                                                                   0.0));
+    }
+
+    // Keeps a scroll offset of this layer within its page.
+    fn clamp_scroll_offset(&self, offset: Point2D<f32>, window_size: Size2D<f32>)
+                           -> Point2D<f32> {
+        let page_size = match self.page_size {
+            Some(size) => size,
+            None => fail!("CompositorLayer: tried to scroll with no page size set"),
+        };
+        let min_x = (window_size.width - page_size.width).min(&0.0);
+        let min_y = (window_size.height - page_size.height).min(&0.0);
+        Point2D(offset.x.clamp(&min_x, &0.0), offset.y.clamp(&min_y, &0.0))
+    }
+
+    // Scrolls the layer of the given pipeline so that the top left of the window is at the given
+    // position in its page, at once or animated. Returns true if a layer moved at once.
+    pub fn scroll_to(&mut self,
+                     pipeline_id: PipelineId,
+                     position: Point2D<f32>,
+                     behavior: ScrollToBehavior,
+                     window_size: Size2D<f32>)
+                     -> bool {
+        if self.pipeline.id != pipeline_id {
+            return do self.children.mut_iter().any |child| {
+                match child.container.scissor {
+                    Some(rect) => child.child.scroll_to(pipeline_id.clone(),
+                                                        position,
+                                                        behavior,
+                                                        rect.size),
+                    None => false,
+                }
+            }
+        }
+        if self.page_size.is_none() {
+            return false
+        }
+
+        let offset = Point2D(-position.x, -position.y);
+        let smooth = match behavior {
+            AutoScroll => self.smooth_scroll,
+            SmoothScroll => true,
+            InstantScroll => false,
+        };
+        self.last_scroll_time = None;
+        if smooth {
+            let target = self.clamp_scroll_offset(offset, window_size);
+            self.scroll_animation = Some(ScrollAnimation::new(self.scroll_offset,
+                                                              target,
+                                                              precise_time_s()));
+            false
+        } else {
+            self.scroll_animation = None;
+            self.set_scroll_offset(offset, window_size)
+        }
+    }
+
+    // Sets whether the scrolls that script asks for in the layer of the given pipeline are
+    // animated. Returns false if the layer is not found.
+    pub fn set_smooth_scroll(&mut self, pipeline_id: PipelineId, smooth: bool) -> bool {
+        if self.pipeline.id == pipeline_id {
+            self.smooth_scroll = smooth;
+            true
+        } else {
+            self.children.mut_iter().any(|x| x.child.set_smooth_scroll(pipeline_id.clone(),
+                                                                       smooth))
+        }
+    }
+
+    // Advances the animated scrolls of this layer and its children, first starting to animate
+    // each layer that the user has stopped scrolling to where it snaps. Returns true if any layer
+    // moved.
+    pub fn animate_scroll(&mut self, now: float, window_size: Size2D<f32>) -> bool {
+        let mut scrolled = false;
+        for child in self.children.mut_iter().filter(|x| !x.child.hidden) {
+            match child.container.scissor {
+                Some(rect) => scrolled = child.child.animate_scroll(now, rect.size) || scrolled,
+                None => {}
+            }
+        }
+
+        match self.last_scroll_time {
+            Some(time) if now - time >= SNAP_DELAY => {
+                self.last_scroll_time = None;
+                self.snap(now, window_size);
+                if self.overscroll != Point2D(0f32, 0f32) {
+                    self.overscroll_animation = Some(ScrollAnimation::new(self.overscroll,
+                                                                          Point2D(0f32, 0f32),
+                                                                          now));
+                }
+            }
+            _ => {}
+        }
+
+        let overscroll = match self.overscroll_animation {
+            Some(ref animation) => Some((animation.offset_at(now), animation.is_finished(now))),
+            None => None,
+        };
+        match overscroll {
+            Some((overscroll, finished)) => {
+                if finished {
+                    self.overscroll_animation = None;
+                }
+                self.overscroll = overscroll;
+                self.update_transform();
+                scrolled = true;
             }
-            FixedPosition => false, // Ignore this scroll event.
+            None => {}
+        }
+
+        let (offset, finished) = match self.scroll_animation {
+            Some(ref animation) => (animation.offset_at(now), animation.is_finished(now)),
+            None => return scrolled,
+        };
+        if finished {
+            self.scroll_animation = None;
+        }
+        self.set_scroll_offset(offset, window_size) || scrolled
+    }
+
+    // Starts animating the scroll of this layer to where scrolling comes to rest, if it isn't
+    // there already.
+    fn snap(&mut self, now: float, window_size: Size2D<f32>) {
+        let page_size = match self.page_size {
+            Some(size) => size,
+            None => return,
+        };
+        let position = Point2D(-self.scroll_offset.x, -self.scroll_offset.y);
+        let max = Point2D((page_size.width - window_size.width).max(&0.0),
+                          (page_size.height - window_size.height).max(&0.0));
+        let proximity = Point2D(window_size.width * SNAP_PROXIMITY,
+                                window_size.height * SNAP_PROXIMITY);
+        let snapped = snapped_position(&self.scroll_snap, position, max, proximity);
+        if snapped != position {
+            debug!("CompositorLayer: snapping scroll position %? to %?", position, snapped);
+            self.scroll_animation = Some(ScrollAnimation::new(self.scroll_offset,
+                                                              Point2D(-snapped.x, -snapped.y),
+                                                              now));
+        }
+    }
+
+    // Sets where scrolling the layer of the given pipeline can come to rest. Returns false if
+    // the layer is not found.
+    pub fn set_scroll_snap(&mut self, pipeline_id: PipelineId, snap: ScrollSnapPositions)
+                           -> bool {
+        if self.pipeline.id == pipeline_id {
+            self.scroll_snap = snap;
+            true
+        } else {
+            self.children.mut_iter().any(|x| x.child.set_scroll_snap(pipeline_id.clone(),
+                                                                     snap.clone()))
+        }
+    }
+
+    // Sets which regions of the layer of the given pipeline scroll gestures go to. Returns false
+    // if the layer is not found.
+    pub fn set_hit_test_data(&mut self, pipeline_id: PipelineId, data: HitTestData) -> bool {
+        if self.pipeline.id == pipeline_id {
+            self.hit_test_data = Some(data);
+            true
+        } else {
+            self.children.mut_iter().any(|x| x.child.set_hit_test_data(pipeline_id.clone(),
+                                                                       data.clone()))
+        }
+    }
+
+    // Sets what happens when scrolling the layer of the given pipeline reaches the edge of its
+    // page. Returns false if the layer is not found.
+    pub fn set_overscroll_behavior(&mut self,
+                                   pipeline_id: PipelineId,
+                                   x: OverscrollBehavior,
+                                   y: OverscrollBehavior)
+                                   -> bool {
+        if self.pipeline.id == pipeline_id {
+            self.overscroll_behavior = (x, y);
+            true
+        } else {
+            self.children.mut_iter().any(|child| {
+                child.child.set_overscroll_behavior(pipeline_id.clone(), x, y)
+            })
+        }
+    }
+
+    // Sets how the scrollbars of the layer of the given pipeline look. Returns false if the layer
+    // is not found.
+    pub fn set_scrollbar_style(&mut self, pipeline_id: PipelineId, style: ScrollbarStyle) -> bool {
+        if self.pipeline.id == pipeline_id {
+            self.scrollbar_style = style;
+            true
+        } else {
+            self.children.mut_iter().any(|x| x.child.set_scrollbar_style(pipeline_id.clone(),
+                                                                         style))
         }
     }
 
@@ -210,6 +615,7 @@ impl CompositorLayer {
             MouseWindowClickEvent(button, _) => ClickEvent(button, cursor),
             MouseWindowMouseDownEvent(button, _) => MouseDownEvent(button, cursor),
             MouseWindowMouseUpEvent(button, _) => MouseUpEvent(button, cursor),
+            MouseWindowMoveEvent(_) => MouseMoveEvent(cursor),
         };
         
         self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(), message));
@@ -218,6 +624,44 @@ impl CompositorLayer {
     // Given the current window size, determine which tiles need to be (re)rendered
     // and sends them off the the appropriate renderer.
     // Returns a bool that is true if the scene should be repainted.
+    // Sends a focus navigation command to the script task of this layer's pipeline.
+    // FIXME: Focus navigation only moves within the root document.
+    pub fn send_focus_navigation_event(&self, navigation: FocusNavigation) {
+        self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(),
+                                                    FocusNavigationEvent(navigation)));
+    }
+
+    // Sends a press of one of the registered access keys to the script task of this layer's
+    // pipeline.
+    pub fn send_access_key_event(&self, key: char) {
+        self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(), AccessKeyEvent(key)));
+    }
+
+    // Sends an undo or redo to the script task of this layer's pipeline.
+    // FIXME: Edits in iframes can't be undone.
+    pub fn send_editing_command(&self, command: EditingCommand) {
+        self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(),
+                                                    EditingCommandEvent(command)));
+    }
+
+    // Tells the script task of this layer's pipeline how far the page is zoomed in. Iframes
+    // aren't told, since they are zoomed with the page, and their visual viewport is their frame.
+    pub fn send_zoom_event(&self, scale: f32) {
+        self.pipeline.script_chan.send(SendEventMsg(self.pipeline.id.clone(), ZoomEvent(scale)));
+    }
+
+    // Sends the value the user picked for a form control, or `None` if they cancelled, to the
+    // script task of the specified pipeline. Returns false if the layer is not found.
+    pub fn send_picker_result(&self, pipeline_id: PipelineId, value: Option<~str>) -> bool {
+        if self.pipeline.id == pipeline_id {
+            self.pipeline.script_chan.send(SendEventMsg(pipeline_id, PickerResultEvent(value)));
+            true
+        } else {
+            self.children.iter().any(|x| x.child.send_picker_result(pipeline_id.clone(),
+                                                                    value.clone()))
+        }
+    }
+
     pub fn get_buffer_request(&mut self, window_rect: Rect<f32>, scale: f32) -> bool {
         let rect = Rect(Point2D(-self.scroll_offset.x + window_rect.origin.x,
                                 -self.scroll_offset.y + window_rect.origin.y),
@@ -451,6 +895,31 @@ impl CompositorLayer {
             };
         }
 
+        // Add the fixed layers above everything else.
+        if !self.fixed_buffers.is_empty() {
+            let mut current_fixed_child = self.fixed_layer.first_child;
+            while current_fixed_child.is_some() {
+                let trash = current_fixed_child.unwrap();
+                do current_fixed_child.unwrap().with_common |common| {
+                    current_fixed_child = common.next_sibling;
+                }
+                self.fixed_layer.remove_child(trash);
+            }
+            for buffer in self.fixed_buffers.iter() {
+                let texture_layer = @mut TextureLayer::new(@buffer.draw_target.clone()
+                                                           as @TextureManager,
+                                                           buffer.screen_pos.size);
+                let rect = buffer.rect;
+                let transform = identity().translate(rect.origin.x, rect.origin.y, 0.0);
+                let transform = transform.scale(rect.size.width, rect.size.height, 1.0);
+                texture_layer.common.set_transform(transform);
+                self.fixed_layer.add_child_end(TextureLayerKind(texture_layer));
+            }
+            self.fixed_layer.common.parent = None;
+            self.fixed_layer.common.prev_sibling = None;
+            self.fixed_layer.common.next_sibling = None;
+            self.root_layer.add_child_end(ContainerLayerKind(self.fixed_layer));
+        }
 
     }
     
@@ -490,6 +959,39 @@ impl CompositorLayer {
         }
     }
 
+    // Replaces the fixed buffers of the specified layer, sending the old ones back to the renderer.
+    // Returns false if the layer is not found. If the epoch of the message does not match the
+    // layer's epoch, the message is ignored.
+    pub fn set_fixed_buffers(&mut self,
+                             pipeline_id: PipelineId,
+                             new_buffers: ~LayerBufferSet,
+                             epoch: Epoch)
+                             -> bool {
+        let cell = Cell::new(new_buffers);
+        if self.pipeline.id == pipeline_id {
+            if self.epoch != epoch {
+                debug!("compositor epoch mismatch: %? != %?, id: %?",
+                       self.epoch,
+                       epoch,
+                       self.pipeline.id);
+                self.pipeline.render_chan.send(UnusedBufferMsg(cell.take().buffers));
+                return true;
+            }
+            let old_buffers = replace(&mut self.fixed_buffers, cell.take().buffers);
+            if !old_buffers.is_empty() {
+                self.pipeline.render_chan.send(UnusedBufferMsg(old_buffers));
+            }
+            match self.quadtree {
+                NoTree(*) => {}
+                Tree(_) => self.build_layer_tree(),
+            }
+            true
+        } else {
+            self.children.mut_iter().map(|x| &mut x.child)
+                .any(|x| x.set_fixed_buffers(pipeline_id, cell.take(), epoch))
+        }
+    }
+
     // Deletes a specified sublayer, including hidden children. Returns false if the layer is not found.
     pub fn delete(&mut self, pipeline_id: PipelineId) -> bool {
         match self.children.iter().position(|x| x.child.pipeline.id == pipeline_id) {
@@ -9,10 +9,17 @@ use windowing::{ApplicationMethods, WindowEvent, WindowMethods};
 use windowing::{IdleWindowEvent, ResizeWindowEvent, LoadUrlWindowEvent, MouseWindowEventClass};
 use windowing::{ScrollWindowEvent, ZoomWindowEvent, NavigationWindowEvent, FinishedWindowEvent};
 use windowing::{QuitWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent, MouseWindowMouseUpEvent};
+use windowing::MouseWindowMoveEvent;
+use windowing::{PickerWindowEvent, FocusWindowEvent, AccessKeyWindowEvent, EditingWindowEvent};
+use windowing::{PinchZoomWindowEvent, TouchScrollWindowEvent};
 
 use servo_msg::compositor_msg::{RenderListener, LayerBufferSet, RenderState};
-use servo_msg::compositor_msg::{ReadyState, ScriptListener, Epoch};
+use servo_msg::compositor_msg::{ReadyState, ScriptListener, Epoch, PickerKind};
+use servo_msg::compositor_msg::{OverscrollBehavior, ScrollBehavior, ScrollSnapPositions};
+use servo_msg::compositor_msg::{HitTestData, TouchAction};
+use servo_msg::compositor_msg::ScrollbarStyle;
 use servo_msg::constellation_msg::{ConstellationChan, NavigateMsg, PipelineId, ResizedWindowMsg, LoadUrlMsg};
+use servo_msg::constellation_msg::Screenshot;
 use servo_msg::constellation_msg;
 use gfx::opts::Opts;
 
@@ -21,7 +28,7 @@ use azure::azure::AzGLContext;
 use std::comm;
 use std::comm::{Chan, SharedChan, Port};
 use std::num::Orderable;
-use std::vec;
+use std::util::replace;
 use std::rt::rtio::RtioTimer;
 use std::rt::io::timer::Timer;
 use geom::matrix::identity;
@@ -36,16 +43,21 @@ use opengles::gl2;
 use png;
 use servo_util::{time, url};
 use servo_util::time::profile;
-use servo_util::time::ProfilerChan;
+use servo_util::time::{CompositeStage, FrameStageMsg, InputStage, ProfilerChan};
 
 use extra::future::from_value;
-use extra::time::precise_time_s;
+use extra::time::{precise_time_ns, precise_time_s};
 
 use constellation::SendableFrameTree;
 use compositing::compositor_layer::CompositorLayer;
+use compositing::scrollbar::Scrollbar;
+use compositing::screenshot::{crop_and_scale, fit_size, flip_rows};
 
 mod quadtree;
 mod compositor_layer;
+mod scroll_animation;
+mod scrollbar;
+mod screenshot;
 
 
 /// The implementation of the layers-based compositor.
@@ -67,6 +79,24 @@ impl ScriptListener for CompositorChan {
         self.chan.send(InvalidateRect(id, rect));
     }
 
+    fn open_picker(&self, id: PipelineId, kind: PickerKind, value: ~str) {
+        self.chan.send(OpenPicker(id, kind, value));
+    }
+
+    fn set_access_keys(&self, id: PipelineId, keys: ~[char]) {
+        self.chan.send(SetAccessKeys(id, keys));
+    }
+
+    fn scroll_to(&self, id: PipelineId, position: Point2D<f32>, behavior: ScrollBehavior) {
+        self.chan.send(ScrollLayerTo(id, position, behavior));
+    }
+
+    fn stop_slow_script(&self, id: PipelineId) -> bool {
+        let (port, chan) = comm::stream();
+        self.chan.send(SlowScript(id, chan));
+        port.recv()
+    }
+
     fn close(&self) {
         self.chan.send(Exit);
     }
@@ -86,6 +116,10 @@ impl RenderListener for CompositorChan {
         self.chan.send(Paint(id, layer_buffer_set, epoch))
     }
 
+    fn paint_fixed_layers(&self, id: PipelineId, layer_buffer_set: ~LayerBufferSet, epoch: Epoch) {
+        self.chan.send(PaintFixedLayers(id, layer_buffer_set, epoch))
+    }
+
     fn new_layer(&self, id: PipelineId, page_size: Size2D<uint>) {
         let Size2D { width, height } = page_size;
         self.chan.send(NewLayer(id, Size2D(width as f32, height as f32)))
@@ -101,6 +135,24 @@ impl RenderListener for CompositorChan {
                                    new_rect.size.height as f32));
         self.chan.send(SetLayerClipRect(id, new_rect))
     }
+    fn set_layer_scroll_snap(&self, id: PipelineId, snap: ScrollSnapPositions) {
+        self.chan.send(SetLayerScrollSnap(id, snap))
+    }
+    fn set_layer_hit_test_data(&self, id: PipelineId, data: HitTestData) {
+        self.chan.send(SetLayerHitTestData(id, data))
+    }
+    fn set_layer_smooth_scroll(&self, id: PipelineId, smooth: bool) {
+        self.chan.send(SetLayerSmoothScroll(id, smooth))
+    }
+    fn set_layer_overscroll_behavior(&self,
+                                     id: PipelineId,
+                                     x: OverscrollBehavior,
+                                     y: OverscrollBehavior) {
+        self.chan.send(SetLayerOverscrollBehavior(id, x, y))
+    }
+    fn set_layer_scrollbar_style(&self, id: PipelineId, style: ScrollbarStyle) {
+        self.chan.send(SetLayerScrollbarStyle(id, style))
+    }
 
     fn delete_layer(&self, id: PipelineId) {
         self.chan.send(DeleteLayer(id))
@@ -145,13 +197,41 @@ pub enum Msg {
     SetLayerPageSize(PipelineId, Size2D<f32>, Epoch),
     /// Alerts the compositor that the specified layer's clipping rect has changed.
     SetLayerClipRect(PipelineId, Rect<f32>),
+    /// Tells the compositor where scrolling the specified layer can come to rest.
+    SetLayerScrollSnap(PipelineId, ScrollSnapPositions),
+    /// Tells the compositor which regions of the specified layer scroll gestures go to.
+    SetLayerHitTestData(PipelineId, HitTestData),
+    /// Tells the compositor whether the scrolls that script asks for in the specified layer are
+    /// smooth.
+    SetLayerSmoothScroll(PipelineId, bool),
+    /// Tells the compositor what happens when scrolling the specified layer reaches the edge of
+    /// its page, horizontally and vertically.
+    SetLayerOverscrollBehavior(PipelineId, OverscrollBehavior, OverscrollBehavior),
+    /// Tells the compositor how the scrollbars of the specified layer look.
+    SetLayerScrollbarStyle(PipelineId, ScrollbarStyle),
     /// Alerts the compositor that the specified layer has been deleted.
     DeleteLayer(PipelineId),
     /// Invalidate a rect for a given layer
     InvalidateRect(PipelineId, Rect<uint>),
+    /// Asks the window to let the user pick a value for a form control of the given pipeline.
+    OpenPicker(PipelineId, PickerKind, ~str),
+    /// Tells the compositor which access keys the document of the given pipeline assigns.
+    SetAccessKeys(PipelineId, ~[char]),
+    /// Asks the window whether to stop a script of the given pipeline that has run for too long,
+    /// and sends back the answer.
+    SlowScript(PipelineId, Chan<bool>),
+    /// Scrolls the specified layer so that the top left of the window is at the given position
+    /// in its page.
+    ScrollLayerTo(PipelineId, Point2D<f32>, ScrollBehavior),
+    /// Sends back a snapshot of what the window shows of the given pipeline when it is next
+    /// composited, scaled down to fit the given size.
+    TakeScreenshot(PipelineId, Size2D<uint>, Chan<Option<Screenshot>>),
 
     /// Requests that the compositor paint the given layer buffer set for the given page size.
     Paint(PipelineId, ~LayerBufferSet, Epoch),
+    /// Replaces the buffers of the fixed layers of the given layer, which stay put in the window
+    /// while the layer scrolls.
+    PaintFixedLayers(PipelineId, ~LayerBufferSet, Epoch),
     /// Alerts the compositor to the current status of page loading.
     ChangeReadyState(ReadyState),
     /// Alerts the compositor to the current status of rendering.
@@ -232,6 +312,12 @@ impl CompositorTask {
         let mut compositor_layer: Option<CompositorLayer> = None;
         let mut constellation_chan: Option<ConstellationChan> = None;
 
+        // The pipeline whose form control the open picker, if any, is for.
+        let mut picker_pipeline: Option<PipelineId> = None;
+
+        // The snapshots to take when the window is next composited.
+        let mut screenshot_requests: ~[(PipelineId, Size2D<uint>, Chan<Option<Screenshot>>)] = ~[];
+
         // Get BufferRequests from each layer.
         let ask_for_tiles = || {
             let window_size_page = Size2D(window_size.width as f32 / world_zoom,
@@ -326,6 +412,52 @@ impl CompositorTask {
                         }
                     }
 
+                    SetLayerScrollSnap(id, snap) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                assert!(layer.set_scroll_snap(id, snap));
+                            }
+                            None => {}
+                        }
+                    }
+
+                    SetLayerHitTestData(id, data) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                assert!(layer.set_hit_test_data(id, data));
+                            }
+                            None => {}
+                        }
+                    }
+
+                    SetLayerSmoothScroll(id, smooth) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                assert!(layer.set_smooth_scroll(id, smooth));
+                            }
+                            None => {}
+                        }
+                    }
+
+                    SetLayerOverscrollBehavior(id, x, y) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                assert!(layer.set_overscroll_behavior(id, x, y));
+                            }
+                            None => {}
+                        }
+                    }
+
+                    SetLayerScrollbarStyle(id, style) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                assert!(layer.set_scrollbar_style(id, style));
+                                recomposite = true;
+                            }
+                            None => {}
+                        }
+                    }
+
                     DeleteLayer(id) => {
                         match compositor_layer {
                             Some(ref mut layer) => {
@@ -352,6 +484,18 @@ impl CompositorTask {
                         // it wishes.
                     }
 
+                    PaintFixedLayers(id, new_layer_buffer_set, epoch) => {
+                        match compositor_layer {
+                            Some(ref mut layer) => {
+                                assert!(layer.set_fixed_buffers(id, new_layer_buffer_set, epoch));
+                                recomposite = true;
+                            }
+                            None => {
+                                fail!("Compositor: given fixed layers with no CompositorLayer");
+                            }
+                        }
+                    }
+
                     InvalidateRect(id, rect) => {
                         match compositor_layer {
                             Some(ref mut layer) => {
@@ -364,14 +508,76 @@ impl CompositorTask {
                             None => {} // Nothing to do
                         }
                     }
+
+                    SlowScript(id, chan) => {
+                        debug!("compositor: slow script in %?", id);
+                        chan.send(window.stop_slow_script());
+                    }
+
+                    TakeScreenshot(id, size, chan) => {
+                        screenshot_requests.push((id, size, chan));
+                        recomposite = true;
+                    }
+
+                    OpenPicker(id, kind, value) => {
+                        picker_pipeline = Some(id);
+                        window.open_picker(kind, value);
+                    }
+
+                    ScrollLayerTo(id, position, behavior) => {
+                        let page_window = Size2D(window_size.width as f32 / world_zoom,
+                                                 window_size.height as f32 / world_zoom);
+                        for layer in compositor_layer.mut_iter() {
+                            if layer.scroll_to(id.clone(), position, behavior, page_window) {
+                                recomposite = true;
+                                ask_for_tiles();
+                            }
+                        }
+                    }
+
+                    // FIXME: Access keys only work in the root document, like focus navigation.
+                    SetAccessKeys(id, keys) => {
+                        match compositor_layer {
+                            Some(ref layer) if layer.pipeline.id == id => {
+                                window.set_access_keys(keys)
+                            }
+                            _ => {}
+                        }
+                    }
                 }
             }
         };
 
         let check_for_window_messages: &fn(WindowEvent) = |event| {
+            // Touch gestures pan and zoom as far as the `touch-action` of where they start allows,
+            // and are otherwise handled as the scrolls and zooms of the mouse and keyboard are.
+            let event = match event {
+                TouchScrollWindowEvent(delta, point) => {
+                    let page_point = Point2D(point.x as f32 / world_zoom,
+                                             point.y as f32 / world_zoom);
+                    let action = do compositor_layer.map_default(TouchAction::auto()) |layer| {
+                        layer.touch_action_at(page_point)
+                    };
+                    ScrollWindowEvent(Point2D(if action.pan_x { delta.x } else { 0.0 },
+                                              if action.pan_y { delta.y } else { 0.0 }),
+                                      point)
+                }
+                PinchZoomWindowEvent(magnification, point) => {
+                    let page_point = Point2D(point.x as f32 / world_zoom,
+                                             point.y as f32 / world_zoom);
+                    let action = do compositor_layer.map_default(TouchAction::auto()) |layer| {
+                        layer.touch_action_at(page_point)
+                    };
+                    if action.pinch_zoom { ZoomWindowEvent(magnification) } else { IdleWindowEvent }
+                }
+                event => event,
+            };
             match event {
                 IdleWindowEvent => {}
 
+                // These were turned into scrolls and zooms above.
+                TouchScrollWindowEvent(*) | PinchZoomWindowEvent(*) => {}
+
                 ResizeWindowEvent(width, height) => {
                     let new_size = Size2D(width, height);
                     if window_size != new_size {
@@ -405,6 +611,7 @@ impl CompositorTask {
                         MouseWindowClickEvent(_, p) => Point2D(p.x / world_zoom, p.y / world_zoom),
                         MouseWindowMouseDownEvent(_, p) => Point2D(p.x / world_zoom, p.y / world_zoom),
                         MouseWindowMouseUpEvent(_, p) => Point2D(p.x / world_zoom, p.y / world_zoom),
+                        MouseWindowMoveEvent(p) => Point2D(p.x / world_zoom, p.y / world_zoom),
                     };
                     for layer in compositor_layer.iter() {
                         layer.send_mouse_event(mouse_window_event, point);
@@ -419,7 +626,8 @@ impl CompositorTask {
                     let page_window = Size2D(window_size.width as f32 / world_zoom,
                                              window_size.height as f32 / world_zoom);
                     for layer in compositor_layer.mut_iter() {
-                        recomposite = layer.scroll(page_delta, page_cursor, page_window) || recomposite;
+                        recomposite = layer.user_scroll(page_delta, page_cursor, page_window) ||
+                            recomposite;
                     }
                     ask_for_tiles();
                 }
@@ -442,6 +650,9 @@ impl CompositorTask {
                                              window_size.height as f32 / world_zoom);
                     for layer in compositor_layer.mut_iter() {
                         layer.scroll(page_delta, page_cursor, page_window);
+                        if world_zoom != old_world_zoom {
+                            layer.send_zoom_event(world_zoom);
+                        }
                     }
 
                     recomposite = true;
@@ -458,6 +669,35 @@ impl CompositorTask {
                     }
                 }
                 
+                FocusWindowEvent(navigation) => {
+                    for layer in compositor_layer.iter() {
+                        layer.send_focus_navigation_event(navigation);
+                    }
+                }
+
+                AccessKeyWindowEvent(key) => {
+                    for layer in compositor_layer.iter() {
+                        layer.send_access_key_event(key);
+                    }
+                }
+
+                EditingWindowEvent(command) => {
+                    for layer in compositor_layer.iter() {
+                        layer.send_editing_command(command);
+                    }
+                }
+
+                PickerWindowEvent(value) => {
+                    match picker_pipeline.take() {
+                        Some(id) => {
+                            for layer in compositor_layer.iter() {
+                                layer.send_picker_result(id.clone(), value.clone());
+                            }
+                        }
+                        None => debug!("Compositor: dropping picker result with no open picker"),
+                    }
+                }
+
                 FinishedWindowEvent => {
                     if self.opts.exit_after_load {
                         done = true;
@@ -474,12 +714,20 @@ impl CompositorTask {
         let profiler_chan = self.profiler_chan.clone();
         let write_png = self.opts.output_file.is_some();
         let exit = self.opts.exit_after_load;
+        let render_backend = self.opts.render_backend;
+        let mut scrollbar = Scrollbar::new();
         let composite = || {
+            let start_time = precise_time_ns();
             do profile(time::CompositingCategory, profiler_chan.clone()) {
                 debug!("compositor: compositing");
                 // Adjust the layer dimensions as necessary to correspond to the size of the window.
                 scene.size = window.size();
 
+                // Draw the scrollbar of the root layer over it, where it has scrolled to.
+                for layer in compositor_layer.iter() {
+                    scrollbar.update(root_layer, layer, render_backend, window_size, world_zoom);
+                }
+
                 // Render the scene.
                 rendergl::render_scene(context, &scene);
             }
@@ -494,15 +742,7 @@ impl CompositorTask {
                                                   height as gl2::GLsizei,
                                                   gl2::RGB, gl2::UNSIGNED_BYTE);
                 // flip image vertically (texture is upside down)
-                let orig_pixels = pixels.clone();
-                let stride = width * 3;
-                for y in range(0, height) {
-                    let dst_start = y * stride;
-                    let src_start = (height - y - 1) * stride;
-                    vec::bytes::copy_memory(pixels.mut_slice(dst_start, dst_start + stride),
-                                            orig_pixels.slice(src_start, src_start + stride),
-                                            stride);
-                }
+                flip_rows(pixels, width, height);
                 let img = png::Image {
                     width: width as u32,
                     height: height as u32,
@@ -515,7 +755,48 @@ impl CompositorTask {
                 done = true;
             }
 
+            // Take the snapshots that were asked for, from the back buffer too.
+            if !screenshot_requests.is_empty() {
+                let (width, height) = (window_size.width as uint, window_size.height as uint);
+                let mut pixels = gl2::read_pixels(0, 0,
+                                                  width as gl2::GLsizei,
+                                                  height as gl2::GLsizei,
+                                                  gl2::RGB, gl2::UNSIGNED_BYTE);
+                flip_rows(pixels, width, height);
+                let window_rect = Rect(Point2D(0f32, 0f32),
+                                       Size2D(width as f32 / world_zoom,
+                                              height as f32 / world_zoom));
+                let requests = replace(&mut screenshot_requests, ~[]);
+                for (id, size, chan) in requests.move_iter() {
+                    let rect = do compositor_layer.chain_ref |layer| {
+                        layer.visible_rect(id.clone(), window_rect.origin, window_rect)
+                    };
+                    let screenshot = do rect.chain |rect| {
+                        // The rect is in page coordinates, and the pixels are in the window's.
+                        let left = ((rect.origin.x * world_zoom) as uint).min(&width);
+                        let top = ((rect.origin.y * world_zoom) as uint).min(&height);
+                        let right = (((rect.origin.x + rect.size.width) * world_zoom) as uint)
+                            .min(&width);
+                        let bottom = (((rect.origin.y + rect.size.height) * world_zoom) as uint)
+                            .min(&height);
+                        let crop = Rect(Point2D(left, top), Size2D(right - left, bottom - top));
+                        let fitted = fit_size(crop.size, size);
+                        if fitted.width == 0 || fitted.height == 0 {
+                            None
+                        } else {
+                            Some(Screenshot {
+                                width: fitted.width,
+                                height: fitted.height,
+                                pixels: crop_and_scale(pixels, width, crop, fitted),
+                            })
+                        }
+                    };
+                    chan.send(screenshot);
+                }
+            }
+
             window.present();
+            profiler_chan.send(FrameStageMsg(CompositeStage, start_time, precise_time_ns()));
 
             if exit { done = true; }
         };
@@ -526,8 +807,29 @@ impl CompositorTask {
             // Check for new messages coming from the rendering task.
             check_for_messages(&self.port);
 
-            // Check for messages coming from the windowing system.
-            check_for_window_messages(window.recv());
+            // Check for messages coming from the windowing system. Handling input is the first
+            // stage of the frames that it leads to.
+            match window.recv() {
+                IdleWindowEvent => {}
+                event => {
+                    let start_time = precise_time_ns();
+                    check_for_window_messages(event);
+                    profiler_chan.send(FrameStageMsg(InputStage, start_time, precise_time_ns()));
+                }
+            }
+
+            // Advance any animated scrolls.
+            let page_window = Size2D(window_size.width as f32 / world_zoom,
+                                     window_size.height as f32 / world_zoom);
+            let now = precise_time_s();
+            let mut scrolled = false;
+            for layer in compositor_layer.mut_iter() {
+                scrolled = layer.animate_scroll(now, page_window) || scrolled;
+            }
+            if scrolled {
+                recomposite = true;
+                ask_for_tiles();
+            }
 
             if recomposite {
                 recomposite = false;
@@ -0,0 +1,92 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Screenshots of the frames of pipelines, which embedders ask the constellation for to show
+//! previews of tabs. They are cut from what the compositor last drew in the window, and scaled
+//! down to the size asked for.
+
+use geom::rect::Rect;
+use geom::size::Size2D;
+use std::vec;
+
+/// How many bytes each pixel of a screenshot takes, as red, green and blue.
+static BYTES_PER_PIXEL: uint = 3;
+
+/// Flips the rows of the pixels of an image, so that the rows of pixels read from the window,
+/// which go from the bottom up, go from the top down.
+pub fn flip_rows(pixels: &mut [u8], width: uint, height: uint) {
+    let stride = width * BYTES_PER_PIXEL;
+    let orig_pixels = pixels.to_owned();
+    for y in range(0, height) {
+        let dst_start = y * stride;
+        let src_start = (height - y - 1) * stride;
+        vec::bytes::copy_memory(pixels.mut_slice(dst_start, dst_start + stride),
+                                orig_pixels.slice(src_start, src_start + stride),
+                                stride);
+    }
+}
+
+/// Returns the largest size that fits in `max` with the aspect ratio of `size`, without making
+/// it any larger than it is.
+pub fn fit_size(size: Size2D<uint>, max: Size2D<uint>) -> Size2D<uint> {
+    if size.width == 0 || size.height == 0 {
+        return Size2D(0, 0)
+    }
+    let scale = (max.width as f32 / size.width as f32).min(&(max.height as f32 /
+                                                             size.height as f32)).min(&1.0);
+    Size2D(((size.width as f32 * scale) as uint).max(&1),
+           ((size.height as f32 * scale) as uint).max(&1))
+}
+
+/// Cuts `rect` out of the pixels of an image of the given width, which go from the top down, and
+/// scales it to `size`. Each pixel of the result is the average of the pixels it covers.
+pub fn crop_and_scale(pixels: &[u8], width: uint, rect: Rect<uint>, size: Size2D<uint>)
+                      -> ~[u8] {
+    let mut result = vec::with_capacity(size.width * size.height * BYTES_PER_PIXEL);
+    for y in range(0, size.height) {
+        let (top, bottom) = covered_span(rect.origin.y, rect.size.height, y, size.height);
+        for x in range(0, size.width) {
+            let (left, right) = covered_span(rect.origin.x, rect.size.width, x, size.width);
+            let mut sums = [0u, 0, 0];
+            for src_y in range(top, bottom) {
+                for src_x in range(left, right) {
+                    let start = (src_y * width + src_x) * BYTES_PER_PIXEL;
+                    for channel in range(0, BYTES_PER_PIXEL) {
+                        sums[channel] += pixels[start + channel] as uint;
+                    }
+                }
+            }
+            let count = (bottom - top) * (right - left);
+            for channel in range(0, BYTES_PER_PIXEL) {
+                result.push((sums[channel] / count) as u8);
+            }
+        }
+    }
+    result
+}
+
+/// Returns the span of the source that the pixel at `index` of `count` pixels covers when `length`
+/// pixels from `start` are scaled to `count`. Every pixel covers at least one.
+fn covered_span(start: uint, length: uint, index: uint, count: uint) -> (uint, uint) {
+    let from = start + index * length / count;
+    let to = (start + (index + 1) * length / count).max(&(from + 1));
+    (from, to)
+}
+
+#[test]
+fn fit_size_keeps_aspect_ratio() {
+    assert!(fit_size(Size2D(800, 600), Size2D(200, 200)) == Size2D(200, 150));
+    assert!(fit_size(Size2D(100, 50), Size2D(200, 200)) == Size2D(100, 50));
+}
+
+#[test]
+fn crop_and_scale_averages_pixels() {
+    use geom::point::Point2D;
+
+    // A 3x2 image whose right two columns are cut out and scaled down to a single pixel.
+    let pixels = ~[0, 0, 0,   10, 20, 30,   30, 40, 50,
+                   0, 0, 0,   10, 20, 30,   30, 40, 50];
+    let result = crop_and_scale(pixels, 3, Rect(Point2D(1, 0), Size2D(2, 2)), Size2D(1, 1));
+    assert!(result == ~[20, 30, 40]);
+}
@@ -0,0 +1,152 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Animated scrolling, choosing where scrolling comes to rest when the page snaps it, and pulling
+//! the root layer past the edge of its page when scrolling overshoots it.
+
+use geom::point::Point2D;
+use servo_msg::compositor_msg::{MandatorySnap, ProximitySnap, ScrollSnapPositions};
+
+/// How long an animated scroll takes, in seconds.
+static SCROLL_ANIMATION_DURATION: float = 0.2;
+
+/// A scroll from one scroll offset to another that is animated over time.
+pub struct ScrollAnimation {
+    from: Point2D<f32>,
+    to: Point2D<f32>,
+    /// When the animation started, in seconds.
+    start_time: float,
+}
+
+impl ScrollAnimation {
+    pub fn new(from: Point2D<f32>, to: Point2D<f32>, start_time: float) -> ScrollAnimation {
+        ScrollAnimation {
+            from: from,
+            to: to,
+            start_time: start_time,
+        }
+    }
+
+    /// Returns the scroll offset at the given time. The scroll eases out, so that it comes to rest
+    /// gently.
+    pub fn offset_at(&self, time: float) -> Point2D<f32> {
+        let progress = ((time - self.start_time) / SCROLL_ANIMATION_DURATION).clamp(&0.0, &1.0);
+        let eased = (1.0 - (1.0 - progress) * (1.0 - progress) * (1.0 - progress)) as f32;
+        Point2D(self.from.x + (self.to.x - self.from.x) * eased,
+                self.from.y + (self.to.y - self.from.y) * eased)
+    }
+
+    pub fn is_finished(&self, time: float) -> bool {
+        time - self.start_time >= SCROLL_ANIMATION_DURATION
+    }
+}
+
+/// Returns the snap position nearest to a scroll position along an axis, if scrolling should
+/// come to rest there. Without mandatory snapping, only positions within `proximity` are snapped
+/// to.
+fn snap_along_axis(positions: &[f32], position: f32, max: f32, mandatory: bool, proximity: f32)
+                   -> Option<f32> {
+    let mut nearest: Option<f32> = None;
+    for &snap_position in positions.iter() {
+        let snap_position = snap_position.clamp(&0.0, &max);
+        match nearest {
+            Some(nearest) if (nearest - position).abs() <= (snap_position - position).abs() => {}
+            _ => nearest = Some(snap_position),
+        }
+    }
+    match nearest {
+        Some(nearest) if mandatory || (nearest - position).abs() <= proximity => Some(nearest),
+        _ => None,
+    }
+}
+
+/// Returns the scroll position, in page coordinates, at which scrolling that stopped at the
+/// given one comes to rest. `max` is the largest scroll position, and `proximity` how near a
+/// snap position scrolling has to stop to snap to it without mandatory snapping.
+pub fn snapped_position(snap: &ScrollSnapPositions,
+                        position: Point2D<f32>,
+                        max: Point2D<f32>,
+                        proximity: Point2D<f32>)
+                        -> Point2D<f32> {
+    let mandatory = match snap.strictness {
+        MandatorySnap => true,
+        ProximitySnap => false,
+    };
+    Point2D(snap_along_axis(snap.x, position.x, max.x, mandatory, proximity.x)
+                .unwrap_or_default(position.x),
+            snap_along_axis(snap.y, position.y, max.y, mandatory, proximity.y)
+                .unwrap_or_default(position.y))
+}
+
+/// Returns how far past the edge of its page the root layer is pulled along an axis, after the
+/// user scrolls it by `delta` further than it can scroll when it was pulled `overscroll` past
+/// already. The further it is pulled, the more it resists, up to `limit`.
+pub fn pull_overscroll(overscroll: f32, delta: f32, limit: f32) -> f32 {
+    let resistance = 1.0 - (overscroll.abs() / limit).min(&1.0);
+    (overscroll + delta * resistance * 0.5).clamp(&-limit, &limit)
+}
+
+/// Takes back what a scroll by `delta` along an axis in the other direction undoes of an
+/// overscroll, before the layer can scroll again. Returns the overscroll that is left, and the
+/// delta left to scroll by.
+pub fn take_back_overscroll(overscroll: f32, delta: f32) -> (f32, f32) {
+    if overscroll * delta >= 0.0 {
+        (overscroll, delta)
+    } else if delta.abs() <= overscroll.abs() {
+        (overscroll + delta, 0.0)
+    } else {
+        (0.0, overscroll + delta)
+    }
+}
+
+#[test]
+fn test_scroll_animation_eases_out() {
+    let animation = ScrollAnimation::new(Point2D(0f32, 0f32), Point2D(0f32, 100f32), 1.0);
+    assert!(animation.offset_at(0.5) == Point2D(0f32, 0f32));
+    let halfway = animation.offset_at(1.0 + SCROLL_ANIMATION_DURATION / 2.0);
+    assert!(halfway.y > 50.0 && halfway.y < 100.0);
+    assert!(!animation.is_finished(1.1));
+    assert!(animation.offset_at(2.0) == Point2D(0f32, 100f32));
+    assert!(animation.is_finished(2.0));
+}
+
+#[test]
+fn test_snapped_position() {
+    let mut snap = ScrollSnapPositions {
+        strictness: ProximitySnap,
+        x: ~[],
+        y: ~[0.0, 500.0, 1000.0, 2000.0],
+    };
+    let max = Point2D(0f32, 1500f32);
+    let proximity = Point2D(100f32, 100f32);
+
+    // Scrolling snaps to the nearest position, if it's near enough.
+    assert!(snapped_position(&snap, Point2D(0f32, 460f32), max, proximity) ==
+            Point2D(0f32, 500f32));
+    assert!(snapped_position(&snap, Point2D(0f32, 700f32), max, proximity) ==
+            Point2D(0f32, 700f32));
+    // Positions past the end of the page snap to its end.
+    assert!(snapped_position(&snap, Point2D(0f32, 1450f32), max, proximity) ==
+            Point2D(0f32, 1500f32));
+
+    snap.strictness = MandatorySnap;
+    assert!(snapped_position(&snap, Point2D(0f32, 700f32), max, proximity) ==
+            Point2D(0f32, 500f32));
+}
+
+#[test]
+fn test_overscroll() {
+    // Pulling resists more and more, and stops at the limit.
+    let first = pull_overscroll(0.0, 40.0, 100.0);
+    assert!(first == 20.0);
+    let second = pull_overscroll(first, 40.0, 100.0);
+    assert!(second > first && second - first < first);
+    assert!(pull_overscroll(100.0, 40.0, 100.0) == 100.0);
+    assert!(pull_overscroll(0.0, -400.0, 100.0) == -100.0);
+
+    // Scrolling back undoes the overscroll first.
+    assert!(take_back_overscroll(20.0, 10.0) == (20.0, 10.0));
+    assert!(take_back_overscroll(20.0, -15.0) == (5.0, 0.0));
+    assert!(take_back_overscroll(20.0, -50.0) == (0.0, -30.0));
+}
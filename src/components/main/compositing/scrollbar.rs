@@ -0,0 +1,133 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The scrollbar of the viewport. The compositor draws it over the page, rather than the page
+//! being rendered with it, since it stays put while the page scrolls beneath it.
+//!
+//! FIXME: Only the root layer has a scrollbar, and only a vertical one.
+
+use compositing::compositor_layer::CompositorLayer;
+
+use azure::AzFloat;
+use azure::azure_hl::{B8G8R8A8, BackendType, Color, ColorPattern, DrawTarget};
+use azure::azure_hl::current_gl_context;
+use geom::matrix::identity;
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use layers::layers::{ContainerLayer, TextureLayer, TextureLayerKind, TextureManager};
+
+/// The shortest the thumb gets, however long the page is, in pixels.
+static MIN_THUMB_LENGTH: f32 = 20.0;
+
+/// Returns where the thumb of a scrollbar with a track of the given length starts along it, and
+/// how long it is, when a page of the given length is scrolled to the given position in a window
+/// of the given length. Returns `None` if the page doesn't overflow the window.
+pub fn thumb_extent(track: f32, window: f32, page: f32, position: f32) -> Option<(f32, f32)> {
+    if page <= window {
+        return None
+    }
+    let length = (track * window / page).max(&MIN_THUMB_LENGTH).min(&track);
+    let progress = (position / (page - window)).clamp(&0.0, &1.0);
+    Some(((track - length) * progress, length))
+}
+
+/// The vertical scrollbar of the root layer, and the layer it's drawn in.
+pub struct Scrollbar {
+    /// The layer, at the end of the root of the scene so that it's drawn over the page, with what
+    /// it's drawn into, if the scrollbar is shown.
+    layer: Option<(@mut TextureLayer, DrawTarget, Size2D<uint>)>,
+}
+
+impl Scrollbar {
+    pub fn new() -> Scrollbar {
+        Scrollbar {
+            layer: None,
+        }
+    }
+
+    /// Draws the scrollbar for where a layer has scrolled to, as its page styles it, or hides it
+    /// if the page fits in the window. The root of the scene is scaled by the zoom, which the
+    /// scrollbar is kept out of.
+    pub fn update(&mut self,
+                  scene_root: @mut ContainerLayer,
+                  layer: &CompositorLayer,
+                  backend: BackendType,
+                  window_size: Size2D<uint>,
+                  zoom: f32) {
+        let thickness = layer.scrollbar_style.width.thickness();
+        let page_size = layer.page_size.unwrap_or_default(Size2D(0f32, 0f32));
+        let window_height = window_size.height as f32;
+        let extent = thumb_extent(window_height,
+                                  window_height / zoom,
+                                  page_size.height,
+                                  -layer.scroll_offset.y);
+        let thumb = match extent {
+            Some(thumb) if thickness > 0 && window_size.width > thickness => thumb,
+            _ => {
+                self.hide(scene_root);
+                return
+            }
+        };
+
+        // Only make a new layer when the scrollbar changes size.
+        let size = Size2D(thickness, window_size.height);
+        match self.layer {
+            Some((_, _, old_size)) if old_size == size => {}
+            _ => {
+                self.hide(scene_root);
+                let draw_target = DrawTarget::new_with_fbo(backend,
+                                                           current_gl_context(),
+                                                           Size2D(size.width as i32,
+                                                                  size.height as i32),
+                                                           B8G8R8A8);
+                let texture_layer = @mut TextureLayer::new(@draw_target.clone() as @TextureManager,
+                                                           size);
+                scene_root.add_child_end(TextureLayerKind(texture_layer));
+                self.layer = Some((texture_layer, draw_target, size));
+            }
+        }
+        let (texture_layer, ref draw_target, _) = *self.layer.get_ref();
+
+        let (thumb_color, track_color) = layer.scrollbar_style.colors.unwrap_or_default(
+            (Color(0.6, 0.6, 0.6, 1.0), Color(0.94, 0.94, 0.94, 1.0)));
+        let (thumb_start, thumb_length) = thumb;
+        let width = thickness as AzFloat;
+        draw_target.make_current();
+        draw_target.fill_rect(&Rect(Point2D(0.0, 0.0), Size2D(width, window_height as AzFloat)),
+                              &ColorPattern(track_color));
+        draw_target.fill_rect(&Rect(Point2D(0.0, thumb_start as AzFloat),
+                                    Size2D(width, thumb_length as AzFloat)),
+                              &ColorPattern(thumb_color));
+        draw_target.flush();
+
+        // The scrollbar is at the right of the window, whatever the zoom.
+        let transform = identity().translate((window_size.width - thickness) as f32 / zoom,
+                                             0.0,
+                                             0.0);
+        let transform = transform.scale(thickness as f32 / zoom, window_height / zoom, 1.0);
+        texture_layer.common.set_transform(transform);
+    }
+
+    fn hide(&mut self, scene_root: @mut ContainerLayer) {
+        match self.layer.take() {
+            Some((texture_layer, _, _)) => scene_root.remove_child(TextureLayerKind(texture_layer)),
+            None => {}
+        }
+    }
+}
+
+#[test]
+fn test_thumb_extent() {
+    // A page that fits has no thumb.
+    assert!(thumb_extent(500.0, 500.0, 400.0, 0.0).is_none());
+
+    // The thumb is as long, against the track, as the window is against the page.
+    assert!(thumb_extent(500.0, 500.0, 1000.0, 0.0) == Some((0.0, 250.0)));
+    assert!(thumb_extent(500.0, 500.0, 1000.0, 250.0) == Some((125.0, 250.0)));
+    assert!(thumb_extent(500.0, 500.0, 1000.0, 500.0) == Some((250.0, 250.0)));
+
+    // A long page has a thumb that can still be grabbed.
+    assert!(thumb_extent(500.0, 500.0, 1000000.0, 0.0) == Some((0.0, MIN_THUMB_LENGTH)));
+}
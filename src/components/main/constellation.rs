@@ -2,7 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use compositing::{CompositorChan, SetIds, SetLayerClipRect};
+use compositing::{CompositorChan, SetIds, SetLayerClipRect, TakeScreenshot};
+use css::select::{DefaultStylesheets, StylesheetText};
 use script::dom::event::ResizeEvent;
 
 use std::cell::Cell;
@@ -13,10 +14,12 @@ use geom::size::Size2D;
 use geom::rect::Rect;
 use gfx::opts::Opts;
 use pipeline::Pipeline;
-use servo_msg::constellation_msg::{ConstellationChan, ExitMsg, FrameRectMsg};
+use servo_msg::constellation_msg::{AddUserAgentStylesheetMsg, ConstellationChan, ExitMsg};
+use servo_msg::constellation_msg::FrameRectMsg;
 use servo_msg::constellation_msg::{InitLoadUrlMsg, LoadIframeUrlMsg, LoadUrlMsg};
 use servo_msg::constellation_msg::{Msg, NavigateMsg, NavigationType};
 use servo_msg::constellation_msg::{PipelineId, RendererReadyMsg, ResizedWindowMsg, SubpageId};
+use servo_msg::constellation_msg::{Screenshot, ScreenshotMsg};
 use servo_msg::constellation_msg;
 use script::script_task::{SendEventMsg, ResizeInactiveMsg, ExecuteMsg};
 use servo_net::image_cache_task::{ImageCacheTask, ImageCacheTaskClient};
@@ -42,6 +45,9 @@ pub struct Constellation {
     pending_sizes: HashMap<(PipelineId, SubpageId), Rect<f32>>,
     profiler_chan: ProfilerChan,
     opts: Opts,
+    /// The style sheets of the browser and of the user that every page starts with, which are
+    /// read once and given to each pipeline, along with those the embedder has added since.
+    default_stylesheets: DefaultStylesheets,
 }
 
 /// Stores the Id of the outermost frame's pipeline, along with a vector of children frames
@@ -258,6 +264,8 @@ impl Constellation {
                  profiler_chan: ProfilerChan)
                  -> ConstellationChan {
             
+        let default_stylesheets = Cell::new(DefaultStylesheets::load(opts.user_agent_stylesheets,
+                                                                     opts.user_stylesheets));
         let opts = Cell::new((*opts).clone());
 
         let (constellation_port, constellation_chan) = special_stream!(ConstellationChan);
@@ -284,6 +292,7 @@ impl Constellation {
                 pending_sizes: HashMap::new(),
                 profiler_chan: profiler_chan.take(),
                 opts: opts.take(),
+                default_stylesheets: default_stylesheets.take(),
             };
             constellation.run();
         }
@@ -349,6 +358,14 @@ impl Constellation {
             ResizedWindowMsg(new_size) => {
                 self.handle_resized_window_msg(new_size);
             }
+            // A snapshot of a pipeline, for tab previews.
+            ScreenshotMsg(pipeline_id, size, response_chan) => {
+                self.handle_screenshot_msg(pipeline_id, size, response_chan);
+            }
+            // A style sheet from the embedder for every page.
+            AddUserAgentStylesheetMsg(url, text) => {
+                self.handle_add_user_agent_stylesheet_msg(url, text);
+            }
         }
         true
     }
@@ -372,6 +389,7 @@ impl Constellation {
                                              self.resource_task.clone(),
                                              self.profiler_chan.clone(),
                                              self.opts.clone(),
+                                             self.default_stylesheets.clone(),
                                              {
                                                  let size = self.compositor_chan.get_size();
                                                  from_value(Size2D(size.width as uint, size.height as uint))
@@ -501,6 +519,7 @@ impl Constellation {
                                   self.image_cache_task.clone(),
                                   self.profiler_chan.clone(),
                                   self.opts.clone(),
+                                  self.default_stylesheets.clone(),
                                   source_pipeline,
                                   size_future)
         } else {
@@ -514,6 +533,7 @@ impl Constellation {
                              self.resource_task.clone(),
                              self.profiler_chan.clone(),
                              self.opts.clone(),
+                             self.default_stylesheets.clone(),
                              size_future)
         };
 
@@ -572,6 +592,7 @@ impl Constellation {
                                              self.resource_task.clone(),
                                              self.profiler_chan.clone(),
                                              self.opts.clone(),
+                                             self.default_stylesheets.clone(),
                                              size_future);
 
         if url.path.ends_with(".js") {
@@ -608,6 +629,7 @@ impl Constellation {
                     let old = self.current_frame().get_ref();
                     for frame in old.iter() {
                         frame.pipeline.revoke_paint_permission();
+                        frame.pipeline.freeze();
                     }
                 }
                 self.navigation_context.forward()
@@ -620,6 +642,7 @@ impl Constellation {
                     let old = self.current_frame().get_ref();
                     for frame in old.iter() {
                         frame.pipeline.revoke_paint_permission();
+                        frame.pipeline.freeze();
                     }
                 }
                 self.navigation_context.back()
@@ -680,6 +703,7 @@ impl Constellation {
 
                     for frame in to_revoke.iter() {
                         frame.pipeline.revoke_paint_permission();
+                        frame.pipeline.freeze();
                     }
 
                     // If to_add is not the root frame, then replace revoked_frame with it.
@@ -733,6 +757,34 @@ impl Constellation {
         }
     }
 
+    // Only the pipelines of the frame tree that is shown have been composited, so the others have
+    // no snapshot.
+    //
+    // FIXME: There is no WebDriver server to take screenshots with this yet.
+    fn handle_screenshot_msg(&self,
+                             pipeline_id: PipelineId,
+                             size: Size2D<uint>,
+                             response_chan: Chan<Option<Screenshot>>) {
+        let shown = do self.current_frame().map_default(false) |&frame_tree| {
+            frame_tree.contains(pipeline_id.clone())
+        };
+        if shown {
+            self.compositor_chan.send(TakeScreenshot(pipeline_id, size, response_chan));
+        } else {
+            response_chan.send(None);
+        }
+    }
+
+    /// Adds a style sheet at the user agent origin to the default sheets that later pipelines
+    /// start with, and to the pipelines that are already running, shown or not.
+    fn handle_add_user_agent_stylesheet_msg(&mut self, url: Url, text: ~str) {
+        self.default_stylesheets.add_user_agent_sheet(StylesheetText::new(url.clone(),
+                                                                          text.clone()));
+        for (_id, pipeline) in self.pipelines.iter() {
+            pipeline.add_user_agent_stylesheet(url.clone(), text.clone());
+        }
+    }
+
     // Grants a frame tree permission to paint; optionally updates navigation to reflect a new page
     fn grant_paint_permission(&mut self, frame_tree: @mut FrameTree, navigation_type: NavigationType) {
         // Give permission to paint to the new frame and all child frames
@@ -763,6 +815,7 @@ impl Constellation {
         port.recv();
         for frame in frame_tree.iter() {
             frame.pipeline.grant_paint_permission();
+            frame.pipeline.thaw();
         }
     }
 }
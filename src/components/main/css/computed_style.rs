@@ -0,0 +1,147 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The resolved values of the properties of elements, as `getComputedStyle()` gives them: the
+//! computed value for most properties, the value that running transitions have reached for
+//! animated colors, and the used value for the sizes of boxes that have been laid out.
+
+use css::node_style::StyledNode;
+use layout::animation::{AnimatableProperty, AnimatedNode, BackgroundColorProperty};
+use layout::animation::{BorderBottomColorProperty, BorderLeftColorProperty};
+use layout::animation::{BorderRightColorProperty, BorderTopColorProperty, ColorProperty};
+use layout::box::element_font_style;
+
+use geom::rect::Rect;
+use gfx::color::Color;
+use gfx::geometry::Au;
+use newcss::values::{CSSDisplayBlock, CSSDisplayInline, CSSDisplayInlineBlock};
+use newcss::values::{CSSDisplayInlineTable, CSSDisplayListItem, CSSDisplayNone, CSSDisplayTable};
+use newcss::values::{CSSDisplayTableCaption, CSSDisplayTableCell, CSSDisplayTableColumn};
+use newcss::values::{CSSDisplayTableColumnGroup, CSSDisplayTableFooterGroup};
+use newcss::values::{CSSDisplayTableHeaderGroup, CSSDisplayTableRow, CSSDisplayTableRowGroup};
+use newcss::values::{CSSFloatLeft, CSSFloatNone, CSSFloatRight};
+use newcss::values::{CSSPositionAbsolute, CSSPositionFixed, CSSPositionRelative};
+use newcss::values::{CSSPositionStatic};
+use newcss::values::{CSSTextAlignCenter, CSSTextAlignJustify, CSSTextAlignLeft};
+use newcss::values::{CSSTextAlignRight};
+use script::dom::node::{AbstractNode, LayoutView};
+use std::float;
+
+/// Returns the resolved value of a property of an element, serialized, or `None` if layout
+/// doesn't know the property. `bounds` are those of the boxes of the element, if it has been
+/// laid out. The element must have been styled.
+pub fn resolved_value(element: AbstractNode<LayoutView>,
+                      property: &str,
+                      bounds: Option<Rect<Au>>)
+                      -> Option<~str> {
+    let style = element.style();
+    let color = |property: AnimatableProperty| {
+        Some(serialize_color(element.animated_color(property)))
+    };
+    match property {
+        "background-color" => color(BackgroundColorProperty),
+        "border-bottom-color" => color(BorderBottomColorProperty),
+        "border-left-color" => color(BorderLeftColorProperty),
+        "border-right-color" => color(BorderRightColorProperty),
+        "border-top-color" => color(BorderTopColorProperty),
+        "color" => color(ColorProperty),
+        "display" => {
+            Some(match style.display(element.is_root()) {
+                CSSDisplayInline => ~"inline",
+                CSSDisplayBlock => ~"block",
+                CSSDisplayListItem => ~"list-item",
+                CSSDisplayInlineBlock => ~"inline-block",
+                CSSDisplayTable => ~"table",
+                CSSDisplayInlineTable => ~"inline-table",
+                CSSDisplayTableRowGroup => ~"table-row-group",
+                CSSDisplayTableHeaderGroup => ~"table-header-group",
+                CSSDisplayTableFooterGroup => ~"table-footer-group",
+                CSSDisplayTableRow => ~"table-row",
+                CSSDisplayTableColumnGroup => ~"table-column-group",
+                CSSDisplayTableColumn => ~"table-column",
+                CSSDisplayTableCell => ~"table-cell",
+                CSSDisplayTableCaption => ~"table-caption",
+                CSSDisplayNone => ~"none",
+            })
+        }
+        "float" => {
+            Some(match style.float() {
+                CSSFloatNone => ~"none",
+                CSSFloatLeft => ~"left",
+                CSSFloatRight => ~"right",
+            })
+        }
+        "font-family" => Some(element_font_style(element).families),
+        "font-size" => Some(serialize_px(element_font_style(element).pt_size)),
+        "font-style" => {
+            let font_style = element_font_style(element);
+            Some(if font_style.italic {
+                ~"italic"
+            } else if font_style.oblique {
+                ~"oblique"
+            } else {
+                ~"normal"
+            })
+        }
+        "position" => {
+            Some(match style.position() {
+                CSSPositionStatic => ~"static",
+                CSSPositionRelative => ~"relative",
+                CSSPositionAbsolute => ~"absolute",
+                CSSPositionFixed => ~"fixed",
+            })
+        }
+        "text-align" => {
+            Some(match style.text_align() {
+                CSSTextAlignLeft => ~"left",
+                CSSTextAlignRight => ~"right",
+                CSSTextAlignCenter => ~"center",
+                CSSTextAlignJustify => ~"justify",
+            })
+        }
+        // FIXME: The bounds of the display items of an element are its border box, so the used
+        // width and height include its padding and borders, whatever its `box-sizing`.
+        "width" => {
+            Some(bounds.map_default(~"auto", |bounds| {
+                serialize_px(*bounds.size.width.to_css_px())
+            }))
+        }
+        "height" => {
+            Some(bounds.map_default(~"auto", |bounds| {
+                serialize_px(*bounds.size.height.to_css_px())
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Serializes a color as CSSOM does: `rgb()` if it is opaque, and `rgba()` otherwise.
+fn serialize_color(color: Color) -> ~str {
+    let component = |value: f32| ((value * 255.0).clamp(&0.0, &255.0) + 0.5) as uint;
+    let (r, g, b) = (component(color.r), component(color.g), component(color.b));
+    if color.a >= 1.0 {
+        fmt!("rgb(%u, %u, %u)", r, g, b)
+    } else {
+        fmt!("rgba(%u, %u, %u, %s)", r, g, b, float::to_str_digits(color.a as float, 3))
+    }
+}
+
+/// Serializes a length in pixels, with at most two decimal places.
+fn serialize_px(px: float) -> ~str {
+    float::to_str_digits(px, 2) + "px"
+}
+
+#[test]
+fn test_serialize_color() {
+    use gfx::color::rgba;
+
+    assert!(serialize_color(rgba(255, 128, 0, 1.0)) == ~"rgb(255, 128, 0)");
+    assert!(serialize_color(rgba(0, 0, 255, 0.5)) == ~"rgba(0, 0, 255, 0.5)");
+}
+
+#[test]
+fn test_serialize_px() {
+    assert!(serialize_px(16.0) == ~"16px");
+    assert!(serialize_px(12.5) == ~"12.5px");
+}
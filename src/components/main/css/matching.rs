@@ -6,15 +6,35 @@
 
 use css::node_util::NodeUtil;
 use css::select_handler::NodeSelectHandler;
+use css::style_sharing::StyleSharingCandidateCache;
+use css::stylist::Stylist;
+use layout::animation::Animations;
 use layout::incremental;
+use layout::incremental::RestyleDamage;
+use layout::prefetch::Prefetcher;
 
+use script::dom::element::{ElementState, FocusState};
 use script::dom::node::{AbstractNode, LayoutView};
 use newcss::complete::CompleteSelectResults;
 use newcss::select::{SelectCtx, SelectResults};
+use script::style::generated_content::reads_attributes;
+use script::style::properties::ComputedValues;
+use script::style::properties::common_types::computed;
+use script::style::properties::longhands::content;
+use script::style::selectors::{After, Before, PseudoElement};
+use servo_util::bloom::BloomFilter;
 use servo_util::tree::TreeNodeRef;
+use extra::arc::Arc;
+use extra::time::precise_time_s;
+use std::ascii::StrAsciiExt;
+use std::ptr;
 
 pub trait MatchMethods {
-    fn restyle_subtree(&self, select_ctx: &SelectCtx);
+    fn restyle_subtree(&self,
+                       select_ctx: &SelectCtx,
+                       stylist: &Stylist,
+                       animations: &mut Animations,
+                       prefetcher: &mut Prefetcher);
 }
 
 impl MatchMethods for AbstractNode<LayoutView> {
@@ -23,46 +43,390 @@ impl MatchMethods for AbstractNode<LayoutView> {
      *
      * This is, importantly, the function that updates the layout data for
      * the node (the reader-auxiliary box in the COW model) with the
-     * computed style.
+     * computed style, along with the values that the new style system computes with `stylist`.
+     * Changes of the values of animatable properties start transitions in
+     * `animations`, and the resources that the new styles refer to are fetched with
+     * `prefetcher`.
      */
-    fn restyle_subtree(&self, select_ctx: &SelectCtx) {
-        // Only elements have styles
-        if self.is_element() {
-            do self.with_imm_element |elem| {
-                let inline_style = match elem.style_attribute {
-                    None => None,
-                    Some(ref sheet) => Some(sheet),
-                };
-                let select_handler = NodeSelectHandler { node: *self };
-                let incomplete_results = select_ctx.select_style(self, inline_style, &select_handler);
-                // Combine this node's results with its parent's to resolve all inherited values
-                let complete_results = compose_results(*self, incomplete_results);
+    fn restyle_subtree(&self,
+                       select_ctx: &SelectCtx,
+                       stylist: &Stylist,
+                       animations: &mut Animations,
+                       prefetcher: &mut Prefetcher) {
+        let mut ancestors = BloomFilter::new();
+        let mut style_sharing_cache = StyleSharingCandidateCache::new();
+        let mut changes = StyleChanges {
+            stylist: stylist,
+            animations: animations,
+            prefetcher: prefetcher,
+            time: precise_time_s(),
+        };
+        restyle_subtree_with_ancestors(*self,
+                                       select_ctx,
+                                       &mut ancestors,
+                                       &mut style_sharing_cache,
+                                       &mut changes);
+        assert!(ancestors.is_empty());
+        debug!("restyle_subtree: %u elements shared styles", style_sharing_cache.shared);
+    }
+}
+
+/// Accounts for changes of the states of elements that the dynamic pseudo-classes match: restyles
+/// the subtrees of the elements whose changed states selectors depend on, and marks the elements
+/// whose focus ring appears or disappears to be repainted. The subtrees of `restyled_elements`,
+/// which edited style rules match, are restyled too.
+pub fn restyle_state_changes(changes: &[(AbstractNode<LayoutView>, ElementState)],
+                             restyled_elements: &[AbstractNode<LayoutView>],
+                             select_ctx: &SelectCtx,
+                             stylist: &Stylist,
+                             animations: &mut Animations,
+                             prefetcher: &mut Prefetcher) {
+    let restyled_states = stylist.state_dependencies();
+    let mut restyled: ~[AbstractNode<LayoutView>] = ~[];
+    for &(element, changed) in changes.iter() {
+        if !element.have_css_select_results() {
+            loop
+        }
+        if changed.intersects(restyled_states) {
+            if !restyled.contains(&element) {
+                restyled.push(element)
+            }
+        } else if changed.has(FocusState) {
+            element.set_restyle_damage(element.get_restyle_damage().add(incremental::Repaint));
+        }
+    }
+    for &element in restyled_elements.iter() {
+        if element.have_css_select_results() && !restyled.contains(&element) {
+            restyled.push(element)
+        }
+    }
+
+    let mut changes = StyleChanges {
+        stylist: stylist,
+        animations: animations,
+        prefetcher: prefetcher,
+        time: precise_time_s(),
+    };
+    for &element in restyled.iter() {
+        // An element in the subtree of another is restyled with it.
+        let mut ancestor = element.parent_node();
+        while ancestor.is_some() && !restyled.contains(ancestor.get_ref()) {
+            ancestor = ancestor.unwrap().parent_node();
+        }
+        if ancestor.is_none() {
+            restyle_subtree_in_place(element, select_ctx, &mut changes);
+        }
+    }
+}
+
+/// Restyles the subtrees of the nodes that script has marked dirty since the last restyle, finding
+/// them by following the nodes that have dirty descendants down from `root`, and clears the marks
+/// of the nodes that are restyled.
+pub fn restyle_dirty_subtrees(root: AbstractNode<LayoutView>,
+                              select_ctx: &SelectCtx,
+                              stylist: &Stylist,
+                              animations: &mut Animations,
+                              prefetcher: &mut Prefetcher) {
+    let mut changes = StyleChanges {
+        stylist: stylist,
+        animations: animations,
+        prefetcher: prefetcher,
+        time: precise_time_s(),
+    };
+    restyle_dirty_subtrees_of(root, select_ctx, &mut changes);
+}
+
+/// Restyles the dirty subtrees under `node`, and returns whether a node in its subtree is still
+/// dirty: the contents of elements whose contents are skipped keep their marks until they are
+/// rendered.
+fn restyle_dirty_subtrees_of(node: AbstractNode<LayoutView>,
+                             select_ctx: &SelectCtx,
+                             changes: &mut StyleChanges) -> bool {
+    // An element on the way down that no restyle has reached yet is styled as if it were dirty,
+    // rather than left without boxes.
+    if node.is_dirty() || (node.is_element() && !node.have_css_select_results()) {
+        restyle_subtree_in_place(node, select_ctx, changes);
+    }
+    // Restyling the subtree of a dirty node clears the marks of the nodes in it, but not those of
+    // the nodes that have dirty descendants, which are cleared on the way down.
+    if !node.has_dirty_descendants() {
+        return false
+    }
+    if node.is_element() && node.read_layout_data(|data| data.skipped_contents_size.is_some()) {
+        return true
+    }
+    let mut still_dirty = false;
+    for kid in node.children() {
+        if restyle_dirty_subtrees_of(kid, select_ctx, changes) {
+            still_dirty = true
+        }
+    }
+    node.set_has_dirty_descendants(still_dirty);
+    still_dirty
+}
+
+/// Restyles the subtree of an element where it is in the document, seeding the filter of
+/// ancestors with its ancestors.
+fn restyle_subtree_in_place(element: AbstractNode<LayoutView>,
+                            select_ctx: &SelectCtx,
+                            changes: &mut StyleChanges) {
+    let mut ancestors = BloomFilter::new();
+    let keys = ancestor_keys_of(element);
+    for key in keys.iter() {
+        ancestors.insert(*key);
+    }
+    let mut style_sharing_cache = StyleSharingCandidateCache::new();
+    restyle_subtree_with_ancestors(element,
+                                   select_ctx,
+                                   &mut ancestors,
+                                   &mut style_sharing_cache,
+                                   changes);
+    for key in keys.iter() {
+        ancestors.remove(*key);
+    }
+    assert!(ancestors.is_empty());
+}
+
+/// Returns the strings that the ancestors of an element add to the filter of the ancestors of its
+/// descendants.
+fn ancestor_keys_of(node: AbstractNode<LayoutView>) -> ~[~str] {
+    let mut keys = ~[];
+    let mut ancestor = find_parent_element_node(node);
+    while ancestor.is_some() {
+        let element = ancestor.unwrap();
+        keys.push_all_move(ancestor_filter_keys(element));
+        ancestor = find_parent_element_node(element);
+    }
+    keys
+}
+
+/// Performs CSS selector matching on a subtree, where `ancestors` holds the names, IDs and
+/// classes of the ancestors of its root, so that selectors that need an ancestor that isn't there
+/// are rejected without walking up the tree. Elements that are styled like one that was styled
+/// just before them share its style and values instead.
+fn restyle_subtree_with_ancestors(node: AbstractNode<LayoutView>,
+                                  select_ctx: &SelectCtx,
+                                  ancestors: &mut BloomFilter,
+                                  style_sharing_cache: &mut StyleSharingCandidateCache,
+                                  changes: &mut StyleChanges) {
+    node.clear_dirty();
+
+    // Only elements have styles
+    if !node.is_element() {
+        for kid in node.children() {
+            restyle_subtree_with_ancestors(kid,
+                                           select_ctx,
+                                           ancestors,
+                                           style_sharing_cache,
+                                           changes);
+        }
+        return
+    }
+
+    match style_sharing_cache.find(node, changes.stylist) {
+        Some(candidate) => {
+            let style = candidate.get_shared_css_select_results();
+            let values = candidate.get_shared_computed_values();
+            changes.note_restyle(node, style.get(), values.get());
+            node.share_css_select_results(style);
+            node.share_computed_values(values);
+            for &pseudo_element in [Before, After].iter() {
+                let values = candidate.get_shared_pseudo_element_values(pseudo_element);
+                note_pseudo_element_restyle(node,
+                                            pseudo_element,
+                                            values.as_ref().map(|values| values.get()));
+                node.share_pseudo_element_values(pseudo_element, values);
+            }
+        }
+        None => {
+            restyle_element(node, select_ctx, &*ancestors, changes);
+            style_sharing_cache.insert(node);
+        }
+    }
+
+    // The contents of the element are skipped, so they aren't styled until they are rendered.
+    if node.read_layout_data(|data| data.skipped_contents_size.is_some()) {
+        return
+    }
+
+    // The element is an ancestor of everything in its subtree.
+    let keys = ancestor_filter_keys(node);
+    for key in keys.iter() {
+        ancestors.insert(*key);
+    }
+    for kid in node.children() {
+        restyle_subtree_with_ancestors(kid, select_ctx, ancestors, style_sharing_cache, changes);
+    }
+    for key in keys.iter() {
+        ancestors.remove(*key);
+    }
+}
+
+/// What restyling a subtree changes besides the styles of its elements.
+struct StyleChanges<'self> {
+    stylist: &'self Stylist,
+    animations: &'self mut Animations,
+    prefetcher: &'self mut Prefetcher,
+    /// When the subtree is restyled, in seconds.
+    time: float,
+}
+
+impl<'self> StyleChanges<'self> {
+    /// Accounts for an element that had a style being given a new one: computes the damage that
+    /// incremental layout will need to fix, and starts transitions. Must be called before the
+    /// element is given its new style.
+    fn note_restyle(&mut self,
+                    node: AbstractNode<LayoutView>,
+                    new: &CompleteSelectResults,
+                    new_values: &ComputedValues) {
+        if !node.have_css_select_results() {
+            return
+        }
+        let old = node.get_css_select_results();
+        // The values of the new style system aren't compared property by property yet, so any
+        // change of them is taken to damage everything. Values that the rule tree gives again are
+        // the same values.
+        let damage = match node.try_computed_values() {
+            Ok(old_values) if ptr::to_unsafe_ptr(old_values) != ptr::to_unsafe_ptr(new_values) &&
+                    *old_values != *new_values => RestyleDamage::all(),
+            _ => incremental::compute_damage(&node, old, new),
+        };
+        node.set_restyle_damage(damage);
+        self.animations.start_transitions(node,
+                                          &old.computed_style(),
+                                          &new.computed_style(),
+                                          new_values,
+                                          self.time);
+    }
+
+    /// Computes the values of the `::before` and `::after` pseudo-elements of an element that has
+    /// just been given its own values.
+    fn restyle_pseudo_elements(&mut self,
+                               node: AbstractNode<LayoutView>,
+                               ancestors: &BloomFilter) {
+        let values = node.get_shared_computed_values();
+        let root_font_size = root_font_size(node).unwrap_or(values.get().font_size);
+        for &pseudo_element in [Before, After].iter() {
+            let new_values = self.stylist.compute_pseudo_element_values(node,
+                                                                        ancestors,
+                                                                        pseudo_element,
+                                                                        &values,
+                                                                        root_font_size);
+            note_pseudo_element_restyle(node,
+                                        pseudo_element,
+                                        new_values.as_ref().map(|values| values.get()));
+            node.share_pseudo_element_values(pseudo_element, new_values);
+        }
+    }
+}
 
-                // If there was an existing style, compute the damage that
-                // incremental layout will need to fix.
-                if self.have_css_select_results() {
-                    let damage = incremental::compute_damage(self, self.get_css_select_results(), &complete_results);
-                    self.set_restyle_damage(damage);
-                }
-                self.set_css_select_results(complete_results);
-            };
+/// Accounts for a pseudo-element of an element being given new values. A change of them damages
+/// everything, as a change of the values of the element does. So does content that reads
+/// attributes, since the element may have been restyled because one of them changed. Must be
+/// called before the pseudo-element is given its new values.
+fn note_pseudo_element_restyle(node: AbstractNode<LayoutView>,
+                               pseudo_element: PseudoElement,
+                               new_values: Option<&ComputedValues>) {
+    let reads_attributes = do new_values.map_default(false) |values| {
+        match values.content {
+            content::Content(ref items) => reads_attributes(items.as_slice()),
+            _ => false,
         }
+    };
+    if reads_attributes || node.get_pseudo_element_values(pseudo_element) != new_values {
+        node.set_restyle_damage(RestyleDamage::all())
+    }
+}
+
+/// Matches an element against the selectors of the style sheets, and sets its style.
+fn restyle_element(node: AbstractNode<LayoutView>,
+                   select_ctx: &SelectCtx,
+                   ancestors: &BloomFilter,
+                   changes: &mut StyleChanges) {
+    do node.with_imm_element |elem| {
+        let inline_style = match elem.style_attribute {
+            None => None,
+            Some(ref sheet) => Some(sheet),
+        };
+        let select_handler = NodeSelectHandler { node: node, ancestors: ancestors };
+        let incomplete_results = select_ctx.select_style(&node, inline_style, &select_handler);
+        // Combine this node's results with its parent's to resolve all inherited values
+        let complete_results = compose_results(node, incomplete_results);
+        let values = compute_values(node, ancestors, changes.stylist);
 
-        for kid in self.children() {
-            kid.restyle_subtree(select_ctx); 
+        changes.note_restyle(node, &complete_results, values.get());
+        node.set_css_select_results(complete_results);
+        node.share_computed_values(values);
+    };
+    changes.restyle_pseudo_elements(node, ancestors);
+    // Elements that share this style are styled alike, so only those that are matched start
+    // fetching the resources it refers to.
+    changes.prefetcher.prefetch_for_element(node);
+}
+
+/// Returns the strings that an element adds to the filter of the ancestors of its descendants:
+/// its lowercase name, its ID and its classes.
+fn ancestor_filter_keys(node: AbstractNode<LayoutView>) -> ~[~str] {
+    do node.with_imm_element |element| {
+        let mut keys = ~[element.tag_name.to_ascii_lower()];
+        for id in element.get_attr("id").iter() {
+            keys.push(id.to_owned());
         }
+        for classes in element.get_attr("class").iter() {
+            for class in classes.split_iter(' ').filter(|class| !class.is_empty()) {
+                keys.push(class.to_owned());
+            }
+        }
+        keys
     }
 }
 
+/// Resolves the inherited values of an element's style from its parent's. An element whose
+/// parent hasn't been styled, which shouldn't happen, inherits nothing, as the root does.
 fn compose_results(node: AbstractNode<LayoutView>, results: SelectResults)
                    -> CompleteSelectResults {
-    match find_parent_element_node(node) {
+    let parent_results = find_parent_element_node(node).map_move(|parent| {
+        parent.try_css_select_results()
+    });
+    match parent_results {
         None => CompleteSelectResults::new_root(results),
-        Some(parent_node) => {
-            let parent_results = parent_node.get_css_select_results();
-            CompleteSelectResults::new_from_parent(parent_results, results)
+        Some(Ok(parent_results)) => CompleteSelectResults::new_from_parent(parent_results, results),
+        Some(Err(_)) => {
+            debug!("compose_results: the parent of an element has no style");
+            CompleteSelectResults::new_root(results)
         }
-    }    
+    }
+}
+
+/// Computes the values of the new style system for an element from those of its parent and of the
+/// root element, which are styled before it. `ancestors` holds the names, IDs and classes of its
+/// ancestors.
+fn compute_values(node: AbstractNode<LayoutView>, ancestors: &BloomFilter, stylist: &Stylist)
+                  -> Arc<ComputedValues> {
+    let parent_values = do find_parent_element_node(node).chain |parent| {
+        parent.read_layout_data(|data| data.computed_values.clone())
+    };
+    stylist.compute_values(node, ancestors, parent_values.as_ref(), root_font_size(node))
+}
+
+/// Returns the computed font size of the root element, which is styled before the other elements,
+/// or `None` if `node` is the root.
+fn root_font_size(node: AbstractNode<LayoutView>) -> Option<computed::Length> {
+    let mut root = node;
+    loop {
+        match find_parent_element_node(root) {
+            Some(ancestor) => root = ancestor,
+            None => break,
+        }
+    }
+    if root == node {
+        None
+    } else {
+        match root.try_computed_values() {
+            Ok(values) => Some(values.font_size),
+            Err(_) => None,
+        }
+    }
 }
 
 fn find_parent_element_node(node: AbstractNode<LayoutView>) -> Option<AbstractNode<LayoutView>> {
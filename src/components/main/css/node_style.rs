@@ -4,16 +4,21 @@
 
 // Style retrieval from DOM elements.
 
-use css::node_util::NodeUtil;
+use css::node_util::{LayoutDataError, NodeUtil, NotElementError};
 use layout::incremental::RestyleDamage;
 
 use newcss::complete::CompleteStyle;
 use script::dom::node::{AbstractNode, LayoutView};
+use script::style::properties::ComputedValues;
+use script::style::properties::longhands::display;
 
 /// Node mixin providing `style` method that returns a `NodeStyle`
 pub trait StyledNode {
     fn style(&self) -> CompleteStyle;
+    fn try_style(&self) -> Result<CompleteStyle, LayoutDataError>;
+    fn computed_values<'a>(&'a self) -> &'a ComputedValues;
     fn restyle_damage(&self) -> RestyleDamage;
+    fn displays_contents(&self) -> bool;
 }
 
 impl StyledNode for AbstractNode<LayoutView> {
@@ -23,7 +28,33 @@ impl StyledNode for AbstractNode<LayoutView> {
         results.computed_style()
     }
 
+    /// Returns the style of the element, or an error if it hasn't been styled yet or isn't an
+    /// element.
+    fn try_style(&self) -> Result<CompleteStyle, LayoutDataError> {
+        if !self.is_element() {
+            return Err(NotElementError)
+        }
+        self.try_css_select_results().map_move(|results| results.computed_style())
+    }
+
+    /// Returns the values that the new style system has computed for the element, for the
+    /// properties that libcss doesn't support.
+    fn computed_values<'a>(&'a self) -> &'a ComputedValues {
+        assert!(self.is_element()); // Only elements can have styles
+        self.get_computed_values()
+    }
+
     fn restyle_damage(&self) -> RestyleDamage {
         self.get_restyle_damage()
     }
+
+    /// Returns true if the node is an element whose `display` is `contents`, so that it generates
+    /// no boxes of its own, and its children generate boxes as if they were children of its
+    /// parent. Nodes that haven't been styled don't.
+    fn displays_contents(&self) -> bool {
+        match self.try_computed_values() {
+            Ok(values) => self.is_element() && values.display == display::contents,
+            Err(_) => false,
+        }
+    }
 }
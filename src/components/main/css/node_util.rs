@@ -6,14 +6,42 @@ use layout::incremental::RestyleDamage;
 
 use std::cast;
 use std::cell::Cell;
+use extra::arc::Arc;
 use newcss::complete::CompleteSelectResults;
 use script::dom::node::{AbstractNode, LayoutView};
+use script::style::properties::ComputedValues;
+use script::style::selectors::{After, Before, PseudoElement};
+
+/// Why the layout data of a node doesn't have what was asked of it.
+#[deriving(Eq)]
+pub enum LayoutDataError {
+    /// Selector matching hasn't styled the node yet.
+    NotStyledError,
+    /// The node isn't an element, and only elements are styled.
+    NotElementError,
+}
 
 pub trait NodeUtil<'self> {
     fn get_css_select_results(self) -> &'self CompleteSelectResults;
+    fn try_css_select_results(self) -> Result<&'self CompleteSelectResults, LayoutDataError>;
     fn set_css_select_results(self, decl: CompleteSelectResults);
+    fn get_shared_css_select_results(self) -> Arc<CompleteSelectResults>;
+    fn try_shared_css_select_results(self) -> Result<Arc<CompleteSelectResults>, LayoutDataError>;
+    fn share_css_select_results(self, style: Arc<CompleteSelectResults>);
     fn have_css_select_results(self) -> bool;
 
+    fn get_computed_values(self) -> &'self ComputedValues;
+    fn try_computed_values(self) -> Result<&'self ComputedValues, LayoutDataError>;
+    fn get_shared_computed_values(self) -> Arc<ComputedValues>;
+    fn share_computed_values(self, values: Arc<ComputedValues>);
+    fn get_pseudo_element_values(self, pseudo_element: PseudoElement)
+                                 -> Option<&'self ComputedValues>;
+    fn get_shared_pseudo_element_values(self, pseudo_element: PseudoElement)
+                                        -> Option<Arc<ComputedValues>>;
+    fn share_pseudo_element_values(self,
+                                   pseudo_element: PseudoElement,
+                                   values: Option<Arc<ComputedValues>>);
+
     fn get_restyle_damage(self) -> RestyleDamage;
     fn set_restyle_damage(self, damage: RestyleDamage);
 }
@@ -27,10 +55,19 @@ impl<'self> NodeUtil<'self> for AbstractNode<LayoutView> {
      * stored in a box that can be overwritten
      */
     fn get_css_select_results(self) -> &'self CompleteSelectResults {
+        match self.try_css_select_results() {
+            Ok(results) => results,
+            Err(_) => fail!(~"style() called on node without a style!"),
+        }
+    }
+
+    /// Returns the style results for the node, or an error if CSS selector matching has not yet
+    /// been performed.
+    fn try_css_select_results(self) -> Result<&'self CompleteSelectResults, LayoutDataError> {
         do self.read_layout_data |layout_data| {
             match layout_data.style {
-                None => fail!(~"style() called on node without a style!"),
-                Some(ref style) => unsafe { cast::transmute_region(style) }
+                None => Err(NotStyledError),
+                Some(ref style) => Ok(unsafe { cast::transmute_region(style.get()) })
             }
         }
     }
@@ -42,10 +79,118 @@ impl<'self> NodeUtil<'self> for AbstractNode<LayoutView> {
 
     /// Update the computed style of an HTML element with a style specified by CSS.
     fn set_css_select_results(self, decl: CompleteSelectResults) {
-        let cell = Cell::new(decl);
+        self.share_css_select_results(Arc::new(decl))
+    }
+
+    /// Returns the style results of the node, to share with another node. Fails if CSS selector
+    /// matching has not yet been performed.
+    fn get_shared_css_select_results(self) -> Arc<CompleteSelectResults> {
+        match self.try_shared_css_select_results() {
+            Ok(results) => results,
+            Err(_) => fail!(~"style() called on node without a style!"),
+        }
+    }
+
+    /// Returns the style results of the node to share, or an error if CSS selector matching has
+    /// not yet been performed.
+    fn try_shared_css_select_results(self) -> Result<Arc<CompleteSelectResults>, LayoutDataError> {
+        do self.read_layout_data |layout_data| {
+            match layout_data.style {
+                None => Err(NotStyledError),
+                Some(ref style) => Ok(style.clone()),
+            }
+        }
+    }
+
+    /// Gives the node the style results of another node that is styled alike.
+    fn share_css_select_results(self, style: Arc<CompleteSelectResults>) {
+        let cell = Cell::new(style);
         self.write_layout_data(|data| data.style = Some(cell.take()));
     }
 
+    /// Returns the values that the new style system has computed for the node. Fails if it
+    /// hasn't been styled yet.
+    fn get_computed_values(self) -> &'self ComputedValues {
+        match self.try_computed_values() {
+            Ok(values) => values,
+            Err(_) => fail!(~"computed values asked of a node without a style!"),
+        }
+    }
+
+    /// Returns the values that the new style system has computed for the node, or an error if it
+    /// hasn't been styled yet.
+    fn try_computed_values(self) -> Result<&'self ComputedValues, LayoutDataError> {
+        do self.read_layout_data |layout_data| {
+            match layout_data.computed_values {
+                None => Err(NotStyledError),
+                Some(ref values) => Ok(unsafe { cast::transmute_region(values.get()) })
+            }
+        }
+    }
+
+    /// Returns the values that the new style system has computed for the node, to share with
+    /// another node. Fails if it hasn't been styled yet.
+    fn get_shared_computed_values(self) -> Arc<ComputedValues> {
+        do self.read_layout_data |layout_data| {
+            match layout_data.computed_values {
+                None => fail!(~"computed values asked of a node without a style!"),
+                Some(ref values) => values.clone(),
+            }
+        }
+    }
+
+    /// Sets the values that the new style system has computed for the node, which other nodes
+    /// that are styled alike may share.
+    fn share_computed_values(self, values: Arc<ComputedValues>) {
+        let cell = Cell::new(values);
+        self.write_layout_data(|data| data.computed_values = Some(cell.take()));
+    }
+
+    /// Returns the values of the `::before` or `::after` pseudo-element of the node, or `None` if
+    /// it generates no content. Other pseudo-elements have no values of their own yet.
+    fn get_pseudo_element_values(self, pseudo_element: PseudoElement)
+                                 -> Option<&'self ComputedValues> {
+        do self.read_layout_data |layout_data| {
+            let values = match pseudo_element {
+                Before => &layout_data.before_values,
+                After => &layout_data.after_values,
+                _ => return None,
+            };
+            match *values {
+                None => None,
+                Some(ref values) => Some(unsafe { cast::transmute_region(values.get()) }),
+            }
+        }
+    }
+
+    /// Returns the values of the `::before` or `::after` pseudo-element of the node, to share
+    /// with another node.
+    fn get_shared_pseudo_element_values(self, pseudo_element: PseudoElement)
+                                        -> Option<Arc<ComputedValues>> {
+        do self.read_layout_data |layout_data| {
+            match pseudo_element {
+                Before => layout_data.before_values.clone(),
+                After => layout_data.after_values.clone(),
+                _ => None,
+            }
+        }
+    }
+
+    /// Sets the values of the `::before` or `::after` pseudo-element of the node, which those of
+    /// other nodes that are styled alike may share.
+    fn share_pseudo_element_values(self,
+                                   pseudo_element: PseudoElement,
+                                   values: Option<Arc<ComputedValues>>) {
+        let cell = Cell::new(values);
+        do self.write_layout_data |data| {
+            match pseudo_element {
+                Before => data.before_values = cell.take(),
+                After => data.after_values = cell.take(),
+                _ => {}
+            }
+        }
+    }
+
     /// Get the description of how to account for recent style changes.
     /// This is a simple bitfield and fine to copy by value.
     fn get_restyle_damage(self) -> RestyleDamage {
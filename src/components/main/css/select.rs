@@ -5,26 +5,97 @@
 use extra::url::Url;
 use std::cell::Cell;
 use std::FromStr;
+use std::io;
 use newcss::stylesheet::Stylesheet;
 use newcss::select::SelectCtx;
-use newcss::types::OriginUA;
+use newcss::types::{OriginUA, OriginUser};
 use newcss::util::DataStream;
+use servo_util::url::make_url;
 
-pub fn new_css_select_ctx() -> SelectCtx {
-    let mut ctx = SelectCtx::new();
-    ctx.append_sheet(html4_default_style(), OriginUA);
-    ctx.append_sheet(servo_default_style(), OriginUA);
-    return ctx;
+/// The default style of the browser.
+static USER_AGENT_STYLE: &'static str = include_str!("user-agent.css");
+
+/// A style sheet that applies to every page. Select contexts are made again when the author
+/// sheets are replaced, so the text is kept to parse each time.
+#[deriving(Clone)]
+pub struct StylesheetText {
+    url: Url,
+    text: ~str,
 }
 
-fn html4_default_style() -> Stylesheet {
-    Stylesheet::new(default_url("html4_style"),
-                    style_stream(html4_default_style_str()))
+impl StylesheetText {
+    pub fn new(url: Url, text: ~str) -> StylesheetText {
+        StylesheetText {
+            url: url,
+            text: text,
+        }
+    }
+
+    /// Loads the style sheets in the given files. Files that can't be read are skipped.
+    pub fn load_all(paths: &[~str]) -> ~[StylesheetText] {
+        do paths.iter().filter_map |path| {
+            match io::read_whole_file_str(&Path(*path)) {
+                Ok(text) => Some(StylesheetText::new(make_url(path.clone(), None), text)),
+                Err(error) => {
+                    error!("failed to load the style sheet %s: %s", *path, error);
+                    None
+                }
+            }
+        }.collect()
+    }
+
+    pub fn parse(&self) -> Stylesheet {
+        Stylesheet::new(self.url.clone(), style_stream(self.text))
+    }
+}
+
+/// The style sheets that every select context starts with, before the author sheets of a page.
+#[deriving(Clone)]
+pub struct DefaultStylesheets {
+    /// The sheets at the user agent origin: the default style of the browser, then those that the
+    /// embedder adds, such as to style widgets of its own.
+    user_agent: ~[StylesheetText],
+    /// The sheets at the user origin, which can override the default style and, with
+    /// `!important`, the style of the page, such as to make text larger or colors stronger.
+    user: ~[StylesheetText],
+}
+
+impl DefaultStylesheets {
+    /// Loads the default style of the browser, which is built into it, followed by the style
+    /// sheets in the given files at the user agent origin and at the user origin.
+    pub fn load(user_agent_paths: &[~str], user_paths: &[~str]) -> DefaultStylesheets {
+        let default_style = StylesheetText::new(default_url("user_agent_style"),
+                                                USER_AGENT_STYLE.to_owned());
+        DefaultStylesheets {
+            user_agent: ~[default_style] + StylesheetText::load_all(user_agent_paths),
+            user: StylesheetText::load_all(user_paths),
+        }
+    }
+
+    /// Adds a style sheet at the user agent origin, after the others, so that it can override the
+    /// default style but not the style of the user or of the page.
+    pub fn add_user_agent_sheet(&mut self, sheet: StylesheetText) {
+        self.user_agent.push(sheet)
+    }
+
+    /// Returns the text of the sheets, those at the user agent origin first, for script to find
+    /// what their selectors test.
+    pub fn texts(&self) -> ~[~str] {
+        self.user_agent.iter().chain(self.user.iter()).map(|sheet| sheet.text.clone()).collect()
+    }
 }
 
-fn servo_default_style() -> Stylesheet {
-    Stylesheet::new(default_url("servo_style"),
-                    style_stream(servo_default_style_str()))
+/// Makes a select context with the given default style sheets, to which the author sheets of a
+/// page are added.
+pub fn new_css_select_ctx(sheets: &DefaultStylesheets) -> SelectCtx {
+    let mut ctx = SelectCtx::new();
+    for sheet in sheets.user_agent.iter() {
+        ctx.append_sheet(sheet.parse(), OriginUA);
+    }
+    for sheet in sheets.user.iter() {
+        ctx.append_sheet(sheet.parse(), OriginUser);
+    }
+    return ctx;
 }
 
 fn default_url(name: &str) -> Url {
@@ -40,96 +111,3 @@ fn style_stream(style: &str) -> DataStream {
     };
     return d;
 }
-
-fn html4_default_style_str() -> ~str {
-~"
-html, address,
-blockquote,
-body, dd, div,
-dl, dt, fieldset, form,
-frame, frameset,
-h1, h2, h3, h4,
-h5, h6, noframes,
-ol, p, ul, center,
-    dir, hr, menu, pre   { display: block; unicode-bidi: embed }
-    li              { display: list-item }
-    head            { display: none }
-    table           { display: table }
-    tr              { display: table-row }
-    thead           { display: table-header-group }
-    tbody           { display: table-row-group }
-    tfoot           { display: table-footer-group }
-    col             { display: table-column }
-    colgroup        { display: table-column-group }
-    td, th          { display: table-cell }
-    caption         { display: table-caption }
-    th              { font-weight: bolder; text-align: center }
-    caption         { text-align: center }
-    body            { margin: 8px }
-    h1              { font-size: 2em; margin: .67em 0 }
-    h2              { font-size: 1.5em; margin: .75em 0 }
-    h3              { font-size: 1.17em; margin: .83em 0 }
-h4, p,
-blockquote, ul,
-fieldset, form,
-ol, dl, dir,
-    menu            { margin: 1.12em 0 }
-    h5              { font-size: .83em; margin: 1.5em 0 }
-    h6              { font-size: .75em; margin: 1.67em 0 }
-h1, h2, h3, h4,
-h5, h6, b,
-    strong          { font-weight: bolder }
-    blockquote      { margin-left: 40px; margin-right: 40px }
-i, cite, em,
-    var, address    { font-style: italic }
-pre, tt, code,
-    kbd, samp       { font-family: monospace }
-    pre             { white-space: pre }
-button, textarea,
-    input, select   { display: inline-block }
-    big             { font-size: 1.17em }
-    small, sub, sup { font-size: .83em }
-    sub             { vertical-align: sub }
-    sup             { vertical-align: super }
-    table           { border-spacing: 2px; }
-thead, tbody,
-    tfoot           { vertical-align: middle }
-    td, th, tr      { vertical-align: inherit }
-    s, strike, del  { text-decoration: line-through }
-    hr              { border: 1px inset }
-ol, ul, dir,
-    menu, dd        { margin-left: 40px }
-    ol              { list-style-type: decimal }
-ol ul, ul ol,
-    ul ul, ol ol    { margin-top: 0; margin-bottom: 0 }
-    u, ins          { text-decoration: underline }
-    br:before       { content: \"\\A\"; white-space: pre-line }
-center          { text-align: center }
-:link, :visited { text-decoration: underline }
-:focus          { outline: thin dotted invert }
-
-/* Begin bidirectionality settings (do not change) */
-BDO[DIR=\"ltr\"]  { direction: ltr; unicode-bidi: bidi-override }
-BDO[DIR=\"rtl\"]  { direction: rtl; unicode-bidi: bidi-override }
-
-*[DIR=\"ltr\"]    { direction: ltr; unicode-bidi: embed }
-*[DIR=\"rtl\"]    { direction: rtl; unicode-bidi: embed }
-
-@media print {
-h1            { page-break-before: always }
-  h1, h2, h3,
-h4, h5, h6    { page-break-after: avoid }
-ul, ol, dl    { page-break-before: avoid }
-}
-
-/* Servo additions */
-:link           { color: blue }
-script          { display: none }
-style           { display: none }
-"
-}
-
-fn servo_default_style_str() -> ~str {
-    // libcss want's this to default to 2px..
-    ~"* { border-width: 0px; }"
-}
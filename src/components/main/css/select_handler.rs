@@ -6,12 +6,16 @@
 /// Implementation of the callbacks that the CSS selector engine uses to query the DOM.
 ///
 
+use std::ascii::StrAsciiExt;
 use std::str::eq_slice;
 use newcss::select::SelectHandler;
 use script::dom::node::{AbstractNode, LayoutView};
+use servo_util::bloom::BloomFilter;
 
-pub struct NodeSelectHandler {
+pub struct NodeSelectHandler<'self> {
     node: AbstractNode<LayoutView>,
+    /// The lowercase names, IDs and classes of the ancestors of the node being styled.
+    ancestors: &'self BloomFilter,
 }
 
 fn with_node_name<R>(node: AbstractNode<LayoutView>, f: &fn(&str) -> R) -> R {
@@ -23,7 +27,7 @@ fn with_node_name<R>(node: AbstractNode<LayoutView>, f: &fn(&str) -> R) -> R {
     }
 }
 
-impl SelectHandler<AbstractNode<LayoutView>> for NodeSelectHandler {
+impl<'self> SelectHandler<AbstractNode<LayoutView>> for NodeSelectHandler<'self> {
     fn with_node_name<R>(&self, node: &AbstractNode<LayoutView>, f: &fn(&str) -> R) -> R {
         with_node_name(*node, f)
     }
@@ -45,9 +49,14 @@ impl SelectHandler<AbstractNode<LayoutView>> for NodeSelectHandler {
         node.parent_node()
     }
 
-    // TODO: Use a Bloom filter.
     fn named_ancestor_node(&self, node: &AbstractNode<LayoutView>, name: &str)
                            -> Option<AbstractNode<LayoutView>> {
+        // Selectors only ask for the ancestors of the node being styled, or of its ancestors or
+        // siblings, which are among the ancestors in the filter.
+        if !self.ancestors.might_contain(name.to_ascii_lower()) {
+            return None
+        }
+
         let mut node = *node;
         loop {
             let parent = node.parent_node();
@@ -0,0 +1,88 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A cache of recently styled elements whose style results, and values of the new style system,
+//! can be shared with the elements styled after them, so that runs of alike siblings, such as the
+//! items of a list, aren't matched against every selector and cascaded again.
+
+use css::stylist::Stylist;
+
+use script::dom::node::{AbstractNode, LayoutView};
+use servo_util::tree::TreeNodeRef;
+
+/// How many recently styled elements are kept.
+static CACHE_SIZE: uint = 8;
+
+pub struct StyleSharingCandidateCache {
+    /// The candidates, the most recently styled first.
+    candidates: ~[AbstractNode<LayoutView>],
+    /// How many elements have shared the style of a candidate.
+    shared: uint,
+}
+
+impl StyleSharingCandidateCache {
+    pub fn new() -> StyleSharingCandidateCache {
+        StyleSharingCandidateCache {
+            candidates: ~[],
+            shared: 0,
+        }
+    }
+
+    /// Returns a candidate whose style the element can share, if there is one.
+    pub fn find(&mut self, element: AbstractNode<LayoutView>, stylist: &Stylist)
+                -> Option<AbstractNode<LayoutView>> {
+        if !can_share_style(element) {
+            return None
+        }
+        let index = match self.candidates.iter().position(|&candidate| {
+            styled_alike(candidate, element) && stylist.can_share_values(candidate, element)
+        }) {
+            Some(index) => index,
+            None => return None,
+        };
+
+        // Keep the candidate that was found at the front.
+        let candidate = self.candidates.remove(index);
+        self.candidates.unshift(candidate);
+        self.shared += 1;
+        Some(candidate)
+    }
+
+    /// Adds an element that has just been styled, if others can share its style.
+    pub fn insert(&mut self, element: AbstractNode<LayoutView>) {
+        if !can_share_style(element) {
+            return
+        }
+        if self.candidates.len() == CACHE_SIZE {
+            self.candidates.pop();
+        }
+        self.candidates.unshift(element);
+    }
+}
+
+/// Returns true if the style of an element only depends on what `styled_alike` compares, along
+/// with what the stylist compares of the attributes and states that its selectors test. The
+/// selectors that libcss supports match elements by their names, IDs, classes, whether they are
+/// links and whether they are the root, and by the same of their ancestors. Elements with IDs are
+/// unlikely to be styled like any other element, and a style attribute is their own.
+fn can_share_style(element: AbstractNode<LayoutView>) -> bool {
+    element.is_element() && element.parent_node().is_some() &&
+        do element.with_imm_element |element| {
+            element.style_attribute.is_none() && element.get_attr("id").is_none()
+        }
+}
+
+/// Returns true if two elements that can share styles are matched by the same selectors, and
+/// inherit the same style, so that they have the same style.
+fn styled_alike(candidate: AbstractNode<LayoutView>, element: AbstractNode<LayoutView>) -> bool {
+    if candidate.parent_node() != element.parent_node() {
+        return false
+    }
+    do candidate.with_imm_element |candidate| {
+        do element.with_imm_element |element| {
+            candidate.tag_name == element.tag_name &&
+                candidate.get_attr("class") == element.get_attr("class")
+        }
+    }
+}
@@ -0,0 +1,280 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The style sheets of a page as the new style system parses them, from which the values of the
+//! properties that libcss doesn't support are computed. Elements are still matched and styled by
+//! libcss as well; the values computed here sit next to its results in the layout data.
+
+use css::select::{DefaultStylesheets, StylesheetText};
+use layout::box::font_style_of;
+
+use geom::size::Size2D;
+use gfx::font_context::FontContext;
+use gfx::geometry::Au;
+use script::dom::element::ElementState;
+use script::dom::node::{AbstractNode, LayoutView};
+use script::style::cascade::{AuthorOrigin, StylesheetOrigin, UserAgentOrigin, UserOrigin};
+use script::style::cascade::cascade_into_rule_tree;
+use script::style::counter_style::CounterStyleMap;
+use script::style::media_queries::{Device, Screen};
+use script::style::properties::common_types::computed;
+use script::style::properties::{CascadeEnvironment, ComputedValues, FontValues, cascade};
+use script::style::properties::longhands::content;
+use script::style::rule_tree::{RuleNodeId, RuleTree};
+use script::style::selector_matching::{SharingRequirements, state_dependencies};
+use script::style::selectors::PseudoElement;
+use script::style::stylesheets::{Stylesheet, parse_stylesheet};
+use extra::arc::Arc;
+use extra::url::Url;
+use servo_util::bloom::BloomFilter;
+use servo_util::url::make_url;
+
+pub struct Stylist {
+    /// The sheets, in the order in which they cascade when their rules are equally specific: the
+    /// default sheets first, then the author sheets of the page in the order they were added.
+    priv sheets: ~[(Stylesheet, StylesheetOrigin)],
+    /// How many of the sheets are default sheets, which stay when the author sheets are replaced.
+    priv default_sheet_count: uint,
+    priv device: Device,
+    /// The URL of the document, which the URLs in values are resolved against.
+    priv doc_url: Option<Url>,
+    /// The size that viewport units are relative to.
+    priv viewport_size: Size2D<Au>,
+    /// The fonts that `ch` is measured in.
+    priv font_ctx: @mut FontContext,
+    /// The predefined counter styles and those that the `@counter-style` rules of the sheets
+    /// define, in which list markers are written.
+    priv counter_styles: @CounterStyleMap,
+    /// What alike elements must also have in common for the selectors of the sheets to match them
+    /// alike, so that they can share the values computed for them.
+    priv sharing_requirements: SharingRequirements,
+    /// The states of elements that the selectors of the sheets test.
+    priv state_dependencies: ElementState,
+    /// The declarations that apply to the elements styled since the sheets or the environment last
+    /// changed, and the values last computed from them.
+    priv rule_tree: @mut RuleTree,
+}
+
+impl Stylist {
+    /// Parses the default style sheets of the browser and of the user. Fonts are measured in
+    /// `font_ctx`.
+    pub fn new(default_stylesheets: &DefaultStylesheets, font_ctx: @mut FontContext) -> Stylist {
+        let mut sheets = ~[];
+        for sheet in default_stylesheets.user_agent.iter() {
+            sheets.push((parse_stylesheet(sheet.text), UserAgentOrigin));
+        }
+        for sheet in default_stylesheets.user.iter() {
+            sheets.push((parse_stylesheet(sheet.text), UserOrigin));
+        }
+        let mut stylist = Stylist {
+            default_sheet_count: sheets.len(),
+            sheets: sheets,
+            device: Device { media_type: Screen },
+            doc_url: None,
+            viewport_size: Size2D(Au(0), Au(0)),
+            font_ctx: font_ctx,
+            counter_styles: @CounterStyleMap::new(),
+            sharing_requirements: SharingRequirements::new(),
+            state_dependencies: ElementState::none(),
+            rule_tree: @mut RuleTree::new(),
+        };
+        stylist.sheets_changed();
+        stylist
+    }
+
+    /// Parses a default style sheet at the user agent origin, which is added after the others of
+    /// that origin and before those of the user and of the page.
+    pub fn add_user_agent_sheet(&mut self, sheet: &StylesheetText) {
+        let sheet = parse_stylesheet(sheet.text);
+        let index = self.sheets.iter().position(|&(_, origin)| origin != UserAgentOrigin);
+        let index = index.unwrap_or(self.sheets.len());
+        self.sheets.insert(index, (sheet, UserAgentOrigin));
+        self.default_sheet_count += 1;
+        self.sheets_changed()
+    }
+
+    /// Adds an author style sheet of the page, after the others.
+    pub fn add_author_sheet(&mut self, sheet: Stylesheet) {
+        self.sheets.push((sheet, AuthorOrigin));
+        self.sheets_changed()
+    }
+
+    /// Removes the author style sheets of the page, so that they can be replaced.
+    pub fn remove_author_sheets(&mut self) {
+        self.sheets.truncate(self.default_sheet_count);
+        self.sheets_changed()
+    }
+
+    /// Returns the counter styles that the sheets define, along with the predefined ones.
+    pub fn counter_styles(&self) -> @CounterStyleMap {
+        self.counter_styles
+    }
+
+    /// Sets the URL of the document and the size that viewport units are relative to. Returns
+    /// true if the size has changed, so that every element has to be restyled.
+    pub fn set_environment(&mut self, doc_url: Url, viewport_size: Size2D<Au>) -> bool {
+        let changed = self.viewport_size != viewport_size;
+        // The values computed before were resolved against the old URL or size.
+        if changed || self.doc_url.as_ref() != Some(&doc_url) {
+            self.rule_tree = @mut RuleTree::new()
+        }
+        self.doc_url = Some(doc_url);
+        self.viewport_size = viewport_size;
+        changed
+    }
+
+    /// Returns true if two elements that have the same parent, name and classes, and neither an
+    /// ID nor a style attribute, have the same values, so that they can share them.
+    pub fn can_share_values(&self, a: AbstractNode<LayoutView>, b: AbstractNode<LayoutView>)
+                            -> bool {
+        self.sharing_requirements.met_by(a, b)
+    }
+
+    /// Returns the states of elements that the selectors of the sheets test, so that a change of
+    /// one of them needs the element to be restyled.
+    pub fn state_dependencies(&self) -> ElementState {
+        self.state_dependencies
+    }
+
+    /// Computes the values of an element from the rules that match it and its `style` attribute,
+    /// and from the values of its parent, or `None` for the root element. `ancestors` holds the
+    /// lowercase names, IDs and classes of the ancestors of the element. `root_font_size` is the
+    /// computed font size of the root element, or `None` while the root itself is styled.
+    ///
+    /// The values are those last computed for an element that the same rules matched, if it had
+    /// the same parent values.
+    ///
+    /// FIXME: URLs are resolved against the document rather than the style sheet they appear in.
+    pub fn compute_values(&self,
+                          element: AbstractNode<LayoutView>,
+                          ancestors: &BloomFilter,
+                          parent: Option<&Arc<ComputedValues>>,
+                          root_font_size: Option<computed::Length>)
+                          -> Arc<ComputedValues> {
+        let rule_node = do element.with_imm_element |elem| {
+            cascade_into_rule_tree(&mut *self.rule_tree,
+                                   self.sheets,
+                                   &self.device,
+                                   element,
+                                   ancestors,
+                                   None,
+                                   elem.style_attribute_declarations.as_ref())
+        };
+        do self.values_of_rule_node(rule_node, parent, root_font_size) |values| {
+            values.mask_image = do values.mask_image.clone().map_move |url| {
+                make_url(url, self.doc_url.clone()).to_str()
+            };
+        }
+    }
+
+    /// Computes the values of a pseudo-element of an element from the rules whose selectors end
+    /// in it, and from `element_values`, the values of the element, which it inherits from.
+    /// Returns `None` if the pseudo-element generates no content, and so no boxes.
+    pub fn compute_pseudo_element_values(&self,
+                                         element: AbstractNode<LayoutView>,
+                                         ancestors: &BloomFilter,
+                                         pseudo_element: PseudoElement,
+                                         element_values: &Arc<ComputedValues>,
+                                         root_font_size: computed::Length)
+                                         -> Option<Arc<ComputedValues>> {
+        let rule_node = cascade_into_rule_tree(&mut *self.rule_tree,
+                                               self.sheets,
+                                               &self.device,
+                                               element,
+                                               ancestors,
+                                               Some(pseudo_element),
+                                               None);
+        // Without rules of its own, the content of a pseudo-element is `normal`.
+        if rule_node == self.rule_tree.root() {
+            return None
+        }
+        let values = do self.values_of_rule_node(rule_node,
+                                                 Some(element_values),
+                                                 Some(root_font_size)) |values| {
+            let resolved = match values.content {
+                content::Content(ref items) => do items.iter().map |item| {
+                    match *item {
+                        content::UrlContent(ref url) => {
+                            let url = make_url(url.clone(), self.doc_url.clone());
+                            content::UrlContent(url.to_str())
+                        }
+                        ref item => item.clone(),
+                    }
+                }.collect(),
+                content::normal | content::none => return,
+            };
+            values.content = content::Content(resolved);
+        };
+        // `normal` computes to `none` for `::before` and `::after`.
+        match values.get().content {
+            content::Content(_) => Some(values),
+            content::normal | content::none => None,
+        }
+    }
+
+    /// Returns the values computed from the declarations of `rule_node` and the values of the
+    /// parent, which `resolve` finishes computing, or the values last computed from them.
+    fn values_of_rule_node(&self,
+                           rule_node: RuleNodeId,
+                           parent: Option<&Arc<ComputedValues>>,
+                           root_font_size: Option<computed::Length>,
+                           resolve: &fn(&mut ComputedValues))
+                           -> Arc<ComputedValues> {
+        match self.rule_tree.cached_values(rule_node, parent) {
+            Some(values) => return values,
+            None => {}
+        }
+        let measure_zero_advance: &fn(&FontValues) -> Option<computed::Length> = |font| {
+            self.zero_advance(font)
+        };
+        let environment = CascadeEnvironment {
+            root_font_size: root_font_size,
+            viewport_width: computed::Length(*self.viewport_size.width as i64),
+            viewport_height: computed::Length(*self.viewport_size.height as i64),
+            measure_zero_advance: measure_zero_advance,
+        };
+        let mut values = cascade(self.rule_tree.declarations(rule_node),
+                                 parent.map_move(|parent| parent.get()),
+                                 &environment);
+        resolve(&mut values);
+        let values = Arc::new(values);
+        self.rule_tree.cache_values(rule_node, parent, values.clone());
+        values
+    }
+
+    /// Accounts for a change of the sheets: defines their counter styles again, rules of later
+    /// sheets replacing the styles of the same name that earlier ones define, and finds out what
+    /// their selectors need alike elements to have in common to share values and which states
+    /// they test. The rule tree is made again, since the rules it refers to are gone.
+    fn sheets_changed(&mut self) {
+        let mut counter_styles = CounterStyleMap::new();
+        let mut sharing_requirements = SharingRequirements::new();
+        let mut states = ElementState::none();
+        for &(ref sheet, _) in self.sheets.iter() {
+            sheet.add_counter_styles(&self.device, &mut counter_styles);
+            for rule in sheet.iter_style_rules(&self.device) {
+                for selector in rule.selectors.iter() {
+                    sharing_requirements.add_selector(selector);
+                    states = states.union(state_dependencies(selector))
+                }
+            }
+        }
+        self.counter_styles = @counter_styles;
+        self.sharing_requirements = sharing_requirements;
+        self.state_dependencies = states;
+        self.rule_tree = @mut RuleTree::new()
+    }
+
+    /// Returns the advance of "0" in the first font that the given font values select, which
+    /// `ch` is relative to, or `None` if there is no such font or it has no "0".
+    fn zero_advance(&self, font: &FontValues) -> Option<computed::Length> {
+        let font_group = self.font_ctx.get_resolved_font_for_style(&font_style_of(font));
+        if font_group.fonts.is_empty() {
+            return None
+        }
+        do font_group.fonts[0].zero_advance().map_move |advance| {
+            computed::Length(*advance as i64)
+        }
+    }
+}
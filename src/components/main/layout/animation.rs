@@ -0,0 +1,319 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS transitions. When restyling changes the value of an animatable property of an element
+//! whose `transition` covers it, a transition from the old value to the new one starts, and until
+//! it ends, the value it has reached overrides the computed value in the layout data of the
+//! element. Layout ticks the running transitions and repaints, without laying out again.
+//!
+//! FIXME: Only colors are animatable, since they are the only values that newcss computes which
+//! can be painted with other values than its own without laying out again.
+
+use css::node_style::StyledNode;
+use layout::display_list_builder::ToGfxColor;
+
+use gfx::color::Color;
+use newcss::complete::CompleteStyle;
+use script::dom::node::{AbstractNode, LayoutView};
+use script::style::properties::ComputedValues;
+use script::style::properties::longhands::transition_property::{TransitionAll, TransitionNone};
+use script::style::properties::longhands::transition_property::TransitionProperty;
+
+/// The properties that transitions can animate.
+#[deriving(Clone, Eq)]
+pub enum AnimatableProperty {
+    ColorProperty,
+    BackgroundColorProperty,
+    BorderTopColorProperty,
+    BorderRightColorProperty,
+    BorderBottomColorProperty,
+    BorderLeftColorProperty,
+}
+
+static ANIMATABLE_PROPERTIES: [AnimatableProperty, ..6] = [
+    ColorProperty,
+    BackgroundColorProperty,
+    BorderTopColorProperty,
+    BorderRightColorProperty,
+    BorderBottomColorProperty,
+    BorderLeftColorProperty,
+];
+
+impl AnimatableProperty {
+    /// Returns the computed value of the property in a style.
+    fn computed_value(&self, style: &CompleteStyle) -> Color {
+        match *self {
+            ColorProperty => style.color().to_gfx_color(),
+            BackgroundColorProperty => style.background_color().to_gfx_color(),
+            BorderTopColorProperty => style.border_top_color().to_gfx_color(),
+            BorderRightColorProperty => style.border_right_color().to_gfx_color(),
+            BorderBottomColorProperty => style.border_bottom_color().to_gfx_color(),
+            BorderLeftColorProperty => style.border_left_color().to_gfx_color(),
+        }
+    }
+
+    /// Returns whether `transition-property` names the property with the given lowercase name,
+    /// either itself or a shorthand that sets it.
+    fn is_named(&self, name: &str) -> bool {
+        let side = match *self {
+            ColorProperty => return name == "color",
+            BackgroundColorProperty => return name == "background-color" || name == "background",
+            BorderTopColorProperty => "top",
+            BorderRightColorProperty => "right",
+            BorderBottomColorProperty => "bottom",
+            BorderLeftColorProperty => "left",
+        };
+        name == "border" || name == "border-color" ||
+            name == fmt!("border-%s", side).as_slice() ||
+            name == fmt!("border-%s-color", side).as_slice()
+    }
+}
+
+/// How a transition progresses over its duration: a cubic Bézier curve from (0, 0) to (1, 1)
+/// through the two control points, as `cubic-bezier()` gives it.
+#[deriving(Clone)]
+pub struct TimingFunction {
+    x1: float,
+    y1: float,
+    x2: float,
+    y2: float,
+}
+
+impl TimingFunction {
+    /// The initial value of `transition-timing-function`, `ease`.
+    pub fn ease() -> TimingFunction {
+        TimingFunction { x1: 0.25, y1: 0.1, x2: 0.25, y2: 1.0 }
+    }
+
+    pub fn linear() -> TimingFunction {
+        TimingFunction { x1: 0.0, y1: 0.0, x2: 1.0, y2: 1.0 }
+    }
+
+    /// Returns how far the transition has gone when the given fraction of its duration has
+    /// passed.
+    pub fn progress(&self, time: float) -> float {
+        if time <= 0.0 {
+            return 0.0
+        }
+        if time >= 1.0 {
+            return 1.0
+        }
+        // The curve's x grows with its parameter, so the parameter for the time can be found by
+        // bisection.
+        let (mut low, mut high) = (0.0, 1.0);
+        let mut t = time;
+        for _ in range(0, 32) {
+            t = (low + high) / 2.0;
+            if bezier(self.x1, self.x2, t) < time {
+                low = t
+            } else {
+                high = t
+            }
+        }
+        bezier(self.y1, self.y2, t)
+    }
+}
+
+/// Returns a coordinate of a point on a cubic Bézier curve from 0 to 1 with the given coordinates
+/// of its control points.
+fn bezier(p1: float, p2: float, t: float) -> float {
+    let u = 1.0 - t;
+    3.0 * u * u * t * p1 + 3.0 * u * t * t * p2 + t * t * t
+}
+
+/// When and how a transition of a property runs, as the `transition` of an element gives it.
+#[deriving(Clone)]
+pub struct TransitionTiming {
+    /// In seconds.
+    duration: float,
+    /// In seconds.
+    delay: float,
+    timing_function: TimingFunction,
+}
+
+/// Returns how a change of a property of an element with the given computed values transitions,
+/// or `None` if the new value applies at once.
+fn transition_timing(values: &ComputedValues, property: AnimatableProperty)
+                     -> Option<TransitionTiming> {
+    let transitions = match values.transition_property {
+        TransitionNone => false,
+        TransitionAll => true,
+        TransitionProperty(ref name) => property.is_named(name.as_slice()),
+    };
+    // A transition that would end before it starts doesn't run.
+    if !transitions || values.transition_duration + values.transition_delay <= 0.0 {
+        return None
+    }
+    let timing_function = values.transition_timing_function;
+    Some(TransitionTiming {
+        duration: values.transition_duration as float,
+        delay: values.transition_delay as float,
+        timing_function: TimingFunction {
+            x1: timing_function.x1 as float,
+            y1: timing_function.y1 as float,
+            x2: timing_function.x2 as float,
+            y2: timing_function.y2 as float,
+        },
+    })
+}
+
+fn same_color(a: Color, b: Color) -> bool {
+    a.r == b.r && a.g == b.g && a.b == b.b && a.a == b.a
+}
+
+fn interpolate(from: Color, to: Color, progress: float) -> Color {
+    let progress = progress as f32;
+    Color {
+        r: from.r + (to.r - from.r) * progress,
+        g: from.g + (to.g - from.g) * progress,
+        b: from.b + (to.b - from.b) * progress,
+        a: from.a + (to.a - from.a) * progress,
+    }
+}
+
+/// A running transition of a property of an element.
+struct Transition {
+    node: AbstractNode<LayoutView>,
+    property: AnimatableProperty,
+    from: Color,
+    to: Color,
+    /// When the transition started, in seconds, before its delay.
+    start_time: float,
+    timing: TransitionTiming,
+}
+
+impl Transition {
+    fn value_at(&self, time: float) -> Color {
+        let elapsed = time - self.start_time - self.timing.delay;
+        let fraction = if self.timing.duration > 0.0 {
+            elapsed / self.timing.duration
+        } else if elapsed >= 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+        interpolate(self.from, self.to, self.timing.timing_function.progress(fraction))
+    }
+
+    fn is_finished(&self, time: float) -> bool {
+        time - self.start_time >= self.timing.delay + self.timing.duration
+    }
+}
+
+/// Node mixin giving the values of animatable properties as running transitions leave them.
+pub trait AnimatedNode {
+    /// Returns the value of a property of the element, as it is painted.
+    fn animated_color(&self, property: AnimatableProperty) -> Color;
+    fn set_animated_color(&self, property: AnimatableProperty, value: Option<Color>);
+}
+
+impl AnimatedNode for AbstractNode<LayoutView> {
+    fn animated_color(&self, property: AnimatableProperty) -> Color {
+        let animated = do self.read_layout_data |data| {
+            data.animated_colors.iter().find(|&&(index, _)| index == property as uint)
+                                       .map(|&&(_, value)| value)
+        };
+        match animated {
+            Some(value) => value,
+            None => property.computed_value(&self.style()),
+        }
+    }
+
+    fn set_animated_color(&self, property: AnimatableProperty, value: Option<Color>) {
+        do self.write_layout_data |data| {
+            data.animated_colors.retain(|&(index, _)| index != property as uint);
+            for &value in value.iter() {
+                data.animated_colors.push((property as uint, value))
+            }
+        }
+    }
+}
+
+/// The running transitions of a document.
+pub struct Animations {
+    priv transitions: ~[Transition],
+}
+
+impl Animations {
+    pub fn new() -> Animations {
+        Animations {
+            transitions: ~[],
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.transitions.is_empty()
+    }
+
+    /// Starts transitions of the properties of an element whose style has changed from `old` to
+    /// `new`, as the `transition-*` values of `new_values` say, and stops those of the properties
+    /// that no longer transition. Must be called before the element is given its new style.
+    pub fn start_transitions(&mut self,
+                             node: AbstractNode<LayoutView>,
+                             old: &CompleteStyle,
+                             new: &CompleteStyle,
+                             new_values: &ComputedValues,
+                             now: float) {
+        for &property in ANIMATABLE_PROPERTIES.iter() {
+            let to = property.computed_value(new);
+            // A transition that is interrupted starts the next one from where it got to.
+            let from = node.animated_color(property);
+            if same_color(from, to) && same_color(property.computed_value(old), to) {
+                loop
+            }
+            self.transitions.retain(|transition| {
+                transition.node != node || transition.property != property
+            });
+            match transition_timing(new_values, property) {
+                Some(timing) if !same_color(from, to) => {
+                    debug!("animation: transitioning property %? of %s",
+                           property,
+                           node.debug_str());
+                    node.set_animated_color(property, Some(from));
+                    self.transitions.push(Transition {
+                        node: node,
+                        property: property,
+                        from: from,
+                        to: to,
+                        start_time: now,
+                        timing: timing,
+                    })
+                }
+                _ => node.set_animated_color(property, None),
+            }
+        }
+    }
+
+    /// Advances the running transitions to the given time, ending those that have finished.
+    pub fn tick(&mut self, now: float) {
+        for transition in self.transitions.iter() {
+            let value = if transition.is_finished(now) {
+                None
+            } else {
+                Some(transition.value_at(now))
+            };
+            transition.node.set_animated_color(transition.property, value);
+        }
+        self.transitions.retain(|transition| !transition.is_finished(now));
+    }
+}
+
+#[test]
+fn test_timing_functions() {
+    let linear = TimingFunction::linear();
+    assert!((linear.progress(0.25) - 0.25).abs() < 0.001);
+    let ease = TimingFunction::ease();
+    assert!(ease.progress(0.0) == 0.0 && ease.progress(1.0) == 1.0);
+    // `ease` starts slowly and speeds up quickly.
+    assert!(ease.progress(0.5) > 0.75 && ease.progress(0.5) < 0.85);
+    assert!(ease.progress(-1.0) == 0.0 && ease.progress(2.0) == 1.0);
+}
+
+#[test]
+fn test_interpolate() {
+    let from = Color { r: 0.0, g: 0.0, b: 1.0, a: 1.0 };
+    let to = Color { r: 1.0, g: 0.0, b: 0.0, a: 0.5 };
+    let halfway = interpolate(from, to, 0.5);
+    assert!(same_color(halfway, Color { r: 0.5, g: 0.0, b: 0.5, a: 0.75 }));
+}
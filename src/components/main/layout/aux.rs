@@ -10,7 +10,7 @@ use servo_util::tree::TreeNodeRef;
 /// Functionality useful for querying the layout-specific data on DOM nodes.
 pub trait LayoutAuxMethods {
     fn initialize_layout_data(self);
-    fn initialize_style_for_subtree(self);
+    fn initialize_style_for_subtree(self) -> uint;
 }
 
 impl LayoutAuxMethods for AbstractNode<LayoutView> {
@@ -22,10 +22,13 @@ impl LayoutAuxMethods for AbstractNode<LayoutView> {
         }
     }
 
-    /// Resets layout data and styles for a Node tree.
-    fn initialize_style_for_subtree(self) {
+    /// Resets layout data and styles for a Node tree, and returns how many nodes are in it.
+    fn initialize_style_for_subtree(self) -> uint {
+        let mut count = 0;
         for n in self.traverse_preorder() {
             n.initialize_layout_data();
+            count += 1;
         }
+        count
     }
 }
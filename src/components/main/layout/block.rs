@@ -4,21 +4,28 @@
 
 //! CSS block layout.
 
+use css::node_style::StyledNode;
 use layout::box::{RenderBox};
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
 use layout::flow::{BlockFlow, FlowContext, FlowData, InlineBlockFlow, FloatFlow};
 use layout::inline::InlineLayout;
-use layout::model::{MaybeAuto, Specified, Auto};
+use layout::model::{Containment, MaybeAuto, Specified, Auto, to_au};
 use layout::float_context::{FloatContext, Invalid};
 
 use std::cell::Cell;
 use geom::point::Point2D;
 use geom::size::Size2D;
 use geom::rect::Rect;
-use gfx::display_list::DisplayList;
-use gfx::geometry::{Au, to_frac_px};
+use gfx::display_list::{BaseDisplayItem, DisplayList, FixedLayer, GroupDisplayItem};
+use gfx::display_list::GroupDisplayItemClass;
+use gfx::effects::GroupEffects;
+use gfx::geometry::Au;
 use gfx::geometry;
+use newcss::values::CSSPositionFixed;
+use script::dom::documenttype::FullQuirks;
+use script::dom::node::{AbstractNode, LayoutView};
+use script::style::properties::common_types::computed;
 
 pub struct BlockFlowData {
     /// Data common to all flows.
@@ -49,11 +56,17 @@ impl BlockFlowData {
     }
 
     pub fn teardown(&mut self) {
-        for box in self.box.iter() {
-            box.teardown();
-        }
         self.box = None;
     }
+
+    fn containment(&self) -> Containment {
+        self.box.map_default(Containment::none(), |box| box.containment())
+    }
+
+    /// The size that stands in for the contents of the block, if they are skipped.
+    fn skipped_contents_size(&self) -> Option<Size2D<Au>> {
+        self.box.map_default(None, |box| box.skipped_contents_size())
+    }
 }
 
 pub trait BlockLayout {
@@ -93,12 +106,21 @@ impl BlockFlowData {
         let mut num_floats = 0;
 
         /* find max width from child block contexts */
+        // With size containment, the widths are those of an empty box, or of the size that stands
+        // in for skipped contents.
+        let size_contained = self.containment().size;
+        for size in self.skipped_contents_size().iter() {
+            min_width = size.width;
+            pref_width = size.width;
+        }
         for child_ctx in self.common.child_iter() {
             assert!(child_ctx.starts_block_flow() || child_ctx.starts_inline_flow());
 
             do child_ctx.with_mut_base |child_node| {
-                min_width = geometry::max(min_width, child_node.min_width);
-                pref_width = geometry::max(pref_width, child_node.pref_width);
+                if !size_contained {
+                    min_width = geometry::max(min_width, child_node.min_width);
+                    pref_width = geometry::max(pref_width, child_node.pref_width);
+                }
 
                 num_floats = num_floats + child_node.num_floats;
             }
@@ -108,9 +130,9 @@ impl BlockFlowData {
            these widths will not include child elements, just padding etc. */
         self.box.map(|&box| {
             //Can compute border width here since it doesn't depend on anything
-            let style = box.style();
+            let values = box.computed_values();
             do box.with_model |model| {
-                model.compute_borders(style)
+                model.compute_borders(values)
             }
             min_width = min_width.add(&box.get_min_width(ctx));
             pref_width = pref_width.add(&box.get_pref_width(ctx));
@@ -192,26 +214,24 @@ impl BlockFlowData {
         let mut x_offset = Au(0);
 
         for &box in self.box.iter() {
-            let style = box.style();
+            let values = box.computed_values();
             do box.with_model |model| {
                 // Can compute padding here since we know containing block width.
-                model.compute_padding(style, remaining_width);
+                model.compute_padding(values, remaining_width);
 
                 // Margins are 0 right now so model.noncontent_width() is just borders + padding.
                 let available_width = remaining_width - model.noncontent_width();
 
                 // Top and bottom margins for blocks are 0 if auto.
-                let margin_top = MaybeAuto::from_margin(style.margin_top(),
-                                                        remaining_width,
-                                                        style.font_size()).specified_or_zero();
-                let margin_bottom = MaybeAuto::from_margin(style.margin_bottom(),
-                                                           remaining_width,
-                                                           style.font_size()).specified_or_zero();
+                let margin_top = MaybeAuto::from_style(values.margin_top,
+                                                       remaining_width).specified_or_zero();
+                let margin_bottom = MaybeAuto::from_style(values.margin_bottom,
+                                                          remaining_width).specified_or_zero();
 
                 let (width, margin_left, margin_right) =
-                    (MaybeAuto::from_width(style.width(), remaining_width, style.font_size()),
-                     MaybeAuto::from_margin(style.margin_left(), remaining_width, style.font_size()),
-                     MaybeAuto::from_margin(style.margin_right(), remaining_width, style.font_size()));
+                    (MaybeAuto::from_style(values.width, remaining_width),
+                     MaybeAuto::from_style(values.margin_left, remaining_width),
+                     MaybeAuto::from_style(values.margin_right, remaining_width));
 
                 let (width, margin_left, margin_right) = self.compute_horiz(width,
                                                                             margin_left,
@@ -319,15 +339,26 @@ impl BlockFlowData {
             };
         }
 
+        // With size containment, the height is that of an empty box, or of the size that stands in
+        // for skipped contents, and the children overflow.
+        let containment = self.containment();
         let mut height = if self.is_root {
             Au::max(ctx.screen_size.size.height, cur_y)
+        } else if containment.size {
+            self.skipped_contents_size().map_default(Au(0), |size| size.height)
         } else {
             cur_y - top_offset
         };
 
         for &box in self.box.iter() {
-            let style = box.style();
-            let maybe_height = MaybeAuto::from_height(style.height(), Au(0), style.font_size());
+            let height_value = box.computed_values().height;
+            let percentage_basis = match height_value {
+                computed::LPA_Percentage(_) => {
+                    percentage_height_basis(box.node(), &*ctx).unwrap_or_default(Au(0))
+                }
+                _ => Au(0),
+            };
+            let maybe_height = MaybeAuto::from_style(height_value, percentage_basis);
             let maybe_height = maybe_height.specified_or_zero();
             height = geometry::max(height, maybe_height);
         }
@@ -349,7 +380,8 @@ impl BlockFlowData {
         //TODO(eatkinson): compute heights using the 'height' property.
         self.common.position.size.height = height + noncontent_height;
 
-        if inorder {
+        // Floats inside a box with layout containment don't affect anything after it.
+        if inorder && !containment.layout {
             let extra_height = height - (cur_y - top_offset) + bottom_offset; 
             self.common.floats_out = float_ctx.translate(Point2D(left_offset, -extra_height));
         } else {
@@ -357,44 +389,111 @@ impl BlockFlowData {
         }
     }
 
+    /// Returns the rect of the content box of the block in the page. Must be called once the
+    /// block knows where it is in the page, as building its display list tells it.
+    pub fn content_rect_in_page(&self) -> Rect<Au> {
+        let x = self.common.abs_position.x + do self.box.map_default(Au(0)) |box| {
+            box.with_model(|model| model.margin.left + model.border.left + model.padding.left)
+        };
+        let y = self.common.abs_position.y + do self.box.map_default(Au(0)) |box| {
+            box.with_model(|model| model.margin.top + model.border.top + model.padding.top)
+        };
+        let w = self.common.position.size.width - do self.box.map_default(Au(0)) |box| {
+            box.with_model(|model| model.noncontent_width())
+        };
+        let h = self.common.position.size.height - do self.box.map_default(Au(0)) |box| {
+            box.with_model(|model| model.noncontent_height())
+        };
+        Rect(Point2D(x, y), Size2D(w, h))
+    }
+
+    /// Returns whether the block is `position: fixed`, so that it is drawn in a fixed layer of
+    /// its own rather than in the display list of the page.
+    ///
+    /// FIXME: Layout doesn't position fixed blocks yet, so they stay where the flow put them, in
+    /// the viewport as it was scrolled when the page was laid out. Nor are transforms supported;
+    /// once they are, a block with a transformed ancestor has to stay in the page, as the
+    /// ancestor is its containing block and scrolls with it.
+    pub fn is_fixed(&self) -> bool {
+        do self.box.map_default(false) |box| {
+            match box.style().position() {
+                CSSPositionFixed => true,
+                _ => false,
+            }
+        }
+    }
+
     pub fn build_display_list_block<E:ExtraDisplayListData>(&mut self,
                                                             builder: &DisplayListBuilder,
                                                             dirty: &Rect<Au>, 
                                                             list: &Cell<DisplayList<E>>) 
                                                             -> bool {
+        // Fixed blocks and their descendants are left to `build_fixed_layer`.
+        if self.is_fixed() {
+            return true;
+        }
+        self.build_display_list_block_contents(builder, dirty, list)
+    }
 
+    /// Builds the fixed layer of a block with `position: fixed`, which holds the display items
+    /// of the block and its descendants, except those of fixed blocks inside it, which have
+    /// layers of their own. `viewport_origin` is where the viewport is scrolled to in the page.
+    ///
+    /// The layer is the size of the border box of the block, so descendants that overflow the
+    /// block are clipped to it.
+    pub fn build_fixed_layer<E:ExtraDisplayListData>(&mut self,
+                                                     builder: &DisplayListBuilder,
+                                                     viewport_origin: &Point2D<Au>)
+                                                     -> FixedLayer<E> {
+        let bounds = Rect(self.common.abs_position, self.common.position.size);
+        let list = Cell::new(DisplayList::new());
+        if !self.build_display_list_block_contents(builder, &bounds, &list) {
+            for child in self.common.child_iter() {
+                child.each_preorder_prune(|flow| {
+                    flow.build_display_list(builder, &bounds, &list)
+                }, |_| { true });
+            }
+        }
+        FixedLayer {
+            bounds: bounds,
+            viewport_position: Point2D(bounds.origin.x - viewport_origin.x,
+                                       bounds.origin.y - viewport_origin.y),
+            list: list.take(),
+        }
+    }
+
+    fn build_display_list_block_contents<E:ExtraDisplayListData>(&mut self,
+                                                                 builder: &DisplayListBuilder,
+                                                                 dirty: &Rect<Au>,
+                                                                 list: &Cell<DisplayList<E>>)
+                                                                 -> bool {
         if self.common.node.is_iframe_element() {
-            let x = self.common.abs_position.x + do self.box.map_default(Au(0)) |box| {
-                box.with_model(|model| model.margin.left + model.border.left + model.padding.left)
-            };
-            let y = self.common.abs_position.y + do self.box.map_default(Au(0)) |box| {
-                box.with_model(|model| model.margin.top + model.border.top + model.padding.top)
-            };
-            let w = self.common.position.size.width - do self.box.map_default(Au(0)) |box| {
-                box.with_model(|model| model.noncontent_width())
-            };
-            let h = self.common.position.size.height - do self.box.map_default(Au(0)) |box| {
-                box.with_model(|model| model.noncontent_height())
-            };
+            let Rect { origin: Point2D { x, y }, size: Size2D { width: w, height: h } } =
+                self.content_rect_in_page();
             do self.common.node.with_mut_iframe_element |iframe_element| {
-                iframe_element.size.get_mut_ref().set_rect(Rect(Point2D(to_frac_px(x) as f32,
-                                                                        to_frac_px(y) as f32),
-                                                                Size2D(to_frac_px(w) as f32,
-                                                                       to_frac_px(h) as f32)));
+                iframe_element.size.get_mut_ref().set_rect(Rect(Point2D(*x.to_css_px() as f32,
+                                                                        *y.to_css_px() as f32),
+                                                                Size2D(*w.to_css_px() as f32,
+                                                                       *h.to_css_px() as f32)));
             }
         }
 
+        // Descendants can overflow the block, so only a block with paint containment culls them
+        // along with itself.
+        let containment = self.containment();
         let abs_rect = Rect(self.common.abs_position, self.common.position.size);
         if !abs_rect.intersects(dirty) {
-            return true;
-        }
-
-        debug!("build_display_list_block: adding display element");
+            if containment.paint {
+                return true;
+            }
+        } else {
+            debug!("build_display_list_block: adding display element");
 
-        // add box that starts block context
-        self.box.map(|&box| {
-            box.build_display_list(builder, dirty, &self.common.abs_position, list)
-        });
+            // add box that starts block context
+            self.box.map(|&box| {
+                box.build_display_list(builder, dirty, &self.common.abs_position, list)
+            });
+        }
 
 
         // TODO: handle any out-of-flow elements
@@ -405,7 +504,82 @@ impl BlockFlowData {
             }
         }
 
+        if containment.paint {
+            self.build_display_list_for_contained_children(builder, dirty, list);
+            return true;
+        }
         false
     }
+
+    /// Builds the display lists of the descendants of a block with paint containment into a
+    /// group of their own, which clips them to the padding box of the block.
+    fn build_display_list_for_contained_children<E:ExtraDisplayListData>(
+            &mut self,
+            builder: &DisplayListBuilder,
+            dirty: &Rect<Au>,
+            list: &Cell<DisplayList<E>>) {
+        let box = match self.box {
+            Some(box) => box,
+            None => return,
+        };
+        let border_box = box.position().translate(&self.common.abs_position);
+        let padding_box = do box.with_model |model| {
+            Rect(Point2D(border_box.origin.x + model.border.left,
+                         border_box.origin.y + model.border.top),
+                 Size2D(border_box.size.width - model.border.left - model.border.right,
+                        border_box.size.height - model.border.top - model.border.bottom))
+        };
+        let clip = match padding_box.intersection(dirty) {
+            Some(clip) => clip,
+            None => return,
+        };
+
+        let group_list = Cell::new(DisplayList::new());
+        for child in self.common.child_iter() {
+            child.each_preorder_prune(|flow| {
+                flow.build_display_list(builder, dirty, &group_list)
+            }, |_| { true });
+        }
+        let mut effects = GroupEffects::none();
+        effects.clip = true;
+        do list.with_mut_ref |list| {
+            let group_display_item = ~GroupDisplayItem {
+                base: BaseDisplayItem {
+                    bounds: clip,
+                    extra: ExtraDisplayListData::new(box),
+                },
+                list: group_list.take(),
+                effects: effects.clone(),
+            };
+            list.append_item(GroupDisplayItemClass(group_display_item))
+        }
+    }
 }
 
+/// Returns the height that a percentage `height` of `node` is a percentage of, or `None` if that
+/// depends on the height of the contents, in which case the percentage behaves as `auto`.
+///
+/// The basis is the specified height of the parent, or the height of the viewport for the root.
+/// In quirks mode, ancestors whose height is `auto` are skipped, as older browsers did, so that
+/// `height: 100%` fills the viewport on pages that don't give `<body>` a height.
+fn percentage_height_basis(node: AbstractNode<LayoutView>, ctx: &LayoutContext) -> Option<Au> {
+    let mut ancestor = node.parent_node();
+    loop {
+        let current = match ancestor {
+            Some(current) if current.is_element() => current,
+            _ => return Some(ctx.screen_size.size.height),
+        };
+        match current.computed_values().height {
+            computed::LPA_Auto if ctx.quirks_mode == FullQuirks => {
+                ancestor = current.parent_node()
+            }
+            computed::LPA_Auto => return None,
+            computed::LPA_Percentage(percent) => {
+                return do percentage_height_basis(current, ctx).map_move |basis| {
+                    basis.scale_by(percent / 100.0)
+                }
+            }
+            computed::LPA_Length(height) => return Some(to_au(height)),
+        }
+    }
+}
@@ -5,38 +5,85 @@
 //! The `RenderBox` type, which represents the leaves of the layout tree.
 
 use css::node_style::StyledNode;
+use css::node_util::NodeUtil;
+use layout::animation::{AnimatedNode, BackgroundColorProperty, BorderBottomColorProperty};
+use layout::animation::{BorderLeftColorProperty, BorderRightColorProperty};
+use layout::animation::{BorderTopColorProperty, ColorProperty};
+use layout::content_visibility::{ContentVisibility, ContentVisibilityVisible};
+use layout::content_visibility::{ContentVisibilityAuto, ContentVisibilityHidden};
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData, ToGfxColor};
 use layout::float_context::{ClearType, ClearLeft, ClearRight, ClearBoth};
-use layout::model::{BoxModel, MaybeAuto};
+use layout::model::{BoxModel, Containment, MaybeAuto, to_au};
+use layout::scroll_snap::{ScrollSnapAlign, ScrollSnapAlignCenter, ScrollSnapAlignEnd};
+use layout::scroll_snap::{ScrollSnapAlignNone, ScrollSnapAlignStart, ScrollSnapType};
 use layout::text;
 
 use std::cell::Cell;
 use std::cmp::ApproxEq;
 use std::managed;
 use std::num::Zero;
+use std::uint;
 use geom::{Point2D, Rect, Size2D, SideOffsets2D};
 use gfx::display_list::{BaseDisplayItem, BorderDisplayItem, BorderDisplayItemClass};
 use gfx::display_list::{DisplayList, ImageDisplayItem, ImageDisplayItemClass};
 use gfx::display_list::{SolidColorDisplayItem, SolidColorDisplayItemClass, TextDisplayItem};
-use gfx::display_list::{TextDisplayItemClass};
-use gfx::font::{FontStyle, FontWeight300};
-use gfx::geometry::Au;
-use gfx::text::text_run::TextRun;
+use gfx::display_list::{TextDecorations, TextDisplayItemClass, WavyLineDisplayItem};
+use gfx::display_list::{GroupDisplayItem, GroupDisplayItemClass, WavyLineDisplayItemClass};
+use gfx::effects::{AlphaMask, Filter, GroupEffects, LuminanceMask, Mask, MaskMode};
+use gfx::effects::{Blur, Brightness, Contrast, Grayscale, HueRotate, Invert, Opacity};
+use gfx::effects::{Saturate, Sepia};
+use gfx::color::Color;
+use gfx::font::{CSSFontStretch, CSSFontWeight, FontFeature, FontStyle, FontStretchCondensed};
+use gfx::font::{FontStretchExpanded, FontStretchExtraCondensed, FontStretchExtraExpanded};
+use gfx::font::{FontStretchNormal, FontStretchSemiCondensed, FontStretchSemiExpanded};
+use gfx::font::{FontStretchUltraCondensed, FontStretchUltraExpanded, FontWeight100};
+use gfx::font::{FontWeight200, FontWeight300, FontWeight400, FontWeight500, FontWeight600};
+use gfx::font::{FontWeight700, FontWeight800, FontWeight900};
+use gfx::geometry;
+use gfx::geometry::{Au, CSSPixel};
+use gfx::text::line_break::{WordBreak, WordBreakBreakAll, WordBreakKeepAll, WordBreakNormal};
+use gfx::text::script::{LeftToRight, RightToLeft, TextDirection};
+use gfx::text::spellcheck::Dictionary;
+use gfx::text::text_run::{TabLength, TabSize, TabSpaces, TextRun};
+use gfx::text::util::{CompressionMode, CompressNone, CompressWhitespace};
+use gfx::text::util::{CompressWhitespaceNewline, TextTransform, TextTransformCapitalize};
+use gfx::text::util::{TextTransformLowercase, TextTransformNone, TextTransformUppercase};
+use CSSColor = newcss::color::Color;
 use newcss::color::rgb;
 use newcss::complete::CompleteStyle;
-use newcss::units::{Em, Px};
-use newcss::units::{Cursive, Fantasy, Monospace, SansSerif, Serif};
 use newcss::values::{CSSClearNone, CSSClearLeft, CSSClearRight, CSSClearBoth};
-use newcss::values::{CSSFontFamilyFamilyName, CSSFontFamilyGenericFamily};
-use newcss::values::{CSSFontSizeLength, CSSFontStyleItalic, CSSFontStyleNormal};
-use newcss::values::{CSSFontStyleOblique, CSSTextAlign, CSSTextDecoration, CSSLineHeight, CSSVerticalAlign};
-use newcss::values::{CSSTextDecorationNone, CSSFloatNone, CSSPositionStatic};
-use newcss::values::{CSSDisplayInlineBlock, CSSDisplayInlineTable};
+use newcss::values::{CSSTextAlign, CSSVerticalAlign};
+use newcss::values::{CSSTextDecorationUnderline, CSSTextDecorationOverline};
+use newcss::values::{CSSTextDecorationLineThrough, CSSFloatNone, CSSPositionStatic};
+use newcss::values::{CSSDisplayInline, CSSDisplayInlineBlock, CSSDisplayInlineTable};
+use newcss::values::CSSDisplayTableCell;
+use script::dom::htmlcanvaselement::{ImageDrawing, TextDrawing, text_origin};
+use script::dom::htmlmeterelement::{MeterRegion, OptimumRegion, SuboptimumRegion};
+use script::dom::htmlmeterelement::EvenLessGoodRegion;
+use script::dom::htmlinputelement::{RangeInput, NumberInput, DateInput, ColorInput, TextInput};
 use script::dom::node::{AbstractNode, LayoutView};
+use script::style::selectors::{After, Before, PseudoElement};
+use script::style::properties::longhands::{backdrop_filter, hyphens, overflow_wrap, tab_size};
+use script::style::properties::longhands::{font_family, font_feature_settings, font_kerning};
+use script::style::properties::longhands::{font_stretch, font_style};
+use script::style::properties::longhands::{font_variant, font_weight, outline_style};
+use script::style::properties::longhands::text_transform;
+use script::style::properties::{ComputedValues, CurrentColor, FontValues};
+use script::style::properties::common_types::computed;
+use script::style::properties::longhands::{content_visibility, empty_cells, mask_mode};
+use script::style::properties::longhands::{line_height, white_space};
+use script::style::properties::longhands::{overscroll_behavior_x, overscroll_behavior_y};
+use script::style::properties::longhands::{scroll_behavior, scroll_snap_align, scrollbar_width};
+use script::style::properties::longhands::word_break;
+use servo_msg::compositor_msg::{AutoScrollbarWidth, OverscrollAuto, OverscrollBehavior};
+use servo_msg::compositor_msg::{NoScrollbars, OverscrollContain, OverscrollNone};
+use servo_msg::compositor_msg::ThinScrollbarWidth;
+use servo_msg::compositor_msg::{MandatorySnap, ProximitySnap, ScrollbarWidth, TouchAction};
 use servo_net::image::holder::ImageHolder;
 use servo_net::local_image_cache::LocalImageCache;
 use servo_util::range::*;
+use servo_util::url::make_url;
 use extra::url::Url;
 
 /// Render boxes (`struct RenderBox`) are the leaves of the layout tree. They cannot position
@@ -66,15 +113,6 @@ pub enum RenderBox {
     UnscannedTextRenderBoxClass(@mut UnscannedTextRenderBox),
 }
 
-impl RenderBox {
-    pub fn teardown(&self) {
-        match *self {
-            TextRenderBoxClass(box) => box.teardown(),
-            _ => ()
-        }
-    }
-}
-
 /// A box that represents a (replaced content) image and its accompanying borders, shadows, etc.
 pub struct ImageRenderBox {
     base: RenderBoxBase,
@@ -84,7 +122,8 @@ pub struct ImageRenderBox {
 impl ImageRenderBox {
     pub fn new(base: RenderBoxBase, image_url: Url, local_image_cache: @mut LocalImageCache)
                -> ImageRenderBox {
-        assert!(base.node.is_image_element());
+        // The images of generated content belong to the element whose pseudo-element made them.
+        assert!(base.node.is_image_element() || base.pseudo_element.is_some());
 
         ImageRenderBox {
             base: base,
@@ -103,9 +142,73 @@ pub struct TextRenderBox {
     range: Range,
 }
 
-impl TextRenderBox {
-    fn teardown(&self) {
-        self.run.teardown();
+/// A form control that layout paints itself, instead of laying out its children.
+pub enum Widget {
+    /// A progress bar, with the fraction of the task that is complete, or `None` if it is
+    /// indeterminate.
+    ProgressWidget(Option<f64>),
+    /// A gauge, with the fraction of it that is filled and where its value falls relative to the
+    /// optimum.
+    MeterWidget(f64, MeterRegion),
+    /// A slider, with how far along its track the thumb is.
+    RangeWidget(f64),
+    /// A number field, with spin buttons.
+    NumberWidget,
+    /// A color well, with the red, green, and blue components of its color.
+    ColorWidget(u8, u8, u8),
+}
+
+/// A shadow cast by the text of a box.
+pub struct TextShadow {
+    /// The offset of the shadow from the text.
+    offset: Point2D<Au>,
+    blur_radius: Au,
+    color: Color,
+}
+
+/// The values of the `white-space` property, which control how whitespace in text is collapsed
+/// and whether lines may wrap.
+#[deriving(Eq)]
+pub enum WhiteSpace {
+    WhiteSpaceNormal,
+    WhiteSpacePre,
+    WhiteSpaceNowrap,
+    WhiteSpacePreWrap,
+    WhiteSpacePreLine,
+}
+
+impl WhiteSpace {
+    /// Returns how whitespace in text with this `white-space` is collapsed before shaping.
+    pub fn compression_mode(&self) -> CompressionMode {
+        match *self {
+            WhiteSpaceNormal | WhiteSpaceNowrap => CompressWhitespaceNewline,
+            WhiteSpacePre | WhiteSpacePreWrap => CompressNone,
+            WhiteSpacePreLine => CompressWhitespace,
+        }
+    }
+
+    /// Returns true if lines may be broken at the soft wrap opportunities in the text.
+    pub fn allows_wrapping(&self) -> bool {
+        match *self {
+            WhiteSpaceNormal | WhiteSpacePreWrap | WhiteSpacePreLine => true,
+            WhiteSpacePre | WhiteSpaceNowrap => false,
+        }
+    }
+
+    /// Returns true if newlines in the text are kept, and force line breaks.
+    pub fn preserves_newlines(&self) -> bool {
+        match *self {
+            WhiteSpacePre | WhiteSpacePreWrap | WhiteSpacePreLine => true,
+            WhiteSpaceNormal | WhiteSpaceNowrap => false,
+        }
+    }
+
+    /// Returns true if runs of spaces and tabs are kept as written.
+    pub fn preserves_spaces(&self) -> bool {
+        match *self {
+            WhiteSpacePre | WhiteSpacePreWrap => true,
+            WhiteSpaceNormal | WhiteSpaceNowrap | WhiteSpacePreLine => false,
+        }
     }
 }
 
@@ -130,6 +233,16 @@ impl UnscannedTextRenderBox {
             }
         }
     }
+
+    /// Creates a box for text that is generated for the element of `base`: the content of one of
+    /// its pseudo-elements, or its list marker. The box is styled as the element is.
+    pub fn new_generated(base: RenderBoxBase, text: ~str) -> UnscannedTextRenderBox {
+        assert!(base.node.is_element());
+        UnscannedTextRenderBox {
+            base: base,
+            text: text,
+        }
+    }
 }
 
 pub enum RenderBoxType {
@@ -158,6 +271,12 @@ pub struct RenderBoxBase {
     /// The core parameters (border, padding, margin) used by the box model.
     model: BoxModel,
 
+    /// The pseudo-element of the node whose content this box holds, if it holds generated content.
+    pseudo_element: Option<PseudoElement>,
+
+    /// Whether this box holds the marker of a list item, which is styled as the item is.
+    marker: bool,
+
     /// A debug ID.
     ///
     /// TODO(#87) Make this only present in debug builds.
@@ -172,6 +291,8 @@ impl RenderBoxBase {
             node: node,
             position: Au::zero_rect(),
             model: Zero::zero(),
+            pseudo_element: None,
+            marker: false,
             id: id,
         }
     }
@@ -238,7 +359,7 @@ impl RenderBox {
     /// Returns true if this element can be split. This is true for text boxes.
     pub fn can_split(&self) -> bool {
         match *self {
-            TextRenderBoxClass(*) => true,
+            TextRenderBoxClass(*) => self.white_space().allows_wrapping(),
             _ => false
         }
     }
@@ -257,7 +378,8 @@ impl RenderBox {
     pub fn can_merge_with_box(&self, other: RenderBox) -> bool {
         match (self, &other) {
             (&UnscannedTextRenderBoxClass(*), &UnscannedTextRenderBoxClass(*)) => {
-                self.font_style() == other.font_style() && self.text_decoration() == other.text_decoration()
+                self.font_style() == other.font_style() &&
+                    self.white_space() == other.white_space()
             },
             (&TextRenderBoxClass(text_box_a), &TextRenderBoxClass(text_box_b)) => {
                 managed::ptr_eq(text_box_a.run, text_box_b.run)
@@ -266,6 +388,44 @@ impl RenderBox {
         }
     }
 
+    /// Returns true if this is a text box that ends in a newline kept by `white-space`, after which
+    /// the line must be broken.
+    pub fn ends_in_forced_break(&self) -> bool {
+        match *self {
+            TextRenderBoxClass(text_box) if !text_box.range.is_empty() => {
+                let last = Range::new(text_box.range.end() - 1, 1);
+                text_box.run.first_newline_in_range(&last).is_some()
+            }
+            _ => false,
+        }
+    }
+
+    /// Splits a text box after its first newline kept by `white-space`, if the newline is not the
+    /// last character of the box. The line has to be broken there, so the text after the newline
+    /// is laid out on its own.
+    pub fn split_after_forced_break(&self) -> Option<(RenderBox, RenderBox)> {
+        let text_box = match *self {
+            TextRenderBoxClass(text_box) => text_box,
+            _ => return None,
+        };
+        match text_box.run.first_newline_in_range(&text_box.range) {
+            Some(offset) if offset + 1 < text_box.range.end() => {
+                let begin = text_box.range.begin();
+                let end = text_box.range.end();
+                let left_range = Range::new(begin, offset + 1 - begin);
+                let right_range = Range::new(offset + 1, end - offset - 1);
+                let left_box = @mut text::adapt_textbox_with_range(text_box.base,
+                                                                   text_box.run,
+                                                                   left_range);
+                let right_box = @mut text::adapt_textbox_with_range(text_box.base,
+                                                                    text_box.run,
+                                                                    right_range);
+                Some((TextRenderBoxClass(left_box), TextRenderBoxClass(right_box)))
+            }
+            _ => None,
+        }
+    }
+
     /// Attempts to split this box so that its width is no more than `max_width`. Fails if this box
     /// is an unscanned text box.
     pub fn split_to_width(&self, max_width: Au, starts_line: bool)
@@ -277,74 +437,101 @@ impl RenderBox {
             }
 
             TextRenderBoxClass(text_box) => {
+                let run = text_box.run;
                 let mut pieces_processed_count: uint = 0;
-                let mut remaining_width: Au = max_width;
-                let mut left_range = Range::new(text_box.range.begin(), 0);
+                let mut left_begin = text_box.range.begin();
+                let range_end = text_box.range.end();
                 let mut right_range: Option<Range> = None;
+                let mut hyphenated = false;
+                let mut broke_word = false;
 
                 debug!("split_to_width: splitting text box (strlen=%u, range=%?, avail_width=%?)",
-                       text_box.run.text.len(),
+                       run.text.len(),
                        text_box.range,
                        max_width);
 
-                for (glyphs, offset, slice_range) in text_box.run.iter_slices_for_range(&text_box.range) {
-                    debug!("split_to_width: considering slice (offset=%?, range=%?, remain_width=%?)",
-                           offset,
-                           slice_range,
-                           remaining_width);
-
-                    let metrics = text_box.run.metrics_for_slice(glyphs, &slice_range);
-                    let advance = metrics.advance_width;
-                    let should_continue: bool;
+                // Slices are the units a line can be broken between. The start of slice `i`,
+                // clipped to the end of this box, is a possible end for the left box.
+                let slice_end = |i: uint| uint::min(run.slice_start(i), range_end);
 
-                    if advance <= remaining_width {
-                        should_continue = true;
+                if starts_line && !self.white_space().preserves_spaces() &&
+                        run.slice_is_whitespace(run.slice_index_for_char(left_begin)) {
+                    debug!("split_to_width: case=skipping leading trimmable whitespace");
+                    left_begin = slice_end(run.slice_index_for_char(left_begin) + 1);
+                    pieces_processed_count += 1;
+                }
 
-                        if starts_line && pieces_processed_count == 0 && glyphs.is_whitespace() {
-                            debug!("split_to_width: case=skipping leading trimmable whitespace");
-                            left_range.shift_by(slice_range.length() as int);
+                let mut left_end = left_begin;
+                if left_begin < range_end {
+                    let first_slice = run.slice_index_for_char(left_begin);
+                    let last_slice = run.slice_index_for_char(range_end - 1);
+
+                    // Binary search for the last slice that still fits. The advance of a prefix
+                    // of the box only grows as slices are added, and measuring it only touches
+                    // the glyphs of partial slices at the ends, so no work is repeated per slice.
+                    let (mut lo, mut hi) = (first_slice, last_slice + 1);
+                    while lo < hi {
+                        let mid = (lo + hi + 1) / 2;
+                        let prefix = Range::new(left_begin, slice_end(mid) - left_begin);
+                        if run.advance_for_range(&prefix) <= max_width {
+                            lo = mid
                         } else {
-                            debug!("split_to_width: case=enlarging span");
-                            remaining_width = remaining_width - advance;
-                            left_range.extend_by(slice_range.length() as int);
+                            hi = mid - 1
                         }
-                    } else {    // The advance is more than the remaining width.
-                        should_continue = false;
-                        let slice_begin = offset + slice_range.begin();
-                        let slice_end = offset + slice_range.end();
-
-                        if glyphs.is_whitespace() {
-                            // If there are still things after the trimmable whitespace, create the
-                            // right chunk.
-                            if slice_end < text_box.range.end() {
-                                debug!("split_to_width: case=skipping trimmable trailing \
-                                        whitespace, then split remainder");
-                                let right_range_end =
-                                    text_box.range.end() - slice_end;
-                                right_range = Some(Range::new(slice_end, right_range_end));
-                            } else {
-                                debug!("split_to_width: case=skipping trimmable trailing \
-                                        whitespace");
-                            }
-                        } else if slice_begin < text_box.range.end() {
-                            // There are still some things left over at the end of the line. Create
-                            // the right chunk.
-                            let right_range_end =
-                                text_box.range.end() - slice_begin;
-                            right_range = Some(Range::new(slice_begin, right_range_end));
-                            debug!("split_to_width: case=splitting remainder with right range=%?",
-                                   right_range);
+                    }
+                    // A line broken at a soft hyphen ends in a visible hyphen, which has to fit
+                    // on the line as well.
+                    while lo > first_slice && lo <= last_slice &&
+                            run.slice_ends_in_soft_hyphen(lo - 1) {
+                        let prefix = Range::new(left_begin, slice_end(lo) - left_begin);
+                        if run.advance_for_range(&prefix) + run.hyphen_advance() <= max_width {
+                            break
                         }
+                        lo -= 1;
+                    }
+                    let fitting_slices = lo - first_slice;
+                    pieces_processed_count += fitting_slices;
+                    if fitting_slices > 0 {
+                        left_end = slice_end(lo);
+                        hyphenated = lo <= last_slice && run.slice_ends_in_soft_hyphen(lo - 1);
                     }
 
-                    pieces_processed_count += 1;
-
-                    if !should_continue {
-                        break
+                    if lo <= last_slice {
+                        // Slice `lo` is the first that doesn't fit on this line.
+                        pieces_processed_count += 1;
+                        let next_begin = if run.slice_is_whitespace(lo) {
+                            debug!("split_to_width: case=skipping trimmable trailing whitespace");
+                            slice_end(lo + 1)
+                        } else if fitting_slices == 0 && starts_line && self.breaks_long_words() {
+                            // Nothing fits on this line, so rather than overflowing it, break the
+                            // word between the last grapheme clusters that fit.
+                            debug!("split_to_width: case=breaking inside a long word");
+                            left_end = run.cluster_boundary_for_width(left_begin,
+                                                                      slice_end(lo + 1),
+                                                                      max_width);
+                            broke_word = true;
+                            left_end
+                        } else {
+                            left_end
+                        };
+                        if next_begin < range_end {
+                            right_range = Some(Range::new(next_begin, range_end - next_begin));
+                            debug!("split_to_width: case=splitting remainder with right range=%?",
+                                   right_range);
+                        }
                     }
                 }
+                let left_range = Range::new(left_begin, left_end - left_begin);
 
-                let left_box = if left_range.length() > 0 {
+                let left_box = if hyphenated {
+                    debug!("split_to_width: case=breaking at a soft hyphen");
+                    let hyphenated_run = @run.hyphenated_run_for_range(&left_range);
+                    let range = Range::new(0, hyphenated_run.char_len());
+                    let new_text_box = @mut text::adapt_textbox_with_range(text_box.base,
+                                                                           hyphenated_run,
+                                                                           range);
+                    Some(TextRenderBoxClass(new_text_box))
+                } else if left_range.length() > 0 {
                     let new_text_box = @mut text::adapt_textbox_with_range(text_box.base,
                                                                            text_box.run,
                                                                            left_range);
@@ -360,7 +547,9 @@ impl RenderBox {
                     Some(TextRenderBoxClass(new_text_box))
                 };
 
-                if pieces_processed_count == 1 || left_box.is_none() {
+                if broke_word {
+                    SplitDidFit(left_box, right_box)
+                } else if pieces_processed_count == 1 || left_box.is_none() {
                     SplitDidNotFit(left_box, right_box)
                 } else {
                     SplitDidFit(left_box, right_box)
@@ -381,30 +570,21 @@ impl RenderBox {
     // get_min_width and get_pref_width?
     fn guess_width (&self) -> Au {
         do self.with_base |base| {
-            if(!base.node.is_element()) {
+            // The width and box model of the element don't apply to its generated content.
+            if(!base.node.is_element() || base.pseudo_element.is_some() || base.marker) {
                 Au(0)
             } else {
-                let style = self.style();
-                let font_size = style.font_size();
-                let width = MaybeAuto::from_width(style.width(),
-                                                  Au(0),
-                                                  font_size).specified_or_zero();
-                let margin_left = MaybeAuto::from_margin(style.margin_left(),
-                                                         Au(0),
-                                                         font_size).specified_or_zero();
-                let margin_right = MaybeAuto::from_margin(style.margin_right(),
-                                                          Au(0),
-                                                          font_size).specified_or_zero();
-                let padding_left = base.model.compute_padding_length(style.padding_left(),
-                                                                     Au(0),
-                                                                     font_size);
-                let padding_right = base.model.compute_padding_length(style.padding_right(),
-                                                                      Au(0),
-                                                                      font_size);
-                let border_left = base.model.compute_border_width(style.border_left_width(),
-                                                                  font_size);
-                let border_right = base.model.compute_border_width(style.border_right_width(),
-                                                                   font_size);
+                let values = self.computed_values();
+                let width = MaybeAuto::from_style(values.width, Au(0)).specified_or_zero();
+                let margin_left = MaybeAuto::from_style(values.margin_left,
+                                                        Au(0)).specified_or_zero();
+                let margin_right = MaybeAuto::from_style(values.margin_right,
+                                                         Au(0)).specified_or_zero();
+                let padding_left = base.model.compute_padding_length(values.padding_left, Au(0));
+                let padding_right = base.model.compute_padding_length(values.padding_right,
+                                                                      Au(0));
+                let border_left = to_au(values.border_left_width);
+                let border_right = to_au(values.border_right_width);
 
                 width + margin_left + margin_right + padding_left + padding_right + 
                     border_left + border_right
@@ -422,7 +602,9 @@ impl RenderBox {
             // That includes borders, margins, and padding, but not child widths. The block
             // `FlowContext` will combine the width of this element and that of its children to
             // arrive at the context width.
-            GenericRenderBoxClass(*) => Au(0),
+            GenericRenderBoxClass(*) => {
+                self.intrinsic_size().map_default(Au(0), |size| size.width)
+            }
 
             ImageRenderBoxClass(image_box) => {
                 // TODO: Consult the CSS `width` property as well as margins and borders.
@@ -445,7 +627,9 @@ impl RenderBox {
             // That includes borders, margins, and padding, but not child widths. The block
             // `FlowContext` will combine the width of this element and that of its children to
             // arrive at the context width.
-            GenericRenderBoxClass(*) => Au(0),
+            GenericRenderBoxClass(*) => {
+                self.intrinsic_size().map_default(Au(0), |size| size.width)
+            }
 
             ImageRenderBoxClass(image_box) => {
                 Au::from_px(image_box.image.get_size().unwrap_or_default(Size2D(0, 0)).width)
@@ -487,7 +671,7 @@ impl RenderBox {
 
     pub fn compute_padding(&self, cb_width: Au) {
         do self.with_mut_base |base| {
-            base.model.compute_padding(base.node.style(), cb_width);
+            base.model.compute_padding(base.node.computed_values(), cb_width);
         }
     }
 
@@ -560,6 +744,20 @@ impl RenderBox {
         }
     }
 
+    /// Returns the values that the new style system has computed for this box: those of the
+    /// pseudo-element whose content it holds, if any, or else those of its nearest
+    /// ancestor-or-self `Element` node. Boxes are only built for elements that have been styled.
+    pub fn computed_values<'a>(&'a self) -> &'a ComputedValues {
+        let element = self.nearest_ancestor_element();
+        match self.with_base(|base| base.pseudo_element) {
+            Some(pseudo_element) => match element.get_pseudo_element_values(pseudo_element) {
+                Some(values) => values,
+                None => fail!(~"generated content without the values of its pseudo-element"),
+            },
+            None => element.get_computed_values(),
+        }
+    }
+
     //
     // Painting
     //
@@ -579,7 +777,7 @@ impl RenderBox {
     /// items, each box puts its display items into the correct stack layer according to CSS 2.1
     /// Appendix E. Finally, the builder flattens the list.
     pub fn build_display_list<E:ExtraDisplayListData>(&self,
-                                                  _: &DisplayListBuilder,
+                                                  builder: &DisplayListBuilder,
                                                   dirty: &Rect<Au>,
                                                   offset: &Point2D<Au>,
                                                   list: &Cell<DisplayList<E>>) {
@@ -596,6 +794,36 @@ impl RenderBox {
             return;
         }
 
+        // A box with effects that apply to its rendering as a whole is drawn as a group.
+        let effects = match self.group_effects(builder.ctx, &absolute_box_bounds) {
+            Some(effects) => effects,
+            None => return,
+        };
+        if effects.is_empty() {
+            return self.build_display_items(builder, &absolute_box_bounds, list)
+        }
+        let group_list = Cell::new(DisplayList::new());
+        self.build_display_items(builder, &absolute_box_bounds, &group_list);
+        do list.with_mut_ref |list| {
+            let group_display_item = ~GroupDisplayItem {
+                base: BaseDisplayItem {
+                    bounds: absolute_box_bounds,
+                    extra: ExtraDisplayListData::new(*self),
+                },
+                list: group_list.take(),
+                effects: effects.clone(),
+            };
+            list.append_item(GroupDisplayItemClass(group_display_item))
+        }
+    }
+
+    /// Adds the display items that draw this render box, whose bounds relative to the display
+    /// list root flow are `absolute_bounds`, to the given display list.
+    fn build_display_items<E:ExtraDisplayListData>(&self,
+                                                   builder: &DisplayListBuilder,
+                                                   absolute_bounds: &Rect<Au>,
+                                                   list: &Cell<DisplayList<E>>) {
+        let absolute_box_bounds = *absolute_bounds;
         match *self {
             UnscannedTextRenderBoxClass(*) => fail!(~"Shouldn't see unscanned boxes here."),
             TextRenderBoxClass(text_box) => {
@@ -604,7 +832,27 @@ impl RenderBox {
                 self.paint_background_if_applicable(list, &absolute_box_bounds);
 
                 let nearest_ancestor_element = self.nearest_ancestor_element();
-                let color = nearest_ancestor_element.style().color().to_gfx_color();
+                let color = nearest_ancestor_element.animated_color(ColorProperty);
+                let decorations = self.text_decorations();
+
+                // Add the text shadows beneath the text, the first shadow on top.
+                for shadow in self.text_shadows().rev_iter() {
+                    do list.with_mut_ref |list| {
+                        let shadow_display_item = ~TextDisplayItem {
+                            base: BaseDisplayItem {
+                                bounds: absolute_box_bounds.translate(&shadow.offset),
+                                extra: ExtraDisplayListData::new(*self),
+                            },
+                            text_run: ~text_box.run.serialize(),
+                            range: text_box.range,
+                            color: shadow.color,
+                            decorations: decorations.with_color(shadow.color),
+                            blur_radius: shadow.blur_radius,
+                        };
+
+                        list.append_item(TextDisplayItemClass(shadow_display_item))
+                    }
+                }
 
                 // Create the text box.
                 do list.with_mut_ref |list| {
@@ -617,11 +865,18 @@ impl RenderBox {
                         text_run: ~text_box.run.serialize(),
                         range: text_box.range,
                         color: color,
+                        decorations: decorations,
+                        blur_radius: Au(0),
                     };
 
                     list.append_item(TextDisplayItemClass(text_display_item))
                 }
 
+                // Mark misspelled words, if applicable.
+                self.paint_spelling_errors_if_applicable(builder.ctx.dictionary,
+                                                         list,
+                                                         &absolute_box_bounds);
+
                 // Draw debug frames for text bounds.
                 //
                 // FIXME(pcwalton): This is a bit of an abuse of the logging infrastructure. We
@@ -670,7 +925,11 @@ impl RenderBox {
             GenericRenderBoxClass(_) => {
 
                 // Add the background to the list, if applicable.
-                self.paint_background_if_applicable(list, &absolute_box_bounds);
+                if !self.is_hidden_empty_cell() {
+                    self.paint_background_if_applicable(list, &absolute_box_bounds);
+                }
+                self.paint_widget_if_applicable(list, &absolute_box_bounds);
+                self.paint_canvas_if_applicable(builder, list, &absolute_box_bounds);
 
                 // FIXME(pcwalton): This is a bit of an abuse of the logging infrastructure. We
                 // should have a real `SERVO_DEBUG` system.
@@ -699,7 +958,11 @@ impl RenderBox {
                 // Add the background to the list, if applicable.
                 self.paint_background_if_applicable(list, &absolute_box_bounds);
 
-                match image_box.image.get_image() {
+                // The image is asked for at the size it's drawn at, which SVG images are
+                // rasterized at rather than scaled to.
+                let size = Size2D(absolute_box_bounds.size.width.to_nearest_px() as uint,
+                                  absolute_box_bounds.size.height.to_nearest_px() as uint);
+                match image_box.image.get_image_at_size(size) {
                     Some(image) => {
                         debug!("(building display list) building image box");
 
@@ -726,9 +989,12 @@ impl RenderBox {
         }
 
         // Add a border, if applicable.
-        //
-        // TODO: Outlines.
-        self.paint_borders_if_applicable(list, &absolute_box_bounds);
+        if !self.is_hidden_empty_cell() {
+            self.paint_borders_if_applicable(list, &absolute_box_bounds);
+        }
+
+        // Add an outline, if applicable.
+        self.paint_outline_if_applicable(list, &absolute_box_bounds);
     }
 
     /// Adds the display items necessary to paint the background of this render box to the display
@@ -742,17 +1008,354 @@ impl RenderBox {
         // doesn't have a render box".
         let nearest_ancestor_element = self.nearest_ancestor_element();
 
-        let background_color = nearest_ancestor_element.style().background_color();
-        if !background_color.alpha.approx_eq(&0.0) {
+        let background_color = nearest_ancestor_element.animated_color(BackgroundColorProperty);
+        if !background_color.a.approx_eq(&0.0) {
             do list.with_mut_ref |list| {
                 let solid_color_display_item = ~SolidColorDisplayItem {
                     base: BaseDisplayItem {
                         bounds: *absolute_bounds,
                         extra: ExtraDisplayListData::new(*self),
                     },
-                    color: background_color.to_gfx_color(),
+                    color: background_color,
+                };
+
+                list.append_item(SolidColorDisplayItemClass(solid_color_display_item))
+            }
+        }
+    }
+
+    /// Returns true if this is the box of a table cell with no content, whose `empty-cells` is
+    /// `hide`, so that it paints neither background nor borders. A cell whose only children are
+    /// white space is empty.
+    pub fn is_hidden_empty_cell(&self) -> bool {
+        let node = match *self {
+            GenericRenderBoxClass(generic_box) => generic_box.node,
+            _ => return false,
+        };
+        if !node.is_element() {
+            return false
+        }
+        match node.style().display(node.is_root()) {
+            CSSDisplayTableCell => {}
+            _ => return false,
+        }
+        let hides_empty = match node.try_computed_values() {
+            Ok(values) => values.empty_cells == empty_cells::hide,
+            Err(_) => false,
+        };
+        if !hides_empty || node.get_pseudo_element_values(Before).is_some() ||
+                node.get_pseudo_element_values(After).is_some() {
+            return false
+        }
+        do node.children().all |child| {
+            child.is_text() && do child.with_imm_text |text_node| {
+                text_node.parent.data.to_str().iter().all(|ch| ch.is_whitespace())
+            }
+        }
+    }
+
+    /// Returns the widget that this box paints, if it is a generic box for a form control.
+    pub fn widget(&self) -> Option<Widget> {
+        let node = match *self {
+            GenericRenderBoxClass(generic_box) => generic_box.node,
+            _ => return None,
+        };
+        if node.is_progress_element() {
+            Some(ProgressWidget(node.with_imm_progress_element(|progress| progress.position())))
+        } else if node.is_meter_element() {
+            do node.with_imm_meter_element |meter| {
+                Some(MeterWidget(meter.position(), meter.region()))
+            }
+        } else if node.is_input_element() {
+            do node.with_imm_input_element |input| {
+                match input.input_type() {
+                    RangeInput => Some(RangeWidget(input.range_position())),
+                    NumberInput => Some(NumberWidget),
+                    ColorInput => {
+                        let (red, green, blue) = input.color_value();
+                        Some(ColorWidget(red, green, blue))
+                    }
+                    TextInput | DateInput => None,
+                }
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Returns the size of the widget this box paints, which is 10em by 1em, except for color
+    /// wells, which are 2em by 1em.
+    pub fn widget_size(&self) -> Size2D<Au> {
+        let em = Au::from_pt(self.font_style().pt_size);
+        match self.widget() {
+            Some(ColorWidget(*)) => Size2D(em.scale_by(2.0), em),
+            _ => Size2D(em.scale_by(10.0), em),
+        }
+    }
+
+    /// Returns the size of the content of this box if it is a generic box that is sized by what
+    /// it paints rather than by its children: the widget of a form control, or the bitmap of a
+    /// canvas.
+    pub fn intrinsic_size(&self) -> Option<Size2D<Au>> {
+        let node = match *self {
+            GenericRenderBoxClass(generic_box) => generic_box.node,
+            _ => return None,
+        };
+        if node.is_canvas_element() {
+            do node.with_imm_canvas_element |canvas| {
+                Some(Size2D(Au::from_px(canvas.Width() as int),
+                            Au::from_px(canvas.Height() as int)))
+            }
+        } else if self.widget().is_some() {
+            Some(self.widget_size())
+        } else {
+            None
+        }
+    }
+
+    /// Adds the display items that draw the text and images drawn on the canvas of this box, if it
+    /// is a canvas, in a group so that nothing is drawn outside the canvas. The text is shaped
+    /// with the fonts that the text of the document is, so it shares their glyph caches.
+    fn paint_canvas_if_applicable<E:ExtraDisplayListData>(&self,
+                                                          builder: &DisplayListBuilder,
+                                                          list: &Cell<DisplayList<E>>,
+                                                          absolute_bounds: &Rect<Au>) {
+        let node = match *self {
+            GenericRenderBoxClass(generic_box) if generic_box.node.is_canvas_element() => {
+                generic_box.node
+            }
+            _ => return,
+        };
+
+        // Drawings are placed in pixels from the top left of the canvas.
+        let canvas_point = |x: f64, y: f64| {
+            Point2D(absolute_bounds.origin.x + Au::from_css_px(CSSPixel(x as float)),
+                    absolute_bounds.origin.y + Au::from_css_px(CSSPixel(y as float)))
+        };
+        let canvas_list = Cell::new(DisplayList::new());
+        do node.with_imm_canvas_element |canvas| {
+            for drawing in canvas.drawings.iter() {
+                match *drawing {
+                    TextDrawing(ref text) => {
+                        let font_ctx = &builder.ctx.font_ctx;
+                        let font_group = font_ctx.get_resolved_font_for_style(&text.font);
+                        let run = font_group.create_textrun(text.text.clone(),
+                                                            LeftToRight,
+                                                            TabSpaces(8),
+                                                            WordBreakNormal);
+                        let range = Range::new(0, run.char_len());
+                        let advance = run.metrics_for_range(&range).advance_width;
+                        let metrics = &run.font.metrics;
+                        let (ascent, descent) = (metrics.ascent, metrics.descent);
+                        let origin = text_origin(text.align, text.baseline, advance, ascent,
+                                                 descent);
+
+                        // Text display items are placed by the top of the font, not by the
+                        // baseline.
+                        let point = canvas_point(text.x, text.y);
+                        let bounds = Rect(Point2D(point.x + origin.x, point.y + origin.y - ascent),
+                                          Size2D(advance, ascent + descent));
+                        do canvas_list.with_mut_ref |canvas_list| {
+                            let text_display_item = ~TextDisplayItem {
+                                base: BaseDisplayItem {
+                                    bounds: bounds,
+                                    extra: ExtraDisplayListData::new(*self),
+                                },
+                                text_run: ~run.serialize(),
+                                range: range,
+                                color: text.color,
+                                decorations: TextDecorations::none(),
+                                blur_radius: Au(0),
+                            };
+                            canvas_list.append_item(TextDisplayItemClass(text_display_item))
+                        }
+                    }
+                    ImageDrawing(ref image, x, y) => {
+                        let size = Size2D(Au::from_px(image.get().width as int),
+                                          Au::from_px(image.get().height as int));
+                        do canvas_list.with_mut_ref |canvas_list| {
+                            let image_display_item = ~ImageDisplayItem {
+                                base: BaseDisplayItem {
+                                    bounds: Rect(canvas_point(x, y), size),
+                                    extra: ExtraDisplayListData::new(*self),
+                                },
+                                image: image.clone(),
+                            };
+                            canvas_list.append_item(ImageDisplayItemClass(image_display_item))
+                        }
+                    }
+                }
+            }
+        }
+
+        do list.with_mut_ref |list| {
+            let group_display_item = ~GroupDisplayItem {
+                base: BaseDisplayItem {
+                    bounds: *absolute_bounds,
+                    extra: ExtraDisplayListData::new(*self),
+                },
+                list: canvas_list.take(),
+                effects: GroupEffects::none(),
+            };
+            list.append_item(GroupDisplayItemClass(group_display_item))
+        }
+    }
+
+    /// Adds the display items that paint the widget of this box, if it has one: a track, and the
+    /// part of it that is filled, which starts at the right in right-to-left text. Sliders also
+    /// have a thumb at the end of the filled part.
+    pub fn paint_widget_if_applicable<E:ExtraDisplayListData>(&self,
+                                                              list: &Cell<DisplayList<E>>,
+                                                              absolute_bounds: &Rect<Au>) {
+        let (position, fill_color) = match self.widget() {
+            None => return,
+            Some(RangeWidget(position)) => {
+                self.paint_range_widget(list, absolute_bounds, position);
+                return
+            }
+            Some(NumberWidget) => {
+                self.paint_number_widget(list, absolute_bounds);
+                return
+            }
+            Some(ColorWidget(red, green, blue)) => {
+                // A swatch of the color, inset in a gray frame.
+                let inset = absolute_bounds.size.height.scale_by(0.2);
+                let swatch = Rect(Point2D(absolute_bounds.origin.x + inset,
+                                          absolute_bounds.origin.y + inset),
+                                  Size2D(absolute_bounds.size.width - inset * Au(2),
+                                         absolute_bounds.size.height - inset * Au(2)));
+                self.paint_widget_parts(list, ~[
+                    (*absolute_bounds, rgb(224, 224, 224)),
+                    (swatch, rgb(red, green, blue)),
+                ]);
+                return
+            }
+            Some(ProgressWidget(position)) => (position, rgb(48, 120, 208)),
+            Some(MeterWidget(position, OptimumRegion)) => (Some(position), rgb(40, 160, 40)),
+            Some(MeterWidget(position, SuboptimumRegion)) => (Some(position), rgb(224, 176, 0)),
+            Some(MeterWidget(position, EvenLessGoodRegion)) => (Some(position), rgb(208, 40, 40)),
+        };
+
+        let mut items = ~[(*absolute_bounds, rgb(224, 224, 224))];
+        match position {
+            Some(position) => {
+                let width = absolute_bounds.size.width.scale_by(position as float);
+                let x = match self.direction() {
+                    RightToLeft => absolute_bounds.origin.x + absolute_bounds.size.width - width,
+                    LeftToRight => absolute_bounds.origin.x,
                 };
+                items.push((Rect(Point2D(x, absolute_bounds.origin.y),
+                                 Size2D(width, absolute_bounds.size.height)),
+                            fill_color));
+            }
+            None => {
+                // FIXME: Indeterminate progress bars should be animated. For now they are striped,
+                // so that they can't be mistaken for empty ones.
+                let stripe = absolute_bounds.size.height;
+                let mut x = absolute_bounds.origin.x;
+                while x < absolute_bounds.origin.x + absolute_bounds.size.width {
+                    let width = geometry::min(stripe, absolute_bounds.origin.x +
+                                              absolute_bounds.size.width - x);
+                    items.push((Rect(Point2D(x, absolute_bounds.origin.y),
+                                     Size2D(width, absolute_bounds.size.height)),
+                                fill_color));
+                    x = x + stripe * Au(2);
+                }
+            }
+        }
+
+        self.paint_widget_parts(list, items);
+    }
+
+    /// Paints a slider: a thin track, filled up to the thumb, and a thumb that is half as wide as
+    /// the slider is high. The center of the thumb travels between the ends of the track.
+    ///
+    /// FIXME: The track and the thumb should be pseudo-elements that can be styled with CSS.
+    fn paint_range_widget<E:ExtraDisplayListData>(&self,
+                                                  list: &Cell<DisplayList<E>>,
+                                                  absolute_bounds: &Rect<Au>,
+                                                  position: f64) {
+        let height = absolute_bounds.size.height;
+        let thumb_width = height.scale_by(0.5);
+        let travel = absolute_bounds.size.width - thumb_width;
+        let thumb_x = match self.direction() {
+            LeftToRight => absolute_bounds.origin.x + travel.scale_by(position as float),
+            RightToLeft => absolute_bounds.origin.x + travel.scale_by(1.0 - position as float),
+        };
+        let thumb_center = thumb_x + thumb_width.scale_by(0.5);
+
+        let track_height = height.scale_by(0.25);
+        let track_y = absolute_bounds.origin.y + (height - track_height).scale_by(0.5);
+        let track = Rect(Point2D(absolute_bounds.origin.x, track_y),
+                         Size2D(absolute_bounds.size.width, track_height));
+        let filled = match self.direction() {
+            LeftToRight => Rect(track.origin, Size2D(thumb_center - track.origin.x, track_height)),
+            RightToLeft => {
+                Rect(Point2D(thumb_center, track_y),
+                     Size2D(track.origin.x + track.size.width - thumb_center, track_height))
+            }
+        };
+        let thumb = Rect(Point2D(thumb_x, absolute_bounds.origin.y), Size2D(thumb_width, height));
+
+        self.paint_widget_parts(list, ~[
+            (track, rgb(224, 224, 224)),
+            (filled, rgb(48, 120, 208)),
+            (thumb, rgb(96, 96, 96)),
+        ]);
+    }
 
+    /// Paints the spin buttons of a number field: two buttons at the inline end of the field,
+    /// half as wide as it is high, with the up button above the down button. Each has a bar
+    /// across it, and the up button also has one down its middle, so that they read as + and -.
+    fn paint_number_widget<E:ExtraDisplayListData>(&self,
+                                                   list: &Cell<DisplayList<E>>,
+                                                   absolute_bounds: &Rect<Au>) {
+        let height = absolute_bounds.size.height;
+        let button_width = height.scale_by(0.5);
+        let button_height = height.scale_by(0.5);
+        let button_x = match self.direction() {
+            LeftToRight => absolute_bounds.origin.x + absolute_bounds.size.width - button_width,
+            RightToLeft => absolute_bounds.origin.x,
+        };
+        let up = Rect(Point2D(button_x, absolute_bounds.origin.y),
+                      Size2D(button_width, button_height));
+        let down = Rect(Point2D(button_x, absolute_bounds.origin.y + button_height),
+                        Size2D(button_width, height - button_height));
+
+        let bar_thickness = button_height.scale_by(0.2);
+        let bar_length = button_width.scale_by(0.6);
+        let bar_across = |button: &Rect<Au>| -> Rect<Au> {
+            Rect(Point2D(button.origin.x + (button.size.width - bar_length).scale_by(0.5),
+                         button.origin.y + (button.size.height - bar_thickness).scale_by(0.5)),
+                 Size2D(bar_length, bar_thickness))
+        };
+        let bar_down_middle =
+            Rect(Point2D(up.origin.x + (button_width - bar_thickness).scale_by(0.5),
+                         up.origin.y + (button_height - bar_length).scale_by(0.5)),
+                 Size2D(bar_thickness, bar_length));
+
+        self.paint_widget_parts(list, ~[
+            (*absolute_bounds, rgb(255, 255, 255)),
+            (up, rgb(224, 224, 224)),
+            (down, rgb(208, 208, 208)),
+            (bar_across(&up), rgb(96, 96, 96)),
+            (bar_down_middle, rgb(96, 96, 96)),
+            (bar_across(&down), rgb(96, 96, 96)),
+        ]);
+    }
+
+    fn paint_widget_parts<E:ExtraDisplayListData>(&self,
+                                                  list: &Cell<DisplayList<E>>,
+                                                  parts: ~[(Rect<Au>, CSSColor)]) {
+        do list.with_mut_ref |list| {
+            for &(bounds, color) in parts.iter() {
+                let solid_color_display_item = ~SolidColorDisplayItem {
+                    base: BaseDisplayItem {
+                        bounds: bounds,
+                        extra: ExtraDisplayListData::new(*self),
+                    },
+                    color: color.to_gfx_color(),
+                };
                 list.append_item(SolidColorDisplayItemClass(solid_color_display_item))
             }
         }
@@ -770,99 +1373,149 @@ impl RenderBox {
 
     /// Converts this node's computed style to a font style used for rendering.
     pub fn font_style(&self) -> FontStyle {
-        let my_style = self.nearest_ancestor_element().style();
+        element_font_style(self.nearest_ancestor_element())
+    }
 
-        debug!("(font style) start: %?", self.nearest_ancestor_element().type_id());
+    /// Returns the text alignment of the computed style of the nearest ancestor-or-self `Element`
+    /// node.
+    pub fn text_align(&self) -> CSSTextAlign {
+        self.nearest_ancestor_element().style().text_align()
+    }
 
-        // FIXME: Too much allocation here.
-        let font_families = do my_style.font_family().map |family| {
-            match *family {
-                CSSFontFamilyFamilyName(ref family_str) => (*family_str).clone(),
-                CSSFontFamilyGenericFamily(Serif)       => ~"serif",
-                CSSFontFamilyGenericFamily(SansSerif)   => ~"sans-serif",
-                CSSFontFamilyGenericFamily(Cursive)     => ~"cursive",
-                CSSFontFamilyGenericFamily(Fantasy)     => ~"fantasy",
-                CSSFontFamilyGenericFamily(Monospace)   => ~"monospace",
-            }
-        };
-        let font_families = font_families.connect(", ");
-        debug!("(font style) font families: `%s`", font_families);
-
-        let font_size = match my_style.font_size() {
-            CSSFontSizeLength(Px(length)) => length,
-            // todo: this is based on a hard coded font size, should be the parent element's font size
-            CSSFontSizeLength(Em(length)) => length * 16f, 
-            _ => 16f // px units
-        };
-        debug!("(font style) font size: `%fpx`", font_size);
+    /// Returns true if the words in this box should be hyphenated automatically, as with
+    /// `hyphens: auto`.
+    pub fn hyphenates_automatically(&self) -> bool {
+        self.computed_values().hyphens == hyphens::auto
+    }
 
-        let (italic, oblique) = match my_style.font_style() {
-            CSSFontStyleNormal => (false, false),
-            CSSFontStyleItalic => (true, false),
-            CSSFontStyleOblique => (false, true),
-        };
+    /// Returns true if lines may be broken at the soft hyphens in the text of this box, which
+    /// `hyphens: none` forbids.
+    pub fn breaks_at_soft_hyphens(&self) -> bool {
+        self.computed_values().hyphens != hyphens::none
+    }
 
-        FontStyle {
-            pt_size: font_size,
-            weight: FontWeight300,
-            italic: italic,
-            oblique: oblique,
-            families: font_families,
+    /// Returns the distance between tab stops in the text of this box.
+    pub fn tab_size(&self) -> TabSize {
+        match self.computed_values().tab_size {
+            tab_size::Spaces(spaces) => TabSpaces(spaces as uint),
+            tab_size::Length(length) => TabLength(to_au(length)),
         }
     }
 
-    /// Returns the text alignment of the computed style of the nearest ancestor-or-self `Element`
-    /// node.
-    pub fn text_align(&self) -> CSSTextAlign {
-        self.nearest_ancestor_element().style().text_align()
+    /// Returns the `word-break` of the text of this box.
+    pub fn word_break(&self) -> WordBreak {
+        match self.computed_values().word_break {
+            word_break::normal => WordBreakNormal,
+            word_break::break_all => WordBreakBreakAll,
+            word_break::keep_all => WordBreakKeepAll,
+        }
     }
 
-    pub fn line_height(&self) -> CSSLineHeight {
-        self.nearest_ancestor_element().style().line_height()
+    /// Returns true if a word too long to fit on a line of its own may be broken between any two
+    /// of its characters, as with `overflow-wrap: break-word`.
+    pub fn breaks_long_words(&self) -> bool {
+        self.computed_values().overflow_wrap == overflow_wrap::break_word
+    }
+
+    /// Returns the `white-space` of the text of this box.
+    pub fn white_space(&self) -> WhiteSpace {
+        match self.computed_values().white_space {
+            white_space::normal => WhiteSpaceNormal,
+            white_space::pre => WhiteSpacePre,
+            white_space::nowrap => WhiteSpaceNowrap,
+            white_space::pre_wrap => WhiteSpacePreWrap,
+            white_space::pre_line => WhiteSpacePreLine,
+        }
+    }
+
+    /// Returns the `text-transform` to apply to the text of this box.
+    pub fn text_transform(&self) -> TextTransform {
+        match self.computed_values().text_transform {
+            text_transform::none => TextTransformNone,
+            text_transform::capitalize => TextTransformCapitalize,
+            text_transform::uppercase => TextTransformUppercase,
+            text_transform::lowercase => TextTransformLowercase,
+        }
+    }
+
+    /// Returns the directionality of the element this box belongs to, which is the base direction
+    /// of the text in it.
+    pub fn direction(&self) -> TextDirection {
+        self.nearest_ancestor_element().directionality()
+    }
+
+    pub fn line_height(&self) -> line_height::ComputedValue {
+        self.computed_values().line_height
     }
 
     pub fn vertical_align(&self) -> CSSVerticalAlign {
         self.nearest_ancestor_element().style().vertical_align()
     }
 
-    /// Returns the text decoration of the computed style of the nearest `Element` node
-    pub fn text_decoration(&self) -> CSSTextDecoration {
-        /// Computes the propagated value of text-decoration, as specified in CSS 2.1 § 16.3.1
-        /// TODO: make sure this works with anonymous box generation.
-        fn get_propagated_text_decoration(element: AbstractNode<LayoutView>) -> CSSTextDecoration {
-            //Skip over non-element nodes in the DOM
-            if(!element.is_element()){
-                return match element.parent_node() {
-                    None => CSSTextDecorationNone,
-                    Some(parent) => get_propagated_text_decoration(parent),
-                };
+    /// Returns the shadows that `text-shadow` casts from the text of this box, from the one
+    /// painted on top to the one painted at the bottom.
+    pub fn text_shadows(&self) -> ~[TextShadow] {
+        let values = self.computed_values();
+        do values.text_shadow.iter().map |shadow| {
+            TextShadow {
+                offset: Point2D(to_au(shadow.offset_x), to_au(shadow.offset_y)),
+                blur_radius: to_au(shadow.blur_radius),
+                color: computed::used_CSSColor(shadow.color, values.color).to_gfx_color(),
             }
+        }.collect()
+    }
 
-            //FIXME: is the root param on display() important?
+    /// Returns the lines that `text-decoration` draws along the text of this box. Decorations
+    /// propagate from an element to the text of its in-flow descendants, as specified in CSS 2.1
+    /// § 16.3.1, and each line is drawn in the `text-decoration-color` of the element that
+    /// declared it, which is its `color` unless it is given.
+    /// TODO: make sure this works with anonymous box generation.
+    pub fn text_decorations(&self) -> TextDecorations {
+        let mut decorations = TextDecorations::none();
+        let mut current = Some(self.nearest_ancestor_element());
+        while current.is_some() {
+            let element = current.unwrap();
+            current = element.parent_node();
+
+            // Skip over non-element nodes in the DOM.
+            if !element.is_element() {
+                loop
+            }
+
+            // Where an element and its ancestor declare the same decoration, the lines coincide,
+            // and the element's own line is the one that shows.
+            let values = element.computed_values();
+            let color = Some(match values.text_decoration_color {
+                CurrentColor => element.animated_color(ColorProperty),
+                color => computed::used_CSSColor(color, values.color).to_gfx_color(),
+            });
+            match element.style().text_decoration() {
+                CSSTextDecorationUnderline if decorations.underline.is_none() => {
+                    decorations.underline = color
+                }
+                CSSTextDecorationOverline if decorations.overline.is_none() => {
+                    decorations.overline = color
+                }
+                CSSTextDecorationLineThrough if decorations.line_through.is_none() => {
+                    decorations.line_through = color
+                }
+                _ => {}
+            }
+
+            // Decorations aren't propagated into floats, absolutely positioned elements, inline
+            // blocks, or inline tables.
+            // FIXME: is the root param on display() important?
             let display_in_flow = match element.style().display(false) {
                 CSSDisplayInlineTable | CSSDisplayInlineBlock => false,
                 _ => true,
             };
-
-            let position = element.style().position();
-            let float = element.style().float();
-
-            let in_flow = (position == CSSPositionStatic) && (float == CSSFloatNone) &&
-                display_in_flow;
-
-            let text_decoration = element.style().text_decoration();
-
-            if(text_decoration == CSSTextDecorationNone && in_flow){
-                match element.parent_node() {
-                    None => CSSTextDecorationNone,
-                    Some(parent) => get_propagated_text_decoration(parent),
-                }
-            }
-            else {
-                text_decoration
+            let in_flow = element.style().position() == CSSPositionStatic &&
+                element.style().float() == CSSFloatNone && display_in_flow;
+            if !in_flow {
+                break
             }
         }
-        get_propagated_text_decoration(self.nearest_ancestor_element())
+        decorations
     }
 
     /// Dumps this node, for debugging.
@@ -887,8 +1540,9 @@ impl RenderBox {
             GenericRenderBoxClass(*) => ~"GenericRenderBox",
             ImageRenderBoxClass(*) => ~"ImageRenderBox",
             TextRenderBoxClass(text_box) => {
-                fmt!("TextRenderBox(text=%s)", text_box.run.text.slice_chars(text_box.range.begin(),
-                                                                             text_box.range.end()))
+                let text = text_box.run.text.as_slice();
+                fmt!("TextRenderBox(text=%s)",
+                     text.slice_chars(text_box.range.begin(), text_box.range.end()))
             }
             UnscannedTextRenderBoxClass(text_box) => {
                 fmt!("UnscannedTextRenderBox(%s)", text_box.text)
@@ -915,10 +1569,11 @@ impl RenderBox {
             return
         }
 
-        let top_color = self.style().border_top_color();
-        let right_color = self.style().border_right_color();
-        let bottom_color = self.style().border_bottom_color();
-        let left_color = self.style().border_left_color();
+        let node = self.node();
+        let top_color = node.animated_color(BorderTopColorProperty);
+        let right_color = node.animated_color(BorderRightColorProperty);
+        let bottom_color = node.animated_color(BorderBottomColorProperty);
+        let left_color = node.animated_color(BorderLeftColorProperty);
 
         // Append the border to the display list.
         do list.with_mut_ref |list| {
@@ -931,13 +1586,407 @@ impl RenderBox {
                                            border.right,
                                            border.bottom,
                                            border.left),
-                color: SideOffsets2D::new(top_color.to_gfx_color(),
-                                        right_color.to_gfx_color(),
-                                        bottom_color.to_gfx_color(),
-                                        left_color.to_gfx_color())
+                color: SideOffsets2D::new(top_color, right_color, bottom_color, left_color)
             };
 
             list.append_item(BorderDisplayItemClass(border_display_item))
         }
     }
+
+    /// Adds wavy red lines beneath the misspelled words of this text box, if its text is editable
+    /// and its spelling is checked.
+    ///
+    /// FIXME: This assumes that the text runs left to right.
+    pub fn paint_spelling_errors_if_applicable<E:ExtraDisplayListData>(
+            &self,
+            dictionary: Option<@Dictionary>,
+            list: &Cell<DisplayList<E>>,
+            abs_bounds: &Rect<Au>) {
+        let (dictionary, text_box) = match (dictionary, *self) {
+            (Some(dictionary), TextRenderBoxClass(text_box)) => (dictionary, text_box),
+            _ => return,
+        };
+        let element = self.nearest_ancestor_element();
+        if !element.is_editable() || !element.is_spellchecked() {
+            return
+        }
+
+        let run = text_box.run;
+        let range = text_box.range;
+        let text: ~str = run.text.as_slice().iter().skip(range.begin()).take(range.length())
+                                                   .collect();
+        let baseline = abs_bounds.origin.y + run.font.metrics.ascent;
+        for word in dictionary.misspelled_words(text).iter() {
+            let start = run.advance_for_range(&Range::new(range.begin(), word.begin()));
+            let width = run.advance_for_range(&Range::new(range.begin() + word.begin(),
+                                                          word.length()));
+            let bounds = Rect(Point2D(abs_bounds.origin.x + start, baseline + Au::from_px(1)),
+                              Size2D(width, Au::from_px(3)));
+            do list.with_mut_ref |list| {
+                let line_display_item = ~WavyLineDisplayItem {
+                    base: BaseDisplayItem {
+                        bounds: bounds,
+                        extra: ExtraDisplayListData::new(*self),
+                    },
+                    color: rgb(255, 0, 0).to_gfx_color(),
+                };
+                list.append_item(WavyLineDisplayItemClass(line_display_item))
+            }
+        }
+    }
+
+    /// Returns the effects that apply to the rendering of this box as a whole, whose bounds
+    /// relative to the display list root flow are `absolute_bounds`, or `None` if the box
+    /// isn't drawn at all. As CSS Masking requires, a box whose mask image hasn't loaded is masked
+    /// by a transparent image, so it isn't drawn until the image loads.
+    ///
+    /// FIXME: The effects of an element should apply to the boxes of its descendants as well, once
+    /// display lists are built per stacking context.
+    pub fn group_effects(&self, ctx: &LayoutContext, absolute_bounds: &Rect<Au>)
+                         -> Option<GroupEffects> {
+        let mask = match self.mask_image() {
+            Some(url) => {
+                let mut image = ImageHolder::new(url, ctx.image_cache);
+                match image.get_image() {
+                    // The image is drawn at its own size from the top left of the border box, and
+                    // repeated across it, as the initial `mask-size`, `mask-position` and
+                    // `mask-repeat` place it.
+                    Some(image) => {
+                        let size = Size2D(Au::from_px(image.get().width as int),
+                                          Au::from_px(image.get().height as int));
+                        Some(Mask {
+                            image: image,
+                            mode: self.mask_mode(),
+                            tile: Rect(absolute_bounds.origin, size),
+                        })
+                    }
+                    None => return None,
+                }
+            }
+            None => None,
+        };
+        Some(GroupEffects {
+            mask: mask,
+            backdrop_filters: self.backdrop_filters(),
+            clip: false,
+        })
+    }
+
+    /// Returns the URL of the `mask-image` of this box, if it has one.
+    pub fn mask_image(&self) -> Option<Url> {
+        // The stylist has resolved the URL already.
+        let element = self.nearest_ancestor_element();
+        element.computed_values().mask_image.map(|url| make_url(url.clone(), None))
+    }
+
+    /// Returns the kinds of containment that the `contain` property of this box gives it, along
+    /// with those that its `content-visibility` implies.
+    pub fn containment(&self) -> Containment {
+        let element = self.nearest_ancestor_element();
+        let contain = element.computed_values().contain;
+        let mut containment = Containment {
+            size: contain.size,
+            layout: contain.layout,
+            paint: contain.paint,
+        };
+        match self.content_visibility() {
+            ContentVisibilityVisible => {}
+            ContentVisibilityAuto | ContentVisibilityHidden => {
+                containment.layout = true;
+                containment.paint = true;
+                if self.skipped_contents_size().is_some() {
+                    containment.size = true
+                }
+            }
+        }
+        containment
+    }
+
+    pub fn content_visibility(&self) -> ContentVisibility {
+        let element = self.nearest_ancestor_element();
+        match element.computed_values().content_visibility {
+            content_visibility::visible => ContentVisibilityVisible,
+            content_visibility::auto => ContentVisibilityAuto,
+            content_visibility::hidden => ContentVisibilityHidden,
+        }
+    }
+
+    /// Returns the size that the `contain-intrinsic-size` of this box gives its content box when
+    /// it has size containment, if it gives one.
+    pub fn contain_intrinsic_size(&self) -> Option<Size2D<Au>> {
+        let element = self.nearest_ancestor_element();
+        do element.computed_values().contain_intrinsic_size.map |&(width, height)| {
+            Size2D(to_au(width), to_au(height))
+        }
+    }
+
+    /// Returns the size that stands in for the contents of this box, if they are skipped because
+    /// of its `content-visibility`.
+    pub fn skipped_contents_size(&self) -> Option<Size2D<Au>> {
+        self.node().read_layout_data(|data| data.skipped_contents_size)
+    }
+
+    /// Returns the axes and strictness of the scroll snapping that the `scroll-snap-type` of this
+    /// box asks for.
+    pub fn scroll_snap_type(&self) -> ScrollSnapType {
+        let element = self.nearest_ancestor_element();
+        let snap_type = element.computed_values().scroll_snap_type;
+        ScrollSnapType {
+            x: snap_type.x,
+            y: snap_type.y,
+            strictness: if snap_type.mandatory { MandatorySnap } else { ProximitySnap },
+        }
+    }
+
+    /// Returns where the `scroll-snap-align` of this box aligns it in the viewport when scrolling
+    /// snaps to it, in the block and inline axes.
+    pub fn scroll_snap_align(&self) -> (ScrollSnapAlign, ScrollSnapAlign) {
+        let to_align = |alignment: scroll_snap_align::Alignment| {
+            match alignment {
+                scroll_snap_align::none => ScrollSnapAlignNone,
+                scroll_snap_align::start => ScrollSnapAlignStart,
+                scroll_snap_align::end => ScrollSnapAlignEnd,
+                scroll_snap_align::center => ScrollSnapAlignCenter,
+            }
+        };
+        let element = self.nearest_ancestor_element();
+        let (block, inline) = element.computed_values().scroll_snap_align;
+        (to_align(block), to_align(inline))
+    }
+
+    /// Returns whether the `scroll-behavior` of this box is `smooth`.
+    pub fn smooth_scroll(&self) -> bool {
+        let element = self.nearest_ancestor_element();
+        element.computed_values().scroll_behavior == scroll_behavior::smooth
+    }
+
+    /// Returns what the `overscroll-behavior` of this box says happens when scrolling it reaches
+    /// the edge of the scrolled content, horizontally and vertically.
+    pub fn overscroll_behavior(&self) -> (OverscrollBehavior, OverscrollBehavior) {
+        let element = self.nearest_ancestor_element();
+        let values = element.computed_values();
+        let x = match values.overscroll_behavior_x {
+            overscroll_behavior_x::auto => OverscrollAuto,
+            overscroll_behavior_x::contain => OverscrollContain,
+            overscroll_behavior_x::none => OverscrollNone,
+        };
+        let y = match values.overscroll_behavior_y {
+            overscroll_behavior_y::auto => OverscrollAuto,
+            overscroll_behavior_y::contain => OverscrollContain,
+            overscroll_behavior_y::none => OverscrollNone,
+        };
+        (x, y)
+    }
+
+    /// Returns the touch gestures that the `touch-action` of this box leaves to the compositor.
+    pub fn touch_action(&self) -> TouchAction {
+        let element = self.nearest_ancestor_element();
+        let touch_action = element.computed_values().touch_action;
+        TouchAction {
+            pan_x: touch_action.pan_x,
+            pan_y: touch_action.pan_y,
+            pinch_zoom: touch_action.pinch_zoom,
+        }
+    }
+
+    /// Returns how thick the `scrollbar-width` of this box makes its scrollbars.
+    pub fn scrollbar_width(&self) -> ScrollbarWidth {
+        let element = self.nearest_ancestor_element();
+        match element.computed_values().scrollbar_width {
+            scrollbar_width::auto => AutoScrollbarWidth,
+            scrollbar_width::thin => ThinScrollbarWidth,
+            scrollbar_width::none => NoScrollbars,
+        }
+    }
+
+    /// Returns the colors of the thumb and of the track of the scrollbars of this box, as its
+    /// `scrollbar-color` gives them, or `None` for the default colors.
+    pub fn scrollbar_colors(&self) -> Option<(Color, Color)> {
+        let element = self.nearest_ancestor_element();
+        let values = element.computed_values();
+        do values.scrollbar_color.map |&(thumb, track)| {
+            (computed::used_CSSColor(thumb, values.color).to_gfx_color(),
+             computed::used_CSSColor(track, values.color).to_gfx_color())
+        }
+    }
+
+    /// Returns the filters that the `backdrop-filter` of this box applies to what is beneath it.
+    pub fn backdrop_filters(&self) -> ~[Filter] {
+        let element = self.nearest_ancestor_element();
+        do element.computed_values().backdrop_filter.iter().map |filter| {
+            match *filter {
+                backdrop_filter::Blur(radius) => Blur(to_au(radius)),
+                backdrop_filter::Brightness(amount) => Brightness(amount as f32),
+                backdrop_filter::Contrast(amount) => Contrast(amount as f32),
+                backdrop_filter::Grayscale(amount) => Grayscale(amount as f32),
+                backdrop_filter::HueRotate(angle) => HueRotate(angle as f32),
+                backdrop_filter::Invert(amount) => Invert(amount as f32),
+                backdrop_filter::Opacity(amount) => Opacity(amount as f32),
+                backdrop_filter::Saturate(amount) => Saturate(amount as f32),
+                backdrop_filter::Sepia(amount) => Sepia(amount as f32),
+            }
+        }.collect()
+    }
+
+    /// Returns whether the mask image of this box masks it by its alpha or its luminance.
+    pub fn mask_mode(&self) -> MaskMode {
+        let element = self.nearest_ancestor_element();
+        match element.computed_values().mask_mode {
+            // Mask images are images rather than SVG `mask` elements, which `match-source` masks
+            // by their alpha.
+            mask_mode::match_source | mask_mode::alpha => AlphaMask,
+            mask_mode::luminance => LuminanceMask,
+        }
+    }
+
+    /// Returns the width and color of the outline of this box, if it has one.
+    ///
+    /// FIXME: Outlines are painted solid, since dotted and dashed lines can't be painted yet.
+    pub fn outline(&self) -> Option<(Au, Color)> {
+        let element = match self.outlined_element() {
+            Some(element) => element,
+            None => return None,
+        };
+        let values = element.computed_values();
+        let width = to_au(values.outline_width);
+        if values.outline_style == outline_style::none || width == Au(0) {
+            return None
+        }
+        Some((width, computed::used_CSSColor(values.outline_color, values.color).to_gfx_color()))
+    }
+
+    /// Returns the element whose outline this box paints, if it has one: either the element of
+    /// its own box, or, for an inline element, which has no box of its own, the element of one of
+    /// the text boxes of its content.
+    fn outlined_element(&self) -> Option<AbstractNode<LayoutView>> {
+        match *self {
+            TextRenderBoxClass(*) => {
+                let mut node = self.node().parent_node();
+                while node.is_some() {
+                    let element = node.unwrap();
+                    if element.is_element() {
+                        match element.style().display(false) {
+                            CSSDisplayInline => {}
+                            _ => return None,
+                        }
+                        if element.computed_values().outline_style != outline_style::none {
+                            return Some(element)
+                        }
+                    }
+                    node = element.parent_node();
+                }
+                None
+            }
+            _ if self.node().is_element() => Some(self.node()),
+            _ => None,
+        }
+    }
+
+    /// Adds the display items necessary to paint the outline of this render box, which is drawn
+    /// just outside its border box.
+    pub fn paint_outline_if_applicable<E:ExtraDisplayListData>(&self,
+                                                               list: &Cell<DisplayList<E>>,
+                                                               abs_bounds: &Rect<Au>) {
+        let (width, color) = match self.outline() {
+            Some(outline) => outline,
+            None => return,
+        };
+        let bounds = Rect(Point2D(abs_bounds.origin.x - width, abs_bounds.origin.y - width),
+                          Size2D(abs_bounds.size.width + width * Au(2),
+                                 abs_bounds.size.height + width * Au(2)));
+        do list.with_mut_ref |list| {
+            let border_display_item = ~BorderDisplayItem {
+                base: BaseDisplayItem {
+                    bounds: bounds,
+                    extra: ExtraDisplayListData::new(*self),
+                },
+                border: SideOffsets2D::new_all_same(width),
+                color: SideOffsets2D::new_all_same(color),
+            };
+            list.append_item(BorderDisplayItemClass(border_display_item))
+        }
+    }
+}
+
+/// Converts the computed style of an element to a font style used for rendering.
+fn font_weight_of(weight: font_weight::ComputedValue) -> CSSFontWeight {
+    match weight {
+        font_weight::Weight100 => FontWeight100,
+        font_weight::Weight200 => FontWeight200,
+        font_weight::Weight300 => FontWeight300,
+        font_weight::Weight400 => FontWeight400,
+        font_weight::Weight500 => FontWeight500,
+        font_weight::Weight600 => FontWeight600,
+        font_weight::Weight700 => FontWeight700,
+        font_weight::Weight800 => FontWeight800,
+        font_weight::Weight900 => FontWeight900,
+    }
+}
+
+fn font_stretch_of(stretch: font_stretch::ComputedValue) -> CSSFontStretch {
+    match stretch {
+        font_stretch::ultra_condensed => FontStretchUltraCondensed,
+        font_stretch::extra_condensed => FontStretchExtraCondensed,
+        font_stretch::condensed => FontStretchCondensed,
+        font_stretch::semi_condensed => FontStretchSemiCondensed,
+        font_stretch::normal => FontStretchNormal,
+        font_stretch::semi_expanded => FontStretchSemiExpanded,
+        font_stretch::expanded => FontStretchExpanded,
+        font_stretch::extra_expanded => FontStretchExtraExpanded,
+        font_stretch::ultra_expanded => FontStretchUltraExpanded,
+    }
+}
+
+pub fn element_font_style(element: AbstractNode<LayoutView>) -> FontStyle {
+    debug!("(font style) start: %?", element.type_id());
+
+    let values = element.computed_values();
+
+    // `font-kerning: none` turns `kern` off, and the feature settings come after it so that they
+    // can turn it back on.
+    let mut features = ~[];
+    if values.font_kerning == font_kerning::none {
+        features.push(FontFeature::new("kern", 0).unwrap());
+    }
+    match values.font_feature_settings {
+        font_feature_settings::normal => {}
+        font_feature_settings::FeatureSettings(ref settings) => {
+            for &(ref tag, value) in settings.iter() {
+                for feature in FontFeature::new(tag.as_slice(), value).iter() {
+                    features.push(feature.clone());
+                }
+            }
+        }
+    }
+
+    let mut style = font_style_of(&values.font_values());
+    style.lang = element.language();
+    style.small_caps = values.font_variant == font_variant::small_caps;
+    style.features = features;
+    style
+}
+
+/// Returns the font style that the values of the font longhands select, for text in no language
+/// in particular, without small capitals and features.
+pub fn font_style_of(font: &FontValues) -> FontStyle {
+    // FIXME: Too much allocation here.
+    let families: ~[&str] = do font.family.iter().map |family| {
+        match *family {
+            font_family::FamilyName(ref name) => name.as_slice(),
+        }
+    }.collect();
+    let font_families = families.connect(", ");
+    debug!("(font style) font families: `%s`", font_families);
+
+    FontStyle {
+        pt_size: *to_au(font.size).to_css_px(),
+        weight: font_weight_of(font.weight),
+        stretch: font_stretch_of(font.stretch),
+        italic: font.style == font_style::italic,
+        oblique: font.style == font_style::oblique,
+        families: font_families,
+        lang: None,
+        small_caps: false,
+        features: ~[],
+    }
 }
@@ -16,6 +16,7 @@ use layout::flow::{FlowContextType, FlowData, InlineBlockFlow, InlineFlow, Table
 use layout::inline::{InlineFlowData, InlineLayout};
 use layout::text::TextRunScanner;
 use css::node_style::StyledNode;
+use css::node_util::NodeUtil;
 
 use newcss::values::{CSSDisplay, CSSDisplayBlock, CSSDisplayInline, CSSDisplayInlineBlock};
 use newcss::values::{CSSDisplayTable, CSSDisplayInlineTable, CSSDisplayListItem};
@@ -27,7 +28,11 @@ use newcss::values::{CSSFloatNone, CSSFloatLeft, CSSFloatRight};
 use layout::float_context::{FloatLeft, FloatRight};
 use script::dom::element::*;
 use script::dom::node::{AbstractNode, CommentNodeTypeId, DoctypeNodeTypeId};
+use script::dom::node::DocumentFragmentNodeTypeId;
 use script::dom::node::{ElementNodeTypeId, LayoutView, TextNodeTypeId};
+use script::style::generated_content::{GeneratedContentState, GeneratedImage, GeneratedText};
+use script::style::properties::longhands::{caption_side, content, list_style_type};
+use script::style::selectors::{After, Before, PseudoElement};
 use servo_util::range::Range;
 use servo_util::tree::{TreeNodeRef, TreeNode};
 use std::cell::Cell;
@@ -35,6 +40,12 @@ use std::cell::Cell;
 pub struct LayoutTreeBuilder {
     next_cid: int,
     next_bid: int,
+    /// What the content generated so far leaves for the content generated after it, such as how
+    /// deeply quotes are nested.
+    generated_content: GeneratedContentState,
+    /// How many list items have been seen among the children of each element that is being
+    /// built, innermost last, which numbers their markers.
+    list_item_counts: ~[int],
 }
 
 impl LayoutTreeBuilder {
@@ -42,6 +53,8 @@ impl LayoutTreeBuilder {
         LayoutTreeBuilder {
             next_cid: -1,
             next_bid: -1,
+            generated_content: GeneratedContentState::new(),
+            list_item_counts: ~[0],
         }
     }
 }
@@ -73,7 +86,7 @@ fn simulate_UA_display_rules(node: AbstractNode<LayoutView>) -> CSSDisplay {
     }
 
     match node.type_id() {
-        DoctypeNodeTypeId | CommentNodeTypeId => CSSDisplayNone,
+        DoctypeNodeTypeId | CommentNodeTypeId | DocumentFragmentNodeTypeId => CSSDisplayNone,
         TextNodeTypeId => CSSDisplayInline,
         ElementNodeTypeId(element_type_id) => {
             match element_type_id {
@@ -220,6 +233,15 @@ impl<'self> BoxGenerator<'self> {
         }
     }
 
+    /// Adds the boxes of content that a pseudo-element of the node being built generates to the
+    /// inline flow of this generator.
+    fn push_generated_boxes(&mut self, boxes: ~[RenderBox]) {
+        match *self.flow {
+            InlineFlow(ref mut inline) => inline.boxes.push_all_move(boxes),
+            _ => fail!(~"generated content must go into an inline flow"),
+        }
+    }
+
     /// Disambiguate between different methods here instead of inlining, since each case has very
     /// different complexity.
     fn make_box(layout_ctx: &LayoutContext,
@@ -275,6 +297,51 @@ impl<'self> BoxGenerator<'self> {
 
 }
 
+/// Returns the nodes that generate boxes as children of `node`: its children, except that elements
+/// with `display: contents` are replaced by the nodes that generate boxes as their children.
+/// Replaced elements, such as images, have no children to show that way, so `display: contents`
+/// hides them as `display: none` does.
+fn box_tree_children(node: AbstractNode<LayoutView>) -> ~[AbstractNode<LayoutView>] {
+    let mut children = ~[];
+    for child in node.children() {
+        if !child.displays_contents() {
+            children.push(child)
+        } else if !(child.is_image_element() || child.is_iframe_element() ||
+                    child.is_media_element() || child.is_input_element()) {
+            children.push_all_move(box_tree_children(child))
+        }
+    }
+    children
+}
+
+/// Returns true if `node` is an element that displays as a table.
+fn is_table(node: AbstractNode<LayoutView>) -> bool {
+    if !node.is_element() {
+        return false
+    }
+    match node.style().display(node.is_root()) {
+        CSSDisplayTable | CSSDisplayInlineTable => true,
+        _ => false,
+    }
+}
+
+/// Moves the captions among the children of a table whose `caption-side` is `bottom` after the
+/// other children, keeping their order, since tables are laid out as blocks.
+fn captions_in_place(children: ~[AbstractNode<LayoutView>]) -> ~[AbstractNode<LayoutView>] {
+    let (bottom_captions, others) = do children.partition |&child| {
+        child.try_style().is_ok() &&
+            match (child.style().display(false), child.try_computed_values()) {
+                (CSSDisplayTableCaption, Ok(values)) => {
+                    values.caption_side == caption_side::bottom
+                }
+                _ => false,
+            }
+    };
+    let mut children = others;
+    children.push_all_move(bottom_captions);
+    children
+}
+
 enum BoxGenResult<'self> {
     NoGenerator,
     ParentGenerator,
@@ -359,9 +426,39 @@ impl LayoutTreeBuilder {
         this_generator.push_node(layout_ctx, cur_node, self);
         debug!("point b: %s", cur_node.debug_str());
 
-        // recurse on child nodes.
-        let prev_gen_cell = Cell::new(Normal(None));
-        for child_node in cur_node.children() {
+        // The content of `::before` goes before the boxes of the children, after the marker if
+        // the node is a list item: in the inline flow of the node if it is inline, or else in an
+        // inline flow of its own, which the inline children that follow continue.
+        let mut before_boxes = self.make_marker_boxes(layout_ctx, cur_node);
+        before_boxes.push_all_move(self.make_generated_boxes(layout_ctx, cur_node, Before));
+        let mut before_generator = None;
+        if !before_boxes.is_empty() {
+            if this_generator.flow.starts_inline_flow() {
+                this_generator.push_generated_boxes(before_boxes)
+            } else {
+                let boxes = Cell::new(before_boxes);
+                before_generator = do this_generator.with_clone |parent_clone| {
+                    Some(self.create_generated_content_generator(cur_node,
+                                                                 parent_clone,
+                                                                 boxes.take()))
+                };
+            }
+        }
+
+        // recurse on child nodes. The children of widgets are only fallback content for browsers
+        // that don't support them, so they get no boxes, and neither do skipped contents.
+        let is_widget = cur_node.is_progress_element() || cur_node.is_meter_element();
+        let is_skipped = cur_node.read_layout_data(|data| data.skipped_contents_size.is_some());
+        let prev_gen_cell = Cell::new(Normal(before_generator));
+        let children = if is_widget || is_skipped {
+            ~[]
+        } else if is_table(cur_node) {
+            captions_in_place(box_tree_children(cur_node))
+        } else {
+            box_tree_children(cur_node)
+        };
+        self.list_item_counts.push(0);
+        for &child_node in children.iter() {
             do parent_generator.with_clone |grandparent_clone| {
                 let grandparent_clone_cell = Cell::new(Some(grandparent_clone));
                 do this_generator.with_clone |parent_clone| {
@@ -387,6 +484,38 @@ impl LayoutTreeBuilder {
             }
         }
 
+        self.list_item_counts.pop();
+
+        // The content of `::after` goes after them, continuing the inline flow of the last child
+        // if there is one.
+        let after_boxes = self.make_generated_boxes(layout_ctx, cur_node, After);
+        if !after_boxes.is_empty() {
+            if this_generator.flow.starts_inline_flow() {
+                this_generator.push_generated_boxes(after_boxes)
+            } else {
+                let last_inline_generator = match prev_gen_cell.take() {
+                    Normal(Some(prev_gen)) => {
+                        if prev_gen.flow.starts_inline_flow() { Some(prev_gen) } else { None }
+                    }
+                    _ => None,
+                };
+                match last_inline_generator {
+                    Some(generator) => {
+                        let mut generator = generator;
+                        generator.push_generated_boxes(after_boxes)
+                    }
+                    None => {
+                        let boxes = Cell::new(after_boxes);
+                        do this_generator.with_clone |parent_clone| {
+                            self.create_generated_content_generator(cur_node,
+                                                                    parent_clone,
+                                                                    boxes.take());
+                        }
+                    }
+                }
+            }
+        }
+
         this_generator.pop_node(layout_ctx, cur_node);
         self.simplify_children_of_flow(layout_ctx, this_generator.flow);
 
@@ -411,6 +540,14 @@ impl LayoutTreeBuilder {
                                       mut sibling_generator: Option<&mut BoxGenerator<'a>>)
                                       -> BoxGenResult<'a> {
 
+        // An element that hasn't been styled by both style systems, which shouldn't happen, gets
+        // no boxes, rather than taking layout down. The boxes that are built may then take their
+        // styles for granted.
+        if node.is_element() && (node.try_style().is_err() || node.try_computed_values().is_err()) {
+            debug!("box_builder: skipping an element without a style");
+            return NoGenerator
+        }
+
         let display = if node.is_element() {
             match node.style().display(node.is_root()) {
                 CSSDisplayNone => return NoGenerator, // tree ends here if 'display: none'
@@ -435,7 +572,9 @@ impl LayoutTreeBuilder {
 
                 ElementNodeTypeId(_) => CSSDisplayInline,
                 TextNodeTypeId => CSSDisplayInline,
-                DoctypeNodeTypeId | CommentNodeTypeId => return NoGenerator,
+                DoctypeNodeTypeId | CommentNodeTypeId | DocumentFragmentNodeTypeId => {
+                    return NoGenerator
+                }
             }
         };
 
@@ -542,6 +681,99 @@ impl LayoutTreeBuilder {
         NewGenerator(new_generator)
     }
 
+    /// Returns the boxes of the content that a pseudo-element of `node` generates, if it is an
+    /// element that has any. The content must be generated in document order, since what comes
+    /// before it changes it: quotes nest.
+    ///
+    /// FIXME: The properties that libcss supports, such as fonts and colors, are those of the
+    /// element, since libcss doesn't style pseudo-elements for layout. For the same reason the
+    /// boxes have no margins, borders or padding of their own, and paint the background of the
+    /// element, as its text does.
+    fn make_generated_boxes(&mut self,
+                            layout_ctx: &LayoutContext,
+                            node: AbstractNode<LayoutView>,
+                            pseudo_element: PseudoElement)
+                            -> ~[RenderBox] {
+        // Replaced elements have no content for pseudo-elements to go before or after.
+        if !node.is_element() || node.is_image_element() || node.is_iframe_element() ||
+                node.is_media_element() || node.is_input_element() {
+            return ~[]
+        }
+        let values = match node.get_pseudo_element_values(pseudo_element) {
+            Some(values) => values,
+            None => return ~[],
+        };
+        let generated = match values.content {
+            content::Content(ref items) => {
+                self.generated_content.generate(items.as_slice(), &values.quotes, node)
+            }
+            content::normal | content::none => return ~[],
+        };
+        let mut boxes = ~[];
+        for generated in generated.move_iter() {
+            let mut base = RenderBoxBase::new(node, self.next_box_id());
+            base.pseudo_element = Some(pseudo_element);
+            match generated {
+                GeneratedText(text) => {
+                    let box = UnscannedTextRenderBox::new_generated(base, text);
+                    boxes.push(UnscannedTextRenderBoxClass(@mut box))
+                }
+                GeneratedImage(url) => {
+                    let box = ImageRenderBox::new(base, url, layout_ctx.image_cache);
+                    boxes.push(ImageRenderBoxClass(@mut box))
+                }
+            }
+        }
+        boxes
+    }
+
+    /// Returns the box of the marker of `node`, if it is a list item whose `list-style-type` isn't
+    /// `none`. List items are numbered from 1 among the children of their parent.
+    ///
+    /// FIXME: Markers are always inside the list item, as with `list-style-position: inside`.
+    /// Neither `counter-reset` and `counter-increment` nor the `start` and `value` attributes of
+    /// HTML lists change the numbering.
+    fn make_marker_boxes(&mut self, layout_ctx: &LayoutContext, node: AbstractNode<LayoutView>)
+                         -> ~[RenderBox] {
+        if !node.is_element() {
+            return ~[]
+        }
+        match node.style().display(node.is_root()) {
+            CSSDisplayListItem => {}
+            _ => return ~[],
+        }
+        let innermost = self.list_item_counts.len() - 1;
+        self.list_item_counts[innermost] += 1;
+        let ordinal = self.list_item_counts[innermost];
+        let list_style_type = match node.try_computed_values() {
+            Ok(values) => &values.list_style_type,
+            Err(_) => return ~[],
+        };
+        let text = match *list_style_type {
+            list_style_type::CounterStyle(ref name) => {
+                layout_ctx.counter_styles.marker_text(ordinal, name.as_slice())
+            }
+            list_style_type::none => return ~[],
+        };
+        let mut base = RenderBoxBase::new(node, self.next_box_id());
+        base.marker = true;
+        let box = UnscannedTextRenderBox::new_generated(base, text);
+        ~[UnscannedTextRenderBoxClass(@mut box)]
+    }
+
+    /// Makes an inline flow for the boxes of content that a pseudo-element of `node` generates,
+    /// as a child of the flow of `parent_generator`, and returns its generator.
+    fn create_generated_content_generator<'a>(&mut self,
+                                              node: AbstractNode<LayoutView>,
+                                              parent_generator: BoxGenerator<'a>,
+                                              boxes: ~[RenderBox])
+                                              -> BoxGenerator<'a> {
+        let mut parent_generator = parent_generator;
+        let mut generator = self.create_child_generator(node, &mut parent_generator, Flow_Inline);
+        generator.push_generated_boxes(boxes);
+        generator
+    }
+
     pub fn create_child_generator<'a>(&mut self,
                               node: AbstractNode<LayoutView>,
                               parent_generator: &mut BoxGenerator<'a>,
@@ -0,0 +1,123 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Skipping the contents of elements with `content-visibility: auto` that are far from the
+//! viewport, and of elements with `content-visibility: hidden`.
+//!
+//! After each layout, the blocks whose contents can be skipped are recorded with where they are.
+//! Before the next one, the elements that are far from the viewport are marked in their layout
+//! data, with the size that stands in for their contents, and neither their descendants are
+//! styled nor boxes are built for them. As the page scrolls, layout asks script for a reflow as
+//! soon as an element comes near the viewport, or goes far from it.
+
+use layout::flow::{BlockFlow, FlowContext};
+
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use gfx::geometry::Au;
+use script::dom::node::{AbstractNode, LayoutView};
+
+/// The values of the `content-visibility` property.
+#[deriving(Eq)]
+pub enum ContentVisibility {
+    ContentVisibilityVisible,
+    /// The contents are skipped while the element is far from the viewport.
+    ContentVisibilityAuto,
+    /// The contents are always skipped.
+    ContentVisibilityHidden,
+}
+
+/// A block whose contents can be skipped, as it was at the last layout.
+pub struct LazyContent {
+    node: AbstractNode<LayoutView>,
+    visibility: ContentVisibility,
+    /// The border box of the block in the page.
+    bounds: Rect<Au>,
+    /// The size of the content box that stands in for the contents of the block when they are
+    /// skipped: its `contain-intrinsic-size`, or else the size its contents last had.
+    skipped_size: Size2D<Au>,
+}
+
+impl LazyContent {
+    /// Returns true if the contents of the block should be skipped while the viewport is where it
+    /// is.
+    fn should_skip(&self, viewport: &Rect<Au>) -> bool {
+        match self.visibility {
+            ContentVisibilityVisible => false,
+            ContentVisibilityAuto => !self.bounds.intersects(&relevant_area(viewport)),
+            ContentVisibilityHidden => true,
+        }
+    }
+
+    fn is_skipped(&self) -> bool {
+        self.node.read_layout_data(|data| data.skipped_contents_size.is_some())
+    }
+}
+
+/// Returns the area around the viewport that the contents of the elements in it are rendered
+/// for, so that they are ready before they are scrolled into view: the viewport, grown by half
+/// its size on each side.
+fn relevant_area(viewport: &Rect<Au>) -> Rect<Au> {
+    let margin = Size2D(viewport.size.width / Au(2), viewport.size.height / Au(2));
+    Rect(Point2D(viewport.origin.x - margin.width, viewport.origin.y - margin.height),
+         Size2D(viewport.size.width + margin.width + margin.width,
+                viewport.size.height + margin.height + margin.height))
+}
+
+/// Finds the blocks of a laid out flow tree whose contents can be skipped. Must be called after
+/// the display list has been built, so that the flows know where they are in the page.
+pub fn find_lazy_content(root: &mut FlowContext) -> ~[LazyContent] {
+    let mut lazy_content = ~[];
+    do root.each_preorder |flow| {
+        match *flow {
+            BlockFlow(ref block) => {
+                for &box in block.box.iter() {
+                    let visibility = box.content_visibility();
+                    if visibility == ContentVisibilityVisible {
+                        loop
+                    }
+                    let bounds = box.position().translate(&block.common.abs_position);
+                    let content_size = do box.with_model |model| {
+                        Size2D(bounds.size.width - model.border.left - model.border.right -
+                                   model.padding.left - model.padding.right,
+                               bounds.size.height - model.border.top - model.border.bottom -
+                                   model.padding.top - model.padding.bottom)
+                    };
+                    lazy_content.push(LazyContent {
+                        node: box.node(),
+                        visibility: visibility,
+                        bounds: bounds,
+                        skipped_size: box.contain_intrinsic_size().unwrap_or_default(content_size),
+                    })
+                }
+            }
+            _ => {}
+        }
+        true
+    };
+    lazy_content
+}
+
+/// Returns true if the contents of any of the blocks should be skipped, or should no longer be
+/// skipped, now that the viewport is where it is.
+pub fn needs_update(lazy_content: &[LazyContent], viewport: &Rect<Au>) -> bool {
+    lazy_content.iter().any(|lazy| lazy.should_skip(viewport) != lazy.is_skipped())
+}
+
+/// Marks the elements whose contents should be skipped while the viewport is where it is, and
+/// unmarks the others.
+pub fn update_skipped_contents(lazy_content: &[LazyContent], viewport: &Rect<Au>) {
+    for lazy in lazy_content.iter() {
+        let skipped_size = if lazy.should_skip(viewport) {
+            Some(lazy.skipped_size)
+        } else {
+            None
+        };
+        debug!("content-visibility: %s contents of %s",
+               if skipped_size.is_some() { "skipping" } else { "rendering" },
+               lazy.node.debug_str());
+        lazy.node.write_layout_data(|data| data.skipped_contents_size = skipped_size);
+    }
+}
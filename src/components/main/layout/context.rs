@@ -5,13 +5,29 @@
 //! Data needed by the layout task.
 
 use geom::rect::Rect;
+use geom::size::Size2D;
 use gfx::font_context::FontContext;
 use gfx::geometry::Au;
+use gfx::text::hyphenation::Hyphenators;
+use gfx::text::spellcheck::Dictionary;
+use script::dom::documenttype::QuirksMode;
+use script::style::counter_style::CounterStyleMap;
 use servo_net::local_image_cache::LocalImageCache;
 
 /// Data needed by the layout task.
 pub struct LayoutContext {
     font_ctx: @mut FontContext,
     image_cache: @mut LocalImageCache,
-    screen_size: Rect<Au>
+    hyphenators: @Hyphenators,
+    /// The dictionary that editable text is spellchecked against, if one could be loaded.
+    dictionary: Option<@Dictionary>,
+    screen_size: Rect<Au>,
+    /// The mode of the document being laid out.
+    quirks_mode: QuirksMode,
+    /// The size that viewport units are relative to, which is that of the visual viewport if
+    /// they follow pinch zoom. The stylist resolves viewport units against it; libcss doesn't
+    /// support them.
+    viewport_units_basis: Size2D<Au>,
+    /// The counter styles that list markers are written in.
+    counter_styles: @CounterStyleMap,
 }
@@ -8,7 +8,10 @@ use layout::box::RenderBox;
 use layout::context::LayoutContext;
 use std::cast::transmute;
 use script::dom::node::AbstractNode;
+use script::style::properties::RGBA;
 
+use azure::AzFloat;
+use azure::azure_hl;
 use gfx;
 use newcss;
 
@@ -67,3 +70,14 @@ impl ToGfxColor for newcss::color::Color {
     }
 }
 
+impl ToGfxColor for RGBA {
+    fn to_gfx_color(&self) -> gfx::color::Color {
+        azure_hl::Color {
+            r: self.red as AzFloat,
+            g: self.green as AzFloat,
+            b: self.blue as AzFloat,
+            a: self.alpha as AzFloat,
+        }
+    }
+}
+
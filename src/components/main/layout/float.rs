@@ -53,9 +53,6 @@ impl FloatFlowData {
     }
 
     pub fn teardown(&mut self) {
-        for box in self.box.iter() {
-            box.teardown();
-        }
         self.box = None;
         self.index = None;
     }
@@ -82,9 +79,9 @@ impl FloatFlowData {
 
 
         self.box.map(|&box| {
-            let style = box.style();
+            let values = box.computed_values();
             do box.with_model |model| {
-                model.compute_borders(style)
+                model.compute_borders(values)
             }
 
             min_width = min_width.add(&box.get_min_width(ctx));
@@ -107,24 +104,20 @@ impl FloatFlowData {
         self.common.is_inorder = false;
 
         for &box in self.box.iter() {
-            let style = box.style();
+            let values = box.computed_values();
             do box.with_model |model| {
                 // Can compute padding here since we know containing block width.
-                model.compute_padding(style, remaining_width);
+                model.compute_padding(values, remaining_width);
 
                 // Margins for floats are 0 if auto.
-                let margin_top = MaybeAuto::from_margin(style.margin_top(),
-                                                        remaining_width,
-                                                        style.font_size()).specified_or_zero();
-                let margin_bottom = MaybeAuto::from_margin(style.margin_bottom(),
-                                                           remaining_width,
-                                                           style.font_size()).specified_or_zero();
-                let margin_left = MaybeAuto::from_margin(style.margin_left(),
-                                                         remaining_width,
-                                                         style.font_size()).specified_or_zero();
-                let margin_right = MaybeAuto::from_margin(style.margin_right(),
-                                                          remaining_width,
-                                                          style.font_size()).specified_or_zero();
+                let margin_top = MaybeAuto::from_style(values.margin_top,
+                                                       remaining_width).specified_or_zero();
+                let margin_bottom = MaybeAuto::from_style(values.margin_bottom,
+                                                          remaining_width).specified_or_zero();
+                let margin_left = MaybeAuto::from_style(values.margin_left,
+                                                        remaining_width).specified_or_zero();
+                let margin_right = MaybeAuto::from_style(values.margin_right,
+                                                         remaining_width).specified_or_zero();
 
 
 
@@ -133,9 +126,8 @@ impl FloatFlowData {
                                                                 remaining_width));
 
 
-                let width = MaybeAuto::from_width(style.width(), 
-                                                  remaining_width,
-                                                  style.font_size()).specified_or_default(shrink_to_fit);
+                let width = MaybeAuto::from_style(values.width, remaining_width);
+                let width = width.specified_or_default(shrink_to_fit);
                 debug!("assign_widths_float -- width: %?", width);
 
                 model.margin.top = margin_top;
@@ -274,10 +266,8 @@ impl FloatFlowData {
         
         //TODO(eatkinson): compute heights properly using the 'height' property.
         for &box in self.box.iter() {
-            let height_prop = 
-                MaybeAuto::from_height(box.style().height(),
-                                       Au(0),
-                                       box.style().font_size()).specified_or_zero();
+            let height_prop =
+                MaybeAuto::from_style(box.computed_values().height, Au(0)).specified_or_zero();
 
             height = geometry::max(height, height_prop) + noncontent_height;
             debug!("assign_height_float -- height: %?", height);
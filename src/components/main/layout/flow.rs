@@ -33,6 +33,7 @@ use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
 use layout::inline::{InlineFlowData};
 use layout::float_context::{FloatContext, Invalid, FloatType};
 use layout::incremental::RestyleDamage;
+use layout::model::Containment;
 use css::node_style::StyledNode;
 use extra::dlist::{DList,MutDListIterator};
 use extra::container::Deque;
@@ -420,6 +421,20 @@ impl<'self> FlowContext {
         }
     }
 
+    /// Returns the containment of the box that starts this flow. Only block-like flows can be
+    /// contained.
+    pub fn containment(&self) -> Containment {
+        match *self {
+            BlockFlow(ref info) => {
+                info.box.map_default(Containment::none(), |box| box.containment())
+            }
+            FloatFlow(ref info) => {
+                info.box.map_default(Containment::none(), |box| box.containment())
+            }
+            _ => Containment::none(),
+        }
+    }
+
 
     // Actual methods that do not require much flow-specific logic
     pub fn foldl_all_boxes<B:Clone>(&mut self, seed: B, cb: &fn(a: B, b: RenderBox) -> B) -> B {
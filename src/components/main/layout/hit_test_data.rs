@@ -0,0 +1,62 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The hit-test data of a page, which tells the compositor where the regions that a scroll
+//! gesture can go to are, so that it can pick the layer that scrolls without asking the page,
+//! and which touch gestures the `touch-action` of each element leaves to it.
+//!
+//! FIXME: Only frames scroll separately from the page, so they are the only scroll regions.
+
+use layout::flow::{BlockFlow, FlowContext};
+
+use geom::point::Point2D;
+use geom::rect::Rect;
+use geom::size::Size2D;
+use gfx::geometry::Au;
+use servo_msg::compositor_msg::{HitTestData, HitTestRegion, ScrollFrameRegion};
+use servo_msg::compositor_msg::{TouchAction, TouchActionRegion};
+
+/// Builds the hit-test data of the page. Must be called after the display list has been built,
+/// so that the flows know where they are in the page.
+pub fn build_hit_test_data(root: &mut FlowContext) -> HitTestData {
+    let mut data = HitTestData::new();
+    do root.each_preorder |flow| {
+        match *flow {
+            BlockFlow(ref block) => {
+                for &box in block.box.iter() {
+                    let touch_action = box.touch_action();
+                    if touch_action != TouchAction::auto() {
+                        let rect = box.position().translate(&block.common.abs_position);
+                        data.regions.push(HitTestRegion {
+                            rect: to_page_rect(rect),
+                            kind: TouchActionRegion(touch_action),
+                        })
+                    }
+                }
+                if block.common.node.is_iframe_element() {
+                    let subpage_id = do block.common.node.with_imm_iframe_element |iframe| {
+                        iframe.size.map(|size| size.subpage_id)
+                    };
+                    // A frame that hasn't been given a subpage yet has nothing to scroll.
+                    for &subpage_id in subpage_id.iter() {
+                        data.regions.push(HitTestRegion {
+                            rect: to_page_rect(block.content_rect_in_page()),
+                            kind: ScrollFrameRegion(subpage_id),
+                        })
+                    }
+                }
+            }
+            _ => {}
+        }
+        true
+    };
+    debug!("hit test data: %u regions", data.regions.len());
+    data
+}
+
+/// Converts a rect in the page to the coordinates that the compositor uses.
+fn to_page_rect(rect: Rect<Au>) -> Rect<f32> {
+    Rect(Point2D(*rect.origin.x.to_css_px() as f32, *rect.origin.y.to_css_px() as f32),
+         Size2D(*rect.size.width.to_css_px() as f32, *rect.size.height.to_css_px() as f32))
+}
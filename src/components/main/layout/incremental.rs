@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use layout::model::Containment;
 use newcss::complete::CompleteSelectResults;
 
 use script::dom::node::{AbstractNode, LayoutView};
@@ -102,6 +103,21 @@ impl RestyleDamage {
         self.intersect(restyle_damage!(Reflow))
     }
 
+    /// Elements of the damage of the descendants of a flow which should also get set on the flow,
+    /// given its containment. Size containment keeps the intrinsic widths of the flow from
+    /// depending on its descendants; with layout containment as well, their layout can't change
+    /// its own either.
+    pub fn propagate_up_through(self, containment: Containment) -> RestyleDamage {
+        let mut damage = self;
+        if containment.size {
+            damage.bits = damage.bits & !(BubbleWidths as int);
+            if containment.layout {
+                damage.bits = damage.bits & !(Reflow as int);
+            }
+        }
+        damage
+    }
+
     /// Elements of self which should also get set on any child flows.
     pub fn propagate_down(self) -> RestyleDamage {
         self.intersect(restyle_damage!(BubbleWidths))
@@ -158,6 +174,7 @@ pub fn compute_damage(node: &AbstractNode<LayoutView>,
 #[cfg(test)]
 mod restyle_damage_tests {
     use super::*;
+    use layout::model::Containment;
 
     #[test]
     fn none_is_empty() {
@@ -197,6 +214,24 @@ mod restyle_damage_tests {
         assert!(d.has(BubbleWidths));
     }
 
+    #[test]
+    fn containment_bounds_propagation() {
+        let d = RestyleDamage::all();
+        let mut containment = Containment::none();
+        assert!(d.propagate_up_through(containment).to_int() == d.to_int());
+
+        containment.size = true;
+        let up = d.propagate_up_through(containment);
+        assert!(up.lacks(BubbleWidths));
+        assert!(up.has(Reflow));
+
+        containment.layout = true;
+        let up = d.propagate_up_through(containment);
+        assert!(up.lacks(BubbleWidths));
+        assert!(up.lacks(Reflow));
+        assert!(up.has(Repaint));
+    }
+
     #[test]
     fn can_intersect() {
         let x = restyle_damage!(Repaint, BubbleWidths);
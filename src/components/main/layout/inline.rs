@@ -5,11 +5,12 @@
 use css::node_style::StyledNode;
 use std::cell::Cell;
 use layout::box::{CannotSplit, GenericRenderBoxClass, ImageRenderBoxClass, RenderBox};
-use layout::box::{SplitDidFit, SplitDidNotFit, TextRenderBoxClass};
+use layout::box::{SplitDidFit, SplitDidNotFit, TextRenderBoxClass, element_font_style};
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder, ExtraDisplayListData};
 use layout::flow::{FlowContext, FlowData, InlineFlow};
 use layout::float_context::FloatContext;
+use layout::model::to_au;
 use layout::util::{ElementMapping};
 use layout::float_context::{PlacementInfo, FloatLeft};
 
@@ -17,19 +18,30 @@ use std::u16;
 use std::util;
 use geom::{Point2D, Rect, Size2D};
 use gfx::display_list::DisplayList;
-use gfx::geometry::Au;
+use gfx::geometry::{Au, CSSPixel};
 use newcss::units::{Em, Px};
-use newcss::values::{CSSFontSizeLength};
+use newcss::values::{CSSDisplayInline, CSSDisplayInlineBlock, CSSDisplayInlineTable};
 use newcss::values::{CSSTextAlignLeft, CSSTextAlignCenter, CSSTextAlignRight, CSSTextAlignJustify};
-use newcss::values::{CSSLineHeightNormal, CSSLineHeightNumber, CSSLineHeightLength, CSSLineHeightPercentage};
 use newcss::values::{CSSVerticalAlignBaseline, CSSVerticalAlignMiddle, CSSVerticalAlignSub, CSSVerticalAlignSuper, 
                      CSSVerticalAlignTextTop, CSSVerticalAlignTextBottom, CSSVerticalAlignTop, CSSVerticalAlignBottom, 
                      CSSVerticalAlignLength, CSSVerticalAlignPercentage};
+use script::dom::documenttype::NoQuirks;
+use script::style::properties::longhands::line_height;
 use servo_util::range::Range;
 use servo_util::tree::TreeNodeRef;
 use extra::container::Deque;
 use extra::ringbuf::RingBuf;
 
+/// Returns the height of a line box that `line_height` gives text whose em box is `font_size` tall.
+/// Lengths and percentages are already resolved in computed line heights.
+fn line_height_for_font_size(line_height: line_height::ComputedValue, font_size: Au) -> Au {
+    match line_height {
+        line_height::Normal => font_size.scale_by(1.14f),
+        line_height::Number(l) => font_size.scale_by(l),
+        line_height::Length(l) => to_au(l),
+    }
+}
+
 /*
 Lineboxes are represented as offsets into the child list, rather than
 as an object that "owns" boxes. Choosing a different set of line
@@ -132,6 +144,9 @@ impl LineboxScanner {
                 debug!("LineboxScanner: Box wasn't appended, because line %u was full.",
                         self.lines.len());
                 self.flush_current_line();
+            } else if self.pending_line_ends_in_forced_break() {
+                debug!("LineboxScanner: Line %u ends in a preserved newline.", self.lines.len());
+                self.flush_current_line();
             } else {
                 debug!("LineboxScanner: appended a box to line %u", self.lines.len());
             }
@@ -170,13 +185,7 @@ impl LineboxScanner {
     }
 
     fn calculate_line_height(&self, box: RenderBox, font_size: Au) -> Au { 
-        match box.line_height() {
-            CSSLineHeightNormal => font_size.scale_by(1.14f),
-            CSSLineHeightNumber(l) => font_size.scale_by(l),
-            CSSLineHeightLength(Em(l)) => font_size.scale_by(l),
-            CSSLineHeightLength(Px(l)) => Au::from_frac_px(l),
-            CSSLineHeightPercentage(p) => font_size.scale_by(p / 100.0f)
-        }
+        line_height_for_font_size(box.line_height(), font_size)
     }
 
     fn box_height(&self, box: RenderBox) -> Au {
@@ -199,8 +208,9 @@ impl LineboxScanner {
 
                 line_height
             }
-            GenericRenderBoxClass(_) => {
-                Au(0)
+            GenericRenderBoxClass(generic_box) => {
+                // Only widgets have heights of their own so far.
+                generic_box.position.size.height
             }
             _ => {
                 fail!(fmt!("Tried to get height of unknown Box variant: %s", box.debug_str()))
@@ -308,6 +318,16 @@ impl LineboxScanner {
 
     /// Returns false only if we should break the line.
     fn try_append_to_line(&mut self, in_box: RenderBox, flow: &mut InlineFlowData) -> bool {
+        // A preserved newline ends the line, so the text after it is laid out separately.
+        let in_box = match in_box.split_after_forced_break() {
+            Some((left_box, right_box)) => {
+                debug!("LineboxScanner: case=splitting box after a preserved newline");
+                self.work_list.push_front(right_box);
+                left_box
+            }
+            None => in_box,
+        };
+
         let line_is_empty: bool = self.pending_line.range.length() == 0;
 
         if line_is_empty {
@@ -446,6 +466,12 @@ impl LineboxScanner {
         }
     }
 
+    /// Returns true if the last box on the pending line ends in a newline kept by `white-space`,
+    /// after which the line must be broken.
+    fn pending_line_ends_in_forced_break(&self) -> bool {
+        self.pending_line.range.length() > 0 && self.new_boxes.last().ends_in_forced_break()
+    }
+
     // unconditional push
     fn push_box_to_line(&mut self, box: RenderBox) {
         debug!("LineboxScanner: Pushing box b%d to line %u", box.id(), self.lines.len());
@@ -490,9 +516,6 @@ impl InlineFlowData {
     }
 
     pub fn teardown(&mut self) {
-        for box in self.boxes.iter() {
-            box.teardown();
-        }
         self.boxes = ~[];
     }
 }
@@ -563,8 +586,13 @@ impl InlineFlowData {
                     GenericRenderBoxClass(generic_box) => {
                         // TODO(#225): There will be different cases here for `inline-block` and
                         // other replaced content.
-                        // FIXME(pcwalton): This seems clownshoes; can we remove?
-                        generic_box.position.size.width = Au::from_px(45);
+                        match box.intrinsic_size() {
+                            Some(size) => generic_box.position.size = size,
+                            None => {
+                                // FIXME(pcwalton): This seems clownshoes; can we remove?
+                                generic_box.position.size.width = Au::from_px(45);
+                            }
+                        }
                     }
                     // FIXME(pcwalton): This isn't very type safe!
                     _ => fail!(fmt!("Tried to assign width to unknown Box variant: %?", box)),
@@ -594,7 +622,32 @@ impl InlineFlowData {
         self.assign_height_inline(ctx);
     }
 
-    pub fn assign_height_inline(&mut self, _: &LayoutContext) {
+    /// Returns the distances from the baseline to the top and to the bottom of the strut of the
+    /// lines of this flow: an invisible box of no width with the font and line height of the block
+    /// that contains them, which lines are at least as tall as.
+    fn strut(&self, ctx: &LayoutContext) -> (Au, Au) {
+        // The node of an inline flow is the first inline-level child of the block, except that the
+        // flow of the content that the block generates belongs to the block itself.
+        let node = self.common.node;
+        let is_inline_level = !node.is_element() || match node.style().display(node.is_root()) {
+            CSSDisplayInline | CSSDisplayInlineBlock | CSSDisplayInlineTable => true,
+            _ => false,
+        };
+        let block = if is_inline_level {
+            node.parent_node().map_default(node, |parent| *parent)
+        } else {
+            node
+        };
+        let font_group = ctx.font_ctx.get_resolved_font_for_style(&element_font_style(block));
+        let font = font_group.fonts[0];
+        let (ascent, descent) = (font.metrics.ascent, font.metrics.descent);
+        let line_height = line_height_for_font_size(block.computed_values().line_height,
+                                                    ascent + descent);
+        let top = ascent + (line_height - ascent - descent).scale_by(0.5f);
+        (top, line_height - top)
+    }
+
+    pub fn assign_height_inline(&mut self, ctx: &LayoutContext) {
 
         debug!("assign_height_inline: assigning height for flow %?", self.common.id);
 
@@ -610,6 +663,7 @@ impl InlineFlowData {
         scanner.scan_for_lines(self);
 
         let mut line_height_offset = Au(0);
+        let (strut_top, strut_bottom) = self.strut(ctx);
 
         // Now, go through each line and lay out the boxes inside
         for line in self.lines.mut_iter() {
@@ -626,6 +680,9 @@ impl InlineFlowData {
             let linebox_align;
             if line.range.begin() < self.boxes.len() {
                 let first_box = self.boxes[line.range.begin()];
+                // FIXME: The initial value of `text-align` is `start`, which aligns right-to-left
+                // paragraphs to the right, but newcss has no `start` and reports it as `left`,
+                // which can't be told apart from an explicit `left`.
                 linebox_align = first_box.text_align();
             } else {
                 // Nothing to lay out, so assume left alignment.
@@ -749,14 +806,7 @@ impl InlineFlowData {
                 do cur_box.with_mut_base |base| {
                     // Get parent node
                     let parent = base.node.parent_node().map_default(base.node, |parent| *parent);
-                    // TODO: When the calculation of font-size style is supported, it should be updated.
-                    let font_size = match parent.style().font_size() {
-                        CSSFontSizeLength(Px(length)) => length,
-                        // todo: this is based on a hard coded font size, should be the parent element's font size
-                        CSSFontSizeLength(Em(length)) => length * 16f, 
-                        _ => 16f // px units
-                    };
-                    parent_text_top = Au::from_frac_px(font_size);
+                    parent_text_top = to_au(parent.computed_values().font_size);
                 }
 
                 // This flag decides whether topmost and bottommost are updated or not.
@@ -816,8 +866,8 @@ impl InlineFlowData {
                     },
                     CSSVerticalAlignLength(length) => {
                         let length_offset = match length {
-                            Em(l) => Au::from_frac_px(cur_box.font_style().pt_size * l),
-                            Px(l) => Au::from_frac_px(l),
+                            Em(l) => Au::from_css_px(CSSPixel(cur_box.font_style().pt_size * l)),
+                            Px(l) => Au::from_css_px(CSSPixel(l)),
                         };
                         -(length_offset + ascent)
                     },
@@ -843,6 +893,20 @@ impl InlineFlowData {
                 }
             }
 
+            // Lines are at least as tall as the strut, except that in quirks and almost standards
+            // modes, lines without text aren't, so that images alone in table cells sit on their
+            // bottom edge rather than on a baseline above it.
+            let has_text = do line.range.eachi().any |i| {
+                match self.boxes[i] {
+                    TextRenderBoxClass(*) => true,
+                    _ => false,
+                }
+            };
+            if has_text || ctx.quirks_mode == NoQuirks {
+                topmost = Au::max(topmost, strut_top);
+                bottommost = Au::max(bottommost, strut_bottom);
+            }
+
             // Calculate the distance from baseline to the top of the biggest box with 'bottom' value.
             // Then, if necessary, update the topmost.
             let topmost_of_bottom = biggest_bottom - bottommost;
@@ -5,48 +5,76 @@
 //! The layout task. Performs layout on the DOM, builds display lists and sends them to be
 /// rendered.
 
-use css::matching::MatchMethods;
-use css::select::new_css_select_ctx;
+use css::computed_style::resolved_value;
+use css::matching::{MatchMethods, restyle_dirty_subtrees, restyle_state_changes};
+use css::select::{DefaultStylesheets, StylesheetText, new_css_select_ctx};
+use css::stylist::Stylist;
+use layout::animation::Animations;
 use layout::aux::LayoutAuxMethods;
 use layout::box_builder::LayoutTreeBuilder;
+use layout::content_visibility::{LazyContent, find_lazy_content, needs_update};
+use layout::content_visibility::update_skipped_contents;
 use layout::context::LayoutContext;
 use layout::display_list_builder::{DisplayListBuilder};
-use layout::flow::FlowContext;
+use layout::flow::{BlockFlow, FlowContext};
+use layout::hit_test_data::build_hit_test_data;
 use layout::incremental::{RestyleDamage, BubbleWidths};
+use layout::prefetch::Prefetcher;
+use layout::scroll_snap::find_snap_positions;
 
 use std::cast::transmute;
 use std::cell::Cell;
 use std::comm::{Port};
+use std::rt::io::timer::Timer;
+use std::rt::rtio::RtioTimer;
+use extra::time::precise_time_s;
 use extra::arc::Arc;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
-use gfx::display_list::DisplayList;
+use gfx::display_list::{DisplayList, FixedLayer};
 use gfx::font_context::FontContext;
-use gfx::geometry::Au;
+use gfx::geometry::{Au, CSSPixel};
 use gfx::opts::Opts;
 use gfx::render_task::{RenderMsg, RenderChan, RenderLayer};
+use gfx::text::hyphenation::Hyphenators;
+use gfx::text::spellcheck::Dictionary;
 use newcss::select::SelectCtx;
-use newcss::stylesheet::Stylesheet;
-use newcss::types::OriginAuthor;
-use script::dom::event::ReflowEvent;
+use newcss::types::{OriginAuthor, OriginUA};
+use script::dom::documenttype::{NoQuirks, QuirksMode};
+use script::dom::element::ElementState;
+use script::dom::event::{AnimationTickEvent, ImageLoadedEvent, ReflowEvent};
 use script::dom::node::{AbstractNode, LayoutView};
-use script::layout_interface::{AddStylesheetMsg, ContentBoxQuery};
+use script::html::cssparse::LayoutStylesheet;
+use script::layout_interface::{AddStylesheetMsg, AddUserAgentStylesheetMsg, ComputedStyleQuery};
+use script::layout_interface::ContentBoxQuery;
 use script::layout_interface::{HitTestQuery, ContentBoxResponse, HitTestResponse};
 use script::layout_interface::{ContentBoxesQuery, ContentBoxesResponse, ExitMsg, LayoutQuery};
-use script::layout_interface::{MatchSelectorsDocumentDamage, Msg};
-use script::layout_interface::{QueryMsg, Reflow, ReflowDocumentDamage};
-use script::layout_interface::{ReflowForDisplay, ReflowMsg};
+use script::layout_interface::{MatchSelectorsDocumentDamage, Msg, RestyleStatesDocumentDamage};
+use script::layout_interface::{QueryMsg, Reflow, ReflowDocumentDamage, TextMetricsQuery};
+use script::layout_interface::RangeBoxesQuery;
+use script::layout_interface::{ReflowForDisplay, ReflowMsg, RepaintDocumentDamage, ScrollMsg};
+use script::layout_interface::{ReplaceStylesheetsMsg, RestyleDirtyDocumentDamage, ZoomMsg};
 use script::script_task::{ReflowCompleteMsg, ScriptChan, SendEventMsg};
+use servo_msg::compositor_msg::{OverscrollAuto, ScrollbarStyle};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId};
 use servo_net::image_cache_task::{ImageCacheTask, ImageResponseMsg};
 use servo_net::local_image_cache::LocalImageCache;
 use servo_util::tree::TreeNodeRef;
-use servo_util::time::{ProfilerChan, profile};
+use servo_util::time::{ForgetMemoryReportsMsg, MemoryReportMsg, ProfilerChan, profile};
+use servo_util::time::profile_frame_stage;
 use servo_util::time;
 use servo_util::range::Range;
 use extra::url::Url;
 
+/// How often running transitions are repainted, in milliseconds.
+static ANIMATION_TICK_INTERVAL: u64 = 16;
+
+/// Returns the start of the paths of the memory reports of the document of a pipeline.
+fn memory_report_prefix(id: PipelineId) -> ~str {
+    fmt!("documents/%u/", *id)
+}
+
 struct LayoutTask {
     id: PipelineId,
     port: Port<Msg>,
@@ -56,12 +84,47 @@ struct LayoutTask {
     image_cache_task: ImageCacheTask,
     local_image_cache: @mut LocalImageCache,
     font_ctx: @mut FontContext,
+    hyphenators: @Hyphenators,
+    dictionary: Option<@Dictionary>,
     doc_url: Option<Url>,
     screen_size: Option<Size2D<Au>>,
+    /// The mode of the document, as of the last reflow.
+    quirks_mode: QuirksMode,
+    /// Where the viewport has scrolled to in the page.
+    viewport_origin: Point2D<Au>,
+    /// The width that the vertical scrollbar of the viewport takes from it, if the page
+    /// overflowed it at the last layout.
+    scrollbar_gutter: Au,
+    /// How far the user has pinch zoomed in.
+    zoom: f32,
+    /// Whether viewport units are relative to the visual viewport, and so change with the zoom.
+    viewport_units_follow_zoom: bool,
+
+    /// The blocks whose contents can be skipped, as they were at the last layout.
+    lazy_content: ~[LazyContent],
+
+    /// The transitions that are running.
+    animations: Animations,
+    /// When script may next be asked to repaint the running transitions, in seconds.
+    next_animation_tick: float,
+    /// The flow tree of the last layout, which is painted again as transitions run.
+    layout_root: Option<FlowContext>,
 
     display_list: Option<Arc<DisplayList<AbstractNode<()>>>>,
 
+    /// The fixed layers of the elements with `position: fixed` that were last built, which are
+    /// drawn above the display list and stay put when the page scrolls.
+    fixed_layers: Option<Arc<~[FixedLayer<AbstractNode<()>>]>>,
+
     css_select_ctx: @mut SelectCtx,
+    /// The default style sheets of the browser and of the user, which every select context starts
+    /// with.
+    default_stylesheets: DefaultStylesheets,
+    /// The style sheets as the new style system parses them, which compute the values of the
+    /// properties that libcss doesn't support.
+    stylist: Stylist,
+    /// Fetches the resources that styles refer to as restyling finds them.
+    prefetcher: Prefetcher,
     profiler_chan: ProfilerChan,
 }
 
@@ -73,6 +136,7 @@ impl LayoutTask {
                   render_chan: RenderChan<AbstractNode<()>>,
                   img_cache_task: ImageCacheTask,
                   opts: Opts,
+                  default_stylesheets: DefaultStylesheets,
                   profiler_chan: ProfilerChan) {
 
         let port = Cell::new(port);
@@ -81,6 +145,7 @@ impl LayoutTask {
         let render_chan = Cell::new(render_chan);
         let img_cache_task = Cell::new(img_cache_task);
         let profiler_chan = Cell::new(profiler_chan);
+        let default_stylesheets = Cell::new(default_stylesheets);
 
         do spawn {
             let mut layout = LayoutTask::new(id,
@@ -90,6 +155,7 @@ impl LayoutTask {
                                              render_chan.take(),
                                              img_cache_task.take(),
                                              &opts,
+                                             default_stylesheets.take(),
                                              profiler_chan.take());
             layout.start();
         };
@@ -102,9 +168,15 @@ impl LayoutTask {
            render_chan: RenderChan<AbstractNode<()>>, 
            image_cache_task: ImageCacheTask,
            opts: &Opts,
+           default_stylesheets: DefaultStylesheets,
            profiler_chan: ProfilerChan)
            -> LayoutTask {
-        let fctx = @mut FontContext::new(opts.render_backend, true, profiler_chan.clone());
+        let fctx = @mut FontContext::new(opts.render_backend,
+                                         true,
+                                         opts.generic_fonts,
+                                         profiler_chan.clone());
+        let local_image_cache = @mut LocalImageCache(image_cache_task.clone());
+        local_image_cache.set_max_decoded_bytes(opts.max_document_image_bytes);
 
         LayoutTask {
             id: id,
@@ -112,15 +184,32 @@ impl LayoutTask {
             constellation_chan: constellation_chan,
             script_chan: script_chan,
             render_chan: render_chan,
-            image_cache_task: image_cache_task.clone(),
-            local_image_cache: @mut LocalImageCache(image_cache_task),
+            image_cache_task: image_cache_task,
+            local_image_cache: local_image_cache,
             font_ctx: fctx,
+            hyphenators: @Hyphenators::new(opts.hyphenation_patterns),
+            dictionary: Dictionary::load(opts.spellcheck_dictionary.clone()).map_move(|d| @d),
             doc_url: None,
             screen_size: None,
+            quirks_mode: NoQuirks,
+            viewport_origin: Point2D(Au(0), Au(0)),
+            scrollbar_gutter: Au(0),
+            zoom: 1.0,
+            viewport_units_follow_zoom: opts.viewport_units_follow_zoom,
+
+            lazy_content: ~[],
+
+            animations: Animations::new(),
+            next_animation_tick: 0.0,
+            layout_root: None,
 
             display_list: None,
+            fixed_layers: None,
             
-            css_select_ctx: @mut new_css_select_ctx(),
+            css_select_ctx: @mut new_css_select_ctx(&default_stylesheets),
+            stylist: Stylist::new(&default_stylesheets, fctx),
+            default_stylesheets: default_stylesheets,
+            prefetcher: Prefetcher::new(fctx, local_image_cache),
             profiler_chan: profiler_chan,
         }
     }
@@ -135,23 +224,43 @@ impl LayoutTask {
     fn build_layout_context(&self) -> LayoutContext {
         let image_cache = self.local_image_cache;
         let font_ctx = self.font_ctx;
+        let hyphenators = self.hyphenators;
+        let dictionary = self.dictionary;
         let screen_size = self.screen_size.unwrap();
+        let viewport_units_basis = if self.viewport_units_follow_zoom {
+            let unzoom = |length: Au| Au(((*length as f32) / self.zoom) as i32);
+            Size2D(unzoom(screen_size.width), unzoom(screen_size.height))
+        } else {
+            screen_size
+        };
+        let screen_size = Size2D(screen_size.width - self.scrollbar_gutter, screen_size.height);
 
         LayoutContext {
             image_cache: image_cache,
             font_ctx: font_ctx,
+            hyphenators: hyphenators,
+            dictionary: dictionary,
             screen_size: Rect(Point2D(Au(0), Au(0)), screen_size),
+            quirks_mode: self.quirks_mode,
+            viewport_units_basis: viewport_units_basis,
+            counter_styles: self.stylist.counter_styles(),
         }
     }
 
     fn handle_request(&mut self) -> bool {
         match self.port.recv() {
             AddStylesheetMsg(sheet) => self.handle_add_stylesheet(sheet),
+            ReplaceStylesheetsMsg(sheets) => self.handle_replace_stylesheets(sheets),
+            AddUserAgentStylesheetMsg(url, text) => {
+                self.handle_add_user_agent_stylesheet(StylesheetText::new(url, text))
+            }
             ReflowMsg(data) => {
                 let data = Cell::new(data);
 
-                do profile(time::LayoutPerformCategory, self.profiler_chan.clone()) {
-                    self.handle_reflow(data.take());
+                do profile_frame_stage(time::LayoutStage, self.profiler_chan.clone()) {
+                    do profile(time::LayoutPerformCategory, self.profiler_chan.clone()) {
+                        self.handle_reflow(data.take());
+                    }
                 }
             }
             QueryMsg(query) => {
@@ -160,8 +269,11 @@ impl LayoutTask {
                     self.handle_query(query.take());
                 }
             }
+            ScrollMsg(origin) => self.handle_scroll(origin),
+            ZoomMsg(scale) => self.handle_zoom(scale),
             ExitMsg => {
                 debug!("layout: ExitMsg received");
+                self.profiler_chan.send(ForgetMemoryReportsMsg(memory_report_prefix(self.id)));
                 return false
             }
         }
@@ -169,9 +281,53 @@ impl LayoutTask {
         true
     }
 
-    fn handle_add_stylesheet(&self, sheet: Stylesheet) {
-        let sheet = Cell::new(sheet);
-        self.css_select_ctx.append_sheet(sheet.take(), OriginAuthor);
+    fn handle_add_stylesheet(&mut self, sheet: LayoutStylesheet) {
+        let LayoutStylesheet { libcss, properties } = sheet;
+        let libcss = Cell::new(libcss);
+        self.css_select_ctx.append_sheet(libcss.take(), OriginAuthor);
+        self.stylist.add_author_sheet(properties);
+    }
+
+    /// Adds a default style sheet at the user agent origin. libcss cascades the sheets of each
+    /// origin apart, so it can follow the author sheets in the select context, while the stylist
+    /// puts it before them.
+    fn handle_add_user_agent_stylesheet(&mut self, sheet: StylesheetText) {
+        self.css_select_ctx.append_sheet(sheet.parse(), OriginUA);
+        self.stylist.add_user_agent_sheet(&sheet);
+        self.default_stylesheets.add_user_agent_sheet(sheet);
+    }
+
+    /// Replaces the author style sheets. A select context can't have sheets taken out of it, so
+    /// a new one is made, while the stylist keeps its default sheets.
+    fn handle_replace_stylesheets(&mut self, sheets: ~[LayoutStylesheet]) {
+        self.css_select_ctx = @mut new_css_select_ctx(&self.default_stylesheets);
+        self.stylist.remove_author_sheets();
+        for sheet in sheets.move_iter() {
+            self.handle_add_stylesheet(sheet);
+        }
+    }
+
+    /// Asks script for a reflow if the viewport has scrolled near content that is skipped, or away
+    /// from content that is rendered and can be skipped.
+    fn handle_scroll(&mut self, origin: Point2D<Au>) {
+        self.viewport_origin = origin;
+        match self.screen_size {
+            Some(screen_size) => {
+                let viewport = Rect(origin, screen_size);
+                if needs_update(self.lazy_content, &viewport) {
+                    self.script_chan.send(SendEventMsg(self.id, ReflowEvent))
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Asks script for a reflow, which resolves viewport units again, if they follow the zoom.
+    fn handle_zoom(&mut self, scale: f32) {
+        self.zoom = scale;
+        if self.viewport_units_follow_zoom && self.screen_size.is_some() {
+            self.script_chan.send(SendEventMsg(self.id, ReflowEvent))
+        }
     }
 
     /// The high-level routine that performs layout tasks.
@@ -198,23 +354,89 @@ impl LayoutTask {
         let resized = self.screen_size != Some(screen_size);
         debug!("resized: %?", resized);
         self.screen_size = Some(screen_size);
+        self.quirks_mode = data.quirks_mode;
 
         // Create a layout context for use throughout the following passes.
         let mut layout_ctx = self.build_layout_context();
 
+        // Every element is restyled when the size that viewport units are relative to changes.
+        let viewport_changed = self.stylist.set_environment(data.url.clone(),
+                                                            layout_ctx.viewport_units_basis);
+        let damage_level = if viewport_changed {
+            MatchSelectorsDocumentDamage
+        } else {
+            data.damage.level
+        };
+
+        // When only the values that running transitions have reached have changed, the flow tree
+        // of the last layout is painted again as it is.
+        let repaint_only = match damage_level {
+            RepaintDocumentDamage => !resized && self.layout_root.is_some(),
+            _ => false,
+        };
+        if repaint_only {
+            node.initialize_style_for_subtree();
+            let mut layout_root = self.layout_root.take_unwrap();
+            if data.goal == ReflowForDisplay {
+                self.build_display_list(&mut layout_root, &layout_ctx, screen_size);
+            }
+            self.layout_root = Some(layout_root);
+
+            self.finish_reflow(data);
+            return
+        }
+
+        // Skip the contents of the blocks that are far from the viewport.
+        update_skipped_contents(self.lazy_content, &Rect(self.viewport_origin, screen_size));
+
         // Initialize layout data for each node.
         //
         // FIXME: This is inefficient. We don't need an entire traversal to do this!
-        do profile(time::LayoutAuxInitCategory, self.profiler_chan.clone()) {
-            node.initialize_style_for_subtree();
-        }
+        let node_count = do profile(time::LayoutAuxInitCategory, self.profiler_chan.clone()) {
+            node.initialize_style_for_subtree()
+        };
 
         // Perform CSS selector matching if necessary.
-        match data.damage.level {
-            ReflowDocumentDamage => {}
+        match damage_level {
+            RepaintDocumentDamage | ReflowDocumentDamage | RestyleDirtyDocumentDamage => {}
             MatchSelectorsDocumentDamage => {
                 do profile(time::LayoutSelectorMatchCategory, self.profiler_chan.clone()) {
-                    node.restyle_subtree(self.css_select_ctx);
+                    node.restyle_subtree(self.css_select_ctx,
+                                         &self.stylist,
+                                         &mut self.animations,
+                                         &mut self.prefetcher);
+                }
+            }
+            RestyleStatesDocumentDamage => {
+                let state_changes: &~[(AbstractNode<LayoutView>, ElementState)] = unsafe {
+                    transmute(&data.damage.state_changes)
+                };
+                let restyled_elements: &~[AbstractNode<LayoutView>] = unsafe {
+                    transmute(&data.damage.restyled_elements)
+                };
+                do profile(time::LayoutSelectorMatchCategory, self.profiler_chan.clone()) {
+                    restyle_state_changes(state_changes.as_slice(),
+                                          restyled_elements.as_slice(),
+                                          self.css_select_ctx,
+                                          &self.stylist,
+                                          &mut self.animations,
+                                          &mut self.prefetcher);
+                }
+            }
+        }
+
+        // Restyle the nodes that script has changed, along with the elements on the way down to
+        // them that no restyle has reached. Only the dirty parts of the tree are walked; an
+        // element that is still unstyled after that gets no boxes.
+        match damage_level {
+            MatchSelectorsDocumentDamage => {}
+            _ => {
+                do profile(time::LayoutSelectorMatchCategory, self.profiler_chan.clone()) {
+                    restyle_dirty_subtrees(*node,
+                                           self.css_select_ctx,
+                                           &self.stylist,
+                                           &mut self.animations,
+                                           &mut self.prefetcher);
                 }
             }
         }
@@ -256,9 +478,11 @@ impl LayoutTask {
             let mut damage = do flow.with_base |base| {
                 base.restyle_damage
             };
+            let containment = flow.containment();
             for child in flow.child_iter() {
                 do child.with_base |child_base| {
-                    damage.union_in_place(child_base.restyle_damage);
+                    damage.union_in_place(child_base.restyle_damage
+                                                    .propagate_up_through(containment));
                 }
             }
             do flow.with_mut_base |base| {
@@ -273,85 +497,198 @@ impl LayoutTask {
         // Perform the primary layout passes over the flow tree to compute the locations of all
         // the boxes.
         do profile(time::LayoutMainCategory, self.profiler_chan.clone()) {
-            do layout_root.each_postorder_prune(|f| f.restyle_damage().lacks(BubbleWidths)) |flow| {
-                flow.bubble_widths(&mut layout_ctx);
+            // Damage doesn't propagate out of boxes with size containment, so the flows beneath
+            // an undamaged flow can still be damaged.
+            do layout_root.each_postorder |flow| {
+                if flow.restyle_damage().has(BubbleWidths) {
+                    flow.bubble_widths(&mut layout_ctx);
+                }
                 true
             };
 
-            // FIXME: We want to do
-            //     for flow in layout_root.traverse_preorder_prune(|f| f.restyle_damage().lacks(Reflow)) 
-            // but FloatContext values can't be reused, so we need to recompute them every time.
-            debug!("assigning widths");
-            do layout_root.each_preorder |flow| {
-                flow.assign_widths(&mut layout_ctx);
-                true
-            };
+            // The page is laid out again, once at most, if it turns out to need a vertical
+            // scrollbar when it didn't, or the other way around, since the scrollbar takes room
+            // from the width of the viewport.
+            let mut laid_out_again = false;
+            loop {
+                // FIXME: We want to do
+                //     for flow in layout_root.traverse_preorder_prune(|f| f.restyle_damage().lacks(Reflow)) 
+                // but FloatContext values can't be reused, so we need to recompute them every time.
+                debug!("assigning widths");
+                do layout_root.each_preorder |flow| {
+                    flow.assign_widths(&mut layout_ctx);
+                    true
+                };
 
-            // For now, this is an inorder traversal
-            // FIXME: prune this traversal as well
-            debug!("assigning height");
-            do layout_root.each_bu_sub_inorder |flow| {
-                flow.assign_height(&mut layout_ctx);
-                true
-            };
+                // For now, this is an inorder traversal
+                // FIXME: prune this traversal as well
+                debug!("assigning height");
+                do layout_root.each_bu_sub_inorder |flow| {
+                    flow.assign_height(&mut layout_ctx);
+                    true
+                };
+
+                let gutter = scrollbar_gutter(&layout_root, screen_size);
+                if gutter == self.scrollbar_gutter || laid_out_again {
+                    break
+                }
+                debug!("layout: laying out again with a scrollbar gutter of %?", gutter);
+                self.scrollbar_gutter = gutter;
+                layout_ctx.screen_size.size.width = screen_size.width - gutter;
+                laid_out_again = true;
+            }
         }
 
         // Build the display list if necessary, and send it to the renderer.
         if data.goal == ReflowForDisplay {
-            do profile(time::LayoutDispListBuildCategory, self.profiler_chan.clone()) {
-                let builder = DisplayListBuilder {
-                    ctx: &layout_ctx,
-                };
+            self.build_display_list(&mut layout_root, &layout_ctx, screen_size);
+
+            // Now that the blocks know where they are in the page, find those whose contents can
+            // be skipped, and lay out again if any of them are far from the viewport.
+            self.lazy_content = find_lazy_content(&mut layout_root);
+            if needs_update(self.lazy_content, &Rect(self.viewport_origin, screen_size)) {
+                data.script_chan.send(SendEventMsg(self.id, ReflowEvent))
+            }
+        }
+        self.layout_root = Some(layout_root);
 
-                let display_list = ~Cell::new(DisplayList::<AbstractNode<()>>::new());
+        self.report_memory(node_count);
+        self.finish_reflow(data);
+    }
 
-                // TODO: Set options on the builder before building.
-                // TODO: Be smarter about what needs painting.
-                let root_pos = &layout_root.position().clone();
-                layout_root.each_preorder_prune(|flow| {  
-                    flow.build_display_list(&builder, root_pos, display_list) 
-                }, |_| { true } );
+    /// Reports how many nodes the document has, and how many images and fonts it holds and how
+    /// much memory they take, under `documents/<pipeline>/`, for `about:memory`.
+    ///
+    /// FIXME: The memory that fonts take isn't known, only how many are loaded.
+    fn report_memory(&self, node_count: uint) {
+        let report = |name: &str, value: uint| {
+            let path = fmt!("%s%s", memory_report_prefix(self.id), name);
+            self.profiler_chan.send(MemoryReportMsg(path, value))
+        };
+        let images = self.local_image_cache.memory_usage();
+        report("dom/nodes", node_count);
+        report("images/decoded", images.decoded_images);
+        report("images/decoded-bytes", images.decoded_bytes);
+        report("images/evicted", images.evicted_images);
+        report("fonts/loaded", self.font_ctx.font_count());
+    }
 
-                let root_size = do layout_root.with_base |base| {
-                    base.position.size
-                };
+    /// Builds the display list of a laid out flow tree and sends it to the renderer, advancing the
+    /// running transitions first, so that it paints the values they have reached.
+    fn build_display_list(&mut self,
+                          layout_root: &mut FlowContext,
+                          layout_ctx: &LayoutContext,
+                          screen_size: Size2D<Au>) {
+        self.animations.tick(precise_time_s());
+
+        do profile(time::LayoutDispListBuildCategory, self.profiler_chan.clone()) {
+            let builder = DisplayListBuilder {
+                ctx: layout_ctx,
+            };
 
-                let display_list = Arc::new(display_list.take());
+            let display_list = ~Cell::new(DisplayList::<AbstractNode<()>>::new());
 
-                for i in range(0,display_list.get().list.len()) {
-                    let node: AbstractNode<LayoutView> = unsafe {
-                        transmute(display_list.get().list[i].base().extra)
-                    };
+            // TODO: Set options on the builder before building.
+            // TODO: Be smarter about what needs painting.
+            let root_pos = &layout_root.position().clone();
+            layout_root.each_preorder_prune(|flow| {  
+                flow.build_display_list(&builder, root_pos, display_list) 
+            }, |_| { true } );
 
-                    do node.write_layout_data |layout_data| {
-                        layout_data.boxes.display_list = Some(display_list.clone());
-
-                        if layout_data.boxes.range.is_none() {
-                            debug!("Creating initial range for node");
-                            layout_data.boxes.range = Some(Range::new(i,1));
-                        } else {
-                            debug!("Appending item to range");
-                            unsafe {
-                                let old_node: AbstractNode<()> = transmute(node);
-                                assert!(old_node == display_list.get().list[i-1].base().extra,
-                                "Non-contiguous arrangement of display items");
-                            }
+            let root_size = do layout_root.with_base |base| {
+                base.position.size
+            };
 
-                            layout_data.boxes.range.unwrap().extend_by(1);
+            let display_list = Arc::new(display_list.take());
+
+            let mut fixed_layers = ~[];
+            build_fixed_layers(layout_root, &builder, &self.viewport_origin, &mut fixed_layers);
+            let fixed_layers = Arc::new(fixed_layers);
+
+            for i in range(0,display_list.get().list.len()) {
+                let node: AbstractNode<LayoutView> = unsafe {
+                    transmute(display_list.get().list[i].base().extra)
+                };
+
+                do node.write_layout_data |layout_data| {
+                    layout_data.boxes.display_list = Some(display_list.clone());
+
+                    if layout_data.boxes.range.is_none() {
+                        debug!("Creating initial range for node");
+                        layout_data.boxes.range = Some(Range::new(i,1));
+                    } else {
+                        debug!("Appending item to range");
+                        unsafe {
+                            let old_node: AbstractNode<()> = transmute(node);
+                            assert!(old_node == display_list.get().list[i-1].base().extra,
+                            "Non-contiguous arrangement of display items");
                         }
+
+                        layout_data.boxes.range.unwrap().extend_by(1);
                     }
                 }
+            }
 
-                let render_layer = RenderLayer {
-                    display_list: display_list.clone(),
-                    size: Size2D(root_size.width.to_nearest_px() as uint,
-                                 root_size.height.to_nearest_px() as uint)
-                };
+            // The `scroll-behavior`, `overscroll-behavior` and scrollbar styles of the root
+            // element apply to the viewport.
+            let (smooth_scroll, overscroll_behavior, scrollbar_style) = match *layout_root {
+                BlockFlow(ref block) => {
+                    let default = (false,
+                                   (OverscrollAuto, OverscrollAuto),
+                                   ScrollbarStyle::default());
+                    block.box.map_default(default, |box| {
+                        let scrollbar_style = ScrollbarStyle {
+                            width: box.scrollbar_width(),
+                            colors: box.scrollbar_colors(),
+                        };
+                        (box.smooth_scroll(), box.overscroll_behavior(), scrollbar_style)
+                    })
+                }
+                _ => (false, (OverscrollAuto, OverscrollAuto), ScrollbarStyle::default()),
+            };
+
+            let render_layer = RenderLayer {
+                display_list: display_list.clone(),
+                fixed_layers: fixed_layers.clone(),
+                size: Size2D(root_size.width.to_nearest_px() as uint,
+                             root_size.height.to_nearest_px() as uint),
+                scroll_snap: find_snap_positions(layout_root, screen_size),
+                hit_test_data: build_hit_test_data(layout_root),
+                smooth_scroll: smooth_scroll,
+                overscroll_behavior: overscroll_behavior,
+                scrollbar_style: scrollbar_style,
+            };
+
+            self.display_list = Some(display_list.clone());
+            self.fixed_layers = Some(fixed_layers);
+
+            self.render_chan.send(RenderMsg(render_layer));
+        } // time(layout: display list building)
+    }
 
-                self.display_list = Some(display_list.clone());
+    /// Asks script to repaint the running transitions when the next frame is due, unless it has
+    /// been asked already and the time hasn't come yet.
+    fn schedule_animation_tick(&mut self) {
+        let now = precise_time_s();
+        if now < self.next_animation_tick {
+            return
+        }
+        self.next_animation_tick = now + (ANIMATION_TICK_INTERVAL as float) / 1000.0;
 
-                self.render_chan.send(RenderMsg(render_layer));
-            } // time(layout: display list building)
+        let tm = Cell::new(Timer::new().unwrap());
+        let script_chan = self.script_chan.clone();
+        let id = self.id;
+        do spawn {
+            let mut tm = tm.take();
+            tm.sleep(ANIMATION_TICK_INTERVAL);
+            script_chan.send(SendEventMsg(id, AnimationTickEvent));
+        }
+    }
+
+    /// Tells script that a reflow is done, and keeps the running transitions going.
+    fn finish_reflow(&mut self, data: &Reflow) {
+        if data.goal == ReflowForDisplay && self.animations.is_running() {
+            self.schedule_animation_tick()
         }
 
         // Tell script that we're done.
@@ -372,39 +709,6 @@ impl LayoutTask {
                     transmute(node)
                 };
 
-                fn box_for_node(node: AbstractNode<LayoutView>) -> Option<Rect<Au>> {
-                    do node.read_layout_data |layout_data| {
-                        match (layout_data.boxes.display_list.clone(), layout_data.boxes.range) {
-                            (Some(display_list), Some(range)) => {
-                                let mut rect: Option<Rect<Au>> = None;
-                                for i in range.eachi() {
-                                    rect = match rect {
-                                        Some(acc) => {
-                                            Some(acc.union(&display_list.get().list[i].bounds()))
-                                        }
-                                        None => Some(display_list.get().list[i].bounds())
-                                    }
-                                }
-                                rect
-                            }
-                            _ => {
-                                let mut acc: Option<Rect<Au>> = None;
-                                for child in node.children() {
-                                    let rect = box_for_node(child);
-                                    match rect {
-                                        None => loop,
-                                        Some(rect) => acc = match acc {
-                                            Some(acc) =>  Some(acc.union(&rect)),
-                                            None => Some(rect)
-                                        }
-                                    }
-                                }
-                                acc
-                            }
-                        }
-                    }
-                }
-
                 let rect = box_for_node(node).unwrap_or_default(Rect(Point2D(Au(0), Au(0)),
                                                                      Size2D(Au(0), Au(0))));
                 reply_chan.send(ContentBoxResponse(rect))
@@ -415,65 +719,75 @@ impl LayoutTask {
                     transmute(node)
                 };
 
-                fn boxes_for_node(node: AbstractNode<LayoutView>,
-                                  boxes: ~[Rect<Au>]) -> ~[Rect<Au>] {
-                    let boxes = Cell::new(boxes);
-                    do node.read_layout_data |layout_data| {
-                        let mut boxes = boxes.take();
-                        match (layout_data.boxes.display_list.clone(), layout_data.boxes.range) {
-                            (Some(display_list), Some(range)) => {
-                                for i in range.eachi() {
-                                    boxes.push(display_list.get().list[i].bounds());
-                                }
-                            }
-                            _ => {
-                                for child in node.children() {
-                                    boxes = boxes_for_node(child, boxes);
-                                }
-                            }
-                        }
-                        boxes
-                    }
-                }
-
                 let mut boxes = ~[];
                 boxes = boxes_for_node(node, boxes);
                 reply_chan.send(ContentBoxesResponse(boxes))
             }
+            RangeBoxesQuery(nodes, reply_chan) => {
+                let mut boxes = ~[];
+                for &node in nodes.iter() {
+                    // FIXME: Isolate this transmutation into a single "bridge" module.
+                    let node: AbstractNode<LayoutView> = unsafe {
+                        transmute(node)
+                    };
+                    boxes = boxes_for_node(node, boxes);
+                }
+                reply_chan.send(ContentBoxesResponse(boxes))
+            }
             HitTestQuery(_, point, reply_chan) => {
-                let response = {
-                    match self.display_list {
-                        Some(ref list) => {
-                            let display_list = list.get();
-                            let (x, y) = (Au::from_frac_px(point.x as float),
-                                    Au::from_frac_px(point.y as float));
-                            let mut resp = Err(());
-                            // iterate in reverse to ensure we have the most recently painted render box
-                            for display_item in display_list.list.rev_iter() {
-                                let bounds = display_item.bounds();
-                                // TODO this check should really be performed by a method of DisplayItem
-                                if x <= bounds.origin.x + bounds.size.width &&
-                                    bounds.origin.x <= x &&
-                                        y < bounds.origin.y + bounds.size.height &&
-                                        bounds.origin.y <  y {
-                                            let node: AbstractNode<LayoutView> = unsafe {
-                                                transmute(display_item.base().extra)
-                                            };
-                                            resp = Ok(HitTestResponse(node));
-                                            break;
-                                        }
+                let (x, y) = (Au::from_css_px(CSSPixel(point.x as float)),
+                              Au::from_css_px(CSSPixel(point.y as float)));
+                // The fixed layers are drawn above the page, where they are in the viewport.
+                let mut fixed_hit = None;
+                match self.fixed_layers {
+                    Some(ref layers) => {
+                        for layer in layers.get().rev_iter() {
+                            let layer_x = x - self.viewport_origin.x - layer.viewport_position.x +
+                                layer.bounds.origin.x;
+                            let layer_y = y - self.viewport_origin.y - layer.viewport_position.y +
+                                layer.bounds.origin.y;
+                            fixed_hit = hit_test(&layer.list, layer_x, layer_y);
+                            if fixed_hit.is_some() {
+                                break
                             }
-                            resp
                         }
-                        None => {
-                            error!("Can't hit test: no display list");
-                            Err(())
-                        },
+                    }
+                    None => {}
+                }
+                let response = match (fixed_hit, &self.display_list) {
+                    (Some(node), _) => Ok(HitTestResponse(node)),
+                    (None, &Some(ref list)) => {
+                        match hit_test(list.get(), x, y) {
+                            Some(node) => Ok(HitTestResponse(node)),
+                            None => Err(()),
+                        }
+                    }
+                    (None, &None) => {
+                        error!("Can't hit test: no display list");
+                        Err(())
                     }
                 };
 
                 reply_chan.send(response)
             }
+            TextMetricsQuery(font, text, reply_chan) => {
+                reply_chan.send(self.font_ctx.measure_text(&font, text))
+            }
+            ComputedStyleQuery(node, property, reply_chan) => {
+                // FIXME: Isolate this transmutation into a single "bridge" module.
+                let node: AbstractNode<LayoutView> = unsafe {
+                    transmute(node)
+                };
+
+                let styled = node.is_element() &&
+                    node.read_layout_data(|data| data.style.is_some());
+                let value = if styled {
+                    resolved_value(node, property, box_for_node(node))
+                } else {
+                    None
+                };
+                reply_chan.send(value)
+            }
         }
     }
 
@@ -492,7 +806,7 @@ impl LayoutTask {
         let f: @fn() -> ~fn(ImageResponseMsg) = || {
             let script_chan = script_chan.clone();
             let f: ~fn(ImageResponseMsg) = |_| {
-                script_chan.send(SendEventMsg(id.clone(), ReflowEvent))
+                script_chan.send(SendEventMsg(id.clone(), ImageLoadedEvent))
             };
             f
         };
@@ -500,3 +814,116 @@ impl LayoutTask {
     }
 }
 
+
+/// Returns the bounds of the boxes of a node, or of its descendants if it has none itself.
+fn box_for_node(node: AbstractNode<LayoutView>) -> Option<Rect<Au>> {
+    do node.read_layout_data |layout_data| {
+        match (layout_data.boxes.display_list.clone(), layout_data.boxes.range) {
+            (Some(display_list), Some(range)) => {
+                let mut rect: Option<Rect<Au>> = None;
+                for i in range.eachi() {
+                    rect = match rect {
+                        Some(acc) => {
+                            Some(acc.union(&display_list.get().list[i].bounds()))
+                        }
+                        None => Some(display_list.get().list[i].bounds())
+                    }
+                }
+                rect
+            }
+            _ => {
+                let mut acc: Option<Rect<Au>> = None;
+                for child in node.children() {
+                    let rect = box_for_node(child);
+                    match rect {
+                        None => loop,
+                        Some(rect) => acc = match acc {
+                            Some(acc) =>  Some(acc.union(&rect)),
+                            None => Some(rect)
+                        }
+                    }
+                }
+                acc
+            }
+        }
+    }
+}
+
+/// Adds the bounds of the display items of `node` to `boxes`, or those of its descendants if it
+/// has none of its own.
+fn boxes_for_node(node: AbstractNode<LayoutView>, boxes: ~[Rect<Au>]) -> ~[Rect<Au>] {
+    let boxes = Cell::new(boxes);
+    do node.read_layout_data |layout_data| {
+        let mut boxes = boxes.take();
+        match (layout_data.boxes.display_list.clone(), layout_data.boxes.range) {
+            (Some(display_list), Some(range)) => {
+                for i in range.eachi() {
+                    boxes.push(display_list.get().list[i].bounds());
+                }
+            }
+            _ => {
+                for child in node.children() {
+                    boxes = boxes_for_node(child, boxes);
+                }
+            }
+        }
+        boxes
+    }
+}
+
+/// Returns the node whose display item in `display_list` was painted last at the given point, if
+/// any.
+fn hit_test(display_list: &DisplayList<AbstractNode<()>>, x: Au, y: Au)
+            -> Option<AbstractNode<LayoutView>> {
+    // iterate in reverse to ensure we have the most recently painted render box
+    for display_item in display_list.list.rev_iter() {
+        let bounds = display_item.bounds();
+        // TODO this check should really be performed by a method of DisplayItem
+        if x <= bounds.origin.x + bounds.size.width &&
+            bounds.origin.x <= x &&
+                y < bounds.origin.y + bounds.size.height &&
+                bounds.origin.y <  y {
+                    return Some(unsafe { transmute(display_item.base().extra) })
+                }
+    }
+    None
+}
+
+/// Builds the fixed layers of the blocks with `position: fixed` in the flow tree under `flow`,
+/// in tree order, so that later ones are drawn above earlier ones.
+///
+/// FIXME: Fixed layers are drawn above the rest of the page whatever their `z-index`, and
+/// `getBoundingClientRect` doesn't find the boxes in them.
+fn build_fixed_layers(flow: &mut FlowContext,
+                      builder: &DisplayListBuilder,
+                      viewport_origin: &Point2D<Au>,
+                      layers: &mut ~[FixedLayer<AbstractNode<()>>]) {
+    match *flow {
+        BlockFlow(ref mut block) => {
+            if block.is_fixed() {
+                layers.push(block.build_fixed_layer(builder, viewport_origin));
+            }
+        }
+        _ => {}
+    }
+    for child in flow.child_iter() {
+        build_fixed_layers(child, builder, viewport_origin, layers);
+    }
+}
+
+/// Returns the width that the vertical scrollbar of the viewport takes from it: its thickness, as
+/// the `scrollbar-width` of the root element says, if the laid out page overflows the viewport.
+fn scrollbar_gutter(layout_root: &FlowContext, screen_size: Size2D<Au>) -> Au {
+    let height = do layout_root.with_base |base| {
+        base.position.size.height
+    };
+    if height <= screen_size.height {
+        return Au(0)
+    }
+    match *layout_root {
+        BlockFlow(ref block) => do block.box.map_default(Au(0)) |box| {
+            Au::from_px(box.scrollbar_width().thickness() as int)
+        },
+        _ => Au(0),
+    }
+}
@@ -7,14 +7,8 @@
 use std::num::Zero;
 use geom::side_offsets::SideOffsets2D;
 use gfx::geometry::Au;
-use newcss::complete::CompleteStyle;
-use newcss::units::{Length, Em, Px};
-use newcss::values::{CSSBorderWidth, CSSBorderWidthLength, CSSBorderWidthMedium};
-use newcss::values::{CSSBorderWidthThick, CSSBorderWidthThin, CSSFontSize, CSSFontSizeLength};
-use newcss::values::{CSSWidth, CSSWidthLength, CSSWidthPercentage, CSSWidthAuto};
-use newcss::values::{CSSHeight, CSSHeightLength, CSSHeightPercentage, CSSHeightAuto};
-use newcss::values::{CSSMargin, CSSMarginLength, CSSMarginPercentage, CSSMarginAuto};
-use newcss::values::{CSSPadding, CSSPaddingLength, CSSPaddingPercentage};
+use script::style::properties::ComputedValues;
+use script::style::properties::common_types::computed;
 /// Encapsulates the borders, padding, and margins, which we collectively call the "box model".
 pub struct BoxModel {
     border: SideOffsets2D<Au>,
@@ -24,14 +18,29 @@ pub struct BoxModel {
     content_box_width: Au,
 }
 
-fn from_length(length: Length, font_size: CSSFontSize) -> Au {
-    match length {
-        Px(v) => Au::from_frac_px(v),
-        Em(em) => {
-            match font_size {
-                CSSFontSizeLength(Px(v)) => Au::from_frac_px(em * v),
-                _ => fail!("expected non-relative font size")
-            }
+/// Converts a length computed by the new style system, which is in app units too, to an `Au`.
+/// Font-relative and viewport-relative units are already resolved in computed lengths.
+pub fn to_au(length: computed::Length) -> Au {
+    Au(*length as i32)
+}
+
+/// Which kinds of containment apply to a box, as the `contain` property gives them.
+#[deriving(Eq)]
+pub struct Containment {
+    /// The size of the box doesn't depend on its contents.
+    size: bool,
+    /// The layout of the contents of the box doesn't affect anything outside it.
+    layout: bool,
+    /// The contents of the box aren't painted outside its padding box.
+    paint: bool,
+}
+
+impl Containment {
+    pub fn none() -> Containment {
+        Containment {
+            size: false,
+            layout: false,
+            paint: false,
         }
     }
 }
@@ -43,27 +52,16 @@ pub enum MaybeAuto {
 }
 
 impl MaybeAuto {
-    pub fn from_margin(margin: CSSMargin, containing_width: Au, font_size: CSSFontSize) -> MaybeAuto {
-        match margin {
-            CSSMarginAuto => Auto,
-            CSSMarginPercentage(percent) => Specified(containing_width.scale_by(percent/100.0)),
-            CSSMarginLength(length) => Specified(from_length(length, font_size))
-        }
-    }
-
-    pub fn from_width(width: CSSWidth, containing_width: Au, font_size: CSSFontSize) -> MaybeAuto {
-        match width {
-            CSSWidthAuto => Auto,
-            CSSWidthPercentage(percent) => Specified(containing_width.scale_by(percent/100.0)),
-            CSSWidthLength(length) => Specified(from_length(length, font_size))
-        }
-    }
-
-    pub fn from_height(height: CSSHeight, cb_height: Au, font_size: CSSFontSize) -> MaybeAuto {
-        match height {
-            CSSHeightAuto => Auto,
-            CSSHeightPercentage(percent) => Specified(cb_height.scale_by(percent/100.0)),
-            CSSHeightLength(length) => Specified(from_length(length, font_size))
+    /// Resolves a computed margin, width or height against the size that its percentages are
+    /// relative to.
+    pub fn from_style(length: computed::LengthOrPercentageOrAuto, percentage_basis: Au)
+                      -> MaybeAuto {
+        match length {
+            computed::LPA_Auto => Auto,
+            computed::LPA_Percentage(percent) => {
+                Specified(percentage_basis.scale_by(percent / 100.0))
+            }
+            computed::LPA_Length(length) => Specified(to_au(length)),
         }
     }
 
@@ -95,20 +93,20 @@ impl Zero for BoxModel {
 }
 
 impl BoxModel {
-    /// Populates the box model parameters from the given computed style.
-    pub fn compute_borders(&mut self, style: CompleteStyle) {
-        // Compute the borders.
-        self.border.top = self.compute_border_width(style.border_top_width(), style.font_size());
-        self.border.right = self.compute_border_width(style.border_right_width(), style.font_size());
-        self.border.bottom = self.compute_border_width(style.border_bottom_width(), style.font_size());
-        self.border.left = self.compute_border_width(style.border_left_width(), style.font_size());
+    /// Populates the box model parameters from the given computed values. Borders whose style is
+    /// `none` have a computed width of zero.
+    pub fn compute_borders(&mut self, values: &ComputedValues) {
+        self.border.top = to_au(values.border_top_width);
+        self.border.right = to_au(values.border_right_width);
+        self.border.bottom = to_au(values.border_bottom_width);
+        self.border.left = to_au(values.border_left_width);
     }
 
-    pub fn compute_padding(&mut self, style: CompleteStyle, containing_width: Au) {
-        self.padding.top = self.compute_padding_length(style.padding_top(), containing_width, style.font_size());
-        self.padding.right = self.compute_padding_length(style.padding_right(), containing_width, style.font_size());
-        self.padding.bottom = self.compute_padding_length(style.padding_bottom(), containing_width, style.font_size());
-        self.padding.left = self.compute_padding_length(style.padding_left(), containing_width, style.font_size());
+    pub fn compute_padding(&mut self, values: &ComputedValues, containing_width: Au) {
+        self.padding.top = self.compute_padding_length(values.padding_top, containing_width);
+        self.padding.right = self.compute_padding_length(values.padding_right, containing_width);
+        self.padding.bottom = self.compute_padding_length(values.padding_bottom, containing_width);
+        self.padding.left = self.compute_padding_length(values.padding_left, containing_width);
     }
 
     pub fn noncontent_width(&self) -> Au {
@@ -127,20 +125,13 @@ impl BoxModel {
         self.margin.left + self.border.left + self.padding.left
     }
 
-    /// Helper function to compute the border width in app units from the CSS border width.
-    pub fn compute_border_width(&self, width: CSSBorderWidth, font_size: CSSFontSize) -> Au {
-        match width {
-            CSSBorderWidthLength(length) => from_length(length, font_size),
-            CSSBorderWidthThin => Au::from_px(1),
-            CSSBorderWidthMedium => Au::from_px(5),
-            CSSBorderWidthThick => Au::from_px(10),
-        }
-    }
-
-    pub fn compute_padding_length(&self, padding: CSSPadding, content_box_width: Au, font_size: CSSFontSize) -> Au {
+    pub fn compute_padding_length(&self,
+                                  padding: computed::LengthOrPercentage,
+                                  content_box_width: Au)
+                                  -> Au {
         match padding {
-            CSSPaddingLength(length) => from_length(length, font_size),
-            CSSPaddingPercentage(p) => content_box_width.scale_by(p/100.0)
+            computed::LP_Length(length) => to_au(length),
+            computed::LP_Percentage(p) => content_box_width.scale_by(p/100.0)
         }
     }
 }
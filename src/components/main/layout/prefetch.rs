@@ -0,0 +1,57 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Speculative fetching of the resources that styles refer to. As soon as restyling gives an
+//! element a style, the fonts of its `font-family` are loaded, rather than when the text boxes
+//! that use them are built, so that text is shaped with the fonts it asks for from the start.
+//! Mask images are fetched and decoded then too, rather than when the element is first painted.
+//!
+//! FIXME: Neither libcss nor the new style system supports `background-image`, so mask images
+//! are the only images fetched from styles. The images of `img` elements are fetched by the HTML
+//! parser.
+
+use css::node_style::StyledNode;
+use layout::box::element_font_style;
+
+use gfx::font::FontStyle;
+use gfx::font_context::FontContext;
+use script::dom::node::{AbstractNode, LayoutView};
+use servo_net::local_image_cache::LocalImageCache;
+use servo_util::url::make_url;
+
+/// Fetches the resources that the styles of elements refer to, each once.
+pub struct Prefetcher {
+    priv font_ctx: @mut FontContext,
+    /// Fetches each image once per round.
+    priv image_cache: @mut LocalImageCache,
+    /// The font styles whose fonts have been loaded.
+    priv fetched_fonts: ~[FontStyle],
+}
+
+impl Prefetcher {
+    pub fn new(font_ctx: @mut FontContext, image_cache: @mut LocalImageCache) -> Prefetcher {
+        Prefetcher {
+            font_ctx: font_ctx,
+            image_cache: image_cache,
+            fetched_fonts: ~[],
+        }
+    }
+
+    /// Starts fetching the resources that the style of an element refers to, unless they have
+    /// been already.
+    pub fn prefetch_for_element(&mut self, element: AbstractNode<LayoutView>) {
+        let font_style = element_font_style(element);
+        if !self.fetched_fonts.contains(&font_style) {
+            debug!("prefetch: loading the fonts of `%s`", font_style.families);
+            self.font_ctx.get_resolved_font_for_style(&font_style);
+            self.fetched_fonts.push(font_style);
+        }
+        // The stylist has resolved the URL already.
+        for url in element.computed_values().mask_image.iter() {
+            let url = make_url(url.clone(), None);
+            self.image_cache.prefetch(&url);
+            self.image_cache.decode(&url);
+        }
+    }
+}
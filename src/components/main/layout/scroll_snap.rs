@@ -0,0 +1,105 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Scroll snapping. The `scroll-snap-type` of the root element makes scrolling the viewport come
+//! to rest where a box with a `scroll-snap-align` is aligned in it, and layout records those
+//! positions for the compositor, which does the scrolling.
+//!
+//! FIXME: Only the viewport scrolls, so it's the only scroll container that snaps.
+
+use layout::flow::{BlockFlow, FlowContext};
+
+use geom::rect::Rect;
+use geom::size::Size2D;
+use gfx::geometry::Au;
+use servo_msg::compositor_msg::{ProximitySnap, ScrollSnapPositions, ScrollSnapStrictness};
+
+/// The values of the `scroll-snap-type` property.
+pub struct ScrollSnapType {
+    /// Whether scrolling snaps horizontally.
+    x: bool,
+    /// Whether scrolling snaps vertically.
+    y: bool,
+    strictness: ScrollSnapStrictness,
+}
+
+impl ScrollSnapType {
+    pub fn none() -> ScrollSnapType {
+        ScrollSnapType {
+            x: false,
+            y: false,
+            strictness: ProximitySnap,
+        }
+    }
+}
+
+/// Where a box is aligned in the viewport along an axis when scrolling snaps to it.
+#[deriving(Eq)]
+pub enum ScrollSnapAlign {
+    ScrollSnapAlignNone,
+    ScrollSnapAlignStart,
+    ScrollSnapAlignEnd,
+    ScrollSnapAlignCenter,
+}
+
+/// Returns the scroll position at which a box that spans from `start` to `end` along an axis is
+/// aligned in a viewport of the given length, or `None` if it doesn't snap along the axis.
+fn snap_position(align: ScrollSnapAlign, start: Au, end: Au, viewport: Au) -> Option<f32> {
+    let position = match align {
+        ScrollSnapAlignNone => return None,
+        ScrollSnapAlignStart => start,
+        ScrollSnapAlignEnd => end - viewport,
+        ScrollSnapAlignCenter => Au((*start + *end - *viewport) / 2),
+    };
+    Some(*position.to_css_px() as f32)
+}
+
+/// Finds the positions that scrolling the viewport snaps to. Must be called after the display
+/// list has been built, so that the flows know where they are in the page.
+pub fn find_snap_positions(root: &mut FlowContext, viewport: Size2D<Au>) -> ScrollSnapPositions {
+    let snap_type = match *root {
+        BlockFlow(ref block) => block.box.map_default(ScrollSnapType::none(), |box| {
+            box.scroll_snap_type()
+        }),
+        _ => ScrollSnapType::none(),
+    };
+    let mut positions = ScrollSnapPositions::none();
+    positions.strictness = snap_type.strictness;
+    if !snap_type.x && !snap_type.y {
+        return positions
+    }
+
+    do root.each_preorder |flow| {
+        match *flow {
+            BlockFlow(ref block) => {
+                for &box in block.box.iter() {
+                    let (block_align, inline_align) = box.scroll_snap_align();
+                    let area: Rect<Au> = box.position().translate(&block.common.abs_position);
+                    if snap_type.x {
+                        for &x in snap_position(inline_align,
+                                                area.origin.x,
+                                                area.origin.x + area.size.width,
+                                                viewport.width).iter() {
+                            positions.x.push(x)
+                        }
+                    }
+                    if snap_type.y {
+                        for &y in snap_position(block_align,
+                                                area.origin.y,
+                                                area.origin.y + area.size.height,
+                                                viewport.height).iter() {
+                            positions.y.push(y)
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        true
+    };
+    debug!("scroll snap: %u horizontal and %u vertical positions",
+           positions.x.len(),
+           positions.y.len());
+    positions
+}
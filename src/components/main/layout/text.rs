@@ -4,17 +4,25 @@
 
 //! Text layout.
 
+use std::uint;
 use std::vec;
 
-use gfx::text::text_run::TextRun;
-use gfx::text::util::{CompressWhitespaceNewline, transform_text};
+use gfx::font::{FontFeature, FontGroup, FontStyle};
+use gfx::text::hyphenation::SOFT_HYPHEN;
+use gfx::text::line_break::WordBreak;
+use gfx::text::script::TextDirection;
+use gfx::text::text_run::{TabSize, TextRun};
+use gfx::text::util::{TextTransformUppercase, apply_text_transform, small_caps_ranges};
+use gfx::text::util::{CompressionMode, collapse_whitespace, true_type_tag};
 use layout::box::{RenderBox, RenderBoxBase, TextRenderBox};
 use layout::box::{TextRenderBoxClass, UnscannedTextRenderBoxClass};
 use layout::context::LayoutContext;
 use layout::flow::FlowContext;
-use newcss::values::{CSSTextDecoration, CSSTextDecorationUnderline};
 use servo_util::range::Range;
 
+/// The size of synthesized small capitals, relative to the capitals of their font.
+static SMALL_CAPS_SCALE: float = 0.7;
+
 
 /// Creates a TextRenderBox from a range and a text run.
 pub fn adapt_textbox_with_range(mut base: RenderBoxBase, run: @TextRun, range: Range)
@@ -23,7 +31,7 @@ pub fn adapt_textbox_with_range(mut base: RenderBoxBase, run: @TextRun, range: R
            run.char_len(),
            range.begin(),
            range.length(),
-           run.text,
+           run.text.as_slice(),
            run.char_len());
 
     assert!(range.begin() < run.char_len());
@@ -54,6 +62,155 @@ impl UnscannedMethods for RenderBox {
     }
 }
 
+/// Inserts soft hyphens at the hyphenation points of the words in `text`, if `box` asks for
+/// automatic hyphenation and there are hyphenation patterns for its language. With
+/// `hyphens: none`, the soft hyphens in the text itself are removed instead, so that lines aren't
+/// broken at them.
+fn hyphenate(ctx: &LayoutContext, box: RenderBox, text: ~str) -> ~str {
+    if !box.breaks_at_soft_hyphens() {
+        return text.iter().filter(|&ch| ch != SOFT_HYPHEN).collect()
+    }
+    if !box.hyphenates_automatically() {
+        return text
+    }
+    let lang = box.nearest_ancestor_element().language();
+    match ctx.hyphenators.find(lang.map(|lang| lang.as_slice())) {
+        Some(hyphenator) => hyphenator.insert_soft_hyphens(text),
+        None => text,
+    }
+}
+
+/// A text run made for the characters `range` of the text of a clump.
+struct RunSegment {
+    run: @TextRun,
+    range: Range,
+    /// For each character of the run, the character of `range` it was made from, or empty if they
+    /// line up.
+    offsets: ~[uint],
+}
+
+impl RunSegment {
+    /// Returns the range of the run made from the characters `range` of the text of the clump,
+    /// which must be within the range of this segment.
+    fn run_range(&self, range: &Range) -> Range {
+        let begin = range.begin() - self.range.begin();
+        let end = range.end() - self.range.begin();
+        if self.offsets.is_empty() {
+            return Range::new(begin, end - begin)
+        }
+        let run_length = self.offsets.len();
+        let run_begin = self.offsets.iter().position(|&o| o >= begin).unwrap_or_default(run_length);
+        let run_end = self.offsets.iter().position(|&o| o >= end).unwrap_or_default(run_length);
+        Range::new(run_begin, run_end - run_begin)
+    }
+}
+
+/// Creates the text runs for the text of a clump, whose characters came from the characters of
+/// its boxes at `source_offsets`. This is a single run, unless the text is in small caps and its
+/// font has no small capitals of its own: then its lowercase letters are made into runs of
+/// capitals of a smaller size, between runs of the rest of the text.
+fn create_runs(ctx: &LayoutContext,
+               font_style: &FontStyle,
+               text: ~str,
+               direction: TextDirection,
+               tab_size: TabSize,
+               word_break: WordBreak,
+               source_offsets: ~[uint])
+               -> ~[RunSegment] {
+    // TODO(#177): Text run creation must account for the renderability of text by font group
+    // fonts. This is probably achieved by creating the font group above and then letting
+    // `FontGroup` decide which `Font` to stick into the text run.
+    let fontgroup = ctx.font_ctx.get_resolved_font_for_style(font_style);
+    if !font_style.small_caps {
+        return ~[single_run(fontgroup, text, direction, tab_size, word_break, source_offsets)]
+    }
+
+    let smcp = true_type_tag('s', 'm', 'c', 'p');
+    if fontgroup.fonts[0].has_feature(smcp) {
+        // The settings of `font-feature-settings` come after this, so they can turn it off.
+        let mut style = font_style.clone();
+        style.features.unshift(FontFeature {
+            tag: smcp,
+            value: 1,
+        });
+        let fontgroup = ctx.font_ctx.get_resolved_font_for_style(&style);
+        return ~[single_run(fontgroup, text, direction, tab_size, word_break, source_offsets)]
+    }
+
+    let mut small_style = font_style.clone();
+    small_style.pt_size = font_style.pt_size * SMALL_CAPS_SCALE;
+    let small_fontgroup = ctx.font_ctx.get_resolved_font_for_style(&small_style);
+    let mut segments = ~[];
+    let ranges = small_caps_ranges(text);
+    for &(range, lowercase) in ranges.iter() {
+        let slice = text.slice_chars(range.begin(), range.end());
+        let (run_text, offsets, group) = if lowercase {
+            let lang = font_style.lang.map(|lang| lang.as_slice());
+            let (capitals, offsets, _) = apply_text_transform(slice,
+                                                              TextTransformUppercase,
+                                                              lang,
+                                                              false);
+            (capitals, offsets, small_fontgroup)
+        } else {
+            (slice.to_owned(), ~[], fontgroup)
+        };
+        let mut run = group.create_textrun(run_text, direction, tab_size, word_break);
+
+        // Map the characters of the run back to the characters of the boxes.
+        let run_source_offsets: ~[uint] = do vec::from_fn(run.char_len()) |i| {
+            let offset = range.begin() + if offsets.is_empty() { i } else { offsets[i] };
+            if source_offsets.is_empty() { offset } else { source_offsets[offset] }
+        };
+        if run_source_offsets.iter().enumerate().any(|(i, &offset)| offset != i) {
+            run.set_source_offsets(run_source_offsets);
+        }
+
+        segments.push(RunSegment {
+            run: @run,
+            range: range,
+            offsets: offsets,
+        });
+    }
+    segments
+}
+
+fn single_run(fontgroup: @FontGroup,
+              text: ~str,
+              direction: TextDirection,
+              tab_size: TabSize,
+              word_break: WordBreak,
+              source_offsets: ~[uint])
+              -> RunSegment {
+    let length = text.char_len();
+    let mut run = fontgroup.create_textrun(text, direction, tab_size, word_break);
+    run.set_source_offsets(source_offsets);
+    RunSegment {
+        run: @run,
+        range: Range::new(0, length),
+        offsets: ~[],
+    }
+}
+
+/// Makes a text box with the given base for each part of the characters `range` of the text of
+/// the clump that is in a different run.
+fn push_text_boxes(base: &RenderBoxBase,
+                   segments: &[RunSegment],
+                   range: &Range,
+                   out_boxes: &mut ~[RenderBox]) {
+    for segment in segments.iter() {
+        let begin = uint::max(range.begin(), segment.range.begin());
+        let end = uint::min(range.end(), segment.range.end());
+        if begin >= end {
+            loop
+        }
+        let run_range = segment.run_range(&Range::new(begin, end - begin));
+        if run_range.length() > 0 {
+            let new_box = @mut adapt_textbox_with_range(*base, segment.run, run_range);
+            out_boxes.push(TextRenderBoxClass(new_box));
+        }
+    }
+}
+
 /// A stack-allocated object for scanning an inline flow into `TextRun`-containing `TextBox`es.
 struct TextRunScanner {
     clump: Range,
@@ -75,18 +232,32 @@ impl TextRunScanner {
             debug!("TextRunScanner: scanning %u boxes for text runs...", inline.boxes.len());
         }
 
-        let mut last_whitespace = true;
+        // Whitespace collapses across all the text of the flow, not just within clumps, since a
+        // sequence of spaces can span text of different styles.
+        let collapsed = {
+            let pieces: ~[Option<(~str, CompressionMode)>] =
+                do flow.imm_inline().boxes.iter().map |box| {
+                    match *box {
+                        UnscannedTextRenderBoxClass(*) => {
+                            Some((box.raw_text(), box.white_space().compression_mode()))
+                        }
+                        _ => None,
+                    }
+                }.collect();
+            collapse_whitespace(pieces)
+        };
+
         let mut out_boxes = ~[];
         for box_i in range(0, flow.imm_inline().boxes.len()) {
             debug!("TextRunScanner: considering box: %?", flow.imm_inline().boxes[box_i].debug_str());
             if box_i > 0 && !can_coalesce_text_nodes(flow.imm_inline().boxes, box_i-1, box_i) {
-                last_whitespace = self.flush_clump_to_list(ctx, flow, last_whitespace, &mut out_boxes);
+                self.flush_clump_to_list(ctx, flow, collapsed, &mut out_boxes);
             }
             self.clump.extend_by(1);
         }
         // handle remaining clumps
         if self.clump.length() > 0 {
-            self.flush_clump_to_list(ctx, flow, last_whitespace, &mut out_boxes);
+            self.flush_clump_to_list(ctx, flow, collapsed, &mut out_boxes);
         }
 
         debug!("TextRunScanner: swapping out boxes.");
@@ -117,24 +288,19 @@ impl TextRunScanner {
     /// necessary for correct painting order. Since we compress several leaf `RenderBox`es here,
     /// the mapping must be adjusted.
     ///
+    /// `collapsed` holds the text of each box of the flow with its whitespace collapsed.
+    ///
     /// N.B. `in_boxes` is passed by reference, since the old code used a `DVec`. The caller is
     /// responsible for swapping out the list. It is not clear to me (pcwalton) that this is still
     /// necessary.
     pub fn flush_clump_to_list(&mut self,
                                ctx: &LayoutContext,
                                flow: &mut FlowContext,
-                               last_whitespace: bool,
-                               out_boxes: &mut ~[RenderBox]) -> bool {
+                               collapsed: &[~str],
+                               out_boxes: &mut ~[RenderBox]) {
         let inline = flow.inline();
         let in_boxes = &inline.boxes;
 
-        fn has_underline(decoration: CSSTextDecoration) -> bool{
-            match decoration {
-                CSSTextDecorationUnderline => true,
-                _ => false
-            }
-        }
-
         assert!(self.clump.length() > 0);
 
         debug!("TextRunScanner: flushing boxes in range=%?", self.clump);
@@ -144,8 +310,6 @@ impl TextRunScanner {
             _ => false
         };
 
-        let mut new_whitespace = last_whitespace;
-
         match (is_singleton, is_text_clump) {
             (false, false) => {
                 fail!(~"WAT: can't coalesce non-text nodes in flush_clump_to_list()!")
@@ -156,50 +320,45 @@ impl TextRunScanner {
             },
             (true, true)  => {
                 let old_box = in_boxes[self.clump.begin()];
-                let text = old_box.raw_text();
+                let text = collapsed[self.clump.begin()].clone();
                 let font_style = old_box.font_style();
-                let underline = has_underline(old_box.text_decoration());
+                let direction = old_box.direction();
+                let tab_size = old_box.tab_size();
+                let word_break = old_box.word_break();
 
-                // TODO(#115): Use the actual CSS `white-space` property of the relevant style.
-                let compression = CompressWhitespaceNewline;
-
-                let (transformed_text, whitespace) = transform_text(text, compression, last_whitespace);
-                new_whitespace = whitespace;
+                let transformed_text = hyphenate(ctx, old_box, text.clone());
+                let lang = font_style.lang.map(|lang| lang.as_slice());
+                let (transformed_text, source_offsets, _) =
+                    apply_text_transform(transformed_text, old_box.text_transform(), lang, false);
 
                 if transformed_text.len() > 0 {
-                    // TODO(#177): Text run creation must account for the renderability of text by
-                    // font group fonts. This is probably achieved by creating the font group above
-                    // and then letting `FontGroup` decide which `Font` to stick into the text run.
-                    let fontgroup = ctx.font_ctx.get_resolved_font_for_style(&font_style);
-                    let run = @fontgroup.create_textrun(transformed_text, underline);
+                    let range = Range::new(0, transformed_text.char_len());
+                    let segments = create_runs(ctx, &font_style, transformed_text, direction,
+                                               tab_size, word_break, source_offsets);
 
                     debug!("TextRunScanner: pushing single text box in range: %? (%?)", self.clump, text);
-                    let new_box = do old_box.with_base |old_box_base| {
-                        let range = Range::new(0, run.char_len());
-                        @mut adapt_textbox_with_range(*old_box_base, run, range)
-                    };
-
-                    out_boxes.push(TextRenderBoxClass(new_box));
+                    do old_box.with_base |old_box_base| {
+                        push_text_boxes(old_box_base, segments, &range, out_boxes);
+                    }
                 }
             },
             (false, true) => {
-                // TODO(#115): Use the actual CSS `white-space` property of the relevant style.
-                let compression = CompressWhitespaceNewline;
-
-                // First, transform/compress text of all the nodes.
-                let mut last_whitespace_in_clump = new_whitespace;
+                // First, transform the collapsed text of all the nodes.
+                let mut in_word = false;
+                let mut source_offsets = ~[];
                 let transformed_strs: ~[~str] = do vec::from_fn(self.clump.length()) |i| {
-                    // TODO(#113): We should be passing the compression context between calls to
-                    // `transform_text`, so that boxes starting and/or ending with whitespace can
-                    // be compressed correctly with respect to the text run.
                     let idx = i + self.clump.begin();
-                    let (new_str, new_whitespace) = transform_text(in_boxes[idx].raw_text(),
-                                                                   compression,
-                                                                   last_whitespace_in_clump);
-                    last_whitespace_in_clump = new_whitespace;
+                    let new_str = hyphenate(ctx, in_boxes[idx], collapsed[idx].clone());
+                    let lang = in_boxes[idx].nearest_ancestor_element().language();
+                    let (new_str, offsets, new_in_word) =
+                        apply_text_transform(new_str,
+                                             in_boxes[idx].text_transform(),
+                                             lang.map(|lang| lang.as_slice()),
+                                             in_word);
+                    in_word = new_in_word;
+                    source_offsets.push(offsets);
                     new_str
                 };
-                new_whitespace = last_whitespace_in_clump;
 
                 // Next, concatenate all of the transformed strings together, saving the new
                 // character indices.
@@ -213,22 +372,35 @@ impl TextRunScanner {
                     char_total += added_chars;
                 }
 
-                // Now create the run.
-                //
-                // TODO(#177): Text run creation must account for the renderability of text by
-                // font group fonts. This is probably achieved by creating the font group above
-                // and then letting `FontGroup` decide which `Font` to stick into the text run.
+                // Source offsets are relative to the text of each box, so they are only needed
+                // for the run if some box had its number of characters changed.
+                let run_source_offsets = if source_offsets.iter().all(|o| o.is_empty()) {
+                    ~[]
+                } else {
+                    let mut run_source_offsets = ~[];
+                    for (i, offsets) in source_offsets.iter().enumerate() {
+                        if offsets.is_empty() {
+                            run_source_offsets.push_all_move(range(0, new_ranges[i].length())
+                                                             .collect());
+                        } else {
+                            run_source_offsets.push_all(*offsets);
+                        }
+                    }
+                    run_source_offsets
+                };
+
+                // Now create the runs.
                 let font_style = in_boxes[self.clump.begin()].font_style();
-                let fontgroup = ctx.font_ctx.get_resolved_font_for_style(&font_style);
-                let underline = has_underline(in_boxes[self.clump.begin()].text_decoration());
+                let direction = in_boxes[self.clump.begin()].direction();
+                let tab_size = in_boxes[self.clump.begin()].tab_size();
+                let word_break = in_boxes[self.clump.begin()].word_break();
 
-                // TextRuns contain a cycle which is usually resolved by the teardown
-                // sequence. If no clump takes ownership, however, it will leak.
                 let clump = self.clump;
-                let run = if clump.length() != 0 && run_str.len() > 0 {
-                    Some(@TextRun::new(fontgroup.fonts[0], run_str, underline))
+                let segments = if clump.length() != 0 && run_str.len() > 0 {
+                    create_runs(ctx, &font_style, run_str, direction, tab_size, word_break,
+                                run_source_offsets)
                 } else {
-                    None
+                    ~[]
                 };
 
                 // Make new boxes with the run and adjusted text indices.
@@ -243,8 +415,7 @@ impl TextRunScanner {
                     }
 
                     do in_boxes[i].with_base |base| {
-                        let new_box = @mut adapt_textbox_with_range(*base, run.unwrap(), range);
-                        out_boxes.push(TextRenderBoxClass(new_box));
+                        push_text_boxes(base, segments, &range, out_boxes);
                     }
                 }
             }
@@ -270,7 +441,5 @@ impl TextRunScanner {
 
         let end = self.clump.end(); // FIXME: borrow checker workaround
         self.clump.reset(end, 0);
-
-        new_whitespace
     } // End of `flush_clump_to_list`.
 }
@@ -4,13 +4,14 @@
 
 use extra::url::Url;
 use compositing::CompositorChan;
+use css::select::DefaultStylesheets;
 use gfx::render_task::{RenderChan, RenderTask};
 use gfx::render_task::{PaintPermissionGranted, PaintPermissionRevoked};
 use gfx::render_task;
 use gfx::opts::Opts;
 use layout::layout_task::LayoutTask;
 use script::layout_interface::LayoutChan;
-use script::script_task::{ExecuteMsg, LoadMsg};
+use script::script_task::{AddDefaultStylesheetMsg, ExecuteMsg, FreezeMsg, LoadMsg, ThawMsg};
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId, SubpageId};
 use script::dom::node::AbstractNode;
 use script::script_task::{AttachLayoutMsg, NewLayoutInfo, ScriptTask, ScriptChan};
@@ -43,6 +44,7 @@ impl Pipeline {
                        image_cache_task: ImageCacheTask,
                        profiler_chan: ProfilerChan,
                        opts: Opts,
+                       default_stylesheets: DefaultStylesheets,
                        script_pipeline: &Pipeline,
                        size_future: Future<Size2D<uint>>) -> Pipeline {
         
@@ -62,6 +64,7 @@ impl Pipeline {
                            render_chan.clone(),
                            image_cache_task.clone(),
                            opts.clone(),
+                           default_stylesheets,
                            profiler_chan);
 
         let new_layout_info = NewLayoutInfo {
@@ -89,6 +92,7 @@ impl Pipeline {
                   resource_task: ResourceTask,
                   profiler_chan: ProfilerChan,
                   opts: Opts,
+                  default_stylesheets: DefaultStylesheets,
                   size: Future<Size2D<uint>>) -> Pipeline {
 
         let (script_port, script_chan) = special_stream!(ScriptChan);
@@ -103,7 +107,12 @@ impl Pipeline {
                            constellation_chan.clone(),
                            resource_task,
                            image_cache_task.clone(),
-                           size);
+                           size,
+                           opts.autoplay_policy,
+                           opts.slow_script_timeout,
+                           opts.save_page_file.clone(),
+                           default_stylesheets.texts(),
+                           profiler_chan.clone());
 
 
         RenderTask::create(id,
@@ -119,6 +128,7 @@ impl Pipeline {
                            render_chan.clone(),
                            image_cache_task,
                            opts.clone(),
+                           default_stylesheets,
                            profiler_chan);
         Pipeline::new(id,
                       subpage_id,
@@ -161,6 +171,22 @@ impl Pipeline {
         self.render_chan.send(PaintPermissionRevoked);
     }
 
+    /// Stops the timers of the page from running while it isn't shown.
+    pub fn freeze(&self) {
+        self.script_chan.send(FreezeMsg(self.id));
+    }
+
+    /// Lets the timers of the page run again once it is shown.
+    pub fn thaw(&self) {
+        self.script_chan.send(ThawMsg(self.id));
+    }
+
+    /// Adds a style sheet at the user agent origin, after the others, to the pages of the script
+    /// task, which restyles those that have loaded.
+    pub fn add_user_agent_stylesheet(&self, url: Url, text: ~str) {
+        self.script_chan.send(AddDefaultStylesheetMsg(self.id, url, text));
+    }
+
     pub fn reload(&mut self) {
         do self.url.clone().map_move() |url| {
             self.load(url);
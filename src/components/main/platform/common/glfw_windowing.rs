@@ -8,7 +8,9 @@ use windowing::{ApplicationMethods, WindowEvent, WindowMethods};
 use windowing::{IdleWindowEvent, ResizeWindowEvent, LoadUrlWindowEvent, MouseWindowEventClass};
 use windowing::{ScrollWindowEvent, ZoomWindowEvent, NavigationWindowEvent, FinishedWindowEvent};
 use windowing::{QuitWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent, MouseWindowMouseUpEvent};
-use windowing::{Forward, Back};
+use windowing::MouseWindowMoveEvent;
+use windowing::{Forward, Back, PickerWindowEvent, FocusWindowEvent, AccessKeyWindowEvent};
+use windowing::EditingWindowEvent;
 
 use alert::{Alert, AlertMethods};
 use std::libc::c_int;
@@ -17,6 +19,8 @@ use geom::point::Point2D;
 use geom::size::Size2D;
 use servo_msg::compositor_msg::{IdleRenderState, RenderState, RenderingRenderState};
 use servo_msg::compositor_msg::{FinishedLoading, Blank, Loading, PerformingLayout, ReadyState};
+use servo_msg::compositor_msg::PickerKind;
+use script::dom::event::{FocusNext, FocusPrevious, ActivateFocused, UndoCommand, RedoCommand};
 
 use glfw;
 
@@ -53,6 +57,12 @@ pub struct Window {
     ready_state: ReadyState,
     render_state: RenderState,
     throbber_frame: u8,
+
+    /// The access keys of the page, lowercased.
+    access_keys: ~[char],
+
+    /// The hook that decides whether to stop slow scripts, if the embedder has set one.
+    slow_script_hook: Option<@fn() -> bool>,
 }
 
 impl WindowMethods<Application> for Window {
@@ -76,6 +86,10 @@ impl WindowMethods<Application> for Window {
             ready_state: Blank,
             render_state: IdleRenderState,
             throbber_frame: 0,
+
+            access_keys: ~[],
+
+            slow_script_hook: None,
         };
 
         install_local_window(window);
@@ -101,6 +115,14 @@ impl WindowMethods<Application> for Window {
                 local_window().handle_mouse(button, action, x as i32, y as i32);
             }
         }
+        do window.glfw_window.set_cursor_pos_callback |win, x, y| {
+            //handle hidpi displays, since GLFW returns non-hi-def coordinates.
+            let (backing_size, _) = win.get_framebuffer_size();
+            let (window_size, _) = win.get_size();
+            let hidpi = (backing_size as f32) / (window_size as f32);
+            let point = Point2D(x as f32 * hidpi, y as f32 * hidpi);
+            local_window().event_queue.push(MouseWindowEventClass(MouseWindowMoveEvent(point)));
+        }
         do window.glfw_window.set_scroll_callback |win, x_offset, y_offset| {
             let dx = (x_offset as f32) * 30.0;
             let dy = (y_offset as f32) * 30.0;
@@ -170,6 +192,30 @@ impl WindowMethods<Application> for Window {
         let (window_size, _) = self.glfw_window.get_size();
         (backing_size as f32) / (window_size as f32)
     }
+
+    /// FIXME: There is no native picker yet, so the picker is cancelled right away.
+    fn open_picker(@mut self, kind: PickerKind, value: ~str) {
+        debug!("no picker for %? with value %s", kind, value);
+        self.event_queue.push(PickerWindowEvent(None));
+    }
+
+    fn set_access_keys(@mut self, keys: ~[char]) {
+        self.access_keys = keys;
+    }
+
+    fn stop_slow_script(@mut self) -> bool {
+        match self.slow_script_hook {
+            Some(hook) => hook(),
+            None => {
+                warn!("a script on this page was taking too long to run, and has been stopped");
+                true
+            }
+        }
+    }
+
+    fn set_slow_script_hook(@mut self, hook: @fn() -> bool) {
+        self.slow_script_hook = Some(hook);
+    }
 }
 
 impl Window {
@@ -209,42 +255,80 @@ impl Window {
             glfw::KEY_MINUS if mods & glfw::MOD_CONTROL != 0 => { // Ctrl--
                 self.event_queue.push(ZoomWindowEvent(0.90909090909));
             }
+            glfw::KEY_Z if mods & glfw::MOD_CONTROL != 0 && mods & glfw::MOD_SHIFT != 0 => {
+                self.event_queue.push(EditingWindowEvent(RedoCommand)); // Ctrl+Shift+Z
+            }
+            glfw::KEY_Z if mods & glfw::MOD_CONTROL != 0 => { // Ctrl+Z
+                self.event_queue.push(EditingWindowEvent(UndoCommand));
+            }
+            glfw::KEY_Y if mods & glfw::MOD_CONTROL != 0 => { // Ctrl+Y
+                self.event_queue.push(EditingWindowEvent(RedoCommand));
+            }
             glfw::KEY_BACKSPACE if mods & glfw::MOD_SHIFT != 0 => { // Shift-Backspace
                 self.event_queue.push(NavigationWindowEvent(Forward));
             }
             glfw::KEY_BACKSPACE => { // Backspace
                 self.event_queue.push(NavigationWindowEvent(Back));
             }
+            glfw::KEY_TAB if mods & glfw::MOD_SHIFT != 0 => { // Shift-Tab
+                self.event_queue.push(FocusWindowEvent(FocusPrevious));
+            }
+            glfw::KEY_TAB => self.event_queue.push(FocusWindowEvent(FocusNext)),
+            glfw::KEY_ENTER => self.event_queue.push(FocusWindowEvent(ActivateFocused)),
+            _ if mods & glfw::MOD_ALT != 0 => self.handle_access_key(key), // Alt+key
             _ => {}
         }
     }
 
+    /// Helper function to handle a key pressed with Alt, which may be an access key.
+    fn handle_access_key(&self, key: c_int) {
+        // GLFW gives letter and digit keys the ASCII codes of their uppercase characters.
+        let key = if key >= glfw::KEY_A && key <= glfw::KEY_Z {
+            (key - glfw::KEY_A) as u8 + 'a' as u8
+        } else if key >= glfw::KEY_0 && key <= glfw::KEY_9 {
+            key as u8
+        } else {
+            return
+        };
+        if self.access_keys.contains(&(key as char)) {
+            self.event_queue.push(AccessKeyWindowEvent(key as char));
+        }
+    }
+
     /// Helper function to handle a click
     fn handle_mouse(&self, button: c_int, action: c_int, x: c_int, y: c_int) {
         // FIXME(tkuehn): max pixel dist should be based on pixel density
         let max_pixel_dist = 10f;
-        let event = match action {
+        // GLFW numbers the secondary button before the middle one, unlike the DOM.
+        let dom_button = match button {
+            1 => 2,
+            2 => 1,
+            button => button as uint,
+        };
+        match action {
             glfw::PRESS => {
                 *self.mouse_down_point = Point2D(x, y);
                 *self.mouse_down_button = button;
-                MouseWindowMouseDownEvent(button as uint, Point2D(x as f32, y as f32))
+                let down_event = MouseWindowMouseDownEvent(dom_button,
+                                                           Point2D(x as f32, y as f32));
+                self.event_queue.push(MouseWindowEventClass(down_event));
             }
             glfw::RELEASE => {
+                let up_event = MouseWindowMouseUpEvent(dom_button, Point2D(x as f32, y as f32));
+                self.event_queue.push(MouseWindowEventClass(up_event));
                 if *self.mouse_down_button == button {
                     let pixel_dist = *self.mouse_down_point - Point2D(x, y);
                     let pixel_dist = ((pixel_dist.x * pixel_dist.x +
                                        pixel_dist.y * pixel_dist.y) as float).sqrt();
                     if pixel_dist < max_pixel_dist {
-                        let click_event = MouseWindowClickEvent(button as uint,
-                                                           Point2D(x as f32, y as f32));
+                        let click_event = MouseWindowClickEvent(dom_button,
+                                                                Point2D(x as f32, y as f32));
                         self.event_queue.push(MouseWindowEventClass(click_event));
                     }
                 }
-                MouseWindowMouseUpEvent(button as uint, Point2D(x as f32, y as f32))
             }
             _ => fail!("I cannot recognize the type of mouse action that occured. :-(")
-        };
-        self.event_queue.push(MouseWindowEventClass(event));
+        }
     }
 
     /// Helper function to pop up an alert box prompting the user to load a URL.
@@ -8,7 +8,8 @@ use windowing::{ApplicationMethods, WindowEvent, WindowMethods};
 use windowing::{IdleWindowEvent, ResizeWindowEvent, LoadUrlWindowEvent, MouseWindowEventClass};
 use windowing::{ScrollWindowEvent, ZoomWindowEvent, NavigationWindowEvent, FinishedWindowEvent};
 use windowing::{QuitWindowEvent, MouseWindowClickEvent, MouseWindowMouseDownEvent, MouseWindowMouseUpEvent};
-use windowing::{Forward, Back};
+use windowing::{Forward, Back, PickerWindowEvent, FocusWindowEvent, AccessKeyWindowEvent};
+use windowing::EditingWindowEvent;
 
 use alert::{Alert, AlertMethods};
 use std::libc::c_int;
@@ -16,9 +17,11 @@ use geom::point::Point2D;
 use geom::size::Size2D;
 use servo_msg::compositor_msg::{IdleRenderState, RenderState, RenderingRenderState};
 use servo_msg::compositor_msg::{FinishedLoading, Blank, Loading, PerformingLayout, ReadyState};
+use servo_msg::compositor_msg::PickerKind;
+use script::dom::event::{FocusNext, FocusPrevious, ActivateFocused, UndoCommand, RedoCommand};
 
-use glut::glut::{ACTIVE_CTRL, ACTIVE_SHIFT, DOUBLE, HAVE_PRECISE_MOUSE_WHEEL, WindowHeight};
-use glut::glut::WindowWidth;
+use glut::glut::{ACTIVE_ALT, ACTIVE_CTRL, ACTIVE_SHIFT, DOUBLE, HAVE_PRECISE_MOUSE_WHEEL};
+use glut::glut::{WindowHeight, WindowWidth};
 use glut::glut;
 
 static THROBBER: [char, ..8] = [ '⣾', '⣽', '⣻', '⢿', '⡿', '⣟', '⣯', '⣷' ];
@@ -53,6 +56,12 @@ pub struct Window {
     ready_state: ReadyState,
     render_state: RenderState,
     throbber_frame: u8,
+
+    /// The access keys of the page, lowercased.
+    access_keys: ~[char],
+
+    /// The hook that decides whether to stop slow scripts, if the embedder has set one.
+    slow_script_hook: Option<@fn() -> bool>,
 }
 
 impl WindowMethods<Application> for Window {
@@ -76,6 +85,10 @@ impl WindowMethods<Application> for Window {
             ready_state: Blank,
             render_state: IdleRenderState,
             throbber_frame: 0,
+
+            access_keys: ~[],
+
+            slow_script_hook: None,
         };
 
         let event_queue = window.event_queue;
@@ -87,6 +100,7 @@ impl WindowMethods<Application> for Window {
         do glut::keyboard_func |key, _, _| {
             window.handle_key(key)
         }
+        // FIXME: Pointer moves aren't sent, so `:hover` never matches in the GLUT window.
         do glut::mouse_func |button, state, x, y| {
             if button < 3 {
                 window.handle_mouse(button, state, x, y);
@@ -154,6 +168,30 @@ impl WindowMethods<Application> for Window {
         //FIXME: Do nothing in GLUT now.
     0f32
     }
+
+    /// FIXME: There is no native picker yet, so the picker is cancelled right away.
+    fn open_picker(@mut self, kind: PickerKind, value: ~str) {
+        debug!("no picker for %? with value %s", kind, value);
+        self.event_queue.push(PickerWindowEvent(None));
+    }
+
+    fn set_access_keys(@mut self, keys: ~[char]) {
+        self.access_keys = keys;
+    }
+
+    fn stop_slow_script(@mut self) -> bool {
+        match self.slow_script_hook {
+            Some(hook) => hook(),
+            None => {
+                warn!("a script on this page was taking too long to run, and has been stopped");
+                true
+            }
+        }
+    }
+
+    fn set_slow_script_hook(@mut self, hook: @fn() -> bool) {
+        self.slow_script_hook = Some(hook);
+    }
 }
 
 impl Window {
@@ -185,6 +223,17 @@ impl Window {
     fn handle_key(&self, key: u8) {
         debug!("got key: %?", key);
         let modifiers = glut::get_modifiers();
+        if (modifiers & ACTIVE_ALT) != 0 {
+            let key = if key >= 'A' as u8 && key <= 'Z' as u8 {
+                key - 'A' as u8 + 'a' as u8
+            } else {
+                key
+            };
+            if self.access_keys.contains(&(key as char)) {
+                self.event_queue.push(AccessKeyWindowEvent(key as char));
+                return
+            }
+        }
         match key {
             42 => self.load_url(),
             43 => self.event_queue.push(ZoomWindowEvent(1.1)),
@@ -199,6 +248,25 @@ impl Window {
                     self.event_queue.push(NavigationWindowEvent(Back));
                 }
             }
+            9 => {
+                if (modifiers & ACTIVE_SHIFT) != 0 {
+                    self.event_queue.push(FocusWindowEvent(FocusPrevious));
+                }
+                else {
+                    self.event_queue.push(FocusWindowEvent(FocusNext));
+                }
+            }
+            13 => self.event_queue.push(FocusWindowEvent(ActivateFocused)),
+            // GLUT gives Ctrl+letter as the control character of the letter.
+            26 => { // Ctrl+Z, or Ctrl+Shift+Z
+                if (modifiers & ACTIVE_SHIFT) != 0 {
+                    self.event_queue.push(EditingWindowEvent(RedoCommand));
+                }
+                else {
+                    self.event_queue.push(EditingWindowEvent(UndoCommand));
+                }
+            }
+            25 => self.event_queue.push(EditingWindowEvent(RedoCommand)), // Ctrl+Y
             _ => {}
         }
     }
@@ -207,28 +275,32 @@ impl Window {
     fn handle_mouse(&self, button: c_int, state: c_int, x: c_int, y: c_int) {
         // FIXME(tkuehn): max pixel dist should be based on pixel density
         let max_pixel_dist = 10f;
-        let event = match state {
+        // GLUT numbers the buttons as the DOM does.
+        let dom_button = button as uint;
+        match state {
             glut::MOUSE_DOWN => {
                 *self.mouse_down_point = Point2D(x, y);
                 *self.mouse_down_button = button;
-                MouseWindowMouseDownEvent(button as uint, Point2D(x as f32, y as f32))
+                let down_event = MouseWindowMouseDownEvent(dom_button,
+                                                           Point2D(x as f32, y as f32));
+                self.event_queue.push(MouseWindowEventClass(down_event));
             }
             glut::MOUSE_UP => {
+                let up_event = MouseWindowMouseUpEvent(dom_button, Point2D(x as f32, y as f32));
+                self.event_queue.push(MouseWindowEventClass(up_event));
                 if *self.mouse_down_button == button {
                     let pixel_dist = *self.mouse_down_point - Point2D(x, y);
                     let pixel_dist = ((pixel_dist.x * pixel_dist.x +
                                        pixel_dist.y * pixel_dist.y) as float).sqrt();
                     if pixel_dist < max_pixel_dist {
-                        let click_event = MouseWindowClickEvent(button as uint,
-                                                           Point2D(x as f32, y as f32));
+                        let click_event = MouseWindowClickEvent(dom_button,
+                                                                Point2D(x as f32, y as f32));
                         self.event_queue.push(MouseWindowEventClass(click_event));
                     }
                 }
-                MouseWindowMouseUpEvent(button as uint, Point2D(x as f32, y as f32))
             }
             _ => fail!("I cannot recognize the type of mouse action that occured. :-(")
-        };
-        self.event_queue.push(MouseWindowEventClass(event));
+        }
     }
 
     /// Helper function to pop up an alert box prompting the user to load a URL.
@@ -6,12 +6,19 @@
 
 use geom::point::Point2D;
 use geom::size::Size2D;
-use servo_msg::compositor_msg::{ReadyState, RenderState};
+use servo_msg::compositor_msg::{ReadyState, RenderState, PickerKind};
+use script::dom::event::{EditingCommand, FocusNavigation};
 
+/// Mouse input, with the button it is about, if any, and where the pointer is in the window. The
+/// buttons are numbered as the DOM numbers them: 0 for the main button, 1 for the middle button
+/// and 2 for the secondary button.
 pub enum MouseWindowEvent {
+    /// Sent after the mouse up event of a press and release that stayed in the same place.
     MouseWindowClickEvent(uint, Point2D<f32>),
     MouseWindowMouseDownEvent(uint, Point2D<f32>),
     MouseWindowMouseUpEvent(uint, Point2D<f32>),
+    /// Sent when the pointer moves over the window.
+    MouseWindowMoveEvent(Point2D<f32>),
 }
 
 pub enum WindowNavigateMsg {
@@ -36,12 +43,33 @@ pub enum WindowEvent {
     ScrollWindowEvent(Point2D<f32>, Point2D<i32>),
     /// Sent when the user zooms.
     ZoomWindowEvent(f32),
+    /// Sent when the user pans with a touch, with the point where the touch started, which the
+    /// `touch-action` there can stop from scrolling along either axis.
+    ///
+    /// FIXME: Neither the GLFW nor the GLUT window has touch input, so nothing sends this.
+    TouchScrollWindowEvent(Point2D<f32>, Point2D<i32>),
+    /// Sent when the user pinches to zoom, with the point where the pinch started, which the
+    /// `touch-action` there can stop from zooming.
+    ///
+    /// FIXME: Nothing sends this either.
+    PinchZoomWindowEvent(f32, Point2D<i32>),
     /// Sent when the user uses chrome navigation (i.e. backspace or shift-backspace).
     NavigationWindowEvent(WindowNavigateMsg),
     /// Sent when rendering is finished.
     FinishedWindowEvent,
     /// Sent when the user quits the application
     QuitWindowEvent,
+    /// Sent when the user presses a key that moves or uses the focus.
+    FocusWindowEvent(FocusNavigation),
+    /// Sent when the user presses one of the access keys registered with `set_access_keys`.
+    AccessKeyWindowEvent(char),
+    /// Sent when the user undoes or redoes an edit, with a keyboard shortcut or the Edit menu.
+    ///
+    /// FIXME: Neither the GLFW nor the GLUT window has menus, so only the shortcuts send this.
+    EditingWindowEvent(EditingCommand),
+    /// Sent when the user closes a picker opened with `open_picker`, with the value they picked,
+    /// or `None` if they cancelled.
+    PickerWindowEvent(Option<~str>),
 }
 
 /// Methods for an abstract Application.
@@ -67,5 +95,21 @@ pub trait WindowMethods<A> {
 
     /// Returns the hidpi factor of the monitor.
     fn hidpi_factor(@mut self) -> f32;
+
+    /// Lets the user pick a value of the given kind, starting from `value`. The window answers
+    /// with a `PickerWindowEvent` once the user is done.
+    fn open_picker(@mut self, kind: PickerKind, value: ~str);
+
+    /// Registers the access keys of the page, replacing any registered before. When the user
+    /// presses one of them with Alt, the window sends an `AccessKeyWindowEvent`.
+    fn set_access_keys(@mut self, keys: ~[char]);
+
+    /// Decides whether to stop a script of the page that has run for too long, by calling the
+    /// slow script hook. The page doesn't respond until the hook returns. Without a hook, slow
+    /// scripts are stopped.
+    fn stop_slow_script(@mut self) -> bool;
+    /// Sets the hook that decides whether to stop slow scripts, for embedders that ask the user
+    /// in their own way. The hook returns whether to stop the script.
+    fn set_slow_script_hook(@mut self, hook: @fn() -> bool);
 }
 
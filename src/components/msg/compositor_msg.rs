@@ -2,12 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use azure::azure_hl::DrawTarget;
+use azure::azure_hl::{Color, DrawTarget};
 use azure::azure::AzGLContext;
+use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
 
-use constellation_msg::PipelineId;
+use constellation_msg::{PipelineId, SubpageId};
 
 #[deriving(Clone)]
 pub struct LayerBuffer {
@@ -53,6 +54,202 @@ pub enum ReadyState {
     FinishedLoading,
 }
 
+/// The kinds of value the embedder can let the user pick for a form control.
+#[deriving(Eq)]
+pub enum PickerKind {
+    /// A date, as `YYYY-MM-DD`.
+    DatePicker,
+    /// A color, as `#rrggbb`.
+    ColorPicker,
+}
+
+/// How strictly scrolling snaps to the snap positions of a layer.
+#[deriving(Clone, Eq)]
+pub enum ScrollSnapStrictness {
+    /// Scrolling always comes to rest at a snap position.
+    MandatorySnap,
+    /// Scrolling comes to rest at a snap position only if it stops near one.
+    ProximitySnap,
+}
+
+/// Where scrolling a layer can come to rest: the scroll positions, in page coordinates, at which
+/// a box that the page snaps to is aligned in the viewport, along each axis that the page snaps
+/// in.
+#[deriving(Clone)]
+pub struct ScrollSnapPositions {
+    strictness: ScrollSnapStrictness,
+    x: ~[f32],
+    y: ~[f32],
+}
+
+impl ScrollSnapPositions {
+    pub fn none() -> ScrollSnapPositions {
+        ScrollSnapPositions {
+            strictness: ProximitySnap,
+            x: ~[],
+            y: ~[],
+        }
+    }
+}
+
+/// What the compositor needs to know about the regions of a page to decide at once which layer a
+/// scroll gesture over them goes to, without asking the page. Layout builds it along with the
+/// display list.
+#[deriving(Clone)]
+pub struct HitTestData {
+    /// The regions, in page coordinates, in the order they are drawn in, so that later ones are
+    /// above earlier ones.
+    regions: ~[HitTestRegion],
+}
+
+impl HitTestData {
+    pub fn new() -> HitTestData {
+        HitTestData {
+            regions: ~[],
+        }
+    }
+
+    /// Returns the gestures that the compositor handles for a touch that starts at the given
+    /// point of the page, which are those that all the touch-action regions there allow.
+    pub fn touch_action_at(&self, point: Point2D<f32>) -> TouchAction {
+        let mut action = TouchAction::auto();
+        for region in self.regions.iter() {
+            match region.kind {
+                TouchActionRegion(region_action) if region.contains(point) => {
+                    action = action.intersect(&region_action)
+                }
+                _ => {}
+            }
+        }
+        action
+    }
+
+    /// Returns the frame region drawn on top at the given point of the page, if any.
+    pub fn region_at<'a>(&'a self, point: Point2D<f32>) -> Option<&'a HitTestRegion> {
+        self.regions.rev_iter().find(|region| {
+            match region.kind {
+                ScrollFrameRegion(_) => region.contains(point),
+                TouchActionRegion(_) => false,
+            }
+        })
+    }
+}
+
+#[deriving(Clone)]
+pub struct HitTestRegion {
+    rect: Rect<f32>,
+    kind: HitTestRegionKind,
+}
+
+impl HitTestRegion {
+    pub fn contains(&self, point: Point2D<f32>) -> bool {
+        point.x >= self.rect.origin.x &&
+            point.x < self.rect.origin.x + self.rect.size.width &&
+            point.y >= self.rect.origin.y &&
+            point.y < self.rect.origin.y + self.rect.size.height
+    }
+}
+
+#[deriving(Clone, Eq)]
+pub enum HitTestRegionKind {
+    /// The frame of the given subpage, which scrolls separately from the page.
+    ScrollFrameRegion(SubpageId),
+    /// An element whose `touch-action` keeps the compositor from handling some touch gestures.
+    TouchActionRegion(TouchAction),
+}
+
+/// The touch gestures that the compositor handles itself, panning and zooming the page, as the
+/// `touch-action` of where a touch starts says. The page is left to handle the others with the
+/// touch events it gets.
+#[deriving(Clone, Eq)]
+pub struct TouchAction {
+    pan_x: bool,
+    pan_y: bool,
+    pinch_zoom: bool,
+}
+
+impl TouchAction {
+    /// All the gestures, as `touch-action: auto` allows.
+    pub fn auto() -> TouchAction {
+        TouchAction {
+            pan_x: true,
+            pan_y: true,
+            pinch_zoom: true,
+        }
+    }
+
+    /// Returns the gestures that both this and `other` allow, as an element allows only what the
+    /// elements it is in allow too.
+    pub fn intersect(&self, other: &TouchAction) -> TouchAction {
+        TouchAction {
+            pan_x: self.pan_x && other.pan_x,
+            pan_y: self.pan_y && other.pan_y,
+            pinch_zoom: self.pinch_zoom && other.pinch_zoom,
+        }
+    }
+}
+
+/// How a scroll that script asks for moves the viewport.
+#[deriving(Clone, Eq)]
+pub enum ScrollBehavior {
+    /// As the `scroll-behavior` of the page says.
+    AutoScroll,
+    /// Animated over a short time.
+    SmoothScroll,
+    /// All at once.
+    InstantScroll,
+}
+
+/// What happens when scrolling a layer along an axis reaches the edge of its page, as the
+/// `overscroll-behavior` of the page says.
+#[deriving(Clone, Eq)]
+pub enum OverscrollBehavior {
+    /// The scroll goes on to the layer beneath, or the root layer bounces.
+    OverscrollAuto,
+    /// The scroll stops at the edge, and the root layer doesn't bounce.
+    OverscrollContain,
+    /// As `contain`. Browsers also drop their other overscroll affordances, such as swiping back
+    /// through the history, but there are none.
+    OverscrollNone,
+}
+
+/// How thick the scrollbars of a layer are, as the `scrollbar-width` of its page says.
+#[deriving(Clone, Eq)]
+pub enum ScrollbarWidth {
+    AutoScrollbarWidth,
+    ThinScrollbarWidth,
+    /// The layer scrolls, but has no scrollbars.
+    NoScrollbars,
+}
+
+impl ScrollbarWidth {
+    /// Returns how thick the scrollbars are, in pixels.
+    pub fn thickness(&self) -> uint {
+        match *self {
+            AutoScrollbarWidth => 15,
+            ThinScrollbarWidth => 8,
+            NoScrollbars => 0,
+        }
+    }
+}
+
+/// How the scrollbars of a layer look, as the `scrollbar-width` and `scrollbar-color` of its page
+/// say.
+pub struct ScrollbarStyle {
+    width: ScrollbarWidth,
+    /// The colors of the thumb and of the track, or `None` for the default colors.
+    colors: Option<(Color, Color)>,
+}
+
+impl ScrollbarStyle {
+    pub fn default() -> ScrollbarStyle {
+        ScrollbarStyle {
+            width: AutoScrollbarWidth,
+            colors: None,
+        }
+    }
+}
+
 /// A newtype struct for denoting the age of messages; prevents race conditions.
 #[deriving(Eq)]
 pub struct Epoch(uint);
@@ -70,8 +267,24 @@ pub trait RenderListener {
     fn new_layer(&self, PipelineId, Size2D<uint>);
     fn set_layer_page_size(&self, PipelineId, Size2D<uint>, Epoch);
     fn set_layer_clip_rect(&self, PipelineId, Rect<uint>);
+    /// Tells the compositor where scrolling the layer of the given pipeline can come to rest.
+    fn set_layer_scroll_snap(&self, PipelineId, ScrollSnapPositions);
+    /// Tells the compositor which regions of the layer of the given pipeline scroll gestures go
+    /// to, such as the frames in it.
+    fn set_layer_hit_test_data(&self, PipelineId, HitTestData);
+    /// Tells the compositor whether the `scroll-behavior` of the page of the given pipeline makes
+    /// the scrolls that script asks for smooth.
+    fn set_layer_smooth_scroll(&self, PipelineId, bool);
+    /// Tells the compositor what happens when scrolling the layer of the given pipeline reaches
+    /// the edge of its page, horizontally and vertically.
+    fn set_layer_overscroll_behavior(&self, PipelineId, OverscrollBehavior, OverscrollBehavior);
+    /// Tells the compositor how the scrollbars of the layer of the given pipeline look.
+    fn set_layer_scrollbar_style(&self, PipelineId, ScrollbarStyle);
     fn delete_layer(&self, PipelineId);
     fn paint(&self, id: PipelineId, layer_buffer_set: ~LayerBufferSet, Epoch);
+    /// Hands the compositor the buffers of the fixed layers of the given pipeline, which are
+    /// positioned in the viewport rather than the page, to replace the ones it has.
+    fn paint_fixed_layers(&self, id: PipelineId, layer_buffer_set: ~LayerBufferSet, Epoch);
     fn set_render_state(&self, render_state: RenderState);
 }
 
@@ -80,6 +293,17 @@ pub trait RenderListener {
 pub trait ScriptListener : Clone {
     fn set_ready_state(&self, ReadyState);
     fn invalidate_rect(&self, PipelineId, Rect<uint>);
+    /// Asks the embedder to let the user pick a value for a form control of the given pipeline,
+    /// starting from the given value.
+    fn open_picker(&self, PipelineId, PickerKind, ~str);
+    /// Tells the embedder which access keys the document of the given pipeline assigns.
+    fn set_access_keys(&self, PipelineId, ~[char]);
+    /// Scrolls the viewport of the given pipeline so that its top left is at the given position
+    /// in the page.
+    fn scroll_to(&self, PipelineId, Point2D<f32>, ScrollBehavior);
+    /// Asks the embedder whether to stop a script of the given pipeline that has run for too
+    /// long, and waits for the answer.
+    fn stop_slow_script(&self, PipelineId) -> bool;
     fn close(&self);
 }
 
@@ -36,6 +36,21 @@ pub enum Msg {
     NavigateMsg(NavigationDirection),
     RendererReadyMsg(PipelineId),
     ResizedWindowMsg(Size2D<uint>),
+    /// Asks for a snapshot of what the compositor last drew of the given pipeline, scaled down to
+    /// fit the given size, or `None` if the pipeline isn't shown.
+    ScreenshotMsg(PipelineId, Size2D<uint>, Chan<Option<Screenshot>>),
+    /// Adds a style sheet, with its URL and text, at the user agent origin after the others, to
+    /// the pages that are open and to those that are opened later, such as for an embedder to
+    /// style widgets of its own.
+    AddUserAgentStylesheetMsg(Url, ~str),
+}
+
+/// A snapshot of a pipeline, such as embedders show as a preview of a tab.
+pub struct Screenshot {
+    width: uint,
+    height: uint,
+    /// The red, green and blue bytes of the pixels, a row at a time from the top down.
+    pixels: ~[u8],
 }
 
 /// Represents the two different ways to which a page can be navigated
@@ -0,0 +1,65 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The pages of the `about:` scheme that the browser makes itself: `about:blank`, and
+//! `about:memory`, which lists the memory reports that the profiler has gathered, such as how
+//! many nodes, images and fonts each document has.
+
+use resource_task::{Done, LoaderTask, LoaderTaskFactory, Payload};
+
+use servo_util::time::{GetMemoryReportsMsg, ProfilerChan};
+use std::comm;
+use std::task;
+
+pub fn factory(profiler_chan: ProfilerChan) -> LoaderTaskFactory {
+    let factory: LoaderTaskFactory = || {
+        let profiler_chan = profiler_chan.clone();
+        let f: LoaderTask = |url, progress_chan| {
+            assert!("about" == url.scheme);
+            let profiler_chan = profiler_chan.clone();
+            do task::spawn {
+                let page = match url.path.as_slice() {
+                    "blank" => Some(~""),
+                    "memory" => {
+                        let (reports_port, reports_chan) = comm::stream();
+                        profiler_chan.send(GetMemoryReportsMsg(reports_chan));
+                        Some(memory_page(reports_port.recv()))
+                    }
+                    _ => None,
+                };
+                match page {
+                    Some(page) => {
+                        progress_chan.send(Payload(page.into_bytes()));
+                        progress_chan.send(Done(Ok(())));
+                    }
+                    None => progress_chan.send(Done(Err(()))),
+                }
+            }
+        };
+        f
+    };
+    factory
+}
+
+/// Makes the page of `about:memory` from the memory reports, which are in the order of their
+/// paths, so that the reports of each document are together.
+fn memory_page(reports: ~[(~str, uint)]) -> ~str {
+    let mut page = ~"<!DOCTYPE html>\n<html><head><title>about:memory</title></head><body>\n";
+    page.push_str("<table>\n<tr><th>Report</th><th>Value</th></tr>\n");
+    for &(ref path, value) in reports.iter() {
+        page.push_str(fmt!("<tr><td>%s</td><td>%u</td></tr>\n", escape(*path), value));
+    }
+    page.push_str("</table>\n</body></html>\n");
+    page
+}
+
+fn escape(text: &str) -> ~str {
+    text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;")
+}
+
+#[test]
+fn memory_page_lists_reports() {
+    let page = memory_page(~[(~"documents/1/dom/nodes", 42)]);
+    assert!(page.contains("<tr><td>documents/1/dom/nodes</td><td>42</td></tr>"));
+}
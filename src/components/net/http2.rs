@@ -0,0 +1,941 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The framing and header compression (HPACK) of HTTP/2, and the state of a connection to an
+//! origin, which multiplexes the loads of many resources as streams. A connection doesn't do its
+//! own I/O: it is given the frames that arrive, and returns the frames to send. The sockets
+//! belong to the tasks of `http2_loader`.
+
+use resource_task::{Done, Payload, ProgressMsg};
+
+use extra::url::Url;
+use std::comm::Chan;
+use std::hashmap::HashMap;
+use std::str;
+
+/// What a client sends first on a connection, before its SETTINGS frame.
+pub static CONNECTION_PREFACE: &'static str = "PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+pub static DATA: u8 = 0x0;
+pub static HEADERS: u8 = 0x1;
+pub static RST_STREAM: u8 = 0x3;
+pub static SETTINGS: u8 = 0x4;
+pub static PUSH_PROMISE: u8 = 0x5;
+pub static PING: u8 = 0x6;
+pub static GOAWAY: u8 = 0x7;
+pub static WINDOW_UPDATE: u8 = 0x8;
+pub static CONTINUATION: u8 = 0x9;
+
+pub static FLAG_END_STREAM: u8 = 0x1;
+/// The flag of SETTINGS and PING frames that acknowledge those of the peer.
+pub static FLAG_ACK: u8 = 0x1;
+pub static FLAG_END_HEADERS: u8 = 0x4;
+pub static FLAG_PADDED: u8 = 0x8;
+pub static FLAG_PRIORITY: u8 = 0x20;
+
+static PROTOCOL_ERROR: u32 = 0x1;
+static FRAME_SIZE_ERROR: u32 = 0x6;
+static REFUSED_STREAM: u32 = 0x7;
+static COMPRESSION_ERROR: u32 = 0x9;
+
+static SETTINGS_ENABLE_PUSH: u16 = 0x2;
+
+/// The length of the longest payload the server may send, since the client's SETTINGS frame
+/// keeps the default.
+pub static MAX_FRAME_SIZE: uint = 16384;
+
+/// The size of the header table that the peer's encoder starts with.
+static DEFAULT_HEADER_TABLE_SIZE: uint = 4096;
+
+/// The entries of the static table of HPACK, which are at indices 1 to 61.
+static STATIC_TABLE: [(&'static str, &'static str), ..61] = [
+    (":authority", ""),
+    (":method", "GET"),
+    (":method", "POST"),
+    (":path", "/"),
+    (":path", "/index.html"),
+    (":scheme", "http"),
+    (":scheme", "https"),
+    (":status", "200"),
+    (":status", "204"),
+    (":status", "206"),
+    (":status", "304"),
+    (":status", "400"),
+    (":status", "404"),
+    (":status", "500"),
+    ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"),
+    ("accept-language", ""),
+    ("accept-ranges", ""),
+    ("accept", ""),
+    ("access-control-allow-origin", ""),
+    ("age", ""),
+    ("allow", ""),
+    ("authorization", ""),
+    ("cache-control", ""),
+    ("content-disposition", ""),
+    ("content-encoding", ""),
+    ("content-language", ""),
+    ("content-length", ""),
+    ("content-location", ""),
+    ("content-range", ""),
+    ("content-type", ""),
+    ("cookie", ""),
+    ("date", ""),
+    ("etag", ""),
+    ("expect", ""),
+    ("expires", ""),
+    ("from", ""),
+    ("host", ""),
+    ("if-match", ""),
+    ("if-modified-since", ""),
+    ("if-none-match", ""),
+    ("if-range", ""),
+    ("if-unmodified-since", ""),
+    ("last-modified", ""),
+    ("link", ""),
+    ("location", ""),
+    ("max-forwards", ""),
+    ("proxy-authenticate", ""),
+    ("proxy-authorization", ""),
+    ("range", ""),
+    ("referer", ""),
+    ("refresh", ""),
+    ("retry-after", ""),
+    ("server", ""),
+    ("set-cookie", ""),
+    ("strict-transport-security", ""),
+    ("transfer-encoding", ""),
+    ("user-agent", ""),
+    ("vary", ""),
+    ("via", ""),
+    ("www-authenticate", ""),
+];
+
+/// The Huffman code of HPACK, as the code of each byte and of the end of the string (256), and
+/// its length in bits. The code is canonical: the codes of each length follow on from those of
+/// the length before, and are in the order of their symbols.
+static HUFFMAN_CODES: [(u32, u8), ..257] = [
+    (0x1ff8, 13), (0x7fffd8, 23), (0xfffffe2, 28), (0xfffffe3, 28), (0xfffffe4, 28),
+    (0xfffffe5, 28), (0xfffffe6, 28), (0xfffffe7, 28), (0xfffffe8, 28), (0xffffea, 24),
+    (0x3ffffffc, 30), (0xfffffe9, 28), (0xfffffea, 28), (0x3ffffffd, 30), (0xfffffeb, 28),
+    (0xfffffec, 28), (0xfffffed, 28), (0xfffffee, 28), (0xfffffef, 28), (0xffffff0, 28),
+    (0xffffff1, 28), (0xffffff2, 28), (0x3ffffffe, 30), (0xffffff3, 28), (0xffffff4, 28),
+    (0xffffff5, 28), (0xffffff6, 28), (0xffffff7, 28), (0xffffff8, 28), (0xffffff9, 28),
+    (0xffffffa, 28), (0xffffffb, 28), (0x14, 6), (0x3f8, 10), (0x3f9, 10),
+    (0xffa, 12), (0x1ff9, 13), (0x15, 6), (0xf8, 8), (0x7fa, 11),
+    (0x3fa, 10), (0x3fb, 10), (0xf9, 8), (0x7fb, 11), (0xfa, 8),
+    (0x16, 6), (0x17, 6), (0x18, 6), (0x0, 5), (0x1, 5),
+    (0x2, 5), (0x19, 6), (0x1a, 6), (0x1b, 6), (0x1c, 6),
+    (0x1d, 6), (0x1e, 6), (0x1f, 6), (0x5c, 7), (0xfb, 8),
+    (0x7ffc, 15), (0x20, 6), (0xffb, 12), (0x3fc, 10), (0x1ffa, 13),
+    (0x21, 6), (0x5d, 7), (0x5e, 7), (0x5f, 7), (0x60, 7),
+    (0x61, 7), (0x62, 7), (0x63, 7), (0x64, 7), (0x65, 7),
+    (0x66, 7), (0x67, 7), (0x68, 7), (0x69, 7), (0x6a, 7),
+    (0x6b, 7), (0x6c, 7), (0x6d, 7), (0x6e, 7), (0x6f, 7),
+    (0x70, 7), (0x71, 7), (0x72, 7), (0xfc, 8), (0x73, 7),
+    (0xfd, 8), (0x1ffb, 13), (0x7fff0, 19), (0x1ffc, 13), (0x3ffc, 14),
+    (0x22, 6), (0x7ffd, 15), (0x3, 5), (0x23, 6), (0x4, 5),
+    (0x24, 6), (0x5, 5), (0x25, 6), (0x26, 6), (0x27, 6),
+    (0x6, 5), (0x74, 7), (0x75, 7), (0x28, 6), (0x29, 6),
+    (0x2a, 6), (0x7, 5), (0x2b, 6), (0x76, 7), (0x2c, 6),
+    (0x8, 5), (0x9, 5), (0x2d, 6), (0x77, 7), (0x78, 7),
+    (0x79, 7), (0x7a, 7), (0x7b, 7), (0x7ffe, 15), (0x7fc, 11),
+    (0x3ffd, 14), (0x1ffd, 13), (0xffffffc, 28), (0xfffe6, 20), (0x3fffd2, 22),
+    (0xfffe7, 20), (0xfffe8, 20), (0x3fffd3, 22), (0x3fffd4, 22), (0x3fffd5, 22),
+    (0x7fffd9, 23), (0x3fffd6, 22), (0x7fffda, 23), (0x7fffdb, 23), (0x7fffdc, 23),
+    (0x7fffdd, 23), (0x7fffde, 23), (0xffffeb, 24), (0x7fffdf, 23), (0xffffec, 24),
+    (0xffffed, 24), (0x3fffd7, 22), (0x7fffe0, 23), (0xffffee, 24), (0x7fffe1, 23),
+    (0x7fffe2, 23), (0x7fffe3, 23), (0x7fffe4, 23), (0x1fffdc, 21), (0x3fffd8, 22),
+    (0x7fffe5, 23), (0x3fffd9, 22), (0x7fffe6, 23), (0x7fffe7, 23), (0xffffef, 24),
+    (0x3fffda, 22), (0x1fffdd, 21), (0xfffe9, 20), (0x3fffdb, 22), (0x3fffdc, 22),
+    (0x7fffe8, 23), (0x7fffe9, 23), (0x1fffde, 21), (0x7fffea, 23), (0x3fffdd, 22),
+    (0x3fffde, 22), (0xfffff0, 24), (0x1fffdf, 21), (0x3fffdf, 22), (0x7fffeb, 23),
+    (0x7fffec, 23), (0x1fffe0, 21), (0x1fffe1, 21), (0x3fffe0, 22), (0x1fffe2, 21),
+    (0x7fffed, 23), (0x3fffe1, 22), (0x7fffee, 23), (0x7fffef, 23), (0xfffea, 20),
+    (0x3fffe2, 22), (0x3fffe3, 22), (0x3fffe4, 22), (0x7ffff0, 23), (0x3fffe5, 22),
+    (0x3fffe6, 22), (0x7ffff1, 23), (0x3ffffe0, 26), (0x3ffffe1, 26), (0xfffeb, 20),
+    (0x7fff1, 19), (0x3fffe7, 22), (0x7ffff2, 23), (0x3fffe8, 22), (0x1ffffec, 25),
+    (0x3ffffe2, 26), (0x3ffffe3, 26), (0x3ffffe4, 26), (0x7ffffde, 27), (0x7ffffdf, 27),
+    (0x3ffffe5, 26), (0xfffff1, 24), (0x1ffffed, 25), (0x7fff2, 19), (0x1fffe3, 21),
+    (0x3ffffe6, 26), (0x7ffffe0, 27), (0x7ffffe1, 27), (0x3ffffe7, 26), (0x7ffffe2, 27),
+    (0xfffff2, 24), (0x1fffe4, 21), (0x1fffe5, 21), (0x3ffffe8, 26), (0x3ffffe9, 26),
+    (0xffffffd, 28), (0x7ffffe3, 27), (0x7ffffe4, 27), (0x7ffffe5, 27), (0xfffec, 20),
+    (0xfffff3, 24), (0xfffed, 20), (0x1fffe6, 21), (0x3fffe9, 22), (0x1fffe7, 21),
+    (0x1fffe8, 21), (0x7ffff3, 23), (0x3fffea, 22), (0x3fffeb, 22), (0x1ffffee, 25),
+    (0x1ffffef, 25), (0xfffff4, 24), (0xfffff5, 24), (0x3ffffea, 26), (0x7ffff4, 23),
+    (0x3ffffeb, 26), (0x7ffffe6, 27), (0x3ffffec, 26), (0x3ffffed, 26), (0x7ffffe7, 27),
+    (0x7ffffe8, 27), (0x7ffffe9, 27), (0x7ffffea, 27), (0x7ffffeb, 27), (0xffffffe, 28),
+    (0x7ffffec, 27), (0x7ffffed, 27), (0x7ffffee, 27), (0x7ffffef, 27), (0x7fffff0, 27),
+    (0x3ffffee, 26), (0x3fffffff, 30),
+];
+
+/// The length of the longest Huffman code.
+static MAX_HUFFMAN_CODE_LENGTH: uint = 30;
+
+/// A frame, the unit that everything on a connection is sent in.
+#[deriving(Eq, Clone)]
+pub struct Frame {
+    frame_type: u8,
+    flags: u8,
+    /// The stream the frame belongs to, or 0 for the connection itself.
+    stream_id: u32,
+    payload: ~[u8],
+}
+
+impl Frame {
+    pub fn new(frame_type: u8, flags: u8, stream_id: u32, payload: ~[u8]) -> Frame {
+        Frame {
+            frame_type: frame_type,
+            flags: flags,
+            stream_id: stream_id,
+            payload: payload,
+        }
+    }
+
+    /// Parses the frame at the start of `input`, returning it with the number of bytes it took,
+    /// or `None` if the whole frame hasn't arrived yet.
+    pub fn parse(input: &[u8]) -> Option<(Frame, uint)> {
+        if input.len() < 9 {
+            return None
+        }
+        let length = payload_length(input);
+        if input.len() < 9 + length {
+            return None
+        }
+        let stream_id = read_u32(input.slice(5, 9)) & 0x7fffffff;
+        let payload = input.slice(9, 9 + length).to_owned();
+        Some((Frame::new(input[3], input[4], stream_id, payload), 9 + length))
+    }
+
+    pub fn to_bytes(&self) -> ~[u8] {
+        let length = self.payload.len();
+        let mut bytes = ~[
+            (length >> 16) as u8, (length >> 8) as u8, length as u8,
+            self.frame_type,
+            self.flags,
+            (self.stream_id >> 24) as u8 & 0x7f, (self.stream_id >> 16) as u8,
+            (self.stream_id >> 8) as u8, self.stream_id as u8,
+        ];
+        bytes.push_all(self.payload);
+        bytes
+    }
+
+    /// Whether the frame at the start of `input` is longer than `MAX_FRAME_SIZE`, which is told
+    /// as soon as its header has arrived, before its payload is waited for.
+    pub fn is_too_large(input: &[u8]) -> bool {
+        input.len() >= 9 && payload_length(input) > MAX_FRAME_SIZE
+    }
+
+    fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag != 0
+    }
+
+    /// Returns the payload of a DATA, HEADERS or PUSH_PROMISE frame without its padding, and for
+    /// HEADERS, without its priority fields, or `None` if the padding is longer than the payload.
+    fn contents<'a>(&'a self) -> Option<&'a [u8]> {
+        let mut start = 0;
+        let mut end = self.payload.len();
+        if self.has_flag(FLAG_PADDED) {
+            if end == 0 || self.payload[0] as uint > end - 1 {
+                return None
+            }
+            start = 1;
+            end -= self.payload[0] as uint;
+        }
+        if self.frame_type == HEADERS && self.has_flag(FLAG_PRIORITY) {
+            start += 5;
+        }
+        if start > end {
+            return None
+        }
+        Some(self.payload.slice(start, end))
+    }
+}
+
+/// Reads the length of the payload from the header of a frame.
+fn payload_length(header: &[u8]) -> uint {
+    ((header[0] as uint) << 16) | ((header[1] as uint) << 8) | header[2] as uint
+}
+
+/// Encodes an integer with an N-bit prefix, in the first byte along with `flags`, the bits above
+/// the prefix.
+pub fn encode_integer(value: uint, prefix_bits: uint, flags: u8, out: &mut ~[u8]) {
+    let max_prefix = (1 << prefix_bits) - 1;
+    if value < max_prefix {
+        out.push(flags | value as u8);
+        return
+    }
+    out.push(flags | max_prefix as u8);
+    let mut value = value - max_prefix;
+    while value >= 128 {
+        out.push((value % 128 + 128) as u8);
+        value /= 128;
+    }
+    out.push(value as u8);
+}
+
+/// Decodes an integer with an N-bit prefix at `*position`, and moves past it.
+pub fn decode_integer(input: &[u8], position: &mut uint, prefix_bits: uint) -> Option<uint> {
+    if *position >= input.len() {
+        return None
+    }
+    let max_prefix = (1 << prefix_bits) - 1;
+    let mut value = input[*position] as uint & max_prefix;
+    *position += 1;
+    if value < max_prefix {
+        return Some(value)
+    }
+    let mut shift = 0;
+    loop {
+        // Larger values than 2^28 are never needed, and could overflow.
+        if *position >= input.len() || shift > 21 {
+            return None
+        }
+        let byte = input[*position] as uint;
+        *position += 1;
+        value += (byte & 0x7f) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(value)
+        }
+    }
+}
+
+/// Encodes a string literal, which is never Huffman coded.
+fn encode_string(string: &str, out: &mut ~[u8]) {
+    encode_integer(string.len(), 7, 0, out);
+    out.push_all(string.as_bytes());
+}
+
+/// Decodes a string literal at `*position`, which may be Huffman coded, and moves past it.
+fn decode_string(input: &[u8], position: &mut uint) -> Option<~str> {
+    if *position >= input.len() {
+        return None
+    }
+    let huffman_coded = input[*position] & 0x80 != 0;
+    let length = match decode_integer(input, position, 7) {
+        Some(length) => length,
+        None => return None,
+    };
+    if *position + length > input.len() {
+        return None
+    }
+    let bytes = input.slice(*position, *position + length);
+    *position += length;
+    let bytes = if huffman_coded {
+        match huffman_decode(bytes) {
+            Some(bytes) => bytes,
+            None => return None,
+        }
+    } else {
+        bytes.to_owned()
+    };
+    if str::is_utf8(bytes) {
+        Some(str::from_utf8(bytes))
+    } else {
+        None
+    }
+}
+
+/// Decodes a Huffman coded string, or returns `None` if it is malformed: if it codes the end of
+/// the string, or if it is padded with more than 7 bits or with bits that aren't all ones.
+fn huffman_decode(input: &[u8]) -> Option<~[u8]> {
+    // Since the code is canonical, the codes of each length are told by their count, and which
+    // symbols they code by the index of the first of them among the symbols sorted by code.
+    let mut counts = [0u32, ..MAX_HUFFMAN_CODE_LENGTH + 1];
+    for &(_, length) in HUFFMAN_CODES.iter() {
+        counts[length as uint] += 1;
+    }
+    let mut first_indices = [0u, ..MAX_HUFFMAN_CODE_LENGTH + 1];
+    for length in range(1, MAX_HUFFMAN_CODE_LENGTH + 1) {
+        first_indices[length] = first_indices[length - 1] + counts[length - 1] as uint;
+    }
+    let mut symbols = [0u16, ..257];
+    let mut next_indices = first_indices;
+    for (symbol, &(_, length)) in HUFFMAN_CODES.iter().enumerate() {
+        symbols[next_indices[length as uint]] = symbol as u16;
+        next_indices[length as uint] += 1;
+    }
+
+    let mut output = ~[];
+    // The bits read of the code being decoded, and the first code of as many bits.
+    let mut code = 0u32;
+    let mut length = 0u;
+    let mut first_code = 0u32;
+    for &byte in input.iter() {
+        for i in range(0u, 8) {
+            code = (code << 1) | ((byte >> (7 - i)) & 1) as u32;
+            length += 1;
+            if code - first_code < counts[length] {
+                let symbol = symbols[first_indices[length] + (code - first_code) as uint];
+                if symbol == 256 {
+                    return None
+                }
+                output.push(symbol as u8);
+                code = 0;
+                length = 0;
+                first_code = 0;
+            } else if length == MAX_HUFFMAN_CODE_LENGTH {
+                return None
+            } else {
+                first_code = (first_code + counts[length]) << 1;
+            }
+        }
+    }
+
+    // What is left is padding, the start of the code of the end of the string, which is all ones.
+    if length > 7 || code != (1 << length) - 1 {
+        return None
+    }
+    Some(output)
+}
+
+/// Encodes the headers of a request as a header block. Headers are indexed in the static table
+/// when they can be, and sent as literals that aren't added to the dynamic table otherwise, so
+/// the encoder keeps no state.
+pub fn encode_headers(headers: &[(~str, ~str)]) -> ~[u8] {
+    let mut out = ~[];
+    for &(ref name, ref value) in headers.iter() {
+        let full_match = STATIC_TABLE.iter().position(|&(n, v)| {
+            n == name.as_slice() && v == value.as_slice()
+        });
+        match full_match {
+            Some(index) => encode_integer(index + 1, 7, 0x80, &mut out),
+            None => {
+                match STATIC_TABLE.iter().position(|&(n, _)| n == name.as_slice()) {
+                    Some(index) => encode_integer(index + 1, 4, 0, &mut out),
+                    None => {
+                        out.push(0);
+                        encode_string(*name, &mut out)
+                    }
+                }
+                encode_string(*value, &mut out)
+            }
+        }
+    }
+    out
+}
+
+/// The state that decoding header blocks keeps between them: the dynamic table of the peer's
+/// encoder, whose entries are indexed after those of the static table, newest first.
+pub struct HeaderDecoder {
+    priv entries: ~[(~str, ~str)],
+    priv size: uint,
+    priv max_size: uint,
+}
+
+impl HeaderDecoder {
+    pub fn new() -> HeaderDecoder {
+        HeaderDecoder {
+            entries: ~[],
+            size: 0,
+            max_size: DEFAULT_HEADER_TABLE_SIZE,
+        }
+    }
+
+    /// Decodes a header block into its headers, or returns `None` if it is malformed, which is
+    /// an error for the whole connection, since the tables are no longer in sync.
+    pub fn decode(&mut self, block: &[u8]) -> Option<~[(~str, ~str)]> {
+        let mut headers = ~[];
+        let mut position = 0;
+        while position < block.len() {
+            let byte = block[position];
+            if byte & 0x80 != 0 {
+                // Indexed
+                let header = decode_integer(block, &mut position, 7).chain(|i| self.get(i));
+                match header {
+                    Some(header) => headers.push(header),
+                    None => return None,
+                }
+            } else if byte & 0x40 != 0 {
+                // Literal, added to the table
+                match self.decode_literal(block, &mut position, 6) {
+                    Some(header) => {
+                        self.insert(header.clone());
+                        headers.push(header)
+                    }
+                    None => return None,
+                }
+            } else if byte & 0x20 != 0 {
+                // Table size update
+                match decode_integer(block, &mut position, 5) {
+                    Some(max_size) if max_size <= DEFAULT_HEADER_TABLE_SIZE => {
+                        self.max_size = max_size;
+                        self.evict(0)
+                    }
+                    _ => return None,
+                }
+            } else {
+                // Literal, not added to the table, and maybe never to be
+                match self.decode_literal(block, &mut position, 4) {
+                    Some(header) => headers.push(header),
+                    None => return None,
+                }
+            }
+        }
+        Some(headers)
+    }
+
+    fn decode_literal(&self, block: &[u8], position: &mut uint, prefix_bits: uint)
+                      -> Option<(~str, ~str)> {
+        let name = match decode_integer(block, position, prefix_bits) {
+            Some(0) => decode_string(block, position),
+            Some(index) => self.get(index).map_move(|(name, _)| name),
+            None => None,
+        };
+        match name {
+            Some(name) => decode_string(block, position).map_move(|value| (name, value)),
+            None => None,
+        }
+    }
+
+    fn get(&self, index: uint) -> Option<(~str, ~str)> {
+        if index == 0 {
+            None
+        } else if index <= STATIC_TABLE.len() {
+            let (name, value) = STATIC_TABLE[index - 1];
+            Some((name.to_owned(), value.to_owned()))
+        } else if index - STATIC_TABLE.len() <= self.entries.len() {
+            Some(self.entries[index - STATIC_TABLE.len() - 1].clone())
+        } else {
+            None
+        }
+    }
+
+    fn insert(&mut self, header: (~str, ~str)) {
+        let size = entry_size(&header);
+        self.evict(size);
+        if size <= self.max_size {
+            self.entries.unshift(header);
+            self.size += size;
+        }
+    }
+
+    /// Evicts the oldest entries until there is room for one of the given size.
+    fn evict(&mut self, room: uint) {
+        while !self.entries.is_empty() && self.size + room > self.max_size {
+            let evicted = self.entries.pop();
+            self.size -= entry_size(&evicted);
+        }
+    }
+}
+
+/// The size an entry counts for in the dynamic table, which includes 32 bytes of overhead.
+fn entry_size(&(ref name, ref value): &(~str, ~str)) -> uint {
+    name.len() + value.len() + 32
+}
+
+/// What a header block is for, which is told by the frame that starts it.
+enum HeaderBlockKind {
+    /// The headers of a response, and whether they end its stream.
+    ResponseHeaders(bool),
+    /// The request of a response that the server promises to push on the given stream.
+    PushedRequest(u32),
+}
+
+/// A connection to an origin, with the streams of the loads on it.
+pub struct Connection {
+    priv decoder: HeaderDecoder,
+    priv next_stream_id: u32,
+    priv streams: HashMap<u32, Chan<ProgressMsg>>,
+    /// The header block of a HEADERS or PUSH_PROMISE frame whose CONTINUATION frames haven't
+    /// all arrived, with its stream and what it is for.
+    priv partial_headers: Option<(u32, HeaderBlockKind, ~[u8])>,
+    /// Whether new streams may be started, which they can't once either side has sent GOAWAY.
+    priv open: bool,
+}
+
+impl Connection {
+    pub fn new() -> Connection {
+        Connection {
+            decoder: HeaderDecoder::new(),
+            next_stream_id: 1,
+            streams: HashMap::new(),
+            partial_headers: None,
+            open: true,
+        }
+    }
+
+    /// Whether loads may be started on the connection.
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Whether any load on the connection hasn't finished.
+    pub fn has_streams(&self) -> bool {
+        !self.streams.is_empty()
+    }
+
+    /// Fails every stream, as when the connection is closed before they have finished.
+    pub fn close(&mut self) {
+        self.open = false;
+        let ids: ~[u32] = self.streams.iter().map(|(&id, _)| id).collect();
+        for &id in ids.iter() {
+            self.end_stream(id, Err(()))
+        }
+    }
+
+    /// Returns what opens the connection: the preface, and the SETTINGS frame of the client,
+    /// which disables server push and keeps the other defaults.
+    pub fn preface(&self) -> ~[u8] {
+        let mut settings = ~[(SETTINGS_ENABLE_PUSH >> 8) as u8, SETTINGS_ENABLE_PUSH as u8];
+        push_u32(0, &mut settings);
+        let mut bytes = CONNECTION_PREFACE.as_bytes().to_owned();
+        bytes.push_all(Frame::new(SETTINGS, 0, 0, settings).to_bytes());
+        bytes
+    }
+
+    /// Starts a GET of `url` on a new stream, to whose channel its response body is sent, and
+    /// returns the frame that requests it.
+    pub fn request(&mut self, url: &Url, progress_chan: Chan<ProgressMsg>) -> Frame {
+        let stream_id = self.next_stream_id;
+        self.next_stream_id += 2;
+        self.streams.insert(stream_id, progress_chan);
+
+        let authority = match url.port {
+            Some(ref port) => fmt!("%s:%s", url.host, *port),
+            None => url.host.clone(),
+        };
+        let mut path = if url.path.is_empty() { ~"/" } else { url.path.clone() };
+        if !url.query.is_empty() {
+            let query: ~[~str] = url.query.iter().map(|&(ref key, ref value)| {
+                fmt!("%s=%s", *key, *value)
+            }).collect();
+            path = path + "?" + query.connect("&");
+        }
+        let headers = ~[
+            (~":method", ~"GET"),
+            (~":scheme", url.scheme.clone()),
+            (~":authority", authority),
+            (~":path", path),
+        ];
+        Frame::new(HEADERS, FLAG_END_HEADERS | FLAG_END_STREAM, stream_id,
+                   encode_headers(headers))
+    }
+
+    /// Handles a frame from the server, and returns the frames to answer it with.
+    pub fn receive(&mut self, frame: Frame) -> ~[Frame] {
+        let frame_type = frame.frame_type;
+        if frame_type == HEADERS || frame_type == PUSH_PROMISE || frame_type == CONTINUATION ||
+                self.partial_headers.is_some() {
+            return self.receive_headers(frame)
+        }
+        if frame_type == DATA {
+            let contents = match frame.contents() {
+                Some(contents) => contents.to_owned(),
+                None => return self.fail_connection(PROTOCOL_ERROR),
+            };
+            let mut replies = ~[];
+            if !contents.is_empty() {
+                // Let the server send as much again, on the connection and on the stream.
+                let mut increment = ~[];
+                push_u32(contents.len() as u32, &mut increment);
+                replies.push(Frame::new(WINDOW_UPDATE, 0, 0, increment.clone()));
+                if !frame.has_flag(FLAG_END_STREAM) {
+                    replies.push(Frame::new(WINDOW_UPDATE, 0, frame.stream_id, increment));
+                }
+            }
+            match self.streams.find(&frame.stream_id) {
+                Some(chan) => chan.send(Payload(contents)),
+                None => {}
+            }
+            if frame.has_flag(FLAG_END_STREAM) {
+                self.end_stream(frame.stream_id, Ok(()))
+            }
+            replies
+        } else if frame_type == RST_STREAM {
+            self.end_stream(frame.stream_id, Err(()));
+            ~[]
+        } else if frame_type == SETTINGS && !frame.has_flag(FLAG_ACK) {
+            ~[Frame::new(SETTINGS, FLAG_ACK, 0, ~[])]
+        } else if frame_type == PING && !frame.has_flag(FLAG_ACK) {
+            ~[Frame::new(PING, FLAG_ACK, 0, frame.payload)]
+        } else if frame_type == GOAWAY {
+            // Streams after the last that the server processed will never be answered.
+            if frame.payload.len() < 4 {
+                return self.fail_connection(PROTOCOL_ERROR)
+            }
+            self.open = false;
+            let last_stream_id = read_u32(frame.payload.slice_to(4)) & 0x7fffffff;
+            let abandoned: ~[u32] = self.streams.iter().filter_map(|(&id, _)| {
+                if id > last_stream_id { Some(id) } else { None }
+            }).collect();
+            for &id in abandoned.iter() {
+                self.end_stream(id, Err(()))
+            }
+            ~[]
+        } else {
+            // PRIORITY, WINDOW_UPDATE (the client sends no DATA) and unknown frames.
+            ~[]
+        }
+    }
+
+    fn receive_headers(&mut self, frame: Frame) -> ~[Frame] {
+        let partial_headers = self.partial_headers.take();
+        let (stream_id, kind, mut block) = if (frame.frame_type == HEADERS ||
+                frame.frame_type == PUSH_PROMISE) && partial_headers.is_none() {
+            let contents = match frame.contents() {
+                Some(contents) => contents,
+                None => return self.fail_connection(PROTOCOL_ERROR),
+            };
+            if frame.frame_type == HEADERS {
+                let kind = ResponseHeaders(frame.has_flag(FLAG_END_STREAM));
+                (frame.stream_id, kind, contents.to_owned())
+            } else if contents.len() < 4 {
+                return self.fail_connection(PROTOCOL_ERROR)
+            } else {
+                let kind = PushedRequest(read_u32(contents.slice_to(4)) & 0x7fffffff);
+                (frame.stream_id, kind, contents.slice_from(4).to_owned())
+            }
+        } else if frame.frame_type == CONTINUATION && partial_headers.is_some() {
+            let (stream_id, kind, block) = partial_headers.unwrap();
+            if stream_id != frame.stream_id {
+                return self.fail_connection(PROTOCOL_ERROR)
+            }
+            (stream_id, kind, block)
+        } else {
+            // Header blocks can't be interleaved with other frames.
+            return self.fail_connection(PROTOCOL_ERROR)
+        };
+        if frame.frame_type == CONTINUATION {
+            block.push_all(frame.payload);
+        }
+        if !frame.has_flag(FLAG_END_HEADERS) {
+            self.partial_headers = Some((stream_id, kind, block));
+            return ~[]
+        }
+
+        // Every block is decoded, even that of a stream we don't know or a push we refuse, to
+        // keep the table in sync.
+        match self.decoder.decode(block) {
+            Some(headers) => {
+                for &(ref name, ref value) in headers.iter() {
+                    debug!("http2: stream %u: %s: %s", stream_id as uint, *name, *value);
+                }
+                match kind {
+                    ResponseHeaders(true) => {
+                        self.end_stream(stream_id, Ok(()));
+                        ~[]
+                    }
+                    ResponseHeaders(false) => ~[],
+                    // The preface disables pushes, but the server may have promised this one
+                    // before it had our SETTINGS.
+                    PushedRequest(promised_stream_id) => {
+                        let mut payload = ~[];
+                        push_u32(REFUSED_STREAM, &mut payload);
+                        ~[Frame::new(RST_STREAM, 0, promised_stream_id, payload)]
+                    }
+                }
+            }
+            None => self.fail_connection(COMPRESSION_ERROR),
+        }
+    }
+
+    fn end_stream(&mut self, stream_id: u32, result: Result<(), ()>) {
+        match self.streams.pop(&stream_id) {
+            Some(chan) => chan.send(Done(result)),
+            None => {}
+        }
+    }
+
+    /// Fails every stream when the server starts a frame longer than `MAX_FRAME_SIZE`, and
+    /// returns the GOAWAY frame that tells it why.
+    pub fn receive_too_large(&mut self) -> ~[Frame] {
+        self.fail_connection(FRAME_SIZE_ERROR)
+    }
+
+    /// Fails every stream after an error that leaves the connection unusable, and returns the
+    /// GOAWAY frame that tells the server why.
+    fn fail_connection(&mut self, error_code: u32) -> ~[Frame] {
+        self.close();
+        let mut payload = ~[];
+        push_u32(0, &mut payload);
+        push_u32(error_code, &mut payload);
+        ~[Frame::new(GOAWAY, 0, 0, payload)]
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0, |value, &byte| (value << 8) | byte as u32)
+}
+
+fn push_u32(value: u32, out: &mut ~[u8]) {
+    out.push_all([(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]);
+}
+
+#[test]
+fn test_integer_coding() {
+    let mut out = ~[];
+    encode_integer(10, 5, 0, &mut out);
+    assert!(out == ~[0x0a]);
+    out = ~[];
+    encode_integer(1337, 5, 0, &mut out);
+    assert!(out == ~[0x1f, 0x9a, 0x0a]);
+    out = ~[];
+    encode_integer(42, 8, 0, &mut out);
+    assert!(out == ~[0x2a]);
+
+    let mut position = 0;
+    assert!(decode_integer([0x1f, 0x9a, 0x0a], &mut position, 5) == Some(1337));
+    assert!(position == 3);
+}
+
+#[test]
+fn test_decode_headers_with_dynamic_table() {
+    let mut decoder = HeaderDecoder::new();
+    let first: &[u8] = &[0x82, 0x86, 0x84, 0x41, 0x0f, 0x77, 0x77, 0x77, 0x2e, 0x65, 0x78, 0x61,
+                         0x6d, 0x70, 0x6c, 0x65, 0x2e, 0x63, 0x6f, 0x6d];
+    assert!(decoder.decode(first) == Some(~[
+        (~":method", ~"GET"),
+        (~":scheme", ~"http"),
+        (~":path", ~"/"),
+        (~":authority", ~"www.example.com"),
+    ]));
+    assert!(decoder.size == 57);
+
+    let second: &[u8] = &[0x82, 0x86, 0x84, 0xbe, 0x58, 0x08, 0x6e, 0x6f, 0x2d, 0x63, 0x61, 0x63,
+                          0x68, 0x65];
+    assert!(decoder.decode(second) == Some(~[
+        (~":method", ~"GET"),
+        (~":scheme", ~"http"),
+        (~":path", ~"/"),
+        (~":authority", ~"www.example.com"),
+        (~"cache-control", ~"no-cache"),
+    ]));
+    assert!(decoder.size == 110);
+
+    assert!(decoder.decode([0xff, 0x00]).is_none());
+}
+
+#[test]
+fn test_encoded_headers_decode() {
+    let headers = ~[(~":method", ~"GET"), (~":path", ~"/style.css"), (~"x-custom", ~"yes")];
+    assert!(HeaderDecoder::new().decode(encode_headers(headers)) == Some(headers.clone()));
+}
+
+#[test]
+fn test_frame_round_trip() {
+    use std::vec;
+
+    let frame = Frame::new(DATA, FLAG_END_STREAM, 3, ~[1, 2, 3]);
+    let mut bytes = frame.to_bytes();
+    assert!(Frame::parse(bytes.slice_to(10)).is_none());
+    bytes.push(0);
+    assert!(Frame::parse(bytes) == Some((frame, 12)));
+
+    assert!(!Frame::is_too_large(bytes));
+    let too_large = Frame::new(DATA, 0, 1, vec::from_elem(MAX_FRAME_SIZE + 1, 0u8));
+    assert!(Frame::is_too_large(too_large.to_bytes().slice_to(9)));
+}
+
+#[test]
+fn test_connection_streams() {
+    use std::comm;
+    use std::from_str::FromStr;
+
+    let mut connection = Connection::new();
+    assert!(connection.receive(Frame::new(SETTINGS, 0, 0, ~[])) ==
+            ~[Frame::new(SETTINGS, FLAG_ACK, 0, ~[])]);
+
+    let (first_port, first_chan) = comm::stream();
+    let (second_port, second_chan) = comm::stream();
+    let url = FromStr::from_str("http://example.com/a").unwrap();
+    let first = connection.request(&url, first_chan);
+    let second = connection.request(&url, second_chan);
+    assert!(first.stream_id == 1 && second.stream_id == 3);
+
+    connection.receive(Frame::new(HEADERS, FLAG_END_HEADERS, 3, ~[0x88]));
+    connection.receive(Frame::new(DATA, FLAG_END_STREAM, 3, ~[4, 5]));
+    assert!(second_port.recv() == Payload(~[4, 5]));
+    assert!(second_port.recv() == Done(Ok(())));
+
+    connection.receive(Frame::new(RST_STREAM, 0, 1, ~[0, 0, 0, 8]));
+    assert!(first_port.recv() == Done(Err(())));
+}
+
+#[test]
+fn test_huffman_decode() {
+    assert!(huffman_decode([0xf1, 0xe3, 0xc2, 0xe5, 0xf2, 0x3a, 0x6b, 0xa0, 0xab, 0x90, 0xf4,
+                            0xff]) == Some("www.example.com".as_bytes().to_owned()));
+    assert!(huffman_decode([0x1f]) == Some(~['a' as u8]));
+    assert!(huffman_decode([]) == Some(~[]));
+
+    // Padding with zeros, padding of 8 bits, and the end of the string are errors.
+    assert!(huffman_decode([0x18]).is_none());
+    assert!(huffman_decode([0x1f, 0xff]).is_none());
+    assert!(huffman_decode([0xff, 0xff, 0xff, 0xff]).is_none());
+
+    // Every symbol decodes from its code.
+    for (symbol, &(code, length)) in HUFFMAN_CODES.slice_to(256).iter().enumerate() {
+        let padding = (8 - length as uint % 8) % 8;
+        let bits = ((code as u64) << padding) | ((1u64 << padding) - 1);
+        let bytes = (length as uint + padding) / 8;
+        let input: ~[u8] = range(0, bytes).map(|i| {
+            (bits >> (8 * (bytes - i - 1))) as u8
+        }).collect();
+        assert!(huffman_decode(input) == Some(~[symbol as u8]));
+    }
+}
+
+#[test]
+fn test_decode_huffman_coded_headers() {
+    let mut decoder = HeaderDecoder::new();
+    let block: &[u8] = &[0x48, 0x82, 0x64, 0x02, 0x58, 0x85, 0xae, 0xc3, 0x77, 0x1a, 0x4b, 0x61,
+                         0x96, 0xd0, 0x7a, 0xbe, 0x94, 0x10, 0x54, 0xd4, 0x44, 0xa8, 0x20, 0x05,
+                         0x95, 0x04, 0x0b, 0x81, 0x66, 0xe0, 0x82, 0xa6, 0x2d, 0x1b, 0xff, 0x6e,
+                         0x91, 0x9d, 0x29, 0xad, 0x17, 0x18, 0x63, 0xc7, 0x8f, 0x0b, 0x97, 0xc8,
+                         0xe9, 0xae, 0x82, 0xae, 0x43, 0xd3];
+    assert!(decoder.decode(block) == Some(~[
+        (~":status", ~"302"),
+        (~"cache-control", ~"private"),
+        (~"date", ~"Mon, 21 Oct 2013 20:13:21 GMT"),
+        (~"location", ~"https://www.example.com"),
+    ]));
+}
+
+#[test]
+fn test_goaway_closes_connection() {
+    use std::comm;
+    use std::from_str::FromStr;
+
+    let mut connection = Connection::new();
+    let (first_port, first_chan) = comm::stream();
+    let (second_port, second_chan) = comm::stream();
+    let url = FromStr::from_str("http://example.com/a").unwrap();
+    connection.request(&url, first_chan);
+    connection.request(&url, second_chan);
+    assert!(connection.is_open() && connection.has_streams());
+
+    // The server will still answer the first stream, but not the second.
+    connection.receive(Frame::new(GOAWAY, 0, 0, ~[0, 0, 0, 1, 0, 0, 0, 0]));
+    assert!(!connection.is_open());
+    assert!(second_port.recv() == Done(Err(())));
+    connection.receive(Frame::new(HEADERS, FLAG_END_HEADERS | FLAG_END_STREAM, 1, ~[0x88]));
+    assert!(first_port.recv() == Done(Ok(())));
+    assert!(!connection.has_streams());
+}
+
+#[test]
+fn test_push_promise_is_refused() {
+    use std::comm;
+    use std::from_str::FromStr;
+
+    let mut connection = Connection::new();
+    let preface = connection.preface();
+    assert!(preface.slice_from(CONNECTION_PREFACE.len()).to_owned() ==
+            ~[0, 0, 6, SETTINGS, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0]);
+
+    let (port, chan) = comm::stream();
+    let url = FromStr::from_str("http://example.com/a").unwrap();
+    connection.request(&url, chan);
+
+    // The promised request is decoded, adding its authority to the table, and then refused.
+    let promise = ~[0, 0, 0, 2, 0x82, 0x84, 0x41, 0x03, 0x61, 0x62, 0x63];
+    assert!(connection.receive(Frame::new(PUSH_PROMISE, FLAG_END_HEADERS, 1, promise)) ==
+            ~[Frame::new(RST_STREAM, 0, 2, ~[0, 0, 0, 7])]);
+    assert!(connection.receive(Frame::new(HEADERS, FLAG_END_HEADERS | FLAG_END_STREAM, 1,
+                                          ~[0x88, 0xbe])) == ~[]);
+    assert!(port.recv() == Done(Ok(())));
+}
@@ -0,0 +1,224 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Loads `http` URLs over HTTP/2 from the origins that are known to speak it in cleartext, given
+//! on the command line as `-2`, and over HTTP/1.1 from other origins. The pool keeps one
+//! connection to each origin, on which the loads of all of its resources are streams, so that
+//! they don't wait on each other.
+//!
+//! FIXME: HTTP/2 is only spoken to the origins that were given, since there is no TLS to
+//! negotiate it with ALPN. They must be given by IP address, since host names can't be resolved
+//! yet.
+
+use http2::{Connection, Frame};
+use http_loader;
+use resource_task::{Done, LoaderTask, LoaderTaskFactory, ProgressMsg};
+
+use std::cell::Cell;
+use std::comm;
+use std::comm::{Chan, Port, SharedChan};
+use std::from_str::FromStr;
+use std::rt::io::{Reader, Writer, io_error};
+use std::rt::io::net::ip::SocketAddr;
+use std::rt::io::net::tcp::TcpStream;
+use extra::url::Url;
+
+enum PoolMsg {
+    /// Load a URL on the connection to the address of its origin, opening one if there is none.
+    Load(Url, SocketAddr, Chan<ProgressMsg>),
+    /// The connection to the address has closed, and starts no more loads.
+    Closed(SocketAddr),
+    Exit,
+}
+
+enum ConnectionMsg {
+    Request(Url, Chan<ProgressMsg>),
+    /// The pool has forgotten the connection, so no more requests will come.
+    Forgotten,
+}
+
+/// Tells the pool to exit when the factory that owns it is dropped. The connections that have
+/// loads in progress finish them first.
+struct PoolHandle {
+    chan: SharedChan<PoolMsg>,
+}
+
+impl Drop for PoolHandle {
+    fn drop(&self) {
+        self.chan.send(Exit)
+    }
+}
+
+pub fn factory(http2_addresses: ~[SocketAddr]) -> LoaderTaskFactory {
+    let pool = PoolHandle {
+        chan: start_pool(),
+    };
+    let f: LoaderTaskFactory = || {
+        let http2_addresses = http2_addresses.clone();
+        let pool = pool.chan.clone();
+        let loader: LoaderTask = |url, progress_chan| {
+            match address_of(&url) {
+                Some(address) if http2_addresses.contains(&address) => {
+                    pool.send(Load(url, address, progress_chan))
+                }
+                _ => (http_loader::factory())(url, progress_chan),
+            }
+        };
+        loader
+    };
+    f
+}
+
+/// Returns the address of the origin of a URL, if its host is an IP address.
+fn address_of(url: &Url) -> Option<SocketAddr> {
+    let port = match url.port {
+        Some(ref port) => port.clone(),
+        None => ~"80",
+    };
+    FromStr::from_str(fmt!("%s:%s", url.host, port))
+}
+
+fn start_pool() -> SharedChan<PoolMsg> {
+    let (port, chan) = comm::stream();
+    let chan = SharedChan::new(chan);
+    let port = Cell::new(port);
+    let pool_chan = Cell::new(chan.clone());
+    do spawn {
+        let port: Port<PoolMsg> = port.take();
+        let pool_chan = pool_chan.take();
+        // There are few origins, so the connections to them are kept in a list.
+        let mut connections: ~[(SocketAddr, Chan<ConnectionMsg>)] = ~[];
+        loop {
+            match port.recv() {
+                Load(url, address, progress_chan) => {
+                    let index = match connections.iter().position(|&(a, _)| a == address) {
+                        Some(index) => index,
+                        None => {
+                            let connection = start_connection(address, pool_chan.clone());
+                            connections.push((address, connection));
+                            connections.len() - 1
+                        }
+                    };
+                    connections[index].second_ref().send(Request(url, progress_chan))
+                }
+                Closed(address) => {
+                    match connections.iter().position(|&(a, _)| a == address) {
+                        Some(index) => connections.remove(index).second().send(Forgotten),
+                        None => {}
+                    }
+                }
+                Exit => {
+                    for &(_, ref connection) in connections.iter() {
+                        connection.send(Forgotten)
+                    }
+                    break
+                }
+            }
+        }
+    }
+    chan
+}
+
+fn start_connection(address: SocketAddr, pool_chan: SharedChan<PoolMsg>) -> Chan<ConnectionMsg> {
+    let (port, chan) = comm::stream();
+    let port = Cell::new(port);
+    let pool_chan = Cell::new(pool_chan);
+    do spawn {
+        run_connection(address, port.take(), pool_chan.take())
+    }
+    chan
+}
+
+/// Runs a connection until it closes, or until the pool has forgotten it and its loads have
+/// finished. Errors of the socket end the loads on it, rather than the task.
+fn run_connection(address: SocketAddr,
+                  port: Port<ConnectionMsg>,
+                  pool_chan: SharedChan<PoolMsg>) {
+    let mut forgotten = false;
+    do io_error::cond.trap(|error| {
+        debug!("http2: error on the connection to %s: %?", address.to_str(), error)
+    }).inside {
+        match TcpStream::connect(address) {
+            Some(stream) => {
+                let mut stream = stream;
+                let mut connection = Connection::new();
+                stream.write(connection.preface());
+                let mut input = ~[];
+                loop {
+                    // Start the loads that have been asked for, waiting for one if there are
+                    // none in progress.
+                    while connection.is_open() && !forgotten &&
+                            (!connection.has_streams() || port.peek()) {
+                        match port.recv() {
+                            Request(url, progress_chan) => {
+                                debug!("http2: requesting %s", url.to_str());
+                                stream.write(connection.request(&url, progress_chan).to_bytes())
+                            }
+                            Forgotten => forgotten = true,
+                        }
+                    }
+                    if !connection.has_streams() {
+                        break
+                    }
+                    // FIXME: A request that is asked for while the task waits here is only sent
+                    // once the next frame arrives. Selecting on the port and the socket together
+                    // needs the socket to be read on one task and written on another, which a
+                    // `TcpStream` can't be yet.
+                    let replies = match read_frame(&mut stream, &mut input) {
+                        Received(frame) => connection.receive(frame),
+                        TooLarge => connection.receive_too_large(),
+                        SocketClosed => {
+                            connection.close();
+                            ~[]
+                        }
+                    };
+                    for reply in replies.iter() {
+                        stream.write(reply.to_bytes())
+                    }
+                }
+            }
+            None => debug!("http2: couldn't connect to %s", address.to_str()),
+        }
+    }
+
+    // Fail the loads that the pool sends before it has forgotten the connection.
+    if !forgotten {
+        pool_chan.send(Closed(address));
+        loop {
+            match port.recv() {
+                Request(_, progress_chan) => progress_chan.send(Done(Err(()))),
+                Forgotten => break,
+            }
+        }
+    }
+}
+
+enum ReadResult {
+    Received(Frame),
+    /// The server has started a frame longer than it may send, which isn't waited for.
+    TooLarge,
+    SocketClosed,
+}
+
+/// Reads the next frame from the socket, keeping what has arrived of the frames after it in
+/// `input`.
+fn read_frame(stream: &mut TcpStream, input: &mut ~[u8]) -> ReadResult {
+    loop {
+        if Frame::is_too_large(*input) {
+            return TooLarge
+        }
+        match Frame::parse(*input) {
+            Some((frame, length)) => {
+                *input = input.slice_from(length).to_owned();
+                return Received(frame)
+            }
+            None => {}
+        }
+        let mut buf = [0u8, ..4096];
+        match stream.read(buf) {
+            Some(length) => input.push_all(buf.slice_to(length)),
+            None => return SocketClosed,
+        }
+    }
+}
@@ -8,9 +8,10 @@ use std::cell::Cell;
 use std::vec;
 use extra::url::Url;
 use http::client::RequestWriter;
-use http::method::Get;
+use http::method::{Get, Post};
+use http::headers::content_type::MediaType;
 use http::headers::HeaderEnum;
-use std::rt::io::Reader;
+use std::rt::io::{Reader, Writer};
 
 pub fn factory() -> LoaderTask {
 	let f: LoaderTask = |url, progress_chan| {
@@ -55,3 +56,28 @@ fn load(url: Url, progress_chan: Chan<ProgressMsg>) {
         progress_chan.send(Payload(buf));
     }
 }
+
+/// Posts `body` to `url` as plain text, on a task of its own, ignoring the response.
+pub fn send_beacon(url: Url, body: ~[u8]) {
+    assert!(url.scheme == ~"http");
+    let url = Cell::new(url);
+    let body = Cell::new(body);
+    do spawn {
+        let url = url.take();
+        let body = body.take();
+        info!("sending a beacon to %s", url.to_str());
+
+        let mut request = ~RequestWriter::new(Post, url.clone());
+        request.headers.content_length = Some(body.len());
+        request.headers.content_type = Some(MediaType {
+            type_: ~"text",
+            subtype: ~"plain",
+            parameters: ~[(~"charset", ~"UTF-8")],
+        });
+        request.write(body);
+        match request.read_response() {
+            Ok(response) => info!("beacon to %s answered with %?", url.to_str(), response.status),
+            Err(_) => info!("beacon to %s failed", url.to_str()),
+        }
+    }
+}
@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use std::uint;
 use std::vec;
 use stb_image = stb_image::image;
 
@@ -20,6 +21,15 @@ pub fn test_image_bin() -> ~[u8] {
     return vec::from_fn(4962, |i| TEST_IMAGE[i]);
 }
 
+/// Returns true if `data` looks like an SVG document rather than an image in a raster format.
+/// SVG images are rasterized, rather than decoded, since they have no size of their own.
+pub fn is_svg(data: &[u8]) -> bool {
+    // The root element may come after an XML declaration, a doctype and comments.
+    let prefix = data.slice_to(uint::min(data.len(), 1024));
+    let svg = bytes!("<svg");
+    prefix.window_iter(svg.len()).any(|window| window == svg)
+}
+
 pub fn load_from_memory(buffer: &[u8]) -> Option<Image> {
     // Can't remember why we do this. Maybe it's what cairo wants
     static FORCE_DEPTH: uint = 4;
@@ -48,3 +58,56 @@ pub fn load_from_memory(buffer: &[u8]) -> Option<Image> {
         stb_image::Error => None
     }
 }
+
+/// Scales `image`, which must have four bytes to a pixel, to `width` by `height` pixels. Each pixel
+/// of the result is the average of the pixels of the image that it covers, or the pixel that it
+/// is in where the image is enlarged.
+pub fn resize(image: &Image, width: uint, height: uint) -> Image {
+    assert!(image.depth == 4);
+    // The range of source pixels that the `i`th of `count` pixels covers in a span of `length`.
+    let covered = |i: uint, count: uint, length: uint| {
+        let start = i * length / count;
+        (start, uint::max(start + 1, (i + 1) * length / count))
+    };
+
+    let mut data = vec::with_capacity(width * height * 4);
+    for y in range(0, height) {
+        let (top, bottom) = covered(y, height, image.height);
+        for x in range(0, width) {
+            let (left, right) = covered(x, width, image.width);
+            let mut sums = [0u, 0, 0, 0];
+            for source_y in range(top, bottom) {
+                for source_x in range(left, right) {
+                    let pixel = (source_y * image.width + source_x) * 4;
+                    for channel in range(0u, 4) {
+                        sums[channel] += image.data[pixel + channel] as uint;
+                    }
+                }
+            }
+            let count = (bottom - top) * (right - left);
+            for channel in range(0u, 4) {
+                data.push((sums[channel] / count) as u8);
+            }
+        }
+    }
+    Image(width, height, 4, data)
+}
+
+#[test]
+fn test_is_svg() {
+    assert!(is_svg(bytes!("<?xml version='1.0'?>\n<!-- icon -->\n<svg></svg>")));
+    assert!(!is_svg(test_image_bin()));
+}
+
+#[test]
+fn test_resize() {
+    let image = Image(2, 2, 4, ~[0, 0, 0, 255,  100, 0, 0, 255,
+                                 0, 200, 0, 255,  0, 0, 40, 255]);
+    let shrunk = resize(&image, 1, 1);
+    assert!(shrunk.data == ~[25, 50, 10, 255]);
+
+    let enlarged = resize(&image, 4, 2);
+    assert!(enlarged.width == 4 && enlarged.height == 2);
+    assert!(enlarged.data.slice(0, 8) == [0, 0, 0, 255, 0, 0, 0, 255]);
+    assert!(enlarged.data.slice(8, 16) == [100, 0, 0, 255, 100, 0, 0, 255]);
+}
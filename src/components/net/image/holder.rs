@@ -91,5 +91,22 @@ impl ImageHolder {
 
         return result;
     }
+
+    /// Returns the image to be drawn at a size in device pixels. SVG images are rasterized at that
+    /// size, so they are kept by the local image cache for each size rather than here.
+    pub fn get_image_at_size(&mut self, size: Size2D<uint>) -> Option<Arc<~Image>> {
+        debug!("get_image_at_size() %? %ux%u", self.url, size.width, size.height);
+        match self.local_image_cache.get_image_at_size(&self.url, size).recv() {
+            ImageReady(image) => Some(image),
+            ImageNotReady => {
+                debug!("image not ready for %s", self.url.to_str());
+                None
+            }
+            ImageFailed => {
+                debug!("image decoding failed for %s", self.url.to_str());
+                None
+            }
+        }
+    }
 }
 
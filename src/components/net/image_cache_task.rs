@@ -2,13 +2,16 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use image::base::{Image, load_from_memory};
+use image::base::{Image, is_svg, load_from_memory};
 use resource_task;
 use resource_task::ResourceTask;
 use servo_util::url::{UrlMap, url_map};
 
+use geom::size::Size2D;
 use std::cell::Cell;
 use std::comm::{Chan, Port, SharedChan, stream};
+use std::hashmap::HashMap;
+use std::task;
 use std::task::spawn;
 use std::to_str::ToStr;
 use std::util::replace;
@@ -38,6 +41,24 @@ pub enum Msg {
     /// Wait for an image to become available (or fail to load).
     WaitForImage(Url, Chan<ImageResponseMsg>),
 
+    /// Request an Image object for a URL at a size in device pixels. If the image is not
+    /// immediately available at that size then ImageNotReady is returned, and an SVG image starts
+    /// being rasterized at it.
+    GetImageAtSize(Url, Size2D<uint>, Chan<ImageResponseMsg>),
+
+    /// Wait for an image to become available at a size in device pixels. SVG images are
+    /// rasterized at that size; other images are returned at their own size. Must be posted after
+    /// Decode
+    WaitForImageAtSize(Url, Size2D<uint>, Chan<ImageResponseMsg>),
+
+    /// Used by the rasterizer tasks to post SVG images rasterized at a size back to the cache
+    priv StoreSizedImage(Url, Size2D<uint>, Option<Arc<~Image>>),
+
+    /// Tell the cache to let go of the decoded image of a URL, and of its rasterizations, which
+    /// are freed once no client holds them either. The image is decoded again from its data the
+    /// next time it is asked for.
+    Evict(Url),
+
     /// For testing
     priv OnMsg(~fn(msg: &Msg)),
 
@@ -82,16 +103,37 @@ pub type ImageCacheTask = SharedChan<Msg>;
 
 type DecoderFactory = ~fn() -> ~fn(&[u8]) -> Option<Image>;
 
+/// Rasterizes SVG data, at a size in device pixels or else at the size the image says it is.
+pub type SvgRasterizer = ~fn(&[u8], Option<Size2D<uint>>) -> Option<Image>;
+
+type SvgRasterizerFactory = ~fn() -> SvgRasterizer;
+
 pub fn ImageCacheTask(resource_task: ResourceTask) -> ImageCacheTask {
     ImageCacheTask_(resource_task, default_decoder_factory)
 }
 
+/// Creates an image cache that rasterizes SVG images with rasterizers from the given factory. The
+/// rasterizer lives in the graphics crate, which this one can't depend on.
+pub fn ImageCacheTaskWithSvg(resource_task: ResourceTask,
+                             svg_rasterizer_factory: SvgRasterizerFactory)
+                             -> ImageCacheTask {
+    spawn_image_cache(resource_task, default_decoder_factory, svg_rasterizer_factory)
+}
+
 pub fn ImageCacheTask_(resource_task: ResourceTask, decoder_factory: DecoderFactory)
                        -> ImageCacheTask {
+    spawn_image_cache(resource_task, decoder_factory, no_svg_rasterizer_factory)
+}
+
+fn spawn_image_cache(resource_task: ResourceTask,
+                     decoder_factory: DecoderFactory,
+                     svg_rasterizer_factory: SvgRasterizerFactory)
+                     -> ImageCacheTask {
     // FIXME: Doing some dancing to avoid copying decoder_factory, our test
     // version of which contains an uncopyable type which rust will currently
     // copy unsoundly
     let decoder_factory_cell = Cell::new(decoder_factory);
+    let svg_rasterizer_factory_cell = Cell::new(svg_rasterizer_factory);
 
     let (port, chan) = stream();
     let chan = SharedChan::new(chan);
@@ -102,10 +144,15 @@ pub fn ImageCacheTask_(resource_task: ResourceTask, decoder_factory: DecoderFact
         let mut cache = ImageCache {
             resource_task: resource_task.clone(),
             decoder_factory: decoder_factory_cell.take(),
+            svg_rasterizer_factory: svg_rasterizer_factory_cell.take(),
             port: port_cell.take(),
             chan: chan_cell.take(),
             state_map: url_map(),
             wait_map: url_map(),
+            svg_map: url_map(),
+            encoded_map: url_map(),
+            sized_map: @mut HashMap::new(),
+            sized_wait_map: url_map(),
             need_exit: None
         };
         cache.run();
@@ -129,6 +176,9 @@ fn SyncImageCacheTask(resource_task: ResourceTask) -> ImageCacheTask {
                 GetImage(url, response) => {
                     inner_cache.send(WaitForImage(url, response));
                 }
+                GetImageAtSize(url, size, response) => {
+                    inner_cache.send(WaitForImageAtSize(url, size, response));
+                }
                 Exit(response) => {
                     inner_cache.send(Exit(response));
                     break;
@@ -146,6 +196,8 @@ struct ImageCache {
     resource_task: ResourceTask,
     /// Creates image decoders
     decoder_factory: DecoderFactory,
+    /// Creates SVG rasterizers
+    svg_rasterizer_factory: SvgRasterizerFactory,
     /// The port on which we'll receive client requests
     port: Port<Msg>,
     /// A copy of the shared chan to give to child tasks
@@ -154,6 +206,14 @@ struct ImageCache {
     state_map: UrlMap<ImageState>,
     /// List of clients waiting on a WaitForImage response
     wait_map: UrlMap<@mut ~[Chan<ImageResponseMsg>]>,
+    /// The source of each SVG image, which is rasterized again for each size it's drawn at
+    svg_map: UrlMap<@~[u8]>,
+    /// The data of each image that has been decoded, to decode it again after it's evicted
+    encoded_map: UrlMap<@~[u8]>,
+    /// The state of rasterizing an SVG image at a size
+    sized_map: @mut HashMap<(Url, uint, uint), SizedImageState>,
+    /// List of clients waiting on a WaitForImageAtSize response before the image is decoded
+    sized_wait_map: UrlMap<@mut ~[(Size2D<uint>, Chan<ImageResponseMsg>)]>,
     need_exit: Option<Chan<()>>,
 }
 
@@ -164,9 +224,19 @@ enum ImageState {
     Prefetched(@Cell<~[u8]>),
     Decoding,
     Decoded(@Arc<~Image>),
+    /// Decoded, and then evicted
+    Evicted,
     Failed
 }
 
+#[deriving(Clone)]
+enum SizedImageState {
+    /// Being rasterized, with the clients waiting for it
+    Rasterizing(@mut ~[Chan<ImageResponseMsg>]),
+    Rasterized(@Arc<~Image>),
+    RasterFailed
+}
+
 #[deriving(Clone)]
 enum AfterPrefetch {
     DoDecode,
@@ -194,9 +264,17 @@ impl ImageCache {
                 Decode(url) => self.decode(url),
                 StoreImage(url, image) => self.store_image(url, image),
                 GetImage(url, response) => self.get_image(url, response),
+                GetImageAtSize(url, size, response) => {
+                    self.get_image_at_size(url, size, response)
+                }
                 WaitForImage(url, response) => {
                     self.wait_for_image(url, response)
                 }
+                WaitForImageAtSize(url, size, response) => {
+                    self.wait_for_image_at_size(url, size, response)
+                }
+                StoreSizedImage(url, size, image) => self.store_sized_image(url, size, image),
+                Evict(url) => self.evict(url),
                 OnMsg(handler) => msg_handlers.push(handler),
                 Exit(response) => {
                     assert!(self.need_exit.is_none());
@@ -216,7 +294,13 @@ impl ImageCache {
                         Prefetching(*) => can_exit = false,
                         Decoding => can_exit = false,
 
-                        Init | Prefetched(*) | Decoded(*) | Failed => ()
+                        Init | Prefetched(*) | Decoded(*) | Evicted | Failed => ()
+                    }
+                }
+                for (_, state) in self.sized_map.iter() {
+                    match *state {
+                        Rasterizing(*) => can_exit = false,
+                        Rasterized(*) | RasterFailed => ()
                     }
                 }
 
@@ -268,7 +352,7 @@ impl ImageCache {
                 self.set_state(url, Prefetching(DoNotDecode));
             }
 
-            Prefetching(*) | Prefetched(*) | Decoding | Decoded(*) | Failed => {
+            Prefetching(*) | Prefetched(*) | Decoding | Decoded(*) | Evicted | Failed => {
                 // We've already begun working on this image
             }
         }
@@ -297,6 +381,7 @@ impl ImageCache {
           | Prefetched(*)
           | Decoding
           | Decoded(*)
+          | Evicted
           | Failed => {
             fail!(~"wrong state for storing prefetched image")
           }
@@ -320,9 +405,19 @@ impl ImageCache {
                 assert!(!data_cell.is_empty());
 
                 let data = data_cell.take();
+                self.encoded_map.insert(url.clone(), @data.clone());
                 let to_cache = self.chan.clone();
                 let url_cell = Cell::new(url.clone());
-                let decode = (self.decoder_factory)();
+                let decode = if is_svg(data) {
+                    // An SVG image is decoded by rasterizing it at its own size. It's kept to be
+                    // rasterized at the sizes it's drawn at.
+                    self.svg_map.insert(url.clone(), @data.clone());
+                    let rasterize = (self.svg_rasterizer_factory)();
+                    let decode: ~fn(&[u8]) -> Option<Image> = |data| rasterize(data, None);
+                    decode
+                } else {
+                    (self.decoder_factory)()
+                };
 
                 do spawn {
                     let url = url_cell.take();
@@ -340,6 +435,12 @@ impl ImageCache {
                 self.set_state(url, Decoding);
             }
 
+            Evicted => {
+                let data = *self.encoded_map.get(&url);
+                self.set_state(url.clone(), Prefetched(@Cell::new((*data).clone())));
+                self.decode(url)
+            }
+
             Decoding | Decoded(*) | Failed => {
                 // We've already begun decoding
             }
@@ -353,19 +454,29 @@ impl ImageCache {
             match image {
               Some(image) => {
                 self.set_state(url.clone(), Decoded(@image.clone()));
-                self.purge_waiters(url, || ImageReady(image.clone()) );
+                self.purge_waiters(url.clone(), || ImageReady(image.clone()) );
               }
               None => {
                 self.set_state(url.clone(), Failed);
-                self.purge_waiters(url, || ImageFailed );
+                self.purge_waiters(url.clone(), || ImageFailed );
               }
             }
+            match self.sized_wait_map.pop(&url) {
+                Some(waiters) => {
+                    let waiters = replace(&mut *waiters, ~[]);
+                    for (size, response) in waiters.move_iter() {
+                        self.wait_for_image_at_size(url.clone(), size, response)
+                    }
+                }
+                None => ()
+            }
           }
 
           Init
           | Prefetching(*)
           | Prefetched(*)
           | Decoded(*)
+          | Evicted
           | Failed => {
             fail!(~"incorrect state in store_image")
           }
@@ -391,6 +502,10 @@ impl ImageCache {
             Prefetching(DoNotDecode) | Prefetched(*) => fail!(~"request for image before decode"),
             Decoding => response.send(ImageNotReady),
             Decoded(image) => response.send(ImageReady((*image).clone())),
+            Evicted => {
+                self.decode(url);
+                response.send(ImageNotReady)
+            }
             Failed => response.send(ImageFailed),
         }
     }
@@ -415,12 +530,175 @@ impl ImageCache {
                 response.send(ImageReady((*image).clone()));
             }
 
+            Evicted => {
+                self.decode(url.clone());
+                self.wait_for_image(url, response)
+            }
+
             Failed => {
                 response.send(ImageFailed);
             }
         }
     }
 
+    fn get_image_at_size(&self, url: Url, size: Size2D<uint>, response: Chan<ImageResponseMsg>) {
+        let image = match self.get_state(url.clone()) {
+            Decoded(image) => image,
+            _ => return self.get_image(url, response),
+        };
+        let data = match self.svg_map.find(&url) {
+            Some(data) => *data,
+            None => return response.send(ImageReady((*image).clone())),
+        };
+        if size.width == image.get().width && size.height == image.get().height {
+            return response.send(ImageReady((*image).clone()))
+        }
+        match self.sized_map.find(&(url.clone(), size.width, size.height)) {
+            Some(&Rasterizing(_)) => response.send(ImageNotReady),
+            Some(&Rasterized(image)) => response.send(ImageReady((*image).clone())),
+            Some(&RasterFailed) => response.send(ImageFailed),
+            None => {
+                self.rasterize(url, data, size);
+                response.send(ImageNotReady)
+            }
+        }
+    }
+
+    fn wait_for_image_at_size(&self, url: Url, size: Size2D<uint>,
+                              response: Chan<ImageResponseMsg>) {
+        match self.get_state(url.clone()) {
+            Init => fail!(~"request for image before prefetch"),
+
+            Prefetching(DoNotDecode) | Prefetched(*) => fail!(~"request for image before decode"),
+
+            Prefetching(DoDecode) | Decoding => {
+                // We don't know yet whether this image is an SVG image
+                if self.sized_wait_map.contains_key(&url) {
+                    let waiters = self.sized_wait_map.find_mut(&url).unwrap();
+                    waiters.push((size, response));
+                } else {
+                    self.sized_wait_map.insert(url, @mut ~[(size, response)]);
+                }
+            }
+
+            Decoded(image) => {
+                let data = match self.svg_map.find(&url) {
+                    Some(data) => *data,
+                    None => {
+                        // Raster images are scaled when they're drawn.
+                        response.send(ImageReady((*image).clone()));
+                        return
+                    }
+                };
+                if size.width == image.get().width && size.height == image.get().height {
+                    response.send(ImageReady((*image).clone()));
+                    return
+                }
+
+                let key = (url.clone(), size.width, size.height);
+                match self.sized_map.find(&key) {
+                    Some(&Rasterizing(waiters)) => {
+                        waiters.push(response);
+                        return
+                    }
+                    Some(&Rasterized(image)) => {
+                        response.send(ImageReady((*image).clone()));
+                        return
+                    }
+                    Some(&RasterFailed) => {
+                        response.send(ImageFailed);
+                        return
+                    }
+                    None => ()
+                }
+
+                self.rasterize(url, data, size);
+                match self.sized_map.find(&key) {
+                    Some(&Rasterizing(waiters)) => waiters.push(response),
+                    _ => fail!(~"incorrect state after rasterize"),
+                }
+            }
+
+            Evicted => {
+                self.decode(url.clone());
+                self.wait_for_image_at_size(url, size, response)
+            }
+
+            Failed => {
+                response.send(ImageFailed);
+            }
+        }
+    }
+
+    /// Starts rasterizing an SVG image at a size, with no clients waiting for it yet.
+    fn rasterize(&self, url: Url, data: @~[u8], size: Size2D<uint>) {
+        // Rasterize on a task of its own, which has nothing but the data of the image, so that
+        // the image can neither load resources nor take the cache down if it fails.
+        let to_cache = self.chan.clone();
+        let url_cell = Cell::new(url.clone());
+        let data_cell = Cell::new((*data).clone());
+        let rasterize_cell = Cell::new((self.svg_rasterizer_factory)());
+
+        do spawn {
+            let url = url_cell.take();
+            debug!("image_cache_task: started rasterizing %s at %ux%u",
+                   url.to_str(), size.width, size.height);
+            let data_cell = Cell::new(data_cell.take());
+            let rasterize_cell = Cell::new(rasterize_cell.take());
+            let image = do task::try {
+                (rasterize_cell.take())(data_cell.take(), Some(size))
+            };
+            let image = match image {
+                Ok(Some(image)) => Some(Arc::new(~image)),
+                Ok(None) | Err(()) => None,
+            };
+            to_cache.send(StoreSizedImage(url.clone(), size, image));
+            debug!("image_cache_task: ended rasterizing %s", url.to_str());
+        }
+
+        self.sized_map.insert((url, size.width, size.height), Rasterizing(@mut ~[]));
+    }
+
+    fn evict(&self, url: Url) {
+        match self.get_state(url.clone()) {
+            Decoded(*) => self.set_state(url.clone(), Evicted),
+            _ => ()
+        }
+        // Rasterizations in progress are left to the clients waiting on them.
+        let mut rasterized = ~[];
+        for (key, state) in self.sized_map.iter() {
+            let &(ref key_url, _, _) = key;
+            match *state {
+                Rasterized(*) if *key_url == url => rasterized.push(key.clone()),
+                _ => ()
+            }
+        }
+        for key in rasterized.iter() {
+            self.sized_map.remove(key);
+        }
+    }
+
+    fn store_sized_image(&self, url: Url, size: Size2D<uint>, image: Option<Arc<~Image>>) {
+        let key = (url, size.width, size.height);
+        let waiters = match self.sized_map.find(&key) {
+            Some(&Rasterizing(waiters)) => waiters,
+            _ => fail!(~"incorrect state in store_sized_image"),
+        };
+        match image {
+            Some(image) => {
+                for response in waiters.iter() {
+                    response.send(ImageReady(image.clone()));
+                }
+                self.sized_map.insert(key, Rasterized(@image));
+            }
+            None => {
+                for response in waiters.iter() {
+                    response.send(ImageFailed);
+                }
+                self.sized_map.insert(key, RasterFailed);
+            }
+        }
+    }
 }
 
 
@@ -462,6 +740,11 @@ fn default_decoder_factory() -> ~fn(&[u8]) -> Option<Image> {
     foo
 }
 
+fn no_svg_rasterizer_factory() -> SvgRasterizer {
+    let rasterize: SvgRasterizer = |_data, _size| None;
+    rasterize
+}
+
 #[cfg(test)]
 fn mock_resource_task(on_load: ~fn(resource: Chan<resource_task::ProgressMsg>)) -> ResourceTask {
     do spawn_listener |port: Port<resource_task::ControlMsg>| {
@@ -1063,3 +1346,93 @@ fn sync_cache_should_wait_for_images() {
     mock_resource_task.send(resource_task::Exit);
 }
 
+
+#[test]
+fn should_rasterize_svg_images_at_the_requested_size() {
+    let mock_resource_task = do mock_resource_task |response| {
+        response.send(resource_task::Payload(bytes!("<svg></svg>").to_owned()));
+        response.send(resource_task::Done(result::Ok(())));
+    };
+
+    // Draws images with no size of their own at 3x2.
+    let svg_rasterizer_factory: SvgRasterizerFactory = || {
+        let rasterize: SvgRasterizer = |_data, size| {
+            let size = size.unwrap_or_default(Size2D(3, 2));
+            let data = std::vec::from_elem(size.width * size.height * 4, 0u8);
+            Some(Image(size.width, size.height, 4, data))
+        };
+        rasterize
+    };
+
+    let image_cache_task = ImageCacheTaskWithSvg(mock_resource_task, svg_rasterizer_factory);
+    let url = make_url(~"file", None);
+
+    image_cache_task.send(Prefetch(url.clone()));
+    image_cache_task.send(Decode(url.clone()));
+
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(WaitForImage(url.clone(), response_chan));
+    match response_port.recv() {
+      ImageReady(image) => assert!(image.get().width == 3 && image.get().height == 2),
+      _ => fail
+    }
+
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(WaitForImageAtSize(url.clone(), Size2D(30, 20), response_chan));
+    match response_port.recv() {
+      ImageReady(image) => assert!(image.get().width == 30 && image.get().height == 20),
+      _ => fail
+    }
+
+    // Getting the image at a size it hasn't been rasterized at starts rasterizing it, and getting
+    // it again once that is done returns it.
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(GetImageAtSize(url.clone(), Size2D(6, 4), response_chan));
+    assert!(response_port.recv() == ImageNotReady);
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(WaitForImageAtSize(url.clone(), Size2D(6, 4), response_chan));
+    response_port.recv();
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(GetImageAtSize(url, Size2D(6, 4), response_chan));
+    match response_port.recv() {
+      ImageReady(image) => assert!(image.get().width == 6 && image.get().height == 4),
+      _ => fail
+    }
+
+    image_cache_task.exit();
+    mock_resource_task.send(resource_task::Exit);
+}
+
+#[test]
+fn should_decode_evicted_images_again() {
+    let mock_resource_task = do mock_resource_task |response| {
+        response.send(resource_task::Payload(test_image_bin()));
+        response.send(resource_task::Done(result::Ok(())));
+    };
+
+    let image_cache_task = ImageCacheTask(mock_resource_task);
+    let url = make_url(~"file", None);
+
+    image_cache_task.send(Prefetch(url.clone()));
+    image_cache_task.send(Decode(url.clone()));
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(WaitForImage(url.clone(), response_chan));
+    match response_port.recv() {
+      ImageReady(_) => (),
+      _ => fail
+    }
+
+    image_cache_task.send(Evict(url.clone()));
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(GetImage(url.clone(), response_chan));
+    assert!(response_port.recv() == ImageNotReady);
+    let (response_chan, response_port) = stream();
+    image_cache_task.send(WaitForImage(url, response_chan));
+    match response_port.recv() {
+      ImageReady(_) => (),
+      _ => fail
+    }
+
+    image_cache_task.exit();
+    mock_resource_task.send(resource_task::Exit);
+}
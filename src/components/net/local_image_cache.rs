@@ -8,11 +8,15 @@ extra message traffic, it also avoids waiting on the same image
 multiple times and thus triggering reflows multiple times.
 */
 
-use image_cache_task::{Decode, GetImage, ImageCacheTask, ImageFailed, ImageNotReady, ImageReady};
-use image_cache_task::{ImageResponseMsg, Prefetch, WaitForImage};
+use image_cache_task::{Decode, Evict, GetImage, GetImageAtSize, ImageCacheTask, ImageFailed};
+use image_cache_task::{ImageNotReady, ImageReady, ImageResponseMsg, Prefetch, WaitForImage};
+use image_cache_task::WaitForImageAtSize;
 
+use geom::size::Size2D;
 use std::comm;
 use std::comm::Port;
+use std::hashmap::HashMap;
+use std::managed;
 use std::task;
 use servo_util::url::{UrlMap, url_map};
 use extra::url::Url;
@@ -22,7 +26,11 @@ pub fn LocalImageCache(image_cache_task: ImageCacheTask) -> LocalImageCache {
         image_cache_task: image_cache_task,
         round_number: 1,
         on_image_available: None,
-        state_map: url_map()
+        state_map: url_map(),
+        response_map: HashMap::new(),
+        decoded_bytes: 0,
+        max_decoded_bytes: None,
+        evicted_images: 0,
     }
 }
 
@@ -30,14 +38,36 @@ pub struct LocalImageCache {
     priv image_cache_task: ImageCacheTask,
     priv round_number: uint,
     priv on_image_available: Option<@fn() -> ~fn(ImageResponseMsg)>,
-    priv state_map: UrlMap<@mut ImageState>
+    priv state_map: UrlMap<@mut ImageState>,
+    /// The responses for each image at each size it has been asked for, in device pixels, where
+    /// `None` is its own size.
+    priv response_map: HashMap<(Url, Option<(uint, uint)>), @mut ResponseState>,
+    /// The size of the decoded images that this cache holds, in bytes.
+    priv decoded_bytes: uint,
+    /// The most bytes of decoded images that this cache may hold, if it is limited.
+    priv max_decoded_bytes: Option<uint>,
+    /// How many images have been evicted to keep within the limit.
+    priv evicted_images: uint,
 }
 
 struct ImageState {
     prefetched: bool,
     decoded: bool,
+}
+
+struct ResponseState {
     last_request_round: uint,
-    last_response: ImageResponseMsg
+    last_response: ImageResponseMsg,
+    /// The size of the decoded image that `last_response` holds, in bytes.
+    decoded_bytes: uint,
+}
+
+/// How much memory the decoded images of a local cache take, for the memory reports of its
+/// document.
+pub struct ImageMemoryUsage {
+    decoded_images: uint,
+    decoded_bytes: uint,
+    evicted_images: uint,
 }
 
 impl LocalImageCache {
@@ -65,8 +95,20 @@ impl LocalImageCache {
     }
 
     // FIXME: Should return a Future
-    pub fn get_image(&self, url: &Url) -> Port<ImageResponseMsg> {
-        let state = self.get_state(url);
+    pub fn get_image(&mut self, url: &Url) -> Port<ImageResponseMsg> {
+        self.get_response(url, None)
+    }
+
+    /// Returns an image to be drawn at a size in device pixels. SVG images are rasterized at that
+    /// size, and other images are returned at their own size, to be scaled when they're drawn.
+    pub fn get_image_at_size(&mut self, url: &Url, size: Size2D<uint>)
+                             -> Port<ImageResponseMsg> {
+        self.get_response(url, Some(size))
+    }
+
+    fn get_response(&mut self, url: &Url, size: Option<Size2D<uint>>)
+                    -> Port<ImageResponseMsg> {
+        let state = self.get_response_state(url, size);
 
         // Save the previous round number for comparison
         let last_round = state.last_request_round;
@@ -97,7 +139,12 @@ impl LocalImageCache {
         }
 
         let (response_port, response_chan) = comm::stream();
-        self.image_cache_task.send(GetImage((*url).clone(), response_chan));
+        match size {
+            Some(size) => {
+                self.image_cache_task.send(GetImageAtSize((*url).clone(), size, response_chan))
+            }
+            None => self.image_cache_task.send(GetImage((*url).clone(), response_chan)),
+        }
 
         let response = response_port.recv();
         match response {
@@ -113,7 +160,13 @@ impl LocalImageCache {
                 let url = (*url).clone();
                 do task::spawn {
                     let (response_port, response_chan) = comm::stream();
-                    image_cache_task.send(WaitForImage(url.clone(), response_chan));
+                    match size {
+                        Some(size) => {
+                            image_cache_task.send(WaitForImageAtSize(url.clone(), size,
+                                                                     response_chan))
+                        }
+                        None => image_cache_task.send(WaitForImage(url.clone(), response_chan)),
+                    }
                     on_image_available(response_port.recv());
                 }
             }
@@ -127,23 +180,98 @@ impl LocalImageCache {
             ImageFailed => ImageFailed
         };
         state.last_response = response_copy;
+        state.decoded_bytes = match state.last_response {
+            ImageReady(ref image) => image.get().data.len(),
+            ImageNotReady | ImageFailed => 0,
+        };
+        self.decoded_bytes += state.decoded_bytes;
+        self.evict_to_limit(Some(state));
 
         let (port, chan) = comm::stream();
         chan.send(response);
         return port;
     }
 
+    /// Limits the size of the decoded images that this cache holds for its document to `max`
+    /// bytes, evicting those that were asked for least recently to keep within it. The image
+    /// cache task lets go of the images that are evicted too, so that they are freed unless
+    /// another document holds them.
+    pub fn set_max_decoded_bytes(&mut self, max: Option<uint>) {
+        self.max_decoded_bytes = max;
+        self.evict_to_limit(None);
+    }
+
+    /// Returns how much memory the decoded images that this cache holds take.
+    pub fn memory_usage(&self) -> ImageMemoryUsage {
+        let decoded_images = self.response_map.iter().count(|(_, state)| {
+            state.decoded_bytes != 0
+        });
+        ImageMemoryUsage {
+            decoded_images: decoded_images,
+            decoded_bytes: self.decoded_bytes,
+            evicted_images: self.evicted_images,
+        }
+    }
+
+    /// Evicts the decoded images that were asked for least recently, other than that of the
+    /// response `keep`, until the cache is within its limit. An evicted image is decoded again by
+    /// the image cache task the next time it is needed.
+    fn evict_to_limit(&mut self, keep: Option<@mut ResponseState>) {
+        let max = match self.max_decoded_bytes {
+            Some(max) => max,
+            None => return,
+        };
+        while self.decoded_bytes > max {
+            let mut victim: Option<(&Url, @mut ResponseState)> = None;
+            for (&(ref url, _), state) in self.response_map.iter() {
+                let kept = keep.map_default(false, |&keep| managed::mut_ptr_eq(keep, *state));
+                if kept || state.decoded_bytes == 0 {
+                    loop
+                }
+                let older = do victim.map_default(true) |&(_, victim)| {
+                    state.last_request_round < victim.last_request_round
+                };
+                if older {
+                    victim = Some((url, *state))
+                }
+            }
+            let victim = match victim {
+                Some((url, victim)) => {
+                    self.image_cache_task.send(Evict(url.clone()));
+                    victim
+                }
+                None => break,
+            };
+            self.decoded_bytes -= victim.decoded_bytes;
+            victim.decoded_bytes = 0;
+            victim.last_response = ImageNotReady;
+            victim.last_request_round = 0;
+            self.evicted_images += 1;
+        }
+    }
+
     fn get_state(&self, url: &Url) -> @mut ImageState {
         let state = do self.state_map.find_or_insert_with(url.clone()) |_| {
             let new_state = @mut ImageState {
                 prefetched: false,
                 decoded: false,
-                last_request_round: 0,
-                last_response: ImageNotReady
             };
             new_state
         };
         *state  // Unborrowing the state
     }
+
+    fn get_response_state(&mut self, url: &Url, size: Option<Size2D<uint>>)
+                          -> @mut ResponseState {
+        let key = (url.clone(), size.map(|size| (size.width, size.height)));
+        let state = do self.response_map.find_or_insert_with(key) |_| {
+            @mut ResponseState {
+                last_request_round: 0,
+                last_response: ImageNotReady,
+                decoded_bytes: 0,
+            }
+        };
+        *state
+    }
 }
 
@@ -4,17 +4,25 @@
 
 //! A task that takes a URL and streams back the binary data.
 
+use about_loader;
 use file_loader;
+use http2_loader;
 use http_loader;
 
 use std::cell::Cell;
 use std::comm::{Chan, Port, SharedChan};
+use std::rt::io::net::ip::SocketAddr;
 use extra::url::Url;
+use servo_util::time::ProfilerChan;
 use util::spawn_listener;
 
 pub enum ControlMsg {
     /// Request the data associated with a particular URL
     Load(Url, Chan<ProgressMsg>),
+    /// Post data to a URL without waiting for the response, such as the analytics that pages
+    /// send as they unload. The request belongs to the resource task, so it is made even if the
+    /// page that sent it has closed by then.
+    Beacon(Url, ~[u8]),
     Exit
 }
 
@@ -36,7 +44,7 @@ Creates a task to load a specific resource
 The ResourceManager delegates loading to a different type of loader task for
 each URL scheme
 */
-type LoaderTaskFactory = ~fn() -> ~fn(url: Url, Chan<ProgressMsg>);
+pub type LoaderTaskFactory = ~fn() -> ~fn(url: Url, Chan<ProgressMsg>);
 
 pub type LoaderTask = ~fn(url: Url, Chan<ProgressMsg>);
 
@@ -51,6 +59,20 @@ pub fn ResourceTask() -> ResourceTask {
     create_resource_task_with_loaders(loaders)
 }
 
+/// Create a ResourceTask with the default loaders and the pages of the `about:` scheme, which
+/// show what the profiler has gathered. The origins at `http2_addresses` are loaded from over
+/// HTTP/2.
+pub fn ResourceTaskWithAbout(profiler_chan: ProfilerChan, http2_addresses: ~[SocketAddr])
+                             -> ResourceTask {
+    let file_loader_factory: LoaderTaskFactory = file_loader::factory;
+    let loaders = ~[
+        (~"file", file_loader_factory),
+        (~"http", http2_loader::factory(http2_addresses)),
+        (~"about", about_loader::factory(profiler_chan))
+    ];
+    create_resource_task_with_loaders(loaders)
+}
+
 fn create_resource_task_with_loaders(loaders: ~[(~str, LoaderTaskFactory)]) -> ResourceTask {
     let loaders_cell = Cell::new(loaders);
     let chan = do spawn_listener |from_client| {
@@ -83,6 +105,9 @@ impl ResourceManager {
               Load(url, progress_chan) => {
                 self.load(url.clone(), progress_chan)
               }
+              Beacon(url, body) => {
+                self.send_beacon(url, body)
+              }
               Exit => {
                 break
               }
@@ -104,6 +129,15 @@ impl ResourceManager {
         }
     }
 
+    fn send_beacon(&self, url: Url, body: ~[u8]) {
+        if url.scheme == ~"http" {
+            debug!("resource_task: sending a beacon to %s", url.to_str());
+            http_loader::send_beacon(url, body)
+        } else {
+            debug!("resource_task: no beacons for scheme %s", url.scheme);
+        }
+    }
+
     fn get_loader_factory(&self, url: &Url) -> Option<LoaderTask> {
         for scheme_loader in self.loaders.iter() {
             match *scheme_loader {
@@ -0,0 +1,243 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The platform audio outputs that the audio task plays rendered audio through: ALSA on Linux,
+//! where libasound is loaded at run time.
+//!
+//! FIXME: Mac OS needs a Core Audio output. Until then, and wherever the output can't be opened,
+//! such as where ALSA isn't installed, audio is rendered in real time and thrown away.
+
+use extra::time::precise_time_ns;
+use std::rt::io::timer::Timer;
+
+/// Somewhere to play audio.
+pub trait AudioSink {
+    /// Plays interleaved 16-bit samples, blocking until the output has room for them.
+    fn write(&mut self, samples: &[i16]);
+}
+
+/// Opens the default audio output of the platform, or an output that discards audio if it can't
+/// be opened.
+#[cfg(target_os = "linux")]
+pub fn open(sample_rate: uint, channels: uint) -> ~AudioSink {
+    match alsa::AlsaSink::open(sample_rate, channels) {
+        Some(sink) => ~sink as ~AudioSink,
+        None => ~NullSink::new(sample_rate, channels) as ~AudioSink,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn open(sample_rate: uint, channels: uint) -> ~AudioSink {
+    ~NullSink::new(sample_rate, channels) as ~AudioSink
+}
+
+/// An output that discards audio, at the rate it would be played.
+pub struct NullSink {
+    sample_rate: uint,
+    channels: uint,
+    /// The time the first samples were written, in nanoseconds.
+    start_time: u64,
+    /// The number of frames written.
+    frames: u64,
+    timer: Option<Timer>,
+}
+
+impl NullSink {
+    pub fn new(sample_rate: uint, channels: uint) -> NullSink {
+        NullSink {
+            sample_rate: sample_rate,
+            channels: channels,
+            start_time: precise_time_ns(),
+            frames: 0,
+            timer: Timer::new(),
+        }
+    }
+}
+
+impl AudioSink for NullSink {
+    fn write(&mut self, samples: &[i16]) {
+        self.frames += (samples.len() / self.channels) as u64;
+        // Wait until the samples would have been played.
+        let played_ms = self.frames * 1000 / (self.sample_rate as u64);
+        let elapsed_ms = (precise_time_ns() - self.start_time) / 1000000;
+        if played_ms > elapsed_ms {
+            for timer in self.timer.mut_iter() {
+                timer.sleep(played_ms - elapsed_ms)
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod alsa {
+    use audio_sink::AudioSink;
+
+    use std::cast;
+    use std::libc::{c_char, c_int, c_long, c_uint, c_ulong, c_void};
+    use std::ptr;
+    use std::vec;
+
+    type snd_pcm_t = c_void;
+
+    static SND_PCM_STREAM_PLAYBACK: c_int = 0;
+    static SND_PCM_FORMAT_S16_LE: c_int = 2;
+    static SND_PCM_ACCESS_RW_INTERLEAVED: c_int = 3;
+
+    static RTLD_NOW: c_int = 2;
+
+    /// How much audio ALSA buffers, in microseconds.
+    static LATENCY: c_uint = 50000;
+
+    #[link_args = "-ldl"]
+    extern {
+        fn dlopen(filename: *c_char, flag: c_int) -> *c_void;
+        fn dlsym(handle: *c_void, symbol: *c_char) -> *c_void;
+        fn dlclose(handle: *c_void) -> c_int;
+    }
+
+    /// The functions of libasound that the sink calls. The library is loaded when an output is
+    /// opened, rather than linked, so that Servo runs where ALSA isn't installed.
+    struct Alsa {
+        library: *c_void,
+        snd_pcm_open: extern "C" fn(pcm: *mut *snd_pcm_t, name: *c_char, stream: c_int,
+                                    mode: c_int) -> c_int,
+        snd_pcm_set_params: extern "C" fn(pcm: *snd_pcm_t,
+                                          format: c_int,
+                                          access: c_int,
+                                          channels: c_uint,
+                                          rate: c_uint,
+                                          soft_resample: c_int,
+                                          latency: c_uint)
+                                          -> c_int,
+        snd_pcm_writei: extern "C" fn(pcm: *snd_pcm_t, buffer: *c_void, size: c_ulong)
+                                      -> c_long,
+        snd_pcm_recover: extern "C" fn(pcm: *snd_pcm_t, err: c_int, silent: c_int) -> c_int,
+        snd_pcm_drain: extern "C" fn(pcm: *snd_pcm_t) -> c_int,
+        snd_pcm_close: extern "C" fn(pcm: *snd_pcm_t) -> c_int,
+    }
+
+    impl Alsa {
+        /// Loads libasound, or returns `None` if it or one of its functions can't be found.
+        #[fixed_stack_segment]
+        fn load() -> Option<Alsa> {
+            unsafe {
+                let library = do "libasound.so.2".with_c_str |name| {
+                    dlopen(name, RTLD_NOW)
+                };
+                if library.is_null() {
+                    debug!("no audio output: libasound can't be loaded");
+                    return None
+                }
+                let names = ["snd_pcm_open", "snd_pcm_set_params", "snd_pcm_writei",
+                             "snd_pcm_recover", "snd_pcm_drain", "snd_pcm_close"];
+                let symbols = do names.map |name| {
+                    do name.with_c_str |name| {
+                        dlsym(library, name)
+                    }
+                };
+                if symbols.iter().any(|symbol| symbol.is_null()) {
+                    debug!("no audio output: libasound lacks the functions it needs");
+                    dlclose(library);
+                    return None
+                }
+                Some(Alsa {
+                    library: library,
+                    snd_pcm_open: cast::transmute(symbols[0]),
+                    snd_pcm_set_params: cast::transmute(symbols[1]),
+                    snd_pcm_writei: cast::transmute(symbols[2]),
+                    snd_pcm_recover: cast::transmute(symbols[3]),
+                    snd_pcm_drain: cast::transmute(symbols[4]),
+                    snd_pcm_close: cast::transmute(symbols[5]),
+                })
+            }
+        }
+    }
+
+    impl Drop for Alsa {
+        #[fixed_stack_segment]
+        fn drop(&self) {
+            unsafe {
+                dlclose(self.library);
+            }
+        }
+    }
+
+    /// The default ALSA playback device.
+    pub struct AlsaSink {
+        alsa: Alsa,
+        pcm: *snd_pcm_t,
+        channels: uint,
+    }
+
+    impl AlsaSink {
+        #[fixed_stack_segment]
+        pub fn open(sample_rate: uint, channels: uint) -> Option<AlsaSink> {
+            let alsa = match Alsa::load() {
+                Some(alsa) => alsa,
+                None => return None,
+            };
+            unsafe {
+                let mut pcm = ptr::null();
+                let result = do "default".with_c_str |name| {
+                    (alsa.snd_pcm_open)(&mut pcm, name, SND_PCM_STREAM_PLAYBACK, 0)
+                };
+                if result < 0 {
+                    debug!("no audio output: failed to open the ALSA device: %d", result as int);
+                    return None
+                }
+                let result = (alsa.snd_pcm_set_params)(pcm,
+                                                       SND_PCM_FORMAT_S16_LE,
+                                                       SND_PCM_ACCESS_RW_INTERLEAVED,
+                                                       channels as c_uint,
+                                                       sample_rate as c_uint,
+                                                       1,
+                                                       LATENCY);
+                if result < 0 {
+                    debug!("no audio output: failed to configure the ALSA device: %d",
+                           result as int);
+                    (alsa.snd_pcm_close)(pcm);
+                    return None
+                }
+                Some(AlsaSink {
+                    alsa: alsa,
+                    pcm: pcm,
+                    channels: channels,
+                })
+            }
+        }
+    }
+
+    impl AudioSink for AlsaSink {
+        #[fixed_stack_segment]
+        fn write(&mut self, samples: &[i16]) {
+            let mut written = 0;
+            let frames = samples.len() / self.channels;
+            while written < frames {
+                unsafe {
+                    let buffer = samples.slice_from(written * self.channels);
+                    let result = (self.alsa.snd_pcm_writei)(self.pcm,
+                                                            vec::raw::to_ptr(buffer) as *c_void,
+                                                            (frames - written) as c_ulong);
+                    if result >= 0 {
+                        written += result as uint
+                    } else if (self.alsa.snd_pcm_recover)(self.pcm, result as c_int, 1) < 0 {
+                        // The device is gone, so drop the rest of the samples.
+                        debug!("failed to write to the ALSA device: %d", result as int);
+                        return
+                    }
+                }
+            }
+        }
+    }
+
+    impl Drop for AlsaSink {
+        #[fixed_stack_segment]
+        fn drop(&self) {
+            unsafe {
+                (self.alsa.snd_pcm_drain)(self.pcm);
+                (self.alsa.snd_pcm_close)(self.pcm);
+            }
+        }
+    }
+}
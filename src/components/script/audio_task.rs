@@ -0,0 +1,487 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The audio task renders the audio graph of an `AudioContext` in real time and mixes it to the
+//! platform's audio output.
+//!
+//! Script builds the graph out of DOM nodes, which send their changes to this task, where each
+//! node has a counterpart that renders it. The task renders the graph in quanta of
+//! `RENDER_QUANTUM` frames, pulling each quantum from the destination node, which pulls it from
+//! the nodes connected to it, and so on. Writing a quantum to the output blocks until the output
+//! has room for it, which keeps rendering in step with playback.
+
+use audio_sink;
+
+use extra::arc::{Arc, RWArc};
+use std::cell::Cell;
+use std::comm::Port;
+use std::f64;
+use std::hashmap::HashMap;
+use std::task::{SingleThreaded, task};
+
+/// The sample rate that audio is rendered at, in frames per second.
+pub static SAMPLE_RATE: f32 = 44100.0;
+
+/// The number of channels that audio is rendered in. Everything is mixed to stereo.
+pub static CHANNELS: uint = 2;
+
+/// The number of frames rendered at a time.
+pub static RENDER_QUANTUM: uint = 128;
+
+/// Identifies a node of an audio graph. The destination node of every graph is
+/// `DESTINATION_NODE`.
+pub type AudioNodeId = uint;
+
+pub static DESTINATION_NODE: AudioNodeId = 0;
+
+/// The shapes of wave that an oscillator can generate.
+#[deriving(Clone, Eq)]
+pub enum Waveform {
+    SineWave,
+    SquareWave,
+    SawtoothWave,
+    TriangleWave,
+}
+
+pub enum AudioNodeKind {
+    OscillatorNodeKind,
+    GainNodeKind,
+    BufferSourceNodeKind,
+}
+
+/// The parameters of nodes that script can change.
+pub enum AudioParamKind {
+    GainParam,
+    FrequencyParam,
+    DetuneParam,
+    PlaybackRateParam,
+}
+
+/// The samples of an audio buffer, one vector of samples per channel.
+pub struct AudioBufferData {
+    sample_rate: f32,
+    channels: ~[~[f32]],
+}
+
+/// Messages from script to the audio task.
+pub enum AudioMsg {
+    /// Adds a node to the graph.
+    CreateNodeMsg(AudioNodeId, AudioNodeKind),
+    /// Connects the output of the first node to the input of the second.
+    ConnectMsg(AudioNodeId, AudioNodeId),
+    /// Disconnects the output of a node from everything it is connected to.
+    DisconnectMsg(AudioNodeId),
+    SetParamMsg(AudioNodeId, AudioParamKind, f32),
+    SetWaveformMsg(AudioNodeId, Waveform),
+    /// Sets the samples that a buffer source plays.
+    SetBufferMsg(AudioNodeId, Arc<AudioBufferData>),
+    SetLoopMsg(AudioNodeId, bool),
+    /// Starts a source node at the given time in seconds.
+    StartMsg(AudioNodeId, f64),
+    /// Stops a source node at the given time in seconds.
+    StopMsg(AudioNodeId, f64),
+    /// Stops rendering and closes the audio output.
+    CloseMsg,
+}
+
+/// A quantum of stereo audio.
+struct AudioBlock {
+    channels: [[f32, ..RENDER_QUANTUM], ..CHANNELS],
+}
+
+impl AudioBlock {
+    fn silence() -> AudioBlock {
+        AudioBlock {
+            channels: [[0.0, ..RENDER_QUANTUM], ..CHANNELS],
+        }
+    }
+
+    fn mix(&mut self, other: &AudioBlock) {
+        for channel in range(0, CHANNELS) {
+            for frame in range(0, RENDER_QUANTUM) {
+                self.channels[channel][frame] += other.channels[channel][frame]
+            }
+        }
+    }
+}
+
+/// The counterpart of a DOM audio node in the audio task.
+struct RenderNode {
+    kind: AudioNodeKind,
+    /// The nodes connected to the input of this one.
+    inputs: ~[AudioNodeId],
+    gain: f32,
+    frequency: f32,
+    /// The detune of an oscillator, in cents.
+    detune: f32,
+    playback_rate: f32,
+    waveform: Waveform,
+    buffer: Option<Arc<AudioBufferData>>,
+    looping: bool,
+    start_time: Option<f64>,
+    stop_time: Option<f64>,
+    /// How far an oscillator is through its cycle, from 0 up to 1.
+    phase: f64,
+    /// The frame of its buffer that a buffer source plays next, which is fractional when the
+    /// playback rate or sample rate differ.
+    position: f64,
+}
+
+impl RenderNode {
+    fn new(kind: AudioNodeKind) -> RenderNode {
+        RenderNode {
+            kind: kind,
+            inputs: ~[],
+            gain: 1.0,
+            frequency: 440.0,
+            detune: 0.0,
+            playback_rate: 1.0,
+            waveform: SineWave,
+            buffer: None,
+            looping: false,
+            start_time: None,
+            stop_time: None,
+            phase: 0.0,
+            position: 0.0,
+        }
+    }
+
+    /// Returns whether a source node is playing at `time`.
+    fn is_playing_at(&self, time: f64) -> bool {
+        match self.start_time {
+            Some(start) => start <= time && self.stop_time.map_default(true, |&stop| time < stop),
+            None => false,
+        }
+    }
+
+    /// Renders a quantum of an oscillator or buffer source, starting at the given time.
+    fn render_source(&mut self, start_time: f64, block: &mut AudioBlock) {
+        let frame_duration = 1.0 / (SAMPLE_RATE as f64);
+        for frame in range(0, RENDER_QUANTUM) {
+            if !self.is_playing_at(start_time + (frame as f64) * frame_duration) {
+                loop
+            }
+            match self.kind {
+                OscillatorNodeKind => {
+                    let sample = oscillator_sample(self.waveform, self.phase);
+                    block.channels[0][frame] = sample;
+                    block.channels[1][frame] = sample;
+                    let frequency = self.frequency * 2f32.pow(&(self.detune / 1200.0));
+                    self.phase = (self.phase + (frequency as f64) * frame_duration).fract();
+                }
+                BufferSourceNodeKind => self.render_buffer_frame(block, frame),
+                GainNodeKind => {}
+            }
+        }
+    }
+
+    /// Renders a frame of a buffer source, interpolating between the frames of its buffer.
+    fn render_buffer_frame(&mut self, block: &mut AudioBlock, frame: uint) {
+        let buffer = match self.buffer {
+            Some(ref buffer) => buffer.get(),
+            None => return,
+        };
+        let length = if buffer.channels.is_empty() { 0 } else { buffer.channels[0].len() };
+        if length == 0 {
+            return
+        }
+        // A negative playback rate plays the buffer backwards, and a loop wraps around either
+        // end of it.
+        let end = length as f64;
+        if self.position >= end || self.position < 0.0 {
+            if !self.looping {
+                return
+            }
+            self.position = (self.position % end + end) % end;
+        }
+
+        let index = self.position.floor() as uint;
+        let fraction = (self.position - self.position.floor()) as f32;
+        let next = if index + 1 < length { index + 1 } else if self.looping { 0 } else { index };
+        // A mono buffer plays in both channels.
+        //
+        // FIXME: Buffers with more than two channels should be down-mixed, rather than have all
+        // but their first two channels dropped.
+        for channel in range(0, CHANNELS) {
+            let samples = &buffer.channels[channel.min(&(buffer.channels.len() - 1))];
+            block.channels[channel][frame] =
+                samples[index] + (samples[next] - samples[index]) * fraction;
+        }
+        let rate = self.playback_rate * buffer.sample_rate / SAMPLE_RATE;
+        self.position += rate as f64;
+    }
+}
+
+/// Returns the sample of a wave at the given phase, from 0 up to 1. Every wave starts at 0.
+fn oscillator_sample(waveform: Waveform, phase: f64) -> f32 {
+    let sample = match waveform {
+        SineWave => (phase * 2.0 * f64::consts::pi).sin(),
+        SquareWave => if phase < 0.5 { 1.0 } else { -1.0 },
+        SawtoothWave => 2.0 * (phase + 0.5).fract() - 1.0,
+        TriangleWave => {
+            if phase < 0.25 {
+                4.0 * phase
+            } else if phase < 0.75 {
+                2.0 - 4.0 * phase
+            } else {
+                4.0 * phase - 4.0
+            }
+        }
+    };
+    sample as f32
+}
+
+/// The graph of render nodes, which the audio task renders.
+struct AudioGraph {
+    nodes: HashMap<AudioNodeId, RenderNode>,
+    /// The quanta that nodes have rendered for the quantum that is being rendered, so that a node
+    /// whose output is connected to several others is rendered only once per quantum.
+    rendered: HashMap<AudioNodeId, AudioBlock>,
+    /// The number of frames rendered so far.
+    frames: u64,
+}
+
+impl AudioGraph {
+    fn new() -> AudioGraph {
+        // The destination node mixes its inputs like a gain node with unit gain.
+        let mut nodes = HashMap::new();
+        nodes.insert(DESTINATION_NODE, RenderNode::new(GainNodeKind));
+        AudioGraph {
+            nodes: nodes,
+            rendered: HashMap::new(),
+            frames: 0,
+        }
+    }
+
+    fn handle_msg(&mut self, msg: AudioMsg) {
+        match msg {
+            CreateNodeMsg(id, kind) => {
+                self.nodes.insert(id, RenderNode::new(kind));
+            }
+            ConnectMsg(source, destination) => {
+                do self.with_node(destination) |node| {
+                    if !node.inputs.contains(&source) {
+                        node.inputs.push(source)
+                    }
+                }
+            }
+            DisconnectMsg(source) => {
+                for (_, node) in self.nodes.mut_iter() {
+                    node.inputs.retain(|&input| input != source)
+                }
+            }
+            SetParamMsg(id, param, value) => {
+                do self.with_node(id) |node| {
+                    match param {
+                        GainParam => node.gain = value,
+                        FrequencyParam => node.frequency = value,
+                        DetuneParam => node.detune = value,
+                        PlaybackRateParam => node.playback_rate = value,
+                    }
+                }
+            }
+            SetWaveformMsg(id, waveform) => self.with_node(id, |node| node.waveform = waveform),
+            SetBufferMsg(id, buffer) => {
+                self.with_node(id, |node| node.buffer = Some(buffer.clone()))
+            }
+            SetLoopMsg(id, looping) => self.with_node(id, |node| node.looping = looping),
+            StartMsg(id, time) => self.with_node(id, |node| node.start_time = Some(time)),
+            StopMsg(id, time) => self.with_node(id, |node| node.stop_time = Some(time)),
+            CloseMsg => {}
+        }
+    }
+
+    fn with_node(&mut self, id: AudioNodeId, f: &fn(&mut RenderNode)) {
+        match self.nodes.find_mut(&id) {
+            Some(node) => f(node),
+            None => {}
+        }
+    }
+
+    /// Renders the next quantum of a node, or returns the quantum it has already rendered. A node
+    /// that is already being rendered further up the graph, because it is part of a cycle, is
+    /// silent.
+    fn render_node(&mut self, id: AudioNodeId) -> AudioBlock {
+        match self.rendered.find(&id) {
+            Some(block) => return *block,
+            None => {}
+        }
+        let mut block = AudioBlock::silence();
+        let mut node = match self.nodes.pop(&id) {
+            Some(node) => node,
+            None => return block,
+        };
+        match node.kind {
+            GainNodeKind => {
+                for &input in node.inputs.iter() {
+                    block.mix(&self.render_node(input))
+                }
+                for channel in range(0, CHANNELS) {
+                    for frame in range(0, RENDER_QUANTUM) {
+                        block.channels[channel][frame] *= node.gain
+                    }
+                }
+            }
+            OscillatorNodeKind | BufferSourceNodeKind => {
+                node.render_source((self.frames as f64) / (SAMPLE_RATE as f64), &mut block)
+            }
+        }
+        self.nodes.insert(id, node);
+        self.rendered.insert(id, block);
+        block
+    }
+
+    /// Renders the next quantum of the destination node as interleaved 16-bit samples.
+    fn render_quantum(&mut self, samples: &mut [i16]) {
+        self.rendered.clear();
+        let block = self.render_node(DESTINATION_NODE);
+        for frame in range(0, RENDER_QUANTUM) {
+            for channel in range(0, CHANNELS) {
+                let sample = block.channels[channel][frame].max(&-1.0).min(&1.0);
+                samples[frame * CHANNELS + channel] = (sample * 32767.0) as i16;
+            }
+        }
+        self.frames += RENDER_QUANTUM as u64;
+    }
+}
+
+/// Spawns the audio task of an audio context, which renders the graph that script builds with
+/// messages on `port`. The task counts the frames it has rendered in `frames`, from which the
+/// context tells the time.
+pub fn spawn_audio_task(port: Port<AudioMsg>, frames: RWArc<u64>) {
+    // The task blocks on the audio output, so it gets a thread of its own.
+    //
+    // FIXME: The thread should have a real-time priority.
+    let port = Cell::new(port);
+    let frames = Cell::new(frames);
+    let mut the_task = task();
+    the_task.sched_mode(SingleThreaded);
+    do the_task.spawn {
+        let port = port.take();
+        let frames = frames.take();
+        let mut sink = audio_sink::open(SAMPLE_RATE as uint, CHANNELS);
+        let mut graph = AudioGraph::new();
+        let mut samples = [0i16, ..RENDER_QUANTUM * CHANNELS];
+        loop {
+            // Apply the changes that script has made since the last quantum.
+            loop {
+                match port.try_recv() {
+                    Some(CloseMsg) => return,
+                    Some(msg) => graph.handle_msg(msg),
+                    None => break,
+                }
+            }
+            graph.render_quantum(samples);
+            sink.write(samples);
+            do frames.write |frames| {
+                *frames = graph.frames
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn buffer_source(samples: ~[f32], playback_rate: f32, looping: bool) -> RenderNode {
+    let mut node = RenderNode::new(BufferSourceNodeKind);
+    node.buffer = Some(Arc::new(AudioBufferData {
+        sample_rate: SAMPLE_RATE,
+        channels: ~[samples],
+    }));
+    node.playback_rate = playback_rate;
+    node.looping = looping;
+    node.start_time = Some(0.0);
+    node
+}
+
+#[cfg(test)]
+fn left_channel(block: &AudioBlock, frames: uint) -> ~[f32] {
+    block.channels[0].slice_to(frames).to_owned()
+}
+
+#[test]
+fn test_buffer_source_plays_once() {
+    let mut node = buffer_source(~[0.5, 1.0, -1.0], 1.0, false);
+    let mut block = AudioBlock::silence();
+    node.render_source(0.0, &mut block);
+    assert!(left_channel(&block, 5) == ~[0.5, 1.0, -1.0, 0.0, 0.0]);
+    // A mono buffer plays in both channels.
+    assert!(block.channels[1][1] == 1.0);
+}
+
+#[test]
+fn test_buffer_source_interpolates() {
+    let mut node = buffer_source(~[0.0, 1.0], 0.5, true);
+    let mut block = AudioBlock::silence();
+    node.render_source(0.0, &mut block);
+    // Between the last frame and the first, a loop interpolates towards the first.
+    assert!(left_channel(&block, 5) == ~[0.0, 0.5, 1.0, 0.5, 0.0]);
+}
+
+#[test]
+fn test_negative_playback_rate() {
+    let mut node = buffer_source(~[0.25, 0.5, 0.75, 1.0], -1.0, true);
+    let mut block = AudioBlock::silence();
+    node.render_source(0.0, &mut block);
+    assert!(left_channel(&block, 6) == ~[0.25, 1.0, 0.75, 0.5, 0.25, 1.0]);
+
+    // Without a loop, the buffer stops once it has played back past its start.
+    let mut node = buffer_source(~[0.25, 0.5, 0.75, 1.0], -1.0, false);
+    node.position = 2.0;
+    let mut block = AudioBlock::silence();
+    node.render_source(0.0, &mut block);
+    assert!(left_channel(&block, 5) == ~[0.75, 0.5, 0.25, 0.0, 0.0]);
+}
+
+#[test]
+fn test_sources_play_between_start_and_stop() {
+    let frame_duration = 1.0 / (SAMPLE_RATE as f64);
+    // A square wave of no frequency stays at its first sample.
+    let mut node = RenderNode::new(OscillatorNodeKind);
+    node.waveform = SquareWave;
+    node.frequency = 0.0;
+    node.start_time = Some(2.0 * frame_duration);
+    node.stop_time = Some(6.5 * frame_duration);
+    let mut block = AudioBlock::silence();
+    node.render_source(0.0, &mut block);
+    assert!(left_channel(&block, 8) == ~[0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0]);
+}
+
+#[test]
+fn test_shared_nodes_render_once_per_quantum() {
+    use std::vec;
+
+    // A source that is connected to two gain nodes, which are both connected to the destination.
+    let (source, left, right) = (1, 2, 3);
+    let mut graph = AudioGraph::new();
+    graph.handle_msg(CreateNodeMsg(source, BufferSourceNodeKind));
+    graph.handle_msg(CreateNodeMsg(left, GainNodeKind));
+    graph.handle_msg(CreateNodeMsg(right, GainNodeKind));
+    let samples = vec::from_elem(RENDER_QUANTUM * 2, 0.25f32);
+    let buffer = AudioBufferData { sample_rate: SAMPLE_RATE, channels: ~[samples] };
+    graph.handle_msg(SetBufferMsg(source, Arc::new(buffer)));
+    graph.handle_msg(StartMsg(source, 0.0));
+    for &gain in [left, right].iter() {
+        graph.handle_msg(ConnectMsg(source, gain));
+        graph.handle_msg(ConnectMsg(gain, DESTINATION_NODE));
+    }
+
+    let mut output = [0i16, ..RENDER_QUANTUM * CHANNELS];
+    graph.render_quantum(output);
+    assert!(output[0] == (0.5 * 32767.0) as i16);
+    assert!(graph.nodes.get(&source).position == RENDER_QUANTUM as f64);
+    graph.render_quantum(output);
+    assert!(graph.nodes.get(&source).position == (RENDER_QUANTUM * 2) as f64);
+}
+
+#[test]
+fn test_cycles_are_silent() {
+    let gain = 1;
+    let mut graph = AudioGraph::new();
+    graph.handle_msg(CreateNodeMsg(gain, GainNodeKind));
+    graph.handle_msg(ConnectMsg(gain, gain));
+    graph.handle_msg(ConnectMsg(gain, DESTINATION_NODE));
+    let mut output = [1i16, ..RENDER_QUANTUM * CHANNELS];
+    graph.render_quantum(output);
+    assert!(output.iter().all(|&sample| sample == 0));
+}
@@ -0,0 +1,164 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use audio_task::AudioBufferData;
+use dom::bindings::codegen::AudioBufferBinding;
+use dom::bindings::conversions::JSValConvertible;
+use dom::bindings::utils::{ErrorResult, FailureUnknown};
+use dom::bindings::utils::{WrapperCache, BindingObject, CacheableWrapper};
+use script_task::page_from_context;
+
+use js::JSVAL_VOID;
+use js::glue::{RUST_JSVAL_IS_NULL, RUST_JSVAL_IS_OBJECT, RUST_JSVAL_TO_OBJECT};
+use js::jsapi::{JSContext, JSObject, JSVal};
+use js::jsapi::{JS_GetArrayLength, JS_GetElement, JS_SetElement, JS_ValueToNumber};
+use std::cast;
+use std::vec;
+
+/// Samples of audio in memory, which buffer sources play. Script fills them in with
+/// `copyToChannel()`.
+///
+/// FIXME: There's no `decodeAudioData()` yet.
+pub struct AudioBuffer {
+    wrapper: WrapperCache,
+    sample_rate: f32,
+    /// The samples of each channel.
+    channels: ~[~[f32]],
+}
+
+impl AudioBuffer {
+    pub fn new(number_of_channels: u32, length: u32, sample_rate: f32) -> @mut AudioBuffer {
+        @mut AudioBuffer {
+            wrapper: WrapperCache::new(),
+            sample_rate: sample_rate,
+            channels: vec::from_fn(number_of_channels as uint, |_| {
+                vec::from_elem(length as uint, 0f32)
+            }),
+        }
+    }
+
+    /// Returns a copy of the samples, for a buffer source to play.
+    pub fn data(&self) -> AudioBufferData {
+        AudioBufferData {
+            sample_rate: self.sample_rate,
+            channels: self.channels.clone(),
+        }
+    }
+}
+
+impl AudioBuffer {
+    pub fn SampleRate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    pub fn Length(&self) -> u32 {
+        self.channels[0].len() as u32
+    }
+
+    pub fn Duration(&self) -> f64 {
+        (self.Length() as f64) / (self.sample_rate as f64)
+    }
+
+    pub fn NumberOfChannels(&self) -> u32 {
+        self.channels.len() as u32
+    }
+
+    /// Copies the samples of a channel into the elements of `destination`, as many as fit.
+    #[fixed_stack_segment]
+    pub fn CopyFromChannel(&self,
+                           cx: *JSContext,
+                           destination: JSVal,
+                           channel: u32,
+                           rv: &mut ErrorResult) {
+        let (array, channel) = match self.array_and_channel(destination, channel) {
+            Some(array_and_channel) => array_and_channel,
+            None => {
+                *rv = Err(FailureUnknown);
+                return
+            }
+        };
+        unsafe {
+            let length = 0u32;
+            if JS_GetArrayLength(cx, array, &length) == 0 {
+                *rv = Err(FailureUnknown);
+                return
+            }
+            let samples = &self.channels[channel];
+            for i in range(0, (length as uint).min(&samples.len())) {
+                let value = samples[i].to_jsval();
+                if JS_SetElement(cx, array, i as u32, &value) == 0 {
+                    *rv = Err(FailureUnknown);
+                    return
+                }
+            }
+        }
+    }
+
+    /// Copies the elements of `source`, as numbers, into a channel, as many as fit.
+    #[fixed_stack_segment]
+    pub fn CopyToChannel(&mut self,
+                         cx: *JSContext,
+                         source: JSVal,
+                         channel: u32,
+                         rv: &mut ErrorResult) {
+        let (array, channel) = match self.array_and_channel(source, channel) {
+            Some(array_and_channel) => array_and_channel,
+            None => {
+                *rv = Err(FailureUnknown);
+                return
+            }
+        };
+        unsafe {
+            let length = 0u32;
+            if JS_GetArrayLength(cx, array, &length) == 0 {
+                *rv = Err(FailureUnknown);
+                return
+            }
+            let samples = &mut self.channels[channel];
+            for i in range(0, (length as uint).min(&samples.len())) {
+                let value = JSVAL_VOID;
+                let number = 0f64;
+                if JS_GetElement(cx, array, i as u32, &value) == 0 ||
+                        JS_ValueToNumber(cx, value, &number) == 0 {
+                    *rv = Err(FailureUnknown);
+                    return
+                }
+                samples[i] = number as f32;
+            }
+        }
+    }
+
+    /// Returns the object that samples are copied to or from, and the index of the channel, if
+    /// `value` is an object and there is such a channel.
+    #[fixed_stack_segment]
+    fn array_and_channel(&self, value: JSVal, channel: u32) -> Option<(*JSObject, uint)> {
+        unsafe {
+            if !RUST_JSVAL_IS_OBJECT(value) || RUST_JSVAL_IS_NULL(value) ||
+                    (channel as uint) >= self.channels.len() {
+                return None
+            }
+            Some((RUST_JSVAL_TO_OBJECT(value), channel as uint))
+        }
+    }
+}
+
+impl CacheableWrapper for AudioBuffer {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        AudioBufferBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for AudioBuffer {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
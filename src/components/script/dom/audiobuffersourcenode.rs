@@ -0,0 +1,101 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use audio_task::{BufferSourceNodeKind, PlaybackRateParam, SetBufferMsg, SetLoopMsg};
+use dom::audiobuffer::AudioBuffer;
+use dom::audiocontext::AudioGraphHandle;
+use dom::audioparam::{AudioParam, MOST_POSITIVE_SINGLE_FLOAT};
+use dom::audioscheduledsourcenode::AudioScheduledSourceNode;
+use dom::bindings::codegen::AudioBufferSourceNodeBinding;
+use dom::bindings::utils::{ErrorResult, FailureUnknown};
+use dom::bindings::utils::{WrapperCache, BindingObject, CacheableWrapper};
+use script_task::page_from_context;
+
+use extra::arc::Arc;
+use js::jsapi::{JSContext, JSObject};
+use std::cast;
+
+/// A node that plays an audio buffer.
+pub struct AudioBufferSourceNode {
+    parent: AudioScheduledSourceNode,
+    buffer: Option<@mut AudioBuffer>,
+    playback_rate: @mut AudioParam,
+    looping: bool,
+}
+
+impl AudioBufferSourceNode {
+    pub fn new(graph: @mut AudioGraphHandle) -> @mut AudioBufferSourceNode {
+        let parent = AudioScheduledSourceNode::new(graph, BufferSourceNodeKind);
+        let id = parent.parent.id;
+        @mut AudioBufferSourceNode {
+            parent: parent,
+            buffer: None,
+            playback_rate: AudioParam::new(graph,
+                                           id,
+                                           PlaybackRateParam,
+                                           1.0,
+                                           -MOST_POSITIVE_SINGLE_FLOAT,
+                                           MOST_POSITIVE_SINGLE_FLOAT),
+            looping: false,
+        }
+    }
+}
+
+impl AudioBufferSourceNode {
+    pub fn GetBuffer(&self) -> Option<@mut AudioBuffer> {
+        self.buffer
+    }
+
+    /// Sets the buffer to play. The node plays the samples the buffer has now; changes to the
+    /// buffer afterwards aren't heard.
+    ///
+    /// FIXME: A buffer that is set can't be replaced, which should be an `InvalidStateError`.
+    pub fn SetBuffer(&mut self, buffer: Option<@mut AudioBuffer>, rv: &mut ErrorResult) {
+        let buffer = match buffer {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        if self.buffer.is_some() {
+            *rv = Err(FailureUnknown);
+            return
+        }
+        self.buffer = Some(buffer);
+        let node = &self.parent.parent;
+        node.graph.send(SetBufferMsg(node.id, Arc::new(buffer.data())))
+    }
+
+    pub fn PlaybackRate(&self) -> @mut AudioParam {
+        self.playback_rate
+    }
+
+    pub fn Loop(&self) -> bool {
+        self.looping
+    }
+
+    pub fn SetLoop(&mut self, looping: bool) {
+        self.looping = looping;
+        let node = &self.parent.parent;
+        node.graph.send(SetLoopMsg(node.id, looping))
+    }
+}
+
+impl CacheableWrapper for AudioBufferSourceNode {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.parent.parent.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        AudioBufferSourceNodeBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for AudioBufferSourceNode {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
@@ -0,0 +1,169 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use audio_task::{AudioMsg, AudioNodeId, AudioNodeKind, CloseMsg, CreateNodeMsg};
+use audio_task::{DESTINATION_NODE, SAMPLE_RATE, spawn_audio_task};
+use dom::audiobuffer::AudioBuffer;
+use dom::audiobuffersourcenode::AudioBufferSourceNode;
+use dom::audiodestinationnode::AudioDestinationNode;
+use dom::bindings::codegen::AudioContextBinding;
+use dom::bindings::utils::{ErrorResult, FailureUnknown, WrapperCache};
+use dom::bindings::utils::{BindingObject, CacheableWrapper};
+use dom::gainnode::GainNode;
+use dom::oscillatornode::OscillatorNode;
+use dom::window::Window;
+
+use extra::arc::RWArc;
+use js::jsapi::{JSContext, JSObject};
+use std::cast;
+use std::comm;
+use std::comm::Chan;
+
+/// The most channels an audio buffer can have.
+static MAX_CHANNELS: u32 = 32;
+
+/// The script side of the audio graph of a context, which the context shares with its nodes.
+pub struct AudioGraphHandle {
+    /// The channel that changes to the graph are sent to the audio task on.
+    priv chan: Chan<AudioMsg>,
+    priv next_node_id: AudioNodeId,
+    /// The number of frames the audio task has rendered.
+    priv frames: RWArc<u64>,
+    priv closed: bool,
+}
+
+impl AudioGraphHandle {
+    /// Sends a change to the graph to the audio task, unless the context has been closed.
+    pub fn send(&self, msg: AudioMsg) {
+        if !self.closed {
+            self.chan.send(msg)
+        }
+    }
+
+    /// Adds a node to the graph and returns its id.
+    pub fn create_node(&mut self, kind: AudioNodeKind) -> AudioNodeId {
+        let id = self.next_node_id;
+        self.next_node_id += 1;
+        self.send(CreateNodeMsg(id, kind));
+        id
+    }
+
+    /// Returns the time of the audio that has been rendered, in seconds.
+    pub fn current_time(&self) -> f64 {
+        self.frames.read(|frames| *frames as f64) / (SAMPLE_RATE as f64)
+    }
+
+    fn close(&mut self) {
+        self.send(CloseMsg);
+        self.closed = true;
+    }
+}
+
+/// An audio graph, which is rendered on a task of its own and played through the platform's audio
+/// output.
+pub struct AudioContext {
+    owner: @mut Window,
+    wrapper: WrapperCache,
+    graph: @mut AudioGraphHandle,
+    destination: @mut AudioDestinationNode,
+}
+
+impl AudioContext {
+    pub fn new(owner: @mut Window) -> @mut AudioContext {
+        let (port, chan) = comm::stream();
+        let frames = RWArc::new(0u64);
+        spawn_audio_task(port, frames.clone());
+
+        let graph = @mut AudioGraphHandle {
+            chan: chan,
+            next_node_id: DESTINATION_NODE + 1,
+            frames: frames,
+            closed: false,
+        };
+        let context = @mut AudioContext {
+            owner: owner,
+            wrapper: WrapperCache::new(),
+            graph: graph,
+            destination: AudioDestinationNode::new(graph),
+        };
+
+        let cx = unsafe {(*owner.page).js_info.get_ref().js_compartment.cx.ptr};
+        let scope = owner.get_wrappercache().get_wrapper();
+        context.wrap_object_shared(cx, scope);
+        context
+    }
+
+    pub fn Constructor(owner: @mut Window, _rv: &mut ErrorResult) -> @mut AudioContext {
+        AudioContext::new(owner)
+    }
+
+    pub fn Destination(&self) -> @mut AudioDestinationNode {
+        self.destination
+    }
+
+    pub fn SampleRate(&self) -> f32 {
+        SAMPLE_RATE
+    }
+
+    pub fn CurrentTime(&self) -> f64 {
+        self.graph.current_time()
+    }
+
+    // FIXME: These errors should be a `NotSupportedError`.
+    pub fn CreateBuffer(&self,
+                        number_of_channels: u32,
+                        length: u32,
+                        sample_rate: f32,
+                        rv: &mut ErrorResult)
+                        -> @mut AudioBuffer {
+        if number_of_channels == 0 || number_of_channels > MAX_CHANNELS || length == 0 ||
+                sample_rate < 3000.0 || sample_rate > 384000.0 {
+            // The bindings ignore what is returned along with an error.
+            *rv = Err(FailureUnknown);
+            return AudioBuffer::new(1, 1, SAMPLE_RATE)
+        }
+        AudioBuffer::new(number_of_channels, length, sample_rate)
+    }
+
+    pub fn CreateBufferSource(&self) -> @mut AudioBufferSourceNode {
+        AudioBufferSourceNode::new(self.graph)
+    }
+
+    pub fn CreateGain(&self) -> @mut GainNode {
+        GainNode::new(self.graph)
+    }
+
+    pub fn CreateOscillator(&self) -> @mut OscillatorNode {
+        OscillatorNode::new(self.graph)
+    }
+
+    // FIXME: This should return a promise, once the bindings support them.
+    pub fn Close(&self) {
+        self.graph.close()
+    }
+}
+
+#[unsafe_destructor]
+impl Drop for AudioContext {
+    fn drop(&self) {
+        self.graph.close()
+    }
+}
+
+impl CacheableWrapper for AudioContext {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        AudioContextBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for AudioContext {
+    fn GetParentObject(&self, _cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        Some(self.owner as @mut CacheableWrapper)
+    }
+}
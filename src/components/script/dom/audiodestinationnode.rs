@@ -0,0 +1,52 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use audio_task::{CHANNELS, DESTINATION_NODE};
+use dom::audiocontext::AudioGraphHandle;
+use dom::audionode::AudioNode;
+use dom::bindings::codegen::AudioDestinationNodeBinding;
+use dom::bindings::utils::{WrapperCache, BindingObject, CacheableWrapper};
+use script_task::page_from_context;
+
+use js::jsapi::{JSContext, JSObject};
+use std::cast;
+
+/// The node of an audio graph that plays what is connected to it through the audio output.
+pub struct AudioDestinationNode {
+    parent: AudioNode,
+}
+
+impl AudioDestinationNode {
+    pub fn new(graph: @mut AudioGraphHandle) -> @mut AudioDestinationNode {
+        @mut AudioDestinationNode {
+            parent: AudioNode::new(graph, DESTINATION_NODE, 1, 0),
+        }
+    }
+}
+
+impl AudioDestinationNode {
+    pub fn MaxChannelCount(&self) -> u32 {
+        CHANNELS as u32
+    }
+}
+
+impl CacheableWrapper for AudioDestinationNode {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.parent.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        AudioDestinationNodeBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for AudioDestinationNode {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
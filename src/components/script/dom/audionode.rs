@@ -0,0 +1,59 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use audio_task::{AudioNodeId, ConnectMsg, DisconnectMsg};
+use dom::audiocontext::AudioGraphHandle;
+use dom::bindings::utils::{ErrorResult, FailureUnknown, WrapperCache};
+
+use std::managed;
+
+/// A node of an audio graph. The audio task renders its counterpart, which has the same id.
+pub struct AudioNode {
+    wrapper: WrapperCache,
+    graph: @mut AudioGraphHandle,
+    id: AudioNodeId,
+    number_of_inputs: u32,
+    number_of_outputs: u32,
+}
+
+impl AudioNode {
+    pub fn new(graph: @mut AudioGraphHandle,
+               id: AudioNodeId,
+               number_of_inputs: u32,
+               number_of_outputs: u32)
+               -> AudioNode {
+        AudioNode {
+            wrapper: WrapperCache::new(),
+            graph: graph,
+            id: id,
+            number_of_inputs: number_of_inputs,
+            number_of_outputs: number_of_outputs,
+        }
+    }
+}
+
+impl AudioNode {
+    // FIXME: This error should be an `IndexSizeError` for a node without inputs, and an
+    // `InvalidAccessError` for a node of another context.
+    pub fn Connect(&self, destination: @mut AudioNode, rv: &mut ErrorResult) {
+        if !managed::mut_ptr_eq(self.graph, destination.graph) || self.number_of_outputs == 0 ||
+                destination.number_of_inputs == 0 {
+            *rv = Err(FailureUnknown);
+            return
+        }
+        self.graph.send(ConnectMsg(self.id, destination.id))
+    }
+
+    pub fn Disconnect(&self) {
+        self.graph.send(DisconnectMsg(self.id))
+    }
+
+    pub fn NumberOfInputs(&self) -> u32 {
+        self.number_of_inputs
+    }
+
+    pub fn NumberOfOutputs(&self) -> u32 {
+        self.number_of_outputs
+    }
+}
@@ -0,0 +1,95 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use audio_task::{AudioNodeId, AudioParamKind, SetParamMsg};
+use dom::audiocontext::AudioGraphHandle;
+use dom::bindings::codegen::AudioParamBinding;
+use dom::bindings::utils::{WrapperCache, BindingObject, CacheableWrapper};
+use script_task::page_from_context;
+
+use js::jsapi::{JSContext, JSObject};
+use std::cast;
+
+/// The largest finite single-precision float, which bounds the parameters that have no nominal
+/// range.
+pub static MOST_POSITIVE_SINGLE_FLOAT: f32 = 3.40282347e+38;
+
+/// A parameter of an audio node, such as the gain of a gain node.
+///
+/// TODO: Automation, with `setValueAtTime()` and the ramps.
+pub struct AudioParam {
+    wrapper: WrapperCache,
+    graph: @mut AudioGraphHandle,
+    node: AudioNodeId,
+    kind: AudioParamKind,
+    value: f32,
+    default_value: f32,
+    min_value: f32,
+    max_value: f32,
+}
+
+impl AudioParam {
+    pub fn new(graph: @mut AudioGraphHandle,
+               node: AudioNodeId,
+               kind: AudioParamKind,
+               default_value: f32,
+               min_value: f32,
+               max_value: f32)
+               -> @mut AudioParam {
+        @mut AudioParam {
+            wrapper: WrapperCache::new(),
+            graph: graph,
+            node: node,
+            kind: kind,
+            value: default_value,
+            default_value: default_value,
+            min_value: min_value,
+            max_value: max_value,
+        }
+    }
+}
+
+impl AudioParam {
+    pub fn Value(&self) -> f32 {
+        self.value
+    }
+
+    /// Sets the value, clamped to the nominal range of the parameter.
+    pub fn SetValue(&mut self, value: f32) {
+        self.value = value.max(&self.min_value).min(&self.max_value);
+        self.graph.send(SetParamMsg(self.node, self.kind, self.value))
+    }
+
+    pub fn DefaultValue(&self) -> f32 {
+        self.default_value
+    }
+
+    pub fn MinValue(&self) -> f32 {
+        self.min_value
+    }
+
+    pub fn MaxValue(&self) -> f32 {
+        self.max_value
+    }
+}
+
+impl CacheableWrapper for AudioParam {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        AudioParamBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for AudioParam {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
@@ -0,0 +1,45 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use audio_task::{AudioNodeKind, StartMsg, StopMsg};
+use dom::audiocontext::AudioGraphHandle;
+use dom::audionode::AudioNode;
+use dom::bindings::utils::{ErrorResult, FailureUnknown};
+
+/// A node that generates audio between the times it is started and stopped.
+pub struct AudioScheduledSourceNode {
+    parent: AudioNode,
+    started: bool,
+}
+
+impl AudioScheduledSourceNode {
+    pub fn new(graph: @mut AudioGraphHandle, kind: AudioNodeKind) -> AudioScheduledSourceNode {
+        let id = graph.create_node(kind);
+        AudioScheduledSourceNode {
+            parent: AudioNode::new(graph, id, 0, 1),
+            started: false,
+        }
+    }
+}
+
+impl AudioScheduledSourceNode {
+    // FIXME: These errors should be an `InvalidStateError` for a node that has already been
+    // started, or a `RangeError` for a negative time.
+    pub fn Start(&mut self, when: f64, rv: &mut ErrorResult) {
+        if self.started || when.is_NaN() || when < 0.0 {
+            *rv = Err(FailureUnknown);
+            return
+        }
+        self.started = true;
+        self.parent.graph.send(StartMsg(self.parent.id, when))
+    }
+
+    pub fn Stop(&mut self, when: f64, rv: &mut ErrorResult) {
+        if !self.started || when.is_NaN() || when < 0.0 {
+            *rv = Err(FailureUnknown);
+            return
+        }
+        self.parent.graph.send(StopMsg(self.parent.id, when))
+    }
+}
@@ -3097,8 +3097,8 @@ class CGGetterCall(CGPerSignatureCall):
     A class to generate a native object getter call for a particular IDL
     getter.
     """
-    def __init__(self, returnType, nativeMethodName, descriptor, attr):
-        CGPerSignatureCall.__init__(self, returnType, [], [],
+    def __init__(self, returnType, argsPre, nativeMethodName, descriptor, attr):
+        CGPerSignatureCall.__init__(self, returnType, argsPre, [],
                                     nativeMethodName, False, descriptor,
                                     attr, getter=True)
 
@@ -3122,8 +3122,8 @@ class CGSetterCall(CGPerSignatureCall):
     A class to generate a native object setter call for a particular IDL
     setter.
     """
-    def __init__(self, argType, nativeMethodName, descriptor, attr):
-        CGPerSignatureCall.__init__(self, None, [],
+    def __init__(self, argType, argsPre, nativeMethodName, descriptor, attr):
+        CGPerSignatureCall.__init__(self, None, argsPre,
                                     [FakeArgument(argType, attr)],
                                     nativeMethodName, False, descriptor, attr,
                                     setter=True)
@@ -3203,6 +3203,16 @@ class CGAbstractStaticMethod(CGAbstractMethod):
         # We only have implementation
         return ""
 
+def abstractThis(descriptor, name):
+    """
+    Returns the code that makes the abstract node of |this|, and the arguments
+    that pass it first, for the members that ask for it in needsAbstract.
+    """
+    if name not in descriptor.needsAbstract:
+        return ('', [])
+    abstractName = re.sub(r'<\w+>', '', descriptor.nativeType)
+    return ('  let abstract_this = %s::from_box(this);\n' % abstractName, ['abstract_this'])
+
 class CGSpecializedMethod(CGAbstractExternMethod):
     """
     A class for generating the C++ code for a specialized method that the JIT
@@ -3219,12 +3229,7 @@ class CGSpecializedMethod(CGAbstractExternMethod):
     def definition_body(self):
         name = self.method.identifier.name
         nativeName = MakeNativeName(self.descriptor.binaryNames.get(name, name))
-        extraPre = ''
-        argsPre = []
-        if name in self.descriptor.needsAbstract:
-            abstractName = re.sub(r'<\w+>', '', self.descriptor.nativeType)
-            extraPre = '  let abstract_this = %s::from_box(this);\n' % abstractName
-            argsPre = ['abstract_this']
+        (extraPre, argsPre) = abstractThis(self.descriptor, name)
         return CGWrapper(CGMethodCall(argsPre, nativeName, self.method.isStatic(),
                                       self.descriptor, self.method),
                          pre=extraPre +
@@ -3281,9 +3286,11 @@ class CGSpecializedGetter(CGAbstractExternMethod):
                                                             getter=True))
         if resultOutParam or self.attr.type.nullable() or not infallible:
             nativeName = "Get" + nativeName
-        return CGWrapper(CGIndenter(CGGetterCall(self.attr.type, nativeName,
+        (extraPre, argsPre) = abstractThis(self.descriptor, name)
+        return CGWrapper(CGIndenter(CGGetterCall(self.attr.type, argsPre, nativeName,
                                                  self.descriptor, self.attr)),
-                         pre="  let obj = (*obj.unnamed);\n" +
+                         pre=extraPre +
+                             "  let obj = (*obj.unnamed);\n" +
                              "  let this = &mut (*this).payload;\n").define()
 
 class CGGenericSetter(CGAbstractBindingMethod):
@@ -3332,9 +3339,11 @@ class CGSpecializedSetter(CGAbstractExternMethod):
     def definition_body(self):
         name = self.attr.identifier.name
         nativeName = "Set" + MakeNativeName(self.descriptor.binaryNames.get(name, name))
-        return CGWrapper(CGIndenter(CGSetterCall(self.attr.type, nativeName,
+        (extraPre, argsPre) = abstractThis(self.descriptor, name)
+        return CGWrapper(CGIndenter(CGSetterCall(self.attr.type, argsPre, nativeName,
                                                  self.descriptor, self.attr)),
-                         pre="  let obj = (*obj.unnamed);\n" +
+                         pre=extraPre +
+                             "  let obj = (*obj.unnamed);\n" +
                              "  let this = &mut (*this).payload;\n").define()
 
 def infallibleForMember(member, type, descriptorProvider):
@@ -3977,9 +3986,10 @@ class CGAbstractClassHook(CGAbstractExternMethod):
 
 def finalizeHook(descriptor, hookName, context):
     if descriptor.customFinalize:
-        return """if (this) {
-  this->%s(%s);
-}""" % (hookName, context)
+        return """let val = JS_GetReservedSlot(obj, 0);
+let this: @mut %s = cast::transmute(RUST_JSVAL_TO_PRIVATE(val));
+this.finalize();
+""" % descriptor.concreteType
     #clearWrapper = "ClearWrapper(self, self);\n" if descriptor.wrapperCache else ""
     if descriptor.workers:
         #release = "self->Release();"
@@ -47,6 +47,9 @@ macro_rules! generate_traceable(
 generate_cacheable_wrapper!(Comment, CommentBinding::Wrap)
 generate_binding_object!(Comment)
 generate_traceable!(Comment)
+generate_cacheable_wrapper!(DocumentFragment, DocumentFragmentBinding::Wrap)
+generate_binding_object!(DocumentFragment)
+generate_traceable!(DocumentFragment)
 generate_cacheable_wrapper!(DocumentType<ScriptView>, DocumentTypeBinding::Wrap)
 generate_binding_object!(DocumentType<ScriptView>)
 generate_traceable!(DocumentType<ScriptView>)
@@ -4,13 +4,15 @@
 
 use dom::bindings::utils::{CacheableWrapper, WrapperCache, Traceable};
 use dom::element::*;
+use dom::eventtarget::EventListener;
 use dom::types::*;
 use dom::node::{AbstractNode, ElementNodeTypeId, TextNodeTypeId, CommentNodeTypeId};
-use dom::node::{DoctypeNodeTypeId, ScriptView};
+use dom::node::{DoctypeNodeTypeId, DocumentFragmentNodeTypeId, ScriptView};
 
 use std::cast;
 use std::libc;
 use std::ptr;
+use js::glue::RUST_JSVAL_TO_OBJECT;
 use js::jsapi::{JSContext, JSObject, JSTracer, JSTRACE_OBJECT, JS_CallTracer};
 use servo_util::tree::TreeNodeRef;
 
@@ -91,6 +93,7 @@ pub fn create(cx: *JSContext, node: &mut AbstractNode<ScriptView>) -> *JSObject
         ElementNodeTypeId(HTMLUnknownElementTypeId) => generate_element!(HTMLUnknownElement),
         CommentNodeTypeId => generate_element!(Comment),
         DoctypeNodeTypeId => generate_element!(DocumentType<ScriptView>),
+        DocumentFragmentNodeTypeId => generate_element!(DocumentFragment),
         TextNodeTypeId => generate_element!(Text)
      }
 }
@@ -130,11 +133,28 @@ impl Traceable for Node<ScriptView> {
                 }
             }
         }
+        #[fixed_stack_segment]
+        fn trace_listener(tracer: *mut JSTracer, listener: &EventListener) {
+            debug!("tracing %s listener", listener.type_);
+            unsafe {
+                (*tracer).debugPrinter = ptr::null();
+                (*tracer).debugPrintIndex = -1;
+                do "event listener".to_c_str().with_ref |name| {
+                    (*tracer).debugPrintArg = name as *libc::c_void;
+                    JS_CallTracer(cast::transmute(tracer),
+                                  RUST_JSVAL_TO_OBJECT(listener.callback),
+                                  JSTRACE_OBJECT as u32);
+                }
+            }
+        }
         error!("tracing %p?:", self.wrapper.get_wrapper());
         trace_node(tracer, self.parent_node, "parent");
         trace_node(tracer, self.first_child, "first child");
         trace_node(tracer, self.last_child, "last child");
         trace_node(tracer, self.next_sibling, "next sibling");
         trace_node(tracer, self.prev_sibling, "prev sibling");
+        for listener in self.event_listeners.iter() {
+            trace_listener(tracer, listener);
+        }
     }
 }
@@ -0,0 +1,245 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The 2D context of canvas elements. Only text and image bitmaps can be drawn so far. Text is
+//! shaped and measured by layout with the same fonts as the text of the document, so that text
+//! drawn on a canvas looks and measures the same as text in the page.
+
+use dom::bindings::codegen::CanvasRenderingContext2DBinding;
+use dom::bindings::utils::{BindingObject, CacheableWrapper, DOMString, ErrorResult};
+use dom::bindings::utils::{FailureUnknown, WrapperCache, str};
+use dom::htmlcanvaselement::{CanvasDrawing, ImageDrawing, TextDrawing};
+use dom::htmlcanvaselement::{CanvasText, CanvasTextAlign, CanvasTextBaseline, StartTextAlign};
+use dom::htmlcanvaselement::{EndTextAlign, LeftTextAlign, RightTextAlign, CenterTextAlign};
+use dom::htmlcanvaselement::{TopTextBaseline, HangingTextBaseline, MiddleTextBaseline};
+use dom::htmlcanvaselement::{AlphabeticTextBaseline, IdeographicTextBaseline};
+use dom::htmlcanvaselement::BottomTextBaseline;
+use dom::imagebitmap::ImageBitmap;
+use dom::node::{AbstractNode, ScriptView};
+use dom::textmetrics::TextMetrics;
+use script_task::page_from_context;
+use style::parsing_utils::one_component_value;
+
+use CSSColor = cssparser::Color;
+use cssparser::{ComponentValue, CurrentColor, RGBA, tokenize};
+use gfx::color::{Color, rgb, rgba};
+use gfx::font::FontStyle;
+use js::jsapi::{JSContext, JSObject};
+use std::cast;
+use std::cell::Cell;
+
+pub struct CanvasRenderingContext2D {
+    wrapper: WrapperCache,
+    canvas: AbstractNode<ScriptView>,
+    /// The values of `fillStyle`, `strokeStyle` and `font` as they were last validly set, and
+    /// what they were parsed to.
+    fill_style: ~str,
+    fill_color: Color,
+    stroke_style: ~str,
+    stroke_color: Color,
+    font: ~str,
+    font_style: FontStyle,
+    text_align: CanvasTextAlign,
+    text_baseline: CanvasTextBaseline,
+}
+
+impl CanvasRenderingContext2D {
+    pub fn new(canvas: AbstractNode<ScriptView>, cx: *JSContext, scope: *JSObject)
+               -> @mut CanvasRenderingContext2D {
+        let context = @mut CanvasRenderingContext2D {
+            wrapper: WrapperCache::new(),
+            canvas: canvas,
+            fill_style: ~"#000000",
+            fill_color: rgb(0, 0, 0),
+            stroke_style: ~"#000000",
+            stroke_color: rgb(0, 0, 0),
+            font: ~"10px sans-serif",
+            font_style: FontStyle::from_css_font("10px sans-serif").unwrap(),
+            text_align: StartTextAlign,
+            text_baseline: AlphabeticTextBaseline,
+        };
+        context.wrap_object_shared(cx, scope);
+        context
+    }
+
+    pub fn Canvas(&self) -> AbstractNode<ScriptView> {
+        self.canvas
+    }
+
+    pub fn FillStyle(&self) -> DOMString {
+        str(self.fill_style.clone())
+    }
+
+    pub fn SetFillStyle(&mut self, value: &DOMString) {
+        // Values that aren't colors are ignored.
+        for &color in parse_color(value.to_str()).iter() {
+            self.fill_style = value.to_str();
+            self.fill_color = color;
+        }
+    }
+
+    pub fn StrokeStyle(&self) -> DOMString {
+        str(self.stroke_style.clone())
+    }
+
+    pub fn SetStrokeStyle(&mut self, value: &DOMString) {
+        for &color in parse_color(value.to_str()).iter() {
+            self.stroke_style = value.to_str();
+            self.stroke_color = color;
+        }
+    }
+
+    pub fn Font(&self) -> DOMString {
+        str(self.font.clone())
+    }
+
+    pub fn SetFont(&mut self, value: &DOMString) {
+        match FontStyle::from_css_font(value.to_str()) {
+            Some(style) => {
+                self.font = value.to_str();
+                self.font_style = style;
+            }
+            None => {}
+        }
+    }
+
+    pub fn TextAlign(&self) -> DOMString {
+        str(match self.text_align {
+            StartTextAlign => ~"start",
+            EndTextAlign => ~"end",
+            LeftTextAlign => ~"left",
+            RightTextAlign => ~"right",
+            CenterTextAlign => ~"center",
+        })
+    }
+
+    pub fn SetTextAlign(&mut self, value: &DOMString) {
+        self.text_align = match value.to_str().as_slice() {
+            "start" => StartTextAlign,
+            "end" => EndTextAlign,
+            "left" => LeftTextAlign,
+            "right" => RightTextAlign,
+            "center" => CenterTextAlign,
+            _ => return,
+        }
+    }
+
+    pub fn TextBaseline(&self) -> DOMString {
+        str(match self.text_baseline {
+            TopTextBaseline => ~"top",
+            HangingTextBaseline => ~"hanging",
+            MiddleTextBaseline => ~"middle",
+            AlphabeticTextBaseline => ~"alphabetic",
+            IdeographicTextBaseline => ~"ideographic",
+            BottomTextBaseline => ~"bottom",
+        })
+    }
+
+    pub fn SetTextBaseline(&mut self, value: &DOMString) {
+        self.text_baseline = match value.to_str().as_slice() {
+            "top" => TopTextBaseline,
+            "hanging" => HangingTextBaseline,
+            "middle" => MiddleTextBaseline,
+            "alphabetic" => AlphabeticTextBaseline,
+            "ideographic" => IdeographicTextBaseline,
+            "bottom" => BottomTextBaseline,
+            _ => return,
+        }
+    }
+
+    /// Draws an image bitmap with its top left corner at a point. Closed bitmaps can't be drawn.
+    pub fn DrawImage(&self, image: @mut ImageBitmap, dx: f64, dy: f64, rv: &mut ErrorResult) {
+        let image = match image.image {
+            Some(ref image) => image.clone(),
+            None => {
+                *rv = Err(FailureUnknown);
+                return
+            }
+        };
+        if dx.is_finite() && dy.is_finite() {
+            self.draw(ImageDrawing(image, dx, dy))
+        }
+    }
+
+    pub fn FillText(&self, text: &DOMString, x: f64, y: f64) {
+        self.draw_text(text, x, y, self.fill_color)
+    }
+
+    /// FIXME: Glyph outlines can't be stroked yet, so the text is filled in the stroke color.
+    pub fn StrokeText(&self, text: &DOMString, x: f64, y: f64) {
+        self.draw_text(text, x, y, self.stroke_color)
+    }
+
+    pub fn MeasureText(&self, text: &DOMString) -> @mut TextMetrics {
+        let (scope, cx) = self.canvas.with_imm_element(|element| element.get_scope_and_cx());
+        let page = page_from_context(cx);
+        let metrics = unsafe {
+            (*page).measure_text(self.font_style.clone(), prepare_text(text))
+        };
+        TextMetrics::new(&metrics, self.text_align, self.text_baseline, cx, scope)
+    }
+
+    fn draw_text(&self, text: &DOMString, x: f64, y: f64, color: Color) {
+        // Text drawn at a point that isn't finite is ignored.
+        if !x.is_finite() || !y.is_finite() {
+            return
+        }
+        self.draw(TextDrawing(CanvasText {
+            text: prepare_text(text),
+            font: self.font_style.clone(),
+            color: color,
+            x: x,
+            y: y,
+            align: self.text_align,
+            baseline: self.text_baseline,
+        }))
+    }
+
+    fn draw(&self, drawing: CanvasDrawing) {
+        let drawing = Cell::new(drawing);
+        do self.canvas.with_mut_canvas_element |canvas| {
+            canvas.draw(drawing.take())
+        }
+    }
+}
+
+/// Replaces the whitespace in text to be drawn or measured with spaces, as canvas does.
+fn prepare_text(text: &DOMString) -> ~str {
+    text.to_str().iter().map(|ch| if ch.is_whitespace() { ' ' } else { ch }).collect()
+}
+
+/// Parses a CSS color for `fillStyle` or `strokeStyle`.
+///
+/// FIXME: `currentColor` should be the `color` of the canvas, but it is treated as invalid.
+fn parse_color(value: &str) -> Option<Color> {
+    let input: ~[ComponentValue] = tokenize(value).map(|(value, _)| value).collect();
+    match one_component_value(input).chain(|value| CSSColor::parse(value)) {
+        Some(RGBA(color)) => {
+            let component = |value: f32| (value * 255.0).round() as u8;
+            Some(rgba(component(color.red), component(color.green), component(color.blue),
+                      color.alpha as float))
+        }
+        Some(CurrentColor) | None => None,
+    }
+}
+
+impl CacheableWrapper for CanvasRenderingContext2D {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        CanvasRenderingContext2DBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for CanvasRenderingContext2D {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
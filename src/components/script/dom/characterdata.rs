@@ -4,10 +4,11 @@
 
 //! DOM bindings for `CharacterData`.
 
-use dom::bindings::utils::{DOMString, str, ErrorResult};
+use dom::bindings::utils::{DOMString, ErrorResult, FailureUnknown, str};
 use dom::bindings::utils::{BindingObject, CacheableWrapper, WrapperCache};
-use dom::node::{Node, NodeTypeId, ScriptView};
+use dom::node::{AbstractNode, Node, NodeTypeId, ScriptView};
 use js::jsapi::{JSObject, JSContext};
+use servo_util::shared_str::{utf16_len, utf16_to_byte_index};
 
 pub struct CharacterData {
     parent: Node<ScriptView>,
@@ -28,30 +29,85 @@ impl CharacterData {
 
     pub fn SetData(&mut self, arg: &DOMString, _rv: &mut ErrorResult) {
         self.data = arg.unwrap();
+        self.parent.content_changed(None, false)
     }
 
+    /// Returns the length of the data in UTF-16 code units, which all the offsets and counts of
+    /// the DOM are in.
     pub fn Length(&self) -> u32 {
-        self.data.len() as u32
+        utf16_len(self.data) as u32
     }
 
-    pub fn SubstringData(&self, offset: u32, count: u32, _rv: &mut ErrorResult) -> DOMString {
-        str(self.data.slice(offset as uint, count as uint).to_str())
+    pub fn SubstringData(&self, offset: u32, count: u32, rv: &mut ErrorResult) -> DOMString {
+        match self.byte_range(offset, count) {
+            Some((begin, end)) => str(self.data.slice(begin, end).to_owned()),
+            None => {
+                *rv = Err(FailureUnknown);
+                str(~"")
+            }
+        }
     }
 
-    pub fn AppendData(&mut self, arg: &DOMString, _rv: &mut ErrorResult) {
+    pub fn AppendData(&mut self,
+                      abstract_self: AbstractNode<ScriptView>,
+                      arg: &DOMString,
+                      _rv: &mut ErrorResult) {
         self.data.push_str(arg.unwrap());
+        self.parent.content_changed(Some(abstract_self), false)
     }
 
-    pub fn InsertData(&mut self, _offset: u32, _arg: &DOMString, _rv: &mut ErrorResult) {
-        fail!("CharacterData::InsertData() is unimplemented")
+    pub fn InsertData(&mut self,
+                      abstract_self: AbstractNode<ScriptView>,
+                      offset: u32,
+                      arg: &DOMString,
+                      rv: &mut ErrorResult) {
+        self.ReplaceData(abstract_self, offset, 0, arg, rv)
     }
 
-    pub fn DeleteData(&mut self, _offset: u32, _count: u32, _rv: &mut ErrorResult) {
-        fail!("CharacterData::DeleteData() is unimplemented")
+    pub fn DeleteData(&mut self,
+                      abstract_self: AbstractNode<ScriptView>,
+                      offset: u32,
+                      count: u32,
+                      rv: &mut ErrorResult) {
+        self.ReplaceData(abstract_self, offset, count, &str(~""), rv)
     }
 
-    pub fn ReplaceData(&mut self, _offset: u32, _count: u32, _arg: &DOMString, _rv: &mut ErrorResult) {
-        fail!("CharacterData::ReplaceData() is unimplemented")
+    pub fn ReplaceData(&mut self,
+                       abstract_self: AbstractNode<ScriptView>,
+                       offset: u32,
+                       count: u32,
+                       arg: &DOMString,
+                       rv: &mut ErrorResult) {
+        match self.byte_range(offset, count) {
+            Some((begin, end)) => {
+                let count = utf16_len(self.data.slice(begin, end));
+                let arg = arg.to_str();
+                self.data = self.data.slice_to(begin).to_owned() + arg + self.data.slice_from(end);
+                for doc in self.parent.owner_doc.iter() {
+                    do doc.with_base |doc| {
+                        doc.data_replaced(abstract_self, offset as uint, count, utf16_len(arg))
+                    }
+                }
+                self.parent.content_changed(Some(abstract_self), false)
+            }
+            None => *rv = Err(FailureUnknown),
+        }
+    }
+
+    /// Returns the byte range of the data that `count` UTF-16 code units from `offset` cover,
+    /// ending at the end of the data if there are fewer. Returns `None` if `offset` is past the
+    /// end of the data.
+    ///
+    /// FIXME: Offsets between the two halves of a surrogate pair are allowed, and split the pair,
+    /// but the data can't hold half a pair, so they are refused.
+    fn byte_range(&self, offset: u32, count: u32) -> Option<(uint, uint)> {
+        let length = utf16_len(self.data);
+        let (offset, count) = (offset as uint, count as uint);
+        let end = if count > length - offset.min(&length) { length } else { offset + count };
+        match (utf16_to_byte_index(self.data, offset), utf16_to_byte_index(self.data, end)) {
+            (Some(begin), Some(end)) => Some((begin, end)),
+            _ => None,
+        }
     }
 }
 
@@ -0,0 +1,74 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::CSSRuleListBinding;
+use dom::bindings::utils::{WrapperCache, CacheableWrapper, BindingObject};
+use dom::cssstylerule::CSSStyleRule;
+use script_task::page_from_context;
+
+use js::jsapi::{JSObject, JSContext};
+
+use std::cast;
+
+pub struct CSSRuleList {
+    wrapper: WrapperCache,
+    rules: ~[@mut CSSStyleRule]
+}
+
+impl CSSRuleList {
+    pub fn new(rules: ~[@mut CSSStyleRule],
+               cx: *JSContext,
+               scope: *JSObject)
+               -> @mut CSSRuleList {
+        let list = @mut CSSRuleList {
+            wrapper: WrapperCache::new(),
+            rules: rules
+        };
+        list.init_wrapper(cx, scope);
+        list
+    }
+
+    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
+        self.wrap_object_shared(cx, scope);
+    }
+
+    pub fn Length(&self) -> u32 {
+        self.rules.len() as u32
+    }
+
+    pub fn Item(&self, index: u32) -> Option<@mut CSSStyleRule> {
+        if index < self.rules.len() as u32 {
+            Some(self.rules[index])
+        } else {
+            None
+        }
+    }
+
+    pub fn IndexedGetter(&self, index: u32, found: &mut bool) -> Option<@mut CSSStyleRule> {
+        *found = index < self.rules.len() as u32;
+        self.Item(index)
+    }
+}
+
+impl CacheableWrapper for CSSRuleList {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        CSSRuleListBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for CSSRuleList {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
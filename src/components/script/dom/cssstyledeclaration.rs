@@ -0,0 +1,238 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Blocks of declarations, as CSSOM exposes them: those of style rules, which script can edit,
+//! and those that `getComputedStyle()` returns. The latter are live: each read asks layout for
+//! the resolved value of the property as the element has it now, after layout has caught up with
+//! the changes made to the document.
+
+use dom::bindings::codegen::CSSStyleDeclarationBinding;
+use dom::bindings::utils::{DOMString, ErrorResult, FailureUnknown, str};
+use dom::bindings::utils::{WrapperCache, CacheableWrapper, BindingObject};
+use dom::cssstylesheet::{parse_declarations, rules_changed};
+use dom::node::{AbstractNode, ScriptView};
+use layout_interface::COMPUTED_STYLE_PROPERTIES;
+use script_task::{Page, page_from_context};
+
+use js::jsapi::{JSObject, JSContext};
+
+use std::ascii::StrAsciiExt;
+use std::cast;
+
+pub struct CSSStyleDeclaration {
+    wrapper: WrapperCache,
+    page: *mut Page,
+    owner: DeclarationsOwner,
+}
+
+/// What a block of declarations belongs to.
+pub enum DeclarationsOwner {
+    /// The resolved style of an element, which is read-only.
+    ComputedStyleOwner(AbstractNode<ScriptView>),
+    /// A style rule of a sheet, with the selectors of the rule and its declarations.
+    StyleRuleOwner(~str, ~[Declaration]),
+}
+
+/// A declaration of a style rule, as it was written.
+///
+/// FIXME: Values aren't checked against the grammars of their properties, or shorthands expanded.
+/// Layout drops those that are invalid when it parses the sheet again.
+#[deriving(Clone)]
+pub struct Declaration {
+    /// The name of the property, in lowercase.
+    name: ~str,
+    value: ~str,
+    important: bool,
+}
+
+impl CSSStyleDeclaration {
+    pub fn new(owner: DeclarationsOwner,
+               page: *mut Page,
+               cx: *JSContext,
+               scope: *JSObject)
+               -> @mut CSSStyleDeclaration {
+        let declaration = @mut CSSStyleDeclaration {
+            wrapper: WrapperCache::new(),
+            page: page,
+            owner: owner,
+        };
+        declaration.wrap_object_shared(cx, scope);
+        declaration
+    }
+
+    pub fn Length(&self) -> u32 {
+        match self.owner {
+            ComputedStyleOwner(_) => COMPUTED_STYLE_PROPERTIES.len() as u32,
+            StyleRuleOwner(_, ref declarations) => declarations.len() as u32,
+        }
+    }
+
+    pub fn Item(&self, index: u32) -> DOMString {
+        if index >= self.Length() {
+            return str(~"")
+        }
+        match self.owner {
+            ComputedStyleOwner(_) => str(COMPUTED_STYLE_PROPERTIES[index].to_owned()),
+            StyleRuleOwner(_, ref declarations) => str(declarations[index].name.clone()),
+        }
+    }
+
+    pub fn IndexedGetter(&self, index: u32, found: &mut bool) -> DOMString {
+        *found = index < self.Length();
+        self.Item(index)
+    }
+
+    /// Returns the value of a property, or the empty string if it isn't declared. For computed
+    /// style, the value is the resolved one, and is empty if the property isn't one that layout
+    /// knows or the element has no style, as when it isn't in the document.
+    pub fn GetPropertyValue(&self, property: &DOMString) -> DOMString {
+        let property = property.to_str().to_ascii_lower();
+        match self.owner {
+            ComputedStyleOwner(element) => {
+                if !COMPUTED_STYLE_PROPERTIES.iter().any(|&p| p == property) {
+                    return str(~"")
+                }
+                let value = unsafe { (*self.page).resolved_style_value(element, property) };
+                str(value.unwrap_or_default(~""))
+            }
+            StyleRuleOwner(_, ref declarations) => {
+                match declarations.iter().find(|declaration| declaration.name == property) {
+                    Some(declaration) => str(declaration.value.clone()),
+                    None => str(~""),
+                }
+            }
+        }
+    }
+
+    pub fn GetPropertyPriority(&self, property: &DOMString) -> DOMString {
+        let property = property.to_str().to_ascii_lower();
+        match self.owner {
+            ComputedStyleOwner(_) => str(~""),
+            StyleRuleOwner(_, ref declarations) => {
+                match declarations.iter().find(|declaration| declaration.name == property) {
+                    Some(declaration) if declaration.important => str(~"important"),
+                    _ => str(~""),
+                }
+            }
+        }
+    }
+
+    /// Declares a property, or removes its declaration if `value` is empty. Computed style can't
+    /// be changed.
+    pub fn SetProperty(&mut self,
+                       property: &DOMString,
+                       value: &DOMString,
+                       priority: &DOMString,
+                       rv: &mut ErrorResult) {
+        let value = value.to_str().trim().to_owned();
+        if value.is_empty() {
+            self.RemoveProperty(property, rv);
+            return
+        }
+        let important = match priority.to_str().to_ascii_lower().as_slice() {
+            "" => false,
+            "important" => true,
+            _ => return,
+        };
+        let declaration = Declaration {
+            name: property.to_str().to_ascii_lower(),
+            value: value,
+            important: important,
+        };
+        match self.owner {
+            ComputedStyleOwner(_) => {
+                *rv = Err(FailureUnknown);
+                return
+            }
+            StyleRuleOwner(_, ref mut declarations) => {
+                match declarations.iter().position(|old| old.name == declaration.name) {
+                    Some(index) => declarations[index] = declaration,
+                    None => declarations.push(declaration),
+                }
+            }
+        }
+        self.declarations_changed();
+    }
+
+    /// Removes the declaration of a property, and returns the value it had.
+    pub fn RemoveProperty(&mut self, property: &DOMString, rv: &mut ErrorResult) -> DOMString {
+        let property = property.to_str().to_ascii_lower();
+        let removed = match self.owner {
+            ComputedStyleOwner(_) => {
+                *rv = Err(FailureUnknown);
+                return str(~"")
+            }
+            StyleRuleOwner(_, ref mut declarations) => {
+                match declarations.iter().position(|declaration| declaration.name == property) {
+                    Some(index) => declarations.remove(index).value,
+                    None => return str(~""),
+                }
+            }
+        };
+        self.declarations_changed();
+        str(removed)
+    }
+
+    pub fn CssText(&self) -> DOMString {
+        match self.owner {
+            ComputedStyleOwner(_) => str(~""),
+            StyleRuleOwner(_, ref declarations) => {
+                str(serialize_declarations(declarations.as_slice()))
+            }
+        }
+    }
+
+    pub fn SetCssText(&mut self, text: &DOMString, rv: &mut ErrorResult) {
+        match self.owner {
+            ComputedStyleOwner(_) => {
+                *rv = Err(FailureUnknown);
+                return
+            }
+            StyleRuleOwner(_, ref mut declarations) => {
+                *declarations = parse_declarations(text.to_str())
+            }
+        }
+        self.declarations_changed();
+    }
+
+    /// Has the style sheets parsed again for layout after the declarations of a style rule have
+    /// changed, restyling the elements that the selectors of the rule match.
+    fn declarations_changed(&self) {
+        match self.owner {
+            ComputedStyleOwner(_) => {}
+            StyleRuleOwner(ref selectors, _) => rules_changed(self.page, [selectors.clone()]),
+        }
+    }
+}
+
+/// Serializes declarations as CSSOM does, each followed by a semicolon.
+pub fn serialize_declarations(declarations: &[Declaration]) -> ~str {
+    let serialized: ~[~str] = do declarations.iter().map |declaration| {
+        let priority = if declaration.important { " !important" } else { "" };
+        fmt!("%s: %s%s;", declaration.name, declaration.value, priority)
+    }.collect();
+    serialized.connect(" ")
+}
+
+impl CacheableWrapper for CSSStyleDeclaration {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        CSSStyleDeclarationBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for CSSStyleDeclaration {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
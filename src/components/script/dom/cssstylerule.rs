@@ -0,0 +1,85 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::CSSStyleRuleBinding;
+use dom::bindings::utils::{DOMString, str};
+use dom::bindings::utils::{WrapperCache, CacheableWrapper, BindingObject};
+use dom::cssstyledeclaration::{CSSStyleDeclaration, Declaration, StyleRuleOwner};
+use script_task::{Page, page_from_context};
+
+use js::jsapi::{JSObject, JSContext};
+
+use std::cast;
+
+pub struct CSSStyleRule {
+    wrapper: WrapperCache,
+    /// The selectors of the rule, as they were written.
+    selector_text: ~str,
+    style: @mut CSSStyleDeclaration,
+}
+
+impl CSSStyleRule {
+    pub fn new(page: *mut Page,
+               selector_text: ~str,
+               declarations: ~[Declaration],
+               cx: *JSContext,
+               scope: *JSObject)
+               -> @mut CSSStyleRule {
+        let style = CSSStyleDeclaration::new(StyleRuleOwner(selector_text.clone(), declarations),
+                                             page,
+                                             cx,
+                                             scope);
+        let rule = @mut CSSStyleRule {
+            wrapper: WrapperCache::new(),
+            selector_text: selector_text,
+            style: style,
+        };
+        rule.wrap_object_shared(cx, scope);
+        rule
+    }
+
+    pub fn SelectorText(&self) -> DOMString {
+        str(self.selector_text.clone())
+    }
+
+    pub fn CssText(&self) -> DOMString {
+        str(self.css_text())
+    }
+
+    pub fn Style(&self) -> @mut CSSStyleDeclaration {
+        self.style
+    }
+
+    /// Returns the rule serialized, as it is now.
+    pub fn css_text(&self) -> ~str {
+        let declarations = self.style.CssText().to_str();
+        if declarations.is_empty() {
+            fmt!("%s { }", self.selector_text)
+        } else {
+            fmt!("%s { %s }", self.selector_text, declarations)
+        }
+    }
+}
+
+impl CacheableWrapper for CSSStyleRule {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        CSSStyleRuleBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for CSSStyleRule {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
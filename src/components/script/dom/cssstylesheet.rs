@@ -0,0 +1,400 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The style sheets of a document, as CSSOM exposes them. Layout can't change the sheets it has
+//! parsed, so each sheet keeps the text of its rules, and is parsed again for layout when script
+//! edits them.
+
+use dom::bindings::codegen::CSSStyleSheetBinding;
+use dom::bindings::utils::{DOMString, ErrorResult, FailureUnknown, null_string, str};
+use dom::bindings::utils::{WrapperCache, CacheableWrapper, BindingObject};
+use dom::cssrulelist::CSSRuleList;
+use dom::cssstyledeclaration::Declaration;
+use dom::cssstylerule::CSSStyleRule;
+use dom::window::Window;
+use html::cssparse::{LayoutStylesheet, StylesheetSource, parse_stylesheet_text};
+use script_task::{Page, page_from_context};
+
+use extra::url::Url;
+use js::jsapi::{JSObject, JSContext};
+
+use std::ascii::StrAsciiExt;
+use std::cast;
+
+pub struct CSSStyleSheet {
+    wrapper: WrapperCache,
+    owner: @mut Window,
+    /// The URL that relative URLs in the sheet resolve against.
+    url: Url,
+    /// Whether the sheet was loaded from `url`, rather than being inline in the document.
+    linked: bool,
+    rules: ~[SheetRule],
+}
+
+/// A rule of a style sheet.
+pub enum SheetRule {
+    StyleSheetRule(@mut CSSStyleRule),
+    /// The text of a rule that isn't exposed to script, such as an at-rule.
+    ///
+    /// FIXME: CSSOM exposes at-rules too, as `CSSMediaRule` and the like.
+    OtherSheetRule(~str),
+}
+
+impl CSSStyleSheet {
+    pub fn new(owner: @mut Window, source: StylesheetSource) -> @mut CSSStyleSheet {
+        let StylesheetSource { url, linked, text } = source;
+        let sheet = @mut CSSStyleSheet {
+            wrapper: WrapperCache::new(),
+            owner: owner,
+            url: url,
+            linked: linked,
+            rules: ~[],
+        };
+        let (scope, cx) = sheet.get_scope_and_cx();
+        sheet.wrap_object_shared(cx, scope);
+        sheet.rules = do split_rules(text).move_iter().map |(prelude, block)| {
+            sheet.new_rule(prelude, block)
+        }.collect();
+        sheet
+    }
+
+    fn get_scope_and_cx(&self) -> (*JSObject, *JSContext) {
+        let cx = unsafe { (*self.owner.page).js_info.get_ref().js_compartment.cx.ptr };
+        let scope = self.owner.get_wrappercache().get_wrapper();
+        (scope, cx)
+    }
+
+    /// Makes a rule of this sheet out of its prelude and the contents of its block, if it has one.
+    fn new_rule(&self, prelude: ~str, block: Option<~str>) -> SheetRule {
+        match block {
+            Some(block) => {
+                if prelude.starts_with("@") {
+                    OtherSheetRule(fmt!("%s { %s }", prelude, block))
+                } else {
+                    let (scope, cx) = self.get_scope_and_cx();
+                    StyleSheetRule(CSSStyleRule::new(self.owner.page,
+                                                     prelude,
+                                                     parse_declarations(block),
+                                                     cx,
+                                                     scope))
+                }
+            }
+            None => OtherSheetRule(prelude + ";"),
+        }
+    }
+
+    /// Returns the style rules of the sheet, which are the rules that script sees.
+    fn style_rules(&self) -> ~[@mut CSSStyleRule] {
+        do self.rules.iter().filter_map |rule| {
+            match *rule {
+                StyleSheetRule(rule) => Some(rule),
+                OtherSheetRule(_) => None,
+            }
+        }.collect()
+    }
+
+    /// Returns the position among all the rules of the sheet of the style rule at `index`, or the
+    /// position after the last rule if `index` is the number of style rules.
+    fn position_of_style_rule(&self, index: u32) -> Option<uint> {
+        let mut style_rules = 0;
+        for (position, rule) in self.rules.iter().enumerate() {
+            match *rule {
+                StyleSheetRule(_) if style_rules == index => return Some(position),
+                StyleSheetRule(_) => style_rules += 1,
+                OtherSheetRule(_) => {}
+            }
+        }
+        if style_rules == index {
+            Some(self.rules.len())
+        } else {
+            None
+        }
+    }
+
+    pub fn GetHref(&self) -> DOMString {
+        if self.linked {
+            str(self.url.to_str())
+        } else {
+            null_string
+        }
+    }
+
+    /// FIXME: CSSOM has this return the same live list each time.
+    pub fn CssRules(&self) -> @mut CSSRuleList {
+        let (scope, cx) = self.get_scope_and_cx();
+        CSSRuleList::new(self.style_rules(), cx, scope)
+    }
+
+    /// Inserts a style rule before the one at `index`, and restyles the elements its selectors
+    /// match.
+    ///
+    /// FIXME: At-rules can't be inserted, since they aren't exposed.
+    pub fn InsertRule(&mut self, rule: &DOMString, index: u32, rv: &mut ErrorResult) -> u32 {
+        let position = match self.position_of_style_rule(index) {
+            Some(position) => position,
+            None => {
+                *rv = Err(FailureUnknown);
+                return 0
+            }
+        };
+        let mut parsed = split_rules(rule.to_str());
+        if parsed.len() != 1 {
+            *rv = Err(FailureUnknown);
+            return 0
+        }
+        let (prelude, block) = parsed.pop();
+        if prelude.starts_with("@") || block.is_none() {
+            *rv = Err(FailureUnknown);
+            return 0
+        }
+
+        let rule = self.new_rule(prelude.clone(), block);
+        self.rules.insert(position, rule);
+        rules_changed(self.owner.page, [prelude]);
+        index
+    }
+
+    /// Deletes the style rule at `index`, and restyles the elements its selectors matched.
+    pub fn DeleteRule(&mut self, index: u32, rv: &mut ErrorResult) {
+        if index >= self.style_rules().len() as u32 {
+            *rv = Err(FailureUnknown);
+            return
+        }
+        let position = self.position_of_style_rule(index).unwrap();
+        let selectors = match self.rules.remove(position) {
+            StyleSheetRule(rule) => rule.selector_text.clone(),
+            OtherSheetRule(_) => fail!("not a style rule"),
+        };
+        rules_changed(self.owner.page, [selectors]);
+    }
+
+    /// Returns the text of the sheet as it is now.
+    pub fn text(&self) -> ~str {
+        let rules: ~[~str] = do self.rules.iter().map |rule| {
+            match *rule {
+                StyleSheetRule(rule) => rule.css_text(),
+                OtherSheetRule(ref text) => text.clone(),
+            }
+        }.collect();
+        rules.connect("\n")
+    }
+
+    /// Returns the selectors of the style rules of the sheet as they were written, with those of
+    /// the style rules in its conditional group rules, such as `@media`.
+    pub fn selectors(&self) -> ~[~str] {
+        let mut selectors = ~[];
+        for rule in self.rules.iter() {
+            match *rule {
+                StyleSheetRule(rule) => selectors.push(rule.selector_text.clone()),
+                OtherSheetRule(ref text) => add_nested_selectors(*text, &mut selectors),
+            }
+        }
+        selectors
+    }
+
+    /// Parses the sheet as it is now for layout.
+    pub fn parse_for_layout(&self) -> LayoutStylesheet {
+        parse_stylesheet_text(self.url.clone(), self.text())
+    }
+}
+
+/// Has the style sheets of the document of a page parsed again for layout after the rules with
+/// the given selectors have been edited, inserted or deleted, and restyles the elements that the
+/// selectors match.
+pub fn rules_changed(page: *mut Page, selectors: &[~str]) {
+    let document = unsafe { (*page).frame.get_ref().document };
+    do document.with_base |document| {
+        document.style_sheets_changed(selectors)
+    }
+}
+
+/// Returns the selectors of the style rules of a style sheet that isn't exposed to script, such as
+/// one of those that apply to every page, with those of the style rules in its conditional group
+/// rules.
+pub fn sheet_selectors(css: &str) -> ~[~str] {
+    let mut selectors = ~[];
+    add_nested_selectors(css, &mut selectors);
+    selectors
+}
+
+/// Adds the selectors of the style rules in the conditional group rules in `css` to `selectors`.
+fn add_nested_selectors(css: &str, selectors: &mut ~[~str]) {
+    for (prelude, block) in split_rules(css).move_iter() {
+        let block = match block {
+            Some(block) => block,
+            None => loop,
+        };
+        if !prelude.starts_with("@") {
+            selectors.push(prelude)
+        } else {
+            let prelude = prelude.to_ascii_lower();
+            if ["@media", "@supports", "@document"].iter().any(|name| prelude.starts_with(*name)) {
+                add_nested_selectors(block, selectors)
+            }
+        }
+    }
+}
+
+/// Returns the characters of some CSS without its comments, each with whether it is in a string
+/// or escaped, where it can't delimit blocks, rules or declarations.
+fn significant_chars(css: &str) -> ~[(char, bool)] {
+    let mut result = ~[];
+    let mut chars = css.iter().peekable();
+    let mut quote = None;
+    loop {
+        let c = match chars.next() {
+            Some(c) => c,
+            None => break,
+        };
+        if c == '\\' {
+            result.push((c, true));
+            match chars.next() {
+                Some(escaped) => result.push((escaped, true)),
+                None => {}
+            }
+        } else if quote.is_some() {
+            result.push((c, true));
+            if Some(c) == quote {
+                quote = None
+            }
+        } else if c == '"' || c == '\'' {
+            result.push((c, true));
+            quote = Some(c)
+        } else if c == '/' && chars.peek() == Some(&'*') {
+            chars.next();
+            let mut previous = ' ';
+            loop {
+                match chars.next() {
+                    Some('/') if previous == '*' => break,
+                    Some(c) => previous = c,
+                    None => break,
+                }
+            }
+        } else {
+            result.push((c, false))
+        }
+    }
+    result
+}
+
+/// Splits the text of a style sheet into its rules, each with its prelude and the contents of its
+/// block, if it has one. Blocks that aren't closed are closed at the end of the text, as CSS
+/// does, and the `<!--` and `-->` that hide inline sheets from old browsers are dropped.
+fn split_rules(css: &str) -> ~[(~str, Option<~str>)] {
+    let mut rules = ~[];
+    let mut prelude = ~"";
+    let mut block = ~"";
+    let mut depth = 0;
+    let clean = |prelude: &str| prelude.replace("<!--", "").replace("-->", "").trim().to_owned();
+    for &(c, quoted) in significant_chars(css).iter() {
+        if quoted {
+            if depth == 0 { prelude.push_char(c) } else { block.push_char(c) }
+            loop
+        }
+        match c {
+            '{' => {
+                if depth > 0 {
+                    block.push_char(c)
+                }
+                depth += 1
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth > 0 {
+                    block.push_char(c)
+                } else {
+                    rules.push((clean(prelude), Some(block.trim().to_owned())));
+                    prelude = ~"";
+                    block = ~"";
+                }
+            }
+            ';' if depth == 0 => {
+                let statement = clean(prelude);
+                if !statement.is_empty() {
+                    rules.push((statement, None))
+                }
+                prelude = ~"";
+            }
+            _ if depth == 0 => prelude.push_char(c),
+            _ => block.push_char(c),
+        }
+    }
+    if depth > 0 {
+        rules.push((clean(prelude), Some(block.trim().to_owned())))
+    }
+    rules
+}
+
+/// Parses the contents of a block of declarations, dropping those without a name or a value.
+pub fn parse_declarations(css: &str) -> ~[Declaration] {
+    let mut declarations = ~[];
+    let mut declaration = ~"";
+    let mut depth = 0;
+    for &(c, quoted) in significant_chars(css).iter() {
+        if !quoted {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' if depth > 0 => depth -= 1,
+                ';' if depth == 0 => {
+                    let parsed = parse_declaration(declaration);
+                    declarations.push_all_move(parsed.move_iter().collect());
+                    declaration = ~"";
+                    loop
+                }
+                _ => {}
+            }
+        }
+        declaration.push_char(c)
+    }
+    let parsed = parse_declaration(declaration);
+    declarations.push_all_move(parsed.move_iter().collect());
+    declarations
+}
+
+fn parse_declaration(css: &str) -> Option<Declaration> {
+    let colon = match css.find(':') {
+        Some(colon) => colon,
+        None => return None,
+    };
+    let name = css.slice_to(colon).trim().to_ascii_lower();
+    let mut value = css.slice_from(colon + 1).trim().to_owned();
+    let mut important = false;
+    match value.rfind('!') {
+        Some(bang) if value.slice_from(bang + 1).trim().to_ascii_lower() == ~"important" => {
+            important = true;
+            value = value.slice_to(bang).trim().to_owned();
+        }
+        _ => {}
+    }
+    if name.is_empty() || value.is_empty() {
+        return None
+    }
+    Some(Declaration {
+        name: name,
+        value: value,
+        important: important,
+    })
+}
+
+impl CacheableWrapper for CSSStyleSheet {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe {
+            cast::transmute(&self.wrapper)
+        }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        CSSStyleSheetBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for CSSStyleSheet {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
@@ -5,19 +5,34 @@
 use dom::bindings::codegen::DocumentBinding;
 use dom::bindings::utils::{DOMString, WrapperCache, ErrorResult, null_string, str};
 use dom::bindings::utils::{BindingObject, CacheableWrapper, rust_box, DerivedWrapper};
+use dom::cssstylesheet::CSSStyleSheet;
+use dom::documenttype::{QuirksMode, NoQuirks, LimitedQuirks, FullQuirks};
+use dom::editing::UndoManager;
 use dom::element::{Element};
 use dom::element::{HTMLHtmlElementTypeId, HTMLHeadElementTypeId, HTMLTitleElementTypeId};
+use dom::element::HTMLAnchorElementTypeId;
+use dom::element::{ElementState, ElementStateFlag, HoverState, ActiveState, FocusState};
 use dom::event::Event;
+use dom::eventdispatcher::fire_event;
 use dom::htmlcollection::HTMLCollection;
 use dom::htmldocument::HTMLDocument;
 use dom::htmlelement::HTMLElement;
 use dom::htmlhtmlelement::HTMLHtmlElement;
 use dom::node::{AbstractNode, ScriptView, Node, ElementNodeTypeId};
+use dom::range::Range;
+use dom::stylesheetlist::StyleSheetList;
 use dom::text::Text;
 use dom::window::Window;
 use dom::windowproxy::WindowProxy;
 use dom::htmltitleelement::HTMLTitleElement;
-
+use html::cssparse::StylesheetSource;
+use style::invalidation::{InvalidationSet, StyleDependencies};
+use style::namespaces::NamespaceMap;
+use style::selector_matching::matches_selector;
+use style::selectors::parse_selector_list;
+
+use cssparser::tokenize;
+use gfx::opts::{AutoplayPolicy, AutoplayAfterActivation};
 use js::jsapi::{JS_AddObjectRoot, JS_RemoveObjectRoot, JSObject, JSContext, JSVal};
 use js::glue::RUST_OBJECT_TO_JSVAL;
 use servo_util::tree::TreeNodeRef;
@@ -25,6 +40,7 @@ use servo_util::tree::TreeNodeRef;
 use std::cast;
 use std::ptr;
 use std::str::eq_slice;
+use std::util::replace;
 
 pub trait WrappableDocument {
     fn init_wrapper(@mut self, cx: *JSContext);
@@ -72,6 +88,14 @@ impl AbstractDocument {
     }
 }
 
+/// Where the markup that `document.write` writes goes.
+pub enum InsertionPoint {
+    /// After the script element that the parser is running.
+    AfterParserScript(AbstractNode<ScriptView>),
+    /// In place of the contents of the document, which script has opened.
+    ScriptCreatedParser,
+}
+
 pub enum DocumentType {
     HTML,
     SVG,
@@ -83,7 +107,44 @@ pub struct Document {
     wrapper: WrapperCache,
     window: Option<@mut Window>,
     doctype: DocumentType,
-    title: ~str
+    title: ~str,
+    /// The default language of the document, from the `Content-Language` pragma.
+    content_language: Option<~str>,
+    /// The element that has the focus, if any.
+    focused: Option<AbstractNode<ScriptView>>,
+    /// The innermost element the pointer is over, if any.
+    hovered: Option<AbstractNode<ScriptView>>,
+    /// The innermost element being activated, if any.
+    active: Option<AbstractNode<ScriptView>>,
+    /// The elements whose states that the dynamic pseudo-classes match have changed since the
+    /// last reflow, with the states that changed.
+    state_changes: ~[(AbstractNode<ScriptView>, ElementState)],
+    /// The undo managers of the editing hosts that have been edited.
+    ///
+    /// FIXME: These are kept after their editing hosts are removed from the document.
+    undo_managers: ~[(AbstractNode<ScriptView>, UndoManager)],
+    /// Whether the user has interacted with the document, by clicking or pressing a key in it.
+    /// Once set, it stays set, and lets media in the document play.
+    user_activated: bool,
+    /// The style sheets of the document, in the order they apply.
+    style_sheets: ~[@mut CSSStyleSheet],
+    /// The selectors of the style sheets that apply to every page, at the user agent and user
+    /// origins, before those of the document.
+    default_style_selectors: @~[~str],
+    /// What the selectors of the style sheets need restyled when a class, an ID or an attribute
+    /// changes, if it has been worked out since the sheets last changed.
+    style_dependencies: @mut Option<StyleDependencies>,
+    /// The mode of the document, which the parser decides from its doctype.
+    quirks_mode: QuirksMode,
+    /// A counter that goes up whenever script changes the document, which the live collections of
+    /// the document share to know when to look for their elements again.
+    dom_version: @mut uint,
+    /// The ranges that script has made in the document, whose boundary points move as the tree
+    /// changes. Each range leaves them once the garbage collector finalizes its wrapper.
+    ranges: @mut ~[@mut Range],
+    /// The insertion point of `document.write`, if the document is open for writing, with the
+    /// markup written there since it opened.
+    insertion_point: @mut Option<(InsertionPoint, ~str)>,
 }
 
 impl Document {
@@ -102,7 +163,21 @@ impl Document {
             wrapper: WrapperCache::new(),
             window: window,
             doctype: doctype,
-            title: ~""
+            title: ~"",
+            content_language: None,
+            focused: None,
+            hovered: None,
+            active: None,
+            state_changes: ~[],
+            undo_managers: ~[],
+            user_activated: false,
+            style_sheets: ~[],
+            default_style_selectors: @~[],
+            style_dependencies: @mut None,
+            quirks_mode: NoQuirks,
+            dom_version: @mut 0,
+            ranges: @mut ~[],
+            insertion_point: @mut None,
         }
     }
 
@@ -198,7 +273,10 @@ impl Document {
     }
 
     pub fn CompatMode(&self) -> DOMString {
-        null_string
+        match self.quirks_mode {
+            FullQuirks => str(~"BackCompat"),
+            NoQuirks | LimitedQuirks => str(~"CSS1Compat"),
+        }
     }
 
     pub fn CharacterSet(&self) -> DOMString {
@@ -213,7 +291,7 @@ impl Document {
         Some(self.root)
     }
 
-    fn get_scope_and_cx(&self) -> (*JSObject, *JSContext) {
+    pub fn get_scope_and_cx(&self) -> (*JSObject, *JSContext) {
         let win = self.window.get_ref();
         let cx = unsafe {(*win.page).js_info.get_ref().js_compartment.cx.ptr};
         let cache = win.get_wrappercache();
@@ -222,17 +300,18 @@ impl Document {
     }
 
     pub fn GetElementsByTagName(&self, tag: &DOMString) -> @mut HTMLCollection {
-        self.createHTMLCollection(|elem| eq_slice(elem.tag_name, tag.to_str()))
+        let tag = tag.to_str();
+        self.createHTMLCollection(|elem| eq_slice(elem.tag_name, tag))
     }
 
     pub fn GetElementsByTagNameNS(&self, _ns: &DOMString, _tag: &DOMString) -> @mut HTMLCollection {
         let (scope, cx) = self.get_scope_and_cx();
-        HTMLCollection::new(~[], cx, scope)
+        HTMLCollection::empty(cx, scope)
     }
 
-    pub fn GetElementsByClassName(&self, _class: &DOMString) -> @mut HTMLCollection {
-        let (scope, cx) = self.get_scope_and_cx();
-        HTMLCollection::new(~[], cx, scope)
+    pub fn GetElementsByClassName(&self, class: &DOMString) -> @mut HTMLCollection {
+        let names = class.to_str();
+        self.createHTMLCollection(|elem| elem.has_class_names(names))
     }
 
     pub fn GetElementById(&self, _id: &DOMString) -> Option<AbstractNode<ScriptView>> {
@@ -247,6 +326,13 @@ impl Document {
         fail!("stub")
     }
 
+    /// FIXME: The document isn't a node, so new ranges start at the start of its root element
+    /// rather than of the document.
+    pub fn CreateRange(&self, _rv: &mut ErrorResult) -> @mut Range {
+        let document = self.root.with_base(|base| base.owner_doc.unwrap());
+        Range::new(document, self.root, 0, self.root, 0)
+    }
+
     pub fn CreateEvent(&self, _interface: &DOMString, _rv: &mut ErrorResult) -> @mut Event {
         fail!("stub")
     }
@@ -320,6 +406,7 @@ impl Document {
                             Node::as_abstract_node(cx, @Text::new(title.to_str())) 
                         };
                         child.add_child(new_text);
+                        child.dirty();
                         break;
                     }
                     if !has_title {
@@ -334,9 +421,11 @@ impl Document {
                         };
                         new_title.add_child(new_text);
                         node.add_child(new_title);
+                        node.dirty();
                     }
                     break;
                 };
+                self.content_changed();
             }
         }
     }
@@ -353,7 +442,7 @@ impl Document {
     }
 
     pub fn GetActiveElement(&self) -> Option<AbstractNode<ScriptView>> {
-        None
+        self.focused
     }
 
     pub fn HasFocus(&self, _rv: &mut ErrorResult) -> bool {
@@ -409,6 +498,12 @@ impl Document {
         null_string
     }
 
+    /// FIXME: CSSOM has this return the same live list each time.
+    pub fn StyleSheets(&self) -> @mut StyleSheetList {
+        let (scope, cx) = self.get_scope_and_cx();
+        StyleSheetList::new(self.style_sheets.clone(), cx, scope)
+    }
+
     pub fn GetPreferredStyleSheetSet(&self) -> DOMString {
         null_string
     }
@@ -425,31 +520,418 @@ impl Document {
     }
 
     pub fn GetElementsByName(&self, name: &DOMString) -> @mut HTMLCollection {
+        let name = name.to_str();
         self.createHTMLCollection(|elem|
-            elem.get_attr("name").is_some() && eq_slice(elem.get_attr("name").unwrap(), name.to_str()))
+            elem.get_attr("name").is_some() && eq_slice(elem.get_attr("name").unwrap(), name))
     }
     
-    pub fn createHTMLCollection(&self, callback: &fn(elem: &Element) -> bool) -> @mut HTMLCollection {
-        let mut elements = ~[];
-        let _ = for child in self.root.traverse_preorder() {
-            if child.is_element() {
-                do child.with_imm_element |elem| {
-                    if callback(elem) {
-                        elements.push(child);
-                    }
-                }
-            }
-        };
+    /// Creates a live collection of the elements of the document that `callback` accepts.
+    pub fn createHTMLCollection(&self, callback: ~fn(elem: &Element) -> bool)
+                                -> @mut HTMLCollection {
         let (scope, cx) = self.get_scope_and_cx();
-        HTMLCollection::new(elements, cx, scope)
+        HTMLCollection::new(self.root, true, callback, Some(self.dom_version), cx, scope)
     }
 
+    /// Restyles the nodes that script has marked dirty and lays out the document again.
     pub fn content_changed(&self) {
+        *self.dom_version += 1;
         for window in self.window.iter() {
             window.content_changed()
         }
     }
 
+    /// Registers a range, so that its boundary points move as the tree changes.
+    pub fn add_range(&self, range: @mut Range) {
+        self.ranges.push(range)
+    }
+
+    /// Moves the boundary points of the ranges of the document out of `node`, which is about to
+    /// be removed from `parent`, where it is the child at `index`.
+    pub fn node_removed(&self,
+                        node: AbstractNode<ScriptView>,
+                        parent: AbstractNode<ScriptView>,
+                        index: uint) {
+        for range in self.ranges.iter() {
+            range.node_removed(node, parent, index)
+        }
+    }
+
+    /// Moves the boundary points of the ranges of the document past `count` children that have
+    /// been inserted into `parent` from `index` on.
+    pub fn children_inserted(&self, parent: AbstractNode<ScriptView>, index: uint, count: uint) {
+        for range in self.ranges.iter() {
+            range.children_inserted(parent, index, count)
+        }
+    }
+
+    /// Moves the boundary points of the ranges of the document that are in the data of `node`,
+    /// where `count` code units from `offset` have been replaced with `length` others.
+    pub fn data_replaced(&self,
+                         node: AbstractNode<ScriptView>,
+                         offset: uint,
+                         count: uint,
+                         length: uint) {
+        for range in self.ranges.iter() {
+            range.data_replaced(node, offset, count, length)
+        }
+    }
+
+    /// Moves the boundary points of the ranges of the document that are past `offset` in the text
+    /// node `node` into `new_node`, which the data after `offset` has been split off into and
+    /// which has been inserted after `node`, and the points right after `node` past `new_node`.
+    pub fn text_split(&self,
+                      node: AbstractNode<ScriptView>,
+                      offset: uint,
+                      new_node: AbstractNode<ScriptView>) {
+        for range in self.ranges.iter() {
+            range.text_split(node, offset, new_node)
+        }
+    }
+
+    /// Adds a style sheet that has loaded after the others of the document.
+    pub fn add_style_sheet(&mut self, source: StylesheetSource) {
+        let sheet = CSSStyleSheet::new(*self.window.get_ref(), source);
+        self.style_sheets.push(sheet);
+        *self.style_dependencies = None;
+    }
+
+    /// Sets the selectors of the style sheets that apply to every page, after one has been added
+    /// to them.
+    pub fn set_default_style_selectors(&mut self, selectors: @~[~str]) {
+        self.default_style_selectors = selectors;
+        *self.style_dependencies = None;
+    }
+
+    /// Sends the style sheets to layout again after script has edited the rules with the given
+    /// selectors, and restyles the elements the selectors match rather than the whole document,
+    /// unless one of them can't be parsed.
+    pub fn style_sheets_changed(&self, selectors: &[~str]) {
+        let sheets = self.style_sheets.iter().map(|sheet| sheet.parse_for_layout()).collect();
+        *self.style_dependencies = None;
+        let restyled = self.elements_matching(selectors);
+        match self.window {
+            Some(window) => window.style_sheets_changed(sheets, restyled),
+            None => {}
+        }
+    }
+
+    /// Returns the elements that any of the given selectors match, or `None` if one of them can't
+    /// be parsed.
+    ///
+    /// FIXME: The prefixes that `@namespace` rules declare aren't known here.
+    fn elements_matching(&self, selectors: &[~str]) -> Option<~[AbstractNode<ScriptView>]> {
+        let namespaces = NamespaceMap::new();
+        let mut parsed = ~[];
+        for text in selectors.iter() {
+            let input = tokenize(text.as_slice()).map(|(value, _)| value).collect();
+            match parse_selector_list(input, &namespaces) {
+                Some(selectors) => parsed.push_all_move(selectors),
+                None => return None,
+            }
+        }
+        Some(do self.root.traverse_preorder().filter |&node| {
+            node.is_element() && parsed.iter().any(|selector| matches_selector(selector, node))
+        }.collect())
+    }
+
+    /// Marks for restyling the elements whose style could change now that the attribute `name` of
+    /// `element` has changed from `old` to `new`, as far as the selectors of the style sheets that
+    /// apply to the document tell, rather than the whole subtree of the element.
+    pub fn attribute_changed(&self,
+                             element: AbstractNode<ScriptView>,
+                             name: &str,
+                             old: Option<&str>,
+                             new: &str) {
+        let set = do self.with_style_dependencies |dependencies| {
+            match name {
+                "class" => dependencies.class_changed(old.unwrap_or(""), new),
+                "id" => dependencies.id_changed(old.unwrap_or(""), new),
+                // The generated content of the element can show the value of any of its
+                // attributes with `attr()`, so it is restyled whenever one of them changes.
+                _ => do dependencies.attribute_changed(name).map_move |set| {
+                    let mut set = set;
+                    set.element = true;
+                    set
+                },
+            }
+        };
+        match set {
+            Some(set) => restyle_invalidated(element, &set),
+            None => {
+                // Without knowing what the selectors test, the element and whatever sibling
+                // selectors might match after it are restyled.
+                let mut current = Some(element);
+                while current.is_some() {
+                    let node = current.unwrap();
+                    node.dirty();
+                    current = node.next_sibling();
+                }
+            }
+        }
+        self.content_changed()
+    }
+
+    /// Marks for restyling what could change now that `inserted` have been inserted among the
+    /// children of `parent`, or children have been removed from it. Inserting or removing a child
+    /// moves its siblings, which structural pseudo-classes and sibling combinators match by their
+    /// positions, so the whole parent is restyled if the selectors of the style sheets test them,
+    /// and only the inserted children otherwise.
+    pub fn children_changed(&self,
+                            parent: AbstractNode<ScriptView>,
+                            inserted: &[AbstractNode<ScriptView>]) {
+        if self.with_style_dependencies(|dependencies| dependencies.depend_on_sibling_positions()) {
+            parent.dirty()
+        } else {
+            for node in inserted.iter() {
+                node.dirty()
+            }
+        }
+        self.content_changed()
+    }
+
+    /// Calls `f` with what the selectors of the style sheets that apply to the document need
+    /// restyled when a class, an ID or an attribute changes, working it out again if the sheets
+    /// of the document have changed.
+    fn with_style_dependencies<R>(&self, f: &fn(&StyleDependencies) -> R) -> R {
+        if self.style_dependencies.is_none() {
+            let mut dependencies = StyleDependencies::new();
+            add_style_dependencies(&mut dependencies, *self.default_style_selectors);
+            for sheet in self.style_sheets.iter() {
+                add_style_dependencies(&mut dependencies, sheet.selectors());
+            }
+            *self.style_dependencies = Some(dependencies);
+        }
+        f(self.style_dependencies.get_ref())
+    }
+
+    /// Makes the point after `script`, which the parser is about to run, the insertion point of
+    /// `document.write`. Returns the insertion point that it replaces, as the markup that a script
+    /// writes can hold scripts that run in turn.
+    pub fn enter_parser_script(&self,
+                               script: AbstractNode<ScriptView>)
+                               -> Option<(InsertionPoint, ~str)> {
+        replace(&mut *self.insertion_point, Some((AfterParserScript(script), ~"")))
+    }
+
+    /// Puts back the insertion point from before the parser ran a script, and returns the markup
+    /// that the script wrote.
+    pub fn leave_parser_script(&self, previous: Option<(InsertionPoint, ~str)>) -> ~str {
+        match replace(&mut *self.insertion_point, previous) {
+            Some((_, markup)) => markup,
+            None => ~"",
+        }
+    }
+
+    /// Moves the focus to `element`, or clears it if `element` is `None`. Restyles the elements
+    /// that gain or lose the focus, so that the focus ring moves too, and fires `blur` at the
+    /// element that loses it and `focus` at the one that gains it.
+    pub fn set_focus(&mut self, element: Option<AbstractNode<ScriptView>>) {
+        if self.focused != element {
+            let old = replace(&mut self.focused, element);
+            for &old in old.iter() {
+                self.state_changes.push((old, ElementState::none().add(FocusState)))
+            }
+            for &element in element.iter() {
+                self.state_changes.push((element, ElementState::none().add(FocusState)))
+            }
+            self.element_states_changed();
+
+            for &window in self.window.iter() {
+                for &old in old.iter() {
+                    fire_event(window, old, "blur", false, false);
+                }
+                for &element in element.iter() {
+                    fire_event(window, element, "focus", false, false);
+                }
+            }
+        }
+    }
+
+    /// Notes that the pointer is now over `element`, or outside the document if `element` is
+    /// `None`, and restyles the elements that `:hover` starts or stops matching.
+    pub fn set_hover(&mut self, element: Option<AbstractNode<ScriptView>>) {
+        if self.hovered != element {
+            let old = replace(&mut self.hovered, element);
+            self.note_chain_state_change(old, element, HoverState);
+            self.element_states_changed();
+        }
+    }
+
+    /// Notes that `element` is now being activated, or that nothing is if `element` is `None`,
+    /// and restyles the elements that `:active` starts or stops matching.
+    pub fn set_active(&mut self, element: Option<AbstractNode<ScriptView>>) {
+        if self.active != element {
+            let old = replace(&mut self.active, element);
+            self.note_chain_state_change(old, element, ActiveState);
+            self.element_states_changed();
+        }
+    }
+
+    /// Returns the elements whose states have changed since the last call, with the states that
+    /// changed.
+    pub fn take_state_changes(&mut self) -> ~[(AbstractNode<ScriptView>, ElementState)] {
+        replace(&mut self.state_changes, ~[])
+    }
+
+    /// Notes the change of a state that matches an element and its ancestors, when the element
+    /// it starts from moves from `old` to `new`. Only the elements that one of them matches and
+    /// the other doesn't change: the common ancestors keep the state.
+    fn note_chain_state_change(&mut self,
+                               old: Option<AbstractNode<ScriptView>>,
+                               new: Option<AbstractNode<ScriptView>>,
+                               flag: ElementStateFlag) {
+        let old_chain = inclusive_ancestors(old);
+        let new_chain = inclusive_ancestors(new);
+        for &node in old_chain.iter().filter(|node| !new_chain.contains(*node)) {
+            self.state_changes.push((node, ElementState::none().add(flag)))
+        }
+        for &node in new_chain.iter().filter(|node| !old_chain.contains(*node)) {
+            self.state_changes.push((node, ElementState::none().add(flag)))
+        }
+    }
+
+    fn element_states_changed(&self) {
+        for window in self.window.iter() {
+            window.element_states_changed()
+        }
+    }
+
+    /// Returns the undo manager of the editing host `host`, creating it if the host hasn't been
+    /// edited before.
+    pub fn undo_manager<'a>(&'a mut self, host: AbstractNode<ScriptView>) -> &'a mut UndoManager {
+        let index = match self.undo_managers.iter().position(|&(ref node, _)| *node == host) {
+            Some(index) => index,
+            None => {
+                self.undo_managers.push((host, UndoManager::new()));
+                self.undo_managers.len() - 1
+            }
+        };
+        let (_, ref mut undo_manager) = self.undo_managers[index];
+        undo_manager
+    }
+
+    /// Returns the elements that sequential focus navigation visits, in the order it visits them.
+    pub fn sequential_focus_order(&self) -> ~[AbstractNode<ScriptView>] {
+        let mut positive: ~[(i32, AbstractNode<ScriptView>)] = ~[];
+        let mut zero = ~[];
+        for node in self.root.traverse_preorder() {
+            match node.sequential_focus_index() {
+                None => {}
+                Some(0) => zero.push(node),
+                Some(index) => {
+                    // Keep elements with the same index in tree order.
+                    let position = positive.iter().position(|&(other, _)| other > index);
+                    positive.insert(position.unwrap_or_default(positive.len()), (index, node));
+                }
+            }
+        }
+        let mut order: ~[AbstractNode<ScriptView>] =
+            positive.iter().map(|&(_, node)| node).collect();
+        order.push_all(zero);
+        order
+    }
+
+    /// Moves the focus to the next element in sequential focus navigation order, or the previous
+    /// one if `forward` is false. The order wraps around at either end. Without a focused
+    /// element, navigation starts from the first or last element.
+    pub fn focus_next(&mut self, forward: bool) {
+        let order = self.sequential_focus_order();
+        if order.is_empty() {
+            return
+        }
+        let current = match self.focused {
+            Some(focused) => order.iter().position(|&node| node == focused),
+            None => None,
+        };
+        let next = match (current, forward) {
+            (Some(i), true) => (i + 1) % order.len(),
+            (Some(i), false) => (i + order.len() - 1) % order.len(),
+            (None, true) => 0,
+            (None, false) => order.len() - 1,
+        };
+        self.set_focus(Some(order[next]));
+    }
+
+    /// Returns when media in the document may start playing, which the page is configured with.
+    pub fn autoplay_policy(&self) -> AutoplayPolicy {
+        match self.window {
+            Some(window) => unsafe { (*window.page).autoplay_policy },
+            None => AutoplayAfterActivation,
+        }
+    }
+
+    /// Starts playing the media elements with the `autoplay` attribute that the autoplay policy
+    /// lets play, once the document has been parsed.
+    pub fn autoplay_media_elements(&self) {
+        for node in self.root.traverse_preorder() {
+            if node.is_media_element() {
+                node.with_mut_media_element(|media| media.autoplay_if_allowed())
+            }
+        }
+    }
+
+    /// Focuses the first focusable element with the `autofocus` attribute, once the document
+    /// has been parsed.
+    pub fn focus_autofocus_element(&mut self) {
+        for node in self.root.traverse_preorder() {
+            if node.is_focusable() &&
+                    node.with_imm_element(|element| element.get_attr("autofocus").is_some()) {
+                self.set_focus(Some(node));
+                return
+            }
+        }
+    }
+
+    /// Returns the element that a URL fragment indicates: the first element with the fragment as
+    /// its ID, or else the first `a` element with it as its name.
+    pub fn fragment_target(&self, fragment: &str) -> Option<AbstractNode<ScriptView>> {
+        let mut named_anchor = None;
+        for node in self.root.traverse_preorder() {
+            if !node.is_element() {
+                loop
+            }
+            let (id_matches, name_matches) = do node.with_imm_element |element| {
+                (element.get_attr("id") == Some(fragment),
+                 element.get_attr("name") == Some(fragment))
+            };
+            if id_matches {
+                return Some(node)
+            }
+            if named_anchor.is_none() && name_matches &&
+                    node.type_id() == ElementNodeTypeId(HTMLAnchorElementTypeId) {
+                named_anchor = Some(node)
+            }
+        }
+        named_anchor
+    }
+
+    /// Returns the keys assigned to elements with `accesskey`, in tree order and without
+    /// duplicates, for the embedder to listen for.
+    pub fn access_keys(&self) -> ~[char] {
+        let mut keys = ~[];
+        for node in self.root.traverse_preorder() {
+            if !node.is_element() {
+                loop
+            }
+            match node.with_imm_element(|element| element.access_key()) {
+                Some(key) if !keys.contains(&key) => keys.push(key),
+                _ => {}
+            }
+        }
+        keys
+    }
+
+    /// Returns the first element in tree order that `key` is assigned to.
+    pub fn element_for_access_key(&self, key: char) -> Option<AbstractNode<ScriptView>> {
+        for node in self.root.traverse_preorder() {
+            if node.is_element() &&
+                    node.with_imm_element(|element| element.access_key()) == Some(key) {
+                return Some(node)
+            }
+        }
+        None
+    }
+
     #[fixed_stack_segment]
     pub fn teardown(&self) {
         unsafe {
@@ -463,3 +945,57 @@ impl Document {
     }
 }
 
+
+/// Returns `node` and its ancestors, innermost first.
+fn inclusive_ancestors(node: Option<AbstractNode<ScriptView>>) -> ~[AbstractNode<ScriptView>] {
+    let mut nodes = ~[];
+    let mut current = node;
+    while current.is_some() {
+        let node = current.unwrap();
+        nodes.push(node);
+        current = node.parent_node();
+    }
+    nodes
+}
+
+/// Adds what the given selectors need restyled to `dependencies`.
+///
+/// FIXME: The prefixes that `@namespace` rules declare aren't known here.
+fn add_style_dependencies(dependencies: &mut StyleDependencies, selectors: &[~str]) {
+    let namespaces = NamespaceMap::new();
+    for text in selectors.iter() {
+        let input = tokenize(text.as_slice()).map(|(value, _)| value).collect();
+        match parse_selector_list(input, &namespaces) {
+            Some(selectors) => {
+                for selector in selectors.iter() {
+                    dependencies.add_selector(selector)
+                }
+            }
+            None => dependencies.add_unknown_selector(),
+        }
+    }
+}
+
+/// Marks the elements that an invalidation set needs restyled, relative to `element`, dirty.
+/// Marking a node dirty restyles its whole subtree, so the descendants of `element` are searched
+/// only if it isn't marked itself.
+fn restyle_invalidated(element: AbstractNode<ScriptView>, set: &InvalidationSet) {
+    if set.element || set.all_descendants {
+        element.dirty()
+    } else if !set.descendants.is_empty() {
+        for node in element.traverse_preorder() {
+            if node != element && node.is_element() &&
+                    set.descendants.iter().any(|feature| feature.matches(node)) {
+                node.dirty()
+            }
+        }
+    }
+    if set.later_siblings {
+        let mut sibling = element.next_sibling();
+        while sibling.is_some() {
+            let node = sibling.unwrap();
+            node.dirty();
+            sibling = node.next_sibling();
+        }
+    }
+}
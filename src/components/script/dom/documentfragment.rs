@@ -0,0 +1,27 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::utils::ErrorResult;
+use dom::node::{AbstractNode, DocumentFragmentNodeTypeId, Node, ScriptView};
+use dom::window::Window;
+
+/// A node that holds other nodes outside of the tree of a document, such as the contents that
+/// ranges take out of it. Inserting a fragment inserts its children instead.
+pub struct DocumentFragment {
+    parent: Node<ScriptView>,
+}
+
+impl DocumentFragment {
+    /// Creates a new, empty document fragment.
+    pub fn new() -> DocumentFragment {
+        DocumentFragment {
+            parent: Node::new(DocumentFragmentNodeTypeId)
+        }
+    }
+
+    pub fn Constructor(owner: @mut Window, _rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
+        let cx = unsafe {(*owner.page).js_info.get_ref().js_compartment.cx.ptr};
+        unsafe { Node::as_abstract_node(cx, @DocumentFragment::new()) }
+    }
+}
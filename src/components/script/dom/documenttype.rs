@@ -5,6 +5,78 @@
 use dom::bindings::utils::{DOMString, str, null_string};
 use dom::node::{ScriptView, Node, DoctypeNodeTypeId};
 
+use std::ascii::StrAsciiExt;
+
+/// The mode of a document, which decides which of the behaviors of older browsers that legacy
+/// pages rely on layout emulates.
+#[deriving(Eq, Clone)]
+pub enum QuirksMode {
+    /// Standards mode.
+    NoQuirks,
+    /// Almost standards mode, which only keeps the line height calculation quirk.
+    LimitedQuirks,
+    FullQuirks,
+}
+
+/// The public identifiers of doctypes that put documents in quirks mode, up to what follows them.
+static QUIRKS_PUBLIC_ID_PREFIXES: &'static [&'static str] = &[
+    "+//silmaril//dtd html pro v0r11 19970101//",
+    "-//as//dtd html 3.0 aswedit + extensions//",
+    "-//advasoft ltd//dtd html 3.0 aswedit + extensions//",
+    "-//ietf//dtd html 2.0 level 1//",
+    "-//ietf//dtd html 2.0 level 2//",
+    "-//ietf//dtd html 2.0 strict level 1//",
+    "-//ietf//dtd html 2.0 strict level 2//",
+    "-//ietf//dtd html 2.0 strict//",
+    "-//ietf//dtd html 2.0//",
+    "-//ietf//dtd html 2.1e//",
+    "-//ietf//dtd html 3.0//",
+    "-//ietf//dtd html 3.2 final//",
+    "-//ietf//dtd html 3.2//",
+    "-//ietf//dtd html 3//",
+    "-//ietf//dtd html level 0//",
+    "-//ietf//dtd html level 1//",
+    "-//ietf//dtd html level 2//",
+    "-//ietf//dtd html level 3//",
+    "-//ietf//dtd html strict level 0//",
+    "-//ietf//dtd html strict level 1//",
+    "-//ietf//dtd html strict level 2//",
+    "-//ietf//dtd html strict level 3//",
+    "-//ietf//dtd html strict//",
+    "-//ietf//dtd html//",
+    "-//metrius//dtd metrius presentational//",
+    "-//microsoft//dtd internet explorer 2.0 html strict//",
+    "-//microsoft//dtd internet explorer 2.0 html//",
+    "-//microsoft//dtd internet explorer 2.0 tables//",
+    "-//microsoft//dtd internet explorer 3.0 html strict//",
+    "-//microsoft//dtd internet explorer 3.0 html//",
+    "-//microsoft//dtd internet explorer 3.0 tables//",
+    "-//netscape comm. corp.//dtd html//",
+    "-//netscape comm. corp.//dtd strict html//",
+    "-//o'reilly and associates//dtd html 2.0//",
+    "-//o'reilly and associates//dtd html extended 1.0//",
+    "-//o'reilly and associates//dtd html extended relaxed 1.0//",
+    "-//sq//dtd html 2.0 hotmetal + extensions//",
+    "-//softquad software//dtd hotmetal pro 6.0::19990601::extensions to html 4.0//",
+    "-//softquad//dtd hotmetal pro 4.0::19971010::extensions to html 4.0//",
+    "-//spyglass//dtd html 2.0 extended//",
+    "-//sun microsystems corp.//dtd hotjava html//",
+    "-//sun microsystems corp.//dtd hotjava strict html//",
+    "-//w3c//dtd html 3 1995-03-24//",
+    "-//w3c//dtd html 3.2 draft//",
+    "-//w3c//dtd html 3.2 final//",
+    "-//w3c//dtd html 3.2//",
+    "-//w3c//dtd html 3.2s draft//",
+    "-//w3c//dtd html 4.0 frameset//",
+    "-//w3c//dtd html 4.0 transitional//",
+    "-//w3c//dtd html experimental 19960712//",
+    "-//w3c//dtd html experimental 970421//",
+    "-//w3c//dtd w3 html//",
+    "-//w3o//dtd w3 html 3.0//",
+    "-//webtechs//dtd mozilla html 2.0//",
+    "-//webtechs//dtd mozilla html//",
+];
+
 /// The `DOCTYPE` tag.
 pub struct DocumentType<View> {
     parent: Node<View>,
@@ -30,6 +102,30 @@ impl DocumentType<ScriptView> {
         }
     }
 
+    /// Returns the mode that this doctype puts the document in, as the HTML parser decides it.
+    pub fn quirks_mode(&self) -> QuirksMode {
+        let public_id = self.public_id.as_ref().map_default(~"", |id| id.to_ascii_lower());
+        let system_id = self.system_id.as_ref().map(|id| id.to_ascii_lower());
+        let html_401 = public_id.starts_with("-//w3c//dtd html 4.01 frameset//") ||
+            public_id.starts_with("-//w3c//dtd html 4.01 transitional//");
+
+        if self.force_quirks || self.name.to_ascii_lower() != ~"html" ||
+                public_id == ~"-//w3o//dtd w3 html strict 3.0//en//" ||
+                public_id == ~"-/w3c/dtd html 4.0 transitional/en" ||
+                public_id == ~"html" ||
+                system_id == Some(~"http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd") ||
+                QUIRKS_PUBLIC_ID_PREFIXES.iter().any(|prefix| public_id.starts_with(*prefix)) ||
+                (system_id.is_none() && html_401) {
+            FullQuirks
+        } else if public_id.starts_with("-//w3c//dtd xhtml 1.0 frameset//") ||
+                public_id.starts_with("-//w3c//dtd xhtml 1.0 transitional//") ||
+                (system_id.is_some() && html_401) {
+            LimitedQuirks
+        } else {
+            NoQuirks
+        }
+    }
+
     pub fn Name(&self) -> DOMString {
         str(self.name.clone())
     }
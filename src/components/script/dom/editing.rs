@@ -0,0 +1,269 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Editing of text inputs, `textarea`s and `contenteditable` elements, and the undo history of
+//! each editing host.
+//!
+//! Every edit is recorded as a transaction with the undo manager of its editing host: the text
+//! control itself, or the outermost editable element around the edited content. Undoing a
+//! transaction puts back the text it changed, and redoing it changes the text again.
+//!
+//! FIXME: There is no selection yet, so text is always inserted and deleted at the end of the
+//! editing host. Nothing sends typed or composed text yet either, since the windows don't report
+//! text input, so the only edits come from `execCommand`.
+
+use dom::bindings::utils::str;
+use dom::document::Document;
+use dom::node::{AbstractNode, Node, ScriptView};
+use dom::text::Text;
+
+use js::jsapi::JSContext;
+use servo_util::tree::TreeNodeRef;
+
+/// The number of transactions an undo manager remembers. Older transactions are forgotten.
+static MAX_TRANSACTIONS: uint = 100;
+
+/// What made a transaction. Typing and composition that continue a transaction of the same kind
+/// are merged into it, so that a burst of typing is undone at once.
+#[deriving(Eq)]
+pub enum TransactionKind {
+    /// Text typed by the user.
+    TypingTransaction,
+    /// Text committed by an input method.
+    CompositionTransaction,
+    /// A command run by `execCommand`.
+    CommandTransaction,
+}
+
+/// A change to the text of a node: the value of a text input, or the data of a text node.
+struct TextChange {
+    node: AbstractNode<ScriptView>,
+    old_text: ~str,
+    new_text: ~str,
+}
+
+struct Transaction {
+    kind: TransactionKind,
+    changes: ~[TextChange],
+}
+
+/// The undo and redo stacks of an editing host.
+pub struct UndoManager {
+    priv undo_stack: ~[Transaction],
+    priv redo_stack: ~[Transaction],
+}
+
+impl UndoManager {
+    pub fn new() -> UndoManager {
+        UndoManager {
+            undo_stack: ~[],
+            redo_stack: ~[],
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Records a change that has been made. A new edit can't be redone after, so this also
+    /// forgets the transactions that were undone.
+    fn record(&mut self, kind: TransactionKind, change: TextChange) {
+        self.redo_stack = ~[];
+        if kind != CommandTransaction && !self.undo_stack.is_empty() {
+            let last_index = self.undo_stack.len() - 1;
+            let last = &mut self.undo_stack[last_index];
+            if last.kind == kind && last.changes.len() == 1 && last.changes[0].node == change.node {
+                last.changes[0].new_text = change.new_text;
+                return
+            }
+        }
+        self.undo_stack.push(Transaction {
+            kind: kind,
+            changes: ~[change],
+        });
+        if self.undo_stack.len() > MAX_TRANSACTIONS {
+            self.undo_stack.shift();
+        }
+    }
+
+    /// Takes back the last transaction. Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if self.undo_stack.is_empty() {
+            return false
+        }
+        let transaction = self.undo_stack.pop();
+        for change in transaction.changes.rev_iter() {
+            set_text(change.node, change.old_text)
+        }
+        self.redo_stack.push(transaction);
+        true
+    }
+
+    /// Makes the last transaction that was taken back again. Returns false if there was nothing
+    /// to redo.
+    pub fn redo(&mut self) -> bool {
+        if self.redo_stack.is_empty() {
+            return false
+        }
+        let transaction = self.redo_stack.pop();
+        for change in transaction.changes.iter() {
+            set_text(change.node, change.new_text)
+        }
+        self.undo_stack.push(transaction);
+        true
+    }
+}
+
+/// Returns the text that edits change in `node`, a text input or a text node.
+fn get_text(node: AbstractNode<ScriptView>) -> ~str {
+    if node.is_text() {
+        node.with_imm_text(|text| text.parent.data.clone())
+    } else {
+        node.with_imm_input_element(|input| input.value())
+    }
+}
+
+fn set_text(node: AbstractNode<ScriptView>, text: &str) {
+    if node.is_text() {
+        node.with_mut_text(|node| node.parent.data = text.to_owned());
+        node.dirty()
+    } else {
+        do node.with_mut_input_element |input| {
+            input.parent.parent.set_attr(&str(~"value"), &str(text.to_owned()))
+        }
+    }
+}
+
+/// Returns the node whose text edits of `host` change: a text input itself, or else the last text
+/// node in the host, which is created if the host has none.
+fn text_node_for_host(cx: *JSContext, host: AbstractNode<ScriptView>)
+                      -> AbstractNode<ScriptView> {
+    if host.is_input_element() {
+        return host
+    }
+    let mut last_text = None;
+    for node in host.traverse_preorder() {
+        if node.is_text() {
+            last_text = Some(node)
+        }
+    }
+    match last_text {
+        Some(text) => text,
+        None => {
+            let text = unsafe { Node::as_abstract_node(cx, @Text::new(~"")) };
+            host.add_child(text);
+            host.dirty();
+            text
+        }
+    }
+}
+
+/// Returns the editing host of the focused element, if it can be edited.
+pub fn focused_editing_host(document: &Document) -> Option<AbstractNode<ScriptView>> {
+    document.focused.chain(|focused| focused.editing_host())
+}
+
+/// Returns the undo manager of the focused editing host, if it has been edited.
+fn focused_undo_manager<'a>(document: &'a Document) -> Option<&'a UndoManager> {
+    let host = match focused_editing_host(document) {
+        Some(host) => host,
+        None => return None,
+    };
+    let entry = document.undo_managers.iter().find(|&&(ref node, _)| *node == host);
+    entry.map_move(|&(_, ref manager)| manager)
+}
+
+/// Returns whether the focused editing host has a transaction to undo.
+pub fn can_undo(document: &Document) -> bool {
+    focused_undo_manager(document).map_default(false, |manager| manager.can_undo())
+}
+
+/// Returns whether the focused editing host has a transaction that was undone to redo.
+pub fn can_redo(document: &Document) -> bool {
+    focused_undo_manager(document).map_default(false, |manager| manager.can_redo())
+}
+
+/// Changes the text of the focused editing host with `edit`, which is given the text and returns
+/// the new text, and records the change as a transaction of the given kind. Returns false if
+/// nothing that can be edited has the focus.
+fn edit_focused_host(document: &mut Document,
+                     cx: *JSContext,
+                     kind: TransactionKind,
+                     edit: &fn(&str) -> ~str)
+                     -> bool {
+    let host = match focused_editing_host(document) {
+        Some(host) => host,
+        None => return false,
+    };
+    let node = text_node_for_host(cx, host);
+    let old_text = get_text(node);
+    let new_text = edit(old_text);
+    if new_text == old_text {
+        return true
+    }
+    set_text(node, new_text);
+    document.undo_manager(host).record(kind, TextChange {
+        node: node,
+        old_text: old_text,
+        new_text: new_text,
+    });
+    document.content_changed();
+    true
+}
+
+/// Inserts `text` into the focused editing host. Returns false if nothing that can be edited has
+/// the focus.
+pub fn insert_text(document: &mut Document,
+                   cx: *JSContext,
+                   text: &str,
+                   kind: TransactionKind)
+                   -> bool {
+    do edit_focused_host(document, cx, kind) |old_text| {
+        let mut new_text = old_text.to_owned();
+        new_text.push_str(text);
+        new_text
+    }
+}
+
+/// Deletes the character before the insertion point of the focused editing host, as Backspace
+/// does. Returns false if nothing that can be edited has the focus.
+pub fn delete_backward(document: &mut Document, cx: *JSContext) -> bool {
+    do edit_focused_host(document, cx, CommandTransaction) |old_text| {
+        if old_text.is_empty() {
+            old_text.to_owned()
+        } else {
+            old_text.slice_to(old_text.char_range_at_reverse(old_text.len()).next).to_owned()
+        }
+    }
+}
+
+/// Undoes the last transaction of the focused editing host. Returns false if there was nothing
+/// to undo.
+pub fn undo(document: &mut Document) -> bool {
+    let undone = match focused_editing_host(document) {
+        Some(host) => document.undo_manager(host).undo(),
+        None => false,
+    };
+    if undone {
+        document.content_changed()
+    }
+    undone
+}
+
+/// Redoes the last transaction of the focused editing host that was undone. Returns false if
+/// there was nothing to redo.
+pub fn redo(document: &mut Document) -> bool {
+    let redone = match focused_editing_host(document) {
+        Some(host) => document.undo_manager(host).redo(),
+        None => false,
+    };
+    if redone {
+        document.content_changed()
+    }
+    redone
+}
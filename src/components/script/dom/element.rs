@@ -4,15 +4,18 @@
 
 //! Element nodes.
 
-use dom::bindings::utils::{null_string, str};
+use dom::bindings::utils::{FailureUnknown, null_string, str};
 use dom::bindings::utils::{BindingObject, CacheableWrapper, DOMString, ErrorResult, WrapperCache};
 use dom::htmlcollection::HTMLCollection;
 use dom::clientrect::ClientRect;
 use dom::clientrectlist::ClientRectList;
 use dom::node::{ElementNodeTypeId, Node, ScriptView, AbstractNode};
+use html::hubbub_html_parser::parse_html_fragment;
+use html::serializer::{serialize_children, serialize_node};
 use layout_interface::{ContentBoxQuery, ContentBoxResponse, ContentBoxesQuery};
 use layout_interface::{ContentBoxesResponse};
 use newcss::stylesheet::Stylesheet;
+use style::properties::{PropertyDeclarationBlock, parse_style_attribute};
 
 use js::jsapi::{JSContext, JSObject};
 
@@ -27,6 +30,8 @@ pub struct Element {
     tag_name: ~str,     // TODO: This should be an atom, not a ~str.
     attrs: ~[Attr],
     style_attribute: Option<Stylesheet>,
+    /// The declarations of the style attribute as the new style system parses them.
+    style_attribute_declarations: Option<PropertyDeclarationBlock>,
 }
 
 impl CacheableWrapper for Element {
@@ -126,9 +131,22 @@ impl<'self> Element {
             tag_name: tag_name,
             attrs: ~[],
             style_attribute: None,
+            style_attribute_declarations: None,
         }
     }
 
+    /// Returns the value of the attribute with the given namespace URL, or the empty string for
+    /// none, and local name.
+    pub fn get_attr_ns(&'self self, namespace: &str, name: &str) -> Option<&'self str> {
+        for attr in self.attrs.iter() {
+            if eq_slice(attr.namespace, namespace) && eq_slice(attr.name, name) {
+                let val: &str = attr.value;
+                return Some(val);
+            }
+        }
+        return None;
+    }
+
     pub fn get_attr(&'self self, name: &str) -> Option<&'self str> {
         // FIXME: Need an each() that links lifetimes in Rust.
         for attr in self.attrs.iter() {
@@ -140,8 +158,134 @@ impl<'self> Element {
         return None;
     }
 
+    /// Returns the value of the given attribute parsed with the HTML rules for parsing
+    /// non-negative integers: leading whitespace is skipped, and anything after the digits is
+    /// ignored. Returns `None` if the attribute is missing or doesn't start with a number.
+    pub fn get_unsigned_attr(&self, name: &str) -> Option<uint> {
+        let value = match self.get_attr(name) {
+            Some(value) => value.trim_left(),
+            None => return None,
+        };
+        let value = if value.starts_with("+") { value.slice_from(1) } else { value };
+        let digits = match value.find(|c: char| !c.is_digit()) {
+            Some(end) => value.slice_to(end),
+            None => value,
+        };
+        FromStr::from_str(digits)
+    }
+
+    /// Returns the key assigned to the element with the `accesskey` attribute: the first of its
+    /// space-separated tokens that is a single character. The key is lowercased, so that it is
+    /// pressed the same way with or without Shift.
+    pub fn access_key(&self) -> Option<char> {
+        let value = match self.get_attr("accesskey") {
+            Some(value) => value,
+            None => return None,
+        };
+        for token in value.word_iter() {
+            if token.char_len() == 1 {
+                return Some(token.to_ascii_lower().char_at(0))
+            }
+        }
+        None
+    }
+
+    /// Returns whether the `class` attribute of the element has all of the space-separated class
+    /// names in `names`, of which there has to be at least one.
+    pub fn has_class_names(&self, names: &str) -> bool {
+        let classes: ~[&str] = match self.get_attr("class") {
+            Some(value) => value.word_iter().collect(),
+            None => return false,
+        };
+        let mut names = names.word_iter().peekable();
+        names.peek().is_some() && names.all(|name| classes.iter().any(|&class| class == name))
+    }
+
+    /// Returns the value of the given attribute parsed with the HTML rules for parsing integers,
+    /// which are those for non-negative integers with an optional leading `-`.
+    pub fn get_int_attr(&self, name: &str) -> Option<i32> {
+        let value = match self.get_attr(name) {
+            Some(value) => value.trim_left(),
+            None => return None,
+        };
+        let (negative, value) = if value.starts_with("-") {
+            (true, value.slice_from(1))
+        } else if value.starts_with("+") {
+            (false, value.slice_from(1))
+        } else {
+            (false, value)
+        };
+        let digits = match value.find(|c: char| !c.is_digit()) {
+            Some(end) => value.slice_to(end),
+            None => value,
+        };
+        let magnitude: Option<i32> = FromStr::from_str(digits);
+        magnitude.map_move(|magnitude| if negative { -magnitude } else { magnitude })
+    }
+
+    /// Returns the value of the given attribute parsed with the HTML rules for parsing
+    /// floating-point number values: leading whitespace is skipped, and anything after the number
+    /// is ignored. Returns `None` if the attribute is missing or doesn't start with a number.
+    pub fn get_float_attr(&self, name: &str) -> Option<f64> {
+        let value = match self.get_attr(name) {
+            Some(value) => value.trim_left(),
+            None => return None,
+        };
+
+        // Find the longest prefix that looks like `-1.5e3`.
+        let chars: ~[char] = value.iter().collect();
+        let digits_from = |start: uint| -> uint {
+            let mut end = start;
+            while end < chars.len() && chars[end].is_digit() {
+                end += 1
+            }
+            end
+        };
+        let mut number = ~"";
+        let mut i = 0;
+        if i < chars.len() && chars[i] == '-' {
+            number.push_char('-');
+            i += 1;
+        }
+        let integer_end = digits_from(i);
+        if integer_end == i {
+            // A number can start with its decimal point, as in `.5`.
+            if !(i + 1 < chars.len() && chars[i] == '.' && chars[i + 1].is_digit()) {
+                return None
+            }
+            number.push_char('0');
+        }
+        for &ch in chars.slice(i, integer_end).iter() {
+            number.push_char(ch)
+        }
+        i = integer_end;
+        if i + 1 < chars.len() && chars[i] == '.' && chars[i + 1].is_digit() {
+            let fraction_end = digits_from(i + 1);
+            for &ch in chars.slice(i, fraction_end).iter() {
+                number.push_char(ch)
+            }
+            i = fraction_end;
+        }
+        if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+            let mut exponent_start = i + 1;
+            if exponent_start < chars.len() && (chars[exponent_start] == '-' ||
+                                                chars[exponent_start] == '+') {
+                exponent_start += 1;
+            }
+            let exponent_end = digits_from(exponent_start);
+            if exponent_end > exponent_start {
+                number.push_char('e');
+                for &ch in chars.slice(i + 1, exponent_end).iter() {
+                    number.push_char(ch)
+                }
+            }
+        }
+        FromStr::from_str(number)
+    }
+
     pub fn set_attr(&mut self, name: &DOMString, value: &DOMString) {
         let name = name.to_str();
+        let old = self.get_attr(name).map_move(|value| value.to_owned());
         let value_cell = Cell::new(value.to_str());
         let mut found = false;
         for attr in self.attrs.mut_iter() {
@@ -160,15 +304,48 @@ impl<'self> Element {
                 Stylesheet::from_attribute(
                     FromStr::from_str("http://www.example.com/").unwrap(),
                     value.get_ref()));
+            self.style_attribute_declarations = Some(parse_style_attribute(value.get_ref()));
         }
 
-        match self.parent.owner_doc {
-            Some(owner) => do owner.with_base |owner| { owner.content_changed() },
-            None => {}
+        self.attribute_changed(name, old, value.to_str())
+    }
+
+    /// Sets the attribute with the given namespace URL, or the empty string for none, and local
+    /// name.
+    pub fn set_attr_ns(&mut self, namespace: &str, name: &DOMString, value: &DOMString) {
+        if namespace.is_empty() {
+            return self.set_attr(name, value)
+        }
+        let name = name.to_str();
+        let old = self.get_attr_ns(namespace, name).map_move(|value| value.to_owned());
+        let mut found = false;
+        for attr in self.attrs.mut_iter() {
+            if eq_slice(attr.namespace, namespace) && eq_slice(attr.name, name) {
+                attr.value = value.to_str();
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            self.attrs.push(Attr::new_ns(namespace.to_owned(), name.clone(), value.to_str()));
+        }
+        self.attribute_changed(name, old, value.to_str())
+    }
+
+    /// Marks what the change of the attribute `name` from `old` to `new` could restyle dirty.
+    fn attribute_changed(&self, name: &str, old: Option<~str>, new: &str) {
+        match (self.parent.abstract, self.parent.owner_doc) {
+            (Some(node), Some(doc)) if "style" != name => {
+                do doc.with_base |doc| {
+                    doc.attribute_changed(node, name, old.map(|value| value.as_slice()), new)
+                }
+            }
+            // The style attribute styles the element alone, and what inherits from it.
+            _ => self.parent.content_changed(None, "style" != name),
         }
     }
 
-    fn get_scope_and_cx(&self) -> (*JSObject, *JSContext) {
+    pub fn get_scope_and_cx(&self) -> (*JSObject, *JSContext) {
         let doc = self.parent.owner_doc.unwrap();
         let win = doc.with_base(|doc| doc.window.unwrap());
         let cx = unsafe {(*win.page).js_info.get_ref().js_compartment.cx.ptr};
@@ -201,8 +378,15 @@ impl Element {
         null_string
     }
 
-    pub fn SetAttribute(&mut self, name: &DOMString, value: &DOMString, _rv: &mut ErrorResult) {
-        self.set_attr(name, value);
+    pub fn SetAttribute(&mut self,
+                        abstract_self: AbstractNode<ScriptView>,
+                        name: &DOMString,
+                        value: &DOMString,
+                        _rv: &mut ErrorResult) {
+        // Borrowing the element through its node lets the change mark the node dirty.
+        do abstract_self.with_mut_element |element| {
+            element.set_attr(name, value)
+        }
     }
 
     pub fn SetAttributeNS(&self, _namespace: &DOMString, _localname: &DOMString, _value: &DOMString, _rv: &mut ErrorResult) {
@@ -224,19 +408,35 @@ impl Element {
         false
     }
 
-    pub fn GetElementsByTagName(&self, _localname: &DOMString) -> @mut HTMLCollection {
-        let (scope, cx) = self.get_scope_and_cx();
-        HTMLCollection::new(~[], cx, scope)
+    pub fn GetElementsByTagName(&self,
+                                abstract_self: AbstractNode<ScriptView>,
+                                localname: &DOMString)
+                                -> @mut HTMLCollection {
+        let localname = localname.to_str();
+        self.create_html_collection(abstract_self, |elem| eq_slice(elem.tag_name, localname))
     }
 
     pub fn GetElementsByTagNameNS(&self, _namespace: &DOMString, _localname: &DOMString, _rv: &mut ErrorResult) -> @mut HTMLCollection {
         let (scope, cx) = self.get_scope_and_cx();
-        HTMLCollection::new(~[], cx, scope)
+        HTMLCollection::empty(cx, scope)
+    }
+
+    pub fn GetElementsByClassName(&self,
+                                  abstract_self: AbstractNode<ScriptView>,
+                                  names: &DOMString)
+                                  -> @mut HTMLCollection {
+        let names = names.to_str();
+        self.create_html_collection(abstract_self, |elem| elem.has_class_names(names))
     }
 
-    pub fn GetElementsByClassName(&self, _names: &DOMString) -> @mut HTMLCollection {
+    /// Creates a live collection of the descendants of this element that `filter` accepts.
+    fn create_html_collection(&self,
+                              abstract_self: AbstractNode<ScriptView>,
+                              filter: ~fn(&Element) -> bool)
+                              -> @mut HTMLCollection {
         let (scope, cx) = self.get_scope_and_cx();
-        HTMLCollection::new(~[], cx, scope)
+        let dom_version = self.parent.owner_doc.map(|doc| doc.with_base(|doc| doc.dom_version));
+        HTMLCollection::new(abstract_self, false, filter, dom_version, cx, scope)
     }
 
     pub fn MozMatchesSelector(&self, _selector: &DOMString, _rv: &mut ErrorResult) -> bool {
@@ -328,7 +528,18 @@ impl Element {
         }
     }
 
-    pub fn ScrollIntoView(&self, _top: bool) {
+    pub fn ScrollIntoView(&self, abstract_self: AbstractNode<ScriptView>, top: bool) {
+        match self.parent.owner_doc {
+            Some(doc) => {
+                match doc.with_base(|doc| doc.window) {
+                    Some(win) => unsafe {
+                        (*win.page).scroll_into_view(abstract_self, top, win.compositor)
+                    },
+                    None => debug!("no window"),
+                }
+            }
+            None => debug!("no document"),
+        }
     }
 
     pub fn ScrollTop(&self) -> i32 {
@@ -369,21 +580,89 @@ impl Element {
         0
     }
 
-    pub fn GetInnerHTML(&self, _rv: &mut ErrorResult) -> DOMString {
-        null_string
-    }
-
-    pub fn SetInnerHTML(&mut self, _value: &DOMString, _rv: &mut ErrorResult) {
-    }
-
-    pub fn GetOuterHTML(&self, _rv: &mut ErrorResult) -> DOMString {
-        null_string
-    }
-
-    pub fn SetOuterHTML(&mut self, _value: &DOMString, _rv: &mut ErrorResult) {
+    pub fn GetInnerHTML(&self,
+                        abstract_self: AbstractNode<ScriptView>,
+                        _rv: &mut ErrorResult)
+                        -> DOMString {
+        str(serialize_children(abstract_self))
+    }
+
+    /// Replaces the children of the element with the nodes that `value` is parsed into as markup
+    /// in the element.
+    pub fn SetInnerHTML(&mut self,
+                        abstract_self: AbstractNode<ScriptView>,
+                        value: &DOMString,
+                        _rv: &mut ErrorResult) {
+        let fragment = self.parse_fragment(abstract_self, value);
+        abstract_self.replace_all_children(fragment)
+    }
+
+    pub fn GetOuterHTML(&self,
+                        abstract_self: AbstractNode<ScriptView>,
+                        _rv: &mut ErrorResult)
+                        -> DOMString {
+        str(serialize_node(abstract_self))
+    }
+
+    /// Replaces the element with the nodes that `value` is parsed into as markup in its parent.
+    /// An element without a parent stays as it is.
+    pub fn SetOuterHTML(&mut self,
+                        abstract_self: AbstractNode<ScriptView>,
+                        value: &DOMString,
+                        _rv: &mut ErrorResult) {
+        let parent = match abstract_self.parent_node() {
+            Some(parent) => parent,
+            None => return,
+        };
+        let fragment = self.parse_fragment(parent, value);
+        parent.insert_child(fragment, Some(abstract_self));
+        abstract_self.remove_from_parent()
+    }
+
+    /// Inserts the nodes that `text` is parsed into as markup before the element, as its first
+    /// or last children, or after it, as `position` says. Markup inserted next to the element is
+    /// parsed in its parent, which it must have.
+    pub fn InsertAdjacentHTML(&mut self,
+                              abstract_self: AbstractNode<ScriptView>,
+                              position: &DOMString,
+                              text: &DOMString,
+                              rv: &mut ErrorResult) {
+        let position = position.to_str().to_ascii_lower();
+        let (parent, before) = match position.as_slice() {
+            "beforebegin" => (abstract_self.parent_node(), Some(abstract_self)),
+            "afterbegin" => (Some(abstract_self), abstract_self.first_child()),
+            "beforeend" => (Some(abstract_self), None),
+            "afterend" => (abstract_self.parent_node(), abstract_self.next_sibling()),
+            _ => (None, None),
+        };
+        match parent {
+            Some(parent) => {
+                let fragment = self.parse_fragment(parent, text);
+                parent.insert_child(fragment, before)
+            }
+            None => *rv = Err(FailureUnknown),
+        }
     }
 
-    pub fn InsertAdjacentHTML(&mut self, _position: &DOMString, _text: &DOMString, _rv: &mut ErrorResult) {
+    /// Parses markup as the contents of `context`, into a document fragment that belongs to the
+    /// document of this element.
+    fn parse_fragment(&self,
+                      context: AbstractNode<ScriptView>,
+                      markup: &DOMString)
+                      -> AbstractNode<ScriptView> {
+        let (_, cx) = self.get_scope_and_cx();
+        let window = self.parent.owner_doc.unwrap().with_base(|doc| doc.window.unwrap());
+        let base_url = unsafe {
+            match (*window.page).url {
+                Some((ref url, _)) => url.clone(),
+                None => FromStr::from_str("about:blank").unwrap(),
+            }
+        };
+        parse_html_fragment(cx,
+                            context,
+                            markup.to_str(),
+                            base_url,
+                            window.image_cache_task.clone())
     }
 
     pub fn QuerySelector(&self, _selectors: &DOMString, _rv: &mut ErrorResult) -> Option<AbstractNode<ScriptView>> {
@@ -392,15 +671,86 @@ impl Element {
 }
 
 pub struct Attr {
+    /// The URL of the namespace of the attribute, or the empty string if it is in none. Only the
+    /// attributes of foreign elements that the parser adjusts, such as `xlink:href`, have one.
+    namespace: ~str,
+    /// The local name of the attribute.
     name: ~str,
     value: ~str,
 }
 
 impl Attr {
     pub fn new(name: ~str, value: ~str) -> Attr {
+        Attr::new_ns(~"", name, value)
+    }
+
+    pub fn new_ns(namespace: ~str, name: ~str, value: ~str) -> Attr {
         Attr {
+            namespace: namespace,
             name: name,
             value: value
         }
     }
+
+    /// Returns the name of the attribute as HTML serializes it, with the prefix of its namespace.
+    pub fn qualified_name(&self) -> ~str {
+        let namespace = self.namespace.as_slice();
+        let prefix = if namespace == XLINK_NAMESPACE {
+            "xlink:"
+        } else if namespace == XML_NAMESPACE {
+            "xml:"
+        } else if namespace == XMLNS_NAMESPACE && "xmlns" != self.name {
+            "xmlns:"
+        } else {
+            ""
+        };
+        prefix + self.name
+    }
+}
+
+pub static XLINK_NAMESPACE: &'static str = "http://www.w3.org/1999/xlink";
+pub static XML_NAMESPACE: &'static str = "http://www.w3.org/XML/1998/namespace";
+pub static XMLNS_NAMESPACE: &'static str = "http://www.w3.org/2000/xmlns/";
+
+/// The states of an element that the dynamic pseudo-classes match.
+pub enum ElementStateFlag {
+    /// `:hover`: the pointer is over the element or one of its descendants.
+    HoverState = 0x01,
+    /// `:active`: the element or one of its descendants is being activated, by the user pressing
+    /// the mouse button over it.
+    ActiveState = 0x02,
+    /// `:focus`: the element has the focus.
+    FocusState = 0x04,
+}
+
+/// A set of ElementStateFlags.
+#[deriving(Eq)]
+pub struct ElementState {
+    priv bits: u8
+}
+
+impl ElementState {
+    pub fn none() -> ElementState {
+        ElementState { bits: 0 }
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn add(self, flag: ElementStateFlag) -> ElementState {
+        ElementState { bits: self.bits | (flag as u8) }
+    }
+
+    pub fn has(self, flag: ElementStateFlag) -> bool {
+        (self.bits & (flag as u8)) != 0
+    }
+
+    pub fn union(self, other: ElementState) -> ElementState {
+        ElementState { bits: self.bits | other.bits }
+    }
+
+    pub fn intersects(self, other: ElementState) -> bool {
+        (self.bits & other.bits) != 0
+    }
 }
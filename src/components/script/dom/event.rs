@@ -2,11 +2,11 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::eventtarget::EventTarget;
+use dom::node::{AbstractNode, ScriptView};
 use dom::window::Window;
 use dom::bindings::codegen::EventBinding;
 use dom::bindings::utils::{CacheableWrapper, BindingObject, DerivedWrapper};
-use dom::bindings::utils::{DOMString, ErrorResult, WrapperCache};
+use dom::bindings::utils::{DOMString, ErrorResult, FailureUnknown, WrapperCache};
 
 use geom::point::Point2D;
 use js::glue::RUST_OBJECT_TO_JSVAL;
@@ -23,6 +23,83 @@ pub enum Event_ {
     ClickEvent(uint, Point2D<f32>),
     MouseDownEvent(uint, Point2D<f32>),
     MouseUpEvent(uint, Point2D<f32>),
+    /// Sent when the pointer moves over the page.
+    MouseMoveEvent(Point2D<f32>),
+    /// The value the user picked in a picker opened for a date or color input, or `None` if they
+    /// cancelled.
+    PickerResultEvent(Option<~str>),
+    /// Sent when the user presses a key that moves or uses the focus.
+    FocusNavigationEvent(FocusNavigation),
+    /// Sent when the user presses one of the access keys the page registered.
+    AccessKeyEvent(char),
+    /// Sent when the user undoes or redoes an edit.
+    EditingCommandEvent(EditingCommand),
+    /// Sent when the page scrolls, with the position in the page of the top left of the viewport.
+    ScrollEvent(Point2D<f32>),
+    /// Sent when the user pinch zooms, with the scale of the visual viewport.
+    ZoomEvent(f32),
+    /// Sent by layout when an image that it is laying out has loaded, so that the document is laid
+    /// out and painted again with it.
+    ImageLoadedEvent,
+    /// Sent by layout while transitions are running, when the values they have reached should be
+    /// repainted.
+    AnimationTickEvent,
+}
+
+impl Event_ {
+    /// Returns whether the event comes from the user pressing a mouse button or a key, which
+    /// activates the document and lets it do what needs the user's consent, such as playing media.
+    pub fn is_user_activation(&self) -> bool {
+        match *self {
+            ClickEvent(*) | MouseDownEvent(*) | PickerResultEvent(*) | FocusNavigationEvent(*) |
+            AccessKeyEvent(*) | EditingCommandEvent(*) => true,
+            ResizeEvent(*) | ReflowEvent | MouseUpEvent(*) | MouseMoveEvent(*) | ScrollEvent(*) |
+            ZoomEvent(*) | ImageLoadedEvent | AnimationTickEvent => false,
+        }
+    }
+
+    /// Returns whether the event comes from the user interacting with the page, rather than from
+    /// the page being resized, laid out or painted.
+    pub fn is_user_interaction(&self) -> bool {
+        match *self {
+            ClickEvent(*) | MouseDownEvent(*) | MouseUpEvent(*) | MouseMoveEvent(*) |
+            PickerResultEvent(*) | FocusNavigationEvent(*) | AccessKeyEvent(*) |
+            EditingCommandEvent(*) | ScrollEvent(*) | ZoomEvent(*) => true,
+            ResizeEvent(*) | ReflowEvent | ImageLoadedEvent | AnimationTickEvent => false,
+        }
+    }
+}
+
+/// The keyboard commands for moving between and using focusable elements.
+pub enum FocusNavigation {
+    /// Tab: focuses the next element in sequential focus navigation order.
+    FocusNext,
+    /// Shift+Tab: focuses the previous element.
+    FocusPrevious,
+    /// Enter: activates the focused element, as clicking it would.
+    ActivateFocused,
+}
+
+/// The commands of the embedder's Edit menu and their keyboard shortcuts, which act on the focused
+/// editing host.
+pub enum EditingCommand {
+    /// Ctrl+Z: takes back the last edit.
+    UndoCommand,
+    /// Ctrl+Y or Ctrl+Shift+Z: makes the last edit that was taken back again.
+    RedoCommand,
+}
+
+/// The phases of the dispatch of an event, as `eventPhase` gives them.
+#[deriving(Eq)]
+pub enum DispatchPhase {
+    /// The event isn't being dispatched.
+    PhaseNone = 0,
+    /// The event is going down from the root to the parent of its target.
+    PhaseCapturing = 1,
+    /// The event is at its target.
+    PhaseAtTarget = 2,
+    /// The event is going back up from the parent of its target to the root.
+    PhaseBubbling = 3,
 }
 
 pub struct Event {
@@ -32,6 +109,20 @@ pub struct Event {
     cancelable: bool,
     bubbles: bool,
     trusted: bool,
+    /// The phase of the dispatch that the event is in.
+    phase: DispatchPhase,
+    /// The node that the event was dispatched at, once it has been.
+    ///
+    /// FIXME: Windows and documents should be event targets too.
+    target: Option<AbstractNode<ScriptView>>,
+    /// The node whose listeners are being called.
+    current_target: Option<AbstractNode<ScriptView>>,
+    /// Whether a listener has stopped the event from going on to other nodes.
+    stop_propagation: bool,
+    /// Whether a listener has stopped the event from going on to any other listener.
+    stop_immediate_propagation: bool,
+    /// Whether the event is being dispatched, when it can't be dispatched again.
+    dispatching: bool,
 }
 
 impl Event {
@@ -42,7 +133,13 @@ impl Event {
             default_prevented: false,
             cancelable: true,
             bubbles: true,
-            trusted: false
+            trusted: false,
+            phase: PhaseNone,
+            target: None,
+            current_target: None,
+            stop_propagation: false,
+            stop_immediate_propagation: false,
+            dispatching: false,
         }
     }
 
@@ -51,33 +148,39 @@ impl Event {
     }
 
     pub fn EventPhase(&self) -> u16 {
-        0
+        self.phase as u16
     }
 
     pub fn Type(&self) -> DOMString {
         self.type_.clone()
     }
 
-    pub fn GetTarget(&self) -> Option<@mut EventTarget> {
-        None
+    pub fn GetTarget(&self) -> Option<AbstractNode<ScriptView>> {
+        self.target
     }
 
-    pub fn GetCurrentTarget(&self) -> Option<@mut EventTarget> {
-        None
+    pub fn GetCurrentTarget(&self) -> Option<AbstractNode<ScriptView>> {
+        self.current_target
     }
 
     pub fn DefaultPrevented(&self) -> bool {
         self.default_prevented
     }
 
+    /// Cancels the default action of the event, if it can be cancelled.
     pub fn PreventDefault(&mut self) {
-        self.default_prevented = true
+        if self.cancelable {
+            self.default_prevented = true
+        }
     }
 
     pub fn StopPropagation(&mut self) {
+        self.stop_propagation = true
     }
 
     pub fn StopImmediatePropagation(&mut self) {
+        self.stop_propagation = true;
+        self.stop_immediate_propagation = true
     }
 
     pub fn Bubbles(&self) -> bool {
@@ -96,10 +199,18 @@ impl Event {
                      type_: &DOMString,
                      bubbles: bool,
                      cancelable: bool,
-                     _rv: &mut ErrorResult) {
+                     rv: &mut ErrorResult) {
+        if self.dispatching {
+            *rv = Err(FailureUnknown);
+            return
+        }
         self.type_ = (*type_).clone();
         self.cancelable = cancelable;
         self.bubbles = bubbles;
+        self.default_prevented = false;
+        self.stop_propagation = false;
+        self.stop_immediate_propagation = false;
+        self.target = None;
     }
 
     pub fn IsTrusted(&self) -> bool {
@@ -108,9 +219,12 @@ impl Event {
 
     pub fn Constructor(_global: @mut Window,
                    type_: &DOMString,
-                   _init: &EventBinding::EventInit,
+                   init: &EventBinding::EventInit,
                    _rv: &mut ErrorResult) -> @mut Event {
-        @mut Event::new(type_)
+        let event = @mut Event::new(type_);
+        event.bubbles = init.bubbles;
+        event.cancelable = init.cancelable;
+        event
     }
 }
 
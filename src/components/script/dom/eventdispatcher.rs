@@ -0,0 +1,165 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Dispatching events to nodes, through the capture, target and bubble phases of the DOM event
+//! model.
+
+use dom::bindings::utils::{CacheableWrapper, str};
+use dom::event::{Event, PhaseNone, PhaseCapturing, PhaseAtTarget, PhaseBubbling};
+use dom::eventtarget::EventListener;
+use dom::mouseevent::MouseEvent;
+use dom::node::{AbstractNode, ScriptView};
+use dom::window::Window;
+
+use geom::point::Point2D;
+use js::JSVAL_NULL;
+use js::glue::RUST_OBJECT_TO_JSVAL;
+use js::jsapi::{JSContext, JS_CallFunctionValue};
+use servo_util::tree::TreeNodeRef;
+use std::cast;
+
+/// Dispatches `event` at `target`: calls the capturing listeners of the ancestors of the target
+/// from the root down, then the listeners of the target, then, if the event bubbles, the other
+/// listeners of the ancestors from the parent up, until one of them stops the propagation of the
+/// event. Returns whether the default action of the event should happen, which it shouldn't if a
+/// listener cancelled the event.
+///
+/// The event must have been wrapped, and must not be being dispatched already.
+pub fn dispatch_event(cx: *JSContext, target: AbstractNode<ScriptView>, event: @mut Event)
+                      -> bool {
+    assert!(!event.dispatching);
+    event.dispatching = true;
+    event.target = Some(target);
+
+    // The path of the event is fixed before any listener runs, so that listeners that move nodes
+    // don't change where it goes.
+    let mut ancestors = ~[];
+    let mut current = target.parent_node();
+    while current.is_some() {
+        let node = current.unwrap();
+        ancestors.push(node);
+        current = node.parent_node();
+    }
+
+    event.phase = PhaseCapturing;
+    for &node in ancestors.rev_iter() {
+        if event.stop_propagation {
+            break
+        }
+        invoke_listeners(cx, node, event, Some(true));
+    }
+
+    if !event.stop_propagation {
+        event.phase = PhaseAtTarget;
+        invoke_listeners(cx, target, event, None);
+    }
+
+    if event.bubbles {
+        event.phase = PhaseBubbling;
+        for &node in ancestors.iter() {
+            if event.stop_propagation {
+                break
+            }
+            invoke_listeners(cx, node, event, Some(false));
+        }
+    }
+
+    event.phase = PhaseNone;
+    event.current_target = None;
+    event.stop_propagation = false;
+    event.stop_immediate_propagation = false;
+    event.dispatching = false;
+    !event.default_prevented
+}
+
+/// Fires an event of the given type that comes from the browser rather than from script at
+/// `target`, and returns whether its default action should happen.
+pub fn fire_event(window: @mut Window,
+                  target: AbstractNode<ScriptView>,
+                  type_: &str,
+                  bubbles: bool,
+                  cancelable: bool)
+                  -> bool {
+    let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+    let scope = window.get_wrappercache().get_wrapper();
+    let event = @mut Event::new(&str(type_.to_owned()));
+    event.bubbles = bubbles;
+    event.cancelable = cancelable;
+    event.trusted = true;
+    event.init_wrapper(cx, scope);
+    dispatch_event(cx, target, event)
+}
+
+/// Fires a mouse event of the given type that comes from the browser at `target`, for `button`
+/// with the pointer at `client_point` in the viewport, and returns whether its default action
+/// should happen. `related_target` is the node that the pointer left or entered, for `mouseover`
+/// and `mouseout`.
+///
+/// FIXME: The browser doesn't know where its window is on the screen, so `screenX` and `screenY`
+/// are the same as `clientX` and `clientY`. Modifier keys aren't known either.
+pub fn fire_mouse_event(window: @mut Window,
+                        target: AbstractNode<ScriptView>,
+                        type_: &str,
+                        button: u16,
+                        client_point: Point2D<i32>,
+                        related_target: Option<AbstractNode<ScriptView>>)
+                        -> bool {
+    let cx = unsafe { (*window.page).js_info.get_ref().js_compartment.cx.ptr };
+    let scope = window.get_wrappercache().get_wrapper();
+    // `detail` is the number of clicks for presses and releases of a button.
+    let detail = match type_ {
+        "click" | "mousedown" | "mouseup" => 1,
+        _ => 0,
+    };
+    let event = @mut MouseEvent::new(&str(type_.to_owned()), true, true, None, detail,
+                                     client_point.x, client_point.y,
+                                     client_point.x, client_point.y,
+                                     false, false, false, false, button, 0, related_target);
+    event.parent.parent.trusted = true;
+    event.init_wrapper(cx, scope);
+    // A mouse event starts with an event, so it can be dispatched as one.
+    let event: @mut Event = unsafe { cast::transmute(event) };
+    dispatch_event(cx, target, event)
+}
+
+/// Calls the listeners of `node` for `event`, in the order they were added: those that listen in
+/// the capture phase if `capture` is `Some(true)`, the others if it is `Some(false)`, and all of
+/// them if it is `None`, as at the target.
+#[fixed_stack_segment]
+fn invoke_listeners(cx: *JSContext,
+                    node: AbstractNode<ScriptView>,
+                    event: @mut Event,
+                    capture: Option<bool>) {
+    let type_ = event.type_.to_str();
+    let listeners: ~[EventListener] = do node.with_base |base| {
+        base.event_listeners.iter().filter(|listener| {
+            listener.type_ == type_ && capture.map_default(true, |&capture| {
+                listener.capture == capture
+            })
+        }).map(|listener| listener.clone()).collect()
+    };
+    if listeners.is_empty() {
+        return
+    }
+
+    event.current_target = Some(node);
+    let this = node.with_base(|base| base.wrapper.get_wrapper());
+    let event_object = event.get_wrappercache().get_wrapper();
+    assert!(this.is_not_null() && event_object.is_not_null());
+    let argv = [unsafe { RUST_OBJECT_TO_JSVAL(event_object) }];
+    for listener in listeners.iter() {
+        if event.stop_immediate_propagation {
+            break
+        }
+        // A listener that an earlier listener removed isn't called.
+        if !node.with_base(|base| base.event_listeners.contains(listener)) {
+            loop
+        }
+        // A listener that throws doesn't stop the dispatch.
+        let rval = JSVAL_NULL;
+        unsafe {
+            JS_CallFunctionValue(cx, this, listener.callback, 1, &argv[0], &rval);
+        }
+    }
+}
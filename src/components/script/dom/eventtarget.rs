@@ -15,6 +15,19 @@ pub struct EventTarget {
     wrapper: WrapperCache
 }
 
+/// A listener that script has added to a node with `addEventListener()`.
+#[deriving(Eq, Clone)]
+pub struct EventListener {
+    /// The type of the events that it listens for.
+    type_: ~str,
+    /// The function to call with the event.
+    ///
+    /// FIXME: Objects with a `handleEvent` method should be listeners too.
+    callback: JSVal,
+    /// Whether it listens in the capture phase, rather than when the event bubbles.
+    capture: bool,
+}
+
 impl EventTarget {
     pub fn new() -> ~EventTarget {
         ~EventTarget {
@@ -0,0 +1,61 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use audio_task::{GainNodeKind, GainParam};
+use dom::audiocontext::AudioGraphHandle;
+use dom::audionode::AudioNode;
+use dom::audioparam::{AudioParam, MOST_POSITIVE_SINGLE_FLOAT};
+use dom::bindings::codegen::GainNodeBinding;
+use dom::bindings::utils::{WrapperCache, BindingObject, CacheableWrapper};
+use script_task::page_from_context;
+
+use js::jsapi::{JSContext, JSObject};
+use std::cast;
+
+/// A node that changes the volume of the audio connected to it.
+pub struct GainNode {
+    parent: AudioNode,
+    gain: @mut AudioParam,
+}
+
+impl GainNode {
+    pub fn new(graph: @mut AudioGraphHandle) -> @mut GainNode {
+        let id = graph.create_node(GainNodeKind);
+        @mut GainNode {
+            parent: AudioNode::new(graph, id, 1, 1),
+            gain: AudioParam::new(graph,
+                                  id,
+                                  GainParam,
+                                  1.0,
+                                  -MOST_POSITIVE_SINGLE_FLOAT,
+                                  MOST_POSITIVE_SINGLE_FLOAT),
+        }
+    }
+}
+
+impl GainNode {
+    pub fn Gain(&self) -> @mut AudioParam {
+        self.gain
+    }
+}
+
+impl CacheableWrapper for GainNode {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.parent.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        GainNodeBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for GainNode {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
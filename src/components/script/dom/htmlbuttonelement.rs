@@ -13,7 +13,7 @@ pub struct HTMLButtonElement {
 
 impl HTMLButtonElement {
     pub fn Autofocus(&self) -> bool {
-        false
+        self.parent.parent.get_attr("autofocus").is_some()
     }
 
     pub fn SetAutofocus(&mut self, _autofocus: bool, _rv: &mut ErrorResult) {
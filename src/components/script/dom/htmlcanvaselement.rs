@@ -2,25 +2,141 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::{ErrorResult};
+use dom::bindings::utils::{DOMString, ErrorResult, str};
+use dom::canvasrenderingcontext2d::CanvasRenderingContext2D;
 use dom::htmlelement::HTMLElement;
+use dom::node::{AbstractNode, ScriptView};
+
+use extra::arc::Arc;
+use geom::point::Point2D;
+use gfx::color::Color;
+use gfx::font::FontStyle;
+use gfx::geometry::Au;
+use servo_net::image::base::Image;
 
 pub struct HTMLCanvasElement {
     parent: HTMLElement,
+    /// The 2D context of the canvas, once script has asked for it.
+    context: Option<@mut CanvasRenderingContext2D>,
+    /// What has been drawn on the canvas since its bitmap was last cleared, in the order it was
+    /// drawn. Layout shapes text with the fonts that it uses for the text of the document.
+    drawings: ~[CanvasDrawing],
+}
+
+/// Something drawn on a canvas.
+pub enum CanvasDrawing {
+    TextDrawing(CanvasText),
+    /// An image bitmap drawn by `drawImage()` at its own size, with its top left corner at a
+    /// point in pixels from the top left of the canvas.
+    ImageDrawing(Arc<~Image>, f64, f64),
+}
+
+/// Where text drawn on a canvas is placed horizontally relative to the point it is drawn at, as
+/// `textAlign` sets it.
+#[deriving(Eq)]
+pub enum CanvasTextAlign {
+    StartTextAlign,
+    EndTextAlign,
+    LeftTextAlign,
+    RightTextAlign,
+    CenterTextAlign,
+}
+
+/// Which baseline of text drawn on a canvas is at the point it is drawn at, as `textBaseline`
+/// sets it.
+#[deriving(Eq)]
+pub enum CanvasTextBaseline {
+    TopTextBaseline,
+    HangingTextBaseline,
+    MiddleTextBaseline,
+    AlphabeticTextBaseline,
+    IdeographicTextBaseline,
+    BottomTextBaseline,
+}
+
+/// A line of text drawn on a canvas by `fillText()` or `strokeText()`.
+pub struct CanvasText {
+    text: ~str,
+    font: FontStyle,
+    color: Color,
+    /// The point the text is drawn at, in pixels from the top left of the canvas.
+    x: f64,
+    y: f64,
+    align: CanvasTextAlign,
+    baseline: CanvasTextBaseline,
+}
+
+/// Returns where the start of the alphabetic baseline of a line of text is relative to the point
+/// it is drawn at, given its advance and the ascent and descent of its font.
+///
+/// FIXME: Canvases don't have a direction yet, so `start` and `end` are always left and right.
+pub fn text_origin(align: CanvasTextAlign,
+                   baseline: CanvasTextBaseline,
+                   advance: Au,
+                   ascent: Au,
+                   descent: Au)
+                   -> Point2D<Au> {
+    let x = match align {
+        StartTextAlign | LeftTextAlign => Au(0),
+        CenterTextAlign => Au(0) - advance.scale_by(0.5),
+        EndTextAlign | RightTextAlign => Au(0) - advance,
+    };
+    let y = match baseline {
+        TopTextBaseline | HangingTextBaseline => ascent,
+        MiddleTextBaseline => (ascent - descent).scale_by(0.5),
+        AlphabeticTextBaseline => Au(0),
+        IdeographicTextBaseline | BottomTextBaseline => Au(0) - descent,
+    };
+    Point2D(x, y)
+}
+
+impl HTMLCanvasElement {
+    /// Draws a line of text or an image on the canvas.
+    pub fn draw(&mut self, drawing: CanvasDrawing) {
+        self.drawings.push(drawing);
+        self.parent.parent.parent.content_changed(None, false)
+    }
+
+    /// Clears the bitmap of the canvas, which setting its width or height does.
+    fn clear(&mut self) {
+        self.drawings = ~[];
+        self.parent.parent.parent.content_changed(None, false)
+    }
 }
 
 impl HTMLCanvasElement {
     pub fn Width(&self) -> u32 {
-        0
+        self.parent.parent.get_unsigned_attr("width").unwrap_or_default(300) as u32
     }
 
-    pub fn SetWidth(&mut self, _width: u32, _rv: &mut ErrorResult) {
+    pub fn SetWidth(&mut self, width: u32, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"width"), &str(width.to_str()));
+        self.clear()
     }
 
     pub fn Height(&self) -> u32 {
-        0
+        self.parent.parent.get_unsigned_attr("height").unwrap_or_default(150) as u32
+    }
+
+    pub fn SetHeight(&mut self, height: u32, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"height"), &str(height.to_str()));
+        self.clear()
     }
 
-    pub fn SetHeight(&mut self, _height: u32, _rv: &mut ErrorResult) {
+    /// Returns the 2D context of the canvas, or `None` for the other kinds of context, which
+    /// aren't supported.
+    pub fn GetContext(&mut self,
+                      abstract_self: AbstractNode<ScriptView>,
+                      context_id: &DOMString,
+                      _rv: &mut ErrorResult)
+                      -> Option<@mut CanvasRenderingContext2D> {
+        if context_id.to_str() != ~"2d" {
+            return None
+        }
+        if self.context.is_none() {
+            let (scope, cx) = self.parent.parent.get_scope_and_cx();
+            self.context = Some(CanvasRenderingContext2D::new(abstract_self, cx, scope));
+        }
+        self.context
     }
 }
@@ -5,42 +5,111 @@
 use dom::bindings::codegen::HTMLCollectionBinding;
 use dom::bindings::utils::{CacheableWrapper, BindingObject, WrapperCache};
 use dom::bindings::utils::{DOMString, ErrorResult};
+use dom::element::Element;
 use dom::node::{AbstractNode, ScriptView};
 use script_task::page_from_context;
+use servo_util::tree::TreeNodeRef;
 
 use js::jsapi::{JSObject, JSContext};
 
 use std::cast;
 use std::ptr;
 
+/// A live collection of the elements under a node that a filter accepts, in tree order, which
+/// reflects the changes to the document made since it was created.
+///
+/// The elements are collected when script first asks for them, and again only once the document
+/// has changed since, as its DOM version says.
 pub struct HTMLCollection {
-    elements: ~[AbstractNode<ScriptView>],
+    /// The node whose descendants the collection is of, or `None` if it is always empty.
+    root: Option<AbstractNode<ScriptView>>,
+    /// Whether the root is in the collection if the filter accepts it, as the root element is in
+    /// the collections of its document.
+    include_root: bool,
+    filter: ~fn(&Element) -> bool,
+    /// The DOM version of the document, which goes up whenever the document changes.
+    dom_version: Option<@mut uint>,
+    /// The elements as they were at the DOM version they were collected at. It is boxed so that
+    /// it can be refreshed when the bindings read the collection, which they borrow immutably.
+    cache: @mut Option<(uint, ~[AbstractNode<ScriptView>])>,
     wrapper: WrapperCache
 }
 
 impl HTMLCollection {
-    pub fn new(elements: ~[AbstractNode<ScriptView>], cx: *JSContext, scope: *JSObject) -> @mut HTMLCollection {
+    /// Creates a collection of the elements under `root` that `filter` accepts, which are looked
+    /// for again whenever `dom_version` has gone up, or every time if there is none.
+    pub fn new(root: AbstractNode<ScriptView>,
+               include_root: bool,
+               filter: ~fn(&Element) -> bool,
+               dom_version: Option<@mut uint>,
+               cx: *JSContext,
+               scope: *JSObject)
+               -> @mut HTMLCollection {
         let collection = @mut HTMLCollection {
-            elements: elements,
+            root: Some(root),
+            include_root: include_root,
+            filter: filter,
+            dom_version: dom_version,
+            cache: @mut None,
             wrapper: WrapperCache::new()
         };
         collection.init_wrapper(cx, scope);
         collection
     }
 
-    pub fn init_wrapper(@mut self, cx: *JSContext, scope: *JSObject) {
-        self.wrap_object_shared(cx, scope);
+    /// Creates a collection that is always empty.
+    pub fn empty(cx: *JSContext, scope: *JSObject) -> @mut HTMLCollection {
+        let collection = @mut HTMLCollection {
+            root: None,
+            include_root: false,
+            filter: |_| false,
+            dom_version: None,
+            cache: @mut None,
+            wrapper: WrapperCache::new()
+        };
+        collection.init_wrapper(cx, scope);
+        collection
     }
-    
+
+    /// Calls `f` with the elements of the collection, collecting them again first if the
+    /// document has changed since they were last collected.
+    fn with_elements<R>(&self, f: &fn(&[AbstractNode<ScriptView>]) -> R) -> R {
+        let version = self.dom_version.map(|&version| *version);
+        let fresh = match (version, &*self.cache) {
+            (Some(version), &Some((cached_version, _))) => version == cached_version,
+            _ => false,
+        };
+        if !fresh {
+            let mut elements = ~[];
+            for root in self.root.iter() {
+                for node in root.traverse_preorder() {
+                    if !node.is_element() || (node == *root && !self.include_root) {
+                        loop
+                    }
+                    if node.with_imm_element(|element| (self.filter)(element)) {
+                        elements.push(node);
+                    }
+                }
+            }
+            *self.cache = Some((version.unwrap_or_default(0), elements));
+        }
+        match *self.cache {
+            Some((_, ref elements)) => f(elements.slice_from(0)),
+            None => f(&[]),
+        }
+    }
+
     pub fn Length(&self) -> u32 {
-        self.elements.len() as u32
+        self.with_elements(|elements| elements.len() as u32)
     }
 
     pub fn Item(&self, index: u32) -> Option<AbstractNode<ScriptView>> {
-        if index < self.Length() {
-            Some(self.elements[index])
-        } else {
-            None
+        do self.with_elements |elements| {
+            if (index as uint) < elements.len() {
+                Some(elements[index])
+            } else {
+                None
+            }
         }
     }
 
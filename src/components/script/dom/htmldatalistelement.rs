@@ -24,6 +24,6 @@ impl HTMLDataListElement {
 
     pub fn Options(&self) -> @mut HTMLCollection {
         let (scope, cx) = self.get_scope_and_cx();
-        HTMLCollection::new(~[], cx, scope)
+        HTMLCollection::empty(cx, scope)
     }
 }
\ No newline at end of file
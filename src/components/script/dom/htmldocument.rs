@@ -3,18 +3,24 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::codegen::HTMLDocumentBinding;
-use dom::bindings::utils::{DOMString, ErrorResult, null_string};
+use dom::bindings::utils::{DOMString, ErrorResult, null_string, str};
 use dom::bindings::utils::{CacheableWrapper, BindingObject, WrapperCache};
 use dom::document::{AbstractDocument, Document, WrappableDocument, HTML};
+use dom::document::{AfterParserScript, ScriptCreatedParser};
+use dom::editing::CommandTransaction;
+use dom::editing;
 use dom::element::HTMLHeadElementTypeId;
 use dom::htmlcollection::HTMLCollection;
 use dom::node::{AbstractNode, ScriptView, ElementNodeTypeId};
 use dom::window::Window;
+use html::hubbub_html_parser::parse_html_fragment;
 
 use js::jsapi::{JSObject, JSContext};
 
 use servo_util::tree::TreeNodeRef;
 
+use std::FromStr;
+use std::ascii::StrAsciiExt;
 use std::libc;
 use std::ptr;
 use std::str::eq_slice;
@@ -40,6 +46,19 @@ impl HTMLDocument {
         let scope = cache.get_wrapper();
         (scope, cx)
     }
+
+    /// Parses markup written into the document as the contents of its root element.
+    fn parse_written_markup(&self, markup: &str) -> AbstractNode<ScriptView> {
+        let (_, cx) = self.get_scope_and_cx();
+        let window = self.parent.window.get_ref();
+        let base_url = unsafe {
+            match (*window.page).url {
+                Some((ref url, _)) => url.clone(),
+                None => FromStr::from_str("about:blank").unwrap(),
+            }
+        };
+        parse_html_fragment(cx, self.parent.root, markup, base_url, window.image_cache_task.clone())
+    }
 }
 
 impl WrappableDocument for HTMLDocument {
@@ -104,7 +123,63 @@ impl HTMLDocument {
         self.parent.createHTMLCollection(|elem| eq_slice(elem.tag_name, "script"))
     }
 
+    /// Opens the document for writing, throwing its contents away, unless the parser is running a
+    /// script, whose writes go after it instead.
+    pub fn Open(&self,
+                _type: &DOMString,
+                _replace: &DOMString,
+                _rv: &mut ErrorResult)
+                -> AbstractDocument {
+        let running_parser_script = match *self.parent.insertion_point {
+            Some((AfterParserScript(_), _)) => true,
+            _ => false,
+        };
+        if !running_parser_script {
+            *self.parent.insertion_point = Some((ScriptCreatedParser, ~""));
+            let fragment = self.parse_written_markup("");
+            self.parent.root.replace_all_children(fragment)
+        }
+        self.parent.window.get_ref().Document()
+    }
+
+    /// Closes the document that script opened for writing. Markup written after that opens it
+    /// again, replacing what it holds.
     pub fn Close(&self, _rv: &mut ErrorResult) {
+        let script_created = match *self.parent.insertion_point {
+            Some((ScriptCreatedParser, _)) => true,
+            _ => false,
+        };
+        if script_created {
+            *self.parent.insertion_point = None
+        }
+    }
+
+    /// Writes `text` as markup after the script that the parser is running, if any, or else into
+    /// the document, opening it first if it isn't open. The document then holds what all that has
+    /// been written since it opened is parsed into.
+    ///
+    /// FIXME: Only one string is taken, as the bindings don't support variadic arguments.
+    pub fn Write(&self, text: &DOMString, rv: &mut ErrorResult) {
+        if self.parent.insertion_point.is_none() {
+            let _ = self.Open(&str(~"text/html"), &str(~""), rv);
+        }
+        let markup = match *self.parent.insertion_point {
+            Some((AfterParserScript(_), ref mut markup)) => {
+                markup.push_str(text.to_str());
+                return
+            }
+            Some((ScriptCreatedParser, ref mut markup)) => {
+                markup.push_str(text.to_str());
+                markup.clone()
+            }
+            None => return,
+        };
+        let fragment = self.parse_written_markup(markup);
+        self.parent.root.replace_all_children(fragment)
+    }
+
+    pub fn Writeln(&self, text: &DOMString, rv: &mut ErrorResult) {
+        self.Write(&str(text.to_str() + "\n"), rv)
     }
 
     pub fn DesignMode(&self) -> DOMString {
@@ -114,12 +189,30 @@ impl HTMLDocument {
     pub fn SetDesignMode(&self, _mode: &DOMString, _rv: &mut ErrorResult) {
     }
 
-    pub fn ExecCommand(&self, _command_id: &DOMString, _show_ui: bool, _value: &DOMString, _rv: &mut ErrorResult) -> bool {
-        false
+    /// Runs an editing command on the focused editing host. The commands supported are
+    /// `insertText`, `delete`, `undo` and `redo`.
+    pub fn ExecCommand(&mut self, command_id: &DOMString, _show_ui: bool, value: &DOMString,
+                       _rv: &mut ErrorResult) -> bool {
+        let (_, cx) = self.get_scope_and_cx();
+        let document = &mut self.parent;
+        match command_id.to_str().to_ascii_lower().as_slice() {
+            "inserttext" => {
+                editing::insert_text(document, cx, value.to_str(), CommandTransaction)
+            }
+            "delete" => editing::delete_backward(document, cx),
+            "undo" => editing::undo(document),
+            "redo" => editing::redo(document),
+            _ => false,
+        }
     }
 
-    pub fn QueryCommandEnabled(&self, _command_id: &DOMString, _rv: &mut ErrorResult) -> bool {
-        false
+    pub fn QueryCommandEnabled(&self, command_id: &DOMString, _rv: &mut ErrorResult) -> bool {
+        match command_id.to_str().to_ascii_lower().as_slice() {
+            "inserttext" | "delete" => editing::focused_editing_host(&self.parent).is_some(),
+            "undo" => editing::can_undo(&self.parent),
+            "redo" => editing::can_redo(&self.parent),
+            _ => false,
+        }
     }
 
     pub fn QueryCommandIndeterm(&self, _command_id: &DOMString, _rv: &mut ErrorResult) -> bool {
@@ -130,8 +223,11 @@ impl HTMLDocument {
         false
     }
 
-    pub fn QueryCommandSupported(&self, _command_id: &DOMString) -> bool {
-        false
+    pub fn QueryCommandSupported(&self, command_id: &DOMString) -> bool {
+        match command_id.to_str().to_ascii_lower().as_slice() {
+            "inserttext" | "delete" | "undo" | "redo" => true,
+            _ => false,
+        }
     }
 
     pub fn QueryCommandValue(&self, _command_id: &DOMString, _rv: &mut ErrorResult) -> DOMString {
@@ -3,13 +3,16 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::codegen::HTMLElementBinding;
-use dom::bindings::utils::{DOMString, null_string, ErrorResult};
+use dom::bindings::utils::{DOMString, null_string, str, ErrorResult};
 use dom::bindings::utils::{CacheableWrapper, BindingObject, WrapperCache};
 use dom::element::{Element, ElementTypeId};
 use dom::node::{AbstractNode, ScriptView};
 use js::jsapi::{JSObject, JSContext, JSVal};
 use js::JSVAL_NULL;
 
+use std::ascii::StrAsciiExt;
+use std::str;
+
 pub struct HTMLElement {
     parent: Element
 }
@@ -31,17 +34,27 @@ impl HTMLElement {
     }
 
     pub fn Lang(&self) -> DOMString {
-        null_string
+        match self.parent.get_attr("lang") {
+            Some(lang) => str(lang.to_owned()),
+            None => str(~""),
+        }
     }
 
-    pub fn SetLang(&mut self, _lang: &DOMString) {
+    pub fn SetLang(&mut self, lang: &DOMString) {
+        self.parent.set_attr(&str(~"lang"), lang);
     }
 
     pub fn Dir(&self) -> DOMString {
-        null_string
+        match self.parent.get_attr("dir") {
+            Some(dir) if ["ltr", "rtl", "auto"].iter().any(|v| dir.eq_ignore_ascii_case(*v)) => {
+                str(dir.to_ascii_lower())
+            }
+            _ => str(~""),
+        }
     }
 
-    pub fn SetDir(&mut self, _dir: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetDir(&mut self, dir: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.set_attr(&str(~"dir"), dir);
     }
 
     pub fn GetItemValue(&self, _cx: *JSContext, _rv: &mut ErrorResult) -> JSVal {
@@ -61,28 +74,57 @@ impl HTMLElement {
     pub fn Click(&self) {
     }
 
+    /// Returns the `tabindex` of the element, which defaults to 0 for elements that are
+    /// focusable without one and -1 for the rest.
     pub fn TabIndex(&self) -> i32 {
-        0
+        match self.parent.get_int_attr("tabindex") {
+            Some(index) => index,
+            None if self.parent.parent.abstract.unwrap().is_focusable() => 0,
+            None => -1,
+        }
     }
 
-    pub fn SetTabIndex(&mut self, _index: i32, _rv: &mut ErrorResult) {
+    pub fn SetTabIndex(&mut self, index: i32, _rv: &mut ErrorResult) {
+        self.parent.set_attr(&str(~"tabindex"), &str(index.to_str()));
     }
 
     pub fn Focus(&self, _rv: &mut ErrorResult) {
+        let node = self.parent.parent.abstract.unwrap();
+        if !node.is_focusable() {
+            return
+        }
+        for doc in self.parent.parent.owner_doc.iter() {
+            doc.with_mut_base(|doc| doc.set_focus(Some(node)))
+        }
     }
 
     pub fn Blur(&self, _rv: &mut ErrorResult) {
+        let node = self.parent.parent.abstract.unwrap();
+        if !node.is_focused() {
+            return
+        }
+        for doc in self.parent.parent.owner_doc.iter() {
+            doc.with_mut_base(|doc| doc.set_focus(None))
+        }
     }
 
     pub fn AccessKey(&self) -> DOMString {
-        null_string
+        match self.parent.get_attr("accesskey") {
+            Some(key) => str(key.to_owned()),
+            None => str(~""),
+        }
     }
 
-    pub fn SetAccessKey(&self, _key: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetAccessKey(&mut self, key: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.set_attr(&str(~"accesskey"), key);
     }
 
+    /// Returns how the assigned access key is pressed: with Alt, in the windows we have.
     pub fn AccessKeyLabel(&self) -> DOMString {
-        null_string
+        match self.parent.access_key() {
+            Some(key) => str(fmt!("Alt+%s", str::from_char(key).to_ascii_upper())),
+            None => str(~""),
+        }
     }
 
     pub fn Draggable(&self) -> bool {
@@ -104,10 +146,11 @@ impl HTMLElement {
     }
 
     pub fn Spellcheck(&self) -> bool {
-        false
+        self.parent.parent.abstract.unwrap().is_spellchecked()
     }
 
-    pub fn SetSpellcheck(&self, _val: bool, _rv: &mut ErrorResult) {
+    pub fn SetSpellcheck(&mut self, spellcheck: bool, _rv: &mut ErrorResult) {
+        self.parent.set_attr(&str(~"spellcheck"), &str(spellcheck.to_str()));
     }
 
     pub fn ClassName(&self) -> DOMString {
@@ -48,7 +48,7 @@ impl HTMLFieldSetElement {
 
     pub fn Elements(&self) -> @mut HTMLCollection {
         let (scope, cx) = self.get_scope_and_cx();
-        HTMLCollection::new(~[], cx, scope)
+        HTMLCollection::empty(cx, scope)
     }
 
     pub fn WillValidate(&self) -> bool {
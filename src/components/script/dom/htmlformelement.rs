@@ -89,7 +89,7 @@ impl HTMLFormElement {
 
     pub fn Elements(&self) -> @mut HTMLCollection {
         let (scope, cx) = self.get_scope_and_cx();
-        HTMLCollection::new(~[], cx, scope)
+        HTMLCollection::empty(cx, scope)
     }
 
     pub fn Length(&self) -> i32 {
@@ -2,11 +2,340 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::{DOMString, null_string, ErrorResult};
+use dom::bindings::utils::{DOMString, null_string, str, ErrorResult, FailureUnknown};
+use dom::eventdispatcher::fire_event;
 use dom::htmlelement::HTMLElement;
+use dom::node::{AbstractNode, ScriptView};
+use dom::validitystate::{ValidityState, VALUE_MISSING, RANGE_UNDERFLOW, RANGE_OVERFLOW};
+use dom::validitystate::{STEP_MISMATCH, CUSTOM_ERROR};
+use dom::window::Window;
+
+use geom::point::Point2D;
+use geom::rect::Rect;
+use gfx::geometry::Au;
+use gfx::text::script::{TextDirection, LeftToRight, RightToLeft};
+use std::ascii::StrAsciiExt;
+use std::u8;
 
 pub struct HTMLInputElement {
     parent: HTMLElement,
+    /// The message given to `setCustomValidity()`. The control suffers from a custom error while
+    /// it isn't empty.
+    custom_validity_error: ~str,
+}
+
+/// The states of the `type` attribute that are implemented. Any other type is a text field.
+#[deriving(Eq)]
+pub enum InputType {
+    TextInput,
+    /// A slider for choosing a number between a minimum and a maximum.
+    RangeInput,
+    /// A field for a number, with spin buttons that step it up and down.
+    NumberInput,
+    /// A field for a date, which the embedder may let the user pick from a calendar.
+    DateInput,
+    /// A color well, which the embedder may let the user pick a color for.
+    ColorInput,
+}
+
+/// Fires the `input` and `change` events that follow a change that the user made to the value of
+/// a control.
+pub fn fire_value_changed(window: @mut Window, input: AbstractNode<ScriptView>) {
+    fire_event(window, input, "input", true, false);
+    fire_event(window, input, "change", true, false);
+}
+
+impl HTMLInputElement {
+    pub fn input_type(&self) -> InputType {
+        match self.parent.parent.get_attr("type") {
+            Some(type_) => {
+                match type_.to_ascii_lower().as_slice() {
+                    "range" => RangeInput,
+                    "number" => NumberInput,
+                    "date" => DateInput,
+                    "color" => ColorInput,
+                    _ => TextInput,
+                }
+            }
+            None => TextInput,
+        }
+    }
+
+    /// Returns the value of the control, after the value sanitization algorithm of its type has
+    /// run: numbers and dates that aren't valid become empty, and colors that aren't valid become
+    /// black. The `value` attribute itself is left as the author wrote it.
+    pub fn value(&self) -> ~str {
+        let value = self.parent.parent.get_attr("value").unwrap_or_default("");
+        match self.input_type() {
+            TextInput => value.iter().filter(|&ch| ch != '\n' && ch != '\r').collect(),
+            RangeInput => self.range_value().to_str(),
+            NumberInput if parse_floating_point_number(value).is_some() => value.to_owned(),
+            DateInput if parse_date(value).is_some() => value.to_owned(),
+            NumberInput | DateInput => ~"",
+            ColorInput if is_simple_color(value) => value.to_ascii_lower(),
+            ColorInput => ~"#000000",
+        }
+    }
+
+    /// Returns the value of a color well as red, green, and blue components.
+    pub fn color_value(&self) -> (u8, u8, u8) {
+        let value = self.value();
+        let component = |i: uint| -> u8 {
+            let digits = value.slice(i, i + 2);
+            u8::from_str_radix(digits, 16).unwrap_or_default(0u8)
+        };
+        (component(1), component(3), component(5))
+    }
+
+    /// Converts `value` to a number the way the control's type does. Dates become the number of
+    /// days since 1970-01-01, so that they can be compared and stepped like numbers.
+    fn parse_numeric_value(&self, value: &str) -> Option<f64> {
+        match self.input_type() {
+            RangeInput | NumberInput => parse_floating_point_number(value),
+            DateInput => parse_date(value).map_move(|(year, month, day)| {
+                days_from_civil(year, month, day) as f64
+            }),
+            TextInput | ColorInput => None,
+        }
+    }
+
+    /// Converts a number back to a value of the control's type.
+    fn serialize_numeric_value(&self, value: f64) -> ~str {
+        match self.input_type() {
+            DateInput => {
+                let (year, month, day) = civil_from_days(value.round() as i64);
+                fmt!("%04d-%02u-%02u", year as int, month, day)
+            }
+            _ => value.to_str(),
+        }
+    }
+
+    /// Returns the value of a number or date control as a number, or `None` if it is empty.
+    fn numeric_value(&self) -> Option<f64> {
+        self.parse_numeric_value(self.value())
+    }
+
+    fn numeric_attr(&self, name: &str) -> Option<f64> {
+        self.parent.parent.get_attr(name).chain(|value| self.parse_numeric_value(value))
+    }
+
+    fn range_minimum(&self) -> f64 {
+        self.parent.parent.get_float_attr("min").unwrap_or_default(0.0)
+    }
+
+    /// The maximum of a range control is never less than its minimum.
+    fn range_maximum(&self) -> f64 {
+        let maximum = self.parent.parent.get_float_attr("max").unwrap_or_default(100.0);
+        maximum.max(&self.range_minimum())
+    }
+
+    /// Returns the step between the values a range, number, or date control allows, or `None`
+    /// if it allows any value. Dates step by days.
+    fn step(&self) -> Option<f64> {
+        match self.parent.parent.get_attr("step") {
+            Some(step) if step.eq_ignore_ascii_case("any") => None,
+            _ => {
+                match self.parent.parent.get_float_attr("step") {
+                    Some(step) if step > 0.0 => Some(step),
+                    _ => Some(1.0),
+                }
+            }
+        }
+    }
+
+    /// Returns the value that steps are counted from: the minimum, if there is one, or zero.
+    fn step_base(&self) -> f64 {
+        match self.input_type() {
+            RangeInput => self.range_minimum(),
+            _ => self.numeric_attr("min").unwrap_or_default(0.0),
+        }
+    }
+
+    /// Clamps `value` to the range of a range control and rounds it to the nearest allowed value.
+    /// Steps are counted from the minimum, and ties round up.
+    fn sanitize_range_value(&self, value: f64) -> f64 {
+        let (minimum, maximum) = (self.range_minimum(), self.range_maximum());
+        let mut value = value.max(&minimum).min(&maximum);
+        for &step in self.step().iter() {
+            value = minimum + ((value - minimum) / step + 0.5).floor() * step;
+            if value > maximum {
+                value -= step;
+            }
+        }
+        value
+    }
+
+    /// Returns the value of a range control. Without a valid `value` attribute, the control sits
+    /// halfway between its minimum and maximum.
+    pub fn range_value(&self) -> f64 {
+        let value = match self.parent.parent.get_float_attr("value") {
+            Some(value) => value,
+            None => (self.range_minimum() + self.range_maximum()) / 2.0,
+        };
+        self.sanitize_range_value(value)
+    }
+
+    /// Sets the value of a range control, and returns whether it changed.
+    fn set_range_value(&mut self, value: f64) -> bool {
+        let value = self.sanitize_range_value(value);
+        let changed = value != self.range_value();
+        self.parent.parent.set_attr(&str(~"value"), &str(value.to_str()));
+        changed
+    }
+
+    /// Returns how far along its track the thumb of a range control is, from 0 at the minimum to
+    /// 1 at the maximum.
+    pub fn range_position(&self) -> f64 {
+        let (minimum, maximum) = (self.range_minimum(), self.range_maximum());
+        if maximum == minimum {
+            0.0
+        } else {
+            (self.range_value() - minimum) / (maximum - minimum)
+        }
+    }
+
+    /// Moves the thumb of a range control whose content box is `bounds` to the horizontal
+    /// position `x`, where the user pressed the mouse. The thumb is half as wide as the control is
+    /// high, and its center travels between the ends of the track, from the right in right-to-left
+    /// text. Returns whether the value changed.
+    pub fn move_range_thumb_to(&mut self, bounds: &Rect<Au>, x: Au, direction: TextDirection)
+                               -> bool {
+        let thumb_width = bounds.size.height.scale_by(0.5);
+        let travel = bounds.size.width - thumb_width;
+        if *travel <= 0 {
+            return false
+        }
+        let offset = x - bounds.origin.x - thumb_width.scale_by(0.5);
+        let position = (*offset as f64 / *travel as f64).max(&0.0).min(&1.0);
+        let position = match direction {
+            LeftToRight => position,
+            RightToLeft => 1.0 - position,
+        };
+        let (minimum, maximum) = (self.range_minimum(), self.range_maximum());
+        self.set_range_value(minimum + position * (maximum - minimum))
+    }
+
+    /// Moves the value of a range control by `steps` steps, as the arrow keys do. Controls that
+    /// allow any value move by 1. Returns whether the value changed.
+    pub fn step_range_value(&mut self, steps: int) -> bool {
+        let step = self.step().unwrap_or_default(1.0);
+        let value = self.range_value() + steps as f64 * step;
+        self.set_range_value(value)
+    }
+
+    /// Moves the value of a number or date control by `steps` steps, as its spin buttons and
+    /// `stepUp()` do. An empty control starts from zero, a value between two steps first moves to
+    /// the step in the direction it is going, and the result is clamped to the minimum and
+    /// maximum. Fails if the control allows any value, or isn't a number or date. No events are
+    /// fired, since `stepUp()` fires none.
+    pub fn step_numeric_value(&mut self, steps: int) -> Result<(), ()> {
+        let step = match (self.input_type(), self.step()) {
+            (NumberInput, Some(step)) | (DateInput, Some(step)) => step,
+            _ => return Err(()),
+        };
+        let base = self.step_base();
+        let value = self.numeric_value().unwrap_or_default(0.0);
+        let steps_from_base = (value - base) / step;
+        let aligned = if steps > 0 { steps_from_base.floor() } else { steps_from_base.ceil() };
+        let mut value = base + (aligned + steps as f64) * step;
+        for &minimum in self.numeric_attr("min").iter() {
+            value = value.max(&minimum);
+        }
+        for &maximum in self.numeric_attr("max").iter() {
+            if value > maximum {
+                value = base + ((maximum - base) / step).floor() * step;
+            }
+        }
+        let value = self.serialize_numeric_value(value);
+        self.parent.parent.set_attr(&str(~"value"), &str(value));
+        Ok(())
+    }
+
+    /// Presses the spin buttons of a number control whose content box is `bounds` at `point`.
+    /// The buttons sit at the inline end of the field, half as wide as it is high, with the up
+    /// button above the down button. Returns whether the value changed, which it doesn't if
+    /// `point` misses them.
+    pub fn press_spin_button_at(&mut self, bounds: &Rect<Au>, point: Point2D<Au>,
+                                direction: TextDirection)
+                                -> bool {
+        let button_width = bounds.size.height.scale_by(0.5);
+        let hit = match direction {
+            LeftToRight => point.x >= bounds.origin.x + bounds.size.width - button_width,
+            RightToLeft => point.x < bounds.origin.x + button_width,
+        };
+        if !hit {
+            return false
+        }
+        let in_upper_half = point.y < bounds.origin.y + bounds.size.height.scale_by(0.5);
+        let steps = if in_upper_half { 1 } else { -1 };
+        let old_value = self.numeric_value();
+        self.step_numeric_value(steps).is_ok() && self.numeric_value() != old_value
+    }
+
+    /// Sets the value the user chose in a date or color picker, and returns whether it changed.
+    pub fn set_picked_value(&mut self, value: ~str) -> bool {
+        let changed = self.parent.parent.get_attr("value") != Some(value.as_slice());
+        self.parent.parent.set_attr(&str(~"value"), &str(value));
+        changed
+    }
+
+    fn has_attr(&self, name: &str) -> bool {
+        self.parent.parent.get_attr(name).is_some()
+    }
+
+    /// Returns whether the control is a candidate for constraint validation: controls that are
+    /// disabled or read-only are never invalid.
+    pub fn will_validate(&self) -> bool {
+        !self.has_attr("disabled") && !self.has_attr("readonly")
+    }
+
+    /// Returns the constraints the value of the control violates, as a set of the flags in
+    /// `dom::validitystate`. Range controls and color wells always have a valid value, since
+    /// their values are sanitized into one.
+    pub fn validity_flags(&self) -> u8 {
+        let mut flags = 0;
+        let input_type = self.input_type();
+        if !self.custom_validity_error.is_empty() {
+            flags |= CUSTOM_ERROR;
+        }
+        if input_type == RangeInput || input_type == ColorInput {
+            return flags
+        }
+        if self.has_attr("required") && self.value().is_empty() {
+            flags |= VALUE_MISSING;
+        }
+        let value = match self.numeric_value() {
+            Some(value) => value,
+            None => return flags,
+        };
+        for &minimum in self.numeric_attr("min").iter() {
+            if value < minimum {
+                flags |= RANGE_UNDERFLOW;
+            }
+        }
+        for &maximum in self.numeric_attr("max").iter() {
+            if value > maximum {
+                flags |= RANGE_OVERFLOW;
+            }
+        }
+        for &step in self.step().iter() {
+            let steps = (value - self.step_base()) / step;
+            if (steps - steps.round()).abs() > 1e-9 {
+                flags |= STEP_MISMATCH;
+            }
+        }
+        flags
+    }
+
+    /// Returns whether the control matches `:invalid`. Controls that aren't candidates for
+    /// constraint validation match neither `:valid` nor `:invalid`.
+    pub fn is_invalid(&self) -> bool {
+        self.will_validate() && self.validity_flags() != 0
+    }
+
+    fn get_string_attr(&self, name: &str) -> DOMString {
+        str(self.parent.parent.get_attr(name).unwrap_or_default("").to_owned())
+    }
 }
 
 impl HTMLInputElement {
@@ -32,7 +361,7 @@ impl HTMLInputElement {
     }
 
     pub fn Autofocus(&self) -> bool {
-        false
+        self.parent.parent.get_attr("autofocus").is_some()
     }
 
     pub fn SetAutofocus(&mut self, _autofocus: bool, _rv: &mut ErrorResult) {
@@ -53,7 +382,7 @@ impl HTMLInputElement {
     }
 
     pub fn Disabled(&self) -> bool {
-        false
+        self.has_attr("disabled")
     }
 
     pub fn SetDisabled(&mut self, _disabled: bool, _rv: &mut ErrorResult) {
@@ -116,10 +445,11 @@ impl HTMLInputElement {
     }
 
     pub fn Max(&self) -> DOMString {
-        null_string
+        self.get_string_attr("max")
     }
 
-    pub fn SetMax(&mut self, _max: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetMax(&mut self, max: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"max"), max);
     }
 
     pub fn MaxLength(&self) -> i32 {
@@ -130,10 +460,11 @@ impl HTMLInputElement {
     }
 
     pub fn Min(&self) -> DOMString {
-        null_string
+        self.get_string_attr("min")
     }
 
-    pub fn SetMin(&mut self, _min: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetMin(&mut self, min: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"min"), min);
     }
 
     pub fn Multiple(&self) -> bool {
@@ -165,14 +496,14 @@ impl HTMLInputElement {
     }
 
     pub fn ReadOnly(&self) -> bool {
-        false
+        self.has_attr("readonly")
     }
 
     pub fn SetReadOnly(&mut self, _read_only: bool, _rv: &mut ErrorResult) {
     }
 
     pub fn Required(&self) -> bool {
-        false
+        self.has_attr("required")
     }
 
     pub fn SetRequired(&mut self, _required: bool, _rv: &mut ErrorResult) {
@@ -193,17 +524,25 @@ impl HTMLInputElement {
     }
 
     pub fn Step(&self) -> DOMString {
-        null_string
+        self.get_string_attr("step")
     }
 
-    pub fn SetStep(&mut self, _step: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetStep(&mut self, step: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"step"), step);
     }
 
     pub fn Type(&self) -> DOMString {
-        null_string
+        match self.input_type() {
+            TextInput => str(~"text"),
+            RangeInput => str(~"range"),
+            NumberInput => str(~"number"),
+            DateInput => str(~"date"),
+            ColorInput => str(~"color"),
+        }
     }
 
-    pub fn SetType(&mut self, _type: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetType(&mut self, type_: &DOMString, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"type"), type_);
     }
 
     pub fn DefaultValue(&self) -> DOMString {
@@ -214,10 +553,12 @@ impl HTMLInputElement {
     }
 
     pub fn Value(&self) -> DOMString {
-        null_string
+        str(self.value())
     }
 
-    pub fn SetValue(&mut self, _value: &DOMString, _rv: &mut ErrorResult) {
+    pub fn SetValue(&mut self, value: &DOMString, _rv: &mut ErrorResult) {
+        // Values are sanitized when they are read.
+        self.parent.parent.set_attr(&str(~"value"), value);
     }
 
     pub fn Width(&self) -> u32 {
@@ -227,22 +568,69 @@ impl HTMLInputElement {
     pub fn SetWidth(&mut self, _width: u32) {
     }
 
-    pub fn WillValidate(&self) -> bool {
-        false
+    pub fn StepUp(&mut self, n: i32, rv: &mut ErrorResult) {
+        if self.step_numeric_value(n as int).is_err() {
+            *rv = Err(FailureUnknown);
+        }
     }
 
-    pub fn SetWillValidate(&self, _will_validate: bool) {
+    pub fn StepDown(&mut self, n: i32, rv: &mut ErrorResult) {
+        if self.step_numeric_value(-n as int).is_err() {
+            *rv = Err(FailureUnknown);
+        }
     }
 
-    pub fn GetValidationMessage(&self, _rv: &mut ErrorResult) -> DOMString {
-        null_string
+    pub fn WillValidate(&self) -> bool {
+        self.will_validate()
     }
 
-    pub fn CheckValidity(&self) -> bool {
-        false
+    pub fn SetWillValidate(&self, _will_validate: bool) {
+    }
+
+    pub fn Validity(&self) -> @mut ValidityState {
+        @mut ValidityState::new(self.validity_flags())
     }
 
-    pub fn SetCustomValidity(&self, _error: &DOMString) {
+    /// Returns the message the user would be shown for the first constraint the control violates.
+    pub fn GetValidationMessage(&self, _rv: &mut ErrorResult) -> DOMString {
+        if !self.will_validate() {
+            return str(~"")
+        }
+        let flags = self.validity_flags();
+        let message = if flags & CUSTOM_ERROR != 0 {
+            self.custom_validity_error.clone()
+        } else if flags & VALUE_MISSING != 0 {
+            ~"Please fill out this field."
+        } else if flags & RANGE_UNDERFLOW != 0 {
+            fmt!("Please enter a value greater than or equal to %s.", self.Min().to_str())
+        } else if flags & RANGE_OVERFLOW != 0 {
+            fmt!("Please enter a value less than or equal to %s.", self.Max().to_str())
+        } else if flags & STEP_MISMATCH != 0 {
+            ~"Please enter a valid value."
+        } else {
+            ~""
+        };
+        str(message)
+    }
+
+    /// Fires `invalid` at the control if it fails.
+    pub fn CheckValidity(&self, abstract_self: AbstractNode<ScriptView>) -> bool {
+        let valid = !self.is_invalid();
+        if !valid {
+            let window = do self.parent.parent.parent.owner_doc.chain |doc| {
+                doc.with_base(|doc| doc.window)
+            };
+            for &window in window.iter() {
+                fire_event(window, abstract_self, "invalid", false, true);
+            }
+        }
+        valid
+    }
+
+    pub fn SetCustomValidity(&mut self, error: &DOMString) {
+        self.custom_validity_error = error.to_str();
+        // Restyle, since the control may have started or stopped matching `:invalid`.
+        self.parent.parent.parent.content_changed(None, true)
     }
 
     pub fn Select(&self) {
@@ -283,3 +671,122 @@ impl HTMLInputElement {
     pub fn SetUseMap(&mut self, _align: &DOMString, _rv: &mut ErrorResult) {
     }
 }
+
+/// Parses a valid floating-point number, such as `-1.5e3` or `.5`. Unlike attributes such as
+/// `min`, which ignore anything after the number, the whole of `value` must be the number.
+fn parse_floating_point_number(value: &str) -> Option<f64> {
+    let bytes = value.as_bytes();
+    let digits_from = |start: uint| -> uint {
+        let mut end = start;
+        while end < bytes.len() && bytes[end] >= '0' as u8 && bytes[end] <= '9' as u8 {
+            end += 1
+        }
+        end
+    };
+    let mut i = 0;
+    if i < bytes.len() && bytes[i] == '-' as u8 {
+        i += 1;
+    }
+    let integer_end = digits_from(i);
+    let mut has_digits = integer_end > i;
+    i = integer_end;
+    if i < bytes.len() && bytes[i] == '.' as u8 {
+        let fraction_end = digits_from(i + 1);
+        if fraction_end == i + 1 {
+            return None
+        }
+        has_digits = true;
+        i = fraction_end;
+    }
+    if !has_digits {
+        return None
+    }
+    if i < bytes.len() && (bytes[i] == 'e' as u8 || bytes[i] == 'E' as u8) {
+        let mut exponent_start = i + 1;
+        if exponent_start < bytes.len() && (bytes[exponent_start] == '-' as u8 ||
+                                            bytes[exponent_start] == '+' as u8) {
+            exponent_start += 1;
+        }
+        i = digits_from(exponent_start);
+        if i == exponent_start {
+            return None
+        }
+    }
+    if i != bytes.len() {
+        return None
+    }
+    // A number can start with its decimal point, which `from_str` doesn't accept.
+    let normalized = if value.starts_with(".") {
+        ~"0" + value
+    } else if value.starts_with("-.") {
+        ~"-0" + value.slice_from(1)
+    } else {
+        value.to_owned()
+    };
+    FromStr::from_str(normalized)
+}
+
+/// Parses a valid date string, `YYYY-MM-DD`, into its year, month, and day. Years have at least
+/// four digits and start at 1.
+fn parse_date(value: &str) -> Option<(i64, uint, uint)> {
+    let parts: ~[&str] = value.split_iter('-').collect();
+    if parts.len() != 3 || parts[0].len() < 4 || parts[1].len() != 2 || parts[2].len() != 2 {
+        return None
+    }
+    if !parts.iter().all(|part| part.iter().all(|ch| ch >= '0' && ch <= '9')) {
+        return None
+    }
+    let year: i64 = match FromStr::from_str(parts[0]) {
+        Some(year) if year > 0 => year,
+        _ => return None,
+    };
+    let month: uint = FromStr::from_str(parts[1]).unwrap();
+    let day: uint = FromStr::from_str(parts[2]).unwrap();
+    if month < 1 || month > 12 || day < 1 || day > days_in_month(year, month) {
+        return None
+    }
+    Some((year, month, day))
+}
+
+fn days_in_month(year: i64, month: uint) -> uint {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days from 1970-01-01 to the given date of the proleptic Gregorian
+/// calendar.
+fn days_from_civil(year: i64, month: uint, day: uint) -> i64 {
+    // Count years from March, so that leap days come at the end of the year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let year_of_era = year - era * 400;
+    let month_from_march = (if month > 2 { month - 3 } else { month + 9 }) as i64;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, uint, uint) {
+    let days = days + 719468;
+    let era = (if days >= 0 { days } else { days - 146096 }) / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 -
+                       day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as uint;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    let year = year_of_era + era * 400 + (if month <= 2 { 1 } else { 0 });
+    (year, month as uint, day)
+}
+
+/// Returns whether `value` is a valid simple color, `#` followed by six hex digits.
+fn is_simple_color(value: &str) -> bool {
+    value.len() == 7 && value.starts_with("#") &&
+        value.slice_from(1).iter().all(|ch| ch.is_digit_radix(16))
+}
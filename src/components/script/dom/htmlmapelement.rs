@@ -30,6 +30,6 @@ impl HTMLMapElement {
 
     pub fn Areas(&self) -> @mut HTMLCollection {
         let (scope, cx) = self.get_scope_and_cx();
-        HTMLCollection::new(~[], cx, scope)        
+        HTMLCollection::empty(cx, scope)        
     }
 }
@@ -2,18 +2,54 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::{DOMString, null_string, ErrorResult};
+use dom::bindings::utils::{DOMString, null_string, ErrorResult, FailureUnknown, str};
 use dom::element::ElementTypeId;
 use dom::htmlelement::HTMLElement;
+use dom::mediasource::MediaSource;
+
+use gfx::opts::{AutoplayAllowed, AutoplayAfterActivation, AutoplayBlocked};
 
 pub struct HTMLMediaElement {
     parent: HTMLElement,
+    /// Whether playback is paused. Media is paused until it autoplays or `play()` is called.
+    paused: bool,
+    /// Whether the audio is muted, once script has set it. Until then, the `muted` attribute
+    /// says.
+    muted: Option<bool>,
+    /// The media source that script feeds the element media through, if any.
+    src_object: Option<@mut MediaSource>,
 }
 
 impl HTMLMediaElement {
     pub fn new(type_id: ElementTypeId, tag_name: ~str) -> HTMLMediaElement {
         HTMLMediaElement {
-            parent: HTMLElement::new(type_id, tag_name)
+            parent: HTMLElement::new(type_id, tag_name),
+            paused: true,
+            muted: None,
+            src_object: None,
+        }
+    }
+
+    /// Returns whether the autoplay policy lets this element start playing. `play()` is allowed
+    /// once the user has activated the document, or always for muted media unless autoplay is
+    /// blocked; the `autoplay` attribute is never allowed when it is blocked.
+    pub fn is_allowed_to_play(&self, autoplay: bool) -> bool {
+        let (policy, activated) = match self.parent.parent.parent.owner_doc {
+            Some(owner) => owner.with_base(|doc| (doc.autoplay_policy(), doc.user_activated)),
+            None => return false,
+        };
+        match policy {
+            AutoplayAllowed => true,
+            AutoplayAfterActivation => activated || self.Muted(),
+            AutoplayBlocked => !autoplay && activated,
+        }
+    }
+
+    /// Starts playing if the element has the `autoplay` attribute and the policy allows it. Called
+    /// once the document has loaded.
+    pub fn autoplay_if_allowed(&mut self) {
+        if self.paused && self.Autoplay() && self.is_allowed_to_play(true) {
+            self.paused = false
         }
     }
 }
@@ -30,6 +66,19 @@ impl HTMLMediaElement {
         null_string
     }
 
+    pub fn GetSrcObject(&self) -> Option<@mut MediaSource> {
+        self.src_object
+    }
+
+    /// Lets go of the media source the element had, and opens the new one. A media source that
+    /// another element already has is left alone, and the element gets no source.
+    pub fn SetSrcObject(&mut self, src_object: Option<@mut MediaSource>) {
+        for &old in self.src_object.iter() {
+            old.detach()
+        }
+        self.src_object = src_object.filtered(|source| source.attach());
+    }
+
     pub fn CrossOrigin(&self) -> DOMString {
         null_string
     }
@@ -71,7 +120,7 @@ impl HTMLMediaElement {
     }
 
     pub fn Paused(&self) -> bool {
-        false
+        self.paused
     }
 
     pub fn DefaultPlaybackRate(&self) -> f64 {
@@ -93,10 +142,14 @@ impl HTMLMediaElement {
     }
 
     pub fn Autoplay(&self) -> bool {
-        false
+        self.parent.parent.get_attr("autoplay").is_some()
     }
 
-    pub fn SetAutoplay(&mut self, _autoplay: bool, _rv: &mut ErrorResult) {
+    // FIXME: Turning autoplay off needs attributes to be removable.
+    pub fn SetAutoplay(&mut self, autoplay: bool, _rv: &mut ErrorResult) {
+        if autoplay {
+            self.parent.parent.set_attr(&str(~"autoplay"), &str(~""))
+        }
     }
 
     pub fn Loop(&self) -> bool {
@@ -106,10 +159,22 @@ impl HTMLMediaElement {
     pub fn SetLoop(&mut self, _loop: bool, _rv: &mut ErrorResult) {
     }
 
-    pub fn Play(&self, _rv: &mut ErrorResult) {
+    // FIXME: This should return a promise that is rejected with a `NotAllowedError` when the
+    // autoplay policy doesn't allow playing, and resolved once playback starts, but the bindings
+    // don't support promises yet. For now it throws.
+    pub fn Play(&mut self, rv: &mut ErrorResult) {
+        if !self.paused {
+            return
+        }
+        if !self.is_allowed_to_play(false) {
+            *rv = Err(FailureUnknown);
+            return
+        }
+        self.paused = false
     }
 
-    pub fn Pause(&self, _rv: &mut ErrorResult) {
+    pub fn Pause(&mut self, _rv: &mut ErrorResult) {
+        self.paused = true
     }
 
     pub fn Controls(&self) -> bool {
@@ -127,16 +192,23 @@ impl HTMLMediaElement {
     }
 
     pub fn Muted(&self) -> bool {
-        false
+        self.muted.unwrap_or_default(self.DefaultMuted())
     }
 
-    pub fn SetMuted(&mut self, _muted: bool) {
+    // FIXME: Unmuting media that autoplayed muted should pause it unless the user has activated
+    // the document.
+    pub fn SetMuted(&mut self, muted: bool) {
+        self.muted = Some(muted)
     }
 
     pub fn DefaultMuted(&self) -> bool {
-        false
+        self.parent.parent.get_attr("muted").is_some()
     }
 
-    pub fn SetDefaultMuted(&mut self, _default_muted: bool, _rv: &mut ErrorResult) {
+    // FIXME: Unsetting needs attributes to be removable.
+    pub fn SetDefaultMuted(&mut self, default_muted: bool, _rv: &mut ErrorResult) {
+        if default_muted {
+            self.parent.parent.set_attr(&str(~"muted"), &str(~""))
+        }
     }
 }
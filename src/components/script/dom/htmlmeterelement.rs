@@ -2,53 +2,103 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::ErrorResult;
+use dom::bindings::utils::{ErrorResult, str};
 use dom::htmlelement::HTMLElement;
 
 pub struct HTMLMeterElement {
     parent: HTMLElement
 }
 
+/// Where the value of a meter falls relative to its optimum, which decides how the gauge is
+/// painted.
+#[deriving(Eq)]
+pub enum MeterRegion {
+    /// The value is in the same part of the range as the optimum.
+    OptimumRegion,
+    /// The value is in a part of the range next to the optimum.
+    SuboptimumRegion,
+    /// The value is at the other end of the range from the optimum.
+    EvenLessGoodRegion,
+}
+
+impl HTMLMeterElement {
+    fn float_attr_or(&self, name: &str, default: f64) -> f64 {
+        self.parent.parent.get_float_attr(name).unwrap_or_default(default)
+    }
+
+    /// Returns the fraction of the gauge that is filled.
+    pub fn position(&self) -> f64 {
+        let (min, max) = (self.Min(), self.Max());
+        if max > min { (self.Value() - min) / (max - min) } else { 0.0 }
+    }
+
+    pub fn region(&self) -> MeterRegion {
+        let (low, high, optimum, value) = (self.Low(), self.High(), self.Optimum(), self.Value());
+        if optimum < low {
+            if value <= low { OptimumRegion }
+            else if value <= high { SuboptimumRegion }
+            else { EvenLessGoodRegion }
+        } else if optimum > high {
+            if value >= high { OptimumRegion }
+            else if value >= low { SuboptimumRegion }
+            else { EvenLessGoodRegion }
+        } else {
+            if value >= low && value <= high { OptimumRegion } else { SuboptimumRegion }
+        }
+    }
+}
+
 impl HTMLMeterElement {
     pub fn Value(&self) -> f64 {
-        0.0
+        let (min, max) = (self.Min(), self.Max());
+        self.float_attr_or("value", 0.0).max(&min).min(&max)
     }
 
-    pub fn SetValue(&mut self, _value: f64, _rv: &mut ErrorResult) {
+    pub fn SetValue(&mut self, value: f64, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"value"), &str(value.to_str()));
     }
 
     pub fn Min(&self) -> f64 {
-        0.0
+        self.float_attr_or("min", 0.0)
     }
 
-    pub fn SetMin(&mut self, _min: f64, _rv: &mut ErrorResult) {
+    pub fn SetMin(&mut self, min: f64, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"min"), &str(min.to_str()));
     }
 
     pub fn Max(&self) -> f64 {
-        0.0
+        let min = self.Min();
+        self.float_attr_or("max", 1.0).max(&min)
     }
 
-    pub fn SetMax(&mut self, _max: f64, _rv: &mut ErrorResult) {
+    pub fn SetMax(&mut self, max: f64, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"max"), &str(max.to_str()));
     }
 
     pub fn Low(&self) -> f64 {
-        0.0
+        let (min, max) = (self.Min(), self.Max());
+        self.float_attr_or("low", min).max(&min).min(&max)
     }
 
-    pub fn SetLow(&mut self, _low: f64, _rv: &mut ErrorResult) {
+    pub fn SetLow(&mut self, low: f64, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"low"), &str(low.to_str()));
     }
 
     pub fn High(&self) -> f64 {
-        0.0
+        let (low, max) = (self.Low(), self.Max());
+        self.float_attr_or("high", max).max(&low).min(&max)
     }
 
-    pub fn SetHigh(&mut self, _high: f64, _rv: &mut ErrorResult) {
+    pub fn SetHigh(&mut self, high: f64, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"high"), &str(high.to_str()));
     }
 
     pub fn Optimum(&self) -> f64 {
-        0.0
+        let (min, max) = (self.Min(), self.Max());
+        self.float_attr_or("optimum", (min + max) / 2.0).max(&min).min(&max)
     }
 
-    pub fn SetOptimum(&mut self, _optimum: f64, _rv: &mut ErrorResult) {
+    pub fn SetOptimum(&mut self, optimum: f64, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"optimum"), &str(optimum.to_str()));
     }
-}
\ No newline at end of file
+}
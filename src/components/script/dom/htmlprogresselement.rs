@@ -2,33 +2,62 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::{ErrorResult};
+use dom::bindings::utils::{ErrorResult, str};
 use dom::htmlelement::HTMLElement;
 
 pub struct HTMLProgressElement {
     parent: HTMLElement,
 }
 
+impl HTMLProgressElement {
+    /// Returns true if the progress bar shows how far the task has gone, which it does when it
+    /// has a valid `value`. Otherwise it only shows that the task is in progress.
+    pub fn is_determinate(&self) -> bool {
+        self.parent.parent.get_float_attr("value").is_some()
+    }
+
+    /// Returns the fraction of the task that is complete, or `None` if the progress bar is
+    /// indeterminate.
+    pub fn position(&self) -> Option<f64> {
+        if self.is_determinate() {
+            Some(self.Value() / self.Max())
+        } else {
+            None
+        }
+    }
+}
+
 impl HTMLProgressElement {
     pub fn Value(&self) -> f64 {
-        0f64
+        match self.parent.parent.get_float_attr("value") {
+            Some(value) if value > 0.0 => value.min(&self.Max()),
+            _ => 0.0,
+        }
     }
 
-    pub fn SetValue(&mut self, _value: f64, _rv: &mut ErrorResult) {
+    pub fn SetValue(&mut self, value: f64, _rv: &mut ErrorResult) {
+        self.parent.parent.set_attr(&str(~"value"), &str(value.to_str()));
     }
 
     pub fn Max(&self) -> f64 {
-        0f64
+        match self.parent.parent.get_float_attr("max") {
+            Some(max) if max > 0.0 => max,
+            _ => 1.0,
+        }
     }
 
-    pub fn SetMax(&mut self, _max: f64, _rv: &mut ErrorResult) {
+    pub fn SetMax(&mut self, max: f64, _rv: &mut ErrorResult) {
+        // Values that aren't positive are ignored.
+        if max > 0.0 {
+            self.parent.parent.set_attr(&str(~"max"), &str(max.to_str()));
+        }
     }
 
     pub fn Position(&self) -> f64 {
-        0f64
+        self.position().unwrap_or_default(-1.0)
     }
 
     pub fn GetPositiom(&self, _rv: &mut ErrorResult) -> f64 {
-        0f64
+        self.Position()
     }
 }
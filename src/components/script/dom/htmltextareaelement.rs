@@ -11,7 +11,7 @@ pub struct HTMLTextAreaElement {
 
 impl HTMLTextAreaElement {
     pub fn Autofocus(&self) -> bool {
-        false
+        self.parent.parent.get_attr("autofocus").is_some()
     }
 
     pub fn SetAutofocus(&mut self, _autofocus: bool, _rv: &mut ErrorResult) {
@@ -0,0 +1,74 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::ImageBitmapBinding;
+use dom::bindings::utils::{BindingObject, CacheableWrapper, WrapperCache};
+use script_task::page_from_context;
+
+use extra::arc::Arc;
+use js::jsapi::{JSContext, JSObject, JSVal};
+use servo_net::image::base::Image;
+use std::cast;
+
+/// A decoded image that can be drawn on canvases without waiting. Its pixels are shared with the
+/// canvases it has been drawn on, and, being immutable, can be sent to other tasks.
+///
+/// FIXME: There are no workers to transfer image bitmaps to yet.
+pub struct ImageBitmap {
+    wrapper: WrapperCache,
+    /// The pixels of the bitmap, or `None` once it has been closed.
+    image: Option<Arc<~Image>>,
+}
+
+/// An image bitmap that has been decoded in another task, and the callback of
+/// `createImageBitmap()` to call with it. The image is `None` if it couldn't be decoded.
+pub struct ImageBitmapData {
+    callback: JSVal,
+    image: Option<Arc<~Image>>,
+}
+
+impl ImageBitmap {
+    pub fn new(image: Arc<~Image>, cx: *JSContext, scope: *JSObject) -> @mut ImageBitmap {
+        let bitmap = @mut ImageBitmap {
+            wrapper: WrapperCache::new(),
+            image: Some(image),
+        };
+        bitmap.wrap_object_shared(cx, scope);
+        bitmap
+    }
+
+    pub fn Width(&self) -> u32 {
+        self.image.map_default(0, |image| image.get().width as u32)
+    }
+
+    pub fn Height(&self) -> u32 {
+        self.image.map_default(0, |image| image.get().height as u32)
+    }
+
+    /// Releases the pixels of the bitmap, once the canvases it has been drawn on no longer need
+    /// them. A closed bitmap is zero by zero pixels and can't be drawn.
+    pub fn Close(&mut self) {
+        self.image = None
+    }
+}
+
+impl CacheableWrapper for ImageBitmap {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        ImageBitmapBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for ImageBitmap {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
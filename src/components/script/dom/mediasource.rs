@@ -0,0 +1,208 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Media sources, which let script feed a media element segments of media data it fetched
+//! itself, as adaptive streaming players do.
+//!
+//! A media source opens when a media element takes it as its `srcObject`. Script then adds a
+//! source buffer for each stream of media and appends segments to it, and ends the stream once
+//! all the segments have been appended.
+//!
+//! FIXME: There's no media decoder yet, so appended segments wait in their source buffers until
+//! one takes them, and no `sourceopen`, `sourceended` or `sourceclose` events are fired, since
+//! events aren't dispatched to the DOM.
+
+use dom::bindings::codegen::MediaSourceBinding;
+use dom::bindings::codegen::MediaSourceBinding::ReadyState;
+use dom::bindings::codegen::MediaSourceBinding::ReadyStateValues::{Closed, Open, Ended};
+use dom::bindings::utils::{DOMString, ErrorResult, FailureUnknown, WrapperCache};
+use dom::bindings::utils::{BindingObject, CacheableWrapper};
+use dom::sourcebuffer::SourceBuffer;
+use dom::window::Window;
+
+use js::jsapi::{JSContext, JSObject};
+use std::ascii::StrAsciiExt;
+use std::cast;
+use std::f64;
+use std::managed;
+
+/// The MIME types of the containers that source buffers accept. Their `codecs` parameters are
+/// ignored.
+static SUPPORTED_TYPES: &'static [&'static str] = &[
+    "audio/mp4",
+    "video/mp4",
+    "audio/webm",
+    "video/webm",
+    "audio/mpeg",
+];
+
+/// Returns whether source buffers accept segments of the given MIME type.
+pub fn is_type_supported(mime_type: &str) -> bool {
+    let essence = match mime_type.find(';') {
+        Some(i) => mime_type.slice_to(i),
+        None => mime_type,
+    }.trim().to_ascii_lower();
+    SUPPORTED_TYPES.iter().any(|&supported| supported == essence)
+}
+
+/// The state of a media source that its source buffers share, since appending to a buffer of an
+/// ended source opens it again.
+pub struct MediaSourceState {
+    ready_state: ReadyState,
+    /// The duration of the media in seconds, which is NaN until script sets it.
+    duration: f64,
+}
+
+impl MediaSourceState {
+    pub fn is_open(&self) -> bool {
+        match self.ready_state {
+            Open => true,
+            Closed | Ended => false,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        match self.ready_state {
+            Closed => true,
+            Open | Ended => false,
+        }
+    }
+
+    /// Opens an ended source again, as appending to one of its buffers does.
+    pub fn reopen_if_ended(&mut self) {
+        match self.ready_state {
+            Ended => self.ready_state = Open,
+            Closed | Open => {}
+        }
+    }
+}
+
+pub struct MediaSource {
+    owner: @mut Window,
+    wrapper: WrapperCache,
+    state: @mut MediaSourceState,
+    source_buffers: ~[@mut SourceBuffer],
+}
+
+impl MediaSource {
+    pub fn new(owner: @mut Window) -> @mut MediaSource {
+        let source = @mut MediaSource {
+            owner: owner,
+            wrapper: WrapperCache::new(),
+            state: @mut MediaSourceState {
+                ready_state: Closed,
+                duration: f64::NaN,
+            },
+            source_buffers: ~[],
+        };
+
+        let cx = unsafe {(*owner.page).js_info.get_ref().js_compartment.cx.ptr};
+        let scope = owner.get_wrappercache().get_wrapper();
+        source.wrap_object_shared(cx, scope);
+        source
+    }
+
+    pub fn Constructor(owner: @mut Window, _rv: &mut ErrorResult) -> @mut MediaSource {
+        MediaSource::new(owner)
+    }
+
+    /// Opens the media source for a media element that takes it as its source. Returns false if
+    /// another media element already has it.
+    pub fn attach(&mut self) -> bool {
+        if !self.state.is_closed() {
+            return false
+        }
+        self.state.ready_state = Open;
+        true
+    }
+
+    /// Closes the media source when its media element lets go of it, which removes its source
+    /// buffers.
+    pub fn detach(&mut self) {
+        self.state.ready_state = Closed;
+        self.state.duration = f64::NaN;
+        for buffer in self.source_buffers.iter() {
+            buffer.detach()
+        }
+        self.source_buffers = ~[];
+    }
+
+    /// Takes the segments appended to each source buffer since they were last taken, with the
+    /// MIME type of the buffer, for the media decoder to play.
+    pub fn take_segments(&mut self) -> ~[(~str, ~[~[u8]])] {
+        do self.source_buffers.map |buffer| {
+            (buffer.mime_type.clone(), buffer.take_segments())
+        }
+    }
+}
+
+impl MediaSource {
+    pub fn ReadyState(&self) -> ReadyState {
+        self.state.ready_state
+    }
+
+    pub fn Duration(&self) -> f64 {
+        self.state.duration
+    }
+
+    pub fn SetDuration(&mut self, duration: f64, rv: &mut ErrorResult) {
+        if duration.is_NaN() || duration < 0.0 || !self.state.is_open() {
+            *rv = Err(FailureUnknown);
+            return
+        }
+        self.state.duration = duration
+    }
+
+    // FIXME: These errors should be a `TypeError` for an empty type, a `NotSupportedError` for a
+    // type that isn't supported, and an `InvalidStateError` when the source isn't open.
+    pub fn AddSourceBuffer(&mut self, mime_type: &DOMString, rv: &mut ErrorResult)
+                           -> @mut SourceBuffer {
+        let mime_type = mime_type.to_str();
+        if !is_type_supported(mime_type) || !self.state.is_open() {
+            *rv = Err(FailureUnknown);
+        }
+        let buffer = SourceBuffer::new(mime_type, self.state);
+        if rv.is_ok() {
+            self.source_buffers.push(buffer);
+        }
+        buffer
+    }
+
+    pub fn RemoveSourceBuffer(&mut self, buffer: @mut SourceBuffer, rv: &mut ErrorResult) {
+        match self.source_buffers.iter().position(|&b| managed::mut_ptr_eq(b, buffer)) {
+            Some(index) => {
+                self.source_buffers.remove(index);
+                buffer.detach();
+            }
+            None => *rv = Err(FailureUnknown),
+        }
+    }
+
+    pub fn EndOfStream(&mut self, rv: &mut ErrorResult) {
+        if !self.state.is_open() {
+            *rv = Err(FailureUnknown);
+            return
+        }
+        // FIXME: The duration should become the end of the buffered media, once segments are
+        // demuxed and their timestamps are known.
+        self.state.ready_state = Ended;
+    }
+}
+
+impl CacheableWrapper for MediaSource {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        MediaSourceBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for MediaSource {
+    fn GetParentObject(&self, _cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        Some(self.owner as @mut CacheableWrapper)
+    }
+}
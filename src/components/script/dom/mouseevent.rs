@@ -6,6 +6,7 @@ use dom::bindings::codegen::MouseEventBinding;
 use dom::bindings::utils::{ErrorResult, DOMString};
 use dom::bindings::utils::{CacheableWrapper, WrapperCache, BindingObject, DerivedWrapper};
 use dom::eventtarget::EventTarget;
+use dom::node::{AbstractNode, ScriptView};
 use dom::uievent::UIEvent;
 use dom::window::Window;
 use dom::windowproxy::WindowProxy;
@@ -24,7 +25,10 @@ pub struct MouseEvent {
     alt_key: bool,
     meta_key: bool,
     button: u16,
-    related_target: Option<@mut EventTarget>
+    /// The node that the pointer left or entered, for `mouseover` and `mouseout`.
+    ///
+    /// FIXME: Other event targets can be related targets too.
+    related_target: Option<AbstractNode<ScriptView>>
 }
 
 impl MouseEvent {
@@ -32,7 +36,7 @@ impl MouseEvent {
                view: Option<@mut WindowProxy>, detail: i32, screen_x: i32,
                screen_y: i32, client_x: i32, client_y: i32, ctrl_key: bool,
                shift_key: bool, alt_key: bool, meta_key: bool, button: u16,
-               _buttons: u16, related_target: Option<@mut EventTarget>) -> MouseEvent {
+               _buttons: u16, related_target: Option<AbstractNode<ScriptView>>) -> MouseEvent {
         MouseEvent {
             parent: UIEvent::new(type_, can_bubble, cancelable, view, detail),
             screen_x: screen_x,
@@ -59,7 +63,7 @@ impl MouseEvent {
         @mut MouseEvent::new(type_, init.bubbles, init.cancelable, init.view, init.detail,
                              init.screenX, init.screenY, init.clientX, init.clientY,
                              init.ctrlKey, init.shiftKey, init.altKey, init.metaKey,
-                             init.button, init.buttons, init.relatedTarget)
+                             init.button, init.buttons, None)
     }
 
     pub fn ScreenX(&self) -> i32 {
@@ -103,7 +107,7 @@ impl MouseEvent {
         0
     }
 
-    pub fn GetRelatedTarget(&self) -> Option<@mut EventTarget> {
+    pub fn GetRelatedTarget(&self) -> Option<AbstractNode<ScriptView>> {
         self.related_target
     }
 
@@ -127,7 +131,7 @@ impl MouseEvent {
                           shiftKeyArg: bool,
                           metaKeyArg: bool,
                           buttonArg: u16,
-                          relatedTargetArg: Option<@mut EventTarget>,
+                          _relatedTargetArg: Option<@mut EventTarget>,
                           _rv: &mut ErrorResult) {
         self.parent.InitUIEvent(typeArg, canBubbleArg, cancelableArg, viewArg, detailArg);
         self.screen_x = screenXArg;
@@ -139,7 +143,7 @@ impl MouseEvent {
         self.shift_key = shiftKeyArg;
         self.meta_key = metaKeyArg;
         self.button = buttonArg;
-        self.related_target = relatedTargetArg;
+        self.related_target = None;
     }
 }
 
@@ -3,23 +3,49 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::utils::{WrapperCache, BindingObject, CacheableWrapper};
-use dom::bindings::utils::{DOMString, ErrorResult, str, null_string};
+use dom::bindings::utils::{DOMString, ErrorResult, FailureUnknown, str, null_string};
 use dom::bindings::codegen::NavigatorBinding;
-use script_task::{page_from_context};
+use script_task::{Page, page_from_context};
 
 use js::jsapi::{JSContext, JSObject};
+use servo_net::resource_task::{Beacon, ResourceTask};
+use servo_util::url::make_url;
 
 use std::cast;
 
+/// The most data that a single beacon may carry.
+static MAX_BEACON_SIZE: uint = 64 * 1024;
+
 pub struct Navigator {
-    wrapper: WrapperCache
+    wrapper: WrapperCache,
+    page: *mut Page,
+    resource_task: ResourceTask,
 }
 
 impl Navigator {
-    pub fn new() -> @mut Navigator {
+    pub fn new(page: *mut Page, resource_task: ResourceTask) -> @mut Navigator {
         @mut Navigator {
-            wrapper: WrapperCache::new()
+            wrapper: WrapperCache::new(),
+            page: page,
+            resource_task: resource_task,
+        }
+    }
+
+    /// Hands `data` to the resource task to post to `url`, which it does even if the page
+    /// unloads first. Returns false if the data is too large to send.
+    pub fn SendBeacon(&self, url: &DOMString, data: &DOMString, rv: &mut ErrorResult) -> bool {
+        let base_url = unsafe { (*self.page).url.map(|&(ref url, _)| url.clone()) };
+        let url = make_url(url.to_str(), base_url);
+        if url.scheme != ~"http" && url.scheme != ~"https" {
+            *rv = Err(FailureUnknown);
+            return false
+        }
+        let body = data.to_str().as_bytes().to_owned();
+        if body.len() > MAX_BEACON_SIZE {
+            return false
         }
+        self.resource_task.send(Beacon(url, body));
+        true
     }
 
     pub fn DoNotTrack(&self) -> DOMString {
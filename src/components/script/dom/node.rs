@@ -6,27 +6,50 @@
 
 use dom::bindings::node;
 use dom::bindings::utils::{WrapperCache, DOMString, null_string, str, ErrorResult};
+use dom::bindings::utils::FailureUnknown;
 use dom::bindings::utils::{BindingObject, CacheableWrapper, rust_box};
 use dom::bindings;
 use dom::characterdata::CharacterData;
+use dom::comment::Comment;
 use dom::document::AbstractDocument;
+use dom::documentfragment::DocumentFragment;
 use dom::element::{Element, ElementTypeId, HTMLImageElementTypeId, HTMLIframeElementTypeId};
-use dom::element::{HTMLStyleElementTypeId};
+use dom::element::{HTMLInputElementTypeId, HTMLMeterElementTypeId, HTMLProgressElementTypeId};
+use dom::element::{HTMLStyleElementTypeId, HTMLAnchorElementTypeId, HTMLAreaElementTypeId};
+use dom::element::{HTMLButtonElementTypeId, HTMLSelectElementTypeId, HTMLTextAreaElementTypeId};
+use dom::element::{HTMLAudioElementTypeId, HTMLVideoElementTypeId, HTMLCanvasElementTypeId};
+use dom::element::{ElementState, HoverState, ActiveState, FocusState};
+use dom::event::Event;
+use dom::eventdispatcher::dispatch_event;
+use dom::eventtarget::EventListener;
+use dom::htmlcanvaselement::HTMLCanvasElement;
 use dom::htmlimageelement::HTMLImageElement;
 use dom::htmliframeelement::HTMLIFrameElement;
+use dom::htmlinputelement::{HTMLInputElement, TextInput};
+use dom::htmlmediaelement::HTMLMediaElement;
+use dom::htmlmeterelement::HTMLMeterElement;
+use dom::htmlprogresselement::HTMLProgressElement;
 use dom::text::Text;
+use style::properties::ComputedValues;
 
+use std::ascii::StrAsciiExt;
 use std::cast;
 use std::cast::transmute;
 use std::libc::c_void;
 use extra::arc::Arc;
-use js::jsapi::{JSObject, JSContext};
+use js::glue::RUST_JSVAL_IS_OBJECT;
+use js::jsapi::{JSObject, JSContext, JSVal};
 use js::rust::Compartment;
 use netsurfcss::util::VoidPtrLike;
 use newcss::complete::CompleteSelectResults;
 use servo_util::tree::{TreeNode, TreeNodeRef};
 use servo_util::range::Range;
+use gfx::color::Color;
 use gfx::display_list::DisplayList;
+use gfx::geometry::Au;
+use geom::size::Size2D;
+use gfx::text::script::{TextDirection, LeftToRight, RightToLeft, first_strong_direction};
+use html::hubbub_html_parser::build_element_from_tag;
 
 //
 // The basic Node structure
@@ -89,6 +112,18 @@ pub struct Node<View> {
     /// The document that this node belongs to.
     owner_doc: Option<AbstractDocument>,
 
+    /// The listeners that script has added to this node, in the order it added them.
+    event_listeners: ~[EventListener],
+
+    /// Whether script has changed this node, its attributes or its children since layout last
+    /// restyled it, so that the next restyle must match the selectors of its subtree again.
+    /// Layout clears this once it has.
+    dirty: bool,
+
+    /// Whether a node in the subtree of this node is dirty, so that restyles must look into it.
+    /// The ancestors of a dirty node always have this set.
+    has_dirty_descendants: bool,
+
     /// Layout information. Only the layout task may touch this data.
     priv layout_data: LayoutData,
 }
@@ -98,10 +133,18 @@ pub struct Node<View> {
 pub enum NodeTypeId {
     DoctypeNodeTypeId,
     CommentNodeTypeId,
+    DocumentFragmentNodeTypeId,
     ElementNodeTypeId(ElementTypeId),
     TextNodeTypeId,
 }
 
+/// The valid states of the `dir` attribute.
+enum DirAttribute {
+    DirLtr,
+    DirRtl,
+    DirAuto,
+}
+
 impl<View> Clone for AbstractNode<View> {
     fn clone(&self) -> AbstractNode<View> {
         *self
@@ -214,6 +257,308 @@ impl<'self, View> AbstractNode<View> {
         self.parent_node().is_none()
     }
 
+    /// Returns whether this node needs to be restyled because script has changed it.
+    pub fn is_dirty(self) -> bool {
+        self.with_base(|b| b.dirty)
+    }
+
+    /// Returns whether a node in the subtree of this node needs to be restyled.
+    pub fn has_dirty_descendants(self) -> bool {
+        self.with_base(|b| b.has_dirty_descendants)
+    }
+
+    /// Marks this node, and so its subtree, as needing to be restyled, and its ancestors as having
+    /// a descendant that does, so that the next restyle finds it without walking the whole
+    /// document.
+    pub fn dirty(self) {
+        self.with_mut_base(|b| b.dirty = true);
+        let mut ancestor = self.parent_node();
+        while ancestor.is_some() {
+            let node = ancestor.unwrap();
+            // The ancestors of an ancestor that is marked are marked already.
+            if node.has_dirty_descendants() {
+                break
+            }
+            node.set_has_dirty_descendants(true);
+            ancestor = node.parent_node();
+        }
+    }
+
+    /// Notes that this node has been restyled.
+    pub fn clear_dirty(self) {
+        self.with_mut_base(|b| b.dirty = false)
+    }
+
+    /// Sets whether a node in the subtree of this node needs to be restyled, once a restyle has
+    /// looked into the subtree.
+    pub fn set_has_dirty_descendants(self, has_dirty_descendants: bool) {
+        self.with_mut_base(|b| b.has_dirty_descendants = has_dirty_descendants)
+    }
+
+    /// Returns the language of this node: the value of the `lang` attribute of the nearest
+    /// inclusive ancestor that has one, or else the document's default language. An empty `lang`
+    /// attribute means that the language is unknown.
+    pub fn language(self) -> Option<~str> {
+        let mut current = Some(self);
+        while current.is_some() {
+            let node = current.unwrap();
+            if node.is_element() {
+                let lang = do node.with_imm_element |element| {
+                    element.get_attr("xml:lang").or(element.get_attr("lang")).map(|lang| {
+                        lang.trim().to_owned()
+                    })
+                };
+                match lang {
+                    Some(lang) => return if lang.is_empty() { None } else { Some(lang) },
+                    None => {}
+                }
+            }
+            current = node.parent_node();
+        }
+
+        do self.with_base(|b| b.owner_doc).chain |doc| {
+            doc.with_base(|doc| doc.content_language.clone())
+        }
+    }
+
+    /// Returns whether this node is the focused element of its document.
+    pub fn is_focused(self) -> bool {
+        let focused = do self.with_base(|b| b.owner_doc).chain |doc| {
+            doc.with_base(|doc| doc.focused)
+        };
+        match focused {
+            Some(focused) => unsafe { focused.raw_object() as uint == self.raw_object() as uint },
+            None => false,
+        }
+    }
+
+    /// Returns whether the pointer is over this element or one of its descendants.
+    pub fn is_hovered(self) -> bool {
+        let hovered = do self.with_base(|b| b.owner_doc).chain |doc| {
+            doc.with_base(|doc| doc.hovered)
+        };
+        self.is_inclusive_ancestor_of(hovered)
+    }
+
+    /// Returns whether this element or one of its descendants is being activated.
+    pub fn is_active(self) -> bool {
+        let active = do self.with_base(|b| b.owner_doc).chain |doc| {
+            doc.with_base(|doc| doc.active)
+        };
+        self.is_inclusive_ancestor_of(active)
+    }
+
+    /// Returns the states of this element that the dynamic pseudo-classes match.
+    pub fn element_state(self) -> ElementState {
+        let mut state = ElementState::none();
+        if self.is_hovered() {
+            state = state.add(HoverState)
+        }
+        if self.is_active() {
+            state = state.add(ActiveState)
+        }
+        if self.is_focused() {
+            state = state.add(FocusState)
+        }
+        state
+    }
+
+    /// Returns whether `node` is this node or one of its descendants. `node` may be seen from
+    /// another view than this node.
+    fn is_inclusive_ancestor_of<OtherView>(self, node: Option<AbstractNode<OtherView>>) -> bool {
+        let mut current = node;
+        while current.is_some() {
+            let node = current.unwrap();
+            if unsafe { node.raw_object() as uint == self.raw_object() as uint } {
+                return true
+            }
+            current = node.parent_node();
+        }
+        false
+    }
+
+    /// Returns whether this element can be focused: links with an `href`, form controls that
+    /// aren't disabled or hidden inputs, and any element with a valid `tabindex`.
+    ///
+    /// TODO: Elements that aren't being rendered shouldn't be focusable.
+    pub fn is_focusable(self) -> bool {
+        if !self.is_element() {
+            return false
+        }
+        do self.with_imm_element |element| {
+            let focusable_by_default = match self.type_id() {
+                ElementNodeTypeId(HTMLAnchorElementTypeId) |
+                ElementNodeTypeId(HTMLAreaElementTypeId) => element.get_attr("href").is_some(),
+                ElementNodeTypeId(HTMLInputElementTypeId) => {
+                    let hidden = match element.get_attr("type") {
+                        Some(type_) => type_.eq_ignore_ascii_case("hidden"),
+                        None => false,
+                    };
+                    !hidden && element.get_attr("disabled").is_none()
+                }
+                ElementNodeTypeId(HTMLButtonElementTypeId) |
+                ElementNodeTypeId(HTMLSelectElementTypeId) |
+                ElementNodeTypeId(HTMLTextAreaElementTypeId) => {
+                    element.get_attr("disabled").is_none()
+                }
+                _ => false,
+            };
+            focusable_by_default || element.get_int_attr("tabindex").is_some()
+        }
+    }
+
+    /// Returns where this element comes in sequential focus navigation, or `None` if it is
+    /// skipped. Elements with a positive index come first, in increasing order of their index,
+    /// and then those with index zero, in tree order. Elements with a negative `tabindex` can be
+    /// focused, but not by sequential navigation.
+    pub fn sequential_focus_index(self) -> Option<i32> {
+        if !self.is_focusable() {
+            return None
+        }
+        match self.with_imm_element(|element| element.get_int_attr("tabindex")) {
+            Some(index) if index < 0 => None,
+            Some(index) => Some(index),
+            None => Some(0),
+        }
+    }
+
+    /// Returns whether the text in this element can be edited: it is a `textarea`, or its nearest
+    /// ancestor-or-self with a `contenteditable` attribute makes it editable.
+    pub fn is_editable(self) -> bool {
+        let mut node = Some(self);
+        while node.is_some() {
+            let element = node.unwrap();
+            if element.is_element() {
+                if element.type_id() == ElementNodeTypeId(HTMLTextAreaElementTypeId) {
+                    return true
+                }
+                let editable = do element.with_imm_element |element| {
+                    element.get_attr("contenteditable").map(|value| {
+                        value.is_empty() || value.eq_ignore_ascii_case("true")
+                    })
+                };
+                match editable {
+                    Some(editable) => return editable,
+                    None => {}
+                }
+            }
+            node = element.parent_node();
+        }
+        false
+    }
+
+    /// Returns the editing host that edits of this element change: text inputs and `textarea`s are
+    /// editing hosts of their own, and editable content belongs to the outermost editable element
+    /// around it. Returns `None` if this element can't be edited.
+    pub fn editing_host(self) -> Option<AbstractNode<View>> {
+        if self.is_input_element() {
+            let input_type = self.with_imm_input_element(|input| input.input_type());
+            return if input_type == TextInput { Some(self) } else { None }
+        }
+        if self.type_id() == ElementNodeTypeId(HTMLTextAreaElementTypeId) {
+            return Some(self)
+        }
+        if !self.is_editable() {
+            return None
+        }
+        let mut host = self;
+        loop {
+            match host.parent_node() {
+                Some(parent) if parent.is_editable() => host = parent,
+                _ => return Some(host),
+            }
+        }
+    }
+
+    /// Returns whether the spelling of the text in this element is checked when it is editable.
+    /// The nearest ancestor-or-self with a `spellcheck` attribute decides, and editable text is
+    /// checked by default.
+    pub fn is_spellchecked(self) -> bool {
+        let mut node = Some(self);
+        while node.is_some() {
+            let element = node.unwrap();
+            if element.is_element() {
+                let spellcheck = do element.with_imm_element |element| {
+                    element.get_attr("spellcheck").map(|value| {
+                        value.is_empty() || value.eq_ignore_ascii_case("true")
+                    })
+                };
+                match spellcheck {
+                    Some(spellcheck) => return spellcheck,
+                    None => {}
+                }
+            }
+            node = element.parent_node();
+        }
+        self.is_editable()
+    }
+
+    /// Returns the directionality of this node, which is set by the `dir` attribute of the nearest
+    /// inclusive ancestor element that has a valid one. Nodes without such an ancestor are
+    /// left-to-right.
+    pub fn directionality(self) -> TextDirection {
+        let mut current = Some(self);
+        while current.is_some() {
+            let node = current.unwrap();
+            if node.is_element() {
+                match node.dir_attribute() {
+                    Some(DirLtr) => return LeftToRight,
+                    Some(DirRtl) => return RightToLeft,
+                    Some(DirAuto) => {
+                        // An element with no strong text takes its parent's directionality.
+                        match node.auto_directionality() {
+                            Some(direction) => return direction,
+                            None => {}
+                        }
+                    }
+                    None => {}
+                }
+            }
+            current = node.parent_node();
+        }
+        LeftToRight
+    }
+
+    /// Returns the state of the `dir` attribute of this element, or `None` if it is missing or
+    /// has an invalid value. `<bdi>` elements default to `dir=auto`.
+    fn dir_attribute(self) -> Option<DirAttribute> {
+        do self.with_imm_element |element| {
+            match element.get_attr("dir") {
+                Some(dir) if dir.eq_ignore_ascii_case("ltr") => Some(DirLtr),
+                Some(dir) if dir.eq_ignore_ascii_case("rtl") => Some(DirRtl),
+                Some(dir) if dir.eq_ignore_ascii_case("auto") => Some(DirAuto),
+                _ if element.tag_name.eq_ignore_ascii_case("bdi") => Some(DirAuto),
+                _ => None,
+            }
+        }
+    }
+
+    /// Implements the `dir=auto` heuristic: returns the direction of the first character with a
+    /// strong direction in the text of this node's descendants, skipping the contents of elements
+    /// that set their own directionality or that do not hold rendered text.
+    fn auto_directionality(self) -> Option<TextDirection> {
+        for child in self.children() {
+            let direction = if child.is_text() {
+                do child.with_imm_text |text| {
+                    first_strong_direction(text.parent.data)
+                }
+            } else if child.is_element() {
+                let skip = child.dir_attribute().is_some() || do child.with_imm_element |element| {
+                    ["script", "style", "textarea"].iter().any(|name| {
+                        element.tag_name.eq_ignore_ascii_case(*name)
+                    })
+                };
+                if skip { None } else { child.auto_directionality() }
+            } else {
+                None
+            };
+            if direction.is_some() {
+                return direction
+            }
+        }
+        None
+    }
+
     //
     // Downcasting borrows
     //
@@ -272,6 +617,10 @@ impl<'self, View> AbstractNode<View> {
         self.type_id() == TextNodeTypeId
     }
 
+    pub fn is_document_fragment(self) -> bool {
+        self.type_id() == DocumentFragmentNodeTypeId
+    }
+
     pub fn with_imm_text<R>(self, f: &fn(&Text) -> R) -> R {
         if !self.is_text() {
             fail!(~"node is not text");
@@ -345,6 +694,76 @@ impl<'self, View> AbstractNode<View> {
         self.transmute_mut(f)
     }
 
+    pub fn is_progress_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLProgressElementTypeId)
+    }
+
+    pub fn with_imm_progress_element<R>(self, f: &fn(&HTMLProgressElement) -> R) -> R {
+        if !self.is_progress_element() {
+            fail!(~"node is not a progress element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn is_canvas_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLCanvasElementTypeId)
+    }
+
+    pub fn with_imm_canvas_element<R>(self, f: &fn(&HTMLCanvasElement) -> R) -> R {
+        if !self.is_canvas_element() {
+            fail!(~"node is not a canvas element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn with_mut_canvas_element<R>(self, f: &fn(&mut HTMLCanvasElement) -> R) -> R {
+        if !self.is_canvas_element() {
+            fail!(~"node is not a canvas element");
+        }
+        self.transmute_mut(f)
+    }
+
+    pub fn is_meter_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLMeterElementTypeId)
+    }
+
+    pub fn with_imm_meter_element<R>(self, f: &fn(&HTMLMeterElement) -> R) -> R {
+        if !self.is_meter_element() {
+            fail!(~"node is not a meter element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn is_input_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLInputElementTypeId)
+    }
+
+    pub fn with_imm_input_element<R>(self, f: &fn(&HTMLInputElement) -> R) -> R {
+        if !self.is_input_element() {
+            fail!(~"node is not an input element");
+        }
+        self.transmute(f)
+    }
+
+    pub fn with_mut_input_element<R>(self, f: &fn(&mut HTMLInputElement) -> R) -> R {
+        if !self.is_input_element() {
+            fail!(~"node is not an input element");
+        }
+        self.transmute_mut(f)
+    }
+
+    pub fn is_media_element(self) -> bool {
+        self.type_id() == ElementNodeTypeId(HTMLAudioElementTypeId) ||
+            self.type_id() == ElementNodeTypeId(HTMLVideoElementTypeId)
+    }
+
+    pub fn with_mut_media_element<R>(self, f: &fn(&mut HTMLMediaElement) -> R) -> R {
+        if !self.is_media_element() {
+            fail!(~"node is not a media element");
+        }
+        self.transmute_mut(f)
+    }
+
     pub fn is_style_element(self) -> bool {
         self.type_id() == ElementNodeTypeId(HTMLStyleElementTypeId)
     }
@@ -400,6 +819,168 @@ impl<View> Iterator<AbstractNode<View>> for AbstractNodeChildrenIterator<View> {
     }
 }
 
+impl AbstractNode<ScriptView> {
+    /// Returns the index of this node among the children of its parent.
+    pub fn index(self) -> uint {
+        let mut index = 0;
+        let mut current = self.prev_sibling();
+        while current.is_some() {
+            index += 1;
+            current = current.unwrap().prev_sibling();
+        }
+        index
+    }
+
+    /// Returns the length of this node, which the offsets of boundary points in it go up to: the
+    /// number of UTF-16 code units of its data, or the number of its children.
+    pub fn length(self) -> uint {
+        if self.is_characterdata() {
+            self.with_imm_characterdata(|data| data.Length() as uint)
+        } else {
+            self.children().len()
+        }
+    }
+
+    /// Returns whether this node is `other` or one of its ancestors.
+    pub fn is_inclusive_ancestor_of(self, other: AbstractNode<ScriptView>) -> bool {
+        let mut current = Some(other);
+        while current.is_some() {
+            let node = current.unwrap();
+            if node == self {
+                return true
+            }
+            current = node.parent_node();
+        }
+        false
+    }
+
+    /// Inserts `child`, which must have no parent, before `before`, or at the end if `before` is
+    /// `None`. A document fragment is emptied into this node instead. The live ranges of the
+    /// document move their boundary points past the new children.
+    pub fn insert_child(self,
+                        child: AbstractNode<ScriptView>,
+                        before: Option<AbstractNode<ScriptView>>) {
+        let children = if child.is_document_fragment() {
+            let children: ~[AbstractNode<ScriptView>] = child.children().collect();
+            for &node in children.iter() {
+                child.remove_child(node);
+            }
+            children
+        } else {
+            ~[child]
+        };
+        if children.is_empty() {
+            return
+        }
+
+        let index = match before {
+            Some(before) => before.index(),
+            None => self.length(),
+        };
+        let owner_doc = self.with_base(|base| base.owner_doc);
+        for &node in children.iter() {
+            self.insert_child_before(node, before);
+            for doc in owner_doc.iter() {
+                node.with_mut_base(|base| base.add_to_doc(*doc));
+            }
+        }
+        for doc in owner_doc.iter() {
+            doc.with_base(|doc| doc.children_inserted(self, index, children.len()));
+        }
+        self.children_changed(children.as_slice());
+    }
+
+    /// Replaces all the children of this node with those of `fragment`, a document fragment, at
+    /// once, so that script sees no tree in between and the document is reflowed only once. The
+    /// live ranges of the document move their boundary points out of the old children.
+    pub fn replace_all_children(self, fragment: AbstractNode<ScriptView>) {
+        let owner_doc = self.with_base(|base| base.owner_doc);
+        let old_children: ~[AbstractNode<ScriptView>] = self.children().collect();
+        for &child in old_children.iter() {
+            for doc in owner_doc.iter() {
+                doc.with_base(|doc| doc.node_removed(child, self, 0));
+            }
+            self.remove_child(child);
+        }
+        if fragment.first_child().is_some() {
+            self.insert_child(fragment, None)
+        } else {
+            self.children_changed([])
+        }
+    }
+
+    /// Removes this node from its parent, if it has one. The live ranges of the document move
+    /// their boundary points out of it first.
+    pub fn remove_from_parent(self) {
+        let parent = match self.parent_node() {
+            Some(parent) => parent,
+            None => return,
+        };
+        let owner_doc = parent.with_base(|base| base.owner_doc);
+        for doc in owner_doc.iter() {
+            doc.with_base(|doc| doc.node_removed(self, parent, self.index()));
+        }
+        parent.remove_child(self);
+        parent.children_changed([]);
+    }
+
+    /// Marks for restyling what could change now that `inserted` have been inserted among the
+    /// children of this node, or children have been removed from it, and lays out the document
+    /// again.
+    fn children_changed(self, inserted: &[AbstractNode<ScriptView>]) {
+        match self.with_base(|base| base.owner_doc) {
+            Some(doc) => doc.with_base(|doc| doc.children_changed(self, inserted)),
+            None => self.dirty(),
+        }
+    }
+
+    /// Returns a copy of this node, with copies of its descendants if `deep` is true. The copy
+    /// has no parent, but belongs to the same document.
+    ///
+    /// FIXME: Only the attributes of elements are copied, not the state of form controls.
+    pub fn clone_node(self, cx: *JSContext, deep: bool) -> AbstractNode<ScriptView> {
+        let copy = match self.type_id() {
+            TextNodeTypeId => {
+                let data = self.with_imm_characterdata(|data| data.data.clone());
+                unsafe { Node::as_abstract_node(cx, @Text::new(data)) }
+            }
+            CommentNodeTypeId => {
+                let data = self.with_imm_characterdata(|data| data.data.clone());
+                unsafe { Node::as_abstract_node(cx, @Comment::new(data)) }
+            }
+            DocumentFragmentNodeTypeId => {
+                unsafe { Node::as_abstract_node(cx, @DocumentFragment::new()) }
+            }
+            ElementNodeTypeId(_) => {
+                let (tag_name, attrs) = do self.with_imm_element |element| {
+                    let attrs: ~[(~str, ~str, ~str)] = element.attrs.iter().map(|attr| {
+                        (attr.namespace.clone(), attr.name.clone(), attr.value.clone())
+                    }).collect();
+                    (element.tag_name.clone(), attrs)
+                };
+                let copy = build_element_from_tag(cx, tag_name);
+                do copy.as_mut_element |element| {
+                    for &(ref namespace, ref name, ref value) in attrs.iter() {
+                        element.set_attr_ns(namespace.as_slice(),
+                                            &str(name.clone()),
+                                            &str(value.clone()));
+                    }
+                }
+                copy
+            }
+            DoctypeNodeTypeId => fail!("can't clone a doctype yet"),
+        };
+        let owner_doc = self.with_base(|base| base.owner_doc);
+        copy.with_mut_base(|base| base.owner_doc = owner_doc);
+        if deep {
+            for child in self.children() {
+                copy.insert_child(child.clone_node(cx, true), None);
+            }
+        }
+        copy
+    }
+}
+
 impl Node<ScriptView> {
     pub unsafe fn as_abstract_node<N>(cx: *JSContext, node: @N) -> AbstractNode<ScriptView> {
         // This surrenders memory management of the node!
@@ -438,16 +1019,55 @@ impl Node<ScriptView> {
 
             owner_doc: None,
 
+            event_listeners: ~[],
+
+            dirty: false,
+            has_dirty_descendants: false,
+
             layout_data: LayoutData::new(),
         }
     }
 
+    /// Marks this node dirty after script has changed it or its attributes, so that the next
+    /// reflow restyles it, and asks its document for that reflow. The later siblings of the node
+    /// are marked too if `with_later_siblings` is true, since selectors with sibling combinators
+    /// may match them because of the attributes of this node.
+    ///
+    /// `node` is this node, if the caller has it; otherwise the node that the accessors of
+    /// `AbstractNode` set while they lend this one out is used.
+    ///
+    /// FIXME: The bindings don't hand the node to the methods that don't ask for it, so when it
+    /// isn't known the whole document is restyled.
+    pub fn content_changed(&self,
+                           node: Option<AbstractNode<ScriptView>>,
+                           with_later_siblings: bool) {
+        match node.or(self.abstract) {
+            Some(node) => {
+                let mut current = Some(node);
+                while current.is_some() {
+                    let node = current.unwrap();
+                    node.dirty();
+                    current = if with_later_siblings { node.next_sibling() } else { None };
+                }
+            }
+            None => {
+                for doc in self.owner_doc.iter() {
+                    doc.with_base(|doc| doc.root.dirty())
+                }
+            }
+        }
+        for doc in self.owner_doc.iter() {
+            doc.with_base(|doc| doc.content_changed())
+        }
+    }
+
     pub fn getNodeType(&self) -> i32 {
         match self.type_id {
             ElementNodeTypeId(_) => 1,
             TextNodeTypeId       => 3,
             CommentNodeTypeId    => 8,
-            DoctypeNodeTypeId    => 10
+            DoctypeNodeTypeId    => 10,
+            DocumentFragmentNodeTypeId => 11,
         }
     }
 
@@ -524,7 +1144,7 @@ impl Node<ScriptView> {
 
     pub fn GetTextContent(&self) -> DOMString {
         match self.type_id {
-          DoctypeNodeTypeId | ElementNodeTypeId(*) => {
+          DoctypeNodeTypeId | DocumentFragmentNodeTypeId | ElementNodeTypeId(*) => {
             let mut content = ~"";
             for node in self.abstract.unwrap().traverse_preorder() {
                 if node.is_text() {
@@ -609,6 +1229,54 @@ impl Node<ScriptView> {
     pub fn HasAttributes(&self) -> bool {
         false
     }
+
+    /// Adds a listener for events of the given type, unless it has already been added. Values
+    /// that aren't objects are ignored.
+    #[fixed_stack_segment]
+    pub fn AddEventListener(&mut self,
+                            _cx: *JSContext,
+                            type_: &DOMString,
+                            listener: JSVal,
+                            capture: bool) {
+        if unsafe { RUST_JSVAL_IS_OBJECT(listener) } == 0 {
+            return
+        }
+        let listener = EventListener {
+            type_: type_.to_str(),
+            callback: listener,
+            capture: capture,
+        };
+        if !self.event_listeners.contains(&listener) {
+            self.event_listeners.push(listener)
+        }
+    }
+
+    pub fn RemoveEventListener(&mut self,
+                               _cx: *JSContext,
+                               type_: &DOMString,
+                               listener: JSVal,
+                               capture: bool) {
+        let listener = EventListener {
+            type_: type_.to_str(),
+            callback: listener,
+            capture: capture,
+        };
+        match self.event_listeners.iter().position(|l| *l == listener) {
+            Some(index) => { self.event_listeners.remove(index); }
+            None => {}
+        }
+    }
+
+    /// Dispatches an event that script made at this node, and returns whether no listener
+    /// cancelled it. An event that is already being dispatched can't be dispatched again.
+    pub fn DispatchEvent(&self, cx: *JSContext, event: @mut Event, rv: &mut ErrorResult) -> bool {
+        if event.dispatching {
+            *rv = Err(FailureUnknown);
+            return false
+        }
+        event.trusted = false;
+        dispatch_event(cx, self.abstract.unwrap(), event)
+    }
 }
 
 /// The CSS library requires that DOM nodes be convertible to `*c_void` via the `VoidPtrLike`
@@ -662,12 +1330,30 @@ pub struct DisplayBoxes {
 
 /// Data that layout associates with a node.
 pub struct LayoutData {
-    /// The results of CSS styling for this node.
-    style: Option<CompleteSelectResults>,
+    /// The results of CSS styling for this node, which elements that are styled alike share.
+    style: Option<Arc<CompleteSelectResults>>,
+
+    /// The values that the new style system computes for the element, which layout reads the
+    /// properties that libcss doesn't support from.
+    computed_values: Option<Arc<ComputedValues>>,
+
+    /// The values of the `::before` and `::after` pseudo-elements of the element, if their content
+    /// generates anything.
+    before_values: Option<Arc<ComputedValues>>,
+    after_values: Option<Arc<ComputedValues>>,
 
     /// Description of how to account for recent style changes.
     restyle_damage: Option<int>,
 
+    /// If the contents of the element are skipped, neither styled, laid out nor painted, since
+    /// it has `content-visibility: auto` and was far from the viewport, the size of the content
+    /// box that stands in for them.
+    skipped_contents_size: Option<Size2D<Au>>,
+
+    /// The colors that running transitions give the element, in place of those of its style,
+    /// each with the index of the property it is the value of.
+    animated_colors: ~[(uint, Color)],
+
     /// The boxes assosiated with this flow.
     /// Used for getBoundingClientRect and friends.
     boxes: DisplayBoxes,
@@ -678,7 +1364,12 @@ impl LayoutData {
     pub fn new() -> LayoutData {
         LayoutData {
             style: None,
+            computed_values: None,
+            before_values: None,
+            after_values: None,
             restyle_damage: None,
+            skipped_contents_size: None,
+            animated_colors: ~[],
             boxes: DisplayBoxes { display_list: None, range: None },
         }
     }
@@ -700,4 +1391,10 @@ impl AbstractNode<LayoutView> {
             blk(&mut b.layout_data)
         }
     }
+
+    /// Returns the script task's view of a node that layout has handed back to script, such as
+    /// the result of a hit test. This must only be called on the script task.
+    pub unsafe fn to_script_node(self) -> AbstractNode<ScriptView> {
+        transmute(self)
+    }
 }
@@ -0,0 +1,97 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use audio_task::{OscillatorNodeKind, FrequencyParam, DetuneParam, SetWaveformMsg};
+use audio_task::{SAMPLE_RATE, SineWave, SquareWave, SawtoothWave, TriangleWave};
+use dom::audiocontext::AudioGraphHandle;
+use dom::audioparam::AudioParam;
+use dom::audioscheduledsourcenode::AudioScheduledSourceNode;
+use dom::bindings::codegen::OscillatorNodeBinding;
+use dom::bindings::codegen::OscillatorNodeBinding::OscillatorType;
+use dom::bindings::codegen::OscillatorNodeBinding::OscillatorTypeValues::{Sine, Square};
+use dom::bindings::codegen::OscillatorNodeBinding::OscillatorTypeValues::{Sawtooth, Triangle};
+use dom::bindings::codegen::OscillatorNodeBinding::OscillatorTypeValues::Custom;
+use dom::bindings::utils::{ErrorResult, FailureUnknown};
+use dom::bindings::utils::{WrapperCache, BindingObject, CacheableWrapper};
+use script_task::page_from_context;
+
+use js::jsapi::{JSContext, JSObject};
+use std::cast;
+
+/// The largest detune, in cents, that doesn't overflow the frequency.
+static MAX_DETUNE: f32 = 153600.0;
+
+/// A node that generates a periodic wave.
+pub struct OscillatorNode {
+    parent: AudioScheduledSourceNode,
+    type_: OscillatorType,
+    frequency: @mut AudioParam,
+    detune: @mut AudioParam,
+}
+
+impl OscillatorNode {
+    pub fn new(graph: @mut AudioGraphHandle) -> @mut OscillatorNode {
+        let parent = AudioScheduledSourceNode::new(graph, OscillatorNodeKind);
+        let id = parent.parent.id;
+        let nyquist = SAMPLE_RATE / 2.0;
+        @mut OscillatorNode {
+            parent: parent,
+            type_: Sine,
+            frequency: AudioParam::new(graph, id, FrequencyParam, 440.0, -nyquist, nyquist),
+            detune: AudioParam::new(graph, id, DetuneParam, 0.0, -MAX_DETUNE, MAX_DETUNE),
+        }
+    }
+}
+
+impl OscillatorNode {
+    pub fn Type(&self) -> OscillatorType {
+        self.type_
+    }
+
+    // FIXME: Setting the type to "custom" should be an `InvalidStateError`; custom waves are
+    // set with `setPeriodicWave()`, which isn't supported yet.
+    pub fn SetType(&mut self, type_: OscillatorType, rv: &mut ErrorResult) {
+        let waveform = match type_ {
+            Sine => SineWave,
+            Square => SquareWave,
+            Sawtooth => SawtoothWave,
+            Triangle => TriangleWave,
+            Custom => {
+                *rv = Err(FailureUnknown);
+                return
+            }
+        };
+        self.type_ = type_;
+        let node = &self.parent.parent;
+        node.graph.send(SetWaveformMsg(node.id, waveform))
+    }
+
+    pub fn Frequency(&self) -> @mut AudioParam {
+        self.frequency
+    }
+
+    pub fn Detune(&self) -> @mut AudioParam {
+        self.detune
+    }
+}
+
+impl CacheableWrapper for OscillatorNode {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.parent.parent.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        OscillatorNodeBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for OscillatorNode {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
@@ -0,0 +1,668 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Ranges, which select the part of a document between two boundary points. The document tells
+//! its ranges of each change to its tree, so that their boundary points stay where they were
+//! around the nodes and text that moved.
+
+use dom::bindings::codegen::RangeBinding;
+use dom::bindings::utils::{BindingObject, CacheableWrapper, WrapperCache};
+use dom::bindings::utils::{DOMString, ErrorResult, FailureUnknown, str};
+use dom::clientrect::ClientRect;
+use dom::document::AbstractDocument;
+use dom::documentfragment::DocumentFragment;
+use dom::node::{AbstractNode, DoctypeNodeTypeId, Node, ScriptView};
+use layout_interface::{ContentBoxesResponse, RangeBoxesQuery};
+use script_task::page_from_context;
+
+use geom::rect::Rect;
+use gfx::geometry::Au;
+use js::jsapi::{JSContext, JSObject};
+use servo_util::tree::TreeNodeRef;
+use std::cast;
+use std::cmp::{Ordering, Less, Equal, Greater};
+use std::comm;
+use std::managed;
+
+/// A point in the tree: a node, and an offset in it, which counts UTF-16 code units in text and
+/// comments, and children in other nodes.
+#[deriving(Clone, Eq)]
+pub struct BoundaryPoint {
+    node: AbstractNode<ScriptView>,
+    offset: uint,
+}
+
+impl BoundaryPoint {
+    pub fn new(node: AbstractNode<ScriptView>, offset: uint) -> BoundaryPoint {
+        BoundaryPoint {
+            node: node,
+            offset: offset,
+        }
+    }
+
+    /// Returns where this point is in the tree compared to `other`, which must be in the same
+    /// tree.
+    pub fn compare(&self, other: &BoundaryPoint) -> Ordering {
+        if self.node == other.node {
+            return self.offset.cmp(&other.offset)
+        }
+        let (ours, theirs) = (inclusive_ancestors(self.node), inclusive_ancestors(other.node));
+        let mut depth = 0;
+        while depth < ours.len() && depth < theirs.len() && ours[depth] == theirs[depth] {
+            depth += 1;
+        }
+        if depth == ours.len() {
+            // Our node is an ancestor of theirs, so the child on the way to theirs decides.
+            if theirs[depth].index() < self.offset { Greater } else { Less }
+        } else if depth == theirs.len() {
+            if ours[depth].index() < other.offset { Less } else { Greater }
+        } else {
+            ours[depth].index().cmp(&theirs[depth].index())
+        }
+    }
+
+    fn node_removed(&mut self,
+                    node: AbstractNode<ScriptView>,
+                    parent: AbstractNode<ScriptView>,
+                    index: uint) {
+        if node.is_inclusive_ancestor_of(self.node) {
+            *self = BoundaryPoint::new(parent, index)
+        } else if self.node == parent && self.offset > index {
+            self.offset -= 1
+        }
+    }
+
+    fn children_inserted(&mut self, parent: AbstractNode<ScriptView>, index: uint, count: uint) {
+        if self.node == parent && self.offset > index {
+            self.offset += count
+        }
+    }
+
+    fn data_replaced(&mut self,
+                     node: AbstractNode<ScriptView>,
+                     offset: uint,
+                     count: uint,
+                     length: uint) {
+        if self.node != node || self.offset <= offset {
+            return
+        }
+        if self.offset <= offset + count {
+            self.offset = offset
+        } else {
+            self.offset = self.offset - count + length
+        }
+    }
+
+    fn text_split(&mut self,
+                  node: AbstractNode<ScriptView>,
+                  offset: uint,
+                  new_node: AbstractNode<ScriptView>) {
+        if self.node == node && self.offset > offset {
+            *self = BoundaryPoint::new(new_node, self.offset - offset)
+        } else if Some(self.node) == new_node.parent_node() && self.offset == new_node.index() {
+            // A point right after the node that was split stays after the new node too.
+            self.offset += 1
+        }
+    }
+}
+
+pub struct Range {
+    wrapper: WrapperCache,
+    document: AbstractDocument,
+    /// The ranges of the document, which this range leaves once script can no longer reach it.
+    /// The range keeps them itself, as its document may be gone by the time its wrapper is
+    /// finalized.
+    ranges: @mut ~[@mut Range],
+    start: BoundaryPoint,
+    end: BoundaryPoint,
+}
+
+impl Range {
+    /// Makes a range of `document` between the given boundary points, which the document keeps
+    /// up to date as its tree changes.
+    pub fn new(document: AbstractDocument,
+               start_node: AbstractNode<ScriptView>,
+               start_offset: uint,
+               end_node: AbstractNode<ScriptView>,
+               end_offset: uint)
+               -> @mut Range {
+        let range = @mut Range {
+            wrapper: WrapperCache::new(),
+            document: document,
+            ranges: document.with_base(|doc| doc.ranges),
+            start: BoundaryPoint::new(start_node, start_offset),
+            end: BoundaryPoint::new(end_node, end_offset),
+        };
+        let (scope, cx) = document.with_base(|doc| doc.get_scope_and_cx());
+        range.wrap_object_shared(cx, scope);
+        document.with_base(|doc| doc.add_range(range));
+        range
+    }
+
+    /// Called when the garbage collector finalizes the wrapper of the range, so that the document
+    /// stops keeping the range up to date.
+    pub fn finalize(@mut self) {
+        self.ranges.retain(|&range| !managed::mut_ptr_eq(range, self))
+    }
+
+    pub fn StartContainer(&self) -> AbstractNode<ScriptView> {
+        self.start.node
+    }
+
+    pub fn StartOffset(&self) -> u32 {
+        self.start.offset as u32
+    }
+
+    pub fn EndContainer(&self) -> AbstractNode<ScriptView> {
+        self.end.node
+    }
+
+    pub fn EndOffset(&self) -> u32 {
+        self.end.offset as u32
+    }
+
+    pub fn Collapsed(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn CommonAncestorContainer(&self) -> AbstractNode<ScriptView> {
+        let mut container = self.start.node;
+        while !container.is_inclusive_ancestor_of(self.end.node) {
+            container = container.parent_node().unwrap();
+        }
+        container
+    }
+
+    pub fn SetStart(&mut self, node: AbstractNode<ScriptView>, offset: u32, rv: &mut ErrorResult) {
+        self.set_point(true, node, offset as uint, rv)
+    }
+
+    pub fn SetEnd(&mut self, node: AbstractNode<ScriptView>, offset: u32, rv: &mut ErrorResult) {
+        self.set_point(false, node, offset as uint, rv)
+    }
+
+    pub fn SetStartBefore(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        self.set_point_around(true, node, false, rv)
+    }
+
+    pub fn SetStartAfter(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        self.set_point_around(true, node, true, rv)
+    }
+
+    pub fn SetEndBefore(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        self.set_point_around(false, node, false, rv)
+    }
+
+    pub fn SetEndAfter(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        self.set_point_around(false, node, true, rv)
+    }
+
+    pub fn Collapse(&mut self, to_start: bool) {
+        if to_start {
+            self.end = self.start.clone()
+        } else {
+            self.start = self.end.clone()
+        }
+    }
+
+    pub fn SelectNode(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        match node.parent_node() {
+            Some(parent) => {
+                let index = node.index();
+                self.start = BoundaryPoint::new(parent, index);
+                self.end = BoundaryPoint::new(parent, index + 1);
+            }
+            None => *rv = Err(FailureUnknown),
+        }
+    }
+
+    pub fn SelectNodeContents(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        if node.type_id() == DoctypeNodeTypeId {
+            *rv = Err(FailureUnknown);
+            return
+        }
+        self.start = BoundaryPoint::new(node, 0);
+        self.end = BoundaryPoint::new(node, node.length());
+    }
+
+    pub fn CompareBoundaryPoints(&self, how: u16, source: @mut Range, rv: &mut ErrorResult)
+                                 -> i16 {
+        let (ours, theirs) = match how {
+            // START_TO_START
+            0 => (self.start.clone(), source.start.clone()),
+            // START_TO_END
+            1 => (self.end.clone(), source.start.clone()),
+            // END_TO_END
+            2 => (self.end.clone(), source.end.clone()),
+            // END_TO_START
+            3 => (self.start.clone(), source.end.clone()),
+            _ => {
+                *rv = Err(FailureUnknown);
+                return 0
+            }
+        };
+        if root_of(ours.node) != root_of(theirs.node) {
+            *rv = Err(FailureUnknown);
+            return 0
+        }
+        match ours.compare(&theirs) {
+            Less => -1,
+            Equal => 0,
+            Greater => 1,
+        }
+    }
+
+    /// Deletes the contents of the range. They are taken out into a fragment as for
+    /// `extractContents()`, which is then dropped.
+    pub fn DeleteContents(&mut self, rv: &mut ErrorResult) {
+        if self.process_contents(true).is_none() {
+            *rv = Err(FailureUnknown)
+        }
+    }
+
+    pub fn ExtractContents(&mut self, rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
+        self.contents(true, rv)
+    }
+
+    pub fn CloneContents(&mut self, rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
+        self.contents(false, rv)
+    }
+
+    pub fn InsertNode(&mut self, node: AbstractNode<ScriptView>, rv: &mut ErrorResult) {
+        let start = self.start.clone();
+        if start.node.is_comment() || start.node == node ||
+                (start.node.is_text() && start.node.parent_node().is_none()) {
+            *rv = Err(FailureUnknown);
+            return
+        }
+        let (parent, mut reference) = if start.node.is_text() {
+            (start.node.parent_node().unwrap(), Some(start.node))
+        } else {
+            (start.node, start.node.children().nth(start.offset))
+        };
+        if node.type_id() == DoctypeNodeTypeId || node.is_inclusive_ancestor_of(parent) {
+            *rv = Err(FailureUnknown);
+            return
+        }
+
+        if start.node.is_text() {
+            reference = Some(split_text(self.cx(), start.node, start.offset));
+        }
+        if reference == Some(node) {
+            reference = node.next_sibling();
+        }
+        node.remove_from_parent();
+        let mut new_offset = match reference {
+            Some(reference) => reference.index(),
+            None => parent.length(),
+        };
+        new_offset += if node.is_document_fragment() { node.length() } else { 1 };
+        parent.insert_child(node, reference);
+
+        if self.Collapsed() {
+            self.end = BoundaryPoint::new(parent, new_offset);
+        }
+    }
+
+    pub fn CloneRange(&self) -> @mut Range {
+        Range::new(self.document, self.start.node, self.start.offset, self.end.node,
+                   self.end.offset)
+    }
+
+    /// Does nothing, as the DOM says now.
+    pub fn Detach(&self) {
+    }
+
+    /// Returns the text that the range selects.
+    ///
+    /// FIXME: The bindings can't call this for `toString()` yet.
+    pub fn Stringify(&self) -> DOMString {
+        let (start, end) = (&self.start, &self.end);
+        if start.node == end.node && start.node.is_text() {
+            return str(substring_data(start.node, start.offset, end.offset - start.offset))
+        }
+        let mut text = ~"";
+        if start.node.is_text() {
+            let count = start.node.length() - start.offset;
+            text.push_str(substring_data(start.node, start.offset, count));
+        }
+        for node in self.CommonAncestorContainer().traverse_preorder() {
+            if node.is_text() && self.contains_node(node) {
+                text.push_str(node.with_imm_characterdata(|data| data.data.clone()));
+            }
+        }
+        if end.node.is_text() {
+            text.push_str(substring_data(end.node, 0, end.offset));
+        }
+        str(text)
+    }
+
+    /// Returns the union of the boxes of the nodes that the range selects.
+    ///
+    /// FIXME: The text nodes that the range starts and ends in are measured whole, rather than
+    /// just the text that the range selects.
+    pub fn GetBoundingClientRect(&self) -> @mut ClientRect {
+        // The nodes that the range selects whose parents it doesn't, and the text at its ends.
+        let mut nodes = ~[];
+        for node in self.CommonAncestorContainer().traverse_preorder() {
+            let selected = self.contains_node(node) && !node.parent_node().map_default(false,
+                |&parent| self.contains_node(parent));
+            let end_text = node.is_text() && (node == self.start.node || node == self.end.node);
+            if selected || end_text {
+                nodes.push(node);
+            }
+        }
+
+        let window = self.document.with_base(|doc| doc.window.unwrap());
+        let (port, chan) = comm::stream();
+        let ContentBoxesResponse(boxes) = unsafe {
+            (*window.page).query_layout(RangeBoxesQuery(nodes, chan), port)
+        };
+        let mut bounds: Option<Rect<Au>> = None;
+        for rect in boxes.iter() {
+            bounds = Some(bounds.map_default(*rect, |bounds| bounds.union(rect)));
+        }
+        let rect = bounds.unwrap_or_default(Rect::zero());
+
+        let (scope, cx) = self.document.with_base(|doc| doc.get_scope_and_cx());
+        ClientRect::new(rect.origin.y.to_f32(),
+                        (rect.origin.y + rect.size.height).to_f32(),
+                        rect.origin.x.to_f32(),
+                        (rect.origin.x + rect.size.width).to_f32(),
+                        cx,
+                        scope)
+    }
+
+    /// Sets the start or the end of the range, moving the other along if the range would end
+    /// before it starts or span two trees otherwise.
+    fn set_point(&mut self,
+                 is_start: bool,
+                 node: AbstractNode<ScriptView>,
+                 offset: uint,
+                 rv: &mut ErrorResult) {
+        if node.type_id() == DoctypeNodeTypeId || offset > node.length() {
+            *rv = Err(FailureUnknown);
+            return
+        }
+        let point = BoundaryPoint::new(node, offset);
+        let same_tree = root_of(node) == root_of(self.start.node);
+        if is_start {
+            if !same_tree || point.compare(&self.end) == Greater {
+                self.end = point.clone();
+            }
+            self.start = point;
+        } else {
+            if !same_tree || point.compare(&self.start) == Less {
+                self.start = point.clone();
+            }
+            self.end = point;
+        }
+    }
+
+    /// Sets the start or the end of the range to just before or just after `node`.
+    fn set_point_around(&mut self,
+                        is_start: bool,
+                        node: AbstractNode<ScriptView>,
+                        after: bool,
+                        rv: &mut ErrorResult) {
+        match node.parent_node() {
+            Some(parent) => {
+                let index = if after { node.index() + 1 } else { node.index() };
+                self.set_point(is_start, parent, index, rv)
+            }
+            None => *rv = Err(FailureUnknown),
+        }
+    }
+
+    /// Returns whether the range selects all of `node`, which must be in the same tree.
+    fn contains_node(&self, node: AbstractNode<ScriptView>) -> bool {
+        BoundaryPoint::new(node, 0).compare(&self.start) == Greater &&
+            BoundaryPoint::new(node, node.length()).compare(&self.end) == Less
+    }
+
+    fn contents(&mut self, extract: bool, rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
+        match self.process_contents(extract) {
+            Some(fragment) => fragment,
+            None => {
+                *rv = Err(FailureUnknown);
+                self.new_fragment()
+            }
+        }
+    }
+
+    /// Moves the contents of the range out of the tree into a new document fragment if `extract`
+    /// is true, leaving the range collapsed where they were, or copies them into it otherwise.
+    /// Returns `None` if the range selects a doctype.
+    fn process_contents(&mut self, extract: bool) -> Option<AbstractNode<ScriptView>> {
+        let cx = self.cx();
+        let fragment = self.new_fragment();
+        if self.Collapsed() {
+            return Some(fragment)
+        }
+
+        let (start, end) = (self.start.clone(), self.end.clone());
+        if start.node == end.node && start.node.is_characterdata() {
+            let count = end.offset - start.offset;
+            fragment.insert_child(copy_data(cx, start.node, start.offset, count), None);
+            if extract {
+                delete_data(start.node, start.offset, count);
+            }
+            return Some(fragment)
+        }
+
+        // The children of the common ancestor that hold the start and the end, unless the start
+        // or the end is in the common ancestor itself, and those between them.
+        let common_ancestor = self.CommonAncestorContainer();
+        let children: ~[AbstractNode<ScriptView>] = common_ancestor.children().collect();
+        let first_partly_selected = if start.node.is_inclusive_ancestor_of(end.node) {
+            None
+        } else {
+            children.iter().find(|child| child.is_inclusive_ancestor_of(start.node)).map(|&c| c)
+        };
+        let last_partly_selected = if end.node.is_inclusive_ancestor_of(start.node) {
+            None
+        } else {
+            children.iter().find(|child| child.is_inclusive_ancestor_of(end.node)).map(|&c| c)
+        };
+        let selected: ~[AbstractNode<ScriptView>] = children.iter().filter(|&&child| {
+            self.contains_node(child)
+        }).map(|&child| child).collect();
+        if selected.iter().any(|child| child.type_id() == DoctypeNodeTypeId) {
+            return None
+        }
+
+        // Where the range collapses to once its contents are taken out.
+        let collapsed_point = if start.node.is_inclusive_ancestor_of(end.node) {
+            start.clone()
+        } else {
+            let mut reference = start.node;
+            while !reference.parent_node().unwrap().is_inclusive_ancestor_of(end.node) {
+                reference = reference.parent_node().unwrap();
+            }
+            BoundaryPoint::new(reference.parent_node().unwrap(), reference.index() + 1)
+        };
+
+        for &child in first_partly_selected.iter() {
+            if child.is_characterdata() {
+                let count = child.length() - start.offset;
+                fragment.insert_child(copy_data(cx, child, start.offset, count), None);
+                if extract {
+                    delete_data(child, start.offset, count);
+                }
+            } else {
+                let copy = child.clone_node(cx, false);
+                fragment.insert_child(copy, None);
+                let mut subrange = self.subrange(start.clone(),
+                                                 BoundaryPoint::new(child, child.length()));
+                match subrange.process_contents(extract) {
+                    Some(subfragment) => copy.insert_child(subfragment, None),
+                    None => return None,
+                }
+            }
+        }
+
+        for &child in selected.iter() {
+            if extract {
+                child.remove_from_parent();
+                fragment.insert_child(child, None);
+            } else {
+                fragment.insert_child(child.clone_node(cx, true), None);
+            }
+        }
+
+        for &child in last_partly_selected.iter() {
+            if child.is_characterdata() {
+                fragment.insert_child(copy_data(cx, child, 0, end.offset), None);
+                if extract {
+                    delete_data(child, 0, end.offset);
+                }
+            } else {
+                let copy = child.clone_node(cx, false);
+                fragment.insert_child(copy, None);
+                let mut subrange = self.subrange(BoundaryPoint::new(child, 0), end.clone());
+                match subrange.process_contents(extract) {
+                    Some(subfragment) => copy.insert_child(subfragment, None),
+                    None => return None,
+                }
+            }
+        }
+
+        if extract {
+            self.start = collapsed_point.clone();
+            self.end = collapsed_point;
+        }
+        Some(fragment)
+    }
+
+    /// Returns a range between the given points, which the document doesn't keep up to date.
+    fn subrange(&self, start: BoundaryPoint, end: BoundaryPoint) -> Range {
+        Range {
+            wrapper: WrapperCache::new(),
+            document: self.document,
+            start: start,
+            end: end,
+        }
+    }
+
+    fn new_fragment(&self) -> AbstractNode<ScriptView> {
+        let fragment = unsafe { Node::as_abstract_node(self.cx(), @DocumentFragment::new()) };
+        fragment.with_mut_base(|base| base.owner_doc = Some(self.document));
+        fragment
+    }
+
+    fn cx(&self) -> *JSContext {
+        let (_, cx) = self.document.with_base(|doc| doc.get_scope_and_cx());
+        cx
+    }
+
+    pub fn node_removed(&mut self,
+                        node: AbstractNode<ScriptView>,
+                        parent: AbstractNode<ScriptView>,
+                        index: uint) {
+        self.start.node_removed(node, parent, index);
+        self.end.node_removed(node, parent, index);
+    }
+
+    pub fn children_inserted(&mut self,
+                             parent: AbstractNode<ScriptView>,
+                             index: uint,
+                             count: uint) {
+        self.start.children_inserted(parent, index, count);
+        self.end.children_inserted(parent, index, count);
+    }
+
+    pub fn data_replaced(&mut self,
+                         node: AbstractNode<ScriptView>,
+                         offset: uint,
+                         count: uint,
+                         length: uint) {
+        self.start.data_replaced(node, offset, count, length);
+        self.end.data_replaced(node, offset, count, length);
+    }
+
+    pub fn text_split(&mut self,
+                      node: AbstractNode<ScriptView>,
+                      offset: uint,
+                      new_node: AbstractNode<ScriptView>) {
+        self.start.text_split(node, offset, new_node);
+        self.end.text_split(node, offset, new_node);
+    }
+}
+
+impl CacheableWrapper for Range {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        RangeBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for Range {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
+
+/// Returns `node` and its ancestors, from the root down.
+fn inclusive_ancestors(node: AbstractNode<ScriptView>) -> ~[AbstractNode<ScriptView>] {
+    let mut ancestors = ~[node];
+    let mut current = node.parent_node();
+    while current.is_some() {
+        let ancestor = current.unwrap();
+        ancestors.push(ancestor);
+        current = ancestor.parent_node();
+    }
+    ancestors.reverse();
+    ancestors
+}
+
+fn root_of(node: AbstractNode<ScriptView>) -> AbstractNode<ScriptView> {
+    inclusive_ancestors(node)[0]
+}
+
+/// Returns `count` code units of the data of the text or comment `node` from `offset`.
+fn substring_data(node: AbstractNode<ScriptView>, offset: uint, count: uint) -> ~str {
+    do node.with_imm_characterdata |data| {
+        let mut rv = Ok(());
+        data.SubstringData(offset as u32, count as u32, &mut rv).to_str()
+    }
+}
+
+/// Returns a copy of the text or comment `node` that has only `count` code units of its data
+/// from `offset`.
+fn copy_data(cx: *JSContext, node: AbstractNode<ScriptView>, offset: uint, count: uint)
+             -> AbstractNode<ScriptView> {
+    let copy = node.clone_node(cx, false);
+    let data = substring_data(node, offset, count);
+    copy.with_mut_characterdata(|copy| copy.data = data.clone());
+    copy
+}
+
+/// Deletes `count` code units of the data of the text or comment `node` from `offset`.
+fn delete_data(node: AbstractNode<ScriptView>, offset: uint, count: uint) {
+    do node.with_mut_characterdata |data| {
+        let mut rv = Ok(());
+        data.DeleteData(node, offset as u32, count as u32, &mut rv)
+    }
+}
+
+/// Splits the text node `node`, which must have a parent, at `offset`, as `splitText()` does,
+/// and returns the new text node that follows it with the data after the offset.
+fn split_text(cx: *JSContext, node: AbstractNode<ScriptView>, offset: uint)
+              -> AbstractNode<ScriptView> {
+    let count = node.length() - offset;
+    let new_node = copy_data(cx, node, offset, count);
+    node.parent_node().unwrap().insert_child(new_node, node.next_sibling());
+    for doc in node.with_base(|base| base.owner_doc).iter() {
+        doc.with_base(|doc| doc.text_split(node, offset, new_node));
+    }
+    delete_data(node, offset, count);
+    new_node
+}
@@ -0,0 +1,129 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::SourceBufferBinding;
+use dom::bindings::utils::{ErrorResult, FailureUnknown, WrapperCache};
+use dom::bindings::utils::{BindingObject, CacheableWrapper};
+use dom::mediasource::MediaSourceState;
+use script_task::page_from_context;
+
+use js::jsapi::{JSContext, JSObject};
+use std::cast;
+use std::util;
+
+/// A buffer that script appends the segments of one stream of media to.
+pub struct SourceBuffer {
+    wrapper: WrapperCache,
+    /// The MIME type of the segments, which `addSourceBuffer` was given.
+    mime_type: ~str,
+    /// The state of the media source the buffer belongs to, or `None` once the buffer has been
+    /// removed from it.
+    source_state: Option<@mut MediaSourceState>,
+    /// The segments appended since the media decoder last took them, in order.
+    segments: ~[~[u8]],
+    /// The offset in seconds to add to the timestamps of the segments appended.
+    timestamp_offset: f64,
+}
+
+impl SourceBuffer {
+    pub fn new(mime_type: ~str, source_state: @mut MediaSourceState) -> @mut SourceBuffer {
+        @mut SourceBuffer {
+            wrapper: WrapperCache::new(),
+            mime_type: mime_type,
+            source_state: Some(source_state),
+            segments: ~[],
+            timestamp_offset: 0.0,
+        }
+    }
+
+    /// Forgets the media source, once the buffer has been removed from it.
+    pub fn detach(&mut self) {
+        self.source_state = None;
+        self.segments = ~[];
+    }
+
+    /// Appends a segment of media data. Fails if the buffer has been removed from its media
+    /// source.
+    ///
+    /// FIXME: `appendBuffer()` should call this, but the bindings can't take typed arrays yet.
+    pub fn append_buffer(&mut self, data: ~[u8], rv: &mut ErrorResult) {
+        match self.source_state {
+            Some(state) => state.reopen_if_ended(),
+            None => {
+                *rv = Err(FailureUnknown);
+                return
+            }
+        }
+        self.segments.push(data)
+    }
+
+    /// Takes the segments appended since they were last taken, for the media decoder to play.
+    pub fn take_segments(&mut self) -> ~[~[u8]] {
+        util::replace(&mut self.segments, ~[])
+    }
+}
+
+impl SourceBuffer {
+    /// Segments are appended at once, so the buffer is never busy with one.
+    pub fn Updating(&self) -> bool {
+        false
+    }
+
+    pub fn TimestampOffset(&self) -> f64 {
+        self.timestamp_offset
+    }
+
+    pub fn SetTimestampOffset(&mut self, timestamp_offset: f64, rv: &mut ErrorResult) {
+        match self.source_state {
+            Some(state) => state.reopen_if_ended(),
+            None => {
+                *rv = Err(FailureUnknown);
+                return
+            }
+        }
+        self.timestamp_offset = timestamp_offset
+    }
+
+    pub fn Abort(&mut self, rv: &mut ErrorResult) {
+        match self.source_state {
+            Some(state) if state.is_open() => {}
+            _ => *rv = Err(FailureUnknown),
+        }
+    }
+
+    // FIXME: This should remove the media between `start` and `end` seconds from the buffer,
+    // once segments are demuxed and their timestamps are known.
+    pub fn Remove(&mut self, start: f64, end: f64, rv: &mut ErrorResult) {
+        let duration = match self.source_state {
+            Some(state) if state.is_open() => state.duration,
+            _ => {
+                *rv = Err(FailureUnknown);
+                return
+            }
+        };
+        if duration.is_NaN() || start < 0.0 || start > duration || end.is_NaN() || end <= start {
+            *rv = Err(FailureUnknown)
+        }
+    }
+}
+
+impl CacheableWrapper for SourceBuffer {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        SourceBufferBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for SourceBuffer {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
@@ -2,10 +2,11 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use dom::bindings::utils::{DOMString, ErrorResult, null_string};
+use dom::bindings::utils::{DOMString, ErrorResult, FailureUnknown, null_string};
 use dom::characterdata::CharacterData;
 use dom::node::{AbstractNode, ScriptView, Node, TextNodeTypeId};
 use dom::window::Window;
+use servo_util::shared_str::utf16_to_byte_index;
 
 /// An HTML text node.
 pub struct Text {
@@ -25,8 +26,33 @@ impl Text {
         unsafe { Node::as_abstract_node(cx, @Text::new(text.to_str())) }
     }
 
-    pub fn SplitText(&self, _offset: u32, _rv: &mut ErrorResult) -> AbstractNode<ScriptView> {
-        fail!("unimplemented")
+    /// Moves the data of this node from `offset` on into a new text node, which is inserted
+    /// after this one. The boundary points of the live ranges of the document that were past
+    /// `offset` move into the new node.
+    pub fn SplitText(&mut self,
+                     abstract_self: AbstractNode<ScriptView>,
+                     offset: u32,
+                     rv: &mut ErrorResult)
+                     -> AbstractNode<ScriptView> {
+        let index = utf16_to_byte_index(self.parent.data, offset as uint);
+        let (doc, index) = match (self.parent.parent.owner_doc, index) {
+            (Some(doc), Some(index)) => (doc, index),
+            _ => {
+                *rv = Err(FailureUnknown);
+                return abstract_self
+            }
+        };
+        let (_, cx) = doc.with_base(|doc| doc.get_scope_and_cx());
+        let rest = self.parent.data.slice_from(index).to_owned();
+        let new_node = unsafe { Node::as_abstract_node(cx, @Text::new(rest)) };
+        new_node.with_mut_base(|base| base.add_to_doc(doc));
+        for parent in abstract_self.parent_node().iter() {
+            parent.insert_child(new_node, abstract_self.next_sibling());
+            doc.with_base(|doc| doc.text_split(abstract_self, offset as uint, new_node));
+        }
+        let count = self.parent.Length() - offset;
+        self.parent.DeleteData(abstract_self, offset, count, rv);
+        new_node
     }
 
     pub fn GetWholeText(&self, _rv: &mut ErrorResult) -> DOMString {
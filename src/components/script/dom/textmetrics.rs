@@ -0,0 +1,105 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::TextMetricsBinding;
+use dom::bindings::utils::{BindingObject, CacheableWrapper, WrapperCache};
+use dom::htmlcanvaselement::{CanvasTextAlign, CanvasTextBaseline, text_origin};
+use script_task::page_from_context;
+
+use gfx::geometry::Au;
+use js::jsapi::{JSContext, JSObject};
+use std::cast;
+use LayoutTextMetrics = gfx::font::TextMetrics;
+
+/// The metrics of a line of text that `measureText()` returns, in pixels from the point the text
+/// would be drawn at.
+pub struct TextMetrics {
+    wrapper: WrapperCache,
+    width: f64,
+    actual_left: f64,
+    actual_right: f64,
+    actual_ascent: f64,
+    actual_descent: f64,
+    font_ascent: f64,
+    font_descent: f64,
+}
+
+impl TextMetrics {
+    /// Makes the metrics of a line of text that layout has measured, as it would be drawn with
+    /// the given alignment and baseline.
+    pub fn new(metrics: &LayoutTextMetrics,
+               align: CanvasTextAlign,
+               baseline: CanvasTextBaseline,
+               cx: *JSContext,
+               scope: *JSObject)
+               -> @mut TextMetrics {
+        let origin = text_origin(align, baseline, metrics.advance_width, metrics.font_ascent,
+                                 metrics.font_descent);
+        let bounding_box = metrics.bounding_box.translate(&origin);
+        let text_metrics = @mut TextMetrics {
+            wrapper: WrapperCache::new(),
+            width: px(metrics.advance_width),
+            actual_left: -px(bounding_box.origin.x),
+            actual_right: px(bounding_box.origin.x + bounding_box.size.width),
+            actual_ascent: -px(bounding_box.origin.y),
+            actual_descent: px(bounding_box.origin.y + bounding_box.size.height),
+            font_ascent: px(metrics.font_ascent - origin.y),
+            font_descent: px(metrics.font_descent + origin.y),
+        };
+        text_metrics.wrap_object_shared(cx, scope);
+        text_metrics
+    }
+
+    pub fn Width(&self) -> f64 {
+        self.width
+    }
+
+    pub fn ActualBoundingBoxLeft(&self) -> f64 {
+        self.actual_left
+    }
+
+    pub fn ActualBoundingBoxRight(&self) -> f64 {
+        self.actual_right
+    }
+
+    pub fn ActualBoundingBoxAscent(&self) -> f64 {
+        self.actual_ascent
+    }
+
+    pub fn ActualBoundingBoxDescent(&self) -> f64 {
+        self.actual_descent
+    }
+
+    pub fn FontBoundingBoxAscent(&self) -> f64 {
+        self.font_ascent
+    }
+
+    pub fn FontBoundingBoxDescent(&self) -> f64 {
+        self.font_descent
+    }
+}
+
+fn px(length: Au) -> f64 {
+    *length.to_css_px() as f64
+}
+
+impl CacheableWrapper for TextMetrics {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        TextMetricsBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for TextMetrics {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
@@ -24,8 +24,11 @@ pub struct UIEvent {
 impl UIEvent {
     pub fn new(type_: &DOMString, can_bubble: bool, cancelable: bool,
                view: Option<@mut WindowProxy>, detail: i32) -> UIEvent {
+        let mut parent = Event::new(type_);
+        parent.bubbles = can_bubble;
+        parent.cancelable = cancelable;
         UIEvent {
-            parent: Event::new(type_),
+            parent: parent,
             can_bubble: can_bubble,
             cancelable: cancelable,
             view: view,
@@ -103,12 +106,14 @@ impl UIEvent {
     }
 
     pub fn CancelBubble(&self) -> bool {
-        //TODO
-        false
+        self.parent.stop_propagation
     }
 
-    pub fn SetCancelBubble(&mut self, _val: bool) {
-        //TODO
+    /// Stops the propagation of the event, as `stopPropagation()` does, if `val` is true.
+    pub fn SetCancelBubble(&mut self, val: bool) {
+        if val {
+            self.parent.StopPropagation()
+        }
     }
 
     pub fn IsChar(&self) -> bool {
@@ -7,6 +7,16 @@ use dom::bindings::codegen::ValidityStateBinding;
 use js::jsapi::{JSContext, JSObject};
 use std::cast;
 
+// The constraints a form control can fail to satisfy, as the bits of `ValidityState::state`.
+pub static VALUE_MISSING: u8 = 1 << 0;
+pub static TYPE_MISMATCH: u8 = 1 << 1;
+pub static PATTERN_MISMATCH: u8 = 1 << 2;
+pub static TOO_LONG: u8 = 1 << 3;
+pub static RANGE_UNDERFLOW: u8 = 1 << 4;
+pub static RANGE_OVERFLOW: u8 = 1 << 5;
+pub static STEP_MISMATCH: u8 = 1 << 6;
+pub static CUSTOM_ERROR: u8 = 1 << 7;
+
 pub struct ValidityState {
     wrapper: WrapperCache,
     state: u8
@@ -14,48 +24,54 @@ pub struct ValidityState {
 
 impl ValidityState {
     pub fn valid() -> ValidityState {
+        ValidityState::new(0)
+    }
+
+    /// Returns the validity of a control that fails the constraints whose flags are set in
+    /// `state`.
+    pub fn new(state: u8) -> ValidityState {
         ValidityState {
             wrapper: WrapperCache::new(),
-            state: 0
+            state: state
         }
     }
 }
 
 impl ValidityState {
     pub fn ValueMissing(&self) -> bool {
-        false
+        self.state & VALUE_MISSING != 0
     }
 
     pub fn TypeMismatch(&self) -> bool {
-        false
+        self.state & TYPE_MISMATCH != 0
     }
 
     pub fn PatternMismatch(&self) -> bool {
-        false
+        self.state & PATTERN_MISMATCH != 0
     }
 
     pub fn TooLong(&self) -> bool {
-        false
+        self.state & TOO_LONG != 0
     }
 
     pub fn RangeUnderflow(&self) -> bool {
-        false
+        self.state & RANGE_UNDERFLOW != 0
     }
 
     pub fn RangeOverflow(&self) -> bool {
-        false
+        self.state & RANGE_OVERFLOW != 0
     }
 
     pub fn StepMismatch(&self) -> bool {
-        false
+        self.state & STEP_MISMATCH != 0
     }
 
     pub fn CustomError(&self) -> bool {
-        false
+        self.state & CUSTOM_ERROR != 0
     }
 
     pub fn Valid(&self) -> bool {
-        true
+        self.state == 0
     }
 }
 
@@ -0,0 +1,80 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The part of the page that is on screen once the user has pinch zoomed, in CSS pixels. Its
+//! attributes are read from the page as the compositor last described it, so they are live.
+
+use dom::bindings::utils::{WrapperCache, BindingObject, CacheableWrapper};
+use dom::bindings::codegen::VisualViewportBinding;
+use script_task::{Page, page_from_context};
+
+use js::jsapi::{JSContext, JSObject};
+
+use std::cast;
+
+pub struct VisualViewport {
+    wrapper: WrapperCache,
+    page: *mut Page,
+}
+
+impl VisualViewport {
+    pub fn new(page: *mut Page) -> @mut VisualViewport {
+        @mut VisualViewport {
+            wrapper: WrapperCache::new(),
+            page: page,
+        }
+    }
+
+    /// The compositor scrolls the page itself as it zooms, so the layout viewport moves with the
+    /// visual viewport, and the offset between them is always zero.
+    ///
+    /// FIXME: The layout viewport should stay put while the user pans around a zoomed page.
+    pub fn OffsetLeft(&self) -> f64 {
+        0.0
+    }
+
+    pub fn OffsetTop(&self) -> f64 {
+        0.0
+    }
+
+    pub fn PageLeft(&self) -> f64 {
+        unsafe { (*self.page).viewport_origin.x as f64 }
+    }
+
+    pub fn PageTop(&self) -> f64 {
+        unsafe { (*self.page).viewport_origin.y as f64 }
+    }
+
+    pub fn Width(&self) -> f64 {
+        unsafe { (*self.page).window_size.get().width as f64 / self.Scale() }
+    }
+
+    pub fn Height(&self) -> f64 {
+        unsafe { (*self.page).window_size.get().height as f64 / self.Scale() }
+    }
+
+    pub fn Scale(&self) -> f64 {
+        unsafe { (*self.page).zoom as f64 }
+    }
+}
+
+impl CacheableWrapper for VisualViewport {
+    fn get_wrappercache(&mut self) -> &mut WrapperCache {
+        unsafe { cast::transmute(&self.wrapper) }
+    }
+
+    fn wrap_object_shared(@mut self, cx: *JSContext, scope: *JSObject) -> *JSObject {
+        let mut unused = false;
+        VisualViewportBinding::Wrap(cx, scope, self, &mut unused)
+    }
+}
+
+impl BindingObject for VisualViewport {
+    fn GetParentObject(&self, cx: *JSContext) -> Option<@mut CacheableWrapper> {
+        let page = page_from_context(cx);
+        unsafe {
+            Some((*page).frame.get_ref().window as @mut CacheableWrapper)
+        }
+    }
+}
@@ -4,17 +4,31 @@
 
 use dom::bindings::codegen::WindowBinding;
 use dom::bindings::utils::{WrapperCache, DOMString, null_string};
-use dom::bindings::utils::{CacheableWrapper, BindingObject};
+use dom::bindings::utils::{CacheableWrapper, BindingObject, ErrorResult, FailureUnknown};
+use dom::bindings::utils::Traceable;
+use dom::cssstyledeclaration::{CSSStyleDeclaration, ComputedStyleOwner};
 use dom::document::AbstractDocument;
+use dom::imagebitmap::ImageBitmapData;
 use dom::node::{AbstractNode, ScriptView};
 use dom::navigator::Navigator;
+use dom::visualviewport::VisualViewport;
 
+use html::cssparse::LayoutStylesheet;
 use layout_interface::ReflowForScriptQuery;
-use script_task::{ExitMsg, FireTimerMsg, Page, ScriptChan};
+use script_task::{ExitMsg, FireTimerMsg, ImageBitmapMsg, Page, ScriptChan};
+use timers::{Timers, now};
 use servo_msg::compositor_msg::ScriptListener;
+use servo_net::image::base::{Image, resize};
+use servo_net::image_cache_task::{Decode, ImageCacheTask, ImageFailed, ImageNotReady};
+use servo_net::image_cache_task::{ImageReady, Prefetch, WaitForImage};
+use servo_net::resource_task::ResourceTask;
+
+use extra::arc::Arc;
+
+use geom::point::Point2D;
 
 use js::glue::*;
-use js::jsapi::{JSObject, JSContext};
+use js::jsapi::{JSObject, JSContext, JSTracer, JSTRACE_OBJECT, JS_CallTracer};
 use js::jsapi::{JSPropertyOp, JSStrictPropertyOp};
 use js::{JSVAL_NULL, JSPROP_ENUMERATE};
 
@@ -23,14 +37,15 @@ use std::cell::Cell;
 use std::comm;
 use std::comm::SharedChan;
 use std::io;
+use std::libc;
 use std::ptr;
-use std::int;
 use std::rt::rtio::RtioTimer;
 use std::rt::io::timer::Timer;
 use js::jsapi::JSVal;
 
 pub enum TimerControlMsg {
-    TimerMessage_Fire(~TimerData),
+    /// Sent when a timer of the window may be due.
+    TimerMessage_Fire,
     TimerMessage_Close,
     TimerMessage_TriggerExit //XXXjdm this is just a quick hack to talk to the script task
 }
@@ -40,9 +55,14 @@ pub struct Window {
     page: *mut Page,
     script_chan: ScriptChan,
     compositor: @ScriptListener,
+    resource_task: ResourceTask,
+    image_cache_task: ImageCacheTask,
     wrapper: WrapperCache,
     timer_chan: SharedChan<TimerControlMsg>,
+    /// The timers that `setTimeout` and `setInterval` have scheduled.
+    timers: @mut Timers,
     navigator: Option<@mut Navigator>,
+    visual_viewport: Option<@mut VisualViewport>,
 }
 
 #[unsafe_destructor]
@@ -52,14 +72,6 @@ impl Drop for Window {
     }
 }
 
-// Holder for the various JS values associated with setTimeout
-// (ie. function value to invoke and all arguments to pass
-//      to the function when calling it)
-pub struct TimerData {
-    funval: JSVal,
-    args: ~[JSVal],
-}
-
 impl Window {
     pub fn Alert(&self, s: &DOMString) {
         // Right now, just print to the console
@@ -109,11 +121,25 @@ impl Window {
 
     pub fn Navigator(&mut self) -> @mut Navigator {
         if self.navigator.is_none() {
-            self.navigator = Some(Navigator::new());
+            self.navigator = Some(Navigator::new(self.page, self.resource_task.clone()));
         }
         self.navigator.unwrap()
     }
 
+    pub fn VisualViewport(&mut self) -> @mut VisualViewport {
+        if self.visual_viewport.is_none() {
+            self.visual_viewport = Some(VisualViewport::new(self.page));
+        }
+        self.visual_viewport.unwrap()
+    }
+
+    pub fn GetComputedStyle(&mut self, element: AbstractNode<ScriptView>)
+                            -> @mut CSSStyleDeclaration {
+        let cx = unsafe { (*self.page).js_info.get_ref().js_compartment.cx.ptr };
+        let scope = self.get_wrappercache().get_wrapper();
+        CSSStyleDeclaration::new(ComputedStyleOwner(element), self.page, cx, scope)
+    }
+
     pub fn Confirm(&self, _message: &DOMString) -> bool {
         false
     }
@@ -129,6 +155,24 @@ impl Window {
         JSVAL_NULL
     }
 
+    pub fn Scroll(&self, x: i32, y: i32) {
+        self.ScrollTo(x, y)
+    }
+
+    pub fn ScrollTo(&self, x: i32, y: i32) {
+        unsafe {
+            (*self.page).scroll_to(Point2D(x as f32, y as f32), self.compositor)
+        }
+    }
+
+    pub fn ScrollBy(&self, x: i32, y: i32) {
+        unsafe {
+            let origin = (*self.page).viewport_origin;
+            (*self.page).scroll_to(Point2D(origin.x + x as f32, origin.y + y as f32),
+                                   self.compositor)
+        }
+    }
+
     pub fn NamedGetter(&self, _cx: *JSContext, _name: &DOMString, _found: &mut bool) -> *JSObject {
         ptr::null()
     }
@@ -145,6 +189,28 @@ impl CacheableWrapper for Window {
     }
 }
 
+impl Traceable for Window {
+    /// Traces the callbacks of the timers of the window, which script may no longer refer to
+    /// otherwise.
+    #[fixed_stack_segment]
+    fn trace(&self, tracer: *mut JSTracer) {
+        do self.timers.each_callback |callback| {
+            unsafe {
+                if RUST_JSVAL_IS_OBJECT(callback) != 0 {
+                    (*tracer).debugPrinter = ptr::null();
+                    (*tracer).debugPrintIndex = -1;
+                    do "timer callback".to_c_str().with_ref |name| {
+                        (*tracer).debugPrintArg = name as *libc::c_void;
+                        JS_CallTracer(cast::transmute(tracer),
+                                      RUST_JSVAL_TO_OBJECT(callback),
+                                      JSTRACE_OBJECT as u32);
+                    }
+                }
+            }
+        }
+    }
+}
+
 impl BindingObject for Window {
     fn GetParentObject(&self, _cx: *JSContext) -> Option<@mut CacheableWrapper> {
         None
@@ -153,37 +219,142 @@ impl BindingObject for Window {
 
 impl Window {
     pub fn SetTimeout(&self, _cx: *JSContext, callback: JSVal, timeout: i32) -> i32 {
-        let timeout = int::max(0, timeout) as u64;
+        let (handle, delay) = self.timers.schedule(callback, timeout, false, now());
+        self.wake_timers_after(delay);
+        handle
+    }
+
+    pub fn SetInterval(&self, _cx: *JSContext, callback: JSVal, timeout: i32) -> i32 {
+        let (handle, delay) = self.timers.schedule(callback, timeout, true, now());
+        self.wake_timers_after(delay);
+        handle
+    }
+
+    pub fn ClearTimeout(&self, handle: i32) {
+        self.timers.clear(handle)
+    }
 
-        // Post a delayed message to the per-window timer task; it will dispatch it
-        // to the relevant script handler that will deal with it.
+    pub fn ClearInterval(&self, handle: i32) {
+        self.timers.clear(handle)
+    }
+
+    /// Has the script task run the timers of the window that are due once `delay` milliseconds
+    /// have passed. The message goes through the timer task of the window, which tells the
+    /// script task which page it is for.
+    pub fn wake_timers_after(&self, delay: u64) {
         let tm = Cell::new(Timer::new().unwrap());
         let chan = self.timer_chan.clone();
         do spawn {
             let mut tm = tm.take();
-            tm.sleep(timeout);
-            chan.send(TimerMessage_Fire(~TimerData {
-                funval: callback,
-                args: ~[]
+            tm.sleep(delay);
+            chan.send(TimerMessage_Fire);
+        }
+    }
+
+    /// Stops the timers of the window from running while its document isn't shown.
+    pub fn freeze(&self) {
+        self.timers.freeze(now())
+    }
+
+    /// Lets the timers of the window run again once its document is shown, as long after they
+    /// were scheduled as they would have run had it not been frozen.
+    pub fn thaw(&self) {
+        for &delay in self.timers.thaw(now()).iter() {
+            self.wake_timers_after(delay)
+        }
+    }
+
+    /// Decodes the image of an image element in another task, and calls `callback` with an image
+    /// bitmap of it, or with null if it can't be decoded. The bitmap is resized to
+    /// `resize_width` by `resize_height` pixels, keeping the aspect ratio of the image if one of
+    /// them is zero. Throws if the element has no image to decode.
+    ///
+    /// FIXME: Blobs should be accepted as well, but they don't have any data yet.
+    pub fn CreateImageBitmap(&self,
+                             image: AbstractNode<ScriptView>,
+                             callback: JSVal,
+                             resize_width: u32,
+                             resize_height: u32,
+                             rv: &mut ErrorResult) {
+        let url = if image.is_image_element() {
+            image.with_imm_image_element(|image| image.image.clone())
+        } else {
+            None
+        };
+        let url = match url {
+            Some(url) => Cell::new(url),
+            None => {
+                *rv = Err(FailureUnknown);
+                return
+            }
+        };
+
+        let image_cache_task = self.image_cache_task.clone();
+        let script_chan = self.script_chan.clone();
+        let id = unsafe { (*self.page).id.clone() };
+        do spawn {
+            let url = url.take();
+            image_cache_task.send(Prefetch(url.clone()));
+            image_cache_task.send(Decode(url.clone()));
+            let (response_port, response_chan) = comm::stream();
+            image_cache_task.send(WaitForImage(url, response_chan));
+            let image = match response_port.recv() {
+                ImageReady(image) => {
+                    Some(resize_bitmap(image, resize_width as uint, resize_height as uint))
+                }
+                ImageNotReady | ImageFailed => None,
+            };
+            script_chan.chan.send(ImageBitmapMsg(id, ~ImageBitmapData {
+                callback: callback,
+                image: image,
             }));
         }
-        return 0; //TODO return handle into list of active timers
     }
 
+    /// Restyles the nodes that script has marked dirty and lays out the document again.
     pub fn content_changed(&self) {
         unsafe {
-            (*self.page).reflow_all(ReflowForScriptQuery, self.script_chan.clone(), self.compositor);
+            (*self.page).reflow_dirty(ReflowForScriptQuery,
+                                      self.script_chan.clone(),
+                                      self.compositor);
+        }
+    }
+
+    /// Restyles the elements whose states that the dynamic pseudo-classes match have changed.
+    pub fn element_states_changed(&self) {
+        unsafe {
+            (*self.page).restyle_element_states(self.script_chan.clone(), self.compositor);
+        }
+    }
+
+    /// Replaces the style sheets that layout has with `sheets`, which script has parsed again
+    /// after editing their rules, and restyles the subtrees of the given elements, or the whole
+    /// document if they are `None`.
+    pub fn style_sheets_changed(&self,
+                                sheets: ~[LayoutStylesheet],
+                                restyled: Option<~[AbstractNode<ScriptView>]>) {
+        unsafe {
+            (*self.page).replace_stylesheets(sheets,
+                                             restyled,
+                                             self.script_chan.clone(),
+                                             self.compositor);
         }
     }
 
     #[fixed_stack_segment]
-    pub fn new(page: *mut Page, script_chan: ScriptChan, compositor: @ScriptListener)
+    pub fn new(page: *mut Page,
+               script_chan: ScriptChan,
+               compositor: @ScriptListener,
+               resource_task: ResourceTask,
+               image_cache_task: ImageCacheTask)
                -> @mut Window {
         let script_chan_clone = script_chan.clone();
         let win = @mut Window {
             page: page,
             script_chan: script_chan,
             compositor: compositor,
+            resource_task: resource_task,
+            image_cache_task: image_cache_task,
             wrapper: WrapperCache::new(),
             timer_chan: {
                 let (timer_port, timer_chan) = comm::stream::<TimerControlMsg>();
@@ -191,14 +362,18 @@ impl Window {
                     loop {
                         match timer_port.recv() {
                             TimerMessage_Close => break,
-                            TimerMessage_Fire(td) => unsafe {script_chan_clone.chan.send(FireTimerMsg((*page).id.clone(), td))},
+                            TimerMessage_Fire => unsafe {
+                                script_chan_clone.chan.send(FireTimerMsg((*page).id.clone()))
+                            },
                             TimerMessage_TriggerExit => script_chan_clone.chan.send(ExitMsg),
                         }
                     }
                 }
                 SharedChan::new(timer_chan)
             },
+            timers: @mut Timers::new(),
             navigator: None,
+            visual_viewport: None,
         };
 
         unsafe {
@@ -216,3 +391,17 @@ impl Window {
     }
 }
 
+/// Resizes a decoded image to the size that `createImageBitmap()` was asked for.
+fn resize_bitmap(image: Arc<~Image>, width: uint, height: uint) -> Arc<~Image> {
+    let (own_width, own_height) = (image.get().width, image.get().height);
+    if own_width == 0 || own_height == 0 {
+        return image
+    }
+    let (width, height) = match (width, height) {
+        (0, 0) => return image,
+        (0, height) => (own_width * height / own_height, height),
+        (width, 0) => (width, own_height * width / own_width),
+        (width, height) => (width, height),
+    };
+    Arc::new(~resize(&**image.get(), width, height))
+}
@@ -0,0 +1,73 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Entry points for fuzzing the parsers, built with `--cfg fuzzing` (`configure
+//! --enable-fuzzing`). They do no I/O and build no DOM, so they can run without a JavaScript
+//! context, a resource task or a window. Each one parses its input in a task of its own and
+//! returns `Err(())` if the parser failed, rather than taking the fuzzer down with it.
+
+use style::stylesheets;
+
+use extra::url::Url;
+use hubbub::hubbub;
+use servo_util::url::make_url;
+use std::from_str::FromStr;
+use std::task;
+
+/// Runs the HTML tokenizer and tree builder over `html`. The tree is built out of node numbers
+/// rather than DOM nodes, so only the parser itself is exercised.
+pub fn parse_html_fragment(html: &[u8]) -> Result<(), ()> {
+    let html = html.to_owned();
+    do task::try {
+        let next_node = @mut 1u;
+        let new_node: @fn() -> hubbub::NodeDataPtr = || {
+            *next_node += 1;
+            *next_node
+        };
+
+        let mut parser = hubbub::Parser("UTF-8", false);
+        parser.set_document_node(1u);
+        parser.enable_scripting(true);
+        parser.enable_styling(true);
+        parser.set_tree_handler(~hubbub::TreeHandler {
+            create_comment: |_| new_node(),
+            create_doctype: |_| new_node(),
+            create_element: |_| new_node(),
+            create_text: |_| new_node(),
+            ref_node: |_| {},
+            unref_node: |_| {},
+            append_child: |_parent, child| child,
+            insert_before: |_parent, child| child,
+            remove_child: |_parent, child| child,
+            clone_node: |_node, _deep| new_node(),
+            reparent_children: |_node, new_parent| new_parent,
+            get_parent: |_node, _element_only| 0u,
+            has_children: |_node| false,
+            form_associate: |_form, _node| {},
+            add_attributes: |_node, _attributes| {},
+            set_quirks_mode: |_mode| {},
+            encoding_change: |_encname| {},
+            complete_script: |_script| {},
+            complete_style: |_style| {},
+        });
+        parser.parse_chunk(html);
+    }
+}
+
+/// Parses `css` as a style sheet, with its selectors and declarations.
+pub fn parse_stylesheet(css: &str) -> Result<(), ()> {
+    let css = css.to_owned();
+    do task::try {
+        stylesheets::parse_stylesheet(css);
+    }
+}
+
+/// Parses `url`, resolving it against `http://example.com/dir/index.html` if it is relative.
+pub fn parse_url(url: &str) -> Result<(), ()> {
+    let url = url.to_owned();
+    do task::try {
+        let base: Url = FromStr::from_str("http://example.com/dir/index.html").unwrap();
+        make_url(url.clone(), Some(base));
+    }
+}
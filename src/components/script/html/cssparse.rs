@@ -7,10 +7,12 @@
 use std::cell::Cell;
 use std::comm;
 use std::comm::Port;
+use std::str;
 use std::task;
 use newcss::stylesheet::Stylesheet;
 use newcss::util::DataStream;
-use servo_net::resource_task::{ResourceTask, ProgressMsg, Load, Payload, Done};
+use servo_net::resource_task::{ResourceTask, Load, Payload, Done};
+use style::stylesheets;
 use extra::url::Url;
 
 /// Where a style sheet comes from.
@@ -19,49 +21,77 @@ pub enum StylesheetProvenance {
     InlineProvenance(Url, ~str),
 }
 
+/// The text of a style sheet, which script keeps so that it can expose the rules of the sheet
+/// and parse it again for layout when they are edited.
+pub struct StylesheetSource {
+    /// The URL that relative URLs in the sheet resolve against.
+    url: Url,
+    /// Whether the sheet was loaded from `url`, rather than being inline in the document.
+    linked: bool,
+    text: ~str,
+}
+
+/// A style sheet parsed for layout: by libcss, which most properties are styled with, and by the
+/// new style system, which computes the properties that libcss doesn't support.
+pub struct LayoutStylesheet {
+    libcss: Stylesheet,
+    properties: stylesheets::Stylesheet,
+}
+
+/// Loads and parses a style sheet in a task of its own. The whole sheet is loaded before it is
+/// parsed, since script keeps its text.
 pub fn spawn_css_parser(provenance: StylesheetProvenance,
                         resource_task: ResourceTask)
-                     -> Port<Stylesheet> {
+                     -> Port<(LayoutStylesheet, StylesheetSource)> {
     let (result_port, result_chan) = comm::stream();
 
     let provenance_cell = Cell::new(provenance);
     do task::spawn {
-        let url = do provenance_cell.with_ref |p| {
-            match *p {
-                UrlProvenance(ref the_url) => (*the_url).clone(),
-                InlineProvenance(ref the_url, _) => (*the_url).clone()
+        let source = match provenance_cell.take() {
+            UrlProvenance(url) => {
+                debug!("cssparse: loading style sheet at %s", url.to_str());
+                let text = load_text(url.clone(), resource_task.clone());
+                StylesheetSource { url: url, linked: true, text: text }
+            }
+            InlineProvenance(url, text) => {
+                StylesheetSource { url: url, linked: false, text: text }
             }
         };
 
-        let sheet = Stylesheet::new(url, data_stream(provenance_cell.take(),
-                                                     resource_task.clone()));
-        result_chan.send(sheet);
+        let sheet = parse_stylesheet_text(source.url.clone(), source.text.clone());
+        result_chan.send((sheet, source));
     }
 
     return result_port;
 }
 
-fn data_stream(provenance: StylesheetProvenance, resource_task: ResourceTask) -> DataStream {
-    match provenance {
-        UrlProvenance(url) => {
-            debug!("cssparse: loading style sheet at %s", url.to_str());
-            let (input_port, input_chan) = comm::stream();
-            resource_task.send(Load(url, input_chan));
-            resource_port_to_data_stream(input_port)
-        }
-        InlineProvenance(_, data) => {
-            data_to_data_stream(data)
-        }
+/// Parses the text of a style sheet for layout.
+pub fn parse_stylesheet_text(url: Url, text: ~str) -> LayoutStylesheet {
+    LayoutStylesheet {
+        properties: stylesheets::parse_stylesheet(text),
+        libcss: Stylesheet::new(url, data_to_data_stream(text)),
     }
 }
 
-fn resource_port_to_data_stream(input_port: Port<ProgressMsg>) -> DataStream {
-    return || {
+/// Loads the style sheet at `url` and decodes it as UTF-8, or as Latin-1 if it isn't valid UTF-8.
+///
+/// FIXME: The encoding should come from the `@charset` rule of the sheet, its `Content-Type` or
+/// the document, as css-syntax says.
+fn load_text(url: Url, resource_task: ResourceTask) -> ~str {
+    let (input_port, input_chan) = comm::stream();
+    resource_task.send(Load(url, input_chan));
+    let mut data = ~[];
+    loop {
         match input_port.recv() {
-            Payload(data) => Some(data),
-            Done(*) => None
+            Payload(bytes) => data.push_all_move(bytes),
+            Done(*) => break,
         }
     }
+    if str::is_utf8(data) {
+        str::from_utf8(data)
+    } else {
+        data.iter().map(|&byte| byte as char).collect()
+    }
 }
 
 fn data_to_data_stream(data: ~str) -> DataStream {
@@ -76,4 +106,3 @@ fn data_to_data_stream(data: ~str) -> DataStream {
         }
     }
 }
-
@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::utils::str;
+use dom::documenttype::{FullQuirks, QuirksMode};
 use dom::element::*;
 use dom::htmlelement::HTMLElement;
 use dom::htmlheadingelement::{Heading1, Heading2, Heading3, Heading4, Heading5, Heading6};
@@ -10,17 +11,19 @@ use dom::htmliframeelement::IFrameSize;
 use dom::htmlformelement::HTMLFormElement;
 use dom::node::{AbstractNode, ElementNodeTypeId, Node, ScriptView};
 use dom::types::*;
-use html::cssparse::{InlineProvenance, StylesheetProvenance, UrlProvenance, spawn_css_parser};
+use html::cssparse::{InlineProvenance, StylesheetProvenance, StylesheetSource, UrlProvenance};
+use html::cssparse::{LayoutStylesheet, spawn_css_parser};
 use js::jsapi::JSContext;
-use newcss::stylesheet::Stylesheet;
 use script_task::page_from_context;
 
 use std::cast;
 use std::cell::Cell;
 use std::comm;
 use std::comm::{Port, SharedChan};
+use std::ascii::StrAsciiExt;
 use std::str::eq_slice;
 use std::task;
+use std::util::replace;
 use std::from_str::FromStr;
 use hubbub::hubbub;
 use servo_msg::constellation_msg::{ConstellationChan, SubpageId};
@@ -74,7 +77,9 @@ macro_rules! handle_htmlmediaelement(
 
 pub struct JSFile {
     data: ~[u8],
-    url: Url
+    url: Url,
+    /// The index of the script element in `HtmlParserResult::scripts`.
+    script: uint,
 }
 
 type JSResult = ~[JSFile];
@@ -85,14 +90,17 @@ enum CSSMessage {
 }
 
 enum JSMessage {
-    JSTaskNewFile(Url),
-    JSTaskNewInlineScript(~str, Url),
+    JSTaskNewFile(Url, uint),
+    JSTaskNewInlineScript(~str, Url, uint),
     JSTaskExit
 }
 
 /// Messages generated by the HTML parser upon discovery of additional resources
 pub enum HtmlDiscoveryMessage {
-    HtmlDiscoveredStyle(Stylesheet),
+    /// A style sheet parsed for layout, with its text for script.
+    HtmlDiscoveredStyle(LayoutStylesheet, StylesheetSource),
+    /// Sent once every style sheet of the document has been sent, so that it can be painted.
+    HtmlDiscoveredAllStyles,
     HtmlDiscoveredIFrame((Url, SubpageId, Future<Size2D<uint>>)),
     HtmlDiscoveredScript(JSResult)
 }
@@ -100,6 +108,13 @@ pub enum HtmlDiscoveryMessage {
 pub struct HtmlParserResult {
     root: AbstractNode<ScriptView>,
     discovery_port: Port<HtmlDiscoveryMessage>,
+    /// The document's default language, if it was set with a `Content-Language` pragma.
+    content_language: Option<~str>,
+    /// The mode of the document, which its doctype decides. Documents without one are in quirks
+    /// mode.
+    quirks_mode: QuirksMode,
+    /// The script elements of the document, in the order in which the parser finished them.
+    scripts: ~[AbstractNode<ScriptView>],
 }
 
 trait NodeWrapping {
@@ -150,8 +165,10 @@ fn css_link_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
     // Send the sheets back in order
     // FIXME: Shouldn't wait until after we've recieved CSSTaskExit to start sending these
     for port in result_vec.iter() {
-        to_parent.send(HtmlDiscoveredStyle(port.recv()));
+        let (sheet, source) = port.recv();
+        to_parent.send(HtmlDiscoveredStyle(sheet, source));
     }
+    to_parent.send(HtmlDiscoveredAllStyles);
 }
 
 fn js_script_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
@@ -161,7 +178,7 @@ fn js_script_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
 
     loop {
         match from_parent.recv() {
-            JSTaskNewFile(url) => {
+            JSTaskNewFile(url, script) => {
                 let (result_port, result_chan) = comm::stream();
                 let resource_task = resource_task.clone();
                 let url_clone = url.clone();
@@ -191,11 +208,15 @@ fn js_script_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
 
                 let bytes = result_port.recv();
                 if bytes.is_some() {
-                    result_vec.push(JSFile { data: bytes.unwrap(), url: url_clone });
+                    result_vec.push(JSFile {
+                        data: bytes.unwrap(),
+                        url: url_clone,
+                        script: script,
+                    });
                 }
             }
-            JSTaskNewInlineScript(data, url) => {
-                result_vec.push(JSFile { data: data.into_bytes(), url: url });
+            JSTaskNewInlineScript(data, url, script) => {
+                result_vec.push(JSFile { data: data.into_bytes(), url: url, script: script });
             }
             JSTaskExit => {
                 break;
@@ -209,7 +230,7 @@ fn js_script_listener(to_parent: SharedChan<HtmlDiscoveryMessage>,
 // Silly macros to handle constructing      DOM nodes. This produces bad code and should be optimized
 // via atomization (issue #85).
 
-fn build_element_from_tag(cx: *JSContext, tag: &str) -> AbstractNode<ScriptView> {
+pub fn build_element_from_tag(cx: *JSContext, tag: &str) -> AbstractNode<ScriptView> {
     // TODO (Issue #85): use atoms
     handle_element!(cx, tag, "a",       HTMLAnchorElementTypeId, HTMLAnchorElement, []);
     handle_element!(cx, tag, "applet",  HTMLAppletElementTypeId, HTMLAppletElement, []);
@@ -218,7 +239,7 @@ fn build_element_from_tag(cx: *JSContext, tag: &str) -> AbstractNode<ScriptView>
     handle_element!(cx, tag, "br",      HTMLBRElementTypeId, HTMLBRElement, []);
     handle_element!(cx, tag, "body",    HTMLBodyElementTypeId, HTMLBodyElement, []);
     handle_element!(cx, tag, "button",  HTMLButtonElementTypeId, HTMLButtonElement, []);
-    handle_element!(cx, tag, "canvas",  HTMLCanvasElementTypeId, HTMLCanvasElement, []);
+    handle_element!(cx, tag, "canvas",  HTMLCanvasElementTypeId, HTMLCanvasElement, [(context: None), (drawings: ~[])]);
     handle_element!(cx, tag, "data",    HTMLDataElementTypeId, HTMLDataElement, []);
     handle_element!(cx, tag, "datalist",HTMLDataListElementTypeId, HTMLDataListElement, []);
     handle_element!(cx, tag, "directory",HTMLDirectoryElementTypeId, HTMLDirectoryElement, []);
@@ -233,7 +254,7 @@ fn build_element_from_tag(cx: *JSContext, tag: &str) -> AbstractNode<ScriptView>
     handle_element!(cx, tag, "hr",      HTMLHRElementTypeId, HTMLHRElement, []);
     handle_element!(cx, tag, "head",    HTMLHeadElementTypeId, HTMLHeadElement, []);
     handle_element!(cx, tag, "html",    HTMLHtmlElementTypeId, HTMLHtmlElement, []);
-    handle_element!(cx, tag, "input",   HTMLInputElementTypeId, HTMLInputElement, []);
+    handle_element!(cx, tag, "input",   HTMLInputElementTypeId, HTMLInputElement, [(custom_validity_error: ~"")]);
     handle_element!(cx, tag, "label",   HTMLLabelElementTypeId, HTMLLabelElement, []);
     handle_element!(cx, tag, "legend",  HTMLLegendElementTypeId, HTMLLegendElement, []);
     handle_element!(cx, tag, "link",    HTMLLinkElementTypeId, HTMLLinkElement, []);
@@ -299,6 +320,185 @@ fn build_element_from_tag(cx: *JSContext, tag: &str) -> AbstractNode<ScriptView>
     }
 }
 
+/// Returns the URL of the namespace that the parser puts an attribute in, or the empty string for
+/// none.
+fn attribute_namespace(ns: hubbub::Ns) -> &'static str {
+    match ns {
+        hubbub::XLinkNs => XLINK_NAMESPACE,
+        hubbub::XmlNs => XML_NAMESPACE,
+        hubbub::XmlNsNs => XMLNS_NAMESPACE,
+        _ => "",
+    }
+}
+
+/// Starts loading the image of an image element from its `src`, resolved against `base_url`.
+fn load_image(node: AbstractNode<ScriptView>, base_url: Url, image_cache_task: &ImageCacheTask) {
+    do node.with_mut_image_element |image_element| {
+        let elem = &mut image_element.parent.parent;
+        let src_opt = elem.get_attr("src").map(|x| x.to_str());
+        match src_opt {
+            None => {}
+            Some(src) => {
+                let img_url = make_url(src, Some(base_url.clone()));
+                image_element.image = Some(img_url.clone());
+                // inform the image cache to load this, but don't store a handle.
+                // TODO (Issue #84): don't prefetch if we are within a <noscript>
+                // tag.
+                image_cache_task.send(image_cache_task::Prefetch(img_url));
+            }
+        }
+    }
+}
+
+/// Returns the elements, outermost first, that markup is parsed in for it to be parsed as the
+/// contents of an element with the given tag name. Hubbub has no fragment case, so the markup is
+/// parsed in a document, inside elements that put the parser into the insertion mode and the
+/// tokenizer state that the element would.
+fn fragment_context_path(tag_name: &str) -> ~[&'static str] {
+    let inner = match tag_name {
+        "html" => return ~["html"],
+        "table" => ~["table"],
+        "caption" => ~["table", "caption"],
+        "colgroup" => ~["table", "colgroup"],
+        "tbody" => ~["table", "tbody"],
+        "thead" => ~["table", "thead"],
+        "tfoot" => ~["table", "tfoot"],
+        "tr" => ~["table", "tbody", "tr"],
+        "td" => ~["table", "tbody", "tr", "td"],
+        "th" => ~["table", "tbody", "tr", "th"],
+        "select" => ~["select"],
+        // The elements whose contents are text.
+        "title" => ~["title"],
+        "textarea" => ~["textarea"],
+        "style" => ~["style"],
+        "xmp" => ~["xmp"],
+        "iframe" => ~["iframe"],
+        "noembed" => ~["noembed"],
+        "noframes" => ~["noframes"],
+        "noscript" => ~["noscript"],
+        "script" => ~["script"],
+        "plaintext" => ~["plaintext"],
+        _ => ~[],
+    };
+    ~["html", "body"] + inner
+}
+
+/// Parses `markup` as the contents of `context`, as the HTML fragment parsing algorithm does, and
+/// returns the nodes that it makes in a document fragment that belongs to the document of
+/// `context`. Images start loading, resolved against `base_url`, but scripts aren't run, and
+/// neither style sheets nor frames are loaded.
+///
+/// FIXME: The markup can close the elements it is parsed in, after which the rest of it is lost.
+pub fn parse_html_fragment(cx: *JSContext,
+                           context: AbstractNode<ScriptView>,
+                           markup: &str,
+                           base_url: Url,
+                           image_cache_task: ImageCacheTask)
+                           -> AbstractNode<ScriptView> {
+    // Markup is parsed in a node that isn't an element as it would be in `body`.
+    let tag_name = if context.is_element() {
+        context.with_imm_element(|element| element.tag_name.to_ascii_lower())
+    } else {
+        ~"body"
+    };
+    let path = fragment_context_path(tag_name);
+    let mut prefix = ~"<!DOCTYPE html>";
+    for tag in path.iter().skip(1) {
+        prefix.push_str(fmt!("<%s>", *tag));
+    }
+
+    let document_node = unsafe { Node::as_abstract_node(cx, @DocumentFragment::new()) };
+    let mut parser = hubbub::Parser("UTF-8", false);
+    parser.set_document_node(unsafe { document_node.to_hubbub_node() });
+    parser.enable_scripting(true);
+    parser.enable_styling(true);
+    parser.set_tree_handler(~hubbub::TreeHandler {
+        create_comment: |data: ~str| {
+            unsafe {
+                Node::as_abstract_node(cx, @Comment::new(data)).to_hubbub_node()
+            }
+        },
+        create_doctype: |doctype: ~hubbub::Doctype| {
+            let ~hubbub::Doctype {name: name,
+                                public_id: public_id,
+                                system_id: system_id,
+                                force_quirks: force_quirks } = doctype;
+            let node = @DocumentType::new(name, public_id, system_id, force_quirks);
+            unsafe {
+                Node::as_abstract_node(cx, node).to_hubbub_node()
+            }
+        },
+        create_element: |tag: ~hubbub::Tag| {
+            let node = build_element_from_tag(cx, tag.name);
+            do node.as_mut_element |element| {
+                for attr in tag.attributes.iter() {
+                    element.set_attr_ns(attribute_namespace(attr.ns),
+                                        &str(attr.name.clone()),
+                                        &str(attr.value.clone()));
+                }
+            }
+            if node.is_image_element() {
+                load_image(node, base_url.clone(), &image_cache_task)
+            }
+            unsafe { node.to_hubbub_node() }
+        },
+        create_text: |data: ~str| {
+            unsafe {
+                Node::as_abstract_node(cx, @Text::new(data)).to_hubbub_node()
+            }
+        },
+        ref_node: |_| {},
+        unref_node: |_| {},
+        append_child: |parent: hubbub::NodeDataPtr, child: hubbub::NodeDataPtr| {
+            unsafe {
+                let parent: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(parent);
+                let child: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(child);
+                parent.add_child(child);
+            }
+            child
+        },
+        insert_before: |_parent, _child| 0u,
+        remove_child: |_parent, _child| 0u,
+        clone_node: |_node, _deep| fail!(~"clone node unimplemented"),
+        reparent_children: |_node, _new_parent| 0u,
+        get_parent: |_node, _element_only| 0u,
+        has_children: |_node| false,
+        form_associate: |_form, _node| {},
+        add_attributes: |_node, _attributes| {},
+        set_quirks_mode: |_mode| {},
+        encoding_change: |_encname| {},
+        complete_script: |_script| {},
+        complete_style: |_style| {},
+    });
+    parser.parse_chunk((prefix + markup).as_bytes().to_owned());
+
+    // The nodes of the fragment are the children of the innermost element of the path.
+    let fragment = unsafe { Node::as_abstract_node(cx, @DocumentFragment::new()) };
+    let mut parent = Some(document_node);
+    for &tag in path.iter() {
+        parent = do parent.chain |parent| {
+            parent.children().filter(|child| {
+                child.is_element() && child.with_imm_element(|element| {
+                    eq_slice(element.tag_name, tag)
+                })
+            }).last()
+        };
+    }
+    for parent in parent.iter() {
+        let children: ~[AbstractNode<ScriptView>] = parent.children().collect();
+        for &child in children.iter() {
+            parent.remove_child(child);
+            fragment.add_child(child);
+        }
+    }
+    let owner_doc = context.with_base(|base| base.owner_doc);
+    fragment.with_mut_base(|base| base.owner_doc = owner_doc);
+    for doc in owner_doc.iter() {
+        fragment.with_mut_base(|base| base.add_to_doc(*doc));
+    }
+    fragment
+}
+
 pub fn parse_html(cx: *JSContext,
                   url: Url,
                   resource_task: ResourceTask,
@@ -346,6 +546,9 @@ pub fn parse_html(cx: *JSContext,
 
     let (css_chan2, css_chan3, js_chan2) = (css_chan.clone(), css_chan.clone(), js_chan.clone());
     let next_subpage_id = Cell::new(next_subpage_id);
+    let content_language = Cell::new_empty();
+    let quirks_mode = @mut FullQuirks;
+    let scripts = @mut ~[];
     
     parser.set_tree_handler(~hubbub::TreeHandler {
         create_comment: |data: ~str| {
@@ -364,6 +567,8 @@ pub fn parse_html(cx: *JSContext,
                                           public_id,
                                           system_id,
                                           force_quirks);
+            *quirks_mode = node.quirks_mode();
+            debug!("quirks mode: %?", *quirks_mode);
             unsafe {
                 Node::as_abstract_node(cx, node).to_hubbub_node()
             }
@@ -375,7 +580,9 @@ pub fn parse_html(cx: *JSContext,
             debug!("-- attach attrs");
             do node.as_mut_element |element| {
                 for attr in tag.attributes.iter() {
-                    element.set_attr(&str(attr.name.clone()), &str(attr.value.clone()));
+                    element.set_attr_ns(attribute_namespace(attr.ns),
+                                        &str(attr.name.clone()),
+                                        &str(attr.value.clone()));
                 }
             }
 
@@ -397,6 +604,26 @@ pub fn parse_html(cx: *JSContext,
                     }
                 }
 
+                // Handle the document's default language from
+                // `<meta http-equiv="Content-Language">`.
+                // TODO: Also honor the HTTP `Content-Language` header.
+                ElementNodeTypeId(HTMLMetaElementTypeId) => {
+                    do node.with_imm_element |element| {
+                        match (element.get_attr("http-equiv"), element.get_attr("content")) {
+                            (Some(equiv), Some(lang)) => {
+                                let lang = lang.trim();
+                                if equiv.eq_ignore_ascii_case("content-language") &&
+                                        !lang.is_empty() && !lang.contains_char(',') &&
+                                        content_language.is_empty() {
+                                    debug!("found content language: %s", lang);
+                                    content_language.put_back(lang.to_owned());
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+
                 ElementNodeTypeId(HTMLIframeElementTypeId) => {
                     let iframe_chan = Cell::new(discovery_chan.clone());
                     do node.with_mut_iframe_element |iframe_element| {
@@ -433,21 +660,7 @@ pub fn parse_html(cx: *JSContext,
                 }
 
                 ElementNodeTypeId(HTMLImageElementTypeId) => {
-                    do node.with_mut_image_element |image_element| {
-                        let elem = &mut image_element.parent.parent;
-                        let src_opt = elem.get_attr("src").map(|x| x.to_str());
-                        match src_opt {
-                            None => {}
-                            Some(src) => {
-                                let img_url = make_url(src, Some(url2.clone()));
-                                image_element.image = Some(img_url.clone());
-                                // inform the image cache to load this, but don't store a handle.
-                                // TODO (Issue #84): don't prefetch if we are within a <noscript>
-                                // tag.
-                                image_cache_task.send(image_cache_task::Prefetch(img_url));
-                            }
-                        }
-                    }
+                    load_image(node, url2.clone(), &image_cache_task)
                 }
 
                 _ => {}
@@ -513,15 +726,18 @@ pub fn parse_html(cx: *JSContext,
             // A little function for holding this lint attr
             fn complete_script(script: hubbub::NodeDataPtr,
                                url: Url,
-                               js_chan: SharedChan<JSMessage>) {
+                               js_chan: SharedChan<JSMessage>,
+                               scripts: @mut ~[AbstractNode<ScriptView>]) {
                 unsafe {
                     let scriptnode: AbstractNode<ScriptView> = NodeWrapping::from_hubbub_node(script);
+                    let index = scripts.len();
+                    scripts.push(scriptnode);
                     do scriptnode.with_imm_element |script| {
                         match script.get_attr("src") {
                             Some(src) => {
                                 debug!("found script: %s", src);
                                 let new_url = make_url(src.to_str(), Some(url.clone()));
-                                js_chan.send(JSTaskNewFile(new_url));
+                                js_chan.send(JSTaskNewFile(new_url, index));
                             }
                             None => {
                                 let mut data = ~[];
@@ -534,13 +750,15 @@ pub fn parse_html(cx: *JSContext,
                                 }
 
                                 debug!("data = %?", data);
-                                js_chan.send(JSTaskNewInlineScript(data.concat(), url.clone()));
+                                js_chan.send(JSTaskNewInlineScript(data.concat(),
+                                                                   url.clone(),
+                                                                   index));
                             }
                         }
                     }
                 }
             }
-            complete_script(script, url3.clone(), js_chan2.clone());
+            complete_script(script, url3.clone(), js_chan2.clone(), scripts);
             debug!("complete script");
         },
         complete_style: |style| {
@@ -588,9 +806,18 @@ pub fn parse_html(cx: *JSContext,
     css_chan.send(CSSTaskExit);
     js_chan.send(JSTaskExit);
 
+    let content_language = if content_language.is_empty() {
+        None
+    } else {
+        Some(content_language.take())
+    };
+
     HtmlParserResult {
         root: root,
         discovery_port: discovery_port,
+        content_language: content_language,
+        quirks_mode: *quirks_mode,
+        scripts: replace(&mut *scripts, ~[]),
     }
 }
 
@@ -0,0 +1,122 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Serializes nodes as markup, as the HTML fragment serialization algorithm does, for
+//! `innerHTML` and `outerHTML` and for saving pages.
+
+use dom::documenttype::{DocumentType, QuirksMode, NoQuirks, LimitedQuirks, FullQuirks};
+use dom::node::{AbstractNode, ScriptView, ElementNodeTypeId, TextNodeTypeId, CommentNodeTypeId};
+use dom::node::{DoctypeNodeTypeId, DocumentFragmentNodeTypeId};
+
+use std::ascii::StrAsciiExt;
+use std::str::eq_slice;
+
+/// The elements that have no contents and no end tag.
+static VOID_ELEMENTS: &'static [&'static str] = &[
+    "area", "base", "basefont", "bgsound", "br", "col", "embed", "frame", "hr", "img", "input",
+    "keygen", "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// The elements whose text is written out as it is, since the parser doesn't unescape it.
+/// Scripting is always enabled, so `noscript` is one of them.
+static RAW_TEXT_ELEMENTS: &'static [&'static str] = &[
+    "style", "script", "xmp", "iframe", "noembed", "noframes", "plaintext", "noscript",
+];
+
+/// Returns the markup of the children of `node`, as `innerHTML` does.
+pub fn serialize_children(node: AbstractNode<ScriptView>) -> ~str {
+    let mut markup = ~"";
+    for child in node.children() {
+        serialize_node_into(child, &mut markup)
+    }
+    markup
+}
+
+/// Returns the markup of `node` and its descendants, as `outerHTML` does.
+pub fn serialize_node(node: AbstractNode<ScriptView>) -> ~str {
+    let mut markup = ~"";
+    serialize_node_into(node, &mut markup);
+    markup
+}
+
+/// Returns the markup of a document whose root element is `root`, to save the page as script
+/// has left it. The doctype isn't kept in the tree, so the markup starts with one that puts the
+/// document in the mode it is in when it is loaded again.
+pub fn serialize_document(root: AbstractNode<ScriptView>, quirks_mode: QuirksMode) -> ~str {
+    let doctype = match quirks_mode {
+        NoQuirks => "<!DOCTYPE html>\n",
+        LimitedQuirks => {
+            "<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Transitional//EN\">\n"
+        }
+        FullQuirks => "",
+    };
+    doctype.to_owned() + serialize_node(root)
+}
+
+fn serialize_node_into(node: AbstractNode<ScriptView>, markup: &mut ~str) {
+    match node.type_id() {
+        ElementNodeTypeId(_) => {
+            let (tag_name, start_tag) = do node.with_imm_element |element| {
+                let tag_name = element.tag_name.to_ascii_lower();
+                let mut start_tag = fmt!("<%s", tag_name);
+                for attr in element.attrs.iter() {
+                    start_tag.push_str(fmt!(" %s=\"%s\"",
+                                            attr.qualified_name(),
+                                            escape(attr.value, true)));
+                }
+                start_tag.push_char('>');
+                (tag_name, start_tag)
+            };
+            markup.push_str(start_tag);
+            if VOID_ELEMENTS.iter().any(|name| eq_slice(*name, tag_name)) {
+                return
+            }
+            for child in node.children() {
+                serialize_node_into(child, markup)
+            }
+            markup.push_str(fmt!("</%s>", tag_name));
+        }
+        TextNodeTypeId => {
+            let raw = do node.parent_node().map_default(false) |parent| {
+                parent.is_element() && do parent.with_imm_element |element| {
+                    RAW_TEXT_ELEMENTS.iter().any(|name| {
+                        element.tag_name.eq_ignore_ascii_case(*name)
+                    })
+                }
+            };
+            let data = node.with_imm_text(|text| text.parent.data.clone());
+            markup.push_str(if raw { data } else { escape(data, false) });
+        }
+        CommentNodeTypeId => {
+            let data = node.with_imm_characterdata(|comment| comment.data.clone());
+            markup.push_str(fmt!("<!--%s-->", data));
+        }
+        DoctypeNodeTypeId => {
+            let name = node.transmute(|doctype: &DocumentType<ScriptView>| doctype.name.clone());
+            markup.push_str(fmt!("<!DOCTYPE %s>", name));
+        }
+        DocumentFragmentNodeTypeId => {
+            for child in node.children() {
+                serialize_node_into(child, markup)
+            }
+        }
+    }
+}
+
+/// Escapes text, or the value of an attribute if `attribute` is true, so that it is parsed back
+/// the same.
+fn escape(text: &str, attribute: bool) -> ~str {
+    let mut escaped = ~"";
+    for ch in text.iter() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '\u00a0' => escaped.push_str("&nbsp;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '<' if !attribute => escaped.push_str("&lt;"),
+            '>' if !attribute => escaped.push_str("&gt;"),
+            _ => escaped.push_char(ch),
+        }
+    }
+    escaped
+}
@@ -6,14 +6,17 @@
 /// coupling between these two components, and enables the DOM to be placed in a separate crate
 /// from layout.
 
+use dom::documenttype::QuirksMode;
+use dom::element::ElementState;
 use dom::node::{AbstractNode, ScriptView, LayoutView};
+use html::cssparse::LayoutStylesheet;
 use script_task::{ScriptChan};
 use std::comm::{Chan, SharedChan};
 use geom::rect::Rect;
 use geom::size::Size2D;
 use geom::point::Point2D;
+use gfx::font::{FontStyle, TextMetrics};
 use gfx::geometry::Au;
-use newcss::stylesheet::Stylesheet;
 use extra::url::Url;
 
 /// Asynchronous messages that script can send to layout.
@@ -21,11 +24,26 @@ use extra::url::Url;
 /// FIXME(pcwalton): I think this should probably be merged with `LayoutQuery` below.
 pub enum Msg {
     /// Adds the given stylesheet to the document.
-    AddStylesheetMsg(Stylesheet),
+    AddStylesheetMsg(LayoutStylesheet),
+
+    /// Replaces the stylesheets of the document with the given ones, which script has parsed
+    /// again after editing their rules.
+    ReplaceStylesheetsMsg(~[LayoutStylesheet]),
+
+    /// Adds a style sheet, with its URL and text, at the user agent origin after the others, which
+    /// stays when the author sheets are replaced.
+    AddUserAgentStylesheetMsg(Url, ~str),
 
     /// Requests a reflow.
     ReflowMsg(~Reflow),
 
+    /// Tells layout where the viewport has scrolled to in the page, so that content that has come
+    /// near it can be rendered.
+    ScrollMsg(Point2D<Au>),
+
+    /// Tells layout how far the user has pinch zoomed in, which shrinks the visual viewport.
+    ZoomMsg(f32),
+
     /// Performs a synchronous layout request.
     ///
     /// FIXME(pcwalton): As noted below, this isn't very type safe.
@@ -41,10 +59,39 @@ pub enum LayoutQuery {
     ContentBoxQuery(AbstractNode<ScriptView>, Chan<ContentBoxResponse>),
     /// Requests the dimensions of all the content boxes, as in the `getClientRects()` call.
     ContentBoxesQuery(AbstractNode<ScriptView>, Chan<ContentBoxesResponse>),
+    /// Requests the dimensions of all the boxes of the given nodes, which a range selects, as in
+    /// the `getBoundingClientRect()` call of ranges.
+    RangeBoxesQuery(~[AbstractNode<ScriptView>], Chan<ContentBoxesResponse>),
     /// Requests the node containing the point of interest
     HitTestQuery(AbstractNode<ScriptView>, Point2D<f32>, Chan<Result<HitTestResponse, ()>>),
+    /// Measures a line of text in the given font, as the `measureText()` of canvas contexts does.
+    TextMetricsQuery(FontStyle, ~str, Chan<TextMetrics>),
+    /// Requests the resolved value of a property of an element, as `getComputedStyle()` gives
+    /// it, or `None` if the property isn't one of `COMPUTED_STYLE_PROPERTIES` or the element has
+    /// no style.
+    ComputedStyleQuery(AbstractNode<ScriptView>, ~str, Chan<Option<~str>>),
 }
 
+/// The properties whose resolved values layout can give, in the order that the declarations
+/// returned by `getComputedStyle()` list them.
+pub static COMPUTED_STYLE_PROPERTIES: &'static [&'static str] = &[
+    "background-color",
+    "border-bottom-color",
+    "border-left-color",
+    "border-right-color",
+    "border-top-color",
+    "color",
+    "display",
+    "float",
+    "font-family",
+    "font-size",
+    "font-style",
+    "height",
+    "position",
+    "text-align",
+    "width",
+];
+
 pub struct ContentBoxResponse(Rect<Au>);
 pub struct ContentBoxesResponse(~[Rect<Au>]);
 pub struct HitTestResponse(AbstractNode<LayoutView>);
@@ -53,8 +100,18 @@ pub struct HitTestResponse(AbstractNode<LayoutView>);
 pub enum DocumentDamageLevel {
     /// Perform CSS selector matching and reflow.
     MatchSelectorsDocumentDamage,
+    /// Perform CSS selector matching on the elements whose states that the dynamic pseudo-classes
+    /// match have changed, where selectors depend on those states, and on the subtrees of the
+    /// elements that edited style rules match, and reflow.
+    RestyleStatesDocumentDamage,
+    /// Perform CSS selector matching on the subtrees of the nodes that script has marked dirty,
+    /// and reflow.
+    RestyleDirtyDocumentDamage,
     /// Reflow, but do not perform CSS selector matching.
     ReflowDocumentDamage,
+    /// Repaint the values that running transitions have reached, but neither perform CSS
+    /// selector matching nor reflow.
+    RepaintDocumentDamage,
 }
 
 impl DocumentDamageLevel {
@@ -64,7 +121,16 @@ impl DocumentDamageLevel {
     /// function removed.
     pub fn add(&mut self, new_damage: DocumentDamageLevel) {
         match (*self, new_damage) {
+            (RepaintDocumentDamage, new_damage) => *self = new_damage,
+            (ReflowDocumentDamage, RepaintDocumentDamage) => {}
             (ReflowDocumentDamage, new_damage) => *self = new_damage,
+            (RestyleDirtyDocumentDamage, RestyleStatesDocumentDamage) |
+            (RestyleDirtyDocumentDamage, MatchSelectorsDocumentDamage) => *self = new_damage,
+            (RestyleDirtyDocumentDamage, _) => {}
+            (RestyleStatesDocumentDamage, MatchSelectorsDocumentDamage) => {
+                *self = MatchSelectorsDocumentDamage
+            }
+            (RestyleStatesDocumentDamage, _) => {}
             (MatchSelectorsDocumentDamage, _) => *self = MatchSelectorsDocumentDamage,
         }
     }
@@ -78,6 +144,11 @@ pub struct DocumentDamage {
     root: AbstractNode<ScriptView>,
     /// The amount of damage that occurred.
     level: DocumentDamageLevel,
+    /// The elements whose states that the dynamic pseudo-classes match have changed, with the
+    /// states that changed.
+    state_changes: ~[(AbstractNode<ScriptView>, ElementState)],
+    /// The elements that the selectors of style rules that script has edited match.
+    restyled_elements: ~[AbstractNode<ScriptView>],
 }
 
 /// Why we're doing reflow.
@@ -105,6 +176,8 @@ pub struct Reflow {
     window_size: Size2D<uint>,
     /// The channel that we send a notification to.
     script_join_chan: Chan<()>,
+    /// The mode of the document, which decides which quirks layout has.
+    quirks_mode: QuirksMode,
 }
 
 /// Encapsulates a channel to the layout task.
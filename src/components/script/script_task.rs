@@ -6,19 +6,30 @@
 /// and layout tasks.
 
 use servo_msg::compositor_msg::{ScriptListener, Loading, PerformingLayout};
-use servo_msg::compositor_msg::FinishedLoading;
-use dom::bindings::utils::GlobalStaticData;
+use servo_msg::compositor_msg::{FinishedLoading, DatePicker, ColorPicker, AutoScroll};
+use dom::bindings::utils::{CacheableWrapper, GlobalStaticData};
+use dom::cssstylesheet::sheet_selectors;
 use dom::document::AbstractDocument;
-use dom::element::Element;
+use dom::editing;
+use dom::element::{Element, HTMLScriptElementTypeId};
 use dom::event::{Event_, ResizeEvent, ReflowEvent, ClickEvent, MouseDownEvent, MouseUpEvent};
+use dom::event::{PickerResultEvent, FocusNavigationEvent, FocusNext, FocusPrevious};
+use dom::event::{ActivateFocused, AccessKeyEvent, EditingCommandEvent, UndoCommand, RedoCommand};
+use dom::event::{AnimationTickEvent, ImageLoadedEvent, ScrollEvent, MouseMoveEvent, ZoomEvent};
+use dom::eventdispatcher::{fire_event, fire_mouse_event};
 use dom::htmldocument::HTMLDocument;
-use dom::node::{define_bindings};
+use dom::htmlinputelement::{RangeInput, NumberInput, DateInput, ColorInput, fire_value_changed};
+use dom::node::{AbstractNode, LayoutView, ScriptView, ElementNodeTypeId, define_bindings};
 use dom::window::Window;
-use layout_interface::{AddStylesheetMsg, DocumentDamage};
+use layout_interface::{AddStylesheetMsg, AddUserAgentStylesheetMsg, DocumentDamage};
+use layout_interface::{ComputedStyleQuery, ContentBoxQuery, ContentBoxResponse};
 use layout_interface::{DocumentDamageLevel, HitTestQuery, HitTestResponse, LayoutQuery};
 use layout_interface::{LayoutChan, MatchSelectorsDocumentDamage, QueryMsg, Reflow};
 use layout_interface::{ReflowDocumentDamage, ReflowForDisplay, ReflowGoal};
-use layout_interface::ReflowMsg;
+use layout_interface::{ReflowMsg, RepaintDocumentDamage, ReplaceStylesheetsMsg};
+use layout_interface::{RestyleDirtyDocumentDamage, RestyleStatesDocumentDamage, ScrollMsg};
+use layout_interface::ZoomMsg;
+use layout_interface::TextMetricsQuery;
 use layout_interface;
 use servo_msg::constellation_msg::{ConstellationChan, LoadUrlMsg, NavigationDirection};
 use servo_msg::constellation_msg::{PipelineId, SubpageId, RendererReadyMsg};
@@ -28,28 +39,44 @@ use servo_msg::constellation_msg;
 use std::cell::Cell;
 use std::comm;
 use std::comm::{Port, SharedChan};
+use std::io;
 use std::io::read_whole_file;
 use std::ptr::null;
+use std::rt::io::timer::Timer;
+use std::rt::rtio::RtioTimer;
 use std::task::{SingleThreaded, task};
 use std::util::replace;
-use dom::window::TimerData;
+use dom::imagebitmap::{ImageBitmap, ImageBitmapData};
+use watchdog::Watchdog;
+use geom::point::Point2D;
 use geom::size::Size2D;
-use html::hubbub_html_parser::HtmlParserResult;
+use gfx::font::{FontStyle, TextMetrics};
+use gfx::geometry::{Au, CSSPixel};
+use gfx::opts::AutoplayPolicy;
+use html::hubbub_html_parser::{HtmlParserResult, parse_html_fragment};
 use html::hubbub_html_parser::{HtmlDiscoveredStyle, HtmlDiscoveredIFrame, HtmlDiscoveredScript};
+use html::hubbub_html_parser::{HtmlDiscoveredAllStyles, HtmlDiscoveryMessage};
+use html::cssparse::LayoutStylesheet;
 use html::hubbub_html_parser;
+use html::serializer::serialize_document;
+use timers;
 use js::JSVAL_NULL;
 use js::global::{global_class, debug_fns};
-use js::glue::RUST_JSVAL_TO_OBJECT;
+use js::glue::RUST_OBJECT_TO_JSVAL;
 use js::jsapi::JSContext;
-use js::jsapi::{JS_CallFunctionValue, JS_GetContextPrivate};
+use js::jsapi::{JSBool, JS_CallFunctionValue, JS_GetContextPrivate, JS_SetOperationCallback};
 use js::rust::{Compartment, Cx};
 use js;
 use servo_net::image_cache_task::ImageCacheTask;
 use servo_net::resource_task::ResourceTask;
+use servo_util::time::{FrameStageMsg, ProfilerChan, ScriptStage};
 use servo_util::tree::TreeNodeRef;
 use servo_util::url::make_url;
 use extra::url::Url;
 use extra::future::{from_value, Future};
+use extra::container::Deque;
+use extra::ringbuf::RingBuf;
+use extra::time::precise_time_ns;
 
 /// Messages used to control the script task.
 pub enum ScriptMsg {
@@ -63,16 +90,153 @@ pub enum ScriptMsg {
     NavigateMsg(NavigationDirection),
     /// Sends a DOM event.
     SendEventMsg(PipelineId, Event_),
-    /// Fires a JavaScript timeout.
-    FireTimerMsg(PipelineId, ~TimerData),
+    /// Runs the JavaScript timers of a page that are due.
+    FireTimerMsg(PipelineId),
+    /// Stops the timers of a page from running, as its document has gone into the session
+    /// history.
+    FreezeMsg(PipelineId),
+    /// Lets the timers of a page run again, as its document is shown.
+    ThawMsg(PipelineId),
+    /// Calls the callback of `createImageBitmap()` with an image that has been decoded.
+    ImageBitmapMsg(PipelineId, ~ImageBitmapData),
     /// Notifies script that reflow is finished.
     ReflowCompleteMsg(PipelineId),
     /// Notifies script that window has been resized but to not take immediate action.
     ResizeInactiveMsg(PipelineId, Size2D<uint>),
+    /// Adds a style sheet, with its URL and text, at the user agent origin after the others, to
+    /// the pages of the script task and to those it loads later.
+    AddDefaultStylesheetMsg(PipelineId, Url, ~str),
     /// Exits the constellation.
     ExitMsg,
 }
 
+/// The sources that the tasks of the event loop come from. Each turn of the loop runs the oldest
+/// task of the first source in this order that has one, so that input isn't kept waiting behind
+/// timers that keep firing, nor behind pages that are loading.
+#[deriving(Eq)]
+enum TaskSource {
+    /// Setting up, navigating and tearing down pages, which other tasks may depend on.
+    ControlTaskSource,
+    /// Events from the user.
+    UserInteractionTaskSource,
+    /// Resizing, laying out and painting pages again.
+    RenderingTaskSource,
+    /// Loading documents and scripts.
+    NetworkingTaskSource,
+    /// Timeouts firing.
+    TimerTaskSource,
+}
+
+impl ScriptMsg {
+    fn task_source(&self) -> TaskSource {
+        match *self {
+            AttachLayoutMsg(*) | NavigateMsg(*) | FreezeMsg(*) | ThawMsg(*) |
+            AddDefaultStylesheetMsg(*) | ExitMsg => ControlTaskSource,
+            SendEventMsg(_, ref event) if event.is_user_interaction() => {
+                UserInteractionTaskSource
+            }
+            SendEventMsg(*) | ReflowCompleteMsg(*) | ResizeInactiveMsg(*) => RenderingTaskSource,
+            LoadMsg(*) | ExecuteMsg(*) | ImageBitmapMsg(*) => NetworkingTaskSource,
+            FireTimerMsg(*) => TimerTaskSource,
+        }
+    }
+}
+
+/// The messages that the script task has received but not handled yet, queued by task source.
+struct TaskQueues {
+    control: RingBuf<ScriptMsg>,
+    user_interaction: RingBuf<ScriptMsg>,
+    rendering: RingBuf<ScriptMsg>,
+    networking: RingBuf<ScriptMsg>,
+    timers: RingBuf<ScriptMsg>,
+}
+
+impl TaskQueues {
+    fn new() -> TaskQueues {
+        TaskQueues {
+            control: RingBuf::new(),
+            user_interaction: RingBuf::new(),
+            rendering: RingBuf::new(),
+            networking: RingBuf::new(),
+            timers: RingBuf::new(),
+        }
+    }
+
+    fn push(&mut self, msg: ScriptMsg) {
+        let queue = match msg.task_source() {
+            ControlTaskSource => &mut self.control,
+            UserInteractionTaskSource => &mut self.user_interaction,
+            RenderingTaskSource => &mut self.rendering,
+            NetworkingTaskSource => &mut self.networking,
+            TimerTaskSource => &mut self.timers,
+        };
+        queue.push_back(msg)
+    }
+
+    /// Takes the task to run next: the oldest of the task source that comes first.
+    fn pop(&mut self) -> Option<ScriptMsg> {
+        if !self.control.is_empty() {
+            self.control.pop_front()
+        } else if !self.user_interaction.is_empty() {
+            self.user_interaction.pop_front()
+        } else if !self.rendering.is_empty() {
+            self.rendering.pop_front()
+        } else if !self.networking.is_empty() {
+            self.networking.pop_front()
+        } else {
+            self.timers.pop_front()
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.control.is_empty() && self.user_interaction.is_empty() &&
+            self.rendering.is_empty() && self.networking.is_empty() && self.timers.is_empty()
+    }
+}
+
+/// What loading a document waits for before painting it and running its scripts.
+enum LoadProgress {
+    /// Something that the HTML parser discovered.
+    DiscoveryProgress(HtmlDiscoveryMessage),
+    /// Sent once the HTML parser has sent everything it discovered.
+    DiscoveryFinished,
+    /// Sent when the first paint of the document shouldn't wait for its style sheets any longer.
+    PaintSuppressionTimeout,
+}
+
+/// How long the first paint of a document waits for its style sheets, in milliseconds.
+static PAINT_SUPPRESSION_TIMEOUT: u64 = 1000;
+
+/// Returns a port on which what the HTML parser discovers arrives, followed by
+/// `DiscoveryFinished`, and `PaintSuppressionTimeout` once the paint suppression timeout has
+/// passed, if the port is still open.
+fn forward_load_progress(discovery_port: Port<HtmlDiscoveryMessage>) -> Port<LoadProgress> {
+    let (load_port, load_chan) = comm::stream();
+    let load_chan = SharedChan::new(load_chan);
+
+    let discovery_port = Cell::new(discovery_port);
+    let discovery_chan = load_chan.clone();
+    do spawn {
+        let discovery_port = discovery_port.take();
+        loop {
+            match discovery_port.try_recv() {
+                Some(message) => discovery_chan.send(DiscoveryProgress(message)),
+                None => break,
+            }
+        }
+        discovery_chan.send(DiscoveryFinished);
+    }
+
+    let timer = Cell::new(Timer::new().unwrap());
+    do spawn {
+        let mut timer = timer.take();
+        timer.sleep(PAINT_SUPPRESSION_TIMEOUT);
+        load_chan.try_send(PaintSuppressionTimeout);
+    }
+
+    load_port
+}
+
 pub struct NewLayoutInfo {
     old_id: PipelineId,
     new_id: PipelineId,
@@ -128,6 +292,27 @@ pub struct Page {
     url: Option<(Url, bool)>,
 
     next_subpage_id: SubpageId,
+
+    /// The input element that the embedder is letting the user pick a value for, if any.
+    picker_input: Option<AbstractNode<ScriptView>>,
+
+    /// The access keys last registered with the compositor.
+    access_keys: ~[char],
+
+    /// When media in the page may start playing.
+    autoplay_policy: AutoplayPolicy,
+
+    /// Where the top left of the viewport is in the page, in pixels, as the compositor last told
+    /// us.
+    viewport_origin: Point2D<f32>,
+
+    /// How far the user has pinch zoomed in, as the compositor last told us: the visual viewport
+    /// is the window shrunk by this much.
+    zoom: f32,
+
+    /// The watchdog of the script task, which interrupts the scripts of the page when they run
+    /// for too long, if there is one.
+    watchdog: Option<Watchdog>,
 }
 
 pub struct PageTree {
@@ -140,7 +325,11 @@ pub struct PageTreeIterator<'self> {
 }
 
 impl PageTree {
-    fn new(id: PipelineId, layout_chan: LayoutChan, size_future: Future<Size2D<uint>>) -> PageTree {
+    fn new(id: PipelineId,
+           layout_chan: LayoutChan,
+           size_future: Future<Size2D<uint>>,
+           autoplay_policy: AutoplayPolicy)
+           -> PageTree {
         PageTree {
             page: @mut Page {
                 id: id,
@@ -152,6 +341,12 @@ impl PageTree {
                 js_info: None,
                 url: None,
                 next_subpage_id: SubpageId(0),
+                picker_input: None,
+                access_keys: ~[],
+                autoplay_policy: autoplay_policy,
+                viewport_origin: Point2D(0f32, 0f32),
+                zoom: 1f32,
+                watchdog: None,
             },
             inner: ~[],
         }
@@ -205,6 +400,8 @@ impl Page {
         self.damage = Some(DocumentDamage {
             root: do self.frame.get_ref().document.with_base |doc| { doc.root },
             level: level,
+            state_changes: ~[],
+            restyled_elements: ~[],
         })
     }
 
@@ -238,6 +435,52 @@ impl Page {
         response_port.recv()
     }
 
+    /// Scrolls the viewport so that its top left is at the given position in the page, in pixels.
+    /// The scroll is smooth if the `scroll-behavior` of the page asks for it.
+    pub fn scroll_to(&mut self, position: Point2D<f32>, compositor: @ScriptListener) {
+        compositor.scroll_to(self.id.clone(), position, AutoScroll)
+    }
+
+    /// Returns where a point in the page is in the viewport, in the whole pixels that mouse events
+    /// give.
+    pub fn client_point(&self, point: Point2D<f32>) -> Point2D<i32> {
+        Point2D((point.x - self.viewport_origin.x) as i32,
+                (point.y - self.viewport_origin.y) as i32)
+    }
+
+    /// Scrolls the viewport vertically so that the top of the box of the element is at its top,
+    /// or, if `top` is false, the bottom of the box is at its bottom.
+    pub fn scroll_into_view(&mut self,
+                            element: AbstractNode<ScriptView>,
+                            top: bool,
+                            compositor: @ScriptListener) {
+        let (port, chan) = comm::stream();
+        let rect = match self.query_layout(ContentBoxQuery(element, chan), port) {
+            ContentBoxResponse(rect) => rect,
+        };
+        let y = if top {
+            rect.origin.y
+        } else {
+            rect.origin.y + rect.size.height - Au::from_px(self.window_size.get().height as int)
+        };
+        self.scroll_to(Point2D(self.viewport_origin.x, y.to_f32()), compositor)
+    }
+
+    /// Measures a line of text in the given font with the fonts of layout, for the
+    /// `measureText()` of canvas contexts and for sizing tooltips.
+    pub fn measure_text(&mut self, font: FontStyle, text: ~str) -> TextMetrics {
+        let (port, chan) = comm::stream();
+        self.query_layout(TextMetricsQuery(font, text, chan), port)
+    }
+
+    /// Returns the resolved value of a property of an element, as `getComputedStyle()` gives it,
+    /// once layout has caught up with the changes made to the document.
+    pub fn resolved_style_value(&mut self, element: AbstractNode<ScriptView>, property: ~str)
+                                -> Option<~str> {
+        let (port, chan) = comm::stream();
+        self.query_layout(ComputedStyleQuery(element, property, chan), port)
+    }
+
     /// This method will wait until the layout task has completed its current action, join the
     /// layout task, and then request a new layout run. It won't wait for the new layout
     /// computation to finish.
@@ -260,6 +503,11 @@ impl Page {
         match self.frame {
             None => fail!(~"Tried to relayout with no root frame!"),
             Some(ref frame) => {
+                let mut damage = replace(&mut self.damage, None).unwrap();
+                damage.state_changes = do frame.document.with_mut_base |doc| {
+                    doc.take_state_changes()
+                };
+
                 // Send new document and relevant styles to layout.
                 let reflow = ~Reflow {
                     document_root: do frame.document.with_base |doc| { doc.root },
@@ -268,13 +516,22 @@ impl Page {
                     window_size: self.window_size.get(),
                     script_chan: script_chan,
                     script_join_chan: join_chan,
-                    damage: replace(&mut self.damage, None).unwrap(),
+                    damage: damage,
+                    quirks_mode: do frame.document.with_base |doc| { doc.quirks_mode },
                 };
 
                 self.layout_chan.send(ReflowMsg(reflow))
             }
         }
 
+        // Keep the access keys registered with the compositor up to date, since any change to the
+        // document, which causes this reflow, might have changed them.
+        let access_keys = self.frame.get_ref().document.with_base(|doc| doc.access_keys());
+        if access_keys != self.access_keys {
+            compositor.set_access_keys(self.id.clone(), access_keys.clone());
+            self.access_keys = access_keys;
+        }
+
         debug!("script: layout forked")
     }
 
@@ -289,7 +546,54 @@ impl Page {
         self.reflow(goal, script_chan, compositor)
     }
 
-    pub fn initialize_js_info(&mut self, js_context: @Cx) {
+    /// Restyles the nodes that script has marked dirty since the last restyle, rather than the
+    /// whole document, and reflows it.
+    pub fn reflow_dirty(&mut self,
+                        goal: ReflowGoal,
+                        script_chan: ScriptChan,
+                        compositor: @ScriptListener) {
+        if self.frame.is_some() {
+            self.damage(RestyleDirtyDocumentDamage);
+        }
+
+        self.reflow(goal, script_chan, compositor)
+    }
+
+    /// Restyles the elements whose states that the dynamic pseudo-classes match have changed, and
+    /// reflows the document for display.
+    pub fn restyle_element_states(&mut self,
+                                  script_chan: ScriptChan,
+                                  compositor: @ScriptListener) {
+        if self.frame.is_some() {
+            self.damage(RestyleStatesDocumentDamage);
+            self.reflow(ReflowForDisplay, script_chan, compositor)
+        }
+    }
+
+    /// Replaces the style sheets that layout has with `sheets`, which script has parsed again
+    /// after editing their rules, restyles the subtrees of the given elements, or the whole
+    /// document if they are `None`, and reflows the document for display.
+    pub fn replace_stylesheets(&mut self,
+                               sheets: ~[LayoutStylesheet],
+                               restyled: Option<~[AbstractNode<ScriptView>]>,
+                               script_chan: ScriptChan,
+                               compositor: @ScriptListener) {
+        if self.frame.is_none() {
+            return
+        }
+        self.layout_chan.send(ReplaceStylesheetsMsg(sheets));
+        match restyled {
+            Some(elements) => {
+                self.damage(RestyleStatesDocumentDamage);
+                self.damage.get_mut_ref().restyled_elements.push_all_move(elements);
+            }
+            None => self.damage(MatchSelectorsDocumentDamage),
+        }
+        self.reflow(ReflowForDisplay, script_chan, compositor)
+    }
+
+    #[fixed_stack_segment]
+    pub fn initialize_js_info(&mut self, js_context: @Cx, watchdog: Option<Watchdog>) {
         // Note that the order that these variables are initialized is _not_ arbitrary. Switching them around
         // can -- and likely will -- lead to things breaking.
 
@@ -311,6 +615,13 @@ impl Page {
             js_context.set_cx_private(page_ptr as *());
         }
 
+        if watchdog.is_some() {
+            unsafe {
+                JS_SetOperationCallback(js_context.ptr, operation_callback);
+            }
+        }
+        self.watchdog = watchdog;
+
         self.js_info = Some(JSPageInfo {
             dom_static: GlobalStaticData(),
             bindings_initialized: false,
@@ -354,6 +665,8 @@ pub struct ScriptTask {
 
     /// The port on which the script task receives messages (load URL, exit, etc.)
     port: Port<ScriptMsg>,
+    /// The messages that have arrived on `port` and wait to be handled.
+    task_queues: TaskQueues,
     /// A channel to hand out when some other task needs to be able to respond to a message from
     /// the script task.
     chan: ScriptChan,
@@ -362,9 +675,110 @@ pub struct ScriptTask {
     constellation_chan: ConstellationChan,
     /// A handle to the compositor for communicating ready state messages.
     compositor: @ScriptListener,
+    /// The channel to report how long tasks take on, as the script stage of frames.
+    profiler_chan: ProfilerChan,
+    /// The file to save the markup of the outermost page to once it has loaded, if any.
+    save_page_file: Option<~str>,
+    /// The selectors of the style sheets that apply to every page, at the user agent and user
+    /// origins, which documents restyle for as they do for their own sheets.
+    default_style_selectors: @~[~str],
 
     /// The JavaScript runtime.
     js_runtime: js::rust::rt,
+
+    /// The watchdog that interrupts scripts that run for too long, unless they may run for as
+    /// long as they like.
+    watchdog: Option<Watchdog>,
+}
+
+/// Called by the JavaScript engine on the script task when the watchdog interrupts a script, as
+/// well as when the engine has reasons of its own. Asks the embedder whether to stop a script
+/// that has run for too long, and stops it if so by returning false, which ends the script with
+/// an exception that it can't catch.
+extern fn operation_callback(cx: *JSContext) -> JSBool {
+    let page = page_from_context(cx);
+    unsafe {
+        let watchdog = match (*page).watchdog {
+            Some(ref watchdog) => watchdog.clone(),
+            None => return 1,
+        };
+        if !watchdog.take_interrupt() {
+            return 1
+        }
+        let window = match (*page).frame {
+            Some(ref frame) => frame.window,
+            None => return 1,
+        };
+        if window.compositor.stop_slow_script((*page).id.clone()) {
+            0
+        } else {
+            watchdog.extend_deadline();
+            1
+        }
+    }
+}
+
+/// Writes the markup of a document to a file.
+fn save_page(document: AbstractDocument, path: &str) {
+    let markup = do document.with_base |document| {
+        serialize_document(document.root, document.quirks_mode)
+    };
+    match io::file_writer(&Path(path), [io::Create, io::Truncate]) {
+        Ok(writer) => writer.write_str(markup),
+        Err(error) => println(fmt!("error: can't save the page to `%s`: %s", path, error)),
+    }
+}
+
+/// Runs a script that the parser found, with the point after its element as the insertion point
+/// of `document.write`. What the script writes is parsed in the parent of the element and inserted
+/// after it, and the inline scripts in that run in turn.
+///
+/// FIXME: Scripts run once the whole document has been parsed, so what they write is parsed on
+/// its own rather than with the markup that follows them, and the external scripts in it aren't
+/// loaded.
+fn run_parser_script(js_info: &JSPageInfo,
+                     document: AbstractDocument,
+                     script: AbstractNode<ScriptView>,
+                     data: ~[u8],
+                     script_url: &Url,
+                     base_url: &Url,
+                     image_cache_task: &ImageCacheTask) {
+    let previous = Cell::new(document.with_base(|document| document.enter_parser_script(script)));
+    let _ = js_info.js_context.evaluate_script(js_info.js_compartment.global_obj,
+                                               data,
+                                               script_url.to_str(),
+                                               1);
+    let markup = document.with_base(|document| document.leave_parser_script(previous.take()));
+    let parent = match script.parent_node() {
+        Some(parent) if !markup.is_empty() => parent,
+        _ => return,
+    };
+
+    let fragment = parse_html_fragment(js_info.js_compartment.cx.ptr,
+                                       parent,
+                                       markup,
+                                       base_url.clone(),
+                                       image_cache_task.clone());
+    let written_scripts: ~[AbstractNode<ScriptView>] = fragment.traverse_preorder().filter(|node| {
+        node.type_id() == ElementNodeTypeId(HTMLScriptElementTypeId) &&
+            node.with_imm_element(|element| element.get_attr("src").is_none())
+    }).collect();
+    parent.insert_child(fragment, script.next_sibling());
+    for &written in written_scripts.iter() {
+        let mut data = ~"";
+        for child in written.children() {
+            if child.is_text() {
+                data.push_str(child.with_imm_text(|text| text.parent.data.clone()));
+            }
+        }
+        run_parser_script(js_info,
+                          document,
+                          written,
+                          data.into_bytes(),
+                          script_url,
+                          base_url,
+                          image_cache_task);
+    }
 }
 
 /// Returns the relevant page from the associated JS Context.
@@ -385,25 +799,45 @@ impl ScriptTask {
                constellation_chan: ConstellationChan,
                resource_task: ResourceTask,
                img_cache_task: ImageCacheTask,
-               initial_size: Future<Size2D<uint>>)
+               initial_size: Future<Size2D<uint>>,
+               autoplay_policy: AutoplayPolicy,
+               slow_script_timeout: uint,
+               save_page_file: Option<~str>,
+               default_stylesheets: ~[~str],
+               profiler_chan: ProfilerChan)
                -> @mut ScriptTask {
         let js_runtime = js::rust::rt();
+        let watchdog = if slow_script_timeout > 0 {
+            Some(Watchdog::new(js_runtime.ptr, slow_script_timeout))
+        } else {
+            None
+        };
+
+        let mut default_style_selectors = ~[];
+        for sheet in default_stylesheets.iter() {
+            default_style_selectors.push_all_move(sheet_selectors(*sheet));
+        }
 
         let script_task = @mut ScriptTask {
-            page_tree: PageTree::new(id, layout_chan, initial_size),
+            page_tree: PageTree::new(id, layout_chan, initial_size, autoplay_policy),
 
             image_cache_task: img_cache_task,
             resource_task: resource_task,
 
             port: port,
+            task_queues: TaskQueues::new(),
             chan: chan,
             constellation_chan: constellation_chan,
             compositor: compositor,
+            profiler_chan: profiler_chan,
+            save_page_file: save_page_file,
+            default_style_selectors: @default_style_selectors,
 
             js_runtime: js_runtime,
+            watchdog: watchdog.clone(),
         };
 
-        script_task.page_tree.page.initialize_js_info(script_task.js_runtime.cx());
+        script_task.page_tree.page.initialize_js_info(script_task.js_runtime.cx(), watchdog);
         script_task
     }
 
@@ -423,10 +857,17 @@ impl ScriptTask {
                                             constellation_chan: ConstellationChan,
                                             resource_task: ResourceTask,
                                             image_cache_task: ImageCacheTask,
-                                            initial_size: Future<Size2D<uint>>) {
+                                            initial_size: Future<Size2D<uint>>,
+                                            autoplay_policy: AutoplayPolicy,
+                                            slow_script_timeout: uint,
+                                            save_page_file: Option<~str>,
+                                            default_stylesheets: ~[~str],
+                                            profiler_chan: ProfilerChan) {
         let compositor = Cell::new(compositor);
         let port = Cell::new(port);
         let initial_size = Cell::new(initial_size);
+        let save_page_file = Cell::new(save_page_file);
+        let default_stylesheets = Cell::new(default_stylesheets);
         // FIXME: rust#6399
         let mut the_task = task();
         the_task.sched_mode(SingleThreaded);
@@ -439,28 +880,60 @@ impl ScriptTask {
                                               constellation_chan.clone(),
                                               resource_task.clone(),
                                               image_cache_task.clone(),
-                                              initial_size.take());
+                                              initial_size.take(),
+                                              autoplay_policy,
+                                              slow_script_timeout,
+                                              save_page_file.take(),
+                                              default_stylesheets.take(),
+                                              profiler_chan.clone());
             script_task.start();
         }
     }
 
-    /// Handles an incoming control message.
+    /// Handles the next task, waiting for a message if none is queued. Every message that has
+    /// arrived is queued first, so that the task that is run is the one with the highest priority.
     fn handle_msg(&mut self) -> bool {
-        match self.port.recv() {
+        if self.task_queues.is_empty() {
+            let msg = self.port.recv();
+            self.task_queues.push(msg);
+        }
+        while self.port.peek() {
+            let msg = self.port.recv();
+            self.task_queues.push(msg);
+        }
+
+        // The watchdog times each task, so that a script that keeps it from finishing can be
+        // stopped.
+        let msg = self.task_queues.pop().unwrap();
+        for watchdog in self.watchdog.iter() {
+            watchdog.task_started()
+        }
+        let start_time = precise_time_ns();
+        match msg {
             // TODO(tkuehn) need to handle auxiliary layouts for iframes
             AttachLayoutMsg(new_layout_info) => self.handle_new_layout(new_layout_info),
             LoadMsg(id, url) => self.load(id, url),
             ExecuteMsg(id, url) => self.handle_execute_msg(id, url),
             SendEventMsg(id, event) => self.handle_event(id, event),
-            FireTimerMsg(id, timer_data) => self.handle_fire_timer_msg(id, timer_data),
+            FireTimerMsg(id) => self.handle_fire_timer_msg(id),
+            FreezeMsg(id) => self.handle_freeze_msg(id),
+            ThawMsg(id) => self.handle_thaw_msg(id),
+            ImageBitmapMsg(id, data) => self.handle_image_bitmap_msg(id, data),
             NavigateMsg(direction) => self.handle_navigate_msg(direction),
             ReflowCompleteMsg(id) => self.handle_reflow_complete_msg(id),
             ResizeInactiveMsg(id, new_size) => self.handle_resize_inactive_msg(id, new_size),
+            AddDefaultStylesheetMsg(id, url, text) => {
+                self.handle_add_default_stylesheet_msg(id, url, text)
+            }
             ExitMsg => {
                 self.handle_exit_msg();
                 return false
             }
         }
+        for watchdog in self.watchdog.iter() {
+            watchdog.task_finished()
+        }
+        self.profiler_chan.send(FrameStageMsg(ScriptStage, start_time, precise_time_ns()));
         true
     }
 
@@ -476,8 +949,11 @@ impl ScriptTask {
         let parent_page_tree = self.page_tree.find(old_id).expect("ScriptTask: received a layout
             whose parent has a PipelineId which does not correspond to a pipeline in the script
             task's page tree. This is a bug.");
-        let new_page_tree = PageTree::new(new_id, layout_chan, size_future);
-        new_page_tree.page.initialize_js_info(self.js_runtime.cx());
+        let new_page_tree = PageTree::new(new_id,
+                                          layout_chan,
+                                          size_future,
+                                          parent_page_tree.page.autoplay_policy);
+        new_page_tree.page.initialize_js_info(self.js_runtime.cx(), self.watchdog.clone());
 
         parent_page_tree.inner.push(new_page_tree);
     }
@@ -503,30 +979,89 @@ impl ScriptTask {
         }
     }
 
-    /// Handles a timer that fired.
+    /// Runs the timers of a page that are due, in the order in which they are due, unless the
+    /// page is frozen.
     #[fixed_stack_segment]
-    fn handle_fire_timer_msg(&mut self, id: PipelineId, timer_data: ~TimerData) {
+    fn handle_fire_timer_msg(&mut self, id: PipelineId) {
         let page = self.page_tree.find(id).expect("ScriptTask: received fire timer msg for a
             pipeline ID not associated with this script task. This is a bug.").page;
+        let window = match page.frame {
+            Some(ref frame) => frame.window,
+            None => return,
+        };
+        let mut ran = false;
+        loop {
+            let timer = match window.timers.take_due(timers::now()) {
+                Some(timer) => timer,
+                None => break,
+            };
+            debug!("ScriptTask: running timer %d", timer.handle as int);
+            unsafe {
+                let js_info = page.js_info.get_ref();
+                // TODO: Support extra arguments. This requires passing a `*JSVal` array as `argv`.
+                let rval = JSVAL_NULL;
+                JS_CallFunctionValue(js_info.js_context.ptr,
+                                     js_info.js_compartment.global_obj.ptr,
+                                     timer.callback,
+                                     0,
+                                     null(),
+                                     &rval);
+            }
+            for &delay in window.timers.finish_running(timers::now()).iter() {
+                window.wake_timers_after(delay)
+            }
+            ran = true;
+        }
+        if ran {
+            // We don't know what the script changed, so for now we will do a total redisplay.
+            page.reflow_all(ReflowForDisplay, self.chan.clone(), self.compositor);
+        }
+    }
+
+    /// Freezes the timers of a page whose document has gone into the session history.
+    fn handle_freeze_msg(&mut self, id: PipelineId) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received freeze msg for a
+            pipeline ID not associated with this script task. This is a bug.").page;
+        for frame in page.frame.iter() {
+            frame.window.freeze()
+        }
+    }
+
+    /// Thaws the timers of a page whose document is shown again.
+    fn handle_thaw_msg(&mut self, id: PipelineId) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received thaw msg for a
+            pipeline ID not associated with this script task. This is a bug.").page;
+        for frame in page.frame.iter() {
+            frame.window.thaw()
+        }
+    }
+
+    /// Handles an image that has been decoded for `createImageBitmap()`, by calling its callback
+    /// with an image bitmap of it, or with null if it couldn't be decoded.
+    #[fixed_stack_segment]
+    fn handle_image_bitmap_msg(&mut self, id: PipelineId, data: ~ImageBitmapData) {
+        let page = self.page_tree.find(id).expect("ScriptTask: received image bitmap msg for a
+            pipeline ID not associated with this script task. This is a bug.").page;
+        let ~ImageBitmapData { callback, image } = data;
         unsafe {
             let js_info = page.js_info.get_ref();
-            let this_value = if timer_data.args.len() > 0 {
-                RUST_JSVAL_TO_OBJECT(timer_data.args[0])
-            } else {
-                js_info.js_compartment.global_obj.ptr
+            let cx = js_info.js_context.ptr;
+            let argument = match image {
+                Some(image) => {
+                    let scope = page.frame.get_ref().window.get_wrappercache().get_wrapper();
+                    let bitmap = ImageBitmap::new(image, cx, scope);
+                    RUST_OBJECT_TO_JSVAL(bitmap.get_wrappercache().get_wrapper())
+                }
+                None => JSVAL_NULL,
             };
-
-            // TODO: Support extra arguments. This requires passing a `*JSVal` array as `argv`.
             let rval = JSVAL_NULL;
-            JS_CallFunctionValue(js_info.js_context.ptr,
-                                 this_value,
-                                 timer_data.funval,
-                                 0,
-                                 null(),
+            JS_CallFunctionValue(cx,
+                                 js_info.js_compartment.global_obj.ptr,
+                                 callback,
+                                 1,
+                                 &argument,
                                  &rval);
-
         }
-        // We don't know what the script changed, so for now we will do a total redisplay.
         page.reflow_all(ReflowForDisplay, self.chan.clone(), self.compositor);
     }
 
@@ -557,6 +1092,35 @@ impl ScriptTask {
         }
     }
 
+    /// Adds a style sheet at the user agent origin to every page of the script task, whose
+    /// layouts parse it, and restyles their documents. Each page is sent the sheet, but only the
+    /// message for the outermost page is acted on, so that the selectors that later documents
+    /// restyle for are added once, whichever order the messages come in.
+    fn handle_add_default_stylesheet_msg(&mut self, id: PipelineId, url: Url, text: ~str) {
+        if self.page_tree.page.id != id {
+            return
+        }
+        let mut selectors = (*self.default_style_selectors).clone();
+        selectors.push_all_move(sheet_selectors(text));
+        let selectors = @selectors;
+        self.default_style_selectors = selectors;
+
+        let script_chan = self.chan.clone();
+        let compositor = self.compositor;
+        for page in self.page_tree.iter() {
+            page.layout_chan.send(AddUserAgentStylesheetMsg(url.clone(), text.clone()));
+            for frame in page.frame.iter() {
+                do frame.document.with_mut_base |document| {
+                    document.set_default_style_selectors(selectors);
+                }
+            }
+            if page.frame.is_some() {
+                page.damage(MatchSelectorsDocumentDamage);
+                page.reflow(ReflowForDisplay, script_chan.clone(), compositor)
+            }
+        }
+    }
+
     /// Handles a request to exit the script task and shut down layout.
     fn handle_exit_msg(&mut self) {
         for page in self.page_tree.iter() {
@@ -566,6 +1130,9 @@ impl ScriptTask {
             }
             page.layout_chan.send(layout_interface::ExitMsg);
         }
+        for watchdog in self.watchdog.iter() {
+            watchdog.exit()
+        }
         self.compositor.close();
     }
 
@@ -611,7 +1178,8 @@ impl ScriptTask {
                                                                  page.next_subpage_id.clone(),
                                                                  self.constellation_chan.clone());
 
-        let HtmlParserResult {root, discovery_port} = html_parsing_result;
+        let HtmlParserResult {root, discovery_port, content_language, quirks_mode, scripts} =
+            html_parsing_result;
 
         // Create the window and document objects.
         let window = {
@@ -622,14 +1190,27 @@ impl ScriptTask {
             // pointer.  We think it's safe here because the main task will hold onto the box,
             // and because the current refcounting implementation of @ doesn't move.
             let page = &mut *page;
-            Window::new(page, self.chan.clone(), self.compositor)
+            Window::new(page,
+                        self.chan.clone(),
+                        self.compositor,
+                        self.resource_task.clone(),
+                        self.image_cache_task.clone())
         };
         let document = HTMLDocument::new(root, Some(window));
+        do document.with_mut_base |document| {
+            document.content_language = content_language.clone();
+            document.quirks_mode = quirks_mode;
+            document.default_style_selectors = self.default_style_selectors;
+        }
 
         // Tie the root into the document.
         do root.with_mut_base |base| {
             base.add_to_doc(document)
         }
+        do document.with_mut_base |document| {
+            document.focus_autofocus_element();
+            document.autoplay_media_elements();
+        }
 
         // Create the root frame.
         page.frame = Some(Frame {
@@ -638,66 +1219,114 @@ impl ScriptTask {
         });
         page.url = Some((url.clone(), true));
 
-        // Send style sheets over to layout.
+        // Send style sheets over to layout as they load, and paint the document once they all
+        // have, without waiting for its scripts. If they take longer than the paint suppression
+        // timeout, the document is painted with those that have loaded, and again as each of the
+        // others arrives.
         //
-        // FIXME: These should be streamed to layout as they're parsed. We don't need to stop here
-        // in the script task.
-
+        // FIXME: Style sheets whose media don't apply to the screen shouldn't hold the paint back.
+        let load_port = forward_load_progress(discovery_port);
         let mut js_scripts = None;
+        let mut painted = false;
         loop {
-            match discovery_port.try_recv() {
-                Some(HtmlDiscoveredScript(scripts)) => {
+            let paint = match load_port.recv() {
+                DiscoveryProgress(HtmlDiscoveredScript(scripts)) => {
                     assert!(js_scripts.is_none());
                     js_scripts = Some(scripts);
+                    false
                 }
-                Some(HtmlDiscoveredStyle(sheet)) => {
+                DiscoveryProgress(HtmlDiscoveredStyle(sheet, source)) => {
                     page.layout_chan.send(AddStylesheetMsg(sheet));
+                    do page.frame.get_ref().document.with_mut_base |document| {
+                        document.add_style_sheet(source)
+                    }
+                    painted
                 }
-                Some(HtmlDiscoveredIFrame((iframe_url, subpage_id, size_future))) => {
+                DiscoveryProgress(HtmlDiscoveredAllStyles) => !painted,
+                DiscoveryProgress(HtmlDiscoveredIFrame((iframe_url, subpage_id, size_future))) => {
                     page.next_subpage_id = SubpageId(*subpage_id + 1);
                     self.constellation_chan.send(LoadIframeUrlMsg(iframe_url,
                                                                   pipeline_id,
                                                                   subpage_id,
                                                                   size_future));
+                    false
+                }
+                PaintSuppressionTimeout => {
+                    debug!("ScriptTask: painting before the style sheets of %s have loaded",
+                           url.to_str());
+                    true
+                }
+                DiscoveryFinished => break,
+            };
+
+            if paint {
+                page.damage(MatchSelectorsDocumentDamage);
+                page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor);
+            }
+            if paint && !painted {
+                painted = true;
+
+                // Scroll to the element that the fragment of the URL indicates, if any.
+                for fragment in url.fragment.iter() {
+                    match document.with_base(|document| document.fragment_target(*fragment)) {
+                        Some(target) => page.scroll_into_view(target, true, self.compositor),
+                        None => debug!("ScriptTask: no element for fragment %s", *fragment),
+                    }
                 }
-                None => break
             }
         }
 
         // Receive the JavaScript scripts.
-        assert!(js_scripts.is_some());
+        assert!(painted && js_scripts.is_some());
         let js_scripts = js_scripts.take_unwrap();
         debug!("js_scripts: %?", js_scripts);
 
-        // Perform the initial reflow.
-        page.damage = Some(DocumentDamage {
-            root: root,
-            level: MatchSelectorsDocumentDamage,
-        });
-        page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor);
-        page.url = Some((url, false));
+        page.url = Some((url.clone(), false));
 
         // Define debug functions.
         let js_info = page.js_info.get_ref();
         js_info.js_compartment.define_functions(debug_fns);
 
-        // Evaluate every script in the document.
+        // Evaluate every script in the document. The time spent loading the document isn't
+        // counted against them.
+        for watchdog in self.watchdog.iter() {
+            watchdog.task_started()
+        }
         for file in js_scripts.iter() {
-            let _ = js_info.js_context.evaluate_script(js_info.js_compartment.global_obj,
-                                                       file.data.clone(),
-                                                       file.url.to_str(),
-                                                       1);
+            run_parser_script(js_info,
+                              document,
+                              scripts[file.script],
+                              file.data.clone(),
+                              &file.url,
+                              &url,
+                              &self.image_cache_task);
+        }
+
+        // Save the outermost page as its scripts have left it, if asked to.
+        if self.page_tree.page.id == pipeline_id {
+            for path in self.save_page_file.iter() {
+                save_page(document, *path)
+            }
         }
     }
 
     /// This is the main entry point for receiving and dispatching DOM events.
-    ///
-    /// TODO: Actually perform DOM event dispatch.
     fn handle_event(&mut self, pipeline_id: PipelineId, event: Event_) {
         let page = self.page_tree.find(pipeline_id).expect("ScriptTask: received an event
             message for a layout channel that is not associated with this script task. This
             is a bug.").page;
 
+        // Input that comes before the page has a document has nothing to go to.
+        if event.is_user_interaction() && page.frame.is_none() {
+            return
+        }
+
+        if event.is_user_activation() {
+            for frame in page.frame.iter() {
+                frame.document.with_mut_base(|document| document.user_activated = true)
+            }
+        }
+
         match event {
             ResizeEvent(new_width, new_height) => {
                 debug!("script got resize event: %u, %u", new_width, new_height);
@@ -720,43 +1349,261 @@ impl ScriptTask {
                 }
             }
 
-            ClickEvent(_button, point) => {
+            // Images don't change the styles of the document, only its layout.
+            ImageLoadedEvent => {
+                if page.frame.is_some() {
+                    page.damage(ReflowDocumentDamage);
+                    page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor)
+                }
+            }
+
+            AnimationTickEvent => {
+                if page.frame.is_some() {
+                    page.damage(RepaintDocumentDamage);
+                    page.reflow(ReflowForDisplay, self.chan.clone(), self.compositor)
+                }
+            }
+
+            ClickEvent(button, point) => {
                 debug!("ClickEvent: clicked at %?", point);
 
-                let root = do page.frame.expect("root frame is None").document.with_base |doc| {
-                    doc.root
-                };
-                let (port, chan) = comm::stream();
-                match page.query_layout(HitTestQuery(root, point, chan), port) {
-                    Ok(node) => match node {
-                        HitTestResponse(node) => {
-                            debug!("clicked on %s", node.debug_str());
-                            let mut node = node;
-                            // traverse node generations until a node that is an element is found
-                            while !node.is_element() {
-                                match node.parent_node() {
-                                    Some(parent) => {
-                                        node = parent;
-                                    }
-                                    None => break
+                let node = self.element_at_point(page, point).map(|&node| {
+                    unsafe { node.to_script_node() }
+                });
+
+                // Clicking focuses the nearest focusable element, or clears the focus.
+                let mut focus = node;
+                while focus.is_some() && !focus.unwrap().is_focusable() {
+                    focus = focus.unwrap().parent_node();
+                }
+                do page.frame.get_ref().document.with_mut_base |document| {
+                    document.set_focus(focus)
+                }
+
+                // The default action of a click, such as following a link, happens unless a
+                // listener cancels it.
+                for &node in node.iter() {
+                    let window = page.frame.get_ref().window;
+                    let client_point = page.client_point(point);
+                    if fire_mouse_event(window, node, "click", button as u16, client_point, None) {
+                        self.activate_element(page, node)
+                    }
+                }
+            }
+            MouseDownEvent(button, point) => {
+                debug!("MouseDownEvent: pressed at %?", point);
+
+                let node = self.element_at_point(page, point);
+                do page.frame.get_ref().document.with_mut_base |document| {
+                    document.set_active(node.map(|&node| unsafe { node.to_script_node() }))
+                }
+
+                // Pressing the mouse on a slider moves its thumb there, and pressing it on the spin
+                // buttons of a number field steps its value, unless a listener for `mousedown`
+                // cancels it.
+                //
+                // TODO: Drag the thumb while the mouse moves, and move the value with the arrow
+                // keys, once key events are sent to script.
+                for &node in node.iter() {
+                    let window = page.frame.get_ref().window;
+                    let client_point = page.client_point(point);
+                    let script_node = unsafe { node.to_script_node() };
+                    if !fire_mouse_event(window, script_node, "mousedown", button as u16,
+                                         client_point, None) {
+                        loop
+                    }
+                    if !node.is_input_element() {
+                        loop
+                    }
+                    let input_type = node.with_imm_input_element(|input| input.input_type());
+                    if input_type != RangeInput && input_type != NumberInput {
+                        loop
+                    }
+                    let node = unsafe { node.to_script_node() };
+                    let (port, chan) = comm::stream();
+                    match page.query_layout(ContentBoxQuery(node, chan), port) {
+                        ContentBoxResponse(bounds) => {
+                            let point = Point2D(Au::from_css_px(CSSPixel(point.x as float)),
+                                                Au::from_css_px(CSSPixel(point.y as float)));
+                            let direction = node.directionality();
+                            let changed = do node.with_mut_input_element |input| {
+                                if input_type == RangeInput {
+                                    input.move_range_thumb_to(&bounds, point.x, direction)
+                                } else {
+                                    input.press_spin_button_at(&bounds, point, direction)
                                 }
+                            };
+                            if changed {
+                                fire_value_changed(window, node)
                             }
-                            if node.is_element() {
-                                do node.with_imm_element |element| {
-                                    if "a" == element.tag_name {
-                                        self.load_url_from_element(page, element)
-                                    }
-                                }
+                        }
+                    }
+                }
+            }
+            MouseUpEvent(button, point) => {
+                do page.frame.get_ref().document.with_mut_base |document| {
+                    document.set_active(None)
+                }
+
+                let node = self.element_at_point(page, point).map(|&node| {
+                    unsafe { node.to_script_node() }
+                });
+                for &node in node.iter() {
+                    let window = page.frame.get_ref().window;
+                    let client_point = page.client_point(point);
+                    fire_mouse_event(window, node, "mouseup", button as u16, client_point, None);
+                }
+            }
+            MouseMoveEvent(point) => {
+                let node = self.element_at_point(page, point).map(|&node| {
+                    unsafe { node.to_script_node() }
+                });
+                let document = page.frame.get_ref().document;
+                let old = document.with_base(|document| document.hovered);
+                do document.with_mut_base |document| {
+                    document.set_hover(node)
+                }
+
+                // Moving onto another element is told to the element that the pointer left, and
+                // then to the one that it entered, each with the other as the related target.
+                let window = page.frame.get_ref().window;
+                let client_point = page.client_point(point);
+                if old != node {
+                    for &old in old.iter() {
+                        fire_mouse_event(window, old, "mouseout", 0, client_point, node);
+                    }
+                    for &node in node.iter() {
+                        fire_mouse_event(window, node, "mouseover", 0, client_point, old);
+                    }
+                }
+                for &node in node.iter() {
+                    fire_mouse_event(window, node, "mousemove", 0, client_point, None);
+                }
+            }
+
+            FocusNavigationEvent(navigation) => {
+                // FIXME: Focus should be able to move into and out of iframes.
+                let document = page.frame.get_ref().document;
+                match navigation {
+                    FocusNext => document.with_mut_base(|document| document.focus_next(true)),
+                    FocusPrevious => {
+                        document.with_mut_base(|document| document.focus_next(false))
+                    }
+                    ActivateFocused => {
+                        let focused = document.with_base(|document| document.focused);
+                        for &node in focused.iter() {
+                            let window = page.frame.get_ref().window;
+                            if fire_event(window, node, "click", true, true) {
+                                self.activate_element(page, node)
                             }
                         }
-                    },
-                    Err(()) => {
-                        debug!(fmt!("layout query error"));
                     }
                 }
             }
-            MouseDownEvent(*) => {}
-            MouseUpEvent(*) => {}
+
+            AccessKeyEvent(key) => {
+                let document = page.frame.get_ref().document;
+                let element = document.with_base(|document| document.element_for_access_key(key));
+                for &node in element.iter() {
+                    if node.is_focusable() {
+                        document.with_mut_base(|document| document.set_focus(Some(node)))
+                    }
+                    self.activate_element(page, node)
+                }
+            }
+
+            EditingCommandEvent(command) => {
+                let document = page.frame.get_ref().document;
+                do document.with_mut_base |document| {
+                    match command {
+                        UndoCommand => editing::undo(document),
+                        RedoCommand => editing::redo(document),
+                    };
+                }
+            }
+
+            ScrollEvent(origin) => {
+                page.viewport_origin = origin;
+                let origin = Point2D(Au::from_css_px(CSSPixel(origin.x as float)),
+                                     Au::from_css_px(CSSPixel(origin.y as float)));
+                page.layout_chan.send(ScrollMsg(origin))
+            }
+
+            ZoomEvent(scale) => {
+                page.zoom = scale;
+                page.layout_chan.send(ZoomMsg(scale))
+            }
+
+            PickerResultEvent(value) => {
+                let input = replace(&mut page.picker_input, None);
+                match (input, value) {
+                    (Some(input), Some(value)) => {
+                        let changed = do input.with_mut_input_element |input| {
+                            input.set_picked_value(value.clone())
+                        };
+                        if changed {
+                            fire_value_changed(page.frame.get_ref().window, input)
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Runs the activation behavior of `node`, which clicking it, pressing Enter while it has the
+    /// focus, or pressing its access key triggers: links are followed, and date and color inputs
+    /// ask the embedder to show a picker.
+    fn activate_element(&mut self, page: @mut Page, node: AbstractNode<ScriptView>) {
+        do node.with_imm_element |element| {
+            if "a" == element.tag_name {
+                self.load_url_from_element(page, element)
+            }
+        }
+
+        if !node.is_input_element() {
+            return
+        }
+        let picker = do node.with_imm_input_element |input| {
+            match input.input_type() {
+                DateInput => Some((DatePicker, input.value())),
+                ColorInput => Some((ColorPicker, input.value())),
+                _ => None,
+            }
+        };
+        for (kind, value) in picker.move_iter() {
+            page.picker_input = Some(node);
+            self.compositor.open_picker(page.id.clone(), kind, value);
+        }
+    }
+
+    /// Returns the element under `point`, which is the nearest element ancestor of the node that
+    /// layout finds there.
+    fn element_at_point(&self, page: @mut Page, point: Point2D<f32>)
+                        -> Option<AbstractNode<LayoutView>> {
+        let root = do page.frame.expect("root frame is None").document.with_base |doc| {
+            doc.root
+        };
+        let (port, chan) = comm::stream();
+        match page.query_layout(HitTestQuery(root, point, chan), port) {
+            Ok(HitTestResponse(node)) => {
+                debug!("hit %s", node.debug_str());
+                let mut node = node;
+                // traverse node generations until a node that is an element is found
+                while !node.is_element() {
+                    match node.parent_node() {
+                        Some(parent) => {
+                            node = parent;
+                        }
+                        None => return None
+                    }
+                }
+                Some(node)
+            }
+            Err(()) => {
+                debug!(fmt!("layout query error"));
+                None
+            }
         }
     }
 
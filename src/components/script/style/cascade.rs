@@ -0,0 +1,163 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The order in which the declarations that apply to an element cascade: by origin and
+//! importance, then by specificity, then by the order in which they appear.
+//!
+//! Layout styles elements with libcss as well, which orders its own cascade; this one gives the
+//! values of the properties that libcss doesn't support.
+
+use dom::node::AbstractNode;
+use extra::arc::Arc;
+use extra::sort::merge_sort;
+use style::media_queries::Device;
+use style::properties::{PropertyDeclaration, PropertyDeclarationBlock};
+use style::rule_tree::{RuleNodeId, RuleTree};
+use servo_util::bloom::BloomFilter;
+use style::selector_matching::matches_selector_with_ancestors;
+use style::selectors::{PseudoElement, STYLE_ATTRIBUTE_SPECIFICITY};
+use style::stylesheets::Stylesheet;
+
+
+/// Where a style sheet comes from.
+#[deriving(Eq, Clone)]
+pub enum StylesheetOrigin {
+    /// The default style of the browser.
+    UserAgentOrigin,
+    /// Style that the user has set up.
+    UserOrigin,
+    /// Style that the document has, including its `style` attributes.
+    AuthorOrigin,
+}
+
+
+/// The declarations of a block that applies to an element, with what decides whether they win.
+#[deriving(Clone)]
+struct ApplicableDeclarations<'self> {
+    level: uint,
+    specificity: u32,
+    source_order: uint,
+    declarations: &'self Arc<~[PropertyDeclaration]>,
+}
+
+impl<'self> ApplicableDeclarations<'self> {
+    fn key(&self) -> (uint, u32, uint) {
+        (self.level, self.specificity, self.source_order)
+    }
+}
+
+
+/// Returns the level of the cascade that declarations of the given origin and importance are at:
+/// important declarations win over normal ones, and the order of origins is reversed for them,
+/// so that users can override authors and the user agent can override everyone.
+pub fn cascade_level(origin: StylesheetOrigin, important: bool) -> uint {
+    match (important, origin) {
+        (false, UserAgentOrigin) => 0,
+        (false, UserOrigin) => 1,
+        (false, AuthorOrigin) => 2,
+        (true, AuthorOrigin) => 3,
+        (true, UserOrigin) => 4,
+        (true, UserAgentOrigin) => 5,
+    }
+}
+
+
+/// Returns the node of `rule_tree` that stands for the declarations of `sheets` and of the
+/// `style` attribute of `element` that apply to it on `device`, adding it if it isn't there yet.
+/// The elements that the same rules match share it. Its declarations are in the order in which
+/// they cascade, from the one that loses to the one that wins: applying them in order leaves each
+/// property with its cascaded value. `sheets` are in the order in which they were added to the
+/// document, which breaks ties between rules of the same specificity. `ancestors` holds the
+/// lowercase names, IDs and classes of the ancestors of `element`, so that selectors that need an
+/// ancestor that isn't there are rejected without walking up the tree.
+///
+/// A rule whose selectors match the element more than once counts with the specificity of the
+/// most specific of them. Selectors with a pseudo-element apply to that pseudo-element, not to
+/// the element: they are the only ones that count when `pseudo_element` is given, and are skipped
+/// otherwise. Pseudo-elements have no `style` attribute of their own.
+pub fn cascade_into_rule_tree<View>(rule_tree: &mut RuleTree,
+                                    sheets: &[(Stylesheet, StylesheetOrigin)],
+                                    device: &Device,
+                                    element: AbstractNode<View>,
+                                    ancestors: &BloomFilter,
+                                    pseudo_element: Option<PseudoElement>,
+                                    style_attribute: Option<&PropertyDeclarationBlock>)
+                                    -> RuleNodeId {
+    let applicable = applicable_declarations(sheets, device, element, ancestors, pseudo_element,
+                                             style_attribute);
+    let blocks: ~[(uint, &Arc<~[PropertyDeclaration]>)] = do applicable.iter().map |applicable| {
+        (applicable.level, applicable.declarations)
+    }.collect();
+    rule_tree.insert(blocks)
+}
+
+
+/// Returns the blocks of declarations that apply to an element, sorted in the order in which they
+/// cascade.
+fn applicable_declarations<'a, View>(sheets: &'a [(Stylesheet, StylesheetOrigin)],
+                                     device: &Device,
+                                     element: AbstractNode<View>,
+                                     ancestors: &BloomFilter,
+                                     pseudo_element: Option<PseudoElement>,
+                                     style_attribute: Option<&'a PropertyDeclarationBlock>)
+                                     -> ~[ApplicableDeclarations<'a>] {
+    let mut applicable = ~[];
+    let mut source_order = 0;
+    let push = |applicable: &mut ~[ApplicableDeclarations<'a>],
+                block: &'a PropertyDeclarationBlock,
+                origin: StylesheetOrigin,
+                specificity: u32,
+                source_order: uint| {
+        let blocks = [(false, &block.normal), (true, &block.important)];
+        for &(important, declarations) in blocks.iter() {
+            if !declarations.get().is_empty() {
+                applicable.push(ApplicableDeclarations {
+                    level: cascade_level(origin, important),
+                    specificity: specificity,
+                    source_order: source_order,
+                    declarations: declarations,
+                })
+            }
+        }
+    };
+
+    for &(ref sheet, origin) in sheets.iter() {
+        for rule in sheet.iter_style_rules(device) {
+            let specificity = rule.selectors.iter().filter(|selector| {
+                selector.pseudo_element == pseudo_element &&
+                    matches_selector_with_ancestors(*selector, element, ancestors)
+            }).map(|selector| selector.specificity).max();
+            match specificity {
+                Some(specificity) => {
+                    push(&mut applicable, &rule.declarations, origin, specificity, source_order)
+                }
+                None => {}
+            }
+            source_order += 1;
+        }
+    }
+    match style_attribute {
+        Some(block) if pseudo_element.is_none() => {
+            push(&mut applicable, block, AuthorOrigin, STYLE_ATTRIBUTE_SPECIFICITY, source_order)
+        }
+        _ => {}
+    }
+
+    merge_sort(applicable, |a, b| a.key() <= b.key())
+}
+
+
+#[test]
+fn test_cascade_levels() {
+    // From the level that loses to the one that wins.
+    let levels = [cascade_level(UserAgentOrigin, false),
+                  cascade_level(UserOrigin, false),
+                  cascade_level(AuthorOrigin, false),
+                  cascade_level(AuthorOrigin, true),
+                  cascade_level(UserOrigin, true),
+                  cascade_level(UserAgentOrigin, true)];
+    for i in range(1, levels.len()) {
+        assert!(levels[i - 1] < levels[i]);
+    }
+}
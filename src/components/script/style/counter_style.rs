@@ -0,0 +1,840 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Counter styles, which turn counter values into the text of list markers and of `counter()`
+//! in generated content: the `@counter-style` rule and the predefined styles of CSS Counter
+//! Styles Level 3.
+
+use std::ascii::StrAsciiExt;
+use std::hashmap::HashMap;
+use std::int;
+use cssparser::*;
+use style::errors::{ErrorLoggerIterator, log_css_error};
+use style::parsing_utils::one_component_value;
+use style::stylesheets::{CSSRule, CSSCounterStyleRule};
+
+
+/// How many times the `fallback` descriptor is followed before giving up and using `decimal`.
+static MAX_FALLBACK_DEPTH: uint = 8;
+
+
+/// The algorithm a counter style uses to represent a counter value.
+#[deriving(Clone)]
+pub enum System {
+    Cyclic,
+    Numeric,
+    Alphabetic,
+    Symbolic,
+    Additive,
+    /// The argument is the value represented by the first symbol.
+    Fixed(int),
+    /// Use the algorithm, and any descriptor not given, of the named counter style.
+    Extends(~str),
+    /// The informal Chinese numbering of the `*-chinese-informal` styles. This can not be written
+    /// as an `@counter-style` rule.
+    ChineseInformal,
+}
+
+/// The counter values a counter style can represent. Outside of them, the fallback style is used.
+#[deriving(Clone)]
+pub enum CounterRange {
+    /// The values the system can represent on its own.
+    AutoRange,
+    /// A list of inclusive bounds. `int::min_value` and `int::max_value` stand for `infinite`.
+    Ranges(~[(int, int)]),
+}
+
+/// The descriptors of an `@counter-style` rule. A descriptor that is not given takes its value
+/// from the extended style when `system` is `extends`, or its initial value otherwise.
+#[deriving(Clone)]
+pub struct CounterStyleRule {
+    name: ~str,
+    system: Option<System>,
+    negative: Option<(~str, ~str)>,
+    prefix: Option<~str>,
+    suffix: Option<~str>,
+    range: Option<CounterRange>,
+    pad: Option<(uint, ~str)>,
+    fallback: Option<~str>,
+    symbols: Option<~[~str]>,
+    additive_symbols: Option<~[(uint, ~str)]>,
+}
+
+/// A counter style with all of its descriptors resolved. `system` is never `Extends`.
+#[deriving(Clone)]
+pub struct CounterStyle {
+    system: System,
+    negative: (~str, ~str),
+    prefix: ~str,
+    suffix: ~str,
+    range: CounterRange,
+    pad: (uint, ~str),
+    fallback: ~str,
+    symbols: ~[~str],
+    additive_symbols: ~[(uint, ~str)],
+}
+
+
+pub fn parse_counter_style_rule(rule: AtRule, parent_rules: &mut ~[CSSRule]) {
+    let location = rule.location;
+    let name = {
+        let mut iter = rule.prelude.move_skip_whitespace();
+        match (iter.next(), iter.next()) {
+            (Some(Ident(name)), None) if is_valid_counter_style_name(name) => name,
+            _ => {
+                log_css_error(location, "Invalid @counter-style name");
+                return
+            }
+        }
+    };
+    let block = match rule.block {
+        Some(block) => block,
+        None => {
+            log_css_error(location, "Invalid @counter-style rule");
+            return
+        }
+    };
+    let mut counter_style = CounterStyleRule {
+        name: name,
+        system: None,
+        negative: None,
+        prefix: None,
+        suffix: None,
+        range: None,
+        pad: None,
+        fallback: None,
+        symbols: None,
+        additive_symbols: None,
+    };
+    for item in ErrorLoggerIterator(parse_declaration_list(block.move_iter())) {
+        match item {
+            Decl_AtRule(rule) => log_css_error(
+                rule.location, fmt!("Unsupported at-rule in @counter-style: @%s", rule.name)),
+            Declaration(Declaration{ location: l, name: n, value: v, _ }) => {
+                if !counter_style.parse_descriptor(n, v) {
+                    log_css_error(l, "Invalid @counter-style descriptor")
+                }
+            }
+        }
+    }
+    if counter_style.has_enough_symbols() {
+        parent_rules.push(CSSCounterStyleRule(counter_style))
+    } else {
+        log_css_error(location, "Not enough symbols for the @counter-style system")
+    }
+}
+
+
+fn is_valid_counter_style_name(name: &str) -> bool {
+    // CSS-wide keywords, `none`, and the styles that must always be available can't be defined.
+    match name.to_ascii_lower().as_slice() {
+        "none" | "initial" | "inherit" | "unset" | "decimal" | "disc" | "circle" | "square"
+        | "disclosure-open" | "disclosure-closed" => false,
+        _ => true,
+    }
+}
+
+
+impl CounterStyleRule {
+    /// Parses the value of a descriptor into this rule. Returns false if the descriptor is
+    /// unknown or its value is invalid, in which case the rule is left unchanged.
+    fn parse_descriptor(&mut self, name: &str, value: &[ComponentValue]) -> bool {
+        match name.to_ascii_lower().as_slice() {
+            "system" => match parse_system(value) {
+                Some(system) => self.system = Some(system),
+                None => return false,
+            },
+            "negative" => match parse_symbol_list(value) {
+                Some(symbols) => match symbols.len() {
+                    1 => self.negative = Some((symbols[0].clone(), ~"")),
+                    2 => self.negative = Some((symbols[0].clone(), symbols[1].clone())),
+                    _ => return false,
+                },
+                None => return false,
+            },
+            "prefix" => match parse_single_symbol(value) {
+                Some(prefix) => self.prefix = Some(prefix),
+                None => return false,
+            },
+            "suffix" => match parse_single_symbol(value) {
+                Some(suffix) => self.suffix = Some(suffix),
+                None => return false,
+            },
+            "range" => match parse_range(value) {
+                Some(range) => self.range = Some(range),
+                None => return false,
+            },
+            "pad" => match parse_pad(value) {
+                Some(pad) => self.pad = Some(pad),
+                None => return false,
+            },
+            "fallback" => {
+                let mut iter = value.skip_whitespace();
+                match (iter.next(), iter.next()) {
+                    (Some(&Ident(ref name)), None) => self.fallback = Some(name.clone()),
+                    _ => return false,
+                }
+            }
+            "symbols" => match parse_symbol_list(value) {
+                Some(symbols) => self.symbols = Some(symbols),
+                None => return false,
+            },
+            "additive-symbols" => match parse_additive_symbols(value) {
+                Some(symbols) => self.additive_symbols = Some(symbols),
+                None => return false,
+            },
+            // TODO: speak-as
+            _ => return false,
+        }
+        true
+    }
+
+    /// Returns whether the rule has the symbols its system needs. A rule that does not is
+    /// invalid and ignored as a whole.
+    fn has_enough_symbols(&self) -> bool {
+        let symbol_count = self.symbols.map_default(0, |symbols| symbols.len());
+        let has_additive_symbols = self.additive_symbols.map_default(false, |symbols| {
+            !symbols.is_empty()
+        });
+        match self.system {
+            None | Some(Cyclic) | Some(Fixed(*)) | Some(Symbolic) => symbol_count >= 1,
+            Some(Numeric) | Some(Alphabetic) => symbol_count >= 2,
+            Some(Additive) => has_additive_symbols,
+            Some(Extends(*)) => self.symbols.is_none() && self.additive_symbols.is_none(),
+            Some(ChineseInformal) => false,
+        }
+    }
+}
+
+
+fn parse_symbol(value: &ComponentValue) -> Option<~str> {
+    // TODO: <image> symbols
+    match value {
+        &String(ref value) | &Ident(ref value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+fn parse_symbol_list(input: &[ComponentValue]) -> Option<~[~str]> {
+    let mut symbols = ~[];
+    for value in input.skip_whitespace() {
+        match parse_symbol(value) {
+            Some(symbol) => symbols.push(symbol),
+            None => return None,
+        }
+    }
+    if symbols.is_empty() { None } else { Some(symbols) }
+}
+
+fn parse_single_symbol(input: &[ComponentValue]) -> Option<~str> {
+    one_component_value(input).chain(parse_symbol)
+}
+
+fn parse_integer(value: &ComponentValue) -> Option<int> {
+    match value {
+        &Number(ref value) => value.int_value.map(|&value| value as int),
+        _ => None,
+    }
+}
+
+fn parse_system(input: &[ComponentValue]) -> Option<System> {
+    let mut iter = input.skip_whitespace();
+    let keyword = match iter.next() {
+        Some(&Ident(ref keyword)) => keyword.to_ascii_lower(),
+        _ => return None,
+    };
+    let system = match keyword.as_slice() {
+        "cyclic" => Cyclic,
+        "numeric" => Numeric,
+        "alphabetic" => Alphabetic,
+        "symbolic" => Symbolic,
+        "additive" => Additive,
+        "fixed" => match iter.next() {
+            None => return Some(Fixed(1)),
+            Some(value) => match parse_integer(value) {
+                Some(first) => Fixed(first),
+                None => return None,
+            },
+        },
+        "extends" => match iter.next() {
+            Some(&Ident(ref name)) => Extends(name.clone()),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    if iter.next().is_none() { Some(system) } else { None }
+}
+
+fn parse_range(input: &[ComponentValue]) -> Option<CounterRange> {
+    fn parse_bound(value: &ComponentValue, infinite: int) -> Option<int> {
+        match value {
+            &Ident(ref keyword) if keyword.eq_ignore_ascii_case("infinite") => Some(infinite),
+            _ => parse_integer(value),
+        }
+    }
+
+    {
+        let mut iter = input.skip_whitespace();
+        match (iter.next(), iter.next()) {
+            (Some(&Ident(ref keyword)), None) if keyword.eq_ignore_ascii_case("auto") => {
+                return Some(AutoRange)
+            }
+            _ => {}
+        }
+    }
+    let mut ranges = ~[];
+    for part in input.split_iter(|value| *value == Comma) {
+        let mut iter = part.skip_whitespace();
+        let bounds = match (iter.next(), iter.next(), iter.next()) {
+            (Some(lower), Some(upper), None) => {
+                (parse_bound(lower, int::min_value), parse_bound(upper, int::max_value))
+            }
+            _ => return None,
+        };
+        match bounds {
+            (Some(lower), Some(upper)) if lower <= upper => ranges.push((lower, upper)),
+            _ => return None,
+        }
+    }
+    Some(Ranges(ranges))
+}
+
+fn parse_pad(input: &[ComponentValue]) -> Option<(uint, ~str)> {
+    let mut iter = input.skip_whitespace();
+    let (first, second) = match (iter.next(), iter.next(), iter.next()) {
+        (Some(first), Some(second), None) => (first, second),
+        _ => return None,
+    };
+    // The integer and the symbol can come in either order.
+    match (parse_integer(first), parse_symbol(second)) {
+        (Some(length), Some(symbol)) if length >= 0 => return Some((length as uint, symbol)),
+        _ => {}
+    }
+    match (parse_symbol(first), parse_integer(second)) {
+        (Some(symbol), Some(length)) if length >= 0 => Some((length as uint, symbol)),
+        _ => None,
+    }
+}
+
+fn parse_additive_symbols(input: &[ComponentValue]) -> Option<~[(uint, ~str)]> {
+    let mut symbols: ~[(uint, ~str)] = ~[];
+    for part in input.split_iter(|value| *value == Comma) {
+        match parse_pad(part) {
+            Some((weight, symbol)) => {
+                // Weights must be given in strictly decreasing order.
+                match symbols.last_opt() {
+                    Some(&(last_weight, _)) if last_weight <= weight => return None,
+                    _ => {}
+                }
+                symbols.push((weight, symbol))
+            }
+            None => return None,
+        }
+    }
+    Some(symbols)
+}
+
+
+impl CounterStyle {
+    /// Returns true if this style can represent `value` on its own, without using its fallback.
+    pub fn is_in_range(&self, value: int) -> bool {
+        match self.range {
+            Ranges(ref ranges) => {
+                ranges.iter().any(|&(lower, upper)| lower <= value && value <= upper)
+            }
+            AutoRange => match self.system {
+                Alphabetic | Symbolic => value >= 1,
+                Additive => value >= 0,
+                ChineseInformal => -9999 <= value && value <= 9999,
+                Cyclic | Numeric | Fixed(*) | Extends(*) => true,
+            },
+        }
+    }
+
+    /// Returns true if the `negative` descriptor is used for negative values.
+    fn uses_negative_sign(&self) -> bool {
+        match self.system {
+            Symbolic | Alphabetic | Numeric | Additive | ChineseInformal => true,
+            Cyclic | Fixed(*) | Extends(*) => false,
+        }
+    }
+
+    /// Runs the counter style's algorithm and applies the `negative` and `pad` descriptors.
+    /// Returns `None` if the algorithm can not represent `value`.
+    fn generate(&self, value: int) -> Option<~str> {
+        let negative = value < 0 && self.uses_negative_sign();
+        // The magnitude is worked out without negating `value`, which overflows for
+        // `int::min_value`.
+        let magnitude = if negative { (-(value + 1)) as uint + 1 } else { value as uint };
+        let representation = match self.system {
+            Cyclic => {
+                let count = self.symbols.len() as int;
+                // The remainder is taken first, as `value - 1` overflows for `int::min_value`.
+                let index = (value % count - 1 + count) % count;
+                Some(self.symbols[index as uint].clone())
+            }
+            Fixed(first) => {
+                // The difference is taken in `uint`s, where it can't overflow.
+                if value >= first && (value as uint) - (first as uint) < self.symbols.len() {
+                    Some(self.symbols[(value as uint) - (first as uint)].clone())
+                } else {
+                    None
+                }
+            }
+            Symbolic => {
+                if magnitude < 1 {
+                    None
+                } else {
+                    let count = self.symbols.len() as uint;
+                    let symbol = &self.symbols[(magnitude - 1) % count];
+                    let mut representation = ~"";
+                    for _ in range(0, (magnitude - 1) / count + 1) {
+                        representation.push_str(*symbol);
+                    }
+                    Some(representation)
+                }
+            }
+            Alphabetic => {
+                if magnitude < 1 {
+                    None
+                } else {
+                    let count = self.symbols.len() as uint;
+                    let mut value = magnitude;
+                    let mut symbols = ~[];
+                    while value != 0 {
+                        value -= 1;
+                        symbols.push(self.symbols[value % count].as_slice());
+                        value /= count;
+                    }
+                    symbols.reverse();
+                    Some(symbols.concat())
+                }
+            }
+            Numeric => {
+                let count = self.symbols.len() as uint;
+                let mut value = magnitude;
+                let mut symbols = ~[self.symbols[value % count].as_slice()];
+                value /= count;
+                while value != 0 {
+                    symbols.push(self.symbols[value % count].as_slice());
+                    value /= count;
+                }
+                symbols.reverse();
+                Some(symbols.concat())
+            }
+            Additive => additive_representation(magnitude, self.additive_symbols),
+            ChineseInformal => chinese_informal_representation(magnitude),
+            Extends(*) => fail!("counter styles are resolved when they are defined"),
+        };
+
+        do representation.map_move |representation| {
+            let (ref before, ref after) = self.negative;
+            let (pad_length, ref pad_symbol) = self.pad;
+            let mut length = representation.char_len();
+            if negative {
+                length += before.char_len() + after.char_len();
+            }
+            let mut result = ~"";
+            if negative {
+                result.push_str(*before);
+            }
+            while length < pad_length {
+                result.push_str(*pad_symbol);
+                length += 1;
+            }
+            result.push_str(representation);
+            if negative {
+                result.push_str(*after);
+            }
+            result
+        }
+    }
+}
+
+fn additive_representation(value: uint, symbols: &[(uint, ~str)]) -> Option<~str> {
+    if value == 0 {
+        // Only a symbol of weight zero can represent zero. It is always the last one.
+        return match symbols.last_opt() {
+            Some(&(0, ref symbol)) => Some(symbol.clone()),
+            _ => None,
+        }
+    }
+    let mut remaining = value;
+    let mut representation = ~"";
+    for &(weight, ref symbol) in symbols.iter() {
+        if weight == 0 || weight > remaining {
+            loop
+        }
+        for _ in range(0, remaining / weight) {
+            representation.push_str(*symbol);
+        }
+        remaining %= weight;
+        if remaining == 0 {
+            return Some(representation)
+        }
+    }
+    None
+}
+
+/// The algorithm for the informal Chinese styles, which is defined in prose by CSS Counter
+/// Styles rather than as a counter style system.
+fn chinese_informal_representation(value: uint) -> Option<~str> {
+    static DIGITS: [&'static str, ..10] = ["零", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+    static MARKERS: [&'static str, ..4] = ["", "十", "百", "千"];
+
+    if value == 0 {
+        return Some(DIGITS[0].to_owned())
+    }
+    if value > 9999 {
+        return None
+    }
+    let digits = [value / 1000, value / 100 % 10, value / 10 % 10, value % 10];
+    let mut representation = ~"";
+    let mut pending_zero = false;
+    for (i, &digit) in digits.iter().enumerate() {
+        let position = 3 - i;
+        if digit == 0 {
+            // Runs of zeros between non-zero digits are written as a single zero, and trailing
+            // zeros are dropped.
+            if !representation.is_empty() {
+                pending_zero = true
+            }
+            loop
+        }
+        if pending_zero {
+            representation.push_str(DIGITS[0]);
+            pending_zero = false;
+        }
+        // Ten to nineteen are written without the leading "one".
+        if !(position == 1 && digit == 1 && value < 20) {
+            representation.push_str(DIGITS[digit]);
+        }
+        representation.push_str(MARKERS[position]);
+    }
+    Some(representation)
+}
+
+
+/// The counter styles that are defined in a document: the predefined ones, and those from
+/// `@counter-style` rules.
+pub struct CounterStyleMap {
+    priv styles: HashMap<~str, CounterStyle>,
+}
+
+impl CounterStyleMap {
+    /// Returns a map that holds only the predefined counter styles.
+    pub fn new() -> CounterStyleMap {
+        let mut styles = HashMap::new();
+        for (name, style) in predefined_counter_styles().move_iter() {
+            styles.insert(name.to_owned(), style);
+        }
+        CounterStyleMap { styles: styles }
+    }
+
+    /// Defines a counter style from an `@counter-style` rule, replacing any style of the same
+    /// name.
+    ///
+    /// TODO: a style that extends a style defined by a later rule extends `decimal` instead.
+    pub fn add_rule(&mut self, rule: &CounterStyleRule) {
+        let style = match rule.system {
+            Some(Extends(ref name)) => {
+                let base = self.get(*name).clone();
+                CounterStyle {
+                    negative: rule.negative.clone().unwrap_or_default(base.negative.clone()),
+                    prefix: rule.prefix.clone().unwrap_or_default(base.prefix.clone()),
+                    suffix: rule.suffix.clone().unwrap_or_default(base.suffix.clone()),
+                    range: rule.range.clone().unwrap_or_default(base.range.clone()),
+                    pad: rule.pad.clone().unwrap_or_default(base.pad.clone()),
+                    fallback: rule.fallback.clone().unwrap_or_default(base.fallback.clone()),
+                    .. base
+                }
+            }
+            _ => CounterStyle {
+                system: rule.system.clone().unwrap_or_default(Symbolic),
+                negative: rule.negative.clone().unwrap_or_default((~"-", ~"")),
+                prefix: rule.prefix.clone().unwrap_or_default(~""),
+                suffix: rule.suffix.clone().unwrap_or_default(~". "),
+                range: rule.range.clone().unwrap_or_default(AutoRange),
+                pad: rule.pad.clone().unwrap_or_default((0, ~"")),
+                fallback: rule.fallback.clone().unwrap_or_default(~"decimal"),
+                symbols: rule.symbols.clone().unwrap_or_default(~[]),
+                additive_symbols: rule.additive_symbols.clone().unwrap_or_default(~[]),
+            },
+        };
+        self.styles.insert(rule.name.clone(), style);
+    }
+
+    /// Returns the counter style with the given name. Names are case-sensitive, except that the
+    /// predefined styles also match ASCII case-insensitively. Unknown names refer to `decimal`.
+    pub fn get<'a>(&'a self, name: &str) -> &'a CounterStyle {
+        match self.styles.find_equiv(&name) {
+            Some(style) => return style,
+            None => {}
+        }
+        match self.styles.find_equiv(&name.to_ascii_lower().as_slice()) {
+            Some(style) => style,
+            None => self.styles.get_equiv(&"decimal"),
+        }
+    }
+
+    /// Returns the representation of `value` in the named counter style, as used by `counter()`
+    /// and `counters()`.
+    pub fn representation(&self, value: int, name: &str) -> ~str {
+        let (_, representation) = self.generate(value, name);
+        representation
+    }
+
+    /// Returns the text of a list marker for `value` in the named counter style, which is its
+    /// representation between the prefix and suffix of the style that represented it: those of
+    /// the fallback style if the named one can't.
+    pub fn marker_text(&self, value: int, name: &str) -> ~str {
+        let (style, representation) = self.generate(value, name);
+        fmt!("%s%s%s", style.prefix, representation, style.suffix)
+    }
+
+    /// Returns the representation of `value` in the named counter style, following the
+    /// `fallback` descriptors until a style can represent it, along with the style that did.
+    fn generate<'a>(&'a self, value: int, name: &str) -> (&'a CounterStyle, ~str) {
+        let mut style = self.get(name);
+        for _ in range(0, MAX_FALLBACK_DEPTH) {
+            if style.is_in_range(value) {
+                match style.generate(value) {
+                    Some(representation) => return (style, representation),
+                    None => {}
+                }
+            }
+            style = self.get(style.fallback);
+        }
+        // The chain of fallback styles loops.
+        let decimal = self.get("decimal");
+        (decimal, decimal.generate(value).unwrap())
+    }
+}
+
+
+fn chars(symbols: &str) -> ~[~str] {
+    symbols.iter().map(|ch| ch.to_str()).collect()
+}
+
+fn numeric(digits: &str) -> CounterStyle {
+    CounterStyle {
+        system: Numeric,
+        negative: (~"-", ~""),
+        prefix: ~"",
+        suffix: ~". ",
+        range: AutoRange,
+        pad: (0, ~""),
+        fallback: ~"decimal",
+        symbols: chars(digits),
+        additive_symbols: ~[],
+    }
+}
+
+fn alphabetic(letters: &str) -> CounterStyle {
+    CounterStyle { system: Alphabetic, symbols: chars(letters), .. numeric("0123456789") }
+}
+
+fn cyclic(symbol: &str) -> CounterStyle {
+    CounterStyle {
+        system: Cyclic,
+        suffix: ~" ",
+        symbols: ~[symbol.to_owned()],
+        .. numeric("0123456789")
+    }
+}
+
+fn additive(lower: int, upper: int, symbols: &[(uint, &str)]) -> CounterStyle {
+    CounterStyle {
+        system: Additive,
+        range: Ranges(~[(lower, upper)]),
+        additive_symbols: symbols.iter().map(|&(weight, symbol)| (weight, symbol.to_owned()))
+                                 .collect(),
+        .. numeric("0123456789")
+    }
+}
+
+fn chinese_informal(negative: &str) -> CounterStyle {
+    CounterStyle {
+        system: ChineseInformal,
+        negative: (negative.to_owned(), ~""),
+        suffix: ~"、",
+        fallback: ~"cjk-decimal",
+        .. numeric("0123456789")
+    }
+}
+
+/// The counter styles of CSS Counter Styles Level 3, Section 7, that are always defined.
+fn predefined_counter_styles() -> ~[(&'static str, CounterStyle)] {
+    static ROMAN: &'static [(uint, &'static str)] = &[
+        (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"), (50, "L"),
+        (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+    ];
+    static LOWER_ROMAN: &'static [(uint, &'static str)] = &[
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"), (50, "l"),
+        (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+    ];
+    static ARMENIAN: &'static [(uint, &'static str)] = &[
+        (9000, "Ք"), (8000, "Փ"), (7000, "Ւ"), (6000, "Ց"), (5000, "Ր"), (4000, "Տ"),
+        (3000, "Վ"), (2000, "Ս"), (1000, "Ռ"), (900, "Ջ"), (800, "Պ"), (700, "Չ"), (600, "Ո"),
+        (500, "Շ"), (400, "Ն"), (300, "Յ"), (200, "Մ"), (100, "Ճ"), (90, "Ղ"), (80, "Ձ"),
+        (70, "Հ"), (60, "Կ"), (50, "Ծ"), (40, "Խ"), (30, "Լ"), (20, "Ի"), (10, "Ժ"), (9, "Թ"),
+        (8, "Ը"), (7, "Է"), (6, "Զ"), (5, "Ե"), (4, "Դ"), (3, "Գ"), (2, "Բ"), (1, "Ա"),
+    ];
+    static LOWER_ARMENIAN: &'static [(uint, &'static str)] = &[
+        (9000, "ք"), (8000, "փ"), (7000, "ւ"), (6000, "ց"), (5000, "ր"), (4000, "տ"),
+        (3000, "վ"), (2000, "ս"), (1000, "ռ"), (900, "ջ"), (800, "պ"), (700, "չ"), (600, "ո"),
+        (500, "շ"), (400, "ն"), (300, "յ"), (200, "մ"), (100, "ճ"), (90, "ղ"), (80, "ձ"),
+        (70, "հ"), (60, "կ"), (50, "ծ"), (40, "խ"), (30, "լ"), (20, "ի"), (10, "ժ"), (9, "թ"),
+        (8, "ը"), (7, "է"), (6, "զ"), (5, "ե"), (4, "դ"), (3, "գ"), (2, "բ"), (1, "ա"),
+    ];
+    static GEORGIAN: &'static [(uint, &'static str)] = &[
+        (10000, "ჵ"), (9000, "ჰ"), (8000, "ჯ"), (7000, "ჴ"), (6000, "ხ"), (5000, "ჭ"),
+        (4000, "წ"), (3000, "ძ"), (2000, "ც"), (1000, "ჩ"), (900, "შ"), (800, "ყ"), (700, "ღ"),
+        (600, "ქ"), (500, "ფ"), (400, "ჳ"), (300, "ტ"), (200, "ს"), (100, "რ"), (90, "ჟ"),
+        (80, "პ"), (70, "ო"), (60, "ჲ"), (50, "ნ"), (40, "მ"), (30, "ლ"), (20, "კ"), (10, "ი"),
+        (9, "თ"), (8, "ჱ"), (7, "ზ"), (6, "ვ"), (5, "ე"), (4, "დ"), (3, "გ"), (2, "ბ"), (1, "ა"),
+    ];
+    static HEBREW: &'static [(uint, &'static str)] = &[
+        (10000, "י׳"), (9000, "ט׳"), (8000, "ח׳"), (7000, "ז׳"), (6000, "ו׳"), (5000, "ה׳"),
+        (4000, "ד׳"), (3000, "ג׳"), (2000, "ב׳"), (1000, "א׳"), (400, "ת"), (300, "ש"),
+        (200, "ר"), (100, "ק"), (90, "צ"), (80, "פ"), (70, "ע"), (60, "ס"), (50, "נ"), (40, "מ"),
+        (30, "ל"), (20, "כ"), (19, "יט"), (18, "יח"), (17, "יז"), (16, "טז"), (15, "טו"),
+        (10, "י"), (9, "ט"), (8, "ח"), (7, "ז"), (6, "ו"), (5, "ה"), (4, "ד"), (3, "ג"),
+        (2, "ב"), (1, "א"),
+    ];
+
+    let cjk_suffix = |style: CounterStyle| CounterStyle { suffix: ~"、", .. style };
+
+    ~[
+        ("decimal", numeric("0123456789")),
+        ("decimal-leading-zero", CounterStyle { pad: (2, ~"0"), .. numeric("0123456789") }),
+        ("arabic-indic", numeric("٠١٢٣٤٥٦٧٨٩")),
+        ("bengali", numeric("০১২৩৪৫৬৭৮৯")),
+        ("cambodian", numeric("០១២៣៤៥៦៧៨៩")),
+        ("cjk-decimal", cjk_suffix(numeric("〇一二三四五六七八九"))),
+        ("devanagari", numeric("०१२३४५६७८९")),
+        ("gujarati", numeric("૦૧૨૩૪૫૬૭૮૯")),
+        ("gurmukhi", numeric("੦੧੨੩੪੫੬੭੮੯")),
+        ("kannada", numeric("೦೧೨೩೪೫೬೭೮೯")),
+        ("khmer", numeric("០១២៣៤៥៦៧៨៩")),
+        ("lao", numeric("໐໑໒໓໔໕໖໗໘໙")),
+        ("malayalam", numeric("൦൧൨൩൪൫൬൭൮൯")),
+        ("mongolian", numeric("᠐᠑᠒᠓᠔᠕᠖᠗᠘᠙")),
+        ("myanmar", numeric("၀၁၂၃၄၅၆၇၈၉")),
+        ("oriya", numeric("୦୧୨୩୪୫୬୭୮୯")),
+        ("persian", numeric("۰۱۲۳۴۵۶۷۸۹")),
+        ("tamil", numeric("௦௧௨௩௪௫௬௭௮௯")),
+        ("telugu", numeric("౦౧౨౩౪౫౬౭౮౯")),
+        ("thai", numeric("๐๑๒๓๔๕๖๗๘๙")),
+        ("tibetan", numeric("༠༡༢༣༤༥༦༧༨༩")),
+
+        ("lower-alpha", alphabetic("abcdefghijklmnopqrstuvwxyz")),
+        ("lower-latin", alphabetic("abcdefghijklmnopqrstuvwxyz")),
+        ("upper-alpha", alphabetic("ABCDEFGHIJKLMNOPQRSTUVWXYZ")),
+        ("upper-latin", alphabetic("ABCDEFGHIJKLMNOPQRSTUVWXYZ")),
+        ("lower-greek", alphabetic("αβγδεζηθικλμνξοπρστυφχψω")),
+        ("hiragana", cjk_suffix(alphabetic(
+            "あいうえおかきくけこさしすせそたちつてとなにぬねのはひふへほまみむめもやゆよらりるれろわゐゑをん"))),
+        ("hiragana-iroha", cjk_suffix(alphabetic(
+            "いろはにほへとちりぬるをわかよたれそつねならむうゐのおくやまけふこえてあさきゆめみしゑひもせす"))),
+        ("katakana", cjk_suffix(alphabetic(
+            "アイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワヰヱヲン"))),
+        ("katakana-iroha", cjk_suffix(alphabetic(
+            "イロハニホヘトチリヌルヲワカヨタレソツネナラムウヰノオクヤマケフコエテアサキユメミシヱヒモセス"))),
+        ("cjk-earthly-branch", cjk_suffix(alphabetic("子丑寅卯辰巳午未申酉戌亥"))),
+        ("cjk-heavenly-stem", cjk_suffix(alphabetic("甲乙丙丁戊己庚辛壬癸"))),
+
+        ("lower-roman", additive(1, 3999, LOWER_ROMAN)),
+        ("upper-roman", additive(1, 3999, ROMAN)),
+        ("armenian", additive(1, 9999, ARMENIAN)),
+        ("upper-armenian", additive(1, 9999, ARMENIAN)),
+        ("lower-armenian", additive(1, 9999, LOWER_ARMENIAN)),
+        ("georgian", additive(1, 19999, GEORGIAN)),
+        ("hebrew", additive(1, 10999, HEBREW)),
+
+        ("cjk-ideographic", chinese_informal("負")),
+        ("trad-chinese-informal", chinese_informal("負")),
+        ("simp-chinese-informal", chinese_informal("负")),
+
+        ("disc", cyclic("•")),
+        ("circle", cyclic("◦")),
+        ("square", cyclic("▪")),
+        ("disclosure-open", cyclic("▾")),
+        ("disclosure-closed", cyclic("▸")),
+    ]
+}
+
+
+#[cfg(test)]
+fn counter_styles_of(css: &str) -> CounterStyleMap {
+    use style::media_queries::{Device, Screen};
+    use style::stylesheets::parse_stylesheet;
+    let mut counter_styles = CounterStyleMap::new();
+    parse_stylesheet(css).add_counter_styles(&Device { media_type: Screen }, &mut counter_styles);
+    counter_styles
+}
+
+#[test]
+fn test_predefined_styles() {
+    let styles = CounterStyleMap::new();
+    assert!(styles.representation(42, "decimal") == ~"42");
+    assert!(styles.representation(-5, "decimal") == ~"-5");
+    assert!(styles.representation(7, "decimal-leading-zero") == ~"07");
+    // The negative sign counts towards the padded length.
+    assert!(styles.representation(-7, "decimal-leading-zero") == ~"-7");
+    assert!(styles.representation(1, "lower-alpha") == ~"a");
+    assert!(styles.representation(27, "lower-alpha") == ~"aa");
+    assert!(styles.representation(1994, "upper-roman") == ~"MCMXCIV");
+    assert!(styles.representation(2023, "cjk-decimal") == ~"二〇二三");
+    assert!(styles.representation(11, "simp-chinese-informal") == ~"十一");
+    assert!(styles.representation(105, "trad-chinese-informal") == ~"一百零五");
+    assert!(styles.representation(-3, "disc") == ~"•");
+    // Predefined names match case-insensitively, and unknown ones mean `decimal`.
+    assert!(styles.representation(4, "Upper-Roman") == ~"IV");
+    assert!(styles.representation(4, "unknown") == ~"4");
+}
+
+#[test]
+fn test_out_of_range_values_fall_back() {
+    let styles = CounterStyleMap::new();
+    assert!(styles.representation(4000, "upper-roman") == ~"4000");
+    assert!(styles.representation(0, "lower-alpha") == ~"0");
+    assert!(styles.representation(10000, "cjk-ideographic") == ~"一〇〇〇〇");
+}
+
+#[test]
+fn test_min_value() {
+    let styles = CounterStyleMap::new();
+    let min = int::min_value;
+    assert!(styles.representation(min, "decimal") == min.to_str());
+    assert!(styles.representation(min, "lower-alpha") == min.to_str());
+    assert!(styles.representation(min, "disc") == ~"•");
+
+    let styles = counter_styles_of("@counter-style neg { system: alphabetic; symbols: a b; \
+                                                       range: infinite infinite; }");
+    // In bijective base two, the magnitude of the smallest value is all ones.
+    assert!(styles.representation(min, "neg") == ~"-" + "a".repeat(int::bits - 1));
+}
+
+#[test]
+fn test_marker_text() {
+    let styles = CounterStyleMap::new();
+    assert!(styles.marker_text(3, "lower-roman") == ~"iii. ");
+    assert!(styles.marker_text(1, "square") == ~"▪ ");
+
+    // A style that falls back uses the prefix and suffix of the style that represents the value.
+    let styles = counter_styles_of("@counter-style paren { system: fixed; symbols: a b; \
+                                                           prefix: '('; suffix: ')'; \
+                                                           fallback: cjk-decimal; }");
+    assert!(styles.marker_text(2, "paren") == ~"(b)");
+    assert!(styles.marker_text(3, "paren") == ~"三、");
+}
+
+#[test]
+fn test_extends() {
+    let styles = counter_styles_of("@counter-style roman-paren { system: extends upper-roman; \
+                                                                suffix: ') '; }");
+    assert!(styles.marker_text(9, "roman-paren") == ~"IX) ");
+    assert!(styles.marker_text(5000, "roman-paren") == ~"5000. ");
+}
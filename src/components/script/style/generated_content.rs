@@ -0,0 +1,261 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Turns the computed value of `content` into the text and images of `::before` and `::after`
+//! boxes.
+//!
+//! Some content depends on what was generated earlier in the document: quotation marks depend on
+//! how deeply quotes are nested at that point. A `GeneratedContentState` carries that state, and
+//! must see the generated content of every element in document order, as box construction does.
+//!
+//! Content can also read the attributes of its element with `attr()`. The document restyles an
+//! element whenever any of its attributes changes, and the values of its pseudo-elements are
+//! computed again; if their content reads attributes, layout is damaged as if the values had
+//! changed, so that the boxes built from them again are measured with the new text.
+
+use std::ascii::StrAsciiExt;
+use std::uint;
+use dom::node::AbstractNode;
+use extra::url::Url;
+use servo_util::url::make_url;
+use style::properties::longhands::content::{ContentItem, StringContent, AttrContent, UrlContent};
+use style::properties::longhands::content::{OpenQuote, CloseQuote, NoOpenQuote, NoCloseQuote};
+use style::properties::longhands::quotes;
+
+
+/// A piece of generated content, which becomes a box of its own.
+#[deriving(Eq)]
+pub enum GeneratedContent {
+    /// Text, which adjacent strings, attributes and quotes are merged into.
+    GeneratedText(~str),
+    /// An image, which is a replaced element, loaded through the image cache as those of `<img>`
+    /// are.
+    GeneratedImage(Url),
+}
+
+
+pub struct GeneratedContentState {
+    /// The number of open quotes that have not been closed yet.
+    priv quote_depth: uint,
+}
+
+impl GeneratedContentState {
+    pub fn new() -> GeneratedContentState {
+        GeneratedContentState { quote_depth: 0 }
+    }
+
+    /// Returns the content generated by `content` for `element`, whose `quotes` property is
+    /// `quotes`, and updates the quote depth. URLs must have been resolved already.
+    pub fn generate<View>(&mut self,
+                          content: &[ContentItem],
+                          quotes: &quotes::ComputedValue,
+                          element: AbstractNode<View>)
+                          -> ~[GeneratedContent] {
+        let language = element.language();
+        let lang = language.as_ref().map(|lang| lang.as_slice());
+        do self.generate_with_attributes(content, quotes, lang) |name| {
+            do element.with_imm_element |element| {
+                element.get_attr(name).map(|&value| value.to_owned())
+            }
+        }
+    }
+
+    /// Returns the content generated by `content` for an element of the language `lang`, reading
+    /// its attributes with `get_attr`.
+    fn generate_with_attributes(&mut self,
+                                content: &[ContentItem],
+                                quotes: &quotes::ComputedValue,
+                                lang: Option<&str>,
+                                get_attr: &fn(&str) -> Option<~str>)
+                                -> ~[GeneratedContent] {
+        let mut generated = ~[];
+        let mut text = ~"";
+        for item in content.iter() {
+            match *item {
+                StringContent(ref value) => text.push_str(*value),
+                UrlContent(ref url) => {
+                    if !text.is_empty() {
+                        generated.push(GeneratedText(text));
+                        text = ~"";
+                    }
+                    generated.push(GeneratedImage(make_url(url.clone(), None)))
+                }
+                AttrContent(ref name) => {
+                    match get_attr(name.as_slice()) {
+                        Some(value) => text.push_str(value.as_slice()),
+                        None => {}
+                    }
+                }
+                OpenQuote => {
+                    match quote_pair(quotes, self.quote_depth, lang) {
+                        Some((open, _)) => text.push_str(open),
+                        None => {}
+                    }
+                    self.quote_depth += 1;
+                }
+                CloseQuote => {
+                    // A close quote with no matching open quote generates nothing.
+                    if self.quote_depth > 0 {
+                        self.quote_depth -= 1;
+                        match quote_pair(quotes, self.quote_depth, lang) {
+                            Some((_, close)) => text.push_str(close),
+                            None => {}
+                        }
+                    }
+                }
+                NoOpenQuote => self.quote_depth += 1,
+                NoCloseQuote => {
+                    if self.quote_depth > 0 {
+                        self.quote_depth -= 1
+                    }
+                }
+            }
+        }
+        if !text.is_empty() {
+            generated.push(GeneratedText(text))
+        }
+        generated
+    }
+}
+
+
+/// Returns true if `content` reads attributes of its element, so that what it generates can
+/// change while its value stays the same.
+pub fn reads_attributes(content: &[ContentItem]) -> bool {
+    do content.iter().any |item| {
+        match *item {
+            AttrContent(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Returns the quotation marks used at the given nesting depth. Levels nested deeper than the
+/// list of quotes reaches use its last pair.
+fn quote_pair<'a>(quotes: &'a quotes::ComputedValue, depth: uint, lang: Option<&str>)
+                  -> Option<(&'a str, &'a str)> {
+    match *quotes {
+        quotes::none => None,
+        quotes::Quotes(ref pairs) => {
+            let (ref open, ref close) = pairs[uint::min(depth, pairs.len() - 1)];
+            Some((open.as_slice(), close.as_slice()))
+        }
+        quotes::auto => {
+            let pairs = quotes_for_language(lang);
+            Some(pairs[uint::min(depth, pairs.len() - 1)])
+        }
+    }
+}
+
+
+/// The quotation marks for `quotes: auto`: the primary and secondary quotes of the language, as
+/// given by the CLDR. Languages that aren't listed use English quotes.
+fn quotes_for_language(lang: Option<&str>) -> &'static [(&'static str, &'static str)] {
+    static ENGLISH: &'static [(&'static str, &'static str)] = &[("“", "”"), ("‘", "’")];
+    static FRENCH: &'static [(&'static str, &'static str)] =
+        &[("«\u00a0", "\u00a0»"), ("“", "”")];
+    static GERMAN: &'static [(&'static str, &'static str)] = &[("„", "“"), ("‚", "‘")];
+    static GUILLEMETS: &'static [(&'static str, &'static str)] = &[("«", "»"), ("„", "“")];
+    static SWISS: &'static [(&'static str, &'static str)] = &[("«", "»"), ("‹", "›")];
+    static POLISH: &'static [(&'static str, &'static str)] = &[("„", "”"), ("«", "»")];
+    static SWEDISH: &'static [(&'static str, &'static str)] = &[("”", "”"), ("’", "’")];
+    static DUTCH: &'static [(&'static str, &'static str)] = &[("‘", "’"), ("“", "”")];
+    static CJK: &'static [(&'static str, &'static str)] = &[("「", "」"), ("『", "』")];
+    static HEBREW: &'static [(&'static str, &'static str)] = &[("”", "”"), ("’", "’")];
+
+    let lang = match lang {
+        Some(lang) => lang.to_ascii_lower(),
+        None => return ENGLISH,
+    };
+    // Regional variants that differ from the rest of the language.
+    if lang.starts_with("de-ch") || lang.starts_with("fr-ch") || lang.starts_with("it-ch") {
+        return SWISS
+    }
+    if lang.starts_with("zh-cn") || lang.starts_with("zh-hans") {
+        return ENGLISH
+    }
+    let primary = match lang.find('-') {
+        Some(i) => lang.slice_to(i),
+        None => lang.as_slice(),
+    };
+    match primary {
+        "fr" => FRENCH,
+        "de" | "cs" | "sk" | "lt" | "is" | "sl" | "bg" | "et" | "ka" => GERMAN,
+        "ru" | "uk" | "be" | "es" | "it" | "pt" | "el" | "ca" | "ar" | "fa" | "nb" | "no"
+        | "nn" => GUILLEMETS,
+        "pl" | "hu" | "ro" | "hr" => POLISH,
+        "sv" | "fi" => SWEDISH,
+        "nl" => DUTCH,
+        "ja" | "zh" => CJK,
+        "he" => HEBREW,
+        _ => ENGLISH,
+    }
+}
+
+#[cfg(test)]
+fn generate_text(state: &mut GeneratedContentState,
+                 content: &[ContentItem],
+                 quotes: &quotes::ComputedValue,
+                 lang: Option<&str>)
+                 -> ~str {
+    let get_attr: &fn(&str) -> Option<~str> = |name| {
+        if "title" == name { Some(~"T") } else { None }
+    };
+    let generated = state.generate_with_attributes(content, quotes, lang, get_attr);
+    if generated.is_empty() {
+        return ~""
+    }
+    assert!(generated.len() == 1);
+    match generated[0] {
+        GeneratedText(ref text) => text.clone(),
+        GeneratedImage(_) => fail!(~"expected text"),
+    }
+}
+
+#[test]
+fn test_nested_quotes() {
+    let mut state = GeneratedContentState::new();
+    let auto = quotes::auto;
+    assert!(generate_text(&mut state, [OpenQuote], &auto, None) == ~"\u201c");
+    assert!(generate_text(&mut state, [OpenQuote], &auto, None) == ~"\u2018");
+    // Levels deeper than the list of quotes reaches use its last pair.
+    assert!(generate_text(&mut state, [OpenQuote], &auto, None) == ~"\u2018");
+    assert!(generate_text(&mut state, [CloseQuote, CloseQuote], &auto, None) ==
+            ~"\u2019\u2019");
+    assert!(generate_text(&mut state, [CloseQuote], &auto, None) == ~"\u201d");
+
+    // A close quote with no open quote generates nothing, and doesn't change the depth.
+    assert!(generate_text(&mut state, [CloseQuote], &auto, None) == ~"");
+    assert!(generate_text(&mut state, [NoOpenQuote, OpenQuote], &auto, None) == ~"\u2018");
+}
+
+#[test]
+fn test_quotes_for_language() {
+    let mut state = GeneratedContentState::new();
+    let auto = quotes::auto;
+    assert!(generate_text(&mut state, [OpenQuote], &auto, Some("de-AT")) == ~"\u201e");
+    assert!(generate_text(&mut state, [CloseQuote], &auto, Some("de-CH")) == ~"\u00bb");
+    assert!(generate_text(&mut state, [OpenQuote], &auto, Some("ja")) == ~"\u300c");
+    assert!(generate_text(&mut state, [CloseQuote], &auto, Some("unknown")) == ~"\u201d");
+
+    let given = quotes::Quotes(~[(~"<", ~">")]);
+    assert!(generate_text(&mut state, [OpenQuote, OpenQuote], &given, None) == ~"<<");
+    assert!(generate_text(&mut state, [CloseQuote], &quotes::none, None) == ~"");
+}
+
+#[test]
+fn test_strings_and_attributes() {
+    let mut state = GeneratedContentState::new();
+    let content = [StringContent(~"["), AttrContent(~"title"), AttrContent(~"unknown"),
+                   StringContent(~"]")];
+    assert!(generate_text(&mut state, content, &quotes::auto, None) == ~"[T]");
+    assert!(reads_attributes(content));
+
+    let content = [StringContent(~"a"), UrlContent(~"http://example.com/b.png"),
+                   StringContent(~"c")];
+    let generated = state.generate_with_attributes(content, &quotes::auto, None, |_| None);
+    assert!(generated.len() == 3);
+    assert!(generated[0] == GeneratedText(~"a") && generated[2] == GeneratedText(~"c"));
+    assert!(!reads_attributes(content));
+}
@@ -0,0 +1,366 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Invalidation sets: what the selectors of the style sheets need restyled when a class, an ID or
+//! an attribute of an element changes, so that a change restyles only the elements whose style
+//! could depend on it rather than the whole subtree of the element. Whether a selector tests the
+//! positions of elements among their siblings is kept too, since otherwise inserting or removing
+//! a child restyles nothing but the new children.
+
+use std::ascii::StrAsciiExt;
+use std::hashmap::HashMap;
+use std::str::eq_slice;
+use dom::node::AbstractNode;
+use style::selector_matching::depends_on_sibling_positions;
+use style::selectors::*;
+
+
+/// A class, an ID or a local name that tells the elements that a compound selector matches apart
+/// from the others, so that only those are restyled.
+#[deriving(Clone, Eq)]
+pub enum Feature {
+    ClassFeature(~str),
+    IdFeature(~str),
+    LocalNameFeature(~str),
+}
+
+impl Feature {
+    pub fn matches<View>(&self, element: AbstractNode<View>) -> bool {
+        static WHITESPACE: &'static [char] = &'static [' ', '\t', '\n', '\r', '\x0c'];
+
+        do element.with_imm_element |element| {
+            match *self {
+                ClassFeature(ref class) => {
+                    element.get_attr("class").map_default(false, |attr| {
+                        attr.split_iter(WHITESPACE).any(|c| eq_slice(c, *class))
+                    })
+                }
+                IdFeature(ref id) => element.get_attr("id").map_default(false, |attr| {
+                    eq_slice(*attr, *id)
+                }),
+                LocalNameFeature(ref name) => element.tag_name.eq_ignore_ascii_case(*name),
+            }
+        }
+    }
+}
+
+
+/// The elements that must be restyled when a class, an ID or an attribute of an element changes,
+/// relative to that element.
+#[deriving(Clone)]
+pub struct InvalidationSet {
+    /// Whether a selector tests it on the elements that it matches.
+    element: bool,
+    /// Whether a selector tests it on an element before a sibling combinator, so that the later
+    /// siblings of the element and their descendants might match.
+    later_siblings: bool,
+    /// Whether a selector tests it on an ancestor of elements that have no feature in common, so
+    /// that any descendant might match.
+    all_descendants: bool,
+    /// The features of the descendants that selectors testing it on an ancestor match.
+    descendants: ~[Feature],
+}
+
+impl InvalidationSet {
+    pub fn new() -> InvalidationSet {
+        InvalidationSet {
+            element: false,
+            later_siblings: false,
+            all_descendants: false,
+            descendants: ~[],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.element && !self.later_siblings && !self.all_descendants &&
+            self.descendants.is_empty()
+    }
+
+    /// Adds what another set needs restyled to this one.
+    pub fn add(&mut self, other: &InvalidationSet) {
+        self.element = self.element || other.element;
+        self.later_siblings = self.later_siblings || other.later_siblings;
+        self.all_descendants = self.all_descendants || other.all_descendants;
+        for feature in other.descendants.iter() {
+            if !self.descendants.contains(feature) {
+                self.descendants.push(feature.clone())
+            }
+        }
+    }
+}
+
+
+/// The invalidation sets of the classes, IDs and attributes that the selectors of some style
+/// sheets test.
+pub struct StyleDependencies {
+    classes: HashMap<~str, InvalidationSet>,
+    ids: HashMap<~str, InvalidationSet>,
+    /// The sets of attributes, by lowercase name.
+    attributes: HashMap<~str, InvalidationSet>,
+    /// What the selectors that test attributes without naming them, as `:invalid` does, need
+    /// restyled when any attribute changes.
+    any_attribute: InvalidationSet,
+    /// Whether a selector tests where elements are among their siblings.
+    sibling_positions: bool,
+    /// Whether a selector couldn't be parsed, so that nothing is known of what it tests.
+    incomplete: bool,
+}
+
+impl StyleDependencies {
+    pub fn new() -> StyleDependencies {
+        StyleDependencies {
+            classes: HashMap::new(),
+            ids: HashMap::new(),
+            attributes: HashMap::new(),
+            any_attribute: InvalidationSet::new(),
+            sibling_positions: false,
+            incomplete: false,
+        }
+    }
+
+    /// Notes that a selector of the style sheets couldn't be parsed.
+    pub fn add_unknown_selector(&mut self) {
+        self.incomplete = true
+    }
+
+    /// Adds what the classes, IDs and attributes that `selector` tests need restyled.
+    pub fn add_selector(&mut self, selector: &Selector) {
+        self.sibling_positions = self.sibling_positions || depends_on_sibling_positions(selector);
+
+        let subject = &selector.compound_selectors;
+        let mut set = InvalidationSet::new();
+        set.element = true;
+        self.add_compound_selector(subject, &set);
+
+        // The elements that the selector matches are the descendants or the later siblings of
+        // those that the compound selectors to the left of the subject match.
+        let mut compound = subject;
+        loop {
+            let (next, combinator) = match compound.next {
+                None => break,
+                Some((ref next, combinator)) => (&**next, combinator),
+            };
+            let mut set = InvalidationSet::new();
+            match combinator {
+                NextSibling | LaterSibling => set.later_siblings = true,
+                Child | Descendant => {
+                    match subject_feature(subject.simple_selectors) {
+                        Some(feature) => set.descendants.push(feature),
+                        None => set.all_descendants = true,
+                    }
+                }
+            }
+            self.add_compound_selector(next, &set);
+            compound = next;
+        }
+    }
+
+    fn add_compound_selector(&mut self, compound: &CompoundSelector, set: &InvalidationSet) {
+        self.add_simple_selectors(compound.simple_selectors, set)
+    }
+
+    fn add_simple_selectors(&mut self, selectors: &[SimpleSelector], set: &InvalidationSet) {
+        for selector in selectors.iter() {
+            match *selector {
+                IDSelector(ref id) => add_to(&mut self.ids, id.clone(), set),
+                ClassSelector(ref class) => add_to(&mut self.classes, class.clone(), set),
+                AttrExists(ref attr) | AttrEqual(ref attr, _) | AttrIncludes(ref attr, _) |
+                AttrDashMatch(ref attr, _) | AttrPrefixMatch(ref attr, _) |
+                AttrSubstringMatch(ref attr, _) | AttrSuffixMatch(ref attr, _) => {
+                    add_to(&mut self.attributes, attr.lowercase_name.clone(), set)
+                }
+                // The language of an element is that of its nearest ancestor with one, so a
+                // change of `lang` can change what `:lang()` matches anywhere in the subtree.
+                Lang(_) => {
+                    let mut set = set.clone();
+                    set.all_descendants = true;
+                    add_to(&mut self.attributes, ~"lang", &set)
+                }
+                Link => add_to(&mut self.attributes, ~"href", set),
+                Valid | Invalid => self.any_attribute.add(set),
+                Negation(ref negated) => self.add_simple_selectors(negated.as_slice(), set),
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns whether inserting or removing a child of an element can change the style of its
+    /// other children, and so of their descendants, as structural pseudo-classes and sibling
+    /// combinators match by position. It can if it isn't known.
+    pub fn depend_on_sibling_positions(&self) -> bool {
+        self.incomplete || self.sibling_positions
+    }
+
+    /// Returns what must be restyled when the class attribute of an element changes from `old`
+    /// to `new`, or `None` if it isn't known.
+    pub fn class_changed(&self, old: &str, new: &str) -> Option<InvalidationSet> {
+        static WHITESPACE: &'static [char] = &'static [' ', '\t', '\n', '\r', '\x0c'];
+
+        let mut result = match self.attribute_changed("class") {
+            None => return None,
+            Some(set) => set,
+        };
+        let old: ~[&str] = old.split_iter(WHITESPACE).filter(|c| !c.is_empty()).collect();
+        let new: ~[&str] = new.split_iter(WHITESPACE).filter(|c| !c.is_empty()).collect();
+        // Only the classes that were added or removed change what the selectors match.
+        let removed = old.iter().filter(|c| !new.contains(*c));
+        let added = new.iter().filter(|c| !old.contains(*c));
+        for &class in removed.chain(added) {
+            for set in self.classes.find_equiv(&class).iter() {
+                result.add(*set)
+            }
+        }
+        Some(result)
+    }
+
+    /// Returns what must be restyled when the ID of an element changes from `old` to `new`, or
+    /// `None` if it isn't known.
+    pub fn id_changed(&self, old: &str, new: &str) -> Option<InvalidationSet> {
+        let mut result = match self.attribute_changed("id") {
+            None => return None,
+            Some(set) => set,
+        };
+        if old != new {
+            for id in [old, new].iter() {
+                for set in self.ids.find_equiv(id).iter() {
+                    result.add(*set)
+                }
+            }
+        }
+        Some(result)
+    }
+
+    /// Returns what must be restyled when the attribute `name` of an element changes, as far as
+    /// the selectors that test it by name tell, or `None` if it isn't known.
+    pub fn attribute_changed(&self, name: &str) -> Option<InvalidationSet> {
+        if self.incomplete {
+            return None
+        }
+        let mut result = self.any_attribute.clone();
+        for set in self.attributes.find_equiv(&name).iter() {
+            result.add(*set)
+        }
+        Some(result)
+    }
+}
+
+fn add_to(map: &mut HashMap<~str, InvalidationSet>, key: ~str, set: &InvalidationSet) {
+    let entry = map.find_or_insert_with(key, |_| InvalidationSet::new());
+    entry.add(set)
+}
+
+/// Returns the feature that the elements that a compound selector matches must have, preferring
+/// the one that the fewest elements are likely to have.
+fn subject_feature(selectors: &[SimpleSelector]) -> Option<Feature> {
+    let mut feature = None;
+    for selector in selectors.iter() {
+        match *selector {
+            IDSelector(ref id) => return Some(IdFeature(id.clone())),
+            ClassSelector(ref class) => feature = Some(ClassFeature(class.clone())),
+            LocalNameSelector{lowercase_name: ref name, _} if feature.is_none() => {
+                feature = Some(LocalNameFeature(name.clone()))
+            }
+            _ => {}
+        }
+    }
+    feature
+}
+
+#[cfg(test)]
+fn dependencies_of(selectors: &[&str]) -> StyleDependencies {
+    use cssparser::tokenize;
+    use style::namespaces::NamespaceMap;
+
+    let namespaces = NamespaceMap::new();
+    let mut dependencies = StyleDependencies::new();
+    for text in selectors.iter() {
+        let input = tokenize(*text).map(|(value, _)| value).collect();
+        match parse_selector_list(input, &namespaces) {
+            Some(selectors) => {
+                for selector in selectors.iter() {
+                    dependencies.add_selector(selector)
+                }
+            }
+            None => dependencies.add_unknown_selector(),
+        }
+    }
+    dependencies
+}
+
+#[test]
+fn test_class_changed() {
+    let dependencies = dependencies_of([".a", ".b .c", ".d + p", "div"]);
+
+    // Classes that stay don't change what the selectors match.
+    assert!(dependencies.class_changed("a", " a ").unwrap().is_empty());
+    assert!(dependencies.class_changed("", "unknown").unwrap().is_empty());
+
+    let set = dependencies.class_changed("", "a").unwrap();
+    assert!(set.element && !set.later_siblings && !set.all_descendants);
+    assert!(set.descendants.is_empty());
+
+    let set = dependencies.class_changed("b x", "x").unwrap();
+    assert!(!set.element && !set.later_siblings && !set.all_descendants);
+    assert!(set.descendants == ~[ClassFeature(~"c")]);
+
+    let set = dependencies.class_changed("x", "x d").unwrap();
+    assert!(!set.element && set.later_siblings);
+
+    // Adding one class and removing another needs what both need.
+    let set = dependencies.class_changed("a", "d").unwrap();
+    assert!(set.element && set.later_siblings);
+}
+
+#[test]
+fn test_id_changed() {
+    let dependencies = dependencies_of(["#x", "#y span", "#y.z > *"]);
+
+    assert!(dependencies.id_changed("x", "x").unwrap().is_empty());
+    assert!(dependencies.id_changed("", "unknown").unwrap().is_empty());
+
+    let set = dependencies.id_changed("", "x").unwrap();
+    assert!(set.element && !set.all_descendants);
+
+    // The selector that the ID is removed from and the one it is given to both count.
+    let set = dependencies.id_changed("y", "x").unwrap();
+    assert!(set.element && set.all_descendants);
+    assert!(set.descendants == ~[LocalNameFeature(~"span")]);
+}
+
+#[test]
+fn test_attribute_changed() {
+    let dependencies = dependencies_of(["[title]", "a:link", "div:lang(en) p",
+                                        "[data-x] ~ span", "[CLASS~=a]"]);
+
+    let set = dependencies.attribute_changed("title").unwrap();
+    assert!(set.element && !set.later_siblings);
+    assert!(dependencies.attribute_changed("href").unwrap().element);
+    assert!(dependencies.attribute_changed("lang").unwrap().all_descendants);
+    assert!(dependencies.attribute_changed("data-x").unwrap().later_siblings);
+    assert!(dependencies.attribute_changed("unknown").unwrap().is_empty());
+
+    // Selectors that test the class attribute by name count for any change of class.
+    assert!(dependencies.class_changed("", "unknown").unwrap().element);
+
+    // `:invalid` can change with any attribute.
+    let dependencies = dependencies_of(["input:invalid"]);
+    assert!(dependencies.attribute_changed("unknown").unwrap().element);
+}
+
+#[test]
+fn test_unknown_selector() {
+    let dependencies = dependencies_of([".a", "a:unknown-pseudo-class"]);
+    assert!(dependencies.class_changed("", "a").is_none());
+    assert!(dependencies.id_changed("", "a").is_none());
+    assert!(dependencies.attribute_changed("title").is_none());
+}
+
+#[test]
+fn test_depend_on_sibling_positions() {
+    assert!(!dependencies_of([".a", "div > p", "ul li"]).depend_on_sibling_positions());
+    assert!(dependencies_of([".a", "li:first-child"]).depend_on_sibling_positions());
+    assert!(dependencies_of(["p:not(:nth-child(2n))"]).depend_on_sibling_positions());
+    assert!(dependencies_of(["h1 + p"]).depend_on_sibling_positions());
+    assert!(dependencies_of(["a:unknown-pseudo-class"]).depend_on_sibling_positions());
+}
@@ -5,7 +5,14 @@
 pub mod stylesheets;
 pub mod errors;
 pub mod selectors;
+pub mod selector_matching;
+pub mod invalidation;
+pub mod cascade;
+pub mod rule_tree;
 pub mod properties;
 pub mod namespaces;
 pub mod media_queries;
+pub mod supports;
+pub mod counter_style;
+pub mod generated_content;
 pub mod parsing_utils;
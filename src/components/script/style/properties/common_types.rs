@@ -12,17 +12,17 @@ pub mod specified {
     use super::{Integer, Float};
     pub use CSSColor = cssparser::Color;
 
+    #[deriving(Eq, Clone)]
     pub enum Length {
         Au(Integer),  // application units
         Em(Float),
         Ex(Float),
-        // XXX uncomment when supported:
-//        Ch(Float),
-//        Rem(Float),
-//        Vw(Float),
-//        Vh(Float),
-//        Vmin(Float),
-//        Vmax(Float),
+        Ch(Float),
+        Rem(Float),
+        Vw(Float),
+        Vh(Float),
+        Vmin(Float),
+        Vmax(Float),
     }
     static AU_PER_PX: Float = 60.;
     static AU_PER_IN: Float = AU_PER_PX * 96.;
@@ -56,6 +56,12 @@ pub mod specified {
                 "pc" => Some(Au((value * AU_PER_PC) as Integer)),
                 "em" => Some(Em(value)),
                 "ex" => Some(Ex(value)),
+                "ch" => Some(Ch(value)),
+                "rem" => Some(Rem(value)),
+                "vw" => Some(Vw(value)),
+                "vh" => Some(Vh(value)),
+                "vmin" => Some(Vmin(value)),
+                "vmax" => Some(Vmax(value)),
                 _ => None
             }
         }
@@ -65,6 +71,7 @@ pub mod specified {
         }
     }
 
+    #[deriving(Eq, Clone)]
     pub enum LengthOrPercentage {
         LP_Length(Length),
         LP_Percentage(Float),
@@ -91,6 +98,7 @@ pub mod specified {
         }
     }
 
+    #[deriving(Eq, Clone)]
     pub enum LengthOrPercentageOrAuto {
         LPA_Length(Length),
         LPA_Percentage(Float),
@@ -123,19 +131,59 @@ pub mod specified {
 pub mod computed {
     use cssparser;
     pub use CSSColor = cssparser::Color;
-    pub use compute_CSSColor = std::util::id;
     use super::*;
     use super::super::longhands::font_weight;
     pub struct Context {
+        /// The computed `color` of the element, which `currentColor` is in other properties.
         current_color: cssparser::RGBA,
+        /// The computed `color` of the parent of the element, or the initial color for the root,
+        /// which `currentColor` is in `color` itself, as `inherit` is.
+        inherited_color: cssparser::RGBA,
         has_border_top: bool,
         has_border_right: bool,
         has_border_bottom: bool,
         has_border_left: bool,
+        has_outline: bool,
         font_size: Length,
         font_weight: font_weight::ComputedValue,
-        // TODO, as needed: root font size, viewport size, etc.
+        /// The computed `font-size` of the root element, which `rem` is relative to, or the
+        /// initial font size while the font size of the root element itself is computed. The
+        /// root is the ancestor of every element, so restyling it when its font size changes
+        /// restyles everything that depends on it.
+        root_font_size: Length,
+        /// The advance of "0" in the first available font of the element, which `ch` is relative
+        /// to, as `Font::zero_advance` measures it. Without one, `ch` is half an em, as CSS Values
+        /// allows when it can't be measured.
+        zero_advance: Option<Length>,
+        /// The size that viewport units are relative to: that of the layout viewport, or of the
+        /// visual viewport when servo runs with viewport units following pinch zoom (`-z`).
+        viewport_width: Length,
+        viewport_height: Length,
     }
+
+    /// Returns the computed value of a property whose specified values are already computed.
+    #[inline]
+    pub fn compute_as_specified<T>(value: T, _context: &Context) -> T {
+        value
+    }
+
+    /// `currentColor` is kept in computed colors, so they are computed as specified.
+    #[inline]
+    pub fn compute_CSSColor(value: CSSColor, _context: &Context) -> CSSColor {
+        value
+    }
+
+    /// Returns the color that a computed color is used as by an element whose computed `color`
+    /// is `current_color`. `currentColor` is kept as a keyword in computed values, so that an
+    /// element that inherits it uses its own `color`, not that of the element that declared it.
+    pub fn used_CSSColor(value: CSSColor, current_color: cssparser::RGBA) -> cssparser::RGBA {
+        match value {
+            cssparser::RGBA(rgba) => rgba,
+            cssparser::CurrentColor => current_color,
+        }
+    }
+
+    #[deriving(Eq, Clone)]
     pub struct Length(Integer);  // in application units
     impl Length {
         pub fn times(self, factor: Float) -> Length {
@@ -151,9 +199,35 @@ pub mod computed {
                 let x_height = 0.5;  // TODO: find that from the font
                 context.font_size.times(value * x_height)
             },
+            specified::Ch(value) => {
+                match context.zero_advance {
+                    Some(zero_advance) => zero_advance.times(value),
+                    None => context.font_size.times(value * 0.5),
+                }
+            }
+            specified::Rem(value) => context.root_font_size.times(value),
+            specified::Vw(value) => context.viewport_width.times(value / 100.),
+            specified::Vh(value) => context.viewport_height.times(value / 100.),
+            specified::Vmin(value) => {
+                let smaller = if *context.viewport_width < *context.viewport_height {
+                    context.viewport_width
+                } else {
+                    context.viewport_height
+                };
+                smaller.times(value / 100.)
+            }
+            specified::Vmax(value) => {
+                let larger = if *context.viewport_width > *context.viewport_height {
+                    context.viewport_width
+                } else {
+                    context.viewport_height
+                };
+                larger.times(value / 100.)
+            }
         }
     }
 
+    #[deriving(Eq, Clone)]
     pub enum LengthOrPercentage {
         LP_Length(Length),
         LP_Percentage(Float),
@@ -166,6 +240,7 @@ pub mod computed {
         }
     }
 
+    #[deriving(Eq, Clone)]
     pub enum LengthOrPercentageOrAuto {
         LPA_Length(Length),
         LPA_Percentage(Float),
@@ -180,3 +255,58 @@ pub mod computed {
         }
     }
 }
+
+
+#[cfg(test)]
+fn context_with_zero_advance(zero_advance: Option<computed::Length>) -> computed::Context {
+    use cssparser::RGBA;
+    use style::properties::longhands::font_weight;
+    let black = RGBA { red: 0., green: 0., blue: 0., alpha: 1. };
+    computed::Context {
+        current_color: black,
+        inherited_color: black,
+        has_border_top: false,
+        has_border_right: false,
+        has_border_bottom: false,
+        has_border_left: false,
+        has_outline: false,
+        font_size: computed::Length(20 * 60),
+        font_weight: font_weight::Weight400,
+        root_font_size: computed::Length(16 * 60),
+        zero_advance: zero_advance,
+        viewport_width: computed::Length(1000 * 60),
+        viewport_height: computed::Length(500 * 60),
+    }
+}
+
+#[cfg(test)]
+fn parse_length(css: &str, negative_ok: bool) -> Option<specified::Length> {
+    use cssparser::tokenize;
+    let (value, _) = tokenize(css).next().unwrap();
+    if negative_ok {
+        specified::Length::parse(&value)
+    } else {
+        specified::Length::parse_non_negative(&value)
+    }
+}
+
+#[test]
+fn test_parse_font_relative_units() {
+    assert!(parse_length("1.5rem", true) == Some(specified::Rem(1.5)));
+    assert!(parse_length("2CH", true) == Some(specified::Ch(2.)));
+    assert!(parse_length("-1rem", true) == Some(specified::Rem(-1.)));
+    assert!(parse_length("-1rem", false).is_none());
+}
+
+#[test]
+fn test_compute_font_relative_units() {
+    // `rem` is relative to the font size of the root, and `em` to that of the element.
+    let context = context_with_zero_advance(None);
+    assert!(computed::compute_Length(specified::Rem(2.), &context) == computed::Length(32 * 60));
+    assert!(computed::compute_Length(specified::Em(2.), &context) == computed::Length(40 * 60));
+
+    // `ch` is the advance of "0", or half an em when it isn't known.
+    assert!(computed::compute_Length(specified::Ch(3.), &context) == computed::Length(30 * 60));
+    let context = context_with_zero_advance(Some(computed::Length(12 * 60)));
+    assert!(computed::compute_Length(specified::Ch(3.), &context) == computed::Length(36 * 60));
+}
@@ -5,6 +5,8 @@
 // This file is a Mako template: http://www.makotemplates.org/
 
 use std::ascii::StrAsciiExt;
+use std::vec;
+use extra::arc::Arc;
 pub use std::iterator;
 pub use cssparser::*;
 pub use style::errors::{ErrorLoggerIterator, log_css_error};
@@ -71,7 +73,8 @@ pub mod longhands {
     <%def name="single_keyword(name, values, inherited=False)">
         <%self:single_component_value name="${name}" inherited="${inherited}">
             // The computed value is the same as the specified value.
-            pub use to_computed_value = std::util::id;
+            pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+            #[deriving(Eq, Clone)]
             pub enum SpecifiedValue {
                 % for value in values.split():
                     ${to_rust_ident(value)},
@@ -167,7 +170,7 @@ pub mod longhands {
 
     // TODO: don't parse values we don't support
     ${single_keyword("display",
-        "inline block list-item inline-block none "
+        "inline block list-item inline-block none contents "
     )}
 //        "table inline-table table-row-group table-header-group table-footer-group "
 //        "table-row table-column-group table-column table-cell table-caption"
@@ -176,6 +179,8 @@ pub mod longhands {
     ${single_keyword("float", "none left right")}
     ${single_keyword("clear", "none left right both")}
 
+    ${single_keyword("direction", "ltr rtl", inherited=True)}
+
     // CSS 2.1, Section 10 - Visual formatting model details
 
     ${predefined_type("width", "LengthOrPercentageOrAuto",
@@ -186,6 +191,7 @@ pub mod longhands {
                       "parse_non_negative")}
 
     <%self:single_component_value name="line-height">
+        #[deriving(Eq, Clone)]
         pub enum SpecifiedValue {
             SpecifiedNormal,
             SpecifiedLength(specified::Length),
@@ -207,6 +213,7 @@ pub mod longhands {
                 _ => None,
             }
         }
+        #[deriving(Eq, Clone)]
         pub enum ComputedValue {
             Normal,
             Length(computed::Length),
@@ -227,21 +234,155 @@ pub mod longhands {
 
     // CSS 2.1, Section 12 - Generated content, automatic numbering, and lists
 
+    <%self:longhand name="content">
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        #[deriving(Eq, Clone)]
+        pub enum ContentItem {
+            StringContent(~str),
+            OpenQuote,
+            CloseQuote,
+            NoOpenQuote,
+            NoCloseQuote,
+            /// The value of the named attribute of the element, or nothing if it has no such
+            /// attribute.
+            AttrContent(~str),
+            /// An image, with its URL not yet resolved against the style sheet.
+            UrlContent(~str),
+        }
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedValue {
+            normal,
+            none,
+            Content(~[ContentItem]),
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { normal }
+        /// normal | none | [ <string> | <uri> | attr(<identifier>) | open-quote | close-quote
+        ///                   | no-open-quote | no-close-quote ]+
+        /// TODO: <counter>
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            match one_component_value(input).chain(get_ident_lower) {
+                Some(ref keyword) if "normal" == *keyword => return Some(normal),
+                Some(ref keyword) if "none" == *keyword => return Some(none),
+                _ => ()
+            }
+            let mut content = ~[];
+            for component_value in input.skip_whitespace() {
+                match component_value {
+                    &String(ref value) => content.push(StringContent(value.to_owned())),
+                    &URL(ref url) => content.push(UrlContent(url.to_owned())),
+                    &Ident(ref keyword) => match keyword.to_ascii_lower().as_slice() {
+                        "open-quote" => content.push(OpenQuote),
+                        "close-quote" => content.push(CloseQuote),
+                        "no-open-quote" => content.push(NoOpenQuote),
+                        "no-close-quote" => content.push(NoCloseQuote),
+                        _ => return None,
+                    },
+                    &Function(ref name, ref arguments)
+                            if "attr" == name.to_ascii_lower() => {
+                        match one_component_value(*arguments) {
+                            // HTML attribute names are case-insensitive.
+                            Some(&Ident(ref attribute)) => {
+                                content.push(AttrContent(attribute.to_ascii_lower()))
+                            }
+                            _ => return None,
+                        }
+                    }
+                    _ => return None,
+                }
+            }
+            if content.is_empty() { None } else { Some(Content(content)) }
+        }
+    </%self:longhand>
+
+    <%self:longhand name="quotes" inherited="True">
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedValue {
+            /// Quotation marks appropriate for the language of the element (CSS Generated
+            /// Content Level 3).
+            auto,
+            none,
+            /// Pairs of open and close quotes, from the outermost level of nesting inwards.
+            Quotes(~[(~str, ~str)]),
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { auto }
+        /// auto | none | [ <string> <string> ]+
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            match one_component_value(input).chain(get_ident_lower) {
+                Some(ref keyword) if "auto" == *keyword => return Some(auto),
+                Some(ref keyword) if "none" == *keyword => return Some(none),
+                _ => ()
+            }
+            let mut quotes = ~[];
+            let mut iter = input.skip_whitespace();
+            loop {
+                match (iter.next(), iter.next()) {
+                    (Some(&String(ref open)), Some(&String(ref close))) => {
+                        quotes.push((open.to_owned(), close.to_owned()))
+                    }
+                    (None, _) => break,
+                    _ => return None,
+                }
+            }
+            if quotes.is_empty() { None } else { Some(Quotes(quotes)) }
+        }
+    </%self:longhand>
+
+    <%self:single_component_value name="list-style-type" inherited="True">
+        // The computed value is the same as the specified value.
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedValue {
+            none,
+            /// The name of a predefined counter style or of an @counter-style rule.
+            CounterStyle(~str),
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            CounterStyle(~"disc")
+        }
+        pub fn from_component_value(v: &ComponentValue) -> Option<SpecifiedValue> {
+            match v {
+                // Counter style names are case-sensitive, but 'none' is a keyword.
+                &Ident(ref value) => {
+                    if "none" == get_ident_lower(v).unwrap() { Some(none) }
+                    else { Some(CounterStyle(value.clone())) }
+                }
+                _ => None,
+            }
+        }
+    </%self:single_component_value>
+
     // CSS 2.1, Section 13 - Paged media
 
     // CSS 2.1, Section 14 - Colors and Backgrounds
 
     ${predefined_type("background-color", "CSSColor",
                       "RGBA(RGBA { red: 0., green: 0., blue: 0., alpha: 0. }) /* transparent */")}
-    ${predefined_type("color", "CSSColor",
-                      "RGBA(RGBA { red: 0., green: 0., blue: 0., alpha: 1. }) /* black */",
-                      inherited=True)}
+    <%self:longhand name="color" inherited="True">
+        pub type SpecifiedValue = specified::CSSColor;
+        /// Unlike in other properties, `currentColor` isn't kept in the computed value of `color`.
+        pub type ComputedValue = RGBA;
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            RGBA { red: 0., green: 0., blue: 0., alpha: 1. }  /* black */
+        }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            computed::used_CSSColor(value, context.inherited_color)
+        }
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            one_component_value(input).chain(specified::CSSColor::parse)
+        }
+    </%self:longhand>
 
     // CSS 2.1, Section 15 - Fonts
 
     <%self:longhand name="font-family" inherited="True">
-        pub use to_computed_value = std::util::id;
-        enum FontFamily {
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        #[deriving(Eq, Clone)]
+        pub enum FontFamily {
             FamilyName(~str),
             // Generic
 //            Serif,
@@ -313,9 +454,10 @@ pub mod longhands {
 
 
     ${single_keyword("font-style", "normal italic oblique", inherited=True)}
-    ${single_keyword("font-variant", "normal", inherited=True)}  // Add small-caps when supported
+    ${single_keyword("font-variant", "normal small-caps", inherited=True)}
 
     <%self:single_component_value name="font-weight" inherited="True">
+        #[deriving(Eq, Clone)]
         pub enum SpecifiedValue {
             Bolder,
             Lighther,
@@ -348,6 +490,7 @@ pub mod longhands {
                 _ => None
             }
         }
+        #[deriving(Eq, Clone)]
         pub enum ComputedValue {
             % for weight in range(100, 901, 100):
                 Weight${weight},
@@ -386,6 +529,62 @@ pub mod longhands {
         }
     </%self:single_component_value>
 
+    // CSS Fonts Level 3
+    ${single_keyword("font-stretch",
+                     "normal ultra-condensed extra-condensed condensed semi-condensed "
+                     "semi-expanded expanded extra-expanded ultra-expanded",
+                     inherited=True)}
+    ${single_keyword("font-kerning", "auto normal none", inherited=True)}
+
+    <%self:longhand name="font-feature-settings" inherited="True">
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedValue {
+            normal,
+            /// Feature tags and their values, in the order they were given.
+            FeatureSettings(~[(~str, u32)]),
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { normal }
+        /// normal | <feature-tag-value>#
+        /// <feature-tag-value> = <string> [ <integer> | on | off ]?
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            match one_component_value(input).chain(get_ident_lower) {
+                Some(ref keyword) if "normal" == *keyword => return Some(normal),
+                _ => ()
+            }
+            let mut settings = ~[];
+            let mut iter = input.skip_whitespace();
+            loop {
+                let tag = match iter.next() {
+                    // Tags are four printable ASCII characters.
+                    Some(&String(ref tag)) if tag.len() == 4 &&
+                            tag.iter().all(|ch| ch >= ' ' && ch <= '~') => tag.to_owned(),
+                    _ => return None,
+                };
+                let (value, next) = match iter.next() {
+                    Some(&Number(ref value)) => match value.int_value {
+                        Some(value) if value >= 0 => (value as u32, iter.next()),
+                        _ => return None,
+                    },
+                    Some(&Ident(ref value)) => match value.to_ascii_lower().as_slice() {
+                        "on" => (1, iter.next()),
+                        "off" => (0, iter.next()),
+                        _ => return None,
+                    },
+                    next => (1, next),
+                };
+                settings.push((tag, value));
+                match next {
+                    Some(&Comma) => (),
+                    None => break,
+                    _ => return None,
+                }
+            }
+            Some(FeatureSettings(settings))
+        }
+    </%self:longhand>
+
     <%self:single_component_value name="font-size" inherited="True">
         pub use to_computed_value = super::super::common_types::computed::compute_Length;
         pub type SpecifiedValue = specified::Length;  // Percentages are the same as em.
@@ -407,11 +606,50 @@ pub mod longhands {
 
     // CSS 2.1, Section 16 - Text
 
-    // TODO: initial value should be 'start' (CSS Text Level 3, direction-dependent.)
-    ${single_keyword("text-align", "left right center justify", inherited=True)}
+    // 'start' and 'end' are from CSS Text Level 3, and are resolved against 'direction'.
+    ${single_keyword("text-align", "start end left right center justify", inherited=True)}
+    ${single_keyword("text-transform", "none capitalize uppercase lowercase", inherited=True)}
+    ${single_keyword("white-space", "normal pre nowrap pre-wrap pre-line", inherited=True)}
+
+    // CSS Text Level 3
+    ${single_keyword("hyphens", "manual none auto", inherited=True)}
+    ${single_keyword("word-break", "normal break-all keep-all", inherited=True)}
+    ${single_keyword("overflow-wrap", "normal break-word", inherited=True)}
+
+    <%self:single_component_value name="tab-size" inherited="True">
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedValue {
+            /// A multiple of the advance of the space character.
+            SpecifiedSpaces(Integer),
+            SpecifiedLength(specified::Length),
+        }
+        #[deriving(Eq, Clone)]
+        pub enum ComputedValue {
+            Spaces(Integer),
+            Length(computed::Length),
+        }
+        #[inline] pub fn get_initial_value() -> ComputedValue { Spaces(8) }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            match value {
+                SpecifiedSpaces(value) => Spaces(value),
+                SpecifiedLength(value) => Length(computed::compute_Length(value, context)),
+            }
+        }
+        /// <integer> | <length>, both non-negative
+        pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+            match input {
+                &Number(ref value) if value.int_value.is_some() && value.value >= 0. => {
+                    Some(SpecifiedSpaces(value.int_value.unwrap()))
+                }
+                _ => specified::Length::parse_non_negative(input).map_move(SpecifiedLength),
+            }
+        }
+    </%self:single_component_value>
 
     <%self:longhand name="text-decoration">
-        pub use to_computed_value = std::util::id;
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        #[deriving(Eq, Clone)]
         pub struct SpecifiedValue {
             underline: bool,
             overline: bool,
@@ -451,9 +689,614 @@ pub mod longhands {
         }
     </%self:longhand>
 
+    // CSS Text Decoration Level 3
+    ${predefined_type("text-decoration-color", "CSSColor", "CurrentColor")}
+
+    <%self:longhand name="text-shadow" inherited="True">
+        #[deriving(Eq, Clone)]
+        pub struct SpecifiedShadow {
+            offset_x: specified::Length,
+            offset_y: specified::Length,
+            blur_radius: specified::Length,
+            /// The color of the text when `None`.
+            color: Option<specified::CSSColor>,
+        }
+        #[deriving(Eq, Clone)]
+        pub struct ComputedShadow {
+            offset_x: computed::Length,
+            offset_y: computed::Length,
+            blur_radius: computed::Length,
+            color: computed::CSSColor,
+        }
+        /// The shadows, from the one painted on top to the one painted at the bottom.
+        pub type SpecifiedValue = ~[SpecifiedShadow];
+        pub type ComputedValue = ~[ComputedShadow];
+        #[inline] pub fn get_initial_value() -> ComputedValue { ~[] }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            value.iter().map(|shadow| {
+                ComputedShadow {
+                    offset_x: computed::compute_Length(shadow.offset_x, context),
+                    offset_y: computed::compute_Length(shadow.offset_y, context),
+                    blur_radius: computed::compute_Length(shadow.blur_radius, context),
+                    color: match shadow.color {
+                        Some(color) => color,
+                        None => CurrentColor,
+                    },
+                }
+            }).collect()
+        }
+        /// none | [ <color>? && <length>{2,3} ]#
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            match one_component_value(input).chain(get_ident_lower) {
+                Some(ref keyword) if "none" == *keyword => return Some(~[]),
+                _ => ()
+            }
+            let mut shadows = ~[];
+            let mut iter = input.skip_whitespace();
+            loop {
+                let mut lengths = ~[];
+                let mut color = None;
+                let mut end_of_input = false;
+                loop {
+                    let component_value = match iter.next() {
+                        Some(&Comma) => break,
+                        None => { end_of_input = true; break }
+                        Some(component_value) => component_value,
+                    };
+                    // The lengths can't be split by the color, and the blur radius can't be
+                    // negative.
+                    let length = if color.is_some() && !lengths.is_empty() {
+                        None
+                    } else if lengths.len() < 2 {
+                        specified::Length::parse(component_value)
+                    } else if lengths.len() == 2 {
+                        specified::Length::parse_non_negative(component_value)
+                    } else {
+                        None
+                    };
+                    match length {
+                        Some(length) => lengths.push(length),
+                        None => match specified::CSSColor::parse(component_value) {
+                            Some(value) if color.is_none() => color = Some(value),
+                            _ => return None,
+                        },
+                    }
+                }
+                if lengths.len() < 2 {
+                    return None
+                }
+                shadows.push(SpecifiedShadow {
+                    offset_x: lengths[0],
+                    offset_y: lengths[1],
+                    blur_radius: if lengths.len() == 3 { lengths[2] } else { specified::Au(0) },
+                    color: color,
+                });
+                if end_of_input {
+                    return Some(shadows)
+                }
+            }
+        }
+    </%self:longhand>
+
     // CSS 2.1, Section 17 - Tables
 
+    // Tables are laid out as blocks, so layout only moves bottom captions after the rows and
+    // hides the background and borders of empty cells.
+    // TODO: `table-layout: fixed` has no effect until there is table layout.
+    ${single_keyword("table-layout", "auto fixed")}
+    ${single_keyword("caption-side", "top bottom", inherited=True)}
+    ${single_keyword("empty-cells", "show hide", inherited=True)}
+
     // CSS 2.1, Section 18 - User interface
+
+    // The initial color should be `invert`, which can't be painted yet.
+    ${predefined_type("outline-color", "CSSColor", "CurrentColor")}
+    ${single_keyword("outline-style", "none solid dotted dashed")}
+    <%self:longhand name="outline-width">
+        pub type SpecifiedValue = specified::Length;
+        pub type ComputedValue = computed::Length;
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            computed::Length(3 * 60)  // medium
+        }
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            one_component_value(input).chain(parse_border_width)
+        }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            if context.has_outline { computed::compute_Length(value, context) }
+            else { computed::Length(0) }
+        }
+    </%self:longhand>
+
+    // CSS Containment Level 1
+    <%self:longhand name="contain">
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        #[deriving(Eq, Clone)]
+        pub struct SpecifiedValue {
+            size: bool,
+            layout: bool,
+            paint: bool,
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            SpecifiedValue { size: false, layout: false, paint: false }
+        }
+        /// none | strict | content | [ size || layout || paint ]
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            let mut value = get_initial_value();
+            let mut keywords = 0;
+            for component_value in input.skip_whitespace() {
+                let keyword = match get_ident_lower(component_value) {
+                    Some(keyword) => keyword,
+                    None => return None,
+                };
+                keywords += 1;
+                match keyword.as_slice() {
+                    "none" if keywords == 1 => return one_component_value(input).map(|_| value),
+                    "strict" if keywords == 1 => {
+                        return one_component_value(input).map(|_| {
+                            SpecifiedValue { size: true, layout: true, paint: true }
+                        })
+                    }
+                    "content" if keywords == 1 => {
+                        return one_component_value(input).map(|_| {
+                            SpecifiedValue { size: false, layout: true, paint: true }
+                        })
+                    }
+                    "size" if !value.size => value.size = true,
+                    "layout" if !value.layout => value.layout = true,
+                    "paint" if !value.paint => value.paint = true,
+                    _ => return None,
+                }
+            }
+            if keywords == 0 { None } else { Some(value) }
+        }
+    </%self:longhand>
+
+    // CSS Containment Level 2
+    ${single_keyword("content-visibility", "visible auto hidden")}
+    <%self:longhand name="contain-intrinsic-size">
+        /// The width and height of the content box, or `None` for `none`.
+        pub type SpecifiedValue = Option<(specified::Length, specified::Length)>;
+        pub type ComputedValue = Option<(computed::Length, computed::Length)>;
+        #[inline] pub fn get_initial_value() -> ComputedValue { None }
+        /// none | <length>{1,2}
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            let mut lengths = ~[];
+            for component_value in input.skip_whitespace() {
+                match get_ident_lower(component_value) {
+                    Some(ref keyword) if "none" == *keyword && lengths.is_empty() => {
+                        return one_component_value(input).map(|_| None)
+                    }
+                    _ => {}
+                }
+                match specified::Length::parse_non_negative(component_value) {
+                    Some(length) if lengths.len() < 2 => lengths.push(length),
+                    _ => return None,
+                }
+            }
+            match lengths.len() {
+                1 => Some(Some((lengths[0], lengths[0]))),
+                2 => Some(Some((lengths[0], lengths[1]))),
+                _ => None,
+            }
+        }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            do value.map_move |(width, height)| {
+                (computed::compute_Length(width, context),
+                 computed::compute_Length(height, context))
+            }
+        }
+    </%self:longhand>
+
+    // CSS Masking Level 1
+    <%self:single_component_value name="mask-image">
+        // The computed value is the same as the specified value.
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        /// The URL of the image, not yet resolved against the style sheet, or `None` for `none`.
+        pub type SpecifiedValue = Option<~str>;
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { None }
+        pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+            match input {
+                &URL(ref url) => Some(Some(url.clone())),
+                _ => match get_ident_lower(input) {
+                    Some(ref keyword) if "none" == *keyword => Some(None),
+                    _ => None,
+                },
+            }
+        }
+    </%self:single_component_value>
+    ${single_keyword("mask-mode", "match-source alpha luminance")}
+
+    // Filter Effects Module Level 2
+    <%self:longhand name="backdrop-filter">
+        /// A filter function. Amounts are fractions, where 1 is 100%, and angles are in degrees.
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedFilter {
+            SpecifiedBlur(specified::Length),
+            SpecifiedBrightness(Float),
+            SpecifiedContrast(Float),
+            SpecifiedGrayscale(Float),
+            SpecifiedHueRotate(Float),
+            SpecifiedInvert(Float),
+            SpecifiedOpacity(Float),
+            SpecifiedSaturate(Float),
+            SpecifiedSepia(Float),
+        }
+        #[deriving(Eq, Clone)]
+        pub enum ComputedFilter {
+            Blur(computed::Length),
+            Brightness(Float),
+            Contrast(Float),
+            Grayscale(Float),
+            HueRotate(Float),
+            Invert(Float),
+            Opacity(Float),
+            Saturate(Float),
+            Sepia(Float),
+        }
+        /// The filters, in the order they are applied.
+        pub type SpecifiedValue = ~[SpecifiedFilter];
+        pub type ComputedValue = ~[ComputedFilter];
+        #[inline] pub fn get_initial_value() -> ComputedValue { ~[] }
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                              -> ComputedValue {
+            value.iter().map(|filter| {
+                match *filter {
+                    SpecifiedBlur(length) => Blur(computed::compute_Length(length, context)),
+                    SpecifiedBrightness(amount) => Brightness(amount),
+                    SpecifiedContrast(amount) => Contrast(amount),
+                    SpecifiedGrayscale(amount) => Grayscale(amount),
+                    SpecifiedHueRotate(angle) => HueRotate(angle),
+                    SpecifiedInvert(amount) => Invert(amount),
+                    SpecifiedOpacity(amount) => Opacity(amount),
+                    SpecifiedSaturate(amount) => Saturate(amount),
+                    SpecifiedSepia(amount) => Sepia(amount),
+                }
+            }).collect()
+        }
+        /// A non-negative <number> or <percentage>. Grayscale, invert, opacity and sepia are
+        /// clamped to 1 where they are applied.
+        fn parse_amount(input: &ComponentValue) -> Option<Float> {
+            match input {
+                &Number(ref value) if value.value >= 0. => Some(value.value),
+                &ast::Percentage(ref value) if value.value >= 0. => Some(value.value / 100.),
+                _ => None,
+            }
+        }
+        fn parse_angle(input: &ComponentValue) -> Option<Float> {
+            match input {
+                &Dimension(ref value, ref unit) => match unit.to_ascii_lower().as_slice() {
+                    "deg" => Some(value.value),
+                    "grad" => Some(value.value * 360. / 400.),
+                    "rad" => Some(value.value * 180. / std::f64::consts::pi),
+                    "turn" => Some(value.value * 360.),
+                    _ => None,
+                },
+                &Number(ref value) if value.value == 0. => Some(0.),
+                _ => None,
+            }
+        }
+        /// none | <filter-function>+
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            match one_component_value(input).chain(get_ident_lower) {
+                Some(ref keyword) if "none" == *keyword => return Some(~[]),
+                _ => ()
+            }
+            let mut filters = ~[];
+            for component_value in input.skip_whitespace() {
+                let (name, arguments) = match component_value {
+                    &Function(ref name, ref arguments) => (name.to_ascii_lower(), arguments),
+                    _ => return None,
+                };
+                // Each function takes one argument, which can be left out for its default.
+                let argument = one_component_value(*arguments);
+                if argument.is_none() && arguments.skip_whitespace().next().is_some() {
+                    return None
+                }
+                let amount = |default: Float| match argument {
+                    Some(argument) => parse_amount(argument),
+                    None => Some(default),
+                };
+                let filter = match name.as_slice() {
+                    "blur" => match argument {
+                        Some(argument) => {
+                            specified::Length::parse_non_negative(argument).map_move(SpecifiedBlur)
+                        }
+                        None => Some(SpecifiedBlur(specified::Au(0))),
+                    },
+                    "brightness" => amount(1.).map_move(SpecifiedBrightness),
+                    "contrast" => amount(1.).map_move(SpecifiedContrast),
+                    "grayscale" => amount(1.).map_move(SpecifiedGrayscale),
+                    "hue-rotate" => match argument {
+                        Some(argument) => parse_angle(argument).map_move(SpecifiedHueRotate),
+                        None => Some(SpecifiedHueRotate(0.)),
+                    },
+                    "invert" => amount(1.).map_move(SpecifiedInvert),
+                    "opacity" => amount(1.).map_move(SpecifiedOpacity),
+                    "saturate" => amount(1.).map_move(SpecifiedSaturate),
+                    "sepia" => amount(1.).map_move(SpecifiedSepia),
+                    _ => None,
+                };
+                match filter {
+                    Some(filter) => filters.push(filter),
+                    None => return None,
+                }
+            }
+            if filters.is_empty() { None } else { Some(filters) }
+        }
+    </%self:longhand>
+
+    // CSS Transitions
+    // TODO: each of these takes a comma-separated list, whose items pair up with those of the
+    // others.
+    <%self:single_component_value name="transition-property">
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        #[deriving(Eq, Clone)]
+        pub enum SpecifiedValue {
+            TransitionNone,
+            TransitionAll,
+            /// The lowercase name of a property.
+            TransitionProperty(~str),
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { TransitionAll }
+        /// none | all | <custom-ident>
+        pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+            do get_ident_lower(input).map_move |keyword| {
+                match keyword.as_slice() {
+                    "none" => TransitionNone,
+                    "all" => TransitionAll,
+                    _ => TransitionProperty(keyword.clone()),
+                }
+            }
+        }
+    </%self:single_component_value>
+
+    <%def name="transition_time(name, allow_negative)">
+        <%self:single_component_value name="${name}">
+            pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+            /// In seconds.
+            pub type SpecifiedValue = Float;
+            pub type ComputedValue = SpecifiedValue;
+            #[inline] pub fn get_initial_value() -> ComputedValue { 0. }
+            /// <time>
+            pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+                match input {
+                    &Dimension(ref value, ref unit)
+                            if ${"true" if allow_negative else "value.value >= 0."} => {
+                        match unit.to_ascii_lower().as_slice() {
+                            "s" => Some(value.value),
+                            "ms" => Some(value.value / 1000.),
+                            _ => None,
+                        }
+                    }
+                    &Number(ref value) if value.value == 0. => Some(0.),
+                    _ => None,
+                }
+            }
+        </%self:single_component_value>
+    </%def>
+
+    // Negative durations are invalid, while negative delays start transitions part way through.
+    ${transition_time("transition-duration", allow_negative=False)}
+    ${transition_time("transition-delay", allow_negative=True)}
+
+    <%self:single_component_value name="transition-timing-function">
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        /// The control points of a cubic Bezier curve from (0, 0) to (1, 1).
+        #[deriving(Eq, Clone)]
+        pub struct SpecifiedValue {
+            x1: Float,
+            y1: Float,
+            x2: Float,
+            y2: Float,
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { cubic_bezier(0.25, 0.1, 0.25, 1.) }
+        fn cubic_bezier(x1: Float, y1: Float, x2: Float, y2: Float) -> SpecifiedValue {
+            SpecifiedValue { x1: x1, y1: y1, x2: x2, y2: y2 }
+        }
+        /// linear | ease | ease-in | ease-out | ease-in-out |
+        /// cubic-bezier(<number>, <number>, <number>, <number>)
+        pub fn from_component_value(input: &ComponentValue) -> Option<SpecifiedValue> {
+            match input {
+                &Function(ref name, ref arguments) if "cubic-bezier" == name.to_ascii_lower() => {
+                    let mut numbers = ~[];
+                    let mut iter = arguments.skip_whitespace();
+                    loop {
+                        match iter.next() {
+                            Some(&Number(ref value)) => numbers.push(value.value),
+                            _ => return None,
+                        }
+                        match iter.next() {
+                            Some(&Comma) if numbers.len() < 4 => (),
+                            None if numbers.len() == 4 => break,
+                            _ => return None,
+                        }
+                    }
+                    // The curve has to be a function of time.
+                    if numbers[0] < 0. || numbers[0] > 1. || numbers[2] < 0. || numbers[2] > 1. {
+                        return None
+                    }
+                    Some(cubic_bezier(numbers[0], numbers[1], numbers[2], numbers[3]))
+                }
+                _ => do get_ident_lower(input).chain |keyword| {
+                    match keyword.as_slice() {
+                        "linear" => Some(cubic_bezier(0., 0., 1., 1.)),
+                        "ease" => Some(get_initial_value()),
+                        "ease-in" => Some(cubic_bezier(0.42, 0., 1., 1.)),
+                        "ease-out" => Some(cubic_bezier(0., 0., 0.58, 1.)),
+                        "ease-in-out" => Some(cubic_bezier(0.42, 0., 0.58, 1.)),
+                        _ => None,
+                    }
+                },
+            }
+        }
+    </%self:single_component_value>
+
+    // CSS Scrollbars Styling Module Level 1
+    ${single_keyword("scrollbar-width", "auto thin none")}
+    <%self:longhand name="scrollbar-color" inherited="True">
+        // The computed value is the same as the specified value.
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        /// The colors of the thumb and of the track, or `None` for `auto`.
+        pub type SpecifiedValue = Option<(specified::CSSColor, specified::CSSColor)>;
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { None }
+        /// auto | <color>{2}
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            match one_component_value(input).chain(get_ident_lower) {
+                Some(ref keyword) if "auto" == *keyword => return Some(None),
+                _ => ()
+            }
+            let mut iter = input.skip_whitespace();
+            let thumb = iter.next().chain(specified::CSSColor::parse);
+            let track = iter.next().chain(specified::CSSColor::parse);
+            match (thumb, track, iter.next()) {
+                (Some(thumb), Some(track), None) => Some(Some((thumb, track))),
+                _ => None,
+            }
+        }
+    </%self:longhand>
+
+    // CSS Overscroll Behavior Module Level 1
+    ${single_keyword("overscroll-behavior-x", "auto contain none")}
+    ${single_keyword("overscroll-behavior-y", "auto contain none")}
+
+    // CSSOM View Module
+    ${single_keyword("scroll-behavior", "auto smooth")}
+
+    // CSS Scroll Snap Module Level 1
+    <%self:longhand name="scroll-snap-type">
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        /// The block axis is vertical and the inline axis horizontal, since there are only
+        /// horizontal writing modes.
+        #[deriving(Eq, Clone)]
+        pub struct SpecifiedValue {
+            x: bool,
+            y: bool,
+            mandatory: bool,
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            SpecifiedValue { x: false, y: false, mandatory: false }
+        }
+        /// none | [ x | y | block | inline | both ] [ mandatory | proximity ]?
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            let mut iter = input.skip_whitespace();
+            let (x, y) = match iter.next().chain(get_ident_lower) {
+                Some(ref keyword) => match keyword.as_slice() {
+                    "none" => return one_component_value(input).map(|_| get_initial_value()),
+                    "x" | "inline" => (true, false),
+                    "y" | "block" => (false, true),
+                    "both" => (true, true),
+                    _ => return None,
+                },
+                None => return None,
+            };
+            let mandatory = match iter.next() {
+                None => false,
+                Some(component_value) => match get_ident_lower(component_value) {
+                    Some(ref keyword) if "mandatory" == *keyword => true,
+                    Some(ref keyword) if "proximity" == *keyword => false,
+                    _ => return None,
+                },
+            };
+            if iter.next().is_some() {
+                return None
+            }
+            Some(SpecifiedValue { x: x, y: y, mandatory: mandatory })
+        }
+    </%self:longhand>
+    <%self:longhand name="scroll-snap-align">
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        #[deriving(Eq, Clone)]
+        pub enum Alignment {
+            none,
+            start,
+            end,
+            center,
+        }
+        /// The alignments in the block and inline axes.
+        pub type SpecifiedValue = (Alignment, Alignment);
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue { (none, none) }
+        fn parse_alignment(input: &ComponentValue) -> Option<Alignment> {
+            match get_ident_lower(input) {
+                Some(ref keyword) => match keyword.as_slice() {
+                    "none" => Some(none),
+                    "start" => Some(start),
+                    "end" => Some(end),
+                    "center" => Some(center),
+                    _ => None,
+                },
+                None => None,
+            }
+        }
+        /// [ none | start | end | center ]{1,2}
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            let mut iter = input.skip_whitespace();
+            let block = match iter.next().chain(parse_alignment) {
+                Some(alignment) => alignment,
+                None => return None,
+            };
+            let inline = match iter.next() {
+                None => block,
+                Some(component_value) => match parse_alignment(component_value) {
+                    Some(alignment) => alignment,
+                    None => return None,
+                },
+            };
+            if iter.next().is_some() {
+                return None
+            }
+            Some((block, inline))
+        }
+    </%self:longhand>
+
+    // Pointer Events Level 2
+    <%self:longhand name="touch-action">
+        pub use to_computed_value = super::super::common_types::computed::compute_as_specified;
+        /// The gestures that the browser handles when a touch starts on the element, rather than
+        /// leaving them to the page.
+        #[deriving(Eq, Clone)]
+        pub struct SpecifiedValue {
+            pan_x: bool,
+            pan_y: bool,
+            pinch_zoom: bool,
+        }
+        pub type ComputedValue = SpecifiedValue;
+        #[inline] pub fn get_initial_value() -> ComputedValue {
+            SpecifiedValue { pan_x: true, pan_y: true, pinch_zoom: true }
+        }
+        /// auto | none | [ pan-x || pan-y || pinch-zoom ] | manipulation
+        pub fn parse(input: &[ComponentValue]) -> Option<SpecifiedValue> {
+            let mut value = SpecifiedValue { pan_x: false, pan_y: false, pinch_zoom: false };
+            let mut keywords = 0;
+            for component_value in input.skip_whitespace() {
+                let keyword = match get_ident_lower(component_value) {
+                    Some(keyword) => keyword,
+                    None => return None,
+                };
+                keywords += 1;
+                match keyword.as_slice() {
+                    "auto" | "manipulation" if keywords == 1 => {
+                        return one_component_value(input).map(|_| get_initial_value())
+                    }
+                    "none" if keywords == 1 => return one_component_value(input).map(|_| value),
+                    "pan-x" if !value.pan_x => value.pan_x = true,
+                    "pan-y" if !value.pan_y => value.pan_y = true,
+                    "pinch-zoom" if !value.pinch_zoom => value.pinch_zoom = true,
+                    _ => return None,
+                }
+            }
+            if keywords == 0 { None } else { Some(value) }
+        }
+    </%self:longhand>
 }
 
 
@@ -523,6 +1366,15 @@ pub mod shorthands {
                      -> Option<(Option<specified::CSSColor>,
                                 Option<border_top_style::SpecifiedValue>,
                                 Option<specified::Length>)> {
+        parse_color_style_width(input, border_top_style::from_component_value)
+    }
+
+    /// Parses the color, style, and width of a border or outline, in any order.
+    pub fn parse_color_style_width<Style>(input: &[ComponentValue],
+                                          parse_style: &fn(&ComponentValue) -> Option<Style>)
+                                          -> Option<(Option<specified::CSSColor>,
+                                                     Option<Style>,
+                                                     Option<specified::Length>)> {
         let mut color = None;
         let mut style = None;
         let mut width = None;
@@ -535,7 +1387,7 @@ pub mod shorthands {
                 }
             }
             if style.is_none() {
-                match border_top_style::from_component_value(component_value) {
+                match parse_style(component_value) {
                     Some(s) => { style = Some(s); any = true; loop },
                     None => ()
                 }
@@ -583,12 +1435,44 @@ pub mod shorthands {
         }
     </%self:shorthand>
 
+    <%self:shorthand name="outline" sub_properties="outline-color outline-style outline-width">
+        let result = parse_color_style_width(input, outline_style::from_component_value);
+        do result.map_move |(color, style, width)| {
+            Longhands {
+                outline_color: color,
+                outline_style: style,
+                outline_width: width,
+            }
+        }
+    </%self:shorthand>
+
+    <%self:shorthand name="overscroll-behavior"
+                     sub_properties="overscroll-behavior-x overscroll-behavior-y">
+        // One value sets both axes.
+        let values: ~[&ComponentValue] = input.skip_whitespace().collect();
+        let (x, y) = match values.len() {
+            1 => (values[0], values[0]),
+            2 => (values[0], values[1]),
+            _ => return None,
+        };
+        match (overscroll_behavior_x::from_component_value(x),
+               overscroll_behavior_y::from_component_value(y)) {
+            (Some(x), Some(y)) => Some(Longhands {
+                overscroll_behavior_x: Some(x),
+                overscroll_behavior_y: Some(y),
+            }),
+            _ => None,
+        }
+    </%self:shorthand>
+
 }
 
 
+/// The declarations of a rule or a `style` attribute. They are shared with the nodes of the rule
+/// tree that stand for them.
 pub struct PropertyDeclarationBlock {
-    important: ~[PropertyDeclaration],
-    normal: ~[PropertyDeclaration],
+    important: Arc<~[PropertyDeclaration]>,
+    normal: Arc<~[PropertyDeclaration]>,
 }
 
 
@@ -607,7 +1491,12 @@ pub fn parse_property_declaration_list(input: ~[Node]) -> PropertyDeclarationBlo
             }
         }
     }
-    PropertyDeclarationBlock { important: important, normal: normal }
+    PropertyDeclarationBlock { important: Arc::new(important), normal: Arc::new(normal) }
+}
+
+/// Parses the declarations of a `style` attribute.
+pub fn parse_style_attribute(input: &str) -> PropertyDeclarationBlock {
+    parse_property_declaration_list(tokenize(input).collect())
 }
 
 
@@ -685,3 +1574,305 @@ impl PropertyDeclaration {
         true
     }
 }
+
+
+/// The number of longhands, which are numbered in the order that they are declared here.
+pub static LONGHAND_COUNT: uint = ${len(LONGHANDS)};
+
+impl PropertyDeclaration {
+    /// Returns the number of the longhand that the declaration sets.
+    pub fn longhand_index(&self) -> uint {
+        match *self {
+            % for i, property in enumerate(LONGHANDS):
+                ${property.ident}_declaration(*) => ${i},
+            % endfor
+        }
+    }
+}
+
+/// Returns whether the longhand with the given number is inherited.
+pub fn is_inherited_longhand(index: uint) -> bool {
+    static INHERITED_LONGHANDS: [bool, ..${len(LONGHANDS)}] = [
+        % for property in LONGHANDS:
+            ${"true" if property.name in INHERITED else "false"},
+        % endfor
+    ];
+    INHERITED_LONGHANDS[index]
+}
+
+
+/// The computed value of every longhand of an element.
+#[deriving(Eq, Clone)]
+pub struct ComputedValues {
+    % for property in LONGHANDS:
+        ${property.ident}: longhands::${property.ident}::ComputedValue,
+    % endfor
+}
+
+impl ComputedValues {
+    /// Returns the initial value of every longhand, which is what the root element inherits.
+    pub fn initial() -> ComputedValues {
+        ComputedValues {
+            % for property in LONGHANDS:
+                ${property.ident}: longhands::${property.ident}::get_initial_value(),
+            % endfor
+        }
+    }
+}
+
+impl ComputedValues {
+    /// Returns the values of the font longhands, which select the fonts of the element.
+    pub fn font_values<'a>(&'a self) -> FontValues<'a> {
+        FontValues {
+            family: &self.font_family,
+            size: self.font_size,
+            style: self.font_style,
+            weight: self.font_weight,
+            stretch: self.font_stretch,
+        }
+    }
+}
+
+/// The computed values of the font longhands of an element, which select its fonts.
+pub struct FontValues<'self> {
+    family: &'self longhands::font_family::ComputedValue,
+    size: computed::Length,
+    style: longhands::font_style::ComputedValue,
+    weight: longhands::font_weight::ComputedValue,
+    stretch: longhands::font_stretch::ComputedValue,
+}
+
+/// What the values of an element are computed against, besides the values of its parent.
+pub struct CascadeEnvironment<'self> {
+    /// The computed `font-size` of the root element, or `None` while the root itself is styled.
+    root_font_size: Option<computed::Length>,
+    /// The size that viewport units are relative to.
+    viewport_width: computed::Length,
+    viewport_height: computed::Length,
+    /// Measures the advance of "0" in the first available font that the given font values select,
+    /// which `ch` is relative to, or returns `None` if it can't.
+    measure_zero_advance: &'self fn(&FontValues) -> Option<computed::Length>,
+}
+
+<%def name="compute_value(property, index, against)">
+    match declared[${index}] {
+        Some(&${property.ident}_declaration(SpecifiedValue(ref value))) => {
+            longhands::${property.ident}::to_computed_value(value.clone(), ${against})
+        }
+        Some(&${property.ident}_declaration(CSSWideKeyword(Initial))) => {
+            longhands::${property.ident}::get_initial_value()
+        }
+        Some(&${property.ident}_declaration(CSSWideKeyword(Inherit))) => {
+            parent.${property.ident}.clone()
+        }
+        // `unset`, or no declaration at all.
+        % if property.name in INHERITED:
+            _ => parent.${property.ident}.clone(),
+        % else:
+            _ => longhands::${property.ident}::get_initial_value(),
+        % endif
+    }
+</%def>
+
+/// Computes the values of an element from the declarations that apply to it, in the order in
+/// which they cascade, as the nodes of the rule tree give them, and from the values of its parent,
+/// or `None` for the root element. The last declaration of each longhand wins. A longhand that
+/// isn't declared, or is declared `unset`, inherits if it is inherited, and takes its initial
+/// value otherwise.
+///
+/// `font-size` and `color` are computed first, against the values of the parent, since the
+/// lengths and colors of the other longhands are relative to them, along with the other font
+/// longhands, which select the font that `ch` is measured in.
+pub fn cascade(declarations: &[&PropertyDeclaration],
+               parent: Option< &ComputedValues>,
+               environment: &CascadeEnvironment)
+               -> ComputedValues {
+    let initial_values;
+    let parent = match parent {
+        Some(parent) => parent,
+        None => {
+            initial_values = ComputedValues::initial();
+            &initial_values
+        }
+    };
+
+    let mut declared: ~[Option< &PropertyDeclaration>] = vec::from_elem(LONGHAND_COUNT, None);
+    for &declaration in declarations.iter() {
+        declared[declaration.longhand_index()] = Some(declaration);
+    }
+
+    <% font_size_index = [longhand.name for longhand in LONGHANDS].index("font-size") %>
+    let parent_context = computed::Context {
+        current_color: parent.color,
+        inherited_color: parent.color,
+        has_border_top: false,
+        has_border_right: false,
+        has_border_bottom: false,
+        has_border_left: false,
+        has_outline: false,
+        font_size: parent.font_size,
+        font_weight: parent.font_weight,
+        root_font_size: match environment.root_font_size {
+            Some(root_font_size) => root_font_size,
+            None => longhands::font_size::get_initial_value(),
+        },
+        // Only `ch` in `font-size` is relative to the font of the parent.
+        zero_advance: match declared[${font_size_index}] {
+            Some(&font_size_declaration(SpecifiedValue(specified::Ch(_)))) => {
+                (environment.measure_zero_advance)(&parent.font_values())
+            }
+            _ => None,
+        },
+        viewport_width: environment.viewport_width,
+        viewport_height: environment.viewport_height,
+    };
+    <%
+        early = ["font-size", "font-family", "font-style", "font-weight", "font-stretch", "color",
+                 "border-top-style", "border-right-style", "border-bottom-style",
+                 "border-left-style", "outline-style"]
+    %>
+    % for index, property in enumerate(LONGHANDS):
+        % if property.name in early:
+            let ${property.ident} = ${compute_value(property, index, "&parent_context")};
+        % endif
+    % endfor
+
+    let zero_advance = (environment.measure_zero_advance)(&FontValues {
+        family: &font_family,
+        size: font_size,
+        style: font_style,
+        weight: font_weight,
+        stretch: font_stretch,
+    });
+    let context = computed::Context {
+        current_color: color,
+        inherited_color: parent.color,
+        % for side in ["top", "right", "bottom", "left"]:
+            has_border_${side}: match border_${side}_style {
+                longhands::border_top_style::none => false,
+                _ => true,
+            },
+        % endfor
+        has_outline: match outline_style {
+            longhands::outline_style::none => false,
+            _ => true,
+        },
+        font_size: font_size,
+        font_weight: parent.font_weight,
+        root_font_size: match environment.root_font_size {
+            Some(root_font_size) => root_font_size,
+            None => font_size,
+        },
+        zero_advance: zero_advance,
+        viewport_width: environment.viewport_width,
+        viewport_height: environment.viewport_height,
+    };
+
+    ComputedValues {
+        % for index, property in enumerate(LONGHANDS):
+            % if property.name in early:
+                ${property.ident}: ${property.ident},
+            % else:
+                ${property.ident}: ${compute_value(property, index, "&context")},
+            % endif
+        % endfor
+    }
+}
+
+#[cfg(test)]
+fn cascade_style_attribute(css: &str, parent: Option< &ComputedValues>) -> ComputedValues {
+    let block = parse_style_attribute(css);
+    let declarations: ~[&PropertyDeclaration] = block.normal.get().iter().collect();
+    // "0" is measured as 0.6em wide.
+    let measure_zero_advance: &fn(&FontValues) -> Option<computed::Length> = |font| {
+        Some(font.size.times(0.6))
+    };
+    let environment = CascadeEnvironment {
+        root_font_size: parent.map(|parent| parent.font_size),
+        viewport_width: computed::Length(1000 * 60),
+        viewport_height: computed::Length(500 * 60),
+        measure_zero_advance: measure_zero_advance,
+    };
+    cascade(declarations, parent, &environment)
+}
+
+#[test]
+fn test_cascade() {
+    let css = "font-size: 20px; margin-top: 2em; tab-size: 4; tab-size: 2";
+    let root = cascade_style_attribute(css, None);
+    assert!(root.font_size == computed::Length(20 * 60));
+    assert!(root.margin_top == computed::LPA_Length(computed::Length(40 * 60)));
+    // The last declaration wins.
+    assert!(root.tab_size == longhands::tab_size::Spaces(2));
+
+    // Inherited longhands inherit unless they are declared, others take their initial values.
+    let child = cascade_style_attribute("", Some(&root));
+    assert!(child.font_size == root.font_size && child.tab_size == root.tab_size);
+    assert!(child.margin_top == computed::LPA_Length(computed::Length(0)));
+    let child = cascade_style_attribute("margin-top: inherit; tab-size: initial", Some(&root));
+    assert!(child.margin_top == root.margin_top);
+    assert!(child.tab_size == longhands::tab_size::get_initial_value());
+    let child = cascade_style_attribute("tab-size: unset; margin-top: unset", Some(&root));
+    assert!(child.tab_size == root.tab_size);
+    assert!(child.margin_top == computed::LPA_Length(computed::Length(0)));
+}
+
+#[test]
+fn test_cascade_relative_lengths() {
+    let root = cascade_style_attribute("font-size: 20px", None);
+
+    // The font size is relative to that of the parent, other lengths to that of the element.
+    let child = cascade_style_attribute("font-size: 2em; margin-top: 1em; margin-left: 1rem",
+                                        Some(&root));
+    assert!(child.font_size == computed::Length(40 * 60));
+    assert!(child.margin_top == computed::LPA_Length(computed::Length(40 * 60)));
+    assert!(child.margin_left == computed::LPA_Length(computed::Length(20 * 60)));
+
+    // `rem` in the font size of the root is relative to the initial font size.
+    let root = cascade_style_attribute("font-size: 2rem", None);
+    assert!(root.font_size == computed::Length(32 * 60));
+
+    let child = cascade_style_attribute("margin-top: 10vw; margin-left: 10vmin", Some(&root));
+    assert!(child.margin_top == computed::LPA_Length(computed::Length(100 * 60)));
+    assert!(child.margin_left == computed::LPA_Length(computed::Length(50 * 60)));
+
+    // `ch` is the measured advance of "0" in the font of the element, or of the parent in the
+    // font size.
+    let child = cascade_style_attribute("font-size: 2ch; margin-top: 10ch", Some(&root));
+    assert!(child.font_size == computed::Length(32 * 60).times(0.6).times(2.));
+    assert!(child.margin_top == computed::LPA_Length(child.font_size.times(0.6).times(10.)));
+}
+
+#[test]
+fn test_cascade_borders_and_colors() {
+    // Borders and outlines without a style have no width.
+    let css = "border-top-width: 5px; border-left: 5px solid; outline-width: 2px";
+    let values = cascade_style_attribute(css, None);
+    assert!(values.border_top_width == computed::Length(0));
+    assert!(values.border_left_width == computed::Length(5 * 60));
+    assert!(values.outline_width == computed::Length(0));
+
+    // `currentColor` in `color` is the color of the parent, and is kept in other properties.
+    let red = RGBA { red: 1., green: 0., blue: 0., alpha: 1. };
+    let root = cascade_style_attribute("color: red; border-top-color: currentColor", None);
+    assert!(root.color == red && root.border_top_color == CurrentColor);
+    let child = cascade_style_attribute("color: currentColor", Some(&root));
+    assert!(child.color == red);
+}
+
+#[test]
+fn test_inherited_current_color() {
+    let red = RGBA { red: 1., green: 0., blue: 0., alpha: 1. };
+    let blue = RGBA { red: 0., green: 0., blue: 1., alpha: 1. };
+    let root = cascade_style_attribute("color: red; scrollbar-color: currentColor blue", None);
+    assert!(root.scrollbar_color == Some((CurrentColor, RGBA(blue))));
+
+    // An element that inherits `currentColor` uses its own color, not that of the element that
+    // declared it.
+    let child = cascade_style_attribute("color: blue", Some(&root));
+    let (thumb, track) = child.scrollbar_color.unwrap();
+    assert!(thumb == CurrentColor);
+    assert!(computed::used_CSSColor(thumb, child.color) == blue);
+    assert!(computed::used_CSSColor(track, child.color) == blue);
+    assert!(computed::used_CSSColor(thumb, root.color) == red);
+}
@@ -0,0 +1,267 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The rule tree: each of its nodes adds the declarations of a block that applies to elements to
+//! those of its ancestors, in the order in which they cascade, so that all the elements that the
+//! same rules match share one node instead of each keeping a list of declarations. Each node
+//! also knows which longhands the declarations on its path set, so that the inherited values of
+//! the elements that set none can be copied from their parents rather than cascaded again, and
+//! keeps the values last computed from its declarations, which the next element that the same
+//! rules match shares if it has the same parent values.
+
+use std::ptr;
+use extra::arc::Arc;
+use extra::bitv::Bitv;
+use style::properties::{ComputedValues, PropertyDeclaration, LONGHAND_COUNT};
+use style::properties::is_inherited_longhand;
+
+
+/// A node of a rule tree, which stands for the declarations on the path to it from the root.
+#[deriving(Eq, Clone)]
+pub struct RuleNodeId(uint);
+
+struct RuleNode {
+    parent: Option<RuleNodeId>,
+    /// The level of the cascade that the declarations of the node are at, as `cascade_level`
+    /// returns.
+    level: uint,
+    /// The declarations that the node adds to those of its parent, or `None` at the root.
+    declarations: Option<Arc<~[PropertyDeclaration]>>,
+    children: ~[RuleNodeId],
+    /// The longhands that the declarations on the path to the node set.
+    declared: Bitv,
+    /// Whether one of those longhands is inherited.
+    declares_inherited: bool,
+    /// The values last computed from the declarations of the node.
+    values: Option<CachedValues>,
+}
+
+/// Values computed from the declarations of a rule node, and the values of the parent they were
+/// computed from, or `None` for the root element. The parent values are kept so that they can be
+/// told apart by identity: no other values can be allocated where they are.
+struct CachedValues {
+    parent: Option<Arc<ComputedValues>>,
+    values: Arc<ComputedValues>,
+}
+
+/// The nodes of a rule tree, the root first. Nodes are never removed: a tree is made again when
+/// the style sheets or what values are computed against change, since every element is restyled
+/// then anyway.
+pub struct RuleTree {
+    priv nodes: ~[RuleNode],
+}
+
+impl RuleTree {
+    pub fn new() -> RuleTree {
+        RuleTree {
+            nodes: ~[RuleNode {
+                parent: None,
+                level: 0,
+                declarations: None,
+                children: ~[],
+                declared: Bitv::new(LONGHAND_COUNT, false),
+                declares_inherited: false,
+                values: None,
+            }],
+        }
+    }
+
+    /// Returns the root, which stands for no declarations.
+    pub fn root(&self) -> RuleNodeId {
+        RuleNodeId(0)
+    }
+
+    /// Returns the number of nodes in the tree.
+    pub fn len(&self) -> uint {
+        self.nodes.len()
+    }
+
+    /// Returns the node that stands for the declarations of `blocks`, which are at the given
+    /// levels of the cascade and in the order in which they cascade, adding the nodes on its path
+    /// that aren't in the tree yet.
+    pub fn insert(&mut self, blocks: &[(uint, &Arc<~[PropertyDeclaration]>)]) -> RuleNodeId {
+        let mut node = self.root();
+        for &(level, declarations) in blocks.iter() {
+            node = self.child(node, level, declarations);
+        }
+        node
+    }
+
+    /// Returns the child of `parent` that adds `declarations` at `level`, adding it if there
+    /// isn't one yet. Blocks are told apart by identity: the same rule gives the same child.
+    fn child(&mut self,
+             parent: RuleNodeId,
+             level: uint,
+             declarations: &Arc<~[PropertyDeclaration]>)
+             -> RuleNodeId {
+        let key = ptr::to_unsafe_ptr(declarations.get());
+        for &child in self.nodes[*parent].children.iter() {
+            let node = &self.nodes[*child];
+            if node.level == level &&
+                    ptr::to_unsafe_ptr(node.declarations.get_ref().get()) == key {
+                return child
+            }
+        }
+
+        let mut declared = self.nodes[*parent].declared.clone();
+        let mut declares_inherited = self.nodes[*parent].declares_inherited;
+        for declaration in declarations.get().iter() {
+            let index = declaration.longhand_index();
+            declared.set(index, true);
+            declares_inherited = declares_inherited || is_inherited_longhand(index);
+        }
+        let child = RuleNodeId(self.nodes.len());
+        self.nodes.push(RuleNode {
+            parent: Some(parent),
+            level: level,
+            declarations: Some(declarations.clone()),
+            children: ~[],
+            declared: declared,
+            declares_inherited: declares_inherited,
+            values: None,
+        });
+        self.nodes[*parent].children.push(child);
+        child
+    }
+
+    /// Returns the declarations that `node` stands for, from the one that loses to the one that
+    /// wins: applying them in order leaves each property with its cascaded value.
+    pub fn declarations<'a>(&'a self, node: RuleNodeId) -> ~[&'a PropertyDeclaration] {
+        let mut blocks = ~[];
+        let mut current = Some(node);
+        while current.is_some() {
+            let node = &self.nodes[*current.unwrap()];
+            for declarations in node.declarations.iter() {
+                blocks.push(declarations.get())
+            }
+            current = node.parent;
+        }
+        let mut result = ~[];
+        for block in blocks.rev_iter() {
+            for declaration in block.iter() {
+                result.push(declaration)
+            }
+        }
+        result
+    }
+
+    /// Returns whether the declarations that `node` stands for set the longhand with the given
+    /// number.
+    pub fn declares(&self, node: RuleNodeId, longhand_index: uint) -> bool {
+        self.nodes[*node].declared.get(longhand_index)
+    }
+
+    /// Returns whether the declarations that `node` stands for set an inherited longhand. The
+    /// inherited values of the elements whose declarations don't are those of their parents.
+    pub fn declares_inherited_longhand(&self, node: RuleNodeId) -> bool {
+        self.nodes[*node].declares_inherited
+    }
+
+    /// Returns the values last computed from the declarations of `node` if they were computed
+    /// from the same values of the parent, which are told apart by identity.
+    pub fn cached_values(&self, node: RuleNodeId, parent: Option<&Arc<ComputedValues>>)
+                         -> Option<Arc<ComputedValues>> {
+        match self.nodes[*node].values {
+            Some(ref cached) if same_values(cached.parent.as_ref(), parent) => {
+                Some(cached.values.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Keeps the values computed from the declarations of `node` and the values of the parent,
+    /// in place of those computed before.
+    pub fn cache_values(&mut self,
+                        node: RuleNodeId,
+                        parent: Option<&Arc<ComputedValues>>,
+                        values: Arc<ComputedValues>) {
+        self.nodes[*node].values = Some(CachedValues {
+            parent: parent.map_move(|parent| parent.clone()),
+            values: values,
+        })
+    }
+}
+
+fn same_values(a: Option<&Arc<ComputedValues>>, b: Option<&Arc<ComputedValues>>) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => ptr::to_unsafe_ptr(a.get()) == ptr::to_unsafe_ptr(b.get()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+fn parse_block(css: &str) -> Arc<~[PropertyDeclaration]> {
+    use style::properties::parse_style_attribute;
+    parse_style_attribute(css).normal
+}
+
+#[test]
+fn test_insert_shares_nodes() {
+    let a = parse_block("color: red");
+    let b = parse_block("margin-top: 1px");
+    let mut tree = RuleTree::new();
+    let ab = tree.insert([(0, &a), (0, &b)]);
+    assert!(tree.len() == 3);
+
+    // The same blocks at the same levels give the same nodes.
+    assert!(tree.insert([(0, &a), (0, &b)]) == ab);
+    let a_node = tree.insert([(0, &a)]);
+    assert!(a_node != ab && tree.len() == 3);
+
+    // Blocks are told apart by identity and by level, not by what they declare.
+    let other_a = parse_block("color: red");
+    assert!(tree.insert([(0, &other_a)]) != a_node);
+    assert!(tree.insert([(1, &a)]) != a_node);
+    assert!(tree.len() == 5);
+    assert!(tree.insert([]) == tree.root());
+}
+
+#[test]
+fn test_declarations_and_declared_longhands() {
+    let a = parse_block("color: red; margin-top: 1px");
+    let b = parse_block("color: blue");
+    let color = a.get()[0].longhand_index();
+    let margin_top = a.get()[1].longhand_index();
+    let mut tree = RuleTree::new();
+    let node = tree.insert([(0, &a), (1, &b)]);
+
+    // The declarations are in cascade order, so the last color wins.
+    let declarations = tree.declarations(node);
+    assert!(declarations.len() == 3);
+    assert!(ptr::to_unsafe_ptr(declarations[0]) == ptr::to_unsafe_ptr(&a.get()[0]));
+    assert!(ptr::to_unsafe_ptr(declarations[2]) == ptr::to_unsafe_ptr(&b.get()[0]));
+    assert!(tree.declarations(tree.root()).is_empty());
+
+    assert!(tree.declares(node, color) && tree.declares(node, margin_top));
+    let b_node = tree.insert([(1, &b)]);
+    assert!(tree.declares(b_node, color) && !tree.declares(b_node, margin_top));
+    assert!(tree.declares_inherited_longhand(b_node));
+
+    // `margin-top` isn't inherited, so elements that only set it inherit all they can.
+    let margin = parse_block("margin-top: 1px");
+    let margin_node = tree.insert([(0, &margin)]);
+    assert!(!tree.declares_inherited_longhand(margin_node));
+    assert!(!tree.declares_inherited_longhand(tree.root()));
+}
+
+#[test]
+fn test_cached_values() {
+    let a = parse_block("color: red");
+    let mut tree = RuleTree::new();
+    let node = tree.insert([(0, &a)]);
+    let parent = Arc::new(ComputedValues::initial());
+    let values = Arc::new(ComputedValues::initial());
+    assert!(tree.cached_values(node, Some(&parent)).is_none());
+
+    tree.cache_values(node, Some(&parent), values.clone());
+    let cached = tree.cached_values(node, Some(&parent)).unwrap();
+    assert!(ptr::to_unsafe_ptr(cached.get()) == ptr::to_unsafe_ptr(values.get()));
+
+    // Equal values of another parent are told apart, as is the root, which has none.
+    let other_parent = Arc::new(ComputedValues::initial());
+    assert!(tree.cached_values(node, Some(&other_parent)).is_none());
+    assert!(tree.cached_values(node, None).is_none());
+    assert!(tree.cached_values(tree.root(), Some(&parent)).is_none());
+}
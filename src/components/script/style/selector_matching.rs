@@ -0,0 +1,529 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Matching of the selectors of the new style system against elements. The stylist of layout
+//! matches the rules of the properties that it computes with it, so `:lang()`, attribute selectors
+//! and structural pseudo-classes apply to those properties, and document methods that take
+//! selectors use it too.
+//!
+//! FIXME: The properties that libcss supports are still matched by libcss, whose select handler
+//! has no callback for the language of an element, so `:lang()` rules don't apply to them.
+
+use std::ascii::StrAsciiExt;
+use std::str::eq_slice;
+use dom::element::{ElementState, HoverState, ActiveState, FocusState};
+use dom::node::AbstractNode;
+use servo_util::bloom::BloomFilter;
+use style::selectors::*;
+
+
+static HTML_NAMESPACE: &'static str = "http://www.w3.org/1999/xhtml";
+
+
+/// Returns true if `element` matches `selector`. Pseudo-elements are not taken into account: a
+/// selector with a pseudo-element matches the element the pseudo-element belongs to.
+pub fn matches_selector<View>(selector: &Selector, element: AbstractNode<View>) -> bool {
+    matches_compound_selector(&selector.compound_selectors, element)
+}
+
+
+/// Returns true if `element` matches `selector`, where `ancestors` holds the lowercase names, IDs
+/// and classes of the ancestors of `element`. Selectors that need an ancestor that isn't in the
+/// filter are rejected without walking up the tree.
+pub fn matches_selector_with_ancestors<View>(selector: &Selector,
+                                             element: AbstractNode<View>,
+                                             ancestors: &BloomFilter)
+                                             -> bool {
+    !can_reject_with_ancestors(&selector.compound_selectors, ancestors) &&
+        matches_selector(selector, element)
+}
+
+
+/// Returns the states of elements that the dynamic pseudo-classes of `selector` test, anywhere in
+/// it: when one of them changes on an element, the elements that the selector might match must be
+/// restyled.
+pub fn state_dependencies(selector: &Selector) -> ElementState {
+    let mut state = ElementState::none();
+    let mut compound = Some(&selector.compound_selectors);
+    while compound.is_some() {
+        let selector = compound.unwrap();
+        state = state.union(simple_selectors_state_dependencies(selector.simple_selectors));
+        compound = match selector.next {
+            None => None,
+            Some((ref next_selector, _)) => Some(&**next_selector),
+        };
+    }
+    state
+}
+
+
+fn simple_selectors_state_dependencies(selectors: &[SimpleSelector]) -> ElementState {
+    let mut state = ElementState::none();
+    for selector in selectors.iter() {
+        state = match *selector {
+            Hover => state.add(HoverState),
+            Active => state.add(ActiveState),
+            Focus => state.add(FocusState),
+            Negation(ref negated) => {
+                state.union(simple_selectors_state_dependencies(negated.as_slice()))
+            }
+            _ => state,
+        }
+    }
+    state
+}
+
+
+/// Returns true if `selector` tests where elements are among their siblings, anywhere in it, with
+/// a structural pseudo-class or a sibling combinator: when a child is inserted into or removed
+/// from an element, the children of that element that the selector might match, and their
+/// descendants, must be restyled.
+pub fn depends_on_sibling_positions(selector: &Selector) -> bool {
+    let mut compound = Some(&selector.compound_selectors);
+    while compound.is_some() {
+        let selector = compound.unwrap();
+        if simple_selectors_depend_on_sibling_positions(selector.simple_selectors) {
+            return true
+        }
+        compound = match selector.next {
+            None => None,
+            Some((_, NextSibling)) | Some((_, LaterSibling)) => return true,
+            Some((ref next_selector, _)) => Some(&**next_selector),
+        };
+    }
+    false
+}
+
+
+fn simple_selectors_depend_on_sibling_positions(selectors: &[SimpleSelector]) -> bool {
+    do selectors.iter().any |selector| {
+        match *selector {
+            NthChild(*) | NthOfType(*) | FirstChild | LastChild | OnlyChild => true,
+            Negation(ref negated) => {
+                simple_selectors_depend_on_sibling_positions(negated.as_slice())
+            }
+            _ => false,
+        }
+    }
+}
+
+
+/// What two elements with the same parent, name and classes, and neither an ID nor a style
+/// attribute, must also have in common for the selectors of the style sheets to match them alike,
+/// so that they can share the values computed for them. Such elements only have their ancestors
+/// in common, so the attributes and states that the selectors test of the elements themselves
+/// must be equal, and selectors that test their siblings or children rule out sharing.
+pub struct SharingRequirements {
+    /// Whether a selector tests something that can't be compared, such as where elements are
+    /// among their siblings.
+    priv impossible: bool,
+    /// The lowercase names of the attributes whose values must be equal.
+    priv attributes: ~[~str],
+    /// The states that must be equal.
+    priv states: ElementState,
+}
+
+impl SharingRequirements {
+    pub fn new() -> SharingRequirements {
+        SharingRequirements {
+            impossible: false,
+            attributes: ~[],
+            states: ElementState::none(),
+        }
+    }
+
+    /// Adds what `selector` tests of the element it matches.
+    pub fn add_selector(&mut self, selector: &Selector) {
+        self.add_simple_selectors(selector.compound_selectors.simple_selectors);
+        let mut compound = &selector.compound_selectors;
+        loop {
+            compound = match compound.next {
+                None => break,
+                Some((ref next_selector, Child)) | Some((ref next_selector, Descendant)) => {
+                    &**next_selector
+                }
+                Some(_) => {
+                    self.impossible = true;
+                    break
+                }
+            }
+        }
+    }
+
+    fn add_simple_selectors(&mut self, selectors: &[SimpleSelector]) {
+        for selector in selectors.iter() {
+            match *selector {
+                IDSelector(*) | ClassSelector(*) | LocalNameSelector{_} | NamespaceSelector(*) |
+                Root => {}
+                AttrExists(ref attr) | AttrEqual(ref attr, _) | AttrIncludes(ref attr, _) |
+                AttrDashMatch(ref attr, _) | AttrPrefixMatch(ref attr, _) |
+                AttrSubstringMatch(ref attr, _) | AttrSuffixMatch(ref attr, _) => {
+                    self.add_attribute(attr.lowercase_name.as_slice())
+                }
+                Lang(_) => {
+                    self.add_attribute("lang");
+                    self.add_attribute("xml:lang")
+                }
+                Link | Visited => self.add_attribute("href"),
+                Hover => self.states = self.states.add(HoverState),
+                Active => self.states = self.states.add(ActiveState),
+                Focus => self.states = self.states.add(FocusState),
+                Negation(ref negated) => self.add_simple_selectors(negated.as_slice()),
+                Empty | NthChild(*) | NthOfType(*) | FirstChild | LastChild | OnlyChild | Valid |
+                Invalid => self.impossible = true,
+            }
+        }
+    }
+
+    fn add_attribute(&mut self, name: &str) {
+        if !self.attributes.iter().any(|attribute| eq_slice(*attribute, name)) {
+            self.attributes.push(name.to_owned())
+        }
+    }
+
+    /// Returns true if two elements that have the same parent, name and classes, and neither an
+    /// ID nor a style attribute, are matched alike.
+    pub fn met_by<View>(&self, a: AbstractNode<View>, b: AbstractNode<View>) -> bool {
+        if self.impossible {
+            return false
+        }
+        let same_attributes = do a.with_imm_element |a| {
+            do b.with_imm_element |b| {
+                do self.attributes.iter().all |name| {
+                    a.get_attr(name.as_slice()) == b.get_attr(name.as_slice())
+                }
+            }
+        };
+        if !same_attributes {
+            return false
+        }
+        if self.states.is_empty() {
+            return true
+        }
+        let (a_state, b_state) = (a.element_state(), b.element_state());
+        do [HoverState, ActiveState, FocusState].iter().all |&flag| {
+            !self.states.has(flag) || a_state.has(flag) == b_state.has(flag)
+        }
+    }
+}
+
+
+/// Returns true if a compound selector certainly doesn't match, because an ancestor that it
+/// requires of the element isn't in the filter.
+fn can_reject_with_ancestors(selector: &CompoundSelector, ancestors: &BloomFilter) -> bool {
+    let mut selector = selector;
+    let mut is_ancestor = false;
+    loop {
+        if is_ancestor {
+            for simple_selector in selector.simple_selectors.iter() {
+                let key = match *simple_selector {
+                    LocalNameSelector{lowercase_name: ref name, _} => name.as_slice(),
+                    IDSelector(ref id) => id.as_slice(),
+                    ClassSelector(ref class) => class.as_slice(),
+                    _ => loop,
+                };
+                if !ancestors.might_contain(key) {
+                    return true
+                }
+            }
+        }
+        match selector.next {
+            None => return false,
+            Some((ref next_selector, combinator)) => {
+                // The parent of an ancestor is an ancestor too, but its siblings aren't.
+                is_ancestor = match combinator {
+                    Child | Descendant => true,
+                    NextSibling | LaterSibling => false,
+                };
+                selector = &**next_selector;
+            }
+        }
+    }
+}
+
+
+fn matches_compound_selector<View>(selector: &CompoundSelector, element: AbstractNode<View>)
+                                   -> bool {
+    if !selector.simple_selectors.iter().all(|s| matches_simple_selector(s, element)) {
+        return false
+    }
+    match selector.next {
+        None => true,
+        Some((ref next_selector, combinator)) => {
+            let (siblings, just_one) = match combinator {
+                Child => (false, true),
+                Descendant => (false, false),
+                NextSibling => (true, true),
+                LaterSibling => (true, false),
+            };
+            let mut node = element;
+            loop {
+                let next_node = if siblings { node.prev_sibling() } else { node.parent_node() };
+                match next_node {
+                    None => return false,
+                    Some(next_node) => node = next_node,
+                }
+                if node.is_element() {
+                    if matches_compound_selector(&**next_selector, node) {
+                        return true
+                    } else if just_one {
+                        return false
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+fn matches_simple_selector<View>(selector: &SimpleSelector, element: AbstractNode<View>) -> bool {
+    static WHITESPACE: &'static [char] = &'static [' ', '\t', '\n', '\r', '\x0c'];
+
+    match *selector {
+        // TODO: case-sensitivity depends on the document type
+        LocalNameSelector{lowercase_name: ref name, _} => {
+            do element.with_imm_element |element| {
+                eq_slice(element.tag_name.to_ascii_lower(), *name)
+            }
+        }
+        // TODO: elements don't know their namespace yet; everything is an HTML element.
+        NamespaceSelector(ref url) => eq_slice(*url, HTML_NAMESPACE),
+        // TODO: case-sensitivity depends on the document type and quirks mode
+        IDSelector(ref id) => {
+            do element.with_imm_element |element| {
+                match element.get_attr("id") {
+                    Some(attr) => eq_slice(attr, *id),
+                    None => false,
+                }
+            }
+        }
+        ClassSelector(ref class) => {
+            do element.with_imm_element |element| {
+                match element.get_attr("class") {
+                    None => false,
+                    Some(attr) => attr.split_iter(WHITESPACE).any(|c| eq_slice(c, *class)),
+                }
+            }
+        }
+
+        AttrExists(ref attr) => matches_attribute(attr, element, "", |_, _| true),
+        AttrEqual(ref attr, ref value) => {
+            matches_attribute(attr, element, *value, |attr_value, value| attr_value == value)
+        }
+        AttrIncludes(ref attr, ref value) => {
+            do matches_attribute(attr, element, *value) |attr_value, value| {
+                !value.is_empty() && !value.iter().any(|c| WHITESPACE.contains(&c)) &&
+                    attr_value.split_iter(WHITESPACE).any(|v| eq_slice(v, value))
+            }
+        }
+        AttrDashMatch(ref attr, ref value) => {
+            do matches_attribute(attr, element, *value) |attr_value, value| {
+                attr_value == value ||
+                    (attr_value.starts_with(value) && attr_value.char_at(value.len()) == '-')
+            }
+        }
+        AttrPrefixMatch(ref attr, ref value) => {
+            do matches_attribute(attr, element, *value) |attr_value, value| {
+                !value.is_empty() && attr_value.starts_with(value)
+            }
+        }
+        AttrSubstringMatch(ref attr, ref value) => {
+            do matches_attribute(attr, element, *value) |attr_value, value| {
+                !value.is_empty() && attr_value.contains(value)
+            }
+        }
+        AttrSuffixMatch(ref attr, ref value) => {
+            do matches_attribute(attr, element, *value) |attr_value, value| {
+                !value.is_empty() && attr_value.ends_with(value)
+            }
+        }
+
+        Root => element.parent_node().is_none(),
+        Lang(ref range) => {
+            match element.language() {
+                Some(lang) => lang_matches(lang, *range),
+                None => false,
+            }
+        }
+        // Links are the `a`, `area` and `link` elements with an `href` attribute. No history
+        // is kept, so none of them has been visited.
+        Link => {
+            do element.with_imm_element |element| {
+                let name = element.tag_name.to_ascii_lower();
+                ["a", "area", "link"].iter().any(|&n| n == name.as_slice()) &&
+                    element.get_attr("href").is_some()
+            }
+        }
+        Visited => false,
+        Focus => element.is_focused(),
+        Hover => element.is_hovered(),
+        Active => element.is_active(),
+        // Only input elements take part in constraint validation so far.
+        Valid => {
+            element.is_input_element() &&
+                element.with_imm_input_element(|input| input.will_validate() && !input.is_invalid())
+        }
+        Invalid => {
+            element.is_input_element() && element.with_imm_input_element(|input| input.is_invalid())
+        }
+        // Structural pseudo-classes only match elements that have a parent.
+        NthChild(a, b) => {
+            element.parent_node().is_some() &&
+                nth_matches(a, b, sibling_position(element, false, false))
+        }
+        NthOfType(a, b) => {
+            element.parent_node().is_some() &&
+                nth_matches(a, b, sibling_position(element, false, true))
+        }
+        FirstChild => {
+            element.parent_node().is_some() && sibling_position(element, false, false) == 1
+        }
+        LastChild => {
+            element.parent_node().is_some() && sibling_position(element, true, false) == 1
+        }
+        OnlyChild => {
+            element.parent_node().is_some() && sibling_position(element, false, false) == 1 &&
+                sibling_position(element, true, false) == 1
+        }
+        // TODO: :empty
+        Empty => false,
+
+        Negation(ref negated) => !negated.iter().all(|s| matches_simple_selector(s, element)),
+    }
+}
+
+
+/// The attributes whose values HTML compares ASCII case-insensitively in selectors.
+static CASE_INSENSITIVE_ATTRIBUTES: &'static [&'static str] = &'static [
+    "accept", "accept-charset", "align", "alink", "axis", "bgcolor", "charset", "checked",
+    "clear", "codetype", "color", "compact", "declare", "defer", "dir", "direction", "disabled",
+    "enctype", "face", "frame", "hreflang", "http-equiv", "lang", "language", "link", "media",
+    "method", "multiple", "nohref", "noresize", "noshade", "nowrap", "readonly", "rel", "rev",
+    "rules", "scope", "scrolling", "selected", "shape", "target", "text", "type", "valign",
+    "valuetype", "vlink",
+];
+
+
+/// Returns true if `element` has the attribute that `attr` selects, and `test` returns true for
+/// its value and the `value` of the selector. Both values are ASCII lowercased first if HTML
+/// compares the values of the attribute case-insensitively.
+fn matches_attribute<View>(attr: &AttrSelector,
+                           element: AbstractNode<View>,
+                           value: &str,
+                           test: &fn(&str, &str) -> bool)
+                           -> bool {
+    do element.with_imm_element |element| {
+        // Attribute names are lowercased by the HTML parser.
+        // TODO: case-sensitivity depends on the document type
+        let attr_value = match attr.namespace {
+            // `*|` selects attributes in any namespace.
+            None => element.get_attr(attr.lowercase_name),
+            Some(ref url) => element.get_attr_ns(url.as_slice(), attr.lowercase_name),
+        };
+        match attr_value {
+            None => false,
+            Some(attr_value) => {
+                let name = attr.lowercase_name.as_slice();
+                if CASE_INSENSITIVE_ATTRIBUTES.iter().any(|&n| n == name) {
+                    test(attr_value.to_ascii_lower(), value.to_ascii_lower())
+                } else {
+                    test(attr_value, value)
+                }
+            }
+        }
+    }
+}
+
+
+/// Returns the position of `element` among the element children of its parent, counting from 1,
+/// from the last child if `from_end` is true, and only counting the elements with the same name
+/// as `element` if `of_type` is true.
+fn sibling_position<View>(element: AbstractNode<View>, from_end: bool, of_type: bool) -> i32 {
+    let name = do element.with_imm_element |element| { element.tag_name.to_ascii_lower() };
+    let mut position = 1;
+    let mut node = element;
+    loop {
+        let sibling = if from_end { node.next_sibling() } else { node.prev_sibling() };
+        match sibling {
+            None => return position,
+            Some(sibling) => node = sibling,
+        }
+        if node.is_element() && (!of_type || do node.with_imm_element |sibling| {
+            eq_slice(sibling.tag_name.to_ascii_lower(), name)
+        }) {
+            position += 1
+        }
+    }
+}
+
+
+/// Returns true if `position` is `a*n + b` for some `n` greater than or equal to zero, as an
+/// `:nth-child(an+b)` selector matches.
+fn nth_matches(a: i32, b: i32, position: i32) -> bool {
+    if a == 0 {
+        position == b
+    } else {
+        let offset = position - b;
+        offset % a == 0 && offset / a >= 0
+    }
+}
+
+
+/// Returns true if the language `lang` is matched by the language range of a `:lang()` selector:
+/// either they are equal, or `range` is a prefix of `lang` followed by a hyphen. The comparison
+/// is ASCII case-insensitive.
+pub fn lang_matches(lang: &str, range: &str) -> bool {
+    let lang = lang.to_ascii_lower();
+    let range = range.to_ascii_lower();
+    lang == range || (lang.starts_with(range) && lang.char_at(range.len()) == '-')
+}
+
+#[test]
+fn test_lang_matches() {
+    assert!(lang_matches("en", "en"));
+    assert!(lang_matches("en-US", "en"));
+    assert!(lang_matches("EN-us", "en-US"));
+    assert!(!lang_matches("eng", "en"));
+    assert!(!lang_matches("en", "en-US"));
+    assert!(!lang_matches("fr", "en"));
+}
+
+#[test]
+fn test_nth_matches() {
+    // :nth-child(2n+1) matches the odd positions.
+    assert!(nth_matches(2, 1, 1) && nth_matches(2, 1, 5));
+    assert!(!nth_matches(2, 1, 4));
+    // :nth-child(-n+3) matches the first three.
+    assert!(nth_matches(-1, 3, 1) && nth_matches(-1, 3, 3));
+    assert!(!nth_matches(-1, 3, 4));
+    // :nth-child(3) matches only the third.
+    assert!(nth_matches(0, 3, 3) && !nth_matches(0, 3, 6));
+}
+
+#[test]
+fn test_sharing_requirements() {
+    use cssparser::tokenize;
+    use style::namespaces::NamespaceMap;
+
+    let requirements_of = |text: &str| {
+        let input = tokenize(text).map(|(value, _)| value).collect();
+        let mut requirements = SharingRequirements::new();
+        for selector in parse_selector_list(input, &NamespaceMap::new()).unwrap().iter() {
+            requirements.add_selector(selector)
+        }
+        requirements
+    };
+    let requirements = requirements_of("li.item, ul > li:not(.done), :root p, a[href] li");
+    assert!(!requirements.impossible && requirements.attributes.is_empty());
+    assert!(requirements.states.is_empty());
+
+    let requirements = requirements_of("li[TITLE], p:lang(en), :link, li:not(:hover)");
+    assert!(!requirements.impossible);
+    assert!(requirements.attributes == ~[~"title", ~"lang", ~"xml:lang", ~"href"]);
+    assert!(requirements.states.has(HoverState) && !requirements.states.has(FocusState));
+
+    assert!(requirements_of("li:first-child").impossible);
+    assert!(requirements_of("h1 + p").impossible);
+    assert!(requirements_of("p:empty").impossible);
+}
@@ -17,6 +17,7 @@ pub struct Selector {
 pub static STYLE_ATTRIBUTE_SPECIFICITY: u32 = 1 << 31;
 
 
+#[deriving(Eq, Clone)]
 pub enum PseudoElement {
     Before,
     After,
@@ -57,6 +58,17 @@ pub enum SimpleSelector {
     Root,
     Lang(~str),
     NthChild(i32, i32),
+    NthOfType(i32, i32),
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    Valid,
+    Invalid,
+    Link,
+    Visited,
+    Focus,
+    Hover,
+    Active,
     Negation(~[SimpleSelector]),
     // ...
 }
@@ -173,7 +185,9 @@ fn compute_specificity(mut selector: &CompoundSelector,
                 &ClassSelector(*)
                 | &AttrExists(*) | &AttrEqual(*) | &AttrIncludes(*) | &AttrDashMatch(*)
                 | &AttrPrefixMatch(*) | &AttrSubstringMatch(*) | &AttrSuffixMatch(*)
-                | &Empty | &Root | &Lang(*) | &NthChild(*)
+                | &Empty | &Root | &Lang(*) | &NthChild(*) | &NthOfType(*) | &FirstChild
+                | &LastChild | &OnlyChild | &Valid | &Invalid | &Link | &Visited | &Focus | &Hover
+                | &Active
                 => specificity.class_like_selectors += 1,
                 &NamespaceSelector(*) => (),
                 &Negation(ref negated)
@@ -219,7 +233,8 @@ fn parse_simple_selectors(iter: &mut Iter, namespaces: &NamespaceMap)
 fn parse_type_selector(iter: &mut Iter, namespaces: &NamespaceMap)
                        -> Option<Option<~[SimpleSelector]>> {
     skip_whitespace(iter);
-    match parse_qualified_name(iter, /* allow_universal = */ true, namespaces) {
+    match parse_qualified_name(iter, /* allow_universal = */ true, namespaces,
+                               /* in_attr_selector = */ false) {
         None => None,  // invalid selector
         Some(None) => Some(None),
         Some(Some((namespace, local_name))) => {
@@ -299,11 +314,18 @@ fn parse_one_simple_selector(iter: &mut Iter, namespaces: &NamespaceMap, inside_
 // Some(Some((None, Some(name)) means *|name
 // Some(Some((Some(url), Some(name))) means prefix|name
 // ... or equivalent
-fn parse_qualified_name(iter: &mut Iter, allow_universal: bool, namespaces: &NamespaceMap)
+// The default namespace doesn't apply to attribute names: without a prefix, they are in no
+// namespace.
+fn parse_qualified_name(iter: &mut Iter, allow_universal: bool, namespaces: &NamespaceMap,
+                        in_attr_selector: bool)
                        -> Option<Option<(Option<~str>, Option<~str>)>> {
     #[inline]
-    fn default_namespace(namespaces: &NamespaceMap, local_name: Option<~str>)
+    fn default_namespace(namespaces: &NamespaceMap, in_attr_selector: bool,
+                         local_name: Option<~str>)
                          -> Option<Option<(Option<~str>, Option<~str>)>> {
+        if in_attr_selector {
+            return Some(Some((Some(~""), local_name)))
+        }
         match namespaces.default {
             None => Some(Some((None, local_name))),
             Some(ref url) => Some(Some((Some(url.to_owned()), local_name))),
@@ -331,24 +353,24 @@ fn parse_qualified_name(iter: &mut Iter, allow_universal: bool, namespaces: &Nam
         Some(&Ident(_)) => {
             let value = get_next_ident(iter);
             match iter.peek() {
-                Some(&Delim('|')) => default_namespace(namespaces, Some(value)),
-                _ => {
+                Some(&Delim('|')) => {
                     let namespace_url = match namespaces.prefix_map.find(&value) {
                         None => return None,  // Undeclared namespace prefix: invalid selector
                         Some(ref url) => url.to_owned(),
                     };
                     explicit_namespace(iter, allow_universal, Some(namespace_url))
                 },
+                _ => default_namespace(namespaces, in_attr_selector, Some(value)),
             }
         },
         Some(&Delim('*')) => {
             iter.next();  // Consume '*'
             match iter.peek() {
-                Some(&Delim('|')) => {
-                    if allow_universal { default_namespace(namespaces, None) }
+                Some(&Delim('|')) => explicit_namespace(iter, allow_universal, None),
+                _ => {
+                    if allow_universal { default_namespace(namespaces, in_attr_selector, None) }
                     else { None }
                 },
-                _ => explicit_namespace(iter, allow_universal, None),
             }
         },
         Some(&Delim('|')) => explicit_namespace(iter, allow_universal, Some(~"")),
@@ -360,7 +382,8 @@ fn parse_qualified_name(iter: &mut Iter, allow_universal: bool, namespaces: &Nam
 fn parse_attribute_selector(content: ~[ComponentValue], namespaces: &NamespaceMap)
                             -> Option<SimpleSelector> {
     let iter = &mut content.move_iter().peekable();
-    let attr = match parse_qualified_name(iter, /* allow_universal = */ false, namespaces) {
+    let attr = match parse_qualified_name(iter, /* allow_universal = */ false, namespaces,
+                                          /* in_attr_selector = */ true) {
         None => return None,  // invalid selector
         Some(None) => return None,
         Some(Some((_, None))) => fail!("Implementation error, this should not happen."),
@@ -397,6 +420,16 @@ fn parse_simple_pseudo_class(name: ~str) -> Option<Either<SimpleSelector, Pseudo
     match name.to_ascii_lower().as_slice() {
         "root" => Some(Left(Root)),
         "empty" => Some(Left(Empty)),
+        "first-child" => Some(Left(FirstChild)),
+        "last-child" => Some(Left(LastChild)),
+        "only-child" => Some(Left(OnlyChild)),
+        "valid" => Some(Left(Valid)),
+        "invalid" => Some(Left(Invalid)),
+        "link" => Some(Left(Link)),
+        "visited" => Some(Left(Visited)),
+        "focus" => Some(Left(Focus)),
+        "hover" => Some(Left(Hover)),
+        "active" => Some(Left(Active)),
 
         // Supported CSS 2.1 pseudo-elements only.
         "before" => Some(Right(Before)),
@@ -414,6 +447,7 @@ fn parse_functional_pseudo_class(name: ~str, arguments: ~[ComponentValue],
     match name.to_ascii_lower().as_slice() {
         "lang" => parse_lang(arguments),
         "nth-child" => parse_nth(arguments).map(|&(a, b)| NthChild(a, b)),
+        "nth-of-type" => parse_nth(arguments).map(|&(a, b)| NthOfType(a, b)),
         "not" => if inside_negation { None } else { parse_negation(arguments, namespaces) },
         _ => None
     }
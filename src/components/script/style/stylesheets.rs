@@ -11,6 +11,8 @@ use style::errors::{ErrorLoggerIterator, log_css_error};
 use style::namespaces::{NamespaceMap, parse_namespace_rule};
 use style::media_queries::{MediaRule, parse_media_rule};
 use style::media_queries;
+use style::supports::{SupportsRule, parse_supports_rule};
+use style::counter_style::{CounterStyleRule, CounterStyleMap, parse_counter_style_rule};
 
 
 pub struct Stylesheet {
@@ -22,6 +24,8 @@ pub struct Stylesheet {
 pub enum CSSRule {
     CSSStyleRule(StyleRule),
     CSSMediaRule(MediaRule),
+    CSSSupportsRule(SupportsRule),
+    CSSCounterStyleRule(CounterStyleRule),
 }
 
 
@@ -31,7 +35,7 @@ pub struct StyleRule {
 }
 
 
-fn parse_stylesheet(css: &str) -> Stylesheet {
+pub fn parse_stylesheet(css: &str) -> Stylesheet {
     static STATE_CHARSET: uint = 1;
     static STATE_IMPORTS: uint = 2;
     static STATE_NAMESPACES: uint = 3;
@@ -111,18 +115,48 @@ pub fn parse_nested_at_rule(lower_name: &str, rule: AtRule,
                             parent_rules: &mut ~[CSSRule], namespaces: &NamespaceMap) {
     match lower_name {
         "media" => parse_media_rule(rule, parent_rules, namespaces),
+        "supports" => parse_supports_rule(rule, parent_rules, namespaces),
+        "counter-style" => parse_counter_style_rule(rule, parent_rules),
         _ => log_css_error(rule.location, fmt!("Unsupported at-rule: @%s", lower_name))
     }
 }
 
 
 impl Stylesheet {
-    fn iter_style_rules<'a>(&'a self, device: &'a media_queries::Device) -> StyleRuleIterator<'a> {
+    /// Returns the style rules of the sheet that apply to `device`, in document order.
+    pub fn iter_style_rules<'a>(&'a self, device: &'a media_queries::Device)
+                                -> StyleRuleIterator<'a> {
         StyleRuleIterator { device: device, stack: ~[(self.rules.as_slice(), 0)] }
     }
+
+    /// Defines the counter styles of the `@counter-style` rules that apply to `device`, in
+    /// document order.
+    pub fn add_counter_styles(&self, device: &media_queries::Device,
+                              counter_styles: &mut CounterStyleMap) {
+        fn add_from_rules(rules: &[CSSRule], device: &media_queries::Device,
+                          counter_styles: &mut CounterStyleMap) {
+            for rule in rules.iter() {
+                match *rule {
+                    CSSStyleRule(*) => (),
+                    CSSMediaRule(ref rule) => {
+                        if rule.media_queries.evaluate(device) {
+                            add_from_rules(rule.rules, device, counter_styles)
+                        }
+                    }
+                    CSSSupportsRule(ref rule) => {
+                        if rule.enabled {
+                            add_from_rules(rule.rules, device, counter_styles)
+                        }
+                    }
+                    CSSCounterStyleRule(ref rule) => counter_styles.add_rule(rule),
+                }
+            }
+        }
+        add_from_rules(self.rules, device, counter_styles)
+    }
 }
 
-struct StyleRuleIterator<'self> {
+pub struct StyleRuleIterator<'self> {
     device: &'self media_queries::Device,
     // FIXME: I couldn’t get this to borrow-check with a stack of VecIterator
     stack: ~[(&'self [CSSRule], uint)],
@@ -133,6 +167,8 @@ impl<'self> Iterator<&'self StyleRule> for StyleRuleIterator<'self> {
         loop {
             match self.stack.pop_opt() {
                 None => return None,
+                // A rule with an empty block.
+                Some((rule_list, i)) if i >= rule_list.len() => (),
                 Some((rule_list, i)) => {
                     if i + 1 < rule_list.len() {
                         self.stack.push((rule_list, i + 1))
@@ -144,6 +180,12 @@ impl<'self> Iterator<&'self StyleRule> for StyleRuleIterator<'self> {
                                 self.stack.push((rule.rules.as_slice(), 0))
                             }
                         }
+                        CSSSupportsRule(ref rule) => {
+                            if rule.enabled {
+                                self.stack.push((rule.rules.as_slice(), 0))
+                            }
+                        }
+                        CSSCounterStyleRule(*) => (),
                     }
                 }
             }
@@ -0,0 +1,202 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! `@supports` rules, whose rules apply only if the declarations and selectors that their
+//! condition asks about parse.
+//!
+//! FIXME: Layout still reads `display`, `float`, `position`, `clear`, text alignment, the lines
+//! of `text-decoration` and every color from libcss, which drops `@supports` rules and what is in
+//! them. They apply to the other properties, such as the box model lengths and font sizes, which
+//! layout reads from the new style system.
+
+use std::ascii::StrAsciiExt;
+use cssparser::*;
+use style::errors::{ErrorLoggerIterator, log_css_error};
+use style::namespaces::NamespaceMap;
+use style::parsing_utils::get_ident_lower;
+use style::properties::PropertyDeclaration;
+use style::selectors::parse_selector_list;
+use style::stylesheets::{CSSRule, CSSSupportsRule, parse_style_rule, parse_nested_at_rule};
+
+
+pub struct SupportsRule {
+    /// Whether the condition holds. It depends only on what the parser supports, so it is
+    /// evaluated once, as the rule is parsed.
+    enabled: bool,
+    rules: ~[CSSRule],
+}
+
+
+pub fn parse_supports_rule(rule: AtRule, parent_rules: &mut ~[CSSRule],
+                           namespaces: &NamespaceMap) {
+    let block = match rule.block {
+        Some(block) => block,
+        None => {
+            log_css_error(rule.location, "Invalid @supports rule");
+            return
+        }
+    };
+    let enabled = match parse_supports_condition(rule.prelude, namespaces) {
+        Some(enabled) => enabled,
+        None => {
+            log_css_error(rule.location, "Invalid @supports condition");
+            return
+        }
+    };
+    let mut rules = ~[];
+    for rule in ErrorLoggerIterator(parse_rule_list(block.move_iter())) {
+        match rule {
+            QualifiedRule(rule) => parse_style_rule(rule, &mut rules, namespaces),
+            AtRule(rule) => parse_nested_at_rule(
+                rule.name.to_ascii_lower(), rule, &mut rules, namespaces),
+        }
+    }
+    parent_rules.push(CSSSupportsRule(SupportsRule {
+        enabled: enabled,
+        rules: rules,
+    }))
+}
+
+
+/// Parses and evaluates a `<supports-condition>`: a test in parentheses negated with `not`, or
+/// tests joined with `and` or with `or`, which can't be mixed without parentheses. Returns
+/// `None` if the condition is invalid.
+pub fn parse_supports_condition(input: ~[ComponentValue], namespaces: &NamespaceMap)
+                                -> Option<bool> {
+    let mut iter = input.move_iter().filter(|value| *value != WhiteSpace);
+    let first = match iter.next() {
+        Some(value) => value,
+        None => return None,
+    };
+    match get_ident_lower(&first) {
+        Some(ref keyword) if "not" == *keyword => {
+            let operand = iter.next();
+            if iter.next().is_some() {
+                return None
+            }
+            return operand.chain(|operand| parse_supports_in_parens(operand, namespaces))
+                          .map_move(|holds| !holds)
+        }
+        _ => {}
+    }
+
+    let mut result = match parse_supports_in_parens(first, namespaces) {
+        Some(result) => result,
+        None => return None,
+    };
+    let mut operator = None;
+    loop {
+        let keyword = match iter.next() {
+            None => return Some(result),
+            Some(value) => match get_ident_lower(&value) {
+                Some(keyword) => keyword,
+                None => return None,
+            },
+        };
+        if "and" != keyword && "or" != keyword {
+            return None
+        }
+        match operator {
+            Some(ref operator) if *operator != keyword => return None,
+            _ => {}
+        }
+        let operand = iter.next().chain(|value| parse_supports_in_parens(value, namespaces));
+        let operand = match operand {
+            Some(operand) => operand,
+            None => return None,
+        };
+        result = if "and" == keyword { result && operand } else { result || operand };
+        operator = Some(keyword);
+    }
+}
+
+
+/// Evaluates a `<supports-in-parens>`: a condition or a declaration in parentheses, or a
+/// `selector()` function. Anything else in parentheses or a function is valid but never holds,
+/// so that tests for syntax that comes later don't invalidate the whole condition.
+fn parse_supports_in_parens(input: ComponentValue, namespaces: &NamespaceMap) -> Option<bool> {
+    match input {
+        ParenthesisBlock(content) => {
+            if is_declaration(content) {
+                Some(supports_declaration(content))
+            } else {
+                Some(parse_supports_condition(content, namespaces).unwrap_or_default(false))
+            }
+        }
+        Function(name, arguments) => {
+            if "selector" == name.to_ascii_lower() {
+                Some(parse_selector_list(arguments, namespaces).is_some())
+            } else {
+                Some(false)
+            }
+        }
+        _ => None,
+    }
+}
+
+
+fn is_declaration(content: &[ComponentValue]) -> bool {
+    let mut iter = content.skip_whitespace();
+    match (iter.next(), iter.next()) {
+        (Some(&Ident(_)), Some(&Colon)) => true,
+        _ => false,
+    }
+}
+
+
+/// Returns whether the property of a declaration is supported with its value, as the parser of
+/// the property decides. The CSS-wide keywords are supported by every property.
+fn supports_declaration(content: ~[ComponentValue]) -> bool {
+    let colon = content.iter().position(|value| *value == Colon).unwrap();
+    let name = match content.iter().find(|value| **value != WhiteSpace) {
+        Some(&Ident(ref name)) => name.clone(),
+        _ => return false,
+    };
+    let mut declarations = ~[];
+    PropertyDeclaration::parse(name, content.slice_from(colon + 1), &mut declarations)
+}
+
+
+#[cfg(test)]
+fn supports(condition: &str) -> Option<bool> {
+    let input = tokenize(condition).map(|(value, _)| value).collect();
+    parse_supports_condition(input, &NamespaceMap::new())
+}
+
+#[test]
+fn test_declarations() {
+    assert!(supports("(color: red)") == Some(true));
+    assert!(supports("( color :red )") == Some(true));
+    assert!(supports("(color: inherit)") == Some(true));
+    assert!(supports("(border-width: 1px 2px)") == Some(true));
+    assert!(supports("(color: 1px)") == Some(false));
+    assert!(supports("(unknown-property: red)") == Some(false));
+}
+
+#[test]
+fn test_operators() {
+    assert!(supports("not (color: 1px)") == Some(true));
+    assert!(supports("(color: red) and (color: 1px)") == Some(false));
+    assert!(supports("(color: red) or (color: 1px)") == Some(true));
+    assert!(supports("(color: red) and (margin-top: 1px) and (color: blue)") == Some(true));
+    assert!(supports("((color: 1px) or (color: red)) and (margin-top: 1px)") == Some(true));
+    assert!(supports("not ((color: red) and (color: 1px))") == Some(true));
+
+    // `and` and `or` can't be mixed without parentheses, and `not` takes one operand.
+    assert!(supports("(color: red) and (color: red) or (color: red)").is_none());
+    assert!(supports("not (color: red) and (color: red)").is_none());
+    assert!(supports("(color: red) (color: red)").is_none());
+    assert!(supports("color: red").is_none());
+    assert!(supports("").is_none());
+}
+
+#[test]
+fn test_selectors_and_unknown_tests() {
+    assert!(supports("selector(a > b)") == Some(true));
+    assert!(supports("selector(a >)") == Some(false));
+    // Tests that aren't understood are valid, but don't hold.
+    assert!(supports("(unknown test)") == Some(false));
+    assert!(supports("unknown(test)") == Some(false));
+    assert!(supports("not unknown(test)") == Some(true));
+}
@@ -0,0 +1,296 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The timers that `setTimeout` and `setInterval` schedule. Each window keeps its timers in the
+//! order in which they are due. For each timer, a task of its own sleeps until the timer is due
+//! and then tells the script task. The script task then runs every timer of the window that is
+//! due by that time, in order, so timers run in the order of their deadlines however the
+//! sleeping tasks happen to wake. The timers of a window whose document is in the session
+//! history, rather than shown, are frozen. Frozen timers don't run, and when the window is shown
+//! again their deadlines move on by as long as it was frozen.
+
+use extra::time::precise_time_ns;
+use js::jsapi::JSVal;
+use std::i32;
+
+/// How deeply timers may nest before their timeouts are clamped.
+static MAX_NESTING_LEVEL: uint = 5;
+
+/// The shortest timeout of timers nested more deeply than `MAX_NESTING_LEVEL`, in milliseconds.
+static MIN_NESTED_TIMEOUT: u64 = 4;
+
+struct ScheduledTimer {
+    handle: i32,
+    callback: JSVal,
+    /// The time between the runs of a timer that `setInterval` scheduled, in milliseconds, or
+    /// `None` for one that runs once.
+    interval: Option<u64>,
+    /// 1 for timers scheduled outside of any timer. Otherwise, one more than the level of the
+    /// timer that scheduled it, or of the timer's previous run if it repeats.
+    nesting_level: uint,
+    /// When the timer is due, in milliseconds on the clock of `precise_time_ns`.
+    deadline: u64,
+    /// The order in which the timers were scheduled, which tells apart those due at once.
+    sequence: uint,
+}
+
+/// A timer that is due, to be run by calling its callback.
+pub struct DueTimer {
+    handle: i32,
+    callback: JSVal,
+}
+
+/// The timers of a window. Their callbacks are kept alive by the window, which traces them with
+/// `each_callback`.
+pub struct Timers {
+    /// The timers that are scheduled, ordered by deadline, then by sequence.
+    priv scheduled: ~[ScheduledTimer],
+    /// The timer that is running, if any, which is scheduled again once it has run if it
+    /// repeats.
+    priv running: Option<ScheduledTimer>,
+    priv next_handle: i32,
+    priv next_sequence: uint,
+    /// When the timers were frozen, in milliseconds, if they are.
+    priv frozen_at: Option<u64>,
+}
+
+/// Returns the time in milliseconds on the clock that timer deadlines use.
+pub fn now() -> u64 {
+    precise_time_ns() / 1000000
+}
+
+impl Timers {
+    pub fn new() -> Timers {
+        Timers {
+            scheduled: ~[],
+            running: None,
+            next_handle: 1,
+            next_sequence: 0,
+            frozen_at: None,
+        }
+    }
+
+    /// Schedules a timer that calls `callback` after `timeout` milliseconds, and every
+    /// `timeout` milliseconds after that if it repeats. Returns the handle of the timer, for
+    /// `clearTimeout` and `clearInterval`. Also returns how long to wait before running the
+    /// timers that are due.
+    pub fn schedule(&mut self, callback: JSVal, timeout: i32, repeats: bool, now: u64)
+                    -> (i32, u64) {
+        let handle = self.next_handle;
+        self.next_handle = if handle == i32::max_value { 1 } else { handle + 1 };
+
+        let nesting_level = self.running.map_default(1, |running| running.nesting_level + 1);
+        let timeout = clamp_timeout(if timeout < 0 { 0 } else { timeout as u64 }, nesting_level);
+        let timer = ScheduledTimer {
+            handle: handle,
+            callback: callback,
+            interval: if repeats { Some(timeout) } else { None },
+            nesting_level: nesting_level,
+            deadline: now + timeout,
+            sequence: self.take_sequence(),
+        };
+        self.insert(timer);
+        (handle, timeout)
+    }
+
+    /// Cancels the timer with the given handle, if it is scheduled. A timer that cancels itself
+    /// while it runs isn't scheduled again.
+    pub fn clear(&mut self, handle: i32) {
+        self.scheduled.retain(|timer| timer.handle != handle);
+        for running in self.running.mut_iter() {
+            if running.handle == handle {
+                running.interval = None
+            }
+        }
+    }
+
+    /// Takes the first timer that is due by `now`, unless the timers are frozen. The timer is
+    /// counted as running until `finish_running` is called, so that the timers it schedules
+    /// nest inside it.
+    pub fn take_due(&mut self, now: u64) -> Option<DueTimer> {
+        if self.frozen_at.is_some() || self.scheduled.is_empty() ||
+                self.scheduled[0].deadline > now {
+            return None
+        }
+        let timer = self.scheduled.shift();
+        let due = DueTimer {
+            handle: timer.handle,
+            callback: timer.callback,
+        };
+        self.running = Some(timer);
+        Some(due)
+    }
+
+    /// Notes that the running timer has returned, and schedules it again if it repeats. Returns
+    /// how long to wait before it is due again, if it is scheduled.
+    pub fn finish_running(&mut self, now: u64) -> Option<u64> {
+        let mut timer = match self.running.take() {
+            Some(timer) => timer,
+            None => return None,
+        };
+        let interval = match timer.interval {
+            Some(interval) => interval,
+            None => return None,
+        };
+        timer.nesting_level += 1;
+        let interval = clamp_timeout(interval, timer.nesting_level);
+        timer.interval = Some(interval);
+        timer.deadline = now + interval;
+        timer.sequence = self.take_sequence();
+        self.insert(timer);
+        Some(interval)
+    }
+
+    /// Freezes the timers, so that none of them runs until they are thawed.
+    pub fn freeze(&mut self, now: u64) {
+        if self.frozen_at.is_none() {
+            self.frozen_at = Some(now)
+        }
+    }
+
+    /// Thaws the timers, moving their deadlines on by as long as they were frozen. Returns how
+    /// long to wait before each of them is due.
+    pub fn thaw(&mut self, now: u64) -> ~[u64] {
+        let frozen_at = match self.frozen_at.take() {
+            Some(frozen_at) => frozen_at,
+            None => return ~[],
+        };
+        let frozen_for = now - frozen_at;
+        do self.scheduled.mut_iter().map |timer| {
+            timer.deadline += frozen_for;
+            if timer.deadline > now { timer.deadline - now } else { 0 }
+        }.collect()
+    }
+
+    /// Calls `f` with the callback of each timer that is scheduled or running, for the garbage
+    /// collector to trace.
+    pub fn each_callback(&self, f: &fn(JSVal)) {
+        for timer in self.scheduled.iter() {
+            f(timer.callback)
+        }
+        for timer in self.running.iter() {
+            f(timer.callback)
+        }
+    }
+
+    fn take_sequence(&mut self) -> uint {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        sequence
+    }
+
+    fn insert(&mut self, timer: ScheduledTimer) {
+        let index = do self.scheduled.iter().position |scheduled| {
+            (scheduled.deadline, scheduled.sequence) > (timer.deadline, timer.sequence)
+        };
+        match index {
+            Some(index) => self.scheduled.insert(index, timer),
+            None => self.scheduled.push(timer),
+        }
+    }
+}
+
+/// Clamps the timeout of a timer that is nested too deeply, so that timers that keep
+/// scheduling themselves can't keep the script task busy.
+fn clamp_timeout(timeout: u64, nesting_level: uint) -> u64 {
+    if nesting_level > MAX_NESTING_LEVEL && timeout < MIN_NESTED_TIMEOUT {
+        MIN_NESTED_TIMEOUT
+    } else {
+        timeout
+    }
+}
+
+#[cfg(test)]
+fn due_handles(timers: &mut Timers, now: u64) -> ~[i32] {
+    let mut handles = ~[];
+    loop {
+        match timers.take_due(now) {
+            Some(timer) => handles.push(timer.handle),
+            None => break,
+        }
+        timers.finish_running(now);
+    }
+    handles
+}
+
+#[test]
+fn test_equal_deadlines_run_in_order() {
+    use js::JSVAL_NULL;
+    let mut timers = Timers::new();
+    let (first, _) = timers.schedule(JSVAL_NULL, 10, false, 0);
+    let (second, _) = timers.schedule(JSVAL_NULL, 5, false, 5);
+    let (third, _) = timers.schedule(JSVAL_NULL, 0, false, 10);
+    let (fourth, _) = timers.schedule(JSVAL_NULL, 2, false, 0);
+    assert!(due_handles(&mut timers, 9) == ~[fourth]);
+    assert!(due_handles(&mut timers, 10) == ~[first, second, third]);
+    assert!(due_handles(&mut timers, 100).is_empty());
+}
+
+#[test]
+fn test_nested_timeouts_are_clamped() {
+    use js::JSVAL_NULL;
+    let mut timers = Timers::new();
+    let (handle, delay) = timers.schedule(JSVAL_NULL, 0, true, 0);
+    assert!(delay == 0);
+    let mut now = 0;
+    let mut delays = ~[];
+    for _ in range(0, 6) {
+        let timer = timers.take_due(now).unwrap();
+        assert!(timer.handle == handle);
+        let delay = timers.finish_running(now).unwrap();
+        delays.push(delay);
+        now += delay;
+    }
+    // The interval runs at nesting levels 1 to 6, and is scheduled again at levels 2 to 7.
+    assert!(delays == ~[0, 0, 0, 0, MIN_NESTED_TIMEOUT, MIN_NESTED_TIMEOUT]);
+
+    // Timeouts scheduled from inside a deeply nested timer are clamped too, but only if
+    // they're shorter than the clamp.
+    timers.take_due(now).unwrap();
+    assert!(timers.schedule(JSVAL_NULL, 1, false, now) == (2, MIN_NESTED_TIMEOUT));
+    assert!(timers.schedule(JSVAL_NULL, 50, false, now) == (3, 50));
+    timers.finish_running(now);
+
+    // Timers scheduled outside of any timer aren't.
+    assert!(timers.schedule(JSVAL_NULL, 1, false, now) == (4, 1));
+}
+
+#[test]
+fn test_clear_running_interval() {
+    use js::JSVAL_NULL;
+    let mut timers = Timers::new();
+    let (handle, _) = timers.schedule(JSVAL_NULL, 10, true, 0);
+    assert!(timers.take_due(10).unwrap().handle == handle);
+    timers.clear(handle);
+    assert!(timers.finish_running(10).is_none());
+    assert!(due_handles(&mut timers, 1000).is_empty());
+
+    let (other, _) = timers.schedule(JSVAL_NULL, 10, true, 0);
+    let (cleared, _) = timers.schedule(JSVAL_NULL, 10, false, 0);
+    timers.clear(cleared);
+    assert!(timers.take_due(10).unwrap().handle == other);
+    assert!(timers.finish_running(10) == Some(10));
+    assert!(due_handles(&mut timers, 19).is_empty());
+    assert!(due_handles(&mut timers, 20) == ~[other]);
+}
+
+#[test]
+fn test_freeze_and_thaw() {
+    use js::JSVAL_NULL;
+    let mut timers = Timers::new();
+    let (first, _) = timers.schedule(JSVAL_NULL, 10, false, 0);
+    let (second, _) = timers.schedule(JSVAL_NULL, 30, false, 0);
+    timers.freeze(5);
+    assert!(due_handles(&mut timers, 40).is_empty());
+
+    // Freezing the timers again doesn't move the time they were frozen at.
+    timers.freeze(20);
+    assert!(timers.thaw(25) == ~[5, 25]);
+    assert!(due_handles(&mut timers, 29).is_empty());
+    assert!(due_handles(&mut timers, 30) == ~[first]);
+    assert!(due_handles(&mut timers, 50) == ~[second]);
+
+    // Thawing timers that aren't frozen does nothing.
+    assert!(timers.thaw(60).is_empty());
+}
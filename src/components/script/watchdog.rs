@@ -0,0 +1,129 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The watchdog that notices scripts that run for too long. The script task tells it when each
+//! of its tasks starts and finishes, and a task of its own checks now and then whether the task
+//! that is running has passed its deadline. If it has, the watchdog triggers the operation
+//! callback of the JavaScript engine, which the engine calls on the script task as soon as the
+//! script checks for interrupts, even in the middle of a loop that never ends, so that the
+//! embedder can ask the user whether to stop it.
+
+use extra::arc::RWArc;
+use extra::time::precise_time_ns;
+use js::jsapi::{JSRuntime, JS_TriggerOperationCallback};
+use std::rt::io::timer::Timer;
+use std::rt::rtio::RtioTimer;
+
+/// How often the watchdog checks on the task that is running, in milliseconds.
+static CHECK_INTERVAL: u64 = 100;
+
+struct WatchdogState {
+    /// When the task that is running should be done by, in nanoseconds, or `None` if no task is
+    /// running or it has already been interrupted.
+    deadline: Option<u64>,
+    /// Whether the watchdog has interrupted the task that is running, and not been told so yet.
+    interrupted: bool,
+    /// Whether the script task has exited, so that the watchdog should too.
+    exited: bool,
+}
+
+/// A handle to the watchdog of a script task.
+#[deriving(Clone)]
+pub struct Watchdog {
+    priv state: RWArc<WatchdogState>,
+    /// How long a task may run before it is interrupted, in nanoseconds.
+    priv timeout: u64,
+}
+
+impl Watchdog {
+    /// Starts a watchdog that interrupts the scripts of `runtime` when a task of the script task
+    /// runs for longer than `timeout` seconds.
+    pub fn new(runtime: *JSRuntime, timeout: uint) -> Watchdog {
+        let state = RWArc::new(WatchdogState {
+            deadline: None,
+            interrupted: false,
+            exited: false,
+        });
+        let watched = state.clone();
+        // The runtime is only touched to trigger its operation callback, which is thread safe.
+        let runtime = runtime as uint;
+        do spawn {
+            let mut timer = Timer::new().unwrap();
+            loop {
+                timer.sleep(CHECK_INTERVAL);
+                // The callback is triggered while the lock is held, so that the script task
+                // can't exit and destroy the runtime in between.
+                let exited = do watched.write |state| {
+                    let now = precise_time_ns();
+                    let overdue = state.deadline.map_default(false, |&deadline| now >= deadline);
+                    if !state.exited && overdue {
+                        state.deadline = None;
+                        state.interrupted = true;
+                        trigger_operation_callback(runtime as *JSRuntime)
+                    }
+                    state.exited
+                };
+                if exited {
+                    break
+                }
+            }
+        }
+
+        Watchdog {
+            state: state,
+            timeout: (timeout as u64) * 1000000000,
+        }
+    }
+
+    /// Notes that the script task has started a task.
+    pub fn task_started(&self) {
+        let deadline = precise_time_ns() + self.timeout;
+        do self.state.write |state| {
+            state.deadline = Some(deadline);
+            state.interrupted = false;
+        }
+    }
+
+    /// Notes that the script task has finished its task.
+    pub fn task_finished(&self) {
+        do self.state.write |state| {
+            state.deadline = None;
+            state.interrupted = false;
+        }
+    }
+
+    /// Returns whether the watchdog has interrupted the task that is running since it was last
+    /// asked, rather than the engine calling the operation callback for reasons of its own.
+    pub fn take_interrupt(&self) -> bool {
+        do self.state.write |state| {
+            let interrupted = state.interrupted;
+            state.interrupted = false;
+            interrupted
+        }
+    }
+
+    /// Lets the task that is running go on for another timeout, once the user has chosen not to
+    /// stop it.
+    pub fn extend_deadline(&self) {
+        let deadline = precise_time_ns() + self.timeout;
+        do self.state.write |state| {
+            state.deadline = Some(deadline)
+        }
+    }
+
+    /// Stops the watchdog. Once this returns, the watchdog no longer touches the runtime, which
+    /// can then be destroyed.
+    pub fn exit(&self) {
+        do self.state.write |state| {
+            state.exited = true
+        }
+    }
+}
+
+#[fixed_stack_segment]
+fn trigger_operation_callback(runtime: *JSRuntime) {
+    unsafe {
+        JS_TriggerOperationCallback(runtime)
+    }
+}
@@ -0,0 +1,99 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A counting Bloom filter of strings, which can tell for certain that a string hasn't been added
+//! to it. Strings can be removed as well as added, so the filter can follow a traversal of a tree,
+//! holding the ancestors of the current node.
+
+use std::hash::Hash;
+use std::vec;
+
+/// The number of bits of a hash that pick a counter.
+static KEY_BITS: uint = 12;
+static KEY_MASK: u64 = (1 << KEY_BITS) - 1;
+
+pub struct BloomFilter {
+    /// How many strings added to the filter hash to each counter. A counter that overflows stays
+    /// at its maximum, since the strings it counts can no longer be told apart from removals.
+    counters: ~[u8],
+}
+
+impl BloomFilter {
+    pub fn new() -> BloomFilter {
+        BloomFilter {
+            counters: vec::from_elem(1 << KEY_BITS, 0u8),
+        }
+    }
+
+    /// The two counters that a string hashes to.
+    fn keys(value: &str) -> (uint, uint) {
+        let hash = value.hash();
+        ((hash & KEY_MASK) as uint, ((hash >> 32) & KEY_MASK) as uint)
+    }
+
+    pub fn insert(&mut self, value: &str) {
+        let (first, second) = BloomFilter::keys(value);
+        for &key in [first, second].iter() {
+            if self.counters[key] != 0xff {
+                self.counters[key] += 1
+            }
+        }
+    }
+
+    /// Removes a string that was added to the filter.
+    pub fn remove(&mut self, value: &str) {
+        let (first, second) = BloomFilter::keys(value);
+        for &key in [first, second].iter() {
+            // A counter that overflowed no longer knows how many strings it counts.
+            if self.counters[key] != 0xff {
+                assert!(self.counters[key] != 0, "removing a string that wasn't in the filter");
+                self.counters[key] -= 1
+            }
+        }
+    }
+
+    /// Returns false if the string is certainly not in the filter, and true if it may be.
+    pub fn might_contain(&self, value: &str) -> bool {
+        let (first, second) = BloomFilter::keys(value);
+        self.counters[first] != 0 && self.counters[second] != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counters.iter().all(|&counter| counter == 0)
+    }
+}
+
+#[test]
+fn test_insert_and_remove() {
+    let mut filter = BloomFilter::new();
+    assert!(!filter.might_contain("div"));
+    filter.insert("div");
+    filter.insert("div");
+    filter.insert("menu");
+    assert!(filter.might_contain("div"));
+    assert!(filter.might_contain("menu"));
+
+    // Each insertion is counted, so the string stays until it has been removed as often.
+    filter.remove("div");
+    assert!(filter.might_contain("div"));
+    filter.remove("div");
+    filter.remove("menu");
+    assert!(!filter.might_contain("div"));
+    assert!(filter.is_empty());
+}
+
+#[test]
+fn test_few_false_positives() {
+    let mut filter = BloomFilter::new();
+    for i in range(0, 100) {
+        filter.insert(fmt!("in%d", i));
+    }
+    let mut false_positives = 0;
+    for i in range(0, 1000) {
+        if filter.might_contain(fmt!("out%d", i)) {
+            false_positives += 1
+        }
+    }
+    assert!(false_positives < 20);
+}
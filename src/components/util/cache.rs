@@ -124,6 +124,11 @@ impl<K: Clone + Eq, V: Clone> LRUCache<K,V> {
         }
     }
 
+    /// Returns how many entries the cache holds.
+    pub fn len(&self) -> uint {
+        self.entries.len()
+    }
+
     pub fn touch(&mut self, pos: uint) -> V {
         let last_index = self.entries.len() - 1;
         if pos != last_index {
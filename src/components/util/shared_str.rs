@@ -0,0 +1,186 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Slices of reference-counted strings, which can be kept, copied, and sent between tasks
+//! without copying the text they refer to.
+
+use extra::arc::Arc;
+use std::cmp::{Eq, Ord};
+use std::to_bytes::{Cb, IterBytes};
+
+/// A byte range of a shared string. Slices compare and hash by their text alone, so they can be
+/// used as keys of caches that are looked up with text from many different strings.
+#[deriving(Clone)]
+pub struct SharedStr {
+    priv buffer: Arc<~str>,
+    priv begin: uint,
+    priv end: uint,
+}
+
+impl SharedStr {
+    /// Returns a slice of all of `text`.
+    pub fn from_owned(text: ~str) -> SharedStr {
+        let end = text.len();
+        SharedStr {
+            buffer: Arc::new(text),
+            begin: 0,
+            end: end,
+        }
+    }
+
+    pub fn as_slice<'a>(&'a self) -> &'a str {
+        self.buffer.get().slice(self.begin, self.end)
+    }
+
+    /// Returns the length of the slice in bytes.
+    pub fn len(&self) -> uint {
+        self.end - self.begin
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.begin == self.end
+    }
+
+    /// Returns the slice from byte `begin` up to but not including byte `end` of this slice,
+    /// which shares its text. Fails unless both are character boundaries.
+    pub fn slice(&self, begin: uint, end: uint) -> SharedStr {
+        assert!(begin <= end && end <= self.len());
+        let text = self.as_slice();
+        assert!(text.is_char_boundary(begin) && text.is_char_boundary(end));
+        SharedStr {
+            buffer: self.buffer.clone(),
+            begin: self.begin + begin,
+            end: self.begin + end,
+        }
+    }
+
+    /// Returns the length of the slice in UTF-16 code units, as the DOM counts it.
+    pub fn utf16_len(&self) -> uint {
+        utf16_len(self.as_slice())
+    }
+
+    /// Returns the slice from UTF-16 code unit `begin` up to but not including code unit `end`
+    /// of this slice, or `None` if either is past the end or between the two halves of a
+    /// surrogate pair.
+    pub fn utf16_slice(&self, begin: uint, end: uint) -> Option<SharedStr> {
+        let text = self.as_slice();
+        match (utf16_to_byte_index(text, begin), utf16_to_byte_index(text, end)) {
+            (Some(begin), Some(end)) if begin <= end => Some(self.slice(begin, end)),
+            _ => None,
+        }
+    }
+
+    /// Returns a slice with the same text that doesn't keep the rest of this slice's string
+    /// alive, for slices that are kept for longer than the string they came from.
+    pub fn compact(&self) -> SharedStr {
+        if self.begin == 0 && self.end == self.buffer.get().len() {
+            self.clone()
+        } else {
+            SharedStr::from_owned(self.as_slice().to_owned())
+        }
+    }
+}
+
+impl Eq for SharedStr {
+    fn eq(&self, other: &SharedStr) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Ord for SharedStr {
+    fn lt(&self, other: &SharedStr) -> bool {
+        self.as_slice() < other.as_slice()
+    }
+}
+
+impl IterBytes for SharedStr {
+    fn iter_bytes(&self, lsb0: bool, f: Cb) -> bool {
+        self.as_slice().iter_bytes(lsb0, f)
+    }
+}
+
+impl ToStr for SharedStr {
+    fn to_str(&self) -> ~str {
+        self.as_slice().to_owned()
+    }
+}
+
+/// Returns the number of UTF-16 code units that encode `text`, which is its length as the DOM
+/// counts it.
+pub fn utf16_len(text: &str) -> uint {
+    text.iter().fold(0, |units, c| units + if c as u32 > 0xFFFF { 2 } else { 1 })
+}
+
+/// Returns the byte offset in `text` of the character at UTF-16 code unit `index`, or of the end
+/// of `text` if `index` is its length. Returns `None` if `index` is past the end of `text` or
+/// between the two halves of a surrogate pair, which has no byte offset.
+pub fn utf16_to_byte_index(text: &str, index: uint) -> Option<uint> {
+    let mut units = 0;
+    for (offset, c) in text.char_offset_iter() {
+        if units == index {
+            return Some(offset)
+        }
+        units += if c as u32 > 0xFFFF { 2 } else { 1 };
+        if units > index {
+            return None
+        }
+    }
+    if units == index {
+        Some(text.len())
+    } else {
+        None
+    }
+}
+
+/// Returns the UTF-16 code unit index of the character at byte `index` of `text`, which may be its
+/// length. Fails unless `index` is a character boundary.
+pub fn byte_to_utf16_index(text: &str, index: uint) -> uint {
+    assert!(text.is_char_boundary(index));
+    utf16_len(text.slice_to(index))
+}
+
+#[test]
+fn test_slice() {
+    let text = SharedStr::from_owned(~"naïve text");
+    assert!(text.len() == 11);
+    assert!(text.slice(0, 6).as_slice() == "naïve");
+    assert!(text.slice(7, 11).slice(1, 3).as_slice() == "ex");
+    assert!(text.slice(4, 4).is_empty());
+}
+
+#[test]
+#[should_fail]
+fn test_slice_inside_char() {
+    SharedStr::from_owned(~"naïve").slice(0, 3);
+}
+
+#[test]
+fn test_compare_by_text() {
+    let a = SharedStr::from_owned(~"the cat");
+    let b = SharedStr::from_owned(~"a cat");
+    assert!(a.slice(4, 7) == b.slice(2, 5));
+    assert!(a.slice(4, 7).compact() == a.slice(4, 7));
+    assert!(a.slice(0, 3) != a.slice(4, 7));
+    assert!(b.slice(2, 5) < a.slice(0, 3));
+    assert!(a.slice(4, 7).hash() == b.slice(2, 5).hash());
+}
+
+#[test]
+fn test_utf16_indices() {
+    // "é" is two bytes and one code unit, "𝄞" four bytes and two code units.
+    let text = "aé𝄞b";
+    assert!(utf16_len(text) == 5);
+    assert!(utf16_to_byte_index(text, 0) == Some(0));
+    assert!(utf16_to_byte_index(text, 2) == Some(3));
+    assert!(utf16_to_byte_index(text, 3) == None);
+    assert!(utf16_to_byte_index(text, 4) == Some(7));
+    assert!(utf16_to_byte_index(text, 5) == Some(8));
+    assert!(utf16_to_byte_index(text, 6) == None);
+    assert!(byte_to_utf16_index(text, 7) == 4);
+
+    let shared = SharedStr::from_owned(text.to_owned());
+    assert!(shared.utf16_len() == 5);
+    assert!(shared.utf16_slice(1, 4).unwrap().as_slice() == "é𝄞");
+    assert!(shared.utf16_slice(1, 3).is_none());
+}
@@ -6,10 +6,17 @@
 use extra::time::precise_time_ns;
 use std::cell::Cell;
 use std::comm::{Port, SharedChan};
+use std::io;
+use std::util;
+use extra::container::Deque;
+use extra::ringbuf::RingBuf;
 use extra::sort::tim_sort;
 use std::iterator::AdditiveIterator;
 use extra::treemap::TreeMap;
 
+/// How many of the most recent frames the profiler keeps the timelines of for the frame trace.
+static MAX_TRACED_FRAMES: uint = 256;
+
 // front-end representation of the profiler used to communicate with the profiler
 #[deriving(Clone)]
 pub struct ProfilerChan {
@@ -30,8 +37,54 @@ impl ProfilerChan {
 pub enum ProfilerMsg {
     // Normal message used for reporting time
     TimeMsg(ProfilerCategory, float),
+    /// Reports the latest value of a measure of memory use, such as the size of a cache or how
+    /// many entries it has evicted, under a path like `glyph-atlas/used-bytes`.
+    MemoryReportMsg(~str, uint),
+    /// Drops the memory reports whose paths start with the given prefix, such as those of a
+    /// document that has gone away.
+    ForgetMemoryReportsMsg(~str),
+    /// Sends the latest memory reports, in the order of their paths, for `about:memory`.
+    GetMemoryReportsMsg(Chan<~[(~str, uint)]>),
     // Message used to force print the profiling metrics
     PrintMsg,
+    /// Writes the metrics gathered so far to a file, in machine-readable form, and acknowledges
+    /// it on the channel once it is written.
+    WriteReportMsg(~str, Chan<()>),
+    /// Reports that a stage of the frame being produced ran from one time to another, in
+    /// nanoseconds as `precise_time_ns` gives them. Compositing finishes the frame.
+    FrameStageMsg(FrameStage, u64, u64),
+    /// Writes the timelines of the most recent frames to a file, as Chrome trace events, and
+    /// acknowledges it on the channel once it is written.
+    WriteFrameTraceMsg(~str, Chan<()>),
+}
+
+/// The stages that an update of the screen goes through, in order: the compositor passing input
+/// on to script, script running, layout, painting, and compositing.
+#[deriving(Eq, Clone)]
+pub enum FrameStage {
+    InputStage,
+    ScriptStage,
+    LayoutStage,
+    PaintStage,
+    CompositeStage,
+}
+
+impl FrameStage {
+    fn name(self) -> &'static str {
+        match self {
+            InputStage => "Input",
+            ScriptStage => "Script",
+            LayoutStage => "Layout",
+            PaintStage => "Paint",
+            CompositeStage => "Composite",
+        }
+    }
+}
+
+/// The stages that ran to produce a frame, in the order they were reported, with their start and
+/// end times in nanoseconds.
+struct FrameTimeline {
+    stages: ~[(FrameStage, u64, u64)],
 }
 
 #[deriving(Eq, Clone, TotalEq, TotalOrd)]
@@ -97,6 +150,12 @@ type ProfilerBuckets = TreeMap<ProfilerCategory, ~[float]>;
 pub struct Profiler {
     port: Port<ProfilerMsg>,
     buckets: ProfilerBuckets,
+    memory_reports: TreeMap<~str, uint>,
+    /// The timelines of the most recent frames, oldest first, and of the frame being produced.
+    frames: RingBuf<FrameTimeline>,
+    current_frame: FrameTimeline,
+    /// How many frames have been finished, including those no longer kept.
+    frame_count: uint,
     last_msg: Option<ProfilerMsg>,
 }
 
@@ -113,6 +172,12 @@ impl Profiler {
         Profiler {
             port: port,
             buckets: ProfilerCategory::empty_buckets(),
+            memory_reports: TreeMap::new(),
+            frames: RingBuf::new(),
+            current_frame: FrameTimeline {
+                stages: ~[],
+            },
+            frame_count: 0,
             last_msg: None,
         }
     }
@@ -130,39 +195,160 @@ impl Profiler {
     fn handle_msg(&mut self, msg: ProfilerMsg) {
         match msg {
             TimeMsg(category, t) => self.buckets.find_mut(&category).unwrap().push(t),
+            MemoryReportMsg(ref path, value) => {
+                self.memory_reports.insert(path.clone(), value);
+            }
+            ForgetMemoryReportsMsg(ref prefix) => self.forget_memory_reports(*prefix),
+            GetMemoryReportsMsg(ref reports_chan) => {
+                reports_chan.send(self.memory_reports.iter().map(|(path, value)| {
+                    (path.clone(), *value)
+                }).collect())
+            }
             PrintMsg => match self.last_msg {
                 // only print if more data has arrived since the last printout
-                Some(TimeMsg(*)) => self.print_buckets(),
+                Some(TimeMsg(*)) | Some(MemoryReportMsg(*)) => {
+                    self.print_buckets();
+                    self.print_memory_reports();
+                }
                 _ => ()
             },
+            WriteReportMsg(ref path, ref done_chan) => {
+                self.write_report(*path);
+                done_chan.send(());
+            }
+            FrameStageMsg(stage, start, end) => {
+                // Frames aren't printed, so they don't count as data for `PrintMsg` to print.
+                return self.record_frame_stage(stage, start, end)
+            }
+            WriteFrameTraceMsg(ref path, ref done_chan) => {
+                self.write_frame_trace(*path);
+                done_chan.send(());
+            }
         };
         self.last_msg = Some(msg);
     }
 
+    fn forget_memory_reports(&mut self, prefix: &str) {
+        let paths: ~[~str] = self.memory_reports.iter().filter_map(|(path, _)| {
+            if path.starts_with(prefix) { Some(path.clone()) } else { None }
+        }).collect();
+        for path in paths.iter() {
+            self.memory_reports.remove(path);
+        }
+    }
+
     fn print_buckets(&mut self) {
         println(fmt!("%31s %15s %15s %15s %15s %15s",
                          "_category_", "_mean (ms)_", "_median (ms)_",
                          "_min (ms)_", "_max (ms)_", "_bucket size_"));
         for (category, data) in self.buckets.iter() {
-            // FIXME(XXX): TreeMap currently lacks mut_iter()
-            let mut data = data.clone();
-            tim_sort(data);
-            let data_len = data.len();
-            if data_len > 0 {
-                let (mean, median, &min, &max) =
-                    (data.iter().map(|&x|x).sum() / (data_len as float),
-                     data[data_len / 2],
-                     data.iter().min().unwrap(),
-                     data.iter().max().unwrap());
+            for &(mean, median, min, max) in bucket_stats(*data).iter() {
                 println(fmt!("%-30s: %15.4f %15.4f %15.4f %15.4f %15u",
-                             category.format(), mean, median, min, max, data_len));
+                             category.format(), mean, median, min, max, data.len()));
+            }
+        }
+        println("");
+    }
+
+    /// Writes the metrics to a file as tab-separated values, one per line: for each category
+    /// with measurements, `time`, the category, the number of measurements and their mean,
+    /// median, minimum and maximum in milliseconds; then for each memory report, `memory`, its
+    /// path and its value.
+    fn write_report(&self, path: &str) {
+        let writer = match io::file_writer(&Path(path), [io::Create, io::Truncate]) {
+            Ok(writer) => writer,
+            Err(error) => {
+                println(fmt!("error: can't write the profiler report to `%s`: %s", path, error));
+                return
+            }
+        };
+        for (category, data) in self.buckets.iter() {
+            for &(mean, median, min, max) in bucket_stats(*data).iter() {
+                writer.write_line(fmt!("time\t%?\t%u\t%.4f\t%.4f\t%.4f\t%.4f",
+                                       *category, data.len(), mean, median, min, max));
+            }
+        }
+        for (path, value) in self.memory_reports.iter() {
+            writer.write_line(fmt!("memory\t%s\t%u", *path, *value));
+        }
+    }
+
+    /// Adds a stage to the frame being produced, and finishes the frame if it is compositing,
+    /// dropping the oldest frame if there are too many.
+    fn record_frame_stage(&mut self, stage: FrameStage, start: u64, end: u64) {
+        self.current_frame.stages.push((stage, start, end));
+        if stage != CompositeStage {
+            return
+        }
+        let frame = util::replace(&mut self.current_frame, FrameTimeline {
+            stages: ~[],
+        });
+        if self.frames.len() == MAX_TRACED_FRAMES {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+        self.frame_count += 1;
+    }
+
+    /// Returns the timelines of the frames that are kept, in the JSON trace event format of
+    /// Chrome's trace viewer: a complete event for each stage, with times in microseconds, on a
+    /// thread of its own for each kind of stage, and the number of its frame as an argument.
+    fn frame_trace(&self) -> ~str {
+        let first_frame = self.frame_count - self.frames.len();
+        let mut events = ~[];
+        for (i, frame) in self.frames.iter().enumerate() {
+            for &(stage, start, end) in frame.stages.iter() {
+                events.push(fmt!("{\"name\":\"%s\",\"cat\":\"frame\",\"ph\":\"X\",\
+                                  \"ts\":%.3f,\"dur\":%.3f,\"pid\":1,\"tid\":%u,\
+                                  \"args\":{\"frame\":%u}}",
+                                 stage.name(),
+                                 (start as float) / 1000f,
+                                 ((end - start) as float) / 1000f,
+                                 stage as uint + 1,
+                                 first_frame + i));
+            }
+        }
+        fmt!("{\"traceEvents\":[%s]}", events.connect(","))
+    }
+
+    fn write_frame_trace(&self, path: &str) {
+        match io::file_writer(&Path(path), [io::Create, io::Truncate]) {
+            Ok(writer) => writer.write_str(self.frame_trace()),
+            Err(error) => {
+                println(fmt!("error: can't write the frame trace to `%s`: %s", path, error))
             }
         }
+    }
+
+    fn print_memory_reports(&self) {
+        if self.memory_reports.is_empty() {
+            return
+        }
+        println(fmt!("%-30s %15s", "_memory report_", "_value_"));
+        for (path, value) in self.memory_reports.iter() {
+            println(fmt!("%-30s: %15u", *path, *value));
+        }
         println("");
     }
 }
 
 
+/// Returns the mean, median, minimum and maximum of a bucket of measurements, or `None` if it is
+/// empty.
+fn bucket_stats(data: &[float]) -> Option<(float, float, float, float)> {
+    if data.is_empty() {
+        return None
+    }
+    let mut data = data.to_owned();
+    tim_sort(data);
+    let data_len = data.len();
+    Some((data.iter().map(|&x|x).sum() / (data_len as float),
+          data[data_len / 2],
+          data[0],
+          data[data_len - 1]))
+}
+
+
 pub fn profile<T>(category: ProfilerCategory, 
                   profiler_chan: ProfilerChan,
                   callback: &fn() -> T)
@@ -175,6 +361,17 @@ pub fn profile<T>(category: ProfilerCategory,
     return val;
 }
 
+/// Reports the time that a stage of the frame being produced takes to the profiler.
+pub fn profile_frame_stage<T>(stage: FrameStage,
+                              profiler_chan: ProfilerChan,
+                              callback: &fn() -> T)
+                              -> T {
+    let start_time = precise_time_ns();
+    let val = callback();
+    profiler_chan.send(FrameStageMsg(stage, start_time, precise_time_ns()));
+    val
+}
+
 pub fn time<T>(msg: &str, callback: &fn() -> T) -> T{
     let start_time = precise_time_ns();
     let val = callback();
@@ -188,10 +385,68 @@ pub fn time<T>(msg: &str, callback: &fn() -> T) -> T{
 
 #[cfg(test)]
 mod test {
+    use std::comm;
+
     // ensure that the order of the buckets matches the order of the enum categories
     #[test]
     fn check_order() {
         let buckets = ProfilerCategory::empty_buckets();
         assert!(buckets.len() == NumBuckets as uint);
     }
+
+    #[test]
+    fn bucket_stats_of_measurements() {
+        assert!(bucket_stats([]).is_none());
+        let stats = bucket_stats([4.0, 1.0, 3.0, 2.0]);
+        assert!(stats == Some((2.5, 3.0, 1.0, 4.0)));
+    }
+
+    #[test]
+    fn forget_memory_reports_by_prefix() {
+        let (port, _chan) = comm::stream();
+        let mut profiler = Profiler::new(port);
+        profiler.handle_msg(MemoryReportMsg(~"documents/1/dom/nodes", 10));
+        profiler.handle_msg(MemoryReportMsg(~"documents/12/dom/nodes", 20));
+        profiler.handle_msg(MemoryReportMsg(~"glyph-atlas/used-bytes", 30));
+        profiler.handle_msg(ForgetMemoryReportsMsg(~"documents/1/"));
+
+        let (reports_port, reports_chan) = comm::stream();
+        profiler.handle_msg(GetMemoryReportsMsg(reports_chan));
+        assert!(reports_port.recv() == ~[(~"documents/12/dom/nodes", 20),
+                                         (~"glyph-atlas/used-bytes", 30)]);
+    }
+
+    #[test]
+    fn frames_end_at_compositing_and_only_the_most_recent_are_kept() {
+        let (port, _chan) = comm::stream();
+        let mut profiler = Profiler::new(port);
+        profiler.handle_msg(FrameStageMsg(LayoutStage, 1000, 3000));
+        profiler.handle_msg(FrameStageMsg(CompositeStage, 4000, 4500));
+        profiler.handle_msg(FrameStageMsg(PaintStage, 5000, 6000));
+        assert!(profiler.frames.len() == 1);
+        assert!(profiler.current_frame.stages == ~[(PaintStage, 5000, 6000)]);
+
+        for i in range(0, MAX_TRACED_FRAMES) {
+            profiler.handle_msg(FrameStageMsg(CompositeStage, i as u64, i as u64));
+        }
+        assert!(profiler.frames.len() == MAX_TRACED_FRAMES);
+        assert!(profiler.frame_count == MAX_TRACED_FRAMES + 1);
+        assert!(profiler.frames.front().unwrap().stages == ~[(PaintStage, 5000, 6000),
+                                                             (CompositeStage, 0, 0)]);
+    }
+
+    #[test]
+    fn frame_trace_as_trace_events() {
+        let (port, _chan) = comm::stream();
+        let mut profiler = Profiler::new(port);
+        assert!(profiler.frame_trace() == ~"{\"traceEvents\":[]}");
+
+        profiler.handle_msg(FrameStageMsg(ScriptStage, 2000, 3500));
+        profiler.handle_msg(FrameStageMsg(CompositeStage, 4000, 4250));
+        assert!(profiler.frame_trace() == ~"{\"traceEvents\":[\
+            {\"name\":\"Script\",\"cat\":\"frame\",\"ph\":\"X\",\"ts\":2.000,\"dur\":1.500,\
+            \"pid\":1,\"tid\":2,\"args\":{\"frame\":0}},\
+            {\"name\":\"Composite\",\"cat\":\"frame\",\"ph\":\"X\",\"ts\":4.000,\"dur\":0.250,\
+            \"pid\":1,\"tid\":5,\"args\":{\"frame\":0}}]}");
+    }
 }
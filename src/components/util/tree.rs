@@ -156,6 +156,41 @@ pub trait TreeNodeRef<Node>: Clone {
         }
     }
 
+    /// Adds a new child to this node's list of children, just before `before`, or at the end if
+    /// `before` is `None`.
+    ///
+    /// Fails unless `new_child` is disconnected from the tree and `before` is a child of this
+    /// node.
+    fn insert_child_before(&self, new_child: Self, before: Option<Self>) {
+        let before = match before {
+            None => return self.add_child(new_child),
+            Some(before) => before,
+        };
+        do self.with_mut_base |this_node| {
+            do new_child.with_mut_base |new_child_node| {
+                assert!((get!(new_child_node, parent_node)).is_none());
+                assert!((get!(new_child_node, prev_sibling)).is_none());
+                assert!((get!(new_child_node, next_sibling)).is_none());
+
+                do before.with_mut_base |before_node| {
+                    match get!(before_node, prev_sibling) {
+                        None => set!(this_node, set_first_child, Some(new_child.clone())),
+                        Some(prev_sibling) => {
+                            do prev_sibling.with_mut_base |prev_sibling_node| {
+                                set!(prev_sibling_node, set_next_sibling, Some(new_child.clone()));
+                                set!(new_child_node, set_prev_sibling, Some(prev_sibling.clone()));
+                            }
+                        }
+                    }
+                    set!(before_node, set_prev_sibling, Some(new_child.clone()));
+                }
+
+                set!(new_child_node, set_next_sibling, Some(before.clone()));
+                set!(new_child_node, set_parent_node, Some((*self).clone()));
+            }
+        }
+    }
+
     /// Removes the given child from this node's list of children.
     ///
     /// Fails unless `child` is a child of this node. (FIXME: This is not yet checked.)
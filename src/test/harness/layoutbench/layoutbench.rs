@@ -0,0 +1,95 @@
+// Copyright 2013 The Servo Project Developers. See the COPYRIGHT
+// file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Loads each page of a corpus in servo a number of times, rendering it offscreen to a file and
+//! exiting, and prints the per-phase times that the profiler reports for each load as
+//! tab-separated values: the page, the run, then the line of the profiler report.
+
+extern mod std;
+extern mod extra;
+
+use extra::getopts::{getopts, optopt, opt_maybe_str, fail_str};
+use std::io;
+use std::os;
+use std::run;
+use std::uint;
+
+fn main() {
+    let args = os::args();
+    let opts = ~[optopt("runs")];
+    let matches = match getopts(args.tail(), opts) {
+        Ok(m) => m,
+        Err(f) => fail!(fail_str(f)),
+    };
+    if matches.free.is_empty() {
+        println("usage: layoutbench [--runs=N] LIST...");
+        os::set_exit_status(1);
+        return;
+    }
+    let runs = match opt_maybe_str(&matches, "runs") {
+        Some(runs) => uint::from_str(runs).expect("--runs must be a number"),
+        None => 5,
+    };
+
+    for page in parse_lists(matches.free).iter() {
+        for run in range(0, runs) {
+            match load_page(*page) {
+                Some(report) => {
+                    for line in report.line_iter().filter(|line| !line.is_empty()) {
+                        println(fmt!("%s\t%u\t%s", *page, run, line));
+                    }
+                }
+                None => {
+                    println(fmt!("error: servo didn't report on `%s`", *page));
+                    os::set_exit_status(1);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the paths of the pages listed in the given files, one per line relative to the file,
+/// skipping blank lines and those starting with `#`.
+fn parse_lists(filenames: &[~str]) -> ~[~str] {
+    let mut pages = ~[];
+    for file in filenames.iter() {
+        let file_path = Path(*file);
+        let contents = match io::read_whole_file_str(&file_path) {
+            Ok(x) => x,
+            Err(s) => fail!(s)
+        };
+        let src_dir = file_path.dirname();
+        for line in contents.line_iter() {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with("#") {
+                pages.push(src_dir + "/" + line);
+            }
+        }
+    }
+    pages
+}
+
+/// Loads a page in servo and returns the profiler report that it writes on exit.
+fn load_page(page: &str) -> Option<~str> {
+    let png_filename = ~"/tmp/layoutbench.png";
+    let report_filename = ~"/tmp/layoutbench-report.tsv";
+    let report_path = Path(report_filename);
+    os::remove_file(&report_path);
+
+    let options = run::ProcessOptions::new();
+    let args = ~[~"-x", ~"-o", png_filename.clone(), ~"-m", report_filename.clone(),
+                 page.to_owned()];
+    let mut process = run::Process::new("./servo", args, options);
+    let _retval = process.finish();
+    os::remove_file(&Path(png_filename));
+
+    let report = io::read_whole_file_str(&report_path).ok();
+    os::remove_file(&report_path);
+    report
+}
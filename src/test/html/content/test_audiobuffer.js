@@ -0,0 +1,26 @@
+var context = new AudioContext();
+var buffer = context.createBuffer(2, 4, 44100);
+is(buffer.length, 4);
+is(buffer.numberOfChannels, 2);
+
+// Samples that don't fit are dropped, and those that aren't given stay as they were.
+buffer.copyToChannel([0.5, -0.25, 1, 0.75, 2], 1);
+var samples = [9, 9, 9, 9, 9];
+buffer.copyFromChannel(samples, 1);
+is(samples.join(","), "0.5,-0.25,1,0.75,9");
+
+buffer.copyToChannel([0.5], 0);
+var first = [9, 9];
+buffer.copyFromChannel(first, 0);
+is(first.join(","), "0.5,0");
+
+var threw = false;
+try {
+  buffer.copyToChannel([0], 2);
+} catch (e) {
+  threw = true;
+}
+is(threw, true);
+
+context.close();
+finish();
@@ -0,0 +1,35 @@
+is(document.styleSheets.length, 1);
+var sheet = document.styleSheets.item(0);
+is(sheet.href, null);
+
+// Only style rules are exposed.
+var rules = sheet.cssRules;
+is(rules.length, 2);
+is(rules.item(0).selectorText, "p");
+is(rules.item(0).cssText, "p { color: red; }");
+is(rules.item(1).style.getPropertyValue("margin"), "0");
+is(rules.item(1).style.getPropertyPriority("margin"), "important");
+
+// Declarations can be changed, added and removed.
+var style = rules.item(0).style;
+style.setProperty("color", "blue", "");
+style.setProperty("font-weight", "bold", "important");
+is(style.length, 2);
+is(style.item(1), "font-weight");
+is(style.cssText, "color: blue; font-weight: bold !important;");
+is(style.removeProperty("color"), "blue");
+is(rules.item(0).cssText, "p { font-weight: bold !important; }");
+style.cssText = "color: green";
+is(style.getPropertyValue("color"), "green");
+is(style.getPropertyValue("font-weight"), "");
+
+// Rules can be inserted and deleted.
+is(sheet.insertRule("span { color: red; }", 1), 1);
+is(sheet.cssRules.length, 3);
+is(sheet.cssRules.item(1).selectorText, "span");
+is(sheet.cssRules.item(2).selectorText, "div");
+sheet.deleteRule(0);
+is(sheet.cssRules.length, 2);
+is(sheet.cssRules.item(0).selectorText, "span");
+
+finish();
@@ -0,0 +1,27 @@
+// Markup that a parser script writes is parsed after the script, once it has returned.
+document.write("<meta name=\"written\">");
+document.writeln("<meta name=\"written\">");
+is(document.getElementsByName("written").length, 0);
+
+setTimeout(function() {
+  is(document.getElementsByName("written").length, 2);
+
+  // Once the document has loaded, writing opens it again, replacing what it holds with all that
+  // has been written since.
+  document.write("<p>one</p>");
+  document.write("<p>two</p>");
+  is(document.getElementsByTagName("p").length, 2);
+  is(document.getElementsByName("written").length, 0);
+
+  // Writing after the document is closed opens it again.
+  document.close();
+  document.write("<p>three</p>");
+  is(document.getElementsByTagName("p").length, 1);
+
+  // Opening the document empties it.
+  document.open();
+  is(document.getElementsByTagName("p").length, 0);
+  document.close();
+
+  finish();
+}, 0);
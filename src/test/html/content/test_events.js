@@ -0,0 +1,77 @@
+var outer = document.createElement("div");
+var inner = document.createElement("span");
+outer.appendChild(inner);
+document.documentElement.appendChild(outer);
+
+// Capturing listeners of the ancestors run first, then those of the target, then the other
+// listeners of the ancestors as the event bubbles.
+var order = [];
+function record(name) {
+  return function(event) {
+    order.push(name + ":" + event.eventPhase);
+    is(event.target, inner);
+  };
+}
+outer.addEventListener("ping", record("outer-capture"), true);
+outer.addEventListener("ping", record("outer-bubble"), false);
+inner.addEventListener("ping", record("inner-bubble"), false);
+inner.addEventListener("ping", record("inner-capture"), true);
+is(inner.dispatchEvent(new Event("ping", {bubbles: true})), true);
+is(order.join(","), "outer-capture:1,inner-bubble:2,inner-capture:2,outer-bubble:3");
+
+// Events that don't bubble stop at the target.
+order = [];
+inner.dispatchEvent(new Event("ping"));
+is(order.join(","), "outer-capture:1,inner-bubble:2,inner-capture:2");
+
+// stopImmediatePropagation stops the other listeners of the same node, and the propagation.
+var calls = [];
+inner.addEventListener("stop", function(event) {
+  calls.push("first");
+  event.stopImmediatePropagation();
+}, false);
+inner.addEventListener("stop", function() { calls.push("second"); }, false);
+outer.addEventListener("stop", function() { calls.push("outer"); }, false);
+inner.dispatchEvent(new Event("stop", {bubbles: true}));
+is(calls.join(","), "first");
+
+// stopPropagation lets the other listeners of the node run.
+calls = [];
+inner.addEventListener("halt", function(event) {
+  calls.push("first");
+  event.stopPropagation();
+}, false);
+inner.addEventListener("halt", function() { calls.push("second"); }, false);
+outer.addEventListener("halt", function() { calls.push("outer"); }, false);
+inner.dispatchEvent(new Event("halt", {bubbles: true}));
+is(calls.join(","), "first,second");
+
+// preventDefault cancels only cancelable events, and dispatchEvent tells whether it did.
+inner.addEventListener("cancel", function(event) { event.preventDefault(); }, false);
+var cancelable = new Event("cancel", {cancelable: true});
+is(inner.dispatchEvent(cancelable), false);
+is(cancelable.defaultPrevented, true);
+var uncancelable = new Event("cancel");
+is(inner.dispatchEvent(uncancelable), true);
+is(uncancelable.defaultPrevented, false);
+
+// Events that scripts dispatch aren't trusted, and the phase is reset once they're dispatched.
+is(cancelable.isTrusted, false);
+is(cancelable.eventPhase, Event.NONE);
+
+// checkValidity fires invalid at a control that fails.
+var input = document.createElement("input");
+input.setAttribute("required", "");
+outer.appendChild(input);
+var invalid = 0;
+input.addEventListener("invalid", function(event) {
+  invalid++;
+  is(event.isTrusted, true);
+}, false);
+is(input.checkValidity(), false);
+is(invalid, 1);
+input.value = "filled";
+is(input.checkValidity(), true);
+is(invalid, 1);
+
+finish();
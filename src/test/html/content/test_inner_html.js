@@ -0,0 +1,30 @@
+var div = document.createElement("div");
+var old = document.createElement("span");
+div.appendChild(old);
+
+div.innerHTML = "<p class=\"x\">one</p>two<!--three-->";
+// The old children are swapped out at once for the parsed nodes.
+is(old.parentNode, null);
+is(div.firstChild.tagName, "P");
+is(div.firstChild.getAttribute("class"), "x");
+is(div.firstChild.nextSibling.nodeValue, "two");
+is(div.lastChild.nodeType, 8);
+is(div.lastChild.nodeValue, "three");
+is(div.innerHTML, "<p class=\"x\">one</p>two<!--three-->");
+
+// Text is escaped when it is serialized, and unescaped when it is parsed.
+div.innerHTML = "a &lt;b&gt; &amp; c";
+is(div.firstChild.nodeValue, "a <b> & c");
+is(div.innerHTML, "a &lt;b&gt; &amp; c");
+
+// Markup is parsed in the element, so the rows of a table body stay rows.
+var tbody = document.createElement("tbody");
+tbody.innerHTML = "<tr><td>cell</td></tr>";
+is(tbody.firstChild.tagName, "TR");
+is(tbody.firstChild.firstChild.tagName, "TD");
+
+// Assigning an empty string removes the children.
+div.innerHTML = "";
+is(div.firstChild, null);
+
+finish();
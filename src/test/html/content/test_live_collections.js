@@ -0,0 +1,34 @@
+var divs = document.getElementsByTagName("div");
+var tagged = document.getElementsByClassName("tagged");
+is(divs.length, 0);
+is(tagged.length, 0);
+
+// Collections follow the nodes that are added to the document, and removed from it.
+var first = document.createElement("div");
+var second = document.createElement("div");
+document.documentElement.appendChild(first);
+document.documentElement.appendChild(second);
+is(divs.length, 2);
+is(divs.item(0), first);
+is(divs.item(1), second);
+
+document.documentElement.removeChild(first);
+is(divs.length, 1);
+is(divs.item(0), second);
+
+// And the attributes that are changed.
+second.setAttribute("class", "tagged other");
+is(tagged.length, 1);
+is(tagged.item(0), second);
+second.setAttribute("class", "other");
+is(tagged.length, 0);
+
+// The collections of an element only hold its descendants.
+var span = document.createElement("span");
+second.appendChild(span);
+var spans = second.getElementsByTagName("span");
+is(spans.length, 1);
+second.removeChild(span);
+is(spans.length, 0);
+
+finish();
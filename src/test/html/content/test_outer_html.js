@@ -0,0 +1,28 @@
+var div = document.createElement("div");
+div.innerHTML = "<span id=\"target\">x</span>";
+var span = div.firstChild;
+
+is(span.outerHTML, "<span id=\"target\">x</span>");
+is(div.outerHTML, "<div><span id=\"target\">x</span></div>");
+
+// Attribute values are escaped, and void elements have no end tag.
+var img = document.createElement("img");
+img.setAttribute("alt", "say \"hi\" & <bye>");
+is(img.outerHTML, "<img alt=\"say &quot;hi&quot; &amp; <bye>\">");
+
+span.insertAdjacentHTML("beforebegin", "<i>1</i>");
+span.insertAdjacentHTML("afterbegin", "<b>2</b>");
+span.insertAdjacentHTML("beforeend", "<b>3</b>");
+span.insertAdjacentHTML("afterend", "<i>4</i>");
+is(div.innerHTML, "<i>1</i><span id=\"target\"><b>2</b>x<b>3</b></span><i>4</i>");
+
+// Setting outerHTML replaces the element with what the markup is parsed into.
+span.outerHTML = "<em>5</em><em>6</em>";
+is(span.parentNode, null);
+is(div.innerHTML, "<i>1</i><em>5</em><em>6</em><i>4</i>");
+
+// An element without a parent can't be replaced.
+img.outerHTML = "<br>";
+is(img.tagName, "IMG");
+
+finish();
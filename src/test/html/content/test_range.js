@@ -0,0 +1,41 @@
+var div = document.createElement("div");
+var text = document.createTextNode("hello world");
+var span = document.createElement("span");
+div.appendChild(text);
+div.appendChild(span);
+
+var range = document.createRange();
+range.setStart(text, 6);
+range.setEnd(div, 2);
+is(range.collapsed, false);
+is(range.commonAncestorContainer, div);
+
+// Inserting data before the start moves it along.
+text.insertData(0, ">> ");
+is(range.startContainer, text);
+is(range.startOffset, 9);
+
+// Splitting the text before the start moves it into the new node.
+var rest = text.splitText(6);
+is(range.startContainer, rest);
+is(range.startOffset, 3);
+is(range.endContainer, div);
+is(range.endOffset, 3);
+
+// Removing a node moves the points in it out to its parent.
+div.removeChild(rest);
+is(range.startContainer, div);
+is(range.startOffset, 1);
+is(range.endOffset, 2);
+
+var other = range.cloneRange();
+other.collapse(true);
+is(other.collapsed, true);
+is(range.compareBoundaryPoints(Range.START_TO_START, other), 0);
+is(range.compareBoundaryPoints(Range.END_TO_END, other), 1);
+
+range.selectNodeContents(span);
+is(range.startContainer, span);
+is(range.endOffset, 0);
+
+finish();
@@ -0,0 +1,22 @@
+var order = [];
+
+// Timers that are due at once run in the order they were scheduled in.
+setTimeout(function() { order.push("a"); }, 0);
+setTimeout(function() { order.push("b"); }, 0);
+var cleared = setTimeout(function() { order.push("cleared"); }, 0);
+clearTimeout(cleared);
+
+var runs = 0;
+var interval = setInterval(function() {
+  runs++;
+  if (runs == 3) {
+    // Clearing an interval while it runs stops it from being scheduled again.
+    clearInterval(interval);
+  }
+}, 1);
+
+setTimeout(function() {
+  is(order.join(","), "a,b");
+  is(runs, 3);
+  finish();
+}, 100);